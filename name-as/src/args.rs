@@ -1,44 +1,318 @@
+use crate::delay::DelaySlots;
+use crate::error::ErrorFormat;
+use crate::lint::WarningLevels;
+use crate::log::Verbosity;
+use crate::operands::MemoryOperands;
+use crate::output::{MemoryShape, OutputFormat};
+use crate::pseudo::PseudoPolicy;
+use crate::summary::{Regions, SummaryFormat};
+use name_core::endian::Endian;
+use name_core::lineinfo::LineInfoFormat;
+use name_core::symbols::SymbolFormat;
 use std::env;
 
 #[derive(Debug)]
 pub struct Args {
+    /// The config file. Left out on the command line, name.toml in the
+    /// current directory
     pub config_fn: String,
+    /// The profile in the config file to build with
+    pub profile: Option<String>,
     pub input_as: String,
     pub output_as: String,
     pub line_info: bool,
+    /// How OUTPUT.li is written
+    pub lineinfo_format: LineInfoFormat,
+    /// Write a listing of the source with addresses and encodings to
+    /// OUTPUT.lst
+    pub listing: bool,
+    /// Byte order of the output binary. Falls back to the config file,
+    /// then to little endian
+    pub endian: Option<Endian>,
+    /// Write the symbol table next to the output in this format
+    pub symbols: Option<SymbolFormat>,
+    /// Symbol files from other builds whose labels the source may use, as
+    /// written with `--symbols`
+    pub import_symbols: Vec<String>,
+    pub format: OutputFormat,
+    /// The word width and depth of the memory readmemh, coe and mif
+    /// output initialize
+    pub memory: MemoryShape,
+    /// How much to report while assembling. Falls back to the config file,
+    /// then to quiet
+    pub verbosity: Option<Verbosity>,
+    /// What to put in branch delay slots. Falls back to the config file,
+    /// then to nothing
+    pub delay_slots: Option<DelaySlots>,
+    /// What to do with pseudo-instructions. Falls back to the config file,
+    /// then to expanding them quietly
+    pub pseudo_instructions: Option<PseudoPolicy>,
+    /// Whether to warn about loads and stores written with the base
+    /// register first. Falls back to the config file, then to warning
+    pub memory_operands: Option<MemoryOperands>,
+    /// Print how much of memory the program takes up once assembled
+    pub summary: Option<SummaryFormat>,
+    /// Where `.text` starts unless the source moves it with `.org`. Falls
+    /// back to the config file, then to 0x00400000
+    pub text_base: Option<u32>,
+    /// Where `.data` starts unless the source moves it with `.org`. Falls
+    /// back to the config file, then to 0x10010000
+    pub data_base: Option<u32>,
+    /// The region sizes the summary measures against, from the config file
+    pub regions: Regions,
+    /// Leave the timestamp out of the build information, so the same
+    /// source always assembles to the same bytes
+    pub reproducible: bool,
+    /// Assemble again every time INPUT is saved
+    pub watch: bool,
+    /// Count the errors and warnings of every run in this file, see
+    /// [crate::usage]. Nothing is recorded without it
+    pub usage_log: Option<String>,
+    /// The assignment runs are counted under in the usage log. Left out,
+    /// the name of INPUT without its extension
+    pub assignment: Option<String>,
+    /// How errors and warnings are written to stderr
+    pub error_format: ErrorFormat,
+    /// Which warnings to report and which to make errors, from the `-W`
+    /// flags. Builds on the config file, see [WarningLevels::or]
+    pub warnings: WarningLevels,
+    /// Take out instructions with nothing to do and report each, see
+    /// [crate::optimize]
+    pub optimize: bool,
 }
 
+/// The config file used when none is given
+pub const DEFAULT_CONFIG: &str = "name.toml";
+
 fn help() {
-    println!("Usage: name [OPTIONS] CONFIG INPUT OUTPUT\n");
+    println!("Usage: name [OPTIONS] [CONFIG] INPUT OUTPUT\n");
     println!("Required:");
-    println!("  CONFIG       A toml configuration file, examples");
-    println!("               are provided in configs/");
+    println!("  CONFIG       A toml configuration file, examples are provided");
+    println!("               in configs/ (default: {})", DEFAULT_CONFIG);
     println!("  INPUT_AS     An input assembly file");
     println!("  OUTPUT_AS    An output assembled file");
     println!("Optional:");
+    println!("  --profile NAME");
+    println!("               Builds with the settings of [profiles.NAME] in the");
+    println!("               config file in place of the top-level ones");
     println!("  --lineinfo");
     println!("   -l          Enables line information export");
+    println!("  --lineinfo-format {{json,binary}}");
+    println!("               Format of OUTPUT.li, implies --lineinfo (default: json)");
+    println!("  --listing    Writes the source with the address and encoding of");
+    println!("               every instruction to OUTPUT.lst");
+    println!("  --reproducible");
+    println!("               Leaves the build time out of the provenance record in");
+    println!("               ELF and object outputs, so they are byte for byte the same");
+    println!("               every time");
+    println!("  --endian {{big,little}}");
+    println!("               Byte order of the output binary (default: little)");
+    println!("  --symbols {{text,json}}");
+    println!("               Writes label addresses to OUTPUT.sym or OUTPUT.sym.json");
+    println!("  --import-symbols FILE");
+    println!("               Lets the source refer to the labels in FILE, a symbol");
+    println!("               file written by --symbols for another build, at their");
+    println!("               addresses there. For overlays and patches assembled");
+    println!("               against a base image without linking. Can be repeated");
+    println!("  --format {{binary,elf,object,ihex,srec,readmemh,coe,mif,annotated}}");
+    println!("               Writes raw instructions, an ELF executable with DWARF");
+    println!("               line information, a relocatable object to link with");
+    println!("               others using `name link`, Intel HEX, Motorola S-records,");
+    println!("               words for Verilog's $readmemh, a Xilinx COE or Altera");
+    println!("               MIF memory initialization file, or every word annotated");
+    println!("               with its address and instruction (default: binary, which");
+    println!("               can be shortened to bin)");
+    println!("  --word-width {{8,16,32}}");
+    println!("               Bits in each word of the memory readmemh, coe and mif");
+    println!("               output initialize (default: 32)");
+    println!("  --depth WORDS");
+    println!("               Words in that memory; coe and mif output is padded with");
+    println!("               zeros to fill it (default: as many as the program takes)");
+    println!("  --delay-slots {{off,nop,reorder}}");
+    println!("               Fills the delay slot after every branch and jump with");
+    println!("               a nop, or with the instruction before it where that is");
+    println!("               safe, for running with delay slots emulated (default: off)");
+    println!("  --pseudo-instructions {{allow,warn,forbid,annotate}}");
+    println!("               Expands pseudo-instructions quietly, with a warning for");
+    println!("               each, not at all, or marked in the listing (default: allow)");
+    println!("  --memory-operands {{strict,lenient}}");
+    println!("               Accepts loads and stores written with the base register");
+    println!("               first, as in `lw $t0, ($t1), 4`, with a warning for each");
+    println!("               or quietly (default: strict)");
+    println!("  --text-base ADDRESS, --data-base ADDRESS");
+    println!("               Where .text and .data start, as 0x addresses, unless");
+    println!("               the source moves them with .org (default: 0x00400000");
+    println!("               and 0x10010000)");
+    println!("  --watch      Keeps running and assembles again every time INPUT");
+    println!("               is saved, printing any errors as it goes");
+    println!("  --usage-log FILE");
+    println!("               Counts the errors and warnings of each run by code in");
+    println!("               FILE, for instructors to see which mistakes come up");
+    println!("               most. Only counts are kept, and only in FILE");
+    println!("  --assignment NAME");
+    println!("               The assignment runs are counted under in the usage log");
+    println!("               (default: INPUT without its extension)");
+    println!("  --summary {{text,json}}");
+    println!("               Prints the size of each section, how much of its region");
+    println!("               of memory it fills and how many instructions there are");
+    println!("  --error-format {{human,json}}");
+    println!("               Writes errors and warnings to stderr with the source");
+    println!("               they point at, or as one JSON document listing each");
+    println!("               with its file, line, column, severity, message and");
+    println!("               code, for editors and scripts (default: human)");
+    println!("  -Wall, -Werror, -WNAME, -Wno-NAME, -Werror=NAME");
+    println!("               Turns on every warning, makes every warning an error, or");
+    println!("               turns one warning on, off or into an error. NAME is");
+    println!("               unused-label, unreachable-code, immediate-truncation,");
+    println!("               branch-to-data or missing-exit (default: only");
+    println!("               unreachable-code and branch-to-data)");
+    println!("  -O, --optimize");
+    println!("               Leaves out instructions with nothing to do, such as a");
+    println!("               `lui $at, 0` from `la` or a jump to the next instruction,");
+    println!("               and lists each one left out. $at may then hold something");
+    println!("               else afterwards. Not for --format object");
+    println!("  --verbose");
+    println!("   -v, -vv     Prints the encoding of every instruction to stderr,");
+    println!("               or with -vv parser output and field details too");
+}
+
+/// Parses a `0x` hexadecimal address
+fn parse_address(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.strip_prefix("0x")?, 16).ok()
 }
 
 pub fn parse_args() -> Result<Args, &'static str> {
     let mut args: Args = Args {
         config_fn: String::new(),
+        profile: None,
         input_as: String::new(),
         output_as: String::new(),
         line_info: false,
+        lineinfo_format: LineInfoFormat::Json,
+        listing: false,
+        endian: None,
+        symbols: None,
+        import_symbols: vec![],
+        format: OutputFormat::Binary,
+        memory: MemoryShape::default(),
+        verbosity: None,
+        delay_slots: None,
+        pseudo_instructions: None,
+        memory_operands: None,
+        summary: None,
+        text_base: None,
+        data_base: None,
+        regions: Regions::default(),
+        reproducible: false,
+        watch: false,
+        usage_log: None,
+        assignment: None,
+        error_format: ErrorFormat::Human,
+        warnings: WarningLevels::default(),
+        optimize: false,
     };
     let args_strings: Vec<String> = env::args().collect();
 
-    if args_strings.len() < 4 {
+    if args_strings.len() < 3 {
         help();
         return Err("Incorrect number of arguments");
     }
 
+    let mut verbose_count = 0;
     let mut arg_index = 1;
-    for arg in args_strings.iter().skip(1) {
+    let mut args_iter = args_strings.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
         let mut parsed_option = true;
         match arg.as_str() {
             "-l" | "--lineinfo" => args.line_info = true,
+            "--lineinfo-format" => match args_iter.next().map(|f| f.parse::<LineInfoFormat>()) {
+                Some(Ok(format)) => {
+                    args.line_info = true;
+                    args.lineinfo_format = format;
+                }
+                _ => return Err("Expected `json` or `binary` after --lineinfo-format"),
+            },
+            "--listing" => args.listing = true,
+            "--profile" => match args_iter.next() {
+                Some(profile) => args.profile = Some(profile.clone()),
+                None => return Err("Expected a profile name after --profile"),
+            },
+            "--reproducible" => args.reproducible = true,
+            "-O" | "--optimize" => args.optimize = true,
+            "--watch" => args.watch = true,
+            "-v" | "--verbose" => verbose_count += 1,
+            "-vv" => verbose_count += 2,
+            "--endian" => match args_iter.next().map(|e| e.parse::<Endian>()) {
+                Some(Ok(endian)) => args.endian = Some(endian),
+                _ => return Err("Expected `big` or `little` after --endian"),
+            },
+            "--symbols" => match args_iter.next().map(|f| f.parse::<SymbolFormat>()) {
+                Some(Ok(format)) => args.symbols = Some(format),
+                _ => return Err("Expected `text` or `json` after --symbols"),
+            },
+            "--import-symbols" => match args_iter.next() {
+                Some(path) => args.import_symbols.push(path.clone()),
+                None => return Err("Expected a symbol file after --import-symbols"),
+            },
+            "--format" => match args_iter.next().map(|f| f.parse::<OutputFormat>()) {
+                Some(Ok(format)) => args.format = format,
+                _ => return Err(
+                    "Expected `binary`, `elf`, `object`, `ihex`, `srec`, `readmemh`, `coe`, `mif` or `annotated` after --format",
+                ),
+            },
+            "--word-width" => match args_iter.next().map(|w| w.as_str()) {
+                Some(width @ ("8" | "16" | "32")) => {
+                    args.memory.width = width.parse().expect("matched a number")
+                }
+                _ => return Err("Expected `8`, `16` or `32` after --word-width"),
+            },
+            "--depth" => match args_iter.next().and_then(|d| d.parse::<u32>().ok()) {
+                Some(depth) if depth > 0 => args.memory.depth = Some(depth),
+                _ => return Err("Expected a number of words after --depth"),
+            },
+            "--delay-slots" => match args_iter.next().map(|m| m.parse::<DelaySlots>()) {
+                Some(Ok(mode)) => args.delay_slots = Some(mode),
+                _ => return Err("Expected `off`, `nop` or `reorder` after --delay-slots"),
+            },
+            "--pseudo-instructions" => match args_iter.next().map(|p| p.parse::<PseudoPolicy>()) {
+                Some(Ok(policy)) => args.pseudo_instructions = Some(policy),
+                _ => return Err(
+                    "Expected `allow`, `warn`, `forbid` or `annotate` after --pseudo-instructions",
+                ),
+            },
+            "--memory-operands" => match args_iter.next().map(|m| m.parse::<MemoryOperands>()) {
+                Some(Ok(mode)) => args.memory_operands = Some(mode),
+                _ => return Err("Expected `strict` or `lenient` after --memory-operands"),
+            },
+            "--text-base" => match args_iter.next().and_then(|a| parse_address(a)) {
+                Some(address) => args.text_base = Some(address),
+                _ => return Err("Expected a 0x address after --text-base"),
+            },
+            "--data-base" => match args_iter.next().and_then(|a| parse_address(a)) {
+                Some(address) => args.data_base = Some(address),
+                _ => return Err("Expected a 0x address after --data-base"),
+            },
+            "--usage-log" => match args_iter.next() {
+                Some(path) => args.usage_log = Some(path.clone()),
+                None => return Err("Expected a file after --usage-log"),
+            },
+            "--assignment" => match args_iter.next() {
+                Some(name) => args.assignment = Some(name.clone()),
+                None => return Err("Expected a name after --assignment"),
+            },
+            "--summary" => match args_iter.next().map(|f| f.parse::<SummaryFormat>()) {
+                Some(Ok(format)) => args.summary = Some(format),
+                _ => return Err("Expected `text` or `json` after --summary"),
+            },
+            "--error-format" => match args_iter.next().map(|f| f.parse::<ErrorFormat>()) {
+                Some(Ok(format)) => args.error_format = format,
+                _ => return Err("Expected `human` or `json` after --error-format"),
+            },
+            flag if flag.starts_with("-W") => {
+                if args.warnings.apply(&flag[2..]).is_err() {
+                    return Err("Unknown warning after -W, expected all, error, NAME, no-NAME or error=NAME with NAME one of unused-label, unreachable-code, immediate-truncation, branch-to-data or missing-exit");
+                }
+            }
             _ => parsed_option = false,
         };
         if parsed_option {
@@ -55,6 +329,21 @@ pub fn parse_args() -> Result<Args, &'static str> {
         arg_index += 1;
     }
 
+    // With only INPUT and OUTPUT given, the config file is name.toml
+    if arg_index == 3 {
+        args.output_as = std::mem::take(&mut args.input_as);
+        args.input_as = std::mem::replace(&mut args.config_fn, DEFAULT_CONFIG.to_string());
+    }
+
+    if verbose_count > 0 {
+        args.verbosity = Some(Verbosity::from_count(verbose_count));
+    }
+
+    // Optimizing takes the addresses the program is assembled at as final
+    if args.optimize && args.format == OutputFormat::Object {
+        return Err("-O cannot be used with --format object, which is linked at other addresses");
+    }
+
     if args.config_fn == String::new() {
         return Err("Expected a configuration file but found none");
     } else if args.input_as == String::new() {
@@ -0,0 +1,164 @@
+/// External assembler commands, as listed in `as_cmd` in the config file
+/// for assembling with another toolchain instead of NMA. A command is
+/// either a line for the platform's shell, `sh -c` or `cmd /C`, or a
+/// program and its arguments run directly, which needs no quoting and
+/// works the same everywhere:
+///
+/// ```toml
+/// as_cmd = [
+///     ["mips-linux-gnu-as", "${INPUT}", "-o", "a.o"],
+///     "mips-linux-gnu-objcopy -O binary a.o ${OUTPUT} && rm a.o",
+/// ]
+/// ```
+///
+/// `${INPUT}` and `${OUTPUT}` stand for the input and output files, and
+/// `${NAME}` for any other environment variable. In a shell line, what they
+/// stand for is quoted for the shell, so a file name with spaces or `;` in
+/// it is still one argument; a shell's own `$NAME` is left to the shell
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum AssemblerCommand {
+    /// A command line run by the platform's shell
+    Shell(String),
+    /// A program and its arguments, run without a shell
+    Argv(Vec<String>),
+}
+
+impl AssemblerCommand {
+    /// The command ready to run, with its placeholders filled in from
+    /// `input`, `output` and the environment
+    pub fn to_command(&self, input: &str, output: &str) -> Result<Command, String> {
+        match self {
+            AssemblerCommand::Shell(line) => Ok(shell(&substitute_with(
+                line,
+                &quote(input),
+                &quote(output),
+                quote,
+            )?)),
+            AssemblerCommand::Argv(argv) => {
+                let Some((program, args)) = argv.split_first() else {
+                    return Err("an assembler command has no program to run".to_string());
+                };
+                let mut command = Command::new(substitute(program, input, output)?);
+                for arg in args {
+                    command.arg(substitute(arg, input, output)?);
+                }
+                Ok(command)
+            }
+        }
+    }
+
+    /// The program the command starts, with its placeholders as written:
+    /// the first word of a shell line, or the program of an argv command
+    pub fn program(&self) -> Option<&str> {
+        match self {
+            AssemblerCommand::Shell(line) => line.split_whitespace().next(),
+            AssemblerCommand::Argv(argv) => argv.first().map(String::as_str),
+        }
+    }
+}
+
+impl fmt::Display for AssemblerCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssemblerCommand::Shell(line) => write!(f, "{}", line),
+            AssemblerCommand::Argv(argv) => write!(f, "{}", argv.join(" ")),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn quote(value: &str) -> String {
+    quote_for_cmd(value)
+}
+
+#[cfg(not(windows))]
+fn quote(value: &str) -> String {
+    quote_for_sh(value)
+}
+
+/// Quotes `value` as a single word for `sh`. Values made only of characters
+/// the shell gives no meaning to are left as they are
+pub fn quote_for_sh(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./,:@%+=".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_string();
+    }
+    // Nothing is special between single quotes, so a single quote ends the
+    // quoting, is escaped, and starts it again
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quotes `value` as a single word for `cmd`. Double quotes keep spaces and
+/// `&|<>^` from being interpreted, but not `%`, which is escaped outside them
+pub fn quote_for_cmd(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./\\:@+".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('"', "\\\"").replace('%', "\"^%\""))
+}
+
+#[cfg(windows)]
+fn shell(line: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    // cmd does its own parsing of the line, so it is passed on unquoted
+    let mut command = Command::new("cmd");
+    command.arg("/C").raw_arg(line);
+    command
+}
+
+#[cfg(not(windows))]
+fn shell(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
+
+/// Fills in `${INPUT}`, `${OUTPUT}` and environment variables in `text`.
+/// `{INPUT_AS}` and `{OUTPUT_AS}`, from older configs, still work
+pub fn substitute(text: &str, input: &str, output: &str) -> Result<String, String> {
+    substitute_with(text, input, output, str::to_string)
+}
+
+/// `substitute`, with the values of environment variables passed through
+/// `escape`. `input` and `output` are filled in as given
+fn substitute_with(
+    text: &str,
+    input: &str,
+    output: &str,
+    escape: fn(&str) -> String,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let Some(length) = rest[start + 2..].find('}') else {
+            return Err(format!(
+                "unterminated `${{` in assembler command `{}`",
+                text
+            ));
+        };
+        let name = &rest[start + 2..start + 2 + length];
+        match name {
+            "INPUT" => result.push_str(input),
+            "OUTPUT" => result.push_str(output),
+            _ => result.push_str(&escape(&env::var(name).map_err(|_| {
+                format!(
+                    "environment variable `{}` in assembler command `{}` is not set",
+                    name, text
+                )
+            })?)),
+        }
+        rest = &rest[start + 2 + length + 1..];
+    }
+    result.push_str(rest);
+    Ok(result
+        .replace("{INPUT_AS}", input)
+        .replace("{OUTPUT_AS}", output))
+}
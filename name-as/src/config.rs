@@ -1,31 +1,171 @@
+/// The config file, `name.toml` by convention. Top-level settings apply to
+/// every build; a `[profiles.NAME]` table holds settings for one target,
+/// such as `mars-compat` or `bare-metal`, which take the place of the
+/// top-level ones when it is picked with `--profile NAME`. Settings given
+/// on the command line take precedence over both. See configs/name.toml
 extern crate serde;
 extern crate toml;
+use name_core::endian::Endian;
 use serde::Deserialize;
 
 use crate::args::Args;
+use crate::command::AssemblerCommand;
+use crate::delay::DelaySlots;
+use crate::lint::WarningLevels;
+use crate::log::Verbosity;
+use crate::operands::MemoryOperands;
+use crate::pseudo::PseudoPolicy;
+use std::collections::BTreeMap;
 use std::fs;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub config_name: String,
-    pub as_cmd: Vec<String>,
+    /// Commands that assemble with another toolchain instead, see
+    /// [crate::command]. Empty to use NMA
+    #[serde(default)]
+    pub as_cmd: Vec<AssemblerCommand>,
+    /// Byte order of the output binary, overridden by `--endian`
+    #[serde(default)]
+    pub endian: Option<Endian>,
+    /// `quiet`, `encodings` or `parser`, overridden by `-v` and `-vv`
+    #[serde(default)]
+    pub verbosity: Option<Verbosity>,
+    /// `off`, `nop` or `reorder`, overridden by `--delay-slots`
+    #[serde(default)]
+    pub delay_slots: Option<DelaySlots>,
+    /// `allow`, `warn`, `forbid` or `annotate`, overridden by
+    /// `--pseudo-instructions`
+    #[serde(default)]
+    pub pseudo_instructions: Option<PseudoPolicy>,
+    /// `strict` or `lenient`, overridden by `--memory-operands`
+    #[serde(default)]
+    pub memory_operands: Option<MemoryOperands>,
+    /// Bytes of memory `.text` has to fit in, as reported by `--summary`
+    #[serde(default)]
+    pub text_size: Option<u32>,
+    /// Bytes of memory `.data` has to fit in, as reported by `--summary`
+    #[serde(default)]
+    pub data_size: Option<u32>,
+    /// Where `.text` starts unless the source moves it with `.org`
+    #[serde(default)]
+    pub text_base: Option<u32>,
+    /// Where `.data` starts unless the source moves it with `.org`
+    #[serde(default)]
+    pub data_base: Option<u32>,
+    /// `-W` flags without the `-W`, as in `["all", "no-unused-label"]`,
+    /// which those on the command line build on
+    #[serde(default)]
+    pub warnings: WarningLevels,
+    /// Named sets of settings for particular targets, picked with
+    /// `--profile`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings for one target. Each one left out keeps the top-level value
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// What the profile is for, shown when an unknown profile is asked for
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub as_cmd: Option<Vec<AssemblerCommand>>,
+    #[serde(default)]
+    pub endian: Option<Endian>,
+    #[serde(default)]
+    pub verbosity: Option<Verbosity>,
+    #[serde(default)]
+    pub delay_slots: Option<DelaySlots>,
+    #[serde(default)]
+    pub pseudo_instructions: Option<PseudoPolicy>,
+    #[serde(default)]
+    pub memory_operands: Option<MemoryOperands>,
+    #[serde(default)]
+    pub text_size: Option<u32>,
+    #[serde(default)]
+    pub data_size: Option<u32>,
+    #[serde(default)]
+    pub text_base: Option<u32>,
+    #[serde(default)]
+    pub data_base: Option<u32>,
+    #[serde(default)]
+    pub warnings: Option<WarningLevels>,
+}
+
+impl Config {
+    /// The settings of the profile called `name` in place of the top-level
+    /// ones they cover
+    pub fn with_profile(mut self, name: &str) -> Result<Config, String> {
+        let Some(profile) = self.profiles.remove(name) else {
+            let known: Vec<String> = self
+                .profiles
+                .iter()
+                .map(|(name, profile)| match &profile.description {
+                    Some(description) => format!("\n  {:<16} {}", name, description),
+                    None => format!("\n  {}", name),
+                })
+                .collect();
+            return Err(if known.is_empty() {
+                format!("unknown profile `{}`; the config file has none", name)
+            } else {
+                format!(
+                    "unknown profile `{}`; the config file has:{}",
+                    name,
+                    known.concat()
+                )
+            });
+        };
+        self.config_name = format!("{} ({})", self.config_name, name);
+        self.as_cmd = profile.as_cmd.unwrap_or(self.as_cmd);
+        self.endian = profile.endian.or(self.endian);
+        self.verbosity = profile.verbosity.or(self.verbosity);
+        self.delay_slots = profile.delay_slots.or(self.delay_slots);
+        self.pseudo_instructions = profile.pseudo_instructions.or(self.pseudo_instructions);
+        self.memory_operands = profile.memory_operands.or(self.memory_operands);
+        self.text_size = profile.text_size.or(self.text_size);
+        self.data_size = profile.data_size.or(self.data_size);
+        self.text_base = profile.text_base.or(self.text_base);
+        self.data_base = profile.data_base.or(self.data_base);
+        self.warnings = profile.warnings.unwrap_or(self.warnings);
+        Ok(self)
+    }
 }
 
 pub fn backup_config() -> Config {
     Config {
         config_name: "backup config".to_string(),
-        as_cmd: ["".to_string()].to_vec(),
+        as_cmd: vec![],
+        endian: None,
+        verbosity: None,
+        delay_slots: None,
+        pseudo_instructions: None,
+        memory_operands: None,
+        text_size: None,
+        data_size: None,
+        text_base: None,
+        data_base: None,
+        warnings: WarningLevels::default(),
+        profiles: BTreeMap::new(),
     }
 }
 
-pub fn parse_config(args: &Args) -> Result<Config, Box<dyn std::error::Error>> {
-    let mut toml_content = fs::read_to_string(&args.config_fn)?;
+/// Reads the config file at `path`, with its top-level settings in effect
+pub fn read_config(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    // Placeholders in `as_cmd` are filled in when the commands run, so
+    // paths never have to be valid TOML strings
+    let toml_content = fs::read_to_string(path)?;
 
-    // Replace patterns
-    toml_content = toml_content.replace("{INPUT_AS}", &args.input_as);
-    toml_content = toml_content.replace("{OUTPUT_AS}", &args.output_as);
+    Ok(toml::from_str(&toml_content)?)
+}
 
-    let config: Config = toml::from_str(&toml_content)?;
+pub fn parse_config(args: &Args) -> Result<Config, Box<dyn std::error::Error>> {
+    let config = read_config(&args.config_fn)?;
 
-    Ok(config)
+    match &args.profile {
+        Some(profile) => Ok(config.with_profile(profile)?),
+        None => Ok(config),
+    }
 }
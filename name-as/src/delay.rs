@@ -0,0 +1,138 @@
+/// Filling branch delay slots. On real MIPS hardware the instruction after
+/// a branch or jump runs before control reaches the target; MARS hides
+/// this by default, and so does the assembler unless asked to fill the
+/// slots for a machine that emulates them
+use crate::directive::{set_option, SetOption};
+use crate::nma::is_branch;
+use crate::parser::{MipsCST, Token};
+use crate::pseudo::{is_pseudo, is_pseudo_branch};
+use name_core::register::{FloatRegister, Register};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// What the assembler puts in the delay slot after each branch and jump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DelaySlots {
+    /// Nothing, the program is assembled as written
+    #[default]
+    Off,
+    /// A `nop`, so the program behaves the same with or without delay
+    /// slots emulated
+    Nop,
+    /// The instruction before the branch when it is safe to move it there,
+    /// like `.set reorder` in GNU as, otherwise a `nop`
+    Reorder,
+}
+
+impl std::str::FromStr for DelaySlots {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(DelaySlots::Off),
+            "nop" => Ok(DelaySlots::Nop),
+            "reorder" => Ok(DelaySlots::Reorder),
+            _ => Err(format!(
+                "unknown delay slot mode `{}`, expected off, nop or reorder",
+                s
+            )),
+        }
+    }
+}
+
+/// Rewrites a parsed program so that every branch and jump is followed by
+/// an instruction that is safe to run in its delay slot. Between
+/// `.set noreorder` and `.set reorder` the program is left as written
+pub fn fill_delay_slots(sequence: Vec<MipsCST>, mode: DelaySlots) -> Vec<MipsCST> {
+    if mode == DelaySlots::Off {
+        return sequence;
+    }
+
+    let mut filled: Vec<MipsCST> = Vec::with_capacity(sequence.len());
+    // The index of the last instruction placed in a delay slot, which must
+    // stay where it is
+    let mut last_slot: Option<usize> = None;
+    let mut reorder = true;
+    for sub_cst in sequence {
+        let (mnemonic, args) = match &sub_cst {
+            MipsCST::Instruction(mnemonic, args) => (mnemonic, args),
+            MipsCST::Directive(name, args) if name.as_str() == ".set" => {
+                if let Ok(SetOption::Reorder(on)) = set_option(name, args) {
+                    reorder = on;
+                }
+                filled.push(sub_cst);
+                continue;
+            }
+            _ => {
+                filled.push(sub_cst);
+                continue;
+            }
+        };
+        if !reorder || !(is_branch(mnemonic.as_str()) || is_pseudo_branch(mnemonic.as_str())) {
+            filled.push(sub_cst);
+            continue;
+        }
+
+        let nop = MipsCST::Instruction(
+            Token {
+                text: "nop".to_string(),
+                ..mnemonic.clone()
+            },
+            vec![],
+        );
+        let movable = mode == DelaySlots::Reorder
+            && last_slot != Some(filled.len().wrapping_sub(1))
+            && matches!(filled.last(), Some(MipsCST::Instruction(previous, previous_args))
+                if can_fill_slot(previous, previous_args, mnemonic, args));
+        let slot = if movable { filled.pop().unwrap() } else { nop };
+        filled.push(sub_cst);
+        last_slot = Some(filled.len());
+        filled.push(slot);
+    }
+    filled
+}
+
+/// Whether `mnemonic args`, the instruction right before a branch with no
+/// label in between, can be moved after it without changing what either
+/// does. The check is conservative: the two may not name any register in
+/// common, whether read or written
+fn can_fill_slot(mnemonic: &Token, args: &[Token], branch: &Token, branch_args: &[Token]) -> bool {
+    // Pseudo-instructions expand to more than one slot's worth. Syscalls
+    // use registers that do not appear as operands, as do floating point
+    // compares, which set the condition bc1t and bc1f test
+    if is_pseudo(mnemonic.as_str(), args)
+        || is_branch(mnemonic.as_str())
+        || mnemonic.as_str() == "syscall"
+        || mnemonic.as_str().starts_with("c.")
+    {
+        return false;
+    }
+
+    let (Some(used), Some(mut branch_used)) = (registers(args), registers(branch_args)) else {
+        return false;
+    };
+    // Linking jumps and branches write the return address
+    if matches!(branch.as_str(), "jal" | "jalr" | "bltzal" | "bgezal") {
+        branch_used.insert(Register::Ra.number());
+    }
+    // Branches on a comparison set $at before they branch
+    if is_pseudo_branch(branch.as_str()) {
+        branch_used.insert(Register::At.number());
+    }
+    used.is_disjoint(&branch_used)
+}
+
+/// The registers named by some operands, with floating point registers
+/// numbered from 32. None if one of them is not a register at all
+fn registers(args: &[Token]) -> Option<BTreeSet<usize>> {
+    let mut registers = BTreeSet::new();
+    for arg in args.iter().filter(|arg| arg.as_str().starts_with('$')) {
+        let number = match arg.as_str().parse::<Register>() {
+            Ok(register) => register.number(),
+            Err(_) => 32 + arg.as_str().parse::<FloatRegister>().ok()?.number(),
+        };
+        registers.insert(number);
+    }
+    Some(registers)
+}
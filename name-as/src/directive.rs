@@ -0,0 +1,380 @@
+/// Assembler directives, which switch between sections and lay out the
+/// contents of `.data`
+use crate::error::{AssemblerError, Location};
+use crate::expr::label_offset;
+use crate::nma::{label_address, parse_int};
+use crate::parser::Token;
+use name_core::endian::Endian;
+use name_core::symbols::DataType;
+use std::collections::BTreeMap;
+
+/// The section instructions and data are being assembled into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Text,
+    Data,
+}
+
+impl SectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::Text => ".text",
+            SectionKind::Data => ".data",
+        }
+    }
+}
+
+/// What a directive does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    /// `.text` or `.data`: later lines are assembled into that section
+    Section(SectionKind),
+    /// `.globl label, ...`: exports labels to other objects when linking
+    Global,
+    /// `.extern label, ...`: declares labels defined in another object,
+    /// which are filled in when linking
+    Extern,
+    /// `.size label, bytes`: sets the size recorded for a label in the
+    /// symbol table, see [symbol_size]
+    Size,
+    /// `.org address`: moves the current section to `address`, see
+    /// [org_address]
+    Org,
+    /// Lays out bytes in `.data`, see [data_bytes]
+    Data,
+    /// `.set option`: changes how the lines after it are assembled, see
+    /// [set_option]. `.set name, value` defines a constant instead, and is
+    /// taken out before directives are looked up
+    Set,
+}
+
+/// An assembler control set with `.set`, as in GNU as and MARS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOption {
+    /// `.set at` or `.set noat`: whether pseudo-instructions may use $at.
+    /// With `noat` the program has $at to itself, and a pseudo-instruction
+    /// that needs it is an error
+    At(bool),
+    /// `.set reorder` or `.set noreorder`: whether the assembler fills
+    /// branch delay slots. With `noreorder` the program is assembled as
+    /// written, leaving the delay slots to whoever wrote it
+    Reorder(bool),
+}
+
+/// Looks up a directive by name
+pub fn directive(name: &Token) -> Result<Directive, AssemblerError> {
+    match name.as_str() {
+        ".text" => Ok(Directive::Section(SectionKind::Text)),
+        ".data" => Ok(Directive::Section(SectionKind::Data)),
+        ".globl" | ".global" => Ok(Directive::Global),
+        ".extern" => Ok(Directive::Extern),
+        ".size" => Ok(Directive::Size),
+        ".org" => Ok(Directive::Org),
+        ".set" => Ok(Directive::Set),
+        ".word" | ".half" | ".byte" | ".float" | ".ascii" | ".asciiz" | ".space" | ".align" => {
+            Ok(Directive::Data)
+        }
+        _ => Err(AssemblerError::InvalidDirective {
+            location: Location::at(name).into(),
+            token: name.text.clone(),
+            message: format!("unknown directive `{}`", name.as_str()),
+        }),
+    }
+}
+
+/// The option a `.set` directive sets
+pub fn set_option(name: &Token, args: &[Token]) -> Result<SetOption, AssemblerError> {
+    let [option] = args else {
+        return Err(operand_count(name, "an option, or a name and a value"));
+    };
+    match option.as_str() {
+        "at" => Ok(SetOption::At(true)),
+        "noat" => Ok(SetOption::At(false)),
+        "reorder" => Ok(SetOption::Reorder(true)),
+        "noreorder" => Ok(SetOption::Reorder(false)),
+        _ => Err(AssemblerError::InvalidDirective {
+            location: Location::at(option).into(),
+            token: option.text.clone(),
+            message: format!(
+                "unknown `.set` option `{}`, expected at, noat, reorder or noreorder",
+                option.as_str()
+            ),
+        }),
+    }
+}
+
+/// The boundary a data directive's contents start on. Words and halfwords
+/// are aligned to their size, `.align n` to 2^n bytes
+pub fn data_alignment(name: &Token, args: &[Token]) -> Result<u32, AssemblerError> {
+    match name.as_str() {
+        ".word" | ".float" => Ok(4),
+        ".half" => Ok(2),
+        ".align" => {
+            let [power] = args else {
+                return Err(operand_count(name, "one power of two"));
+            };
+            match parse_int(power)? {
+                value @ 0..=12 => Ok(1 << value),
+                _ => Err(AssemblerError::InvalidImmediate {
+                    location: Location::at(power).into(),
+                    token: power.text.clone(),
+                    message: format!("alignment `{}` out of range, expected 0 to 12", power.text),
+                }),
+            }
+        }
+        _ => Ok(1),
+    }
+}
+
+/// The type recorded for labels naming what a data directive lays out.
+/// `.space` and `.align` reserve bytes without saying what they hold
+pub fn data_type(name: &Token) -> Option<DataType> {
+    match name.as_str() {
+        ".word" => Some(DataType::Word),
+        ".half" => Some(DataType::Half),
+        ".byte" => Some(DataType::Byte),
+        ".float" => Some(DataType::Float),
+        ".ascii" | ".asciiz" => Some(DataType::Ascii),
+        _ => None,
+    }
+}
+
+/// The label and byte count of a `.size` directive
+pub fn symbol_size<'a>(
+    name: &Token,
+    args: &'a [Token],
+) -> Result<(&'a Token, u32), AssemblerError> {
+    let [label, size] = args else {
+        return Err(operand_count(name, "a label and a byte count"));
+    };
+    Ok((label, integer(size, 0, i64::from(u32::MAX))? as u32))
+}
+
+/// The address a `.org` directive moves to. Before anything has been
+/// assembled into a section, this is where the section starts; after, the
+/// gap up to it is filled with zeros. `here` is the current address in
+/// `section` and `empty` whether it holds anything yet
+pub fn org_address(
+    name: &Token,
+    args: &[Token],
+    section: SectionKind,
+    here: u32,
+    empty: bool,
+) -> Result<u32, AssemblerError> {
+    let [address] = args else {
+        return Err(operand_count(name, "one address"));
+    };
+    let address = integer(address, 0, i64::from(u32::MAX))? as u32;
+    if section == SectionKind::Text && !address.is_multiple_of(4) {
+        return Err(AssemblerError::InvalidDirective {
+            location: Location::at(&args[0]).into(),
+            token: args[0].text.clone(),
+            message: format!(
+                "`.org` address 0x{:08x} in .text is not a multiple of 4",
+                address
+            ),
+        });
+    }
+    if !empty && address < here {
+        return Err(AssemblerError::InvalidDirective {
+            location: Location::at(&args[0]).into(),
+            token: args[0].text.clone(),
+            message: format!(
+                "`.org` cannot move back to 0x{:08x}, the section is already at 0x{:08x}",
+                address, here
+            ),
+        });
+    }
+    Ok(address)
+}
+
+/// Whether a data directive operand names a label, perhaps plus or minus
+/// a number, rather than giving a number or string
+pub fn is_label(arg: &Token) -> bool {
+    label_offset(arg).is_some()
+}
+
+/// The labels whose addresses a data directive lays out. Only `.word` has
+/// room for an address, so other directives have none
+pub fn data_labels<'a>(name: &Token, args: &'a [Token]) -> Vec<&'a Token> {
+    match name.as_str() {
+        ".word" => args.iter().filter(|arg| is_label(arg)).collect(),
+        _ => vec![],
+    }
+}
+
+/// The bytes a data directive lays out, not counting alignment. Without
+/// `labels`, every label is taken to be at address 0, which is enough to
+/// size the data but not to fill it in
+pub fn data_bytes(
+    name: &Token,
+    args: &[Token],
+    endian: Endian,
+    labels: Option<&BTreeMap<String, u32>>,
+) -> Result<Vec<u8>, AssemblerError> {
+    let mut bytes = vec![];
+    match name.as_str() {
+        ".word" => {
+            for arg in non_empty(name, args, "words")? {
+                let value = if is_label(arg) {
+                    match labels {
+                        Some(labels) => label_address(labels, arg)?,
+                        None => 0,
+                    }
+                } else {
+                    integer(arg, i64::from(i32::MIN), i64::from(u32::MAX))? as u32
+                };
+                bytes.extend(endian.u32_to_bytes(value));
+            }
+        }
+        ".half" => {
+            for arg in non_empty(name, args, "halfwords")? {
+                let value = integer(arg, i64::from(i16::MIN), i64::from(u16::MAX))? as u16;
+                bytes.extend(endian.u16_to_bytes(value));
+            }
+        }
+        ".byte" => {
+            for arg in non_empty(name, args, "bytes")? {
+                bytes.push(integer(arg, i64::from(i8::MIN), i64::from(u8::MAX))? as u8);
+            }
+        }
+        ".float" => {
+            for arg in non_empty(name, args, "numbers")? {
+                bytes.extend(endian.u32_to_bytes(float(arg)?.to_bits()));
+            }
+        }
+        ".ascii" | ".asciiz" => {
+            for arg in non_empty(name, args, "strings")? {
+                bytes.extend(string(arg)?);
+                if name.as_str() == ".asciiz" {
+                    bytes.push(0);
+                }
+            }
+        }
+        ".space" => {
+            let [length] = args else {
+                return Err(operand_count(name, "one byte count"));
+            };
+            bytes.resize(integer(length, 0, i64::from(u16::MAX))? as usize, 0);
+        }
+        // All .align does is pad up to the boundary
+        _ => (),
+    }
+    Ok(bytes)
+}
+
+fn non_empty<'a>(
+    name: &Token,
+    args: &'a [Token],
+    expected: &str,
+) -> Result<&'a [Token], AssemblerError> {
+    if args.is_empty() {
+        return Err(operand_count(name, &format!("one or more {}", expected)));
+    }
+    Ok(args)
+}
+
+fn operand_count(name: &Token, expected: &str) -> AssemblerError {
+    AssemblerError::OperandCount {
+        location: Location::at(name).into(),
+        token: name.text.clone(),
+        message: format!("`{}` expects {}", name.as_str(), expected),
+    }
+}
+
+/// Parses an integer operand, which has to be in `min..=max`
+fn integer(arg: &Token, min: i64, max: i64) -> Result<i64, AssemblerError> {
+    if arg.as_str().starts_with('"') {
+        return Err(AssemblerError::OperandType {
+            location: Location::at(arg).into(),
+            token: arg.text.clone(),
+            message: format!("expected a number, found string {}", arg.as_str()),
+        });
+    }
+    if is_label(arg) {
+        return Err(AssemblerError::OperandType {
+            location: Location::at(arg).into(),
+            token: arg.text.clone(),
+            message: format!(
+                "expected a number, found label `{}`; only `.word` can hold the address of a label",
+                arg.as_str()
+            ),
+        });
+    }
+    let value = parse_int(arg)?;
+    if !(min..=max).contains(&value) {
+        return Err(AssemblerError::InvalidImmediate {
+            location: Location::at(arg).into(),
+            token: arg.text.clone(),
+            message: format!(
+                "value `{}` out of range, expected {} to {}",
+                arg.as_str(),
+                min,
+                max
+            ),
+        });
+    }
+    Ok(value)
+}
+
+/// Parses a single precision operand, which may also be written as an integer
+fn float(arg: &Token) -> Result<f32, AssemblerError> {
+    match arg.as_str().parse::<f32>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(AssemblerError::InvalidImmediate {
+            location: Location::at(arg).into(),
+            token: arg.text.clone(),
+            message: format!(
+                "expected a single precision number, found `{}`",
+                arg.as_str()
+            ),
+        }),
+    }
+}
+
+/// Decodes a double-quoted string literal into its bytes, handling the
+/// usual backslash escapes
+fn string(arg: &Token) -> Result<Vec<u8>, AssemblerError> {
+    let Some(inner) = arg
+        .as_str()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+    else {
+        return Err(AssemblerError::OperandType {
+            location: Location::at(arg).into(),
+            token: arg.text.clone(),
+            message: format!(
+                "expected a string in double quotes, found `{}`",
+                arg.as_str()
+            ),
+        });
+    };
+
+    let mut bytes = vec![];
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"' | '\'')) => c,
+                other => {
+                    return Err(AssemblerError::InvalidDirective {
+                        location: Location::at(arg).into(),
+                        token: arg.text.clone(),
+                        message: format!(
+                            "unknown escape `\\{}` in string",
+                            other.map(String::from).unwrap_or_default()
+                        ),
+                    })
+                }
+            },
+            c => c,
+        };
+        let mut buf = [0; 4];
+        bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+    }
+    Ok(bytes)
+}
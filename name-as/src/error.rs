@@ -0,0 +1,562 @@
+use std::fmt;
+
+use name_core::diagnostic::{Diagnostic, Note, Severity, Span};
+
+use crate::lint::Lint;
+use crate::parser::Token;
+
+/// A position in an assembly source file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// How many characters to underline
+    pub length: usize,
+    /// The full text of the line, kept so diagnostics can be rendered
+    /// without re-reading the source
+    pub source_line: String,
+}
+
+impl Location {
+    /// Points at a token. The file name and source line are filled in
+    /// later by [AssemblerError::with_source]
+    pub fn at(token: &Token) -> Location {
+        Location {
+            file: String::new(),
+            line: token.line,
+            column: token.column,
+            length: token.text.chars().count(),
+            source_line: String::new(),
+        }
+    }
+
+    /// Covers everything from the start of `first` to the end of `last`.
+    /// Falls back to just `first` if the two are on different lines
+    pub fn spanning(first: &Token, last: &Token) -> Location {
+        let mut location = Location::at(first);
+        if first.line == last.line && last.column >= first.column {
+            location.length = last.column + last.text.chars().count() - first.column;
+        }
+        location
+    }
+
+    /// Points just past the end of `token`, where something is missing
+    pub fn after(token: &Token) -> Location {
+        Location {
+            file: String::new(),
+            line: token.line,
+            column: token.column + token.text.chars().count(),
+            length: 1,
+            source_line: String::new(),
+        }
+    }
+}
+
+/// Everything that can go wrong while assembling a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssemblerError {
+    /// Reading the input or writing an output file failed
+    Io { path: String, message: String },
+    /// The source text does not match the grammar
+    Syntax {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// The mnemonic does not name a known instruction
+    UnknownInstruction {
+        location: Box<Location>,
+        token: String,
+    },
+    /// A register operand is malformed or out of range
+    InvalidRegister {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// An immediate or shift amount could not be parsed or does not fit
+    InvalidImmediate {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// An immediate parsed fine but does not fit in the instruction's
+    /// 16-bit field
+    ImmediateOutOfRange {
+        location: Box<Location>,
+        token: String,
+        message: String,
+        /// How to load the value some other way
+        help: String,
+    },
+    /// A shift amount outside 0 to 31
+    ShiftAmountOutOfRange {
+        location: Box<Location>,
+        token: String,
+    },
+    /// The instruction was given the wrong number of operands
+    OperandCount {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// An operand is of the wrong kind, e.g. an immediate where the
+    /// instruction expects a register
+    OperandType {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// A label was referenced but never declared. `references` holds every
+    /// use of it in source order, so is never empty
+    UndeclaredLabel {
+        token: String,
+        references: Vec<Location>,
+        /// A declared label with a similar name, if there is one
+        suggestion: Option<String>,
+    },
+    /// A label was declared more than once
+    DuplicateLabel {
+        location: Box<Location>,
+        token: String,
+        previous: Box<Location>,
+    },
+    /// The requested entry point is not a label or an instruction address
+    /// in the program
+    InvalidEntry {
+        token: String,
+        message: String,
+        /// A declared label with a similar name, if there is one
+        suggestion: Option<String>,
+    },
+    /// `.text` and `.data` were placed over each other
+    SectionOverlap { token: String, message: String },
+    /// A section runs past the end of the 32-bit address space
+    AddressOverflow {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// An encoded value does not fit in its instruction field
+    FieldOverflow {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// A directive is unknown or its operands are malformed
+    InvalidDirective {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// An instruction in `.data`, or data in `.text`
+    WrongSection {
+        location: Box<Location>,
+        token: String,
+        message: String,
+    },
+    /// A label exported with `.globl` but not defined, or declared
+    /// `.extern` and defined anyway
+    SymbolVisibility {
+        location: Box<Location>,
+        token: String,
+        message: String,
+        /// The conflicting `.extern`, if there is one
+        previous: Option<Box<Location>>,
+        help: String,
+    },
+    /// A pseudo-instruction where the pseudo-instruction policy forbids
+    /// them, or warns about them
+    PseudoInstruction {
+        location: Box<Location>,
+        token: String,
+        message: String,
+        /// The real instructions it expands into
+        help: String,
+    },
+    /// Something that assembles but is probably a mistake, see
+    /// [crate::lint]. Only an error when the warning is made one
+    Lint {
+        location: Box<Location>,
+        token: String,
+        lint: Lint,
+        message: String,
+        help: Option<String>,
+    },
+}
+
+impl AssemblerError {
+    /// The source position this error points at, if any
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            AssemblerError::Io { .. }
+            | AssemblerError::InvalidEntry { .. }
+            | AssemblerError::SectionOverlap { .. } => None,
+            AssemblerError::UndeclaredLabel { references, .. } => references.first(),
+            AssemblerError::Syntax { location, .. }
+            | AssemblerError::UnknownInstruction { location, .. }
+            | AssemblerError::InvalidRegister { location, .. }
+            | AssemblerError::InvalidImmediate { location, .. }
+            | AssemblerError::ImmediateOutOfRange { location, .. }
+            | AssemblerError::ShiftAmountOutOfRange { location, .. }
+            | AssemblerError::OperandCount { location, .. }
+            | AssemblerError::OperandType { location, .. }
+            | AssemblerError::DuplicateLabel { location, .. }
+            | AssemblerError::FieldOverflow { location, .. }
+            | AssemblerError::InvalidDirective { location, .. }
+            | AssemblerError::WrongSection { location, .. }
+            | AssemblerError::SymbolVisibility { location, .. }
+            | AssemblerError::PseudoInstruction { location, .. }
+            | AssemblerError::Lint { location, .. }
+            | AssemblerError::AddressOverflow { location, .. } => Some(location),
+        }
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Location> {
+        match self {
+            AssemblerError::Io { .. }
+            | AssemblerError::InvalidEntry { .. }
+            | AssemblerError::SectionOverlap { .. } => None,
+            AssemblerError::UndeclaredLabel { references, .. } => references.first_mut(),
+            AssemblerError::Syntax { location, .. }
+            | AssemblerError::UnknownInstruction { location, .. }
+            | AssemblerError::InvalidRegister { location, .. }
+            | AssemblerError::InvalidImmediate { location, .. }
+            | AssemblerError::ImmediateOutOfRange { location, .. }
+            | AssemblerError::ShiftAmountOutOfRange { location, .. }
+            | AssemblerError::OperandCount { location, .. }
+            | AssemblerError::OperandType { location, .. }
+            | AssemblerError::DuplicateLabel { location, .. }
+            | AssemblerError::FieldOverflow { location, .. }
+            | AssemblerError::InvalidDirective { location, .. }
+            | AssemblerError::WrongSection { location, .. }
+            | AssemblerError::SymbolVisibility { location, .. }
+            | AssemblerError::PseudoInstruction { location, .. }
+            | AssemblerError::Lint { location, .. }
+            | AssemblerError::AddressOverflow { location, .. } => Some(location.as_mut()),
+        }
+    }
+
+    /// The offending piece of source text
+    pub fn token(&self) -> &str {
+        match self {
+            AssemblerError::Io { path, .. } => path,
+            AssemblerError::Syntax { token, .. }
+            | AssemblerError::UnknownInstruction { token, .. }
+            | AssemblerError::InvalidRegister { token, .. }
+            | AssemblerError::InvalidImmediate { token, .. }
+            | AssemblerError::ImmediateOutOfRange { token, .. }
+            | AssemblerError::ShiftAmountOutOfRange { token, .. }
+            | AssemblerError::OperandCount { token, .. }
+            | AssemblerError::OperandType { token, .. }
+            | AssemblerError::UndeclaredLabel { token, .. }
+            | AssemblerError::DuplicateLabel { token, .. }
+            | AssemblerError::InvalidEntry { token, .. }
+            | AssemblerError::SectionOverlap { token, .. }
+            | AssemblerError::FieldOverflow { token, .. }
+            | AssemblerError::InvalidDirective { token, .. }
+            | AssemblerError::WrongSection { token, .. }
+            | AssemblerError::SymbolVisibility { token, .. }
+            | AssemblerError::PseudoInstruction { token, .. }
+            | AssemblerError::Lint { token, .. }
+            | AssemblerError::AddressOverflow { token, .. } => token,
+        }
+    }
+
+    /// A short, stable name for the kind of error, used as the code of its
+    /// [Diagnostic]
+    pub fn code(&self) -> &'static str {
+        match self {
+            AssemblerError::Io { .. } => "io",
+            AssemblerError::Syntax { .. } => "syntax",
+            AssemblerError::UnknownInstruction { .. } => "unknown-instruction",
+            AssemblerError::InvalidRegister { .. } => "invalid-register",
+            AssemblerError::InvalidImmediate { .. } => "invalid-immediate",
+            AssemblerError::ImmediateOutOfRange { .. } => "immediate-out-of-range",
+            AssemblerError::ShiftAmountOutOfRange { .. } => "shift-amount-out-of-range",
+            AssemblerError::OperandCount { .. } => "operand-count",
+            AssemblerError::OperandType { .. } => "operand-type",
+            AssemblerError::UndeclaredLabel { .. } => "undeclared-label",
+            AssemblerError::DuplicateLabel { .. } => "duplicate-label",
+            AssemblerError::InvalidEntry { .. } => "invalid-entry",
+            AssemblerError::SectionOverlap { .. } => "section-overlap",
+            AssemblerError::FieldOverflow { .. } => "field-overflow",
+            AssemblerError::InvalidDirective { .. } => "invalid-directive",
+            AssemblerError::WrongSection { .. } => "wrong-section",
+            AssemblerError::SymbolVisibility { .. } => "symbol-visibility",
+            AssemblerError::PseudoInstruction { .. } => "pseudo-instruction",
+            AssemblerError::AddressOverflow { .. } => "address-overflow",
+            AssemblerError::Lint { lint, .. } => lint.name(),
+        }
+    }
+
+    /// The error in the structured form shared with editors and other tools
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: self.code().to_string(),
+            message: self.message(),
+            span: self.location().map(Span::from),
+            notes: self
+                .related()
+                .into_iter()
+                .map(|(message, location)| Note {
+                    message: message.to_string(),
+                    span: location.into(),
+                })
+                .collect(),
+            help: self.help(),
+        }
+    }
+
+    /// A one-line, human-readable description of the error
+    pub fn message(&self) -> String {
+        match self {
+            AssemblerError::Io { path, message } => format!("{}: {}", path, message),
+            AssemblerError::UnknownInstruction { token, .. } => {
+                format!("unknown instruction `{}`", token)
+            }
+            AssemblerError::ShiftAmountOutOfRange { token, .. } => {
+                format!("shift amount `{}` out of range, expected 0 to 31", token)
+            }
+            AssemblerError::UndeclaredLabel {
+                token, references, ..
+            } => {
+                if references.len() > 1 {
+                    let lines: Vec<String> =
+                        references.iter().map(|l| l.line.to_string()).collect();
+                    format!(
+                        "use of undeclared label `{}` ({} references, on lines {})",
+                        token,
+                        references.len(),
+                        lines.join(", ")
+                    )
+                } else {
+                    format!("use of undeclared label `{}`", token)
+                }
+            }
+            AssemblerError::DuplicateLabel {
+                location,
+                token,
+                previous,
+            } => format!(
+                "label `{}` on line {} was already defined on line {}",
+                token, location.line, previous.line
+            ),
+            AssemblerError::Syntax { message, .. }
+            | AssemblerError::InvalidRegister { message, .. }
+            | AssemblerError::InvalidImmediate { message, .. }
+            | AssemblerError::ImmediateOutOfRange { message, .. }
+            | AssemblerError::OperandCount { message, .. }
+            | AssemblerError::OperandType { message, .. }
+            | AssemblerError::InvalidEntry { message, .. }
+            | AssemblerError::SectionOverlap { message, .. }
+            | AssemblerError::FieldOverflow { message, .. }
+            | AssemblerError::InvalidDirective { message, .. }
+            | AssemblerError::WrongSection { message, .. }
+            | AssemblerError::SymbolVisibility { message, .. }
+            | AssemblerError::PseudoInstruction { message, .. }
+            | AssemblerError::Lint { message, .. }
+            | AssemblerError::AddressOverflow { message, .. } => message.clone(),
+        }
+    }
+
+    /// Related positions worth showing alongside the error, each with a
+    /// note explaining it
+    pub fn related(&self) -> Vec<(&'static str, &Location)> {
+        match self {
+            AssemblerError::DuplicateLabel { previous, .. } => {
+                vec![("first defined here", previous)]
+            }
+            AssemblerError::SymbolVisibility {
+                previous: Some(previous),
+                ..
+            } => vec![("declared `.extern` here", previous)],
+            AssemblerError::UndeclaredLabel { references, .. } => references
+                .iter()
+                .skip(1)
+                .map(|location| ("also referenced here", location))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// A suggestion for fixing the error, if one is known
+    pub fn help(&self) -> Option<String> {
+        match self {
+            AssemblerError::UndeclaredLabel {
+                suggestion: Some(suggestion),
+                ..
+            }
+            | AssemblerError::InvalidEntry {
+                suggestion: Some(suggestion),
+                ..
+            } => Some(format!(
+                "a label with a similar name exists: `{}`",
+                suggestion
+            )),
+            AssemblerError::ImmediateOutOfRange { help, .. }
+            | AssemblerError::SymbolVisibility { help, .. }
+            | AssemblerError::PseudoInstruction { help, .. } => Some(help.clone()),
+            AssemblerError::Lint { help, .. } => help.clone(),
+            _ => None,
+        }
+    }
+
+    /// Attaches the file name and the offending line of `source` so the
+    /// error can be rendered on its own
+    pub fn with_source(mut self, file: &str, source: &str) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.fill(file, source);
+        }
+        match &mut self {
+            AssemblerError::DuplicateLabel { previous, .. }
+            | AssemblerError::SymbolVisibility {
+                previous: Some(previous),
+                ..
+            } => previous.fill(file, source),
+            AssemblerError::UndeclaredLabel { references, .. } => {
+                for location in references.iter_mut().skip(1) {
+                    location.fill(file, source);
+                }
+            }
+            _ => (),
+        }
+        self
+    }
+}
+
+impl From<&Location> for Span {
+    fn from(location: &Location) -> Span {
+        Span {
+            file: location.file.clone(),
+            line: location.line,
+            column: location.column,
+            length: location.length,
+        }
+    }
+}
+
+impl Location {
+    fn fill(&mut self, file: &str, source: &str) {
+        self.file = file.to_string();
+        self.source_line = source
+            .lines()
+            .nth(self.line.saturating_sub(1))
+            .unwrap_or("")
+            .to_string();
+    }
+
+    /// Writes the source line with the location underlined
+    fn write_snippet(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line_label = self.line.to_string();
+        let gutter = " ".repeat(line_label.len());
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter, self.file, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_label, self.source_line)?;
+
+        // Columns are 1-indexed characters, tabs are preserved so the
+        // underline lines up with the source as displayed
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = self.length.max(1);
+        write!(f, "{} | {}{}", gutter, padding, "^".repeat(width))
+    }
+}
+
+/// Renders the error rustc-style, underlining the offending token:
+///
+/// ```text
+/// error: unknown register `$t10`
+///  --> prog.asm:3:8
+///   |
+/// 3 |    add $t10, $t2, $t3
+///   |        ^^^^
+/// ```
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.render(f, "error")
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+impl AssemblerError {
+    fn render(&self, f: &mut fmt::Formatter, severity: &str) -> fmt::Result {
+        write!(f, "{}: {}", severity, self.message())?;
+
+        if let Some(location) = self.location() {
+            writeln!(f)?;
+            location.write_snippet(f)?;
+        }
+
+        for (note, location) in self.related() {
+            writeln!(f, "\nnote: {}", note)?;
+            location.write_snippet(f)?;
+        }
+
+        if let Some(help) = self.help() {
+            write!(f, "\nhelp: {}", help)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How name-as writes errors and warnings to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Rendered with the source they point at, for people
+    #[default]
+    Human,
+    /// A `diagnostics` document, see [name_core::diagnostic::DiagnosticReport],
+    /// for editors and grading scripts
+    Json,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "unknown error format `{}`, expected `human` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// Something worth pointing out that did not stop the program assembling,
+/// described like the error it would be under a stricter setting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning(pub AssemblerError);
+
+impl Warning {
+    /// The warning in the structured form shared with editors and other
+    /// tools
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..self.0.to_diagnostic()
+        }
+    }
+}
+
+/// Renders like [AssemblerError], but labelled `warning:`
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.render(f, "warning")
+    }
+}
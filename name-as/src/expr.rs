@@ -0,0 +1,521 @@
+/// Constant expressions in operands, such as `(BUFFER_SIZE*4)+8` or
+/// `table+4`. Operators are those of C, with C's precedence: unary `-`,
+/// `~` and `+`, then `*` `/` `%`, `+` `-`, `<<` `>>`, `&`, `^` and `|`.
+/// Arithmetic is done in 64 bits and anything that overflows them is an
+/// error; whether the result fits the operand is up to the operand.
+///
+/// Names in an expression are constants defined with `.eqv NAME, value`
+/// (or `.equ`, or `.set`, which can give one a new value further on), which
+/// are substituted before anything is laid out, or
+/// labels. An operand that takes a label can add a constant to it or take
+/// one from it, but cannot otherwise compute with an address
+use crate::error::{AssemblerError, Location};
+use crate::parser::{MipsCST, Token};
+use name_core::memmap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    /// A constant or a label
+    Name(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::And => "&",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Remainder => "%",
+        }
+    }
+
+    /// How tightly the operator binds, higher first
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::Xor => 2,
+            BinaryOp::And => 3,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 4,
+            BinaryOp::Add | BinaryOp::Subtract => 5,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder => 6,
+        }
+    }
+
+    fn apply(self, left: i64, right: i64) -> Result<i64, String> {
+        let overflow = || format!("`{} {} {}` overflows", left, self.symbol(), right);
+        match self {
+            BinaryOp::Or => Ok(left | right),
+            BinaryOp::Xor => Ok(left ^ right),
+            BinaryOp::And => Ok(left & right),
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight if !(0..64).contains(&right) => {
+                Err(format!("cannot shift by {}, expected 0 to 63", right))
+            }
+            BinaryOp::ShiftLeft => {
+                let shifted = left << right;
+                if shifted >> right == left {
+                    Ok(shifted)
+                } else {
+                    Err(overflow())
+                }
+            }
+            BinaryOp::ShiftRight => Ok(left >> right),
+            BinaryOp::Add => left.checked_add(right).ok_or_else(overflow),
+            BinaryOp::Subtract => left.checked_sub(right).ok_or_else(overflow),
+            BinaryOp::Multiply => left.checked_mul(right).ok_or_else(overflow),
+            BinaryOp::Divide | BinaryOp::Remainder if right == 0 => {
+                Err(format!("`{} {} 0` divides by zero", left, self.symbol()))
+            }
+            BinaryOp::Divide => left.checked_div(right).ok_or_else(overflow),
+            BinaryOp::Remainder => left.checked_rem(right).ok_or_else(overflow),
+        }
+    }
+}
+
+/// Splits an expression into numbers, names, operators and parentheses
+fn tokenize(text: &str) -> Option<Vec<&str>> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_ascii_alphanumeric() || c == '_' {
+            rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len())
+        } else if rest.starts_with("<<") || rest.starts_with(">>") {
+            2
+        } else if "()+-~*/%&^|".contains(c) {
+            1
+        } else {
+            return None;
+        };
+        tokens.push(&rest[..length]);
+        rest = rest[length..].trim_start();
+    }
+    Some(tokens)
+}
+
+fn binary_op(token: &str) -> Option<BinaryOp> {
+    match token {
+        "|" => Some(BinaryOp::Or),
+        "^" => Some(BinaryOp::Xor),
+        "&" => Some(BinaryOp::And),
+        "<<" => Some(BinaryOp::ShiftLeft),
+        ">>" => Some(BinaryOp::ShiftRight),
+        "+" => Some(BinaryOp::Add),
+        "-" => Some(BinaryOp::Subtract),
+        "*" => Some(BinaryOp::Multiply),
+        "/" => Some(BinaryOp::Divide),
+        "%" => Some(BinaryOp::Remainder),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).copied()
+    }
+
+    fn take(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.next += 1;
+        token
+    }
+
+    /// Operators binding at least as tightly as `min_precedence`, by
+    /// precedence climbing
+    fn binary(&mut self, min_precedence: u8) -> Option<Expr> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek().and_then(binary_op) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.next += 1;
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Some(left)
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        match self.take()? {
+            "-" => Some(Expr::Negate(Box::new(self.unary()?))),
+            "~" => Some(Expr::Not(Box::new(self.unary()?))),
+            "+" => self.unary(),
+            "(" => {
+                let inner = self.binary(0)?;
+                (self.take()? == ")").then_some(inner)
+            }
+            token if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                let value = match token.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => token.parse::<i64>(),
+                };
+                value.ok().map(Expr::Number)
+            }
+            token if token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => {
+                Some(Expr::Name(token.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Expr {
+    /// Parses `text`, or None if it is not an expression
+    pub fn parse(text: &str) -> Option<Expr> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+        };
+        let expr = parser.binary(0)?;
+        (parser.next == parser.tokens.len()).then_some(expr)
+    }
+
+    /// The value of the expression, with `value_of` giving the value of
+    /// each name. Names it gives none for are an error
+    pub fn eval(&self, value_of: &dyn Fn(&str) -> Option<i64>) -> Result<i64, EvalError> {
+        match self {
+            Expr::Number(value) => Ok(*value),
+            Expr::Name(name) => value_of(name).ok_or_else(|| EvalError::Unknown(name.clone())),
+            Expr::Negate(inner) => inner
+                .eval(value_of)?
+                .checked_neg()
+                .ok_or_else(|| EvalError::Overflow("negating it overflows".to_string())),
+            Expr::Not(inner) => Ok(!inner.eval(value_of)?),
+            Expr::Binary(op, left, right) => op
+                .apply(left.eval(value_of)?, right.eval(value_of)?)
+                .map_err(EvalError::Overflow),
+        }
+    }
+
+    /// Every name in the expression, in order
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Expr::Number(_) => vec![],
+            Expr::Name(name) => vec![name],
+            Expr::Negate(inner) | Expr::Not(inner) => inner.names(),
+            Expr::Binary(_, left, right) => {
+                let mut names = left.names();
+                names.extend(right.names());
+                names
+            }
+        }
+    }
+
+    /// The expression with every constant in `constants` replaced by its
+    /// value and every part that no longer has a name in it worked out
+    fn fold(&self, constants: &BTreeMap<String, i64>) -> Result<Expr, EvalError> {
+        let folded = match self {
+            Expr::Name(name) => match constants.get(name) {
+                Some(value) => Expr::Number(*value),
+                None => self.clone(),
+            },
+            Expr::Number(_) => self.clone(),
+            Expr::Negate(inner) => Expr::Negate(Box::new(inner.fold(constants)?)),
+            Expr::Not(inner) => Expr::Not(Box::new(inner.fold(constants)?)),
+            Expr::Binary(op, left, right) => Expr::Binary(
+                *op,
+                Box::new(left.fold(constants)?),
+                Box::new(right.fold(constants)?),
+            ),
+        };
+        if folded.names().is_empty() {
+            Ok(Expr::Number(folded.eval(&|_| None)?))
+        } else {
+            Ok(folded)
+        }
+    }
+
+    /// A label plus or minus a constant, as the label and the constant.
+    /// None for anything else
+    pub fn label_offset(&self) -> Option<(&str, i64)> {
+        let constant = |expr: &Expr| {
+            if expr.names().is_empty() {
+                expr.eval(&|_| None).ok()
+            } else {
+                None
+            }
+        };
+        match self {
+            Expr::Name(name) => Some((name, 0)),
+            Expr::Binary(BinaryOp::Add, left, right) => match (constant(left), constant(right)) {
+                (None, Some(offset)) => left
+                    .label_offset()
+                    .map(|(label, base)| (label, base.wrapping_add(offset))),
+                (Some(offset), None) => right
+                    .label_offset()
+                    .map(|(label, base)| (label, base.wrapping_add(offset))),
+                _ => None,
+            },
+            Expr::Binary(BinaryOp::Subtract, left, right) => {
+                let offset = constant(right)?;
+                left.label_offset()
+                    .map(|(label, base)| (label, base.wrapping_sub(offset)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Written back out as source, with parentheses only where they are needed
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = |expr: &Expr, f: &mut fmt::Formatter| match expr {
+            Expr::Binary(..) => write!(f, "({})", expr),
+            _ => write!(f, "{}", expr),
+        };
+        match self {
+            Expr::Number(value) => write!(f, "{}", value),
+            Expr::Name(name) => write!(f, "{}", name),
+            Expr::Negate(inner) => {
+                write!(f, "-")?;
+                operand(inner, f)
+            }
+            Expr::Not(inner) => {
+                write!(f, "~")?;
+                operand(inner, f)
+            }
+            Expr::Binary(op, left, right) => {
+                operand(left, f)?;
+                write!(f, "{}", op.symbol())?;
+                operand(right, f)
+            }
+        }
+    }
+}
+
+/// Why an expression has no value
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// A name that is neither a constant nor, where one is allowed, a label
+    Unknown(String),
+    /// Arithmetic that overflows or divides by zero
+    Overflow(String),
+}
+
+/// The label `token` names and the constant added to it, for operands
+/// such as `table` or `table+8`. None if it is something else
+pub fn label_offset(token: &Token) -> Option<(String, i64)> {
+    Expr::parse(token.as_str())?
+        .label_offset()
+        .map(|(label, offset)| (label.to_string(), offset))
+}
+
+/// The value of a numeric operand, which may be an expression of numbers
+pub fn evaluate(token: &Token) -> Result<i64, AssemblerError> {
+    let error = |message: String| AssemblerError::InvalidImmediate {
+        location: Location::at(token).into(),
+        token: token.text.clone(),
+        message,
+    };
+    let Some(expr) = Expr::parse(token.as_str()) else {
+        return Err(error(format!(
+            "failed to parse immediate `{}`",
+            token.as_str()
+        )));
+    };
+    expr.eval(&|_| None).map_err(|e| {
+        error(match e {
+            EvalError::Unknown(name) => format!(
+                "expected a number, found `{}`, which is not a constant; define one with `.eqv {}, value`",
+                name, name
+            ),
+            EvalError::Overflow(message) => {
+                format!("cannot work out `{}`: {}", token.as_str(), message)
+            }
+        })
+    })
+}
+
+/// Whether a directive defines a constant. `.set` with one operand sets an
+/// assembler option instead
+pub(crate) fn is_constant_definition(name: &Token, args: &[Token]) -> bool {
+    match name.as_str() {
+        ".eqv" | ".equ" => true,
+        ".set" => args.len() != 1,
+        _ => false,
+    }
+}
+
+/// Defines the constants of every `.eqv` and `.set` and substitutes them
+/// into the operands after it. Constants have to be defined before they
+/// are used, and only those defined with `.set` can be given a new value
+/// later on. The definitions themselves are taken out.
+///
+/// The addresses of the memory map, such as `MMIO_CONSOLE_TX`, are defined
+/// from the start (see [memmap::CONSTANTS]), unless the program has a
+/// label, symbol or constant of its own by that name
+pub fn substitute_constants(sequence: Vec<MipsCST>) -> Result<Vec<MipsCST>, AssemblerError> {
+    let labels: BTreeSet<String> = sequence
+        .iter()
+        .filter_map(|cst| match cst {
+            MipsCST::Label(label) => Some(label.text.clone()),
+            _ => None,
+        })
+        .collect();
+    // Names the program declares or uses as symbols of its own
+    let symbols: BTreeSet<&str> = sequence
+        .iter()
+        .filter_map(|cst| match cst {
+            MipsCST::Directive(name, args) if matches!(name.as_str(), ".globl" | ".extern") => {
+                Some(args.iter().map(Token::as_str))
+            }
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    // The memory map constants the program has not taken the name of yet
+    let mut predefined: BTreeSet<String> = memmap::CONSTANTS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .filter(|name| !labels.contains(name) && !symbols.contains(name.as_str()))
+        .collect();
+    let mut constants: BTreeMap<String, i64> = memmap::CONSTANTS
+        .iter()
+        .filter(|(name, _)| predefined.contains(*name))
+        .map(|(name, value)| (name.to_string(), i64::from(*value)))
+        .collect();
+    // Constants defined with `.eqv`, which keep their value
+    let mut fixed: BTreeSet<String> = BTreeSet::new();
+    let mut substituted = vec![];
+    for cst in sequence {
+        match cst {
+            MipsCST::Directive(name, args) if is_constant_definition(&name, &args) => {
+                if let Some(constant) = args.first() {
+                    if predefined.remove(&constant.text) {
+                        constants.remove(&constant.text);
+                    }
+                }
+                define_constant(&name, &args, &labels, &mut constants, &mut fixed)?
+            }
+            MipsCST::Instruction(mnemonic, args) => substituted.push(MipsCST::Instruction(
+                mnemonic,
+                substitute_args(args, &constants)?,
+            )),
+            MipsCST::Directive(name, args) => {
+                substituted.push(MipsCST::Directive(name, substitute_args(args, &constants)?))
+            }
+            cst => substituted.push(cst),
+        }
+    }
+    Ok(substituted)
+}
+
+fn define_constant(
+    name: &Token,
+    args: &[Token],
+    labels: &BTreeSet<String>,
+    constants: &mut BTreeMap<String, i64>,
+    fixed: &mut BTreeSet<String>,
+) -> Result<(), AssemblerError> {
+    let invalid = |token: &Token, message: String| AssemblerError::InvalidDirective {
+        location: Location::at(token).into(),
+        token: token.text.clone(),
+        message,
+    };
+    let [constant, value] = args else {
+        return Err(AssemblerError::OperandCount {
+            location: Location::at(name).into(),
+            token: name.text.clone(),
+            message: format!("`{}` expects a name and a value", name.as_str()),
+        });
+    };
+    if !matches!(Expr::parse(constant.as_str()), Some(Expr::Name(_))) {
+        return Err(invalid(
+            constant,
+            format!("`{}` is not a name for a constant", constant.as_str()),
+        ));
+    }
+    if labels.contains(&constant.text) {
+        return Err(invalid(
+            constant,
+            format!("`{}` is already a label", constant.as_str()),
+        ));
+    }
+    let redefinable = name.as_str() == ".set";
+    if let Some(previous) = constants
+        .get(&constant.text)
+        .filter(|_| !redefinable || fixed.contains(&constant.text))
+    {
+        return Err(invalid(
+            constant,
+            format!(
+                "`{}` is already defined, as {}",
+                constant.as_str(),
+                previous
+            ),
+        ));
+    }
+    let [value] = substitute_args(vec![value.clone()], constants)?
+        .try_into()
+        .unwrap();
+    if !redefinable {
+        fixed.insert(constant.text.clone());
+    }
+    constants.insert(constant.text.clone(), evaluate(&value)?);
+    Ok(())
+}
+
+/// Substitutes constants into operands. Operands without constants in
+/// them are left as written, to be worked out where they are used, so that
+/// errors show them as they are in the source
+fn substitute_args(
+    args: Vec<Token>,
+    constants: &BTreeMap<String, i64>,
+) -> Result<Vec<Token>, AssemblerError> {
+    args.into_iter()
+        .map(|arg| {
+            let Some(expr) = Expr::parse(arg.as_str()) else {
+                return Ok(arg);
+            };
+            if !expr
+                .names()
+                .iter()
+                .any(|name| constants.contains_key(*name))
+            {
+                return Ok(arg);
+            }
+            let folded = expr.fold(constants).map_err(|e| match e {
+                EvalError::Overflow(message) => AssemblerError::InvalidImmediate {
+                    location: Location::at(&arg).into(),
+                    token: arg.text.clone(),
+                    message: format!("cannot work out `{}`: {}", arg.as_str(), message),
+                },
+                EvalError::Unknown(_) => unreachable!("folding leaves unknown names in"),
+            })?;
+            Ok(Token {
+                text: folded.to_string(),
+                ..arg
+            })
+        })
+        .collect()
+}
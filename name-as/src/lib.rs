@@ -0,0 +1,27 @@
+extern crate pest;
+extern crate pest_derive;
+
+pub mod args;
+pub mod command;
+pub mod config;
+pub mod delay;
+pub mod directive;
+pub mod error;
+pub mod expr;
+pub mod link;
+pub mod lint;
+pub mod listing;
+pub mod log;
+pub mod mars;
+
+pub mod nma;
+pub mod operands;
+pub mod optimize;
+pub mod outline;
+pub mod output;
+pub mod parser;
+pub mod pseudo;
+pub mod summary;
+pub mod usage;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watch;
@@ -0,0 +1,315 @@
+use crate::nma::{
+    AssembledObject, Relocation, RelocationKind, Section, DATA_SECTION, TEXT_SECTION,
+};
+/// Linking: combining relocatable objects, as written by `name-as --format
+/// object`, into one program. Each object's `.text` and `.data` are laid
+/// out one after the other in the order given, labels declared `.extern`
+/// are matched with labels another object exports with `.globl`, and every
+/// relocation is filled in with the final address of its label. Labels that
+/// are not exported are local to their object, so two objects can each
+/// have their own `loop`. The linked program's sections start where the
+/// first object's do
+use crate::pseudo::hi_adjusted;
+use name_core::lineinfo::LineTable;
+use name_core::schema;
+use name_core::symbols::Symbol;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+const OBJECT_KIND: &str = "object";
+
+/// An assembled program as a relocatable `object` document, see
+/// [name_core::schema]
+pub fn object_to_string(assembled: &AssembledObject) -> Result<String, Box<dyn std::error::Error>> {
+    schema::to_json(OBJECT_KIND, assembled)
+}
+
+/// Reads back an object written by [object_to_string]
+pub fn object_from_str(json: &str) -> Result<AssembledObject, Box<dyn std::error::Error>> {
+    schema::from_json(OBJECT_KIND, json)
+}
+
+/// Everything that can stop objects from linking. Objects are named by the
+/// path they were read from
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    /// There was nothing to link
+    NoObjects,
+    /// An object was assembled in a different byte order from the first
+    MixedEndian { object: String },
+    /// Two objects export a label with the same name
+    DuplicateSymbol {
+        symbol: String,
+        first: String,
+        second: String,
+    },
+    /// A label an object refers to is neither its own nor exported by
+    /// another object
+    UndefinedSymbol { symbol: String, object: String },
+    /// A jump to a label outside the 256MB region the jump is in
+    JumpOutOfRange { symbol: String, object: String },
+    /// The requested entry point is not an exported label or an address
+    InvalidEntry { entry: String },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkError::NoObjects => write!(f, "no objects to link"),
+            LinkError::MixedEndian { object } => write!(
+                f,
+                "{} was assembled in a different byte order from the other objects",
+                object
+            ),
+            LinkError::DuplicateSymbol {
+                symbol,
+                first,
+                second,
+            } => write!(
+                f,
+                "`{}` is exported by both {} and {}; only one of them can declare it `.globl`",
+                symbol, first, second
+            ),
+            LinkError::UndefinedSymbol { symbol, object } => write!(
+                f,
+                "{} refers to `{}`, which no object exports; declare it `.globl` where it is defined",
+                object, symbol
+            ),
+            LinkError::JumpOutOfRange { symbol, object } => write!(
+                f,
+                "{} jumps to `{}`, which is linked outside the 256MB region the jump can reach",
+                object, symbol
+            ),
+            LinkError::InvalidEntry { entry } => write!(
+                f,
+                "entry point `{}` is not an exported label or a 0x address",
+                entry
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Where one object ends up in the linked program
+struct Placement {
+    /// Bytes from the start of `.text`
+    text: u32,
+    /// Bytes from the start of `.data`
+    data: u32,
+    /// The addresses the object's `.text` was assembled at, including the
+    /// one just past the end that a label after the last instruction has
+    text_range: RangeInclusive<u32>,
+    /// How far the object's `.text` and `.data` move
+    text_shift: u32,
+    data_shift: u32,
+}
+
+impl Placement {
+    fn new(object: &AssembledObject, text_base: u32, data_base: u32, text: u32, data: u32) -> Self {
+        let text_start = object.text_address();
+        Placement {
+            text,
+            data,
+            text_range: text_start..=text_start + object.text().len() as u32,
+            text_shift: (text_base + text).wrapping_sub(text_start),
+            data_shift: (data_base + data).wrapping_sub(object.data_address()),
+        }
+    }
+
+    /// Moves an address in the object to where it is in the linked program.
+    /// Everything outside `.text` is in `.data`
+    fn relocate(&self, address: u32) -> u32 {
+        if self.text_range.contains(&address) {
+            address.wrapping_add(self.text_shift)
+        } else {
+            address.wrapping_add(self.data_shift)
+        }
+    }
+}
+
+/// Links `objects`, each paired with the path it was read from, into one
+/// program starting at `entry`: a label some object exports or a `0x`
+/// address. Without it, the program starts at `main` if any object
+/// exports it, otherwise at the first instruction
+pub fn link(
+    objects: &[(String, AssembledObject)],
+    entry: Option<&str>,
+) -> Result<AssembledObject, LinkError> {
+    let Some((_, first)) = objects.first() else {
+        return Err(LinkError::NoObjects);
+    };
+    let endian = first.endian;
+    let text_base = first.text_address();
+    let data_base = objects
+        .iter()
+        .find_map(|(_, object)| object.section(DATA_SECTION))
+        .map_or(first.data_address(), |section| section.address);
+
+    let mut placements: Vec<Placement> = vec![];
+    let mut text: Vec<u8> = vec![];
+    let mut data: Vec<u8> = vec![];
+    let mut data_alignment: u32 = 1;
+    for (path, object) in objects {
+        if object.endian != endian {
+            return Err(LinkError::MixedEndian {
+                object: path.clone(),
+            });
+        }
+        let alignment = object.data_alignment.max(1);
+        data_alignment = data_alignment.max(alignment);
+        data.resize((data.len() as u32).next_multiple_of(alignment) as usize, 0);
+        placements.push(Placement::new(
+            object,
+            text_base,
+            data_base,
+            text.len() as u32,
+            data.len() as u32,
+        ));
+        text.extend_from_slice(object.text());
+        data.extend_from_slice(object.data());
+    }
+
+    // Exported labels, with their final address and the object that
+    // exports them
+    let mut exported: BTreeMap<&str, (u32, &str)> = BTreeMap::new();
+    for ((path, object), placement) in objects.iter().zip(&placements) {
+        for symbol in object.symbols.iter().filter(|symbol| symbol.global) {
+            if let Some((_, first)) = exported.get(symbol.name.as_str()) {
+                return Err(LinkError::DuplicateSymbol {
+                    symbol: symbol.name.clone(),
+                    first: first.to_string(),
+                    second: path.clone(),
+                });
+            }
+            exported.insert(&symbol.name, (placement.relocate(symbol.address), path));
+        }
+    }
+
+    let mut relocations: Vec<Relocation> = vec![];
+    for ((path, object), placement) in objects.iter().zip(&placements) {
+        for relocation in &object.relocations {
+            let own = object.symbols.iter().find(|s| s.name == relocation.symbol);
+            let address = match (own, exported.get(relocation.symbol.as_str())) {
+                (Some(symbol), _) => placement.relocate(symbol.address),
+                (None, Some((address, _))) => *address,
+                (None, None) => {
+                    return Err(LinkError::UndefinedSymbol {
+                        symbol: relocation.symbol.clone(),
+                        object: path.clone(),
+                    })
+                }
+            };
+            let address = address.wrapping_add(relocation.addend as u32);
+            if relocation.kind == RelocationKind::Jump26 {
+                let delay_slot = text_base + relocation.offset + placement.text + 4;
+                if (address ^ delay_slot) & 0xf000_0000 != 0 {
+                    return Err(LinkError::JumpOutOfRange {
+                        symbol: relocation.symbol.clone(),
+                        object: path.clone(),
+                    });
+                }
+            }
+            let (section, offset) = if relocation.section == DATA_SECTION {
+                (&mut data, relocation.offset + placement.data)
+            } else {
+                (&mut text, relocation.offset + placement.text)
+            };
+            let at = offset as usize..offset as usize + 4;
+            let word = endian.u32_from_bytes(section[at.clone()].try_into().unwrap());
+            section[at].copy_from_slice(&endian.u32_to_bytes(patch(
+                word,
+                relocation.kind,
+                address,
+            )));
+            relocations.push(Relocation {
+                offset,
+                ..relocation.clone()
+            });
+        }
+    }
+
+    let mut symbols: Vec<Symbol> = objects
+        .iter()
+        .zip(&placements)
+        .flat_map(|((_, object), placement)| {
+            object.symbols.iter().map(|symbol| Symbol {
+                address: placement.relocate(symbol.address),
+                ..symbol.clone()
+            })
+        })
+        .collect();
+    symbols.sort();
+
+    let mut lineinfo = LineTable::default();
+    for ((_, object), placement) in objects.iter().zip(&placements) {
+        let files = lineinfo.files.len() as u32;
+        let pseudo_ops = lineinfo.pseudo_ops.len() as u32;
+        lineinfo.files.extend(object.lineinfo.files.iter().cloned());
+        lineinfo
+            .pseudo_ops
+            .extend(object.lineinfo.pseudo_ops.iter().cloned());
+        lineinfo
+            .lines
+            .extend(object.lineinfo.lines.iter().map(|li| {
+                let mut li = li.clone();
+                li.instr_addr = li.instr_addr.wrapping_add(placement.text_shift);
+                li.file += files;
+                li.pseudo_op = li.pseudo_op.map(|index| index + pseudo_ops);
+                li
+            }));
+    }
+
+    let entry = match entry {
+        Some(entry) => match entry.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => exported.get(entry).map(|(address, _)| *address),
+        }
+        .ok_or_else(|| LinkError::InvalidEntry {
+            entry: entry.to_string(),
+        })?,
+        None => exported
+            .get("main")
+            .map_or(text_base, |(address, _)| *address),
+    };
+
+    let mut sections = vec![Section {
+        name: TEXT_SECTION.to_string(),
+        address: text_base,
+        data: text,
+    }];
+    if !data.is_empty() {
+        sections.push(Section {
+            name: DATA_SECTION.to_string(),
+            address: data_base,
+            data,
+        });
+    }
+
+    Ok(AssembledObject {
+        sections,
+        entry,
+        endian,
+        lineinfo,
+        symbols,
+        relocations,
+        data_alignment,
+        externs: vec![],
+        build: None,
+        warnings: vec![],
+        optimizations: vec![],
+    })
+}
+
+/// Fills the address of a label into the part of `word` that `kind` says
+/// holds it
+fn patch(word: u32, kind: RelocationKind, address: u32) -> u32 {
+    match kind {
+        RelocationKind::Jump26 => (word & 0xfc00_0000) | ((address >> 2) & 0x03ff_ffff),
+        RelocationKind::Hi16 => (word & 0xffff_0000) | (address >> 16),
+        RelocationKind::Hi16Adjusted => (word & 0xffff_0000) | hi_adjusted(address),
+        RelocationKind::Lo16 => (word & 0xffff_0000) | (address & 0xffff),
+        RelocationKind::Word32 => address,
+    }
+}
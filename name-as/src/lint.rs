@@ -0,0 +1,433 @@
+/// Warnings about programs that assemble fine but probably do not do what
+/// was meant, and how each is treated. Every warning has a name, which is
+/// its code in diagnostics and how `-W` flags and the config file refer to
+/// it: `-Wall` turns them all on, `-WNAME` and `-Wno-NAME` turn one on or
+/// off, and `-Werror` or `-Werror=NAME` make warnings errors
+use crate::directive::{directive, Directive};
+use crate::error::{AssemblerError, Location, Warning};
+use crate::expr::{label_offset, Expr};
+use crate::nma::{label_operand, parse_int};
+use crate::parser::{MipsCST, Token};
+use crate::pseudo::memory_label;
+use name_core::register::Register;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// The things name-as can warn about beyond what it needs to assemble
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    /// A label nothing refers to
+    UnusedLabel,
+    /// An instruction straight after a jump, with no label to reach it by
+    UnreachableCode,
+    /// A `.byte` or `.half` value that only fits unsigned, so signed
+    /// loads read it back as a negative number
+    ImmediateTruncation,
+    /// A branch or jump to a label in `.data`
+    BranchToData,
+    /// A program whose last instruction lets execution run past it
+    MissingExit,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedLabel,
+        Lint::UnreachableCode,
+        Lint::ImmediateTruncation,
+        Lint::BranchToData,
+        Lint::MissingExit,
+    ];
+
+    /// The name of the warning, as used by `-W` and as its diagnostic code
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedLabel => "unused-label",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::ImmediateTruncation => "immediate-truncation",
+            Lint::BranchToData => "branch-to-data",
+            Lint::MissingExit => "missing-exit",
+        }
+    }
+
+    /// How the warning is treated when nothing says otherwise. Only the
+    /// ones that are almost always a mistake are on; the rest come up in
+    /// working programs too, so wait for `-Wall`
+    fn default_level(&self) -> Level {
+        match self {
+            Lint::UnreachableCode | Lint::BranchToData => Level::Warn,
+            Lint::UnusedLabel | Lint::ImmediateTruncation | Lint::MissingExit => Level::Allow,
+        }
+    }
+}
+
+impl std::str::FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Lint::ALL.iter().map(Lint::name).collect();
+                format!(
+                    "unknown warning `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// What becomes of a warning
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Left out
+    Allow,
+    /// Reported, and the program still assembles
+    Warn,
+    /// Reported as an error, and the program does not assemble
+    Deny,
+}
+
+/// How each warning is treated, built up from `-W` flags given without
+/// the `-W`: `all`, `error`, `no-error`, `NAME`, `no-NAME` and
+/// `error=NAME`. Warnings no flag mentions keep their default level
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct WarningLevels {
+    levels: BTreeMap<Lint, Level>,
+    /// Whether every warning is an error, as `-Werror` asks, including
+    /// those that are not lints, such as `--pseudo-instructions warn`
+    errors: Option<bool>,
+}
+
+impl WarningLevels {
+    /// Applies one flag. Flags given later take precedence, except that
+    /// `all` leaves alone warnings already set
+    pub fn apply(&mut self, flag: &str) -> Result<(), String> {
+        match flag {
+            "all" => {
+                for lint in Lint::ALL {
+                    self.levels.entry(lint).or_insert(Level::Warn);
+                }
+            }
+            "error" => self.errors = Some(true),
+            "no-error" => self.errors = Some(false),
+            _ => {
+                let (name, level) = if let Some(name) = flag.strip_prefix("error=") {
+                    (name, Level::Deny)
+                } else if let Some(name) = flag.strip_prefix("no-") {
+                    (name, Level::Allow)
+                } else {
+                    (flag, Level::Warn)
+                };
+                self.levels.insert(name.parse()?, level);
+            }
+        }
+        Ok(())
+    }
+
+    /// These levels, with those they leave unset taken from `fallback`,
+    /// so the command line can build on the config file
+    pub fn or(mut self, fallback: WarningLevels) -> WarningLevels {
+        for (lint, level) in fallback.levels {
+            self.levels.entry(lint).or_insert(level);
+        }
+        self.errors = self.errors.or(fallback.errors);
+        self
+    }
+
+    /// How warnings with diagnostic code `code` are treated
+    pub fn level(&self, code: &str) -> Level {
+        let level = match code.parse::<Lint>() {
+            Ok(lint) => self
+                .levels
+                .get(&lint)
+                .copied()
+                .unwrap_or(lint.default_level()),
+            Err(_) => Level::Warn,
+        };
+        match (level, self.errors) {
+            (Level::Warn, Some(true)) => Level::Deny,
+            _ => level,
+        }
+    }
+
+    /// Leaves out the warnings that are allowed, in source order. The
+    /// first one that is an error instead fails the build
+    pub fn sort(&self, mut warnings: Vec<Warning>) -> Result<Vec<Warning>, AssemblerError> {
+        warnings.retain(|Warning(w)| self.level(w.code()) != Level::Allow);
+        warnings.sort_by_key(|Warning(w)| w.location().map(|l| (l.line, l.column)));
+        match warnings
+            .iter()
+            .position(|Warning(w)| self.level(w.code()) == Level::Deny)
+        {
+            Some(index) => Err(warnings.swap_remove(index).0),
+            None => Ok(warnings),
+        }
+    }
+}
+
+impl TryFrom<Vec<String>> for WarningLevels {
+    type Error = String;
+
+    fn try_from(flags: Vec<String>) -> Result<Self, Self::Error> {
+        let mut levels = WarningLevels::default();
+        for flag in &flags {
+            levels.apply(flag)?;
+        }
+        Ok(levels)
+    }
+}
+
+/// What the lints look at: the program as written, once constants are
+/// substituted, and where everything ended up
+pub(crate) struct Program<'a> {
+    pub sequence: &'a [MipsCST],
+    pub labels: &'a BTreeMap<String, u32>,
+    /// The addresses `.data` covers
+    pub data: Range<u32>,
+    /// The label execution starts at, which needs no reference
+    pub entry: &'a str,
+}
+
+/// Every lint that applies to `program`, whatever its level
+pub(crate) fn check(program: &Program) -> Vec<Warning> {
+    let mut found = vec![];
+    found.extend(unused_labels(program));
+    found.extend(unreachable_code(program.sequence));
+    found.extend(immediate_truncation(program.sequence));
+    found.extend(branches_to_data(program));
+    found.extend(missing_exit(program.sequence));
+    found.into_iter().map(Warning).collect()
+}
+
+fn lint_error(
+    lint: Lint,
+    location: Location,
+    token: &Token,
+    message: String,
+    help: Option<String>,
+) -> AssemblerError {
+    AssemblerError::Lint {
+        location: location.into(),
+        token: token.text.clone(),
+        lint,
+        message,
+        help,
+    }
+}
+
+/// The whole statement, from the mnemonic to its last operand
+fn statement(mnemonic: &Token, args: &[Token]) -> Location {
+    Location::spanning(mnemonic, args.last().unwrap_or(mnemonic))
+}
+
+/// Whether execution never goes on to the next instruction
+fn unconditional(mnemonic: &str) -> bool {
+    matches!(mnemonic, "j" | "jr" | "eret")
+}
+
+fn unused_labels(program: &Program) -> Vec<AssemblerError> {
+    let mut used: BTreeSet<String> = BTreeSet::from([program.entry.to_string()]);
+    for sub_cst in program.sequence {
+        if let MipsCST::Instruction(_, args) | MipsCST::Directive(_, args) = sub_cst {
+            for arg in args {
+                if let Some(expr) = Expr::parse(arg.as_str()) {
+                    used.extend(expr.names().into_iter().map(str::to_string));
+                }
+            }
+        }
+    }
+    program
+        .sequence
+        .iter()
+        .filter_map(|sub_cst| match sub_cst {
+            MipsCST::Label(label) if !used.contains(label.as_str()) => Some(lint_error(
+                Lint::UnusedLabel,
+                Location::at(label),
+                label,
+                format!("label `{}` is never used", label.as_str()),
+                None,
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The first instruction of each run that follows a jump with no label in
+/// between. A `nop` there is taken to be filling the delay slot
+fn unreachable_code(sequence: &[MipsCST]) -> Vec<AssemblerError> {
+    let mut found = vec![];
+    let mut after: Option<&Token> = None;
+    for sub_cst in sequence {
+        match sub_cst {
+            MipsCST::Label(_) => after = None,
+            MipsCST::Directive(name, _) => {
+                if let Ok(Directive::Section(_)) = directive(name) {
+                    after = None;
+                }
+            }
+            MipsCST::Instruction(mnemonic, args) => {
+                if let Some(jump) = after {
+                    if mnemonic.as_str() == "nop" {
+                        continue;
+                    }
+                    found.push(lint_error(
+                        Lint::UnreachableCode,
+                        statement(mnemonic, args),
+                        mnemonic,
+                        format!(
+                            "`{}` can never run: it comes straight after the `{}` on line {} with no label to reach it by",
+                            mnemonic.as_str(),
+                            jump.as_str(),
+                            jump.line
+                        ),
+                        Some("add a label if something jumps here, or remove it".to_string()),
+                    ));
+                    after = None;
+                } else if unconditional(mnemonic.as_str()) {
+                    after = Some(mnemonic);
+                }
+            }
+            MipsCST::Sequence(_) => unreachable!(),
+        }
+    }
+    found
+}
+
+fn immediate_truncation(sequence: &[MipsCST]) -> Vec<AssemblerError> {
+    let mut found = vec![];
+    for sub_cst in sequence {
+        let MipsCST::Directive(name, args) = sub_cst else {
+            continue;
+        };
+        let (bits, load, unsigned_load) = match name.as_str() {
+            ".byte" => (8, "lb", "lbu"),
+            ".half" => (16, "lh", "lhu"),
+            _ => continue,
+        };
+        for arg in args {
+            // Anything that does not parse is reported when it is laid out
+            let Ok(value) = parse_int(arg) else {
+                continue;
+            };
+            let signed_max = (1i64 << (bits - 1)) - 1;
+            if !(signed_max + 1..1 << bits).contains(&value) {
+                continue;
+            }
+            found.push(lint_error(
+                Lint::ImmediateTruncation,
+                Location::at(arg),
+                arg,
+                format!(
+                    "`{}` does not fit in a signed {}-bit value, so `{}` reads it back as {}",
+                    arg.as_str(),
+                    bits,
+                    load,
+                    value - (1 << bits)
+                ),
+                Some(format!(
+                    "read it with `{}` to get {}, or write it as {} if the negative number is meant",
+                    unsigned_load,
+                    value,
+                    value - (1 << bits)
+                )),
+            ));
+        }
+    }
+    found
+}
+
+fn branches_to_data(program: &Program) -> Vec<AssemblerError> {
+    let mut found = vec![];
+    for sub_cst in program.sequence {
+        let MipsCST::Instruction(mnemonic, args) = sub_cst else {
+            continue;
+        };
+        // `la` and loads and stores take the address of data on purpose
+        if mnemonic.as_str() == "la" || memory_label(mnemonic.as_str(), args).is_some() {
+            continue;
+        }
+        let Some(target) = label_operand(mnemonic, args) else {
+            continue;
+        };
+        let Some((label, _)) = label_offset(target) else {
+            continue;
+        };
+        if !program
+            .labels
+            .get(&label)
+            .is_some_and(|address| program.data.contains(address))
+        {
+            continue;
+        }
+        found.push(lint_error(
+            Lint::BranchToData,
+            Location::at(target),
+            target,
+            format!(
+                "`{}` goes to `{}`, which labels data in .data rather than instructions",
+                mnemonic.as_str(),
+                label
+            ),
+            None,
+        ));
+    }
+    found
+}
+
+/// Whether `mnemonic args` loads `value` into $v0, as `li $v0, 10` does
+fn loads_v0(mnemonic: &Token, args: &[Token], value: i64) -> bool {
+    let constant = match (mnemonic.as_str(), args) {
+        ("li", [_, imm]) => imm,
+        ("addi" | "addiu" | "ori", [_, rs, imm])
+            if rs.as_str().parse::<Register>() == Ok(Register::Zero) =>
+        {
+            imm
+        }
+        _ => return false,
+    };
+    args[0].as_str().parse::<Register>() == Ok(Register::V0)
+        && parse_int(constant).is_ok_and(|constant| constant == value)
+}
+
+/// The last instruction, unless it jumps away, stops the program or is the
+/// exit syscall. NAME ends a program that runs off the end of `.text`, but
+/// MARS and real hardware carry on into whatever comes next
+fn missing_exit(sequence: &[MipsCST]) -> Vec<AssemblerError> {
+    let instructions: Vec<(&Token, &Vec<Token>)> = sequence
+        .iter()
+        .filter_map(|sub_cst| match sub_cst {
+            MipsCST::Instruction(mnemonic, args) => Some((mnemonic, args)),
+            _ => None,
+        })
+        .collect();
+    let Some((mnemonic, args)) = instructions.last() else {
+        return vec![];
+    };
+    let exits = match mnemonic.as_str() {
+        "break" => true,
+        "syscall" => {
+            instructions.len() >= 2 && {
+                let (previous, previous_args) = instructions[instructions.len() - 2];
+                loads_v0(previous, previous_args, 10) || loads_v0(previous, previous_args, 17)
+            }
+        }
+        other => unconditional(other),
+    };
+    if exits {
+        return vec![];
+    }
+    vec![lint_error(
+        Lint::MissingExit,
+        statement(mnemonic, args),
+        mnemonic,
+        "execution runs on past the last instruction of the program".to_string(),
+        Some(
+            "end it with `li $v0, 10` and `syscall`; NAME stops here, but MARS and real hardware run on into whatever comes next"
+                .to_string(),
+        ),
+    )]
+}
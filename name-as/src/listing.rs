@@ -0,0 +1,77 @@
+/// Assembler listings: the source annotated with the section, address and
+/// machine word of everything it assembled into, like `as -al`
+use crate::nma::{AssembledObject, MIPS_INSTR_BYTE_WIDTH};
+use name_core::lineinfo::{LineInfo, PseudoOp};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Width of the section, address and word columns, so that lines without
+/// code still line up with the source of those that have it
+const CODE_COLUMNS: usize = 23;
+
+/// Renders a listing of `source`, which `assembled` was assembled from.
+/// Every source line is shown once, prefixed with the address and word of
+/// the instruction it assembled into, if any. With `annotate_pseudo`, each
+/// instruction a pseudo-instruction became is marked with a comment naming
+/// it
+pub fn listing(source: &str, assembled: &AssembledObject, annotate_pseudo: bool) -> String {
+    let mut by_line: BTreeMap<u32, Vec<&LineInfo>> = BTreeMap::new();
+    for li in &assembled.lineinfo.lines {
+        by_line.entry(li.span.line).or_default().push(li);
+    }
+
+    let mut out = String::new();
+    for (index, text) in source.lines().enumerate() {
+        let line_number = index as u32 + 1;
+        let rows = by_line.get(&line_number).map(Vec::as_slice).unwrap_or(&[]);
+
+        // A pseudo-instruction, or a branch with a nop added after it, is
+        // listed with each of the real instructions it became underneath
+        let expanded = rows.len() > 1 || rows.iter().any(|li| li.pseudo_op.is_some());
+        let code = match rows.first() {
+            Some(first) if !expanded => code_columns(assembled, first),
+            _ => String::new(),
+        };
+        let row = format!("{:>5} {:CODE_COLUMNS$} {}", line_number, code, text);
+        writeln!(out, "{}", row.trim_end()).unwrap();
+        if expanded {
+            for li in rows {
+                let code = code_columns(assembled, li);
+                let mut row = format!("{:>5} {}     {}", "", code, li.line_contents);
+                if let (true, Some(index)) = (annotate_pseudo, li.pseudo_op) {
+                    let pseudo_op = &assembled.lineinfo.pseudo_ops[index as usize];
+                    write!(row, "  # expanded from `{}`", statement(text, pseudo_op)).unwrap();
+                }
+                writeln!(out, "{}", row.trim_end()).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// The section, address and machine word of one assembled instruction
+fn code_columns(assembled: &AssembledObject, li: &LineInfo) -> String {
+    let offset = (li.instr_addr - assembled.text_address()) as usize;
+    let bytes = &assembled.text()[offset..offset + MIPS_INSTR_BYTE_WIDTH as usize];
+    let word = assembled.endian.u32_from_bytes(bytes.try_into().unwrap());
+    format!(".text {:08x} {:08x}", li.instr_addr, word)
+}
+
+/// The text of a pseudo-instruction as written on `line`
+fn statement<'a>(line: &'a str, pseudo_op: &'a PseudoOp) -> &'a str {
+    let start = pseudo_op.span.column.saturating_sub(1) as usize;
+    let length = pseudo_op
+        .span
+        .end_column
+        .saturating_sub(pseudo_op.span.column) as usize;
+    match line.char_indices().nth(start) {
+        Some((begin, _)) => {
+            let end = line[begin..]
+                .char_indices()
+                .nth(length)
+                .map_or(line.len(), |(offset, _)| begin + offset);
+            line[begin..end].trim_end()
+        }
+        None => pseudo_op.text.as_str(),
+    }
+}
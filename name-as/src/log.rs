@@ -0,0 +1,61 @@
+/// Diagnostic output about what the assembler is doing, written to stderr
+/// and silent unless asked for with `-v` or `-vv`
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much the assembler reports while it works. Each level includes the
+/// ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// Errors only
+    #[default]
+    Quiet,
+    /// The address and encoding of every instruction (`-v`)
+    Encodings,
+    /// Parser output, label addresses and how each field was encoded (`-vv`)
+    Parser,
+}
+
+impl Verbosity {
+    /// The level selected by giving `-v` `count` times
+    pub fn from_count(count: usize) -> Verbosity {
+        match count {
+            0 => Verbosity::Quiet,
+            1 => Verbosity::Encodings,
+            _ => Verbosity::Parser,
+        }
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Quiet as u8);
+
+/// Sets the level for the rest of the process
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are shown
+pub fn enabled(level: Verbosity) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Prints a message at [Verbosity::Encodings]
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Encodings) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a message at [Verbosity::Parser]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Parser) {
+            eprintln!($($arg)*);
+        }
+    };
+}
@@ -1,65 +1,375 @@
-extern crate pest;
-extern crate pest_derive;
-//use name_const::LineInfo;
+use name_as::args::{parse_args, Args, DEFAULT_CONFIG};
+use name_as::config;
+use name_as::error::{AssemblerError, ErrorFormat, Warning};
+use name_as::info;
+use name_as::listing::listing;
+use name_as::log;
+use name_as::nma::{assemble_source, AssembledObject, AssemblerOptions};
+use name_as::pseudo::PseudoPolicy;
+use name_as::summary::Summary;
+use name_as::usage::record_usage;
+use name_as::watch::watch;
+use name_core::buildinfo::BuildInfo;
+use name_core::diagnostic::{Diagnostic, DiagnosticReport, Severity, DIAGNOSTICS_KIND};
+use name_core::lineinfo::lineinfo_export;
+use name_core::schema;
+use name_core::symbols::{symbols_export, symbols_import, Symbol};
+use std::fs;
+use std::path::Path;
 
-pub mod args;
-pub mod config;
+/// Builds an [AssemblerError::Io] for a failed file operation
+fn io_error(path: &str, err: impl std::fmt::Display) -> AssemblerError {
+    AssemblerError::Io {
+        path: path.to_string(),
+        message: err.to_string(),
+    }
+}
 
-pub mod nma;
-pub mod parser;
+/// Reads the symbol files given with --import-symbols, returning their
+/// symbols and their contents. A name may be in several files, but only
+/// at the same address in each
+fn read_imports(paths: &[String]) -> Result<(Vec<Symbol>, Vec<String>), AssemblerError> {
+    let mut imports: Vec<String> = vec![];
+    let mut imported_symbols: Vec<Symbol> = vec![];
+    for import_fn in paths {
+        let contents = fs::read_to_string(import_fn).map_err(|e| io_error(import_fn, e))?;
+        for symbol in symbols_import(&contents).map_err(|e| io_error(import_fn, e))? {
+            if let Some(earlier) = imported_symbols
+                .iter()
+                .find(|earlier| earlier.name == symbol.name && earlier.address != symbol.address)
+            {
+                return Err(io_error(
+                    import_fn,
+                    format!(
+                        "`{}` is at 0x{:08x} here but at 0x{:08x} in an earlier symbol file",
+                        symbol.name, symbol.address, earlier.address
+                    ),
+                ));
+            }
+            imported_symbols.push(symbol);
+        }
+        imports.push(contents);
+    }
+    Ok((imported_symbols, imports))
+}
 
-use args::parse_args;
-use nma::assemble;
-use std::process::Command;
+/// Where the errors and warnings of a build go, as --error-format asks:
+/// straight to stderr, or kept for one JSON document written at the end
+struct Report {
+    format: ErrorFormat,
+    diagnostics: Vec<Diagnostic>,
+}
 
-fn main() -> Result<(), String> {
-    // Parse command line arguments and the config file
-    let cmd_args = parse_args()?;
+impl Report {
+    fn new(format: ErrorFormat) -> Report {
+        Report {
+            format,
+            diagnostics: vec![],
+        }
+    }
 
-    let config: config::Config = match config::parse_config(&cmd_args) {
-        Ok(v) => v,
-        _ => {
-            println!("WARN : Failed to parse config file, defaulting to nma");
-            config::backup_config()
+    fn warning(&mut self, warning: &Warning) {
+        match self.format {
+            ErrorFormat::Human => eprintln!("{}", warning),
+            ErrorFormat::Json => self.diagnostics.push(warning.to_diagnostic()),
+        }
+    }
+
+    /// A warning about the build as a whole rather than a place in the
+    /// source
+    fn build_warning(&mut self, code: &str, message: String) {
+        match self.format {
+            ErrorFormat::Human => eprintln!("warning: {}", message),
+            ErrorFormat::Json => self.diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                code: code.to_string(),
+                message,
+                span: None,
+                notes: vec![],
+                help: None,
+            }),
+        }
+    }
+
+    /// Ends the build with its result. In JSON the document is written
+    /// here if the build succeeded, and is the error if it failed
+    fn finish(mut self, result: Result<(), AssemblerError>) -> Result<(), String> {
+        if self.format == ErrorFormat::Human {
+            return result.map_err(|e| e.to_string());
+        }
+        if let Err(e) = &result {
+            self.diagnostics.push(e.to_diagnostic());
         }
+        let report = DiagnosticReport {
+            diagnostics: self.diagnostics,
+        };
+        let json = schema::to_json(DIAGNOSTICS_KIND, &report).map_err(|e| e.to_string())?;
+        match result {
+            Ok(()) => {
+                eprint!("{}", json);
+                Ok(())
+            }
+            Err(_) => Err(json.trim_end().to_string()),
+        }
+    }
+}
+
+/// Assembles INPUT into OUTPUT, along with any line info, listing and
+/// symbol files asked for
+fn assemble(program_arguments: &Args, report: &mut Report) -> Result<(), AssemblerError> {
+    let input_fn = &program_arguments.input_as;
+    let output_fn = &program_arguments.output_as;
+
+    let file_contents: String = fs::read_to_string(input_fn).map_err(|e| io_error(input_fn, e))?;
+    let (imported_symbols, imports) = read_imports(&program_arguments.import_symbols)?;
+
+    let options = AssemblerOptions {
+        file_name: input_fn.clone(),
+        endian: program_arguments.endian.unwrap_or_default(),
+        entry: None,
+        delay_slots: program_arguments.delay_slots.unwrap_or_default(),
+        pseudo_instructions: program_arguments.pseudo_instructions.unwrap_or_default(),
+        memory_operands: program_arguments.memory_operands.unwrap_or_default(),
+        text_base: program_arguments.text_base,
+        data_base: program_arguments.data_base,
+        imported_symbols,
+        warnings: program_arguments.warnings.clone(),
+        optimize: program_arguments.optimize,
+    };
+    let assembled = assemble_source(&file_contents, &options);
+    if let Some(usage_fn) = &program_arguments.usage_log {
+        record_run(program_arguments, usage_fn, &assembled, report);
+    }
+    let mut assembled = assembled?;
+    assembled.build = Some(BuildInfo::new(
+        "name-as",
+        env!("CARGO_PKG_VERSION"),
+        &options.settings(),
+        &std::iter::once(&file_contents)
+            .chain(&imports)
+            .map(|contents| contents.as_bytes())
+            .collect::<Vec<_>>(),
+        program_arguments.reproducible,
+    ));
+    for warning in &assembled.warnings {
+        report.warning(warning);
+    }
+    if program_arguments.optimize {
+        print_optimizations(input_fn, &assembled);
+    }
+
+    let writer = program_arguments
+        .format
+        .shaped_writer(program_arguments.memory);
+    if !writer.relocatable() {
+        for label in &assembled.externs {
+            report.build_warning(
+                "unlinked-extern",
+                format!(
+                    "`{}` is declared .extern and left at address 0; use --format object and `name link` to fill it in",
+                    label
+                ),
+            );
+        }
+    }
+
+    if !writer.keeps_data() && !assembled.data().is_empty() {
+        report.build_warning(
+            "data-left-out",
+            format!(
+                "the binary format only holds .text, so {} bytes of .data are left out; use --format elf to keep them",
+                assembled.data().len()
+            ),
+        );
+    }
+
+    let mut output: Vec<u8> = vec![];
+    writer
+        .write(&assembled, input_fn, &mut output)
+        .map_err(|e| io_error(output_fn, e))?;
+    fs::write(output_fn, output).map_err(|e| io_error(output_fn, e))?;
+
+    if program_arguments.listing {
+        let listing_fn = format!("{}.lst", output_fn);
+        fs::write(
+            &listing_fn,
+            listing(
+                &file_contents,
+                &assembled,
+                options.pseudo_instructions == PseudoPolicy::Annotate,
+            ),
+        )
+        .map_err(|e| io_error(&listing_fn, e))?;
+    }
+
+    if program_arguments.line_info {
+        let lineinfo_fn = format!("{}.li", output_fn);
+        lineinfo_export(
+            lineinfo_fn.clone(),
+            &assembled.lineinfo,
+            program_arguments.lineinfo_format,
+        )
+        .map_err(|e| io_error(&lineinfo_fn, e))?;
+    }
+
+    if let Some(format) = program_arguments.symbols {
+        let symbols_fn = format!("{}.{}", output_fn, format.extension());
+        symbols_export(symbols_fn.clone(), &assembled.symbols, format)
+            .map_err(|e| io_error(&symbols_fn, e))?;
+    }
+
+    if let Some(format) = program_arguments.summary {
+        let summary = Summary::new(&assembled, program_arguments.regions)
+            .render(format)
+            .map_err(|e| io_error("stdout", e))?;
+        print!("{}", summary);
+    }
+
+    Ok(())
+}
+
+/// Lists what -O left out, then how much that saved
+fn print_optimizations(input_fn: &str, assembled: &AssembledObject) {
+    for optimization in &assembled.optimizations {
+        println!("{}: {}", input_fn, optimization);
+    }
+    let removed: usize = assembled.optimizations.iter().map(|o| o.removed).sum();
+    let remaining = assembled.text().len() / 4;
+    println!(
+        "{}: -O left out {} of {} instructions",
+        input_fn,
+        removed,
+        removed + remaining
+    );
+}
+
+/// Adds the diagnostics of one run to the usage log. The log is only for
+/// the instructor, so a log that cannot be written is reported and the
+/// build goes on
+fn record_run(
+    program_arguments: &Args,
+    usage_fn: &str,
+    assembled: &Result<AssembledObject, AssemblerError>,
+    report: &mut Report,
+) {
+    let assignment = program_arguments.assignment.clone().unwrap_or_else(|| {
+        Path::new(&program_arguments.input_as)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let codes: Vec<&str> = match assembled {
+        Ok(assembled) => assembled.warnings.iter().map(|w| w.0.code()).collect(),
+        Err(e) => vec![e.code()],
     };
+    if let Err(e) = record_usage(Path::new(usage_fn), &assignment, &codes) {
+        report.build_warning(
+            "usage-log",
+            format!("cannot update usage log {}: {}", usage_fn, e),
+        );
+    }
+}
 
+/// Assembles INPUT with NMA, or with the commands in the config file if
+/// it has any
+fn build(config: &config::Config, cmd_args: &Args) -> Result<(), String> {
     if config.as_cmd.is_empty() {
         // If no provided as config, default to NMA
-        assemble(&cmd_args)?;
-    } else {
-        // Otherwise, use provided assembler command
-        println!("Config Name:   {}", config.config_name);
-        println!("Assembler CMD: {:?}", config.as_cmd);
-
-        for full_cmd in &config.as_cmd {
-            let split_cmd: Vec<&str> = full_cmd.split_whitespace().collect();
-
-            match Command::new(split_cmd[0]).args(&split_cmd[1..]).output() {
-                Ok(output) => {
-                    if output.status.success() {
-                        if !&output.stdout.is_empty() {
-                            println!(
-                                "CMD {}\n{}",
-                                full_cmd,
-                                String::from_utf8_lossy(&output.stdout)
-                            );
-                        }
-                    } else if !&output.stderr.is_empty() {
-                        eprintln!(
+        let mut report = Report::new(cmd_args.error_format);
+        let result = assemble(cmd_args, &mut report);
+        return report.finish(result);
+    }
+
+    // Otherwise, use provided assembler command
+    info!("Config Name:   {}", config.config_name);
+    info!("Assembler CMD: {:?}", config.as_cmd);
+
+    for full_cmd in &config.as_cmd {
+        let mut command = full_cmd.to_command(&cmd_args.input_as, &cmd_args.output_as)?;
+
+        match command.output() {
+            Ok(output) => {
+                if output.status.success() {
+                    if !&output.stdout.is_empty() {
+                        println!(
                             "CMD {}\n{}",
                             full_cmd,
-                            String::from_utf8_lossy(&output.stderr)
+                            String::from_utf8_lossy(&output.stdout)
                         );
                     }
-                }
-                Err(err) => {
-                    eprintln!("CMD {}\nError: {}", full_cmd, err);
-                    return Err("Failed to run assembler command".to_string());
+                } else if !&output.stderr.is_empty() {
+                    eprintln!(
+                        "CMD {}\n{}",
+                        full_cmd,
+                        String::from_utf8_lossy(&output.stderr)
+                    );
                 }
             }
+            Err(err) => {
+                eprintln!("CMD {}\nError: {}", full_cmd, err);
+                return Err("Failed to run assembler command".to_string());
+            }
         }
     }
+    Ok(())
+}
+
+fn main() -> Result<(), String> {
+    // Parse command line arguments and the config file
+    let mut cmd_args = parse_args()?;
+
+    let config: config::Config = match config::parse_config(&cmd_args) {
+        Ok(v) => v,
+        // A profile that cannot be had would build for the wrong target
+        Err(e) if cmd_args.profile.is_some() => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        // Without a name.toml of its own, a project simply uses NMA
+        Err(_) if cmd_args.config_fn == DEFAULT_CONFIG && !Path::new(DEFAULT_CONFIG).exists() => {
+            config::backup_config()
+        }
+        Err(e) => {
+            eprintln!(
+                "WARN : Failed to parse config file ({}), defaulting to nma",
+                e
+            );
+            config::backup_config()
+        }
+    };
+
+    // The command line takes precedence over the config file
+    cmd_args.endian = cmd_args.endian.or(config.endian);
+    cmd_args.delay_slots = cmd_args.delay_slots.or(config.delay_slots);
+    cmd_args.pseudo_instructions = cmd_args.pseudo_instructions.or(config.pseudo_instructions);
+    cmd_args.memory_operands = cmd_args.memory_operands.or(config.memory_operands);
+    cmd_args.text_base = cmd_args.text_base.or(config.text_base);
+    cmd_args.data_base = cmd_args.data_base.or(config.data_base);
+    cmd_args.warnings = cmd_args.warnings.clone().or(config.warnings.clone());
+    if let Some(size) = config.text_size {
+        cmd_args.regions.text = size;
+    }
+    if let Some(size) = config.data_size {
+        cmd_args.regions.data = size;
+    }
+    log::set_verbosity(cmd_args.verbosity.or(config.verbosity).unwrap_or_default());
+
+    if !cmd_args.watch {
+        if let Err(e) = build(&config, &cmd_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let input = std::path::PathBuf::from(&cmd_args.input_as);
+    watch(&[input], || match build(&config, &cmd_args) {
+        Ok(()) => eprintln!(
+            "assembled {} into {}",
+            cmd_args.input_as, cmd_args.output_as
+        ),
+        Err(e) => eprintln!("{}", e),
+    })?;
 
     Ok(())
 }
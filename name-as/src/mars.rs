@@ -0,0 +1,795 @@
+/// Importing programs written for MARS, the simulator many courses start
+/// with. MARS accepts a few things NAME does not: macros, `.include`,
+/// `.eqv` as plain text substitution, character literals, `value:count`
+/// repetition in data, `.double`, the kernel segments, addresses after
+/// `.text` and `.data`, and `.extern` with a size. [translate_source]
+/// rewrites each of these into source NAME assembles, with a note saying
+/// what was done or why it could not be, and [translate_settings] turns a
+/// MARS settings file into a config file
+use std::collections::BTreeMap;
+
+/// What became of one construct
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteKind {
+    /// Rewritten into something that assembles to the same bytes
+    Translated,
+    /// Rewritten, but it does not behave quite as it does in MARS
+    Approximated,
+    /// Left as it was, so NAME will reject or ignore it
+    Unsupported,
+}
+
+impl NoteKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NoteKind::Translated => "note",
+            NoteKind::Approximated | NoteKind::Unsupported => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportNote {
+    /// The file the construct is in, empty for the file being imported
+    /// and as written in `.include` for others
+    pub file: String,
+    /// The line it is on, counted from 1, or 0 for the whole file
+    pub line: usize,
+    pub kind: NoteKind,
+    pub message: String,
+}
+
+/// A program translated from MARS
+#[derive(Debug, Clone, Default)]
+pub struct Imported {
+    pub source: String,
+    pub notes: Vec<ImportNote>,
+}
+
+/// How deep macros may expand into each other and files include each
+/// other before the import gives up, which only a cycle should reach
+const MAX_DEPTH: usize = 16;
+
+/// A macro defined with `.macro name (%a, %b)` and `.end_macro`
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+/// One line split into its parts. Labels keep the text they were written
+/// with, including the colons
+struct Line<'a> {
+    labels: &'a str,
+    word: &'a str,
+    args: &'a str,
+    comment: &'a str,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Where the comment of `line` starts, past any strings
+fn comment_start(line: &str) -> usize {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return i,
+            _ => (),
+        }
+    }
+    line.len()
+}
+
+fn split_line(line: &str) -> Line<'_> {
+    let (code, comment) = line.split_at(comment_start(line));
+    let mut rest = code;
+    let mut labels_end = 0;
+    loop {
+        let trimmed = rest.trim_start();
+        let name_len = trimmed
+            .find(|c: char| !is_name_char(c))
+            .unwrap_or(trimmed.len());
+        if name_len == 0 || !trimmed[name_len..].starts_with(':') {
+            break;
+        }
+        labels_end += rest.len() - trimmed.len() + name_len + 1;
+        rest = &code[labels_end..];
+    }
+    let rest = rest.trim();
+    let word_len = rest
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(rest.len());
+    Line {
+        labels: &code[..labels_end],
+        word: &rest[..word_len],
+        args: rest[word_len..].trim(),
+        comment,
+    }
+}
+
+/// A line of output: `code` after any labels, lined up as MARS examples
+/// usually are, and the comment it was written with
+fn statement(labels: &str, code: &str, comment: &str) -> String {
+    let labels = labels.trim();
+    let mut line = format!("{:<8}{}", labels, code);
+    if labels.len() >= 8 {
+        line = format!("{} {}", labels, code);
+    }
+    if !comment.is_empty() {
+        line = format!("{} {}", line, comment);
+    }
+    line
+}
+
+/// Splits operands at the commas outside strings and parentheses
+fn split_args(args: &str) -> Vec<String> {
+    let mut found = vec![];
+    let mut current = String::new();
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for c in args.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                found.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        found.push(current.trim().to_string());
+    }
+    found
+}
+
+/// Replaces `name` wherever it stands as a whole name in `code`, outside
+/// strings and not as part of a register such as `$t0` or a macro
+/// parameter such as `%t0`
+fn replace_name(code: &str, name: &str, with: &str) -> String {
+    let mut replaced = String::new();
+    let mut in_string = false;
+    let mut rest = code;
+    while let Some(c) = rest.chars().next() {
+        if c == '"' {
+            in_string = !in_string;
+        }
+        let previous = replaced.chars().last();
+        let starts_name = !previous.is_some_and(|p| is_name_char(p) || p == '$' || p == '%');
+        if !in_string && starts_name && rest.starts_with(name) {
+            let after = rest[name.len()..].chars().next();
+            if !after.is_some_and(is_name_char) {
+                replaced.push_str(with);
+                rest = &rest[name.len()..];
+                continue;
+            }
+        }
+        replaced.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    replaced
+}
+
+/// The code of a character literal such as `'a'` or `'\n'`
+fn char_code(literal: &str) -> Option<u32> {
+    let inner = literal.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let c = match (chars.next()?, chars.next()) {
+        ('\\', Some(escaped)) => match escaped {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' | '\'' | '"' => escaped,
+            _ => return None,
+        },
+        (c, None) => return Some(c as u32),
+        _ => return None,
+    };
+    chars.next().is_none().then_some(c as u32)
+}
+
+/// `code` with its character literals replaced by their codes, if it has
+/// any. Literals in strings are left alone
+fn replace_char_literals(code: &str) -> Option<String> {
+    let mut replaced = String::new();
+    let mut in_string = false;
+    let mut changed = false;
+    let mut rest = code;
+    while let Some(c) = rest.chars().next() {
+        if c == '"' {
+            in_string = !in_string;
+        }
+        if c == '\'' && !in_string {
+            let length = if rest[1..].starts_with('\\') { 4 } else { 3 };
+            if let Some(value) = rest.get(..length).and_then(char_code) {
+                replaced.push_str(&value.to_string());
+                rest = &rest[length..];
+                changed = true;
+                continue;
+            }
+        }
+        replaced.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    changed.then_some(replaced)
+}
+
+/// Operands with MARS's `value:count` repetition written out in full
+fn repeat_values(args: &[String]) -> Option<Vec<String>> {
+    let mut expanded = vec![];
+    let mut repeated = false;
+    for arg in args {
+        match arg.split_once(':') {
+            Some((value, count)) => {
+                let count: usize = count.trim().parse().ok()?;
+                expanded.extend(std::iter::repeat_n(value.trim().to_string(), count));
+                repeated = true;
+            }
+            None => expanded.push(arg.clone()),
+        }
+    }
+    repeated.then_some(expanded)
+}
+
+/// Whether an `.eqv` value is something NAME can take as a constant, as
+/// opposed to text such as a register that MARS pastes in where the name
+/// is used
+fn is_numeric_value(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| is_name_char(c) || c.is_whitespace() || "+-*/%&|^~<>()".contains(c))
+}
+
+struct Translator<'a> {
+    include: &'a mut dyn FnMut(&str) -> Result<String, String>,
+    notes: Vec<ImportNote>,
+    output: Vec<String>,
+    /// Macros by name and number of parameters, as MARS lets a name be
+    /// reused with a different number
+    macros: BTreeMap<(String, usize), Macro>,
+    /// The macro being read, between `.macro` and `.end_macro`
+    defining: Option<(String, Macro)>,
+    /// `.eqv` names that stand for text rather than a number
+    text_eqvs: Vec<(String, String)>,
+    /// `.text` or `.data`, to go back to after laying out an `.extern`
+    section: &'static str,
+    expansions: usize,
+    depth: usize,
+}
+
+impl Translator<'_> {
+    fn note(&mut self, file: &str, line: usize, kind: NoteKind, message: String) {
+        self.notes.push(ImportNote {
+            file: file.to_string(),
+            line,
+            kind,
+            message,
+        });
+    }
+
+    fn translate_file(&mut self, file: &str, source: &str) {
+        for (index, line) in source.lines().enumerate() {
+            self.translate_line(file, index + 1, line);
+        }
+        if let Some((name, _)) = self.defining.take() {
+            self.note(
+                file,
+                0,
+                NoteKind::Unsupported,
+                format!("macro `{}` has no `.end_macro`, so it was left out", name),
+            );
+        }
+    }
+
+    fn translate_line(&mut self, file: &str, number: usize, line: &str) {
+        if self.defining.is_some() {
+            if split_line(line).word == ".end_macro" {
+                let (name, definition) = self.defining.take().unwrap();
+                self.macros
+                    .insert((name, definition.params.len()), definition);
+            } else if let Some((_, definition)) = &mut self.defining {
+                definition.body.push(line.to_string());
+            }
+            self.output.push(format!("# {}", line));
+            return;
+        }
+
+        let mut text = line.to_string();
+        let parts = split_line(line);
+        if parts.word != ".eqv" {
+            for (name, value) in &self.text_eqvs {
+                let (code, comment) = text.split_at(comment_start(&text));
+                text = format!("{}{}", replace_name(code, name, value), comment);
+            }
+        }
+        let (code, comment) = text.split_at(comment_start(&text));
+        if let Some(replaced) = replace_char_literals(code) {
+            self.note(
+                file,
+                number,
+                NoteKind::Translated,
+                "character literals were replaced by their codes".to_string(),
+            );
+            text = format!("{}{}", replaced, comment);
+        }
+        let parts = split_line(&text);
+        let mut args = split_args(parts.args);
+        // MARS writes `.eqv NAME value` and `.extern label size` without
+        // a comma
+        if matches!(parts.word, ".eqv" | ".extern") && args.len() == 1 {
+            if let Some((name, value)) = args[0].split_once(char::is_whitespace) {
+                args = vec![name.to_string(), value.trim().to_string()];
+            }
+        }
+        let (labels, comment) = (parts.labels, parts.comment);
+
+        match parts.word {
+            ".macro" => self.define_macro(file, number, &parts, line),
+            ".end_macro" => {
+                self.note(
+                    file,
+                    number,
+                    NoteKind::Unsupported,
+                    "`.end_macro` without a `.macro` before it".to_string(),
+                );
+                self.output.push(line.to_string());
+            }
+            ".include" => self.include_file(file, number, &args, line),
+            ".eqv" => match args.as_slice() {
+                [name, value] if !is_numeric_value(value) => {
+                    self.note(
+                        file,
+                        number,
+                        NoteKind::Translated,
+                        format!(
+                            "`{}` is text rather than a number, so it was put in place wherever `{}` is used",
+                            value, name
+                        ),
+                    );
+                    self.text_eqvs.push((name.clone(), value.clone()));
+                    self.output.push(format!("# {}", line));
+                }
+                _ => self.output.push(text.clone()),
+            },
+            ".ktext" | ".kdata" => {
+                let section = if parts.word == ".ktext" {
+                    ".text"
+                } else {
+                    ".data"
+                };
+                self.note(
+                    file,
+                    number,
+                    NoteKind::Approximated,
+                    format!(
+                        "NAME has no kernel segments, so `{}` became `{}`; run with `--handler` to use an exception handler there",
+                        parts.word, section
+                    ),
+                );
+                self.section = section;
+                self.output.push(statement(labels, section, comment));
+            }
+            ".text" | ".data" if !args.is_empty() => {
+                self.section = if parts.word == ".text" {
+                    ".text"
+                } else {
+                    ".data"
+                };
+                self.note(
+                    file,
+                    number,
+                    NoteKind::Translated,
+                    format!(
+                        "`{} {}` became `{}` and `.org {}`",
+                        parts.word, args[0], parts.word, args[0]
+                    ),
+                );
+                self.output.push(statement(labels, parts.word, comment));
+                self.output.push(format!("        .org {}", args[0]));
+            }
+            ".text" => {
+                self.section = ".text";
+                self.output.push(text.clone());
+            }
+            ".data" => {
+                self.section = ".data";
+                self.output.push(text.clone());
+            }
+            ".extern" if args.len() == 2 => {
+                self.note(
+                    file,
+                    number,
+                    NoteKind::Approximated,
+                    format!(
+                        "`.extern {0} {1}` became {1} bytes of `.data` named `{0}`, where MARS puts it at 0x10000000",
+                        args[0], args[1]
+                    ),
+                );
+                self.output.push(statement(labels, ".data", comment));
+                self.output
+                    .push(format!("{}:  .space {}", args[0], args[1]));
+                self.output.push(format!("        {}", self.section));
+            }
+            ".double" => self.double(file, number, labels, &args, comment, &text),
+            ".word" | ".half" | ".byte" | ".float" => match repeat_values(&args) {
+                Some(values) => {
+                    self.note(
+                        file,
+                        number,
+                        NoteKind::Translated,
+                        format!(
+                            "`value:count` repetition written out as {} values",
+                            values.len()
+                        ),
+                    );
+                    let data = format!("{} {}", parts.word, values.join(", "));
+                    self.output.push(statement(labels, &data, comment));
+                }
+                None => self.output.push(text.clone()),
+            },
+            word => {
+                let call_args = match parts.args.strip_prefix('(') {
+                    Some(inner) if parts.args.ends_with(')') => {
+                        split_args(&inner[..inner.len() - 1])
+                    }
+                    _ => args.clone(),
+                };
+                let key = (word.to_string(), call_args.len());
+                match self.macros.get(&key).cloned() {
+                    Some(definition) => {
+                        if !labels.is_empty() {
+                            self.output.push(labels.to_string());
+                        }
+                        self.expand(file, number, &key.0, &definition, &call_args);
+                    }
+                    None if self.macros.keys().any(|(name, _)| name == word) => {
+                        self.note(
+                            file,
+                            number,
+                            NoteKind::Unsupported,
+                            format!("no macro `{}` takes {} operands", word, call_args.len()),
+                        );
+                        self.output.push(text.clone());
+                    }
+                    None => self.output.push(text.clone()),
+                }
+            }
+        }
+    }
+
+    fn define_macro(&mut self, file: &str, number: usize, parts: &Line, line: &str) {
+        let (name, params) = match parts
+            .args
+            .split_once(|c: char| c == '(' || c.is_whitespace())
+        {
+            Some((name, params)) => (name.trim(), params.trim().trim_end_matches(')')),
+            None => (parts.args, ""),
+        };
+        let params: Vec<String> = split_args(params.trim_start_matches('('));
+        if name.is_empty() || params.iter().any(|param| !param.starts_with('%')) {
+            self.note(
+                file,
+                number,
+                NoteKind::Unsupported,
+                "a macro needs a name, and parameters that start with `%`".to_string(),
+            );
+            self.output.push(line.to_string());
+            return;
+        }
+        self.note(
+            file,
+            number,
+            NoteKind::Translated,
+            format!(
+                "macro `{}` is written out in full wherever it is used",
+                name
+            ),
+        );
+        self.output.push(format!("# {}", line));
+        self.defining = Some((
+            name.to_string(),
+            Macro {
+                params,
+                body: vec![],
+            },
+        ));
+    }
+
+    /// Writes out a use of a macro. Labels defined in the body get a
+    /// suffix for each use, as in MARS, so the macro can be used twice
+    fn expand(
+        &mut self,
+        file: &str,
+        number: usize,
+        name: &str,
+        definition: &Macro,
+        args: &[String],
+    ) {
+        if self.depth >= MAX_DEPTH {
+            self.note(
+                file,
+                number,
+                NoteKind::Unsupported,
+                format!("macro `{}` uses itself, so it was not written out", name),
+            );
+            return;
+        }
+        self.expansions += 1;
+        let suffix = format!("_M{}", self.expansions);
+        let labels: Vec<String> = definition
+            .body
+            .iter()
+            .flat_map(|line| {
+                split_line(line)
+                    .labels
+                    .split(':')
+                    .map(|label| label.trim().to_string())
+                    .filter(|label| !label.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        // Longest first, so `%a` does not eat the start of `%ab`
+        let mut params: Vec<(&String, &String)> = definition.params.iter().zip(args).collect();
+        params.sort_by_key(|(param, _)| std::cmp::Reverse(param.len()));
+
+        self.output.push(
+            format!("# {} {}", name, args.join(", "))
+                .trim_end()
+                .to_string(),
+        );
+        self.depth += 1;
+        for line in &definition.body {
+            let mut line = line.clone();
+            for (param, arg) in &params {
+                line = line.replace(param.as_str(), arg);
+            }
+            for label in &labels {
+                line = replace_name(&line, label, &format!("{}{}", label, suffix));
+            }
+            self.translate_line(file, number, &line);
+        }
+        self.depth -= 1;
+    }
+
+    fn include_file(&mut self, file: &str, number: usize, args: &[String], line: &str) {
+        let Some(included) = args
+            .first()
+            .and_then(|arg| arg.strip_prefix('"'))
+            .and_then(|arg| arg.strip_suffix('"'))
+        else {
+            self.note(
+                file,
+                number,
+                NoteKind::Unsupported,
+                "`.include` takes a file name in quotes".to_string(),
+            );
+            self.output.push(line.to_string());
+            return;
+        };
+        if self.depth >= MAX_DEPTH {
+            self.note(
+                file,
+                number,
+                NoteKind::Unsupported,
+                format!("`{}` includes itself, so it was not read again", included),
+            );
+            return;
+        }
+        match (self.include)(included) {
+            Ok(source) => {
+                self.note(
+                    file,
+                    number,
+                    NoteKind::Translated,
+                    format!(
+                        "the contents of `{}` were put in place of `.include`",
+                        included
+                    ),
+                );
+                self.output.push(format!("# {}", line));
+                self.depth += 1;
+                self.translate_file(included, &source);
+                self.depth -= 1;
+            }
+            Err(e) => {
+                self.note(
+                    file,
+                    number,
+                    NoteKind::Unsupported,
+                    format!("`{}` could not be read: {}", included, e),
+                );
+                self.output.push(line.to_string());
+            }
+        }
+    }
+
+    /// `.double`, which NAME does not have, laid out as the two words of
+    /// each value with the low word first, as MARS does in its
+    /// little-endian memory
+    fn double(
+        &mut self,
+        file: &str,
+        number: usize,
+        labels: &str,
+        args: &[String],
+        comment: &str,
+        text: &str,
+    ) {
+        let values = repeat_values(args).unwrap_or_else(|| args.to_vec());
+        let Ok(values) = values
+            .iter()
+            .map(|value| value.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+        else {
+            self.note(
+                file,
+                number,
+                NoteKind::Unsupported,
+                "`.double` takes numbers".to_string(),
+            );
+            self.output.push(text.to_string());
+            return;
+        };
+        let words: Vec<String> = values
+            .iter()
+            .flat_map(|value| {
+                let bits = value.to_bits();
+                [bits as u32, (bits >> 32) as u32]
+            })
+            .map(|word| format!("0x{:08x}", word))
+            .collect();
+        self.note(
+            file,
+            number,
+            NoteKind::Translated,
+            "NAME has no `.double`, so each value was laid out as two words, low word first as in little-endian memory".to_string(),
+        );
+        self.output.push(statement(labels, ".align 3", comment));
+        self.output
+            .push(format!("        .word {}", words.join(", ")));
+    }
+}
+
+/// Translates a program written for MARS into one NAME assembles. Files it
+/// names with `.include` are read with `include` and put in its place
+pub fn translate_source(
+    source: &str,
+    include: &mut dyn FnMut(&str) -> Result<String, String>,
+) -> Imported {
+    let mut translator = Translator {
+        include,
+        notes: vec![],
+        output: vec![],
+        macros: BTreeMap::new(),
+        defining: None,
+        text_eqvs: vec![],
+        section: ".text",
+        expansions: 0,
+        depth: 0,
+    };
+    translator.translate_file("", source);
+    let mut source = translator.output.join("\n");
+    source.push('\n');
+    Imported {
+        source,
+        notes: translator.notes,
+    }
+}
+
+/// Where MARS's memory configurations put `.text` and `.data`
+fn memory_configuration(name: &str) -> Option<(u32, u32)> {
+    match name {
+        "Default" => Some((0x00400000, 0x10010000)),
+        "CompactDataAtZero" => Some((0x00003000, 0x00000000)),
+        "CompactTextAtZero" => Some((0x00000000, 0x00002000)),
+        _ => None,
+    }
+}
+
+/// Translates MARS's settings, in the Java properties form it keeps them
+/// in, into a name.toml giving NAME the same ones where it has them
+pub fn translate_settings(properties: &str) -> (String, Vec<ImportNote>) {
+    let mut config = vec![
+        "config_name = \"Imported from MARS\"".to_string(),
+        "as_cmd = []".to_string(),
+    ];
+    let mut notes = vec![];
+    let mut note = |line: usize, kind: NoteKind, message: String| {
+        notes.push(ImportNote {
+            file: String::new(),
+            line,
+            kind,
+            message,
+        })
+    };
+    let mut ignored = 0;
+    for (index, line) in properties.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(['=', ':']) else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let enabled = value.eq_ignore_ascii_case("true");
+        let number = index + 1;
+        match key {
+            "ExtendedAssembler" if !enabled => {
+                config.push("pseudo_instructions = \"forbid\"".to_string());
+                note(
+                    number,
+                    NoteKind::Translated,
+                    "pseudo-instructions are turned off".to_string(),
+                );
+            }
+            "DelayedBranching" if enabled => note(
+                number,
+                NoteKind::Approximated,
+                "delayed branching is up to the emulator: run with `name run --delay-slots`".to_string(),
+            ),
+            "StartAtMain" if !enabled => note(
+                number,
+                NoteKind::Approximated,
+                "NAME starts at `main` if there is one: run with `--entry 0x00400000` to start at the first instruction".to_string(),
+            ),
+            "WarningsAreErrors" if enabled => {
+                config.push("warnings = [\"error\"]".to_string());
+                note(
+                    number,
+                    NoteKind::Translated,
+                    "warnings are treated as errors".to_string(),
+                );
+            }
+            "MemoryConfiguration" => match memory_configuration(value) {
+                Some((text_base, data_base)) => {
+                    if value != "Default" {
+                        config.push(format!("text_base = 0x{:08x}", text_base));
+                        config.push(format!("data_base = 0x{:08x}", data_base));
+                        note(
+                            number,
+                            NoteKind::Approximated,
+                            format!(
+                                "{} places .text and .data, but the stack and MMIO stay at NAME's usual addresses",
+                                value
+                            ),
+                        );
+                    }
+                }
+                None => note(
+                    number,
+                    NoteKind::Unsupported,
+                    format!("unknown memory configuration `{}`", value),
+                ),
+            },
+            "ExtendedAssembler" | "DelayedBranching" | "StartAtMain" | "WarningsAreErrors" => (),
+            _ => ignored += 1,
+        }
+    }
+    if ignored > 0 {
+        note(
+            0,
+            NoteKind::Translated,
+            format!(
+                "{} editor and display settings have nothing to do with assembling and were left out",
+                ignored
+            ),
+        );
+    }
+    let mut config = config.join("\n");
+    config.push('\n');
+    (config, notes)
+}
@@ -1,595 +1,1559 @@
 /// NAME Mips Assembler
-use crate::args::Args;
+use crate::delay::{fill_delay_slots, DelaySlots};
+use crate::directive::{
+    data_alignment, data_bytes, data_labels, data_type, directive, org_address, set_option,
+    symbol_size, Directive, SectionKind, SetOption,
+};
+use crate::error::{AssemblerError, Location, Warning};
+use crate::expr::{evaluate, label_offset, substitute_constants, Expr};
+use crate::lint::{self, WarningLevels};
+use crate::log::{self, Verbosity};
+use crate::{info, trace};
 //use crate::lineinfo::*;
-use name_const::lineinfo::*;
-use crate::parser::print_cst;
-use std::collections::HashMap;
-use std::fs;
-use std::fs::File;
+use crate::operands::{normalize_memory_operands, MemoryOperands};
+use crate::optimize::{self, Optimization, Plan};
+use crate::parser::{print_cst, Token};
+use crate::pseudo::{
+    expand, expanded_len, is_pseudo, is_pseudo_branch, memory_label, PseudoPolicy,
+};
+use name_core::buildinfo::BuildInfo;
+use name_core::elf::{write_elf, ElfProgram};
+use name_core::endian::Endian;
+use name_core::isa::{self, Class, Format, InstructionInfo, IsaLevel, Op, Operand, Operands};
+use name_core::lineinfo::{LineInfo, LineTable, PseudoOp, Span};
+use name_core::memmap;
+use name_core::register::{FloatRegister, Register};
+use name_core::symbols::{DataType, Symbol};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
 use std::str;
 
-fn mask_u8(n: u8, x: u8) -> Result<u8, &'static str> {
-    let out = n & ((1 << x) - 1);
-    if out != n {
-        Err("Masking error")
-    } else {
-        Ok(out)
-    }
+/// Where `.text` starts unless `.org` or [AssemblerOptions] move it
+pub const TEXT_ADDRESS_BASE: u32 = memmap::TEXT_START;
+/// Where `.data` starts unless `.org` or [AssemblerOptions] move it, as in
+/// MARS and SPIM
+pub const DATA_ADDRESS_BASE: u32 = memmap::DATA_START;
+pub(crate) const MIPS_INSTR_BYTE_WIDTH: u32 = 4;
+
+/// How the 16-bit immediate of an I-type instruction is extended
+enum ImmKind {
+    /// Sign-extended, -32768 to 32767
+    Signed,
+    /// Zero-extended, 0 to 65535
+    Unsigned,
 }
 
-fn mask_u32(n: u32, x: u8) -> Result<u32, &'static str> {
-    let out = n & ((1 << x) - 1);
-    if out != n {
-        Err("Masking error")
-    } else {
-        Ok(out)
+impl ImmKind {
+    fn range(&self) -> std::ops::RangeInclusive<i64> {
+        match self {
+            ImmKind::Signed => i64::from(i16::MIN)..=i64::from(i16::MAX),
+            ImmKind::Unsigned => 0..=i64::from(u16::MAX),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            ImmKind::Signed => "a signed",
+            ImmKind::Unsigned => "an unsigned",
+        }
     }
 }
 
-const TEXT_ADDRESS_BASE: u32 = 0x400000;
-const MIPS_INSTR_BYTE_WIDTH: u32 = 4;
+/// `nop`, which is `sll $zero, $zero, 0` written without operands
+const NOP: InstructionInfo = InstructionInfo {
+    mnemonic: "nop",
+    op: Op::Sll,
+    format: Format::R,
+    level: IsaLevel::Mips1,
+    operands: Operands::Empty,
+    class: Class::Alu,
+    sample: 0,
+};
+
+/// Looks up a machine instruction in the shared ISA table, see
+/// [name_core::isa]
+pub fn operation(mnemonic: &str) -> Option<&'static InstructionInfo> {
+    match mnemonic {
+        "nop" => Some(&NOP),
+        _ => isa::instruction(mnemonic),
+    }
+}
 
-/// The form of an R-type instruction, specificially
-/// which arguments it expects in which order
-enum RForm {
-    RdRsRt,
-    RdRtShamt,
+/// Whether `mnemonic` is a machine instruction the assembler can encode,
+/// as opposed to a pseudo-instruction or an unknown mnemonic
+pub fn is_instruction(mnemonic: &str) -> bool {
+    operation(mnemonic).is_some()
 }
 
-/// The variable components of an R-type instruction
-pub struct R {
-    shamt: u8,
-    funct: u8,
-    form: RForm,
+/// Write a u32 into a file in the given byte order
+pub fn write_u32(mut file: impl Write, data: u32, endian: Endian) -> std::io::Result<()> {
+    file.write_all(&endian.u32_to_bytes(data))
 }
 
-/// The form of an I-type instruction, specifically
-/// which arguments it expects in which order
-enum IForm {
-    RtImm,
-    RtImmRs,
-    RtRsImm,
-    RsRtLabel,
+/// Given a register name or `$n` number, assemble it into its integer representation
+fn assemble_reg(token: &Token) -> Result<u8, AssemblerError> {
+    match token.as_str().parse::<Register>() {
+        Ok(register) => Ok(register.number() as u8),
+        Err(e) => Err(AssemblerError::InvalidRegister {
+            location: Location::at(token).into(),
+            token: token.text.clone(),
+            message: e.describe(token.as_str()),
+        }),
+    }
 }
 
-/// The variable components of an I-type instruction
-pub struct I {
-    opcode: u8,
-    form: IForm,
+/// Given a `$fn` register, assemble it into its integer representation
+fn assemble_freg(token: &Token) -> Result<u8, AssemblerError> {
+    match token.as_str().parse::<FloatRegister>() {
+        Ok(register) => Ok(register.number() as u8),
+        Err(e) => Err(AssemblerError::InvalidRegister {
+            location: Location::at(token).into(),
+            token: token.text.clone(),
+            message: e.describe(token.as_str()),
+        }),
+    }
 }
 
-/// The variable component of a J-type instruction
-pub struct J {
-    opcode: u8,
+/// Describes what kind of value an operand slot accepts
+fn describe_operand(operand: &str) -> &'static str {
+    if operand.starts_with("$f") {
+        "a floating-point register"
+    } else if operand.starts_with('$') {
+        "a register"
+    } else if operand == "label" {
+        "a label"
+    } else {
+        "an immediate"
+    }
 }
 
-/// Parses an R-type instruction mnemonic into an [R]
-pub fn r_operation(mnemonic: &str) -> Result<R, &'static str> {
-    match mnemonic {
-        "add" => Ok(R {
-            shamt: 0,
-            funct: 0x20,
-            form: RForm::RdRsRt,
-        }),
-        "sub" => Ok(R {
-            shamt: 0,
-            funct: 0x22,
-            form: RForm::RdRsRt,
-        }),
-        "sll" => Ok(R {
-            shamt: 0,
-            funct: 0x00,
-            form: RForm::RdRtShamt,
-        }),
-        "srl" => Ok(R {
-            shamt: 0,
-            funct: 0x02,
-            form: RForm::RdRtShamt,
-        }),
-        "xor" => Ok(R {
-            shamt: 0,
-            funct: 0x26,
-            form: RForm::RdRsRt,
-        }),
-        _ => Err("Failed to match R-instr mnemonic"),
+/// Checks that `args` supply exactly the operands listed in an instruction's
+/// signature, with registers where registers are expected and vice versa
+pub fn check_operands(
+    mnemonic: &Token,
+    args: &[Token],
+    operands: &[&str],
+    signature: &str,
+) -> Result<(), AssemblerError> {
+    let usage = if signature.is_empty() {
+        format!("`{}`", mnemonic.as_str())
+    } else {
+        format!("`{} {}`", mnemonic.as_str(), signature)
+    };
+
+    if args.len() > operands.len() {
+        // Underline every operand past the end of the signature
+        let extra = &args[operands.len()..];
+        return Err(AssemblerError::OperandCount {
+            location: Location::spanning(&extra[0], &extra[extra.len() - 1]).into(),
+            token: extra[0].text.clone(),
+            message: format!(
+                "`{}` takes {} operand(s) but {} were provided, expected {}",
+                mnemonic.as_str(),
+                operands.len(),
+                args.len(),
+                usage
+            ),
+        });
+    }
+
+    if args.len() < operands.len() {
+        // Point just after the last thing that was written
+        let last = args.last().unwrap_or(mnemonic);
+        return Err(AssemblerError::OperandCount {
+            location: Location::after(last).into(),
+            token: last.text.clone(),
+            message: format!(
+                "`{}` is missing operand(s) {}, expected {}",
+                mnemonic.as_str(),
+                operands[args.len()..].join(", "),
+                usage
+            ),
+        });
     }
+
+    for (operand, arg) in operands.iter().zip(args) {
+        if operand.starts_with('$') != arg.as_str().starts_with('$') {
+            return Err(AssemblerError::OperandType {
+                location: Location::at(arg).into(),
+                token: arg.text.clone(),
+                message: format!(
+                    "expected {} for `{}` but found `{}`, expected {}",
+                    describe_operand(operand),
+                    operand,
+                    arg.as_str(),
+                    usage
+                ),
+            });
+        }
+    }
+
+    Ok(())
 }
 
-/// Parses an I-type instruction mnemonic into an [I]
-pub fn i_operation(mnemonic: &str) -> Result<I, &'static str> {
-    match mnemonic {
-        "ori" => Ok(I {
-            opcode: 0xd,
-            form: IForm::RtRsImm,
-        }),
-        "lb" => Ok(I {
-            opcode: 0x20,
-            form: IForm::RtImmRs,
-        }),
-        "lbu" => Ok(I {
-            opcode: 0x24,
-            form: IForm::RtImmRs,
-        }),
-        "lh" => Ok(I {
-            opcode: 0x21,
-            form: IForm::RtImmRs,
-        }),
-        "lhu" => Ok(I {
-            opcode: 0x25,
-            form: IForm::RtImmRs,
-        }),
-        "lw" => Ok(I {
-            opcode: 0x23,
-            form: IForm::RtImmRs,
-        }),
-        "ll" => Ok(I {
-            opcode: 0x30,
-            form: IForm::RtImmRs,
-        }),
-        "lui" => Ok(I {
-            opcode: 0xf,
-            form: IForm::RtImm,
-        }),
-        "sb" => Ok(I {
-            opcode: 0x28,
-            form: IForm::RtImmRs,
-        }),
-        "sh" => Ok(I {
-            opcode: 0x29,
-            form: IForm::RtImmRs,
-        }),
-        "sw" => Ok(I {
-            opcode: 0x2b,
-            form: IForm::RtImmRs,
-        }),
-        "sc" => Ok(I {
-            opcode: 0x38,
-            form: IForm::RtImmRs,
-        }),
-        "beq" => Ok(I {
-            opcode: 0x4,
-            form: IForm::RsRtLabel,
-        }),
-        "bne" => Ok(I {
-            opcode: 0x5,
-            form: IForm::RsRtLabel,
-        }),
-        _ => Err("Failed to match I-instr mnemonic"),
+/// Parses the shift amount of a shift instruction, which must fit in the
+/// 5-bit shamt field
+fn parse_shamt(token: &Token) -> Result<u8, AssemblerError> {
+    let value = parse_int(token)?;
+    if (0..=31).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(AssemblerError::ShiftAmountOutOfRange {
+            location: Location::at(token).into(),
+            token: token.text.clone(),
+        })
     }
 }
 
-/// Parses a J-type instruction mnemonic into a [J]
-fn j_operation(mnemonic: &str) -> Result<J, &'static str> {
+/// Parses a decimal or `0x`-prefixed hexadecimal integer, or an
+/// expression of them, see [crate::expr]
+pub fn parse_int(token: &Token) -> Result<i64, AssemblerError> {
+    evaluate(token)
+}
+
+/// The R-type instruction that does the same thing as an immediate
+/// arithmetic or logical instruction, taking the constant in a register
+fn register_form(mnemonic: &str) -> Option<&'static str> {
     match mnemonic {
-        "j" => Ok(J { opcode: 0x2 }),
-        "jal" => Ok(J { opcode: 0x3 }),
-        _ => Err("Failed to match J-instr mnemonic"),
+        "addi" => Some("add"),
+        "addiu" => Some("addu"),
+        "slti" => Some("slt"),
+        "sltiu" => Some("sltu"),
+        "andi" => Some("and"),
+        "ori" => Some("or"),
+        "xori" => Some("xor"),
+        _ => None,
     }
 }
 
-/// Write a u32 into a file, zero-padded to 32 bits (4 bytes)
-pub fn write_u32(mut file: &File, data: u32) -> std::io::Result<()> {
-    fn convert_endianness(input: u32) -> u32 {
-        ((input & 0x000000FF) << 24)
-            | ((input & 0x0000FF00) << 8)
-            | ((input & 0x00FF0000) >> 8)
-            | ((input & 0xFF000000) >> 24)
+/// Parses the immediate operand of an I-type instruction, checking that it
+/// fits in the 16-bit field once sign- or zero-extended. Out of range
+/// values are an error, with a suggestion to go through `li` instead
+fn encode_imm(
+    info: &InstructionInfo,
+    kind: ImmKind,
+    mnemonic: &Token,
+    args: &[Token],
+    imm: &Token,
+) -> Result<u16, AssemblerError> {
+    let value = parse_int(imm)?;
+    let range = kind.range();
+    if range.contains(&value) {
+        return Ok(value as u16);
     }
 
-    const PADDED_LENGTH: usize = 4;
+    let help = match (info.operands, register_form(mnemonic.as_str())) {
+        (Operands::RtRsImm | Operands::RtRsUimm, Some(r_mnemonic)) => format!(
+            "load the constant into a register first: `li $at, {}` then `{} {}, {}, $at`",
+            imm.as_str(),
+            r_mnemonic,
+            args[0].as_str(),
+            args[1].as_str()
+        ),
+        (operands, _) if operands.is_memory() => format!(
+            "compute the address first: `li $at, {}` and `addu $at, $at, {}`, then use `0($at)`",
+            imm.as_str(),
+            args[2].as_str()
+        ),
+        _ => format!(
+            "use `li {}, {}` to load a full 32-bit constant",
+            args[0].as_str(),
+            imm.as_str()
+        ),
+    };
 
-    // Create a 4-length buffer
-    let mut padded_buffer: [u8; PADDED_LENGTH] = [0; PADDED_LENGTH];
+    Err(AssemblerError::ImmediateOutOfRange {
+        location: Location::at(imm).into(),
+        token: imm.text.clone(),
+        message: format!(
+            "immediate `{}` is out of range for `{}`, which takes {} 16-bit value ({} to {})",
+            imm.as_str(),
+            mnemonic.as_str(),
+            kind.describe(),
+            range.start(),
+            range.end()
+        ),
+        help,
+    })
+}
 
-    // Convert data into bytes
-    let bytes: [u8; PADDED_LENGTH] = (convert_endianness(data)).to_be_bytes();
+/// The address `bytes` on from `here`, where `token` lays them out. Fails
+/// if they run past the end of memory
+fn advance(here: u32, bytes: u32, token: &Token) -> Result<u32, AssemblerError> {
+    here.checked_add(bytes)
+        .ok_or_else(|| AssemblerError::AddressOverflow {
+            location: Location::at(token).into(),
+            token: token.text.clone(),
+            message: format!(
+                "`{}` at 0x{:08x} runs past the end of memory",
+                token.as_str(),
+                here
+            ),
+        })
+}
 
-    // Copy bytes into buffer at offset s.t. value is left-padded with 0s
-    let copy_index = PADDED_LENGTH - bytes.len();
-    padded_buffer[copy_index..].copy_from_slice(&bytes);
+/// Encodes the offset from a branch at `instr_address` to its target label:
+/// the signed number of instructions from the delay slot after the branch,
+/// which is where the processor counts from, so it can reach 32768
+/// instructions back and 32767 forward
+fn branch_offset(
+    labels: &BTreeMap<String, u32>,
+    token: &Token,
+    instr_address: u32,
+) -> Result<u16, AssemblerError> {
+    let target = label_address(labels, token)?;
+    let delay_slot = instr_address.wrapping_add(MIPS_INSTR_BYTE_WIDTH);
+    let distance = target.wrapping_sub(delay_slot) as i32;
+    let overflow = |message: String| AssemblerError::FieldOverflow {
+        location: Location::at(token).into(),
+        token: token.text.clone(),
+        message,
+    };
+    if distance % MIPS_INSTR_BYTE_WIDTH as i32 != 0 {
+        return Err(overflow(format!(
+            "branch target `{}` at 0x{:08x} is not on an instruction boundary",
+            token.as_str(),
+            target
+        )));
+    }
+    match i16::try_from(distance / MIPS_INSTR_BYTE_WIDTH as i32) {
+        Ok(offset) => Ok(offset as u16),
+        Err(_) => Err(overflow(format!(
+            "`{}` at 0x{:08x} is out of reach of the branch at 0x{:08x}, which reaches 32768 instructions back and 32767 forward; branch around a `j` to it instead",
+            token.as_str(),
+            target,
+            instr_address
+        ))),
+    }
+}
 
-    // Write to file
-    file.write_all(&padded_buffer)
+/// Looks up the address of a label operand, which may add a number to the
+/// label or take one from it
+pub fn label_address(labels: &BTreeMap<String, u32>, token: &Token) -> Result<u32, AssemblerError> {
+    let Some((label, offset)) = label_offset(token) else {
+        if Expr::parse(token.as_str()).is_some_and(|expr| !expr.names().is_empty()) {
+            return Err(AssemblerError::OperandType {
+                location: Location::at(token).into(),
+                token: token.text.clone(),
+                message: format!(
+                    "expected a label, or a label plus or minus a number, found `{}`",
+                    token.as_str()
+                ),
+            });
+        }
+        return Err(AssemblerError::UndeclaredLabel {
+            token: token.text.clone(),
+            references: vec![Location::at(token)],
+            suggestion: similar_label(labels, token.as_str()),
+        });
+    };
+    match labels.get(&label) {
+        Some(v) => Ok(v.wrapping_add(offset as u32)),
+        None => Err(AssemblerError::UndeclaredLabel {
+            references: vec![Location::at(token)],
+            suggestion: similar_label(labels, &label),
+            token: label,
+        }),
+    }
 }
 
-/// Converts a numbered mnemonic ($t0, $s8, etc) or literal (55, 67, etc) to its integer representation
-fn reg_number(mnemonic: &str) -> Result<u8, &'static str> {
-    if mnemonic.len() != 3 {
-        return Err("Mnemonic out of bounds");
+/// Whether `mnemonic` is a branch or jump, and so has a delay slot
+pub(crate) fn is_branch(mnemonic: &str) -> bool {
+    operation(mnemonic).is_some_and(|info| matches!(info.class, Class::Branch | Class::Jump))
+}
+
+/// Whether `mnemonic` is a load or store, taking an `imm($rs)` operand
+pub(crate) fn is_memory_access(mnemonic: &str) -> bool {
+    operation(mnemonic).is_some_and(|info| info.operands.is_memory())
+}
+
+/// Whether `mnemonic` is `j` or `jal`, whose target is an absolute address
+fn is_jump(mnemonic: &str) -> bool {
+    operation(mnemonic).is_some_and(|info| info.operands == Operands::Target)
+}
+
+/// The operand of an instruction that names a branch or jump target, if any
+pub(crate) fn label_operand<'a>(mnemonic: &Token, args: &'a [Token]) -> Option<&'a Token> {
+    let target = operation(mnemonic.as_str()).and_then(|info| {
+        info.operands
+            .list()
+            .iter()
+            .position(|operand| matches!(operand, Operand::Label | Operand::Target))
+    });
+    if let Some(position) = target {
+        args.get(position)
+    } else if mnemonic.as_str() == "la" {
+        args.get(1)
+    } else if is_pseudo_branch(mnemonic.as_str()) {
+        args.last()
+    } else {
+        memory_label(mnemonic.as_str(), args)
     }
+}
 
-    match mnemonic.chars().nth(2) {
-        Some(c) => match c.to_digit(10) {
-            Some(digit) => {
-                if digit <= 31 {
-                    Ok(digit as u8)
-                } else {
-                    Err("Expected u8")
-                }
+/// The relocations needed by the instruction, or the instructions a
+/// pseudo-instruction expands into, at `offset` in .text. Branches are
+/// relative to the pc, so they need none
+fn relocations_for(mnemonic: &Token, args: &[Token], offset: u32) -> Vec<Relocation> {
+    let relocation = |offset, symbol: &Token, kind| {
+        let (symbol, addend) = label_offset(symbol).unwrap_or((symbol.text.clone(), 0));
+        Relocation {
+            section: TEXT_SECTION.to_string(),
+            offset,
+            symbol,
+            kind,
+            addend: addend as i32,
+        }
+    };
+    match (mnemonic.as_str(), args) {
+        ("j" | "jal", [target]) => vec![relocation(offset, target, RelocationKind::Jump26)],
+        ("la", [_, label]) => vec![
+            relocation(offset, label, RelocationKind::Hi16),
+            relocation(offset + MIPS_INSTR_BYTE_WIDTH, label, RelocationKind::Lo16),
+        ],
+        // Loads and stores from a label hold the lower half in their own
+        // offset, after the `addu` of any base register
+        (mnemonic, args) => match memory_label(mnemonic, args) {
+            Some(label) => {
+                let based = args
+                    .get(2)
+                    .is_some_and(|base| base.as_str().parse::<Register>() != Ok(Register::Zero));
+                let last = offset + MIPS_INSTR_BYTE_WIDTH * (1 + based as u32);
+                vec![
+                    relocation(offset, label, RelocationKind::Hi16Adjusted),
+                    relocation(last, label, RelocationKind::Lo16),
+                ]
             }
-            _ => Err("Invalid register index"),
+            None => vec![],
         },
-        _ => Err("Malformed mnemonic"),
-    }
-}
-
-/// Given a register or number, assemble it into its integer representation
-fn assemble_reg(mnemonic: &str) -> Result<u8, &'static str> {
-    // match on everything after $
-    match &mnemonic[1..] {
-        "zero" => Ok(0),
-        "at" => Ok(1),
-        "gp" => Ok(28),
-        "sp" => Ok(29),
-        "fp" => Ok(30),
-        "ra" => Ok(31),
-        _ => {
-            let n = reg_number(mnemonic)?;
-            let reg = match mnemonic.chars().nth(1) {
-                Some('v') => n + 2,
-                Some('a') => n + 4,
-                Some('t') => {
-                    if n <= 7 {
-                        n + 8
-                    } else {
-                        // t8, t9 = 24, 25
-                        // 24 - 8 + n
-                        n + 16
-                    }
-                }
-                Some('s') => n + 16,
-                _ => {
-                    // Catch registers like $0
-                    mnemonic.parse::<u8>().unwrap_or(99)
-                }
-            };
-            if reg <= 31 {
-                Ok(reg)
-            } else {
-                Err("Register out of bounds")
-            }
-        }
     }
 }
 
-/// Enforce a specific length for a given vector
-fn enforce_length(arr: &Vec<&str>, len: usize) -> Result<u32, &'static str> {
-    if arr.len() != len {
-        Err("Failed length enforcement")
-    } else {
-        Ok(0)
+/// The relocations for the labels among the operands of a `.word` whose
+/// first word is at `offset` in .data
+fn word_relocations(args: &[Token], offset: u32) -> Vec<Relocation> {
+    (offset..)
+        .step_by(MIPS_INSTR_BYTE_WIDTH as usize)
+        .zip(args)
+        .filter_map(|(offset, arg)| label_offset(arg).map(|label| (offset, label)))
+        .map(|(offset, (symbol, addend))| Relocation {
+            section: DATA_SECTION.to_string(),
+            offset,
+            symbol,
+            kind: RelocationKind::Word32,
+            addend: addend as i32,
+        })
+        .collect()
+}
+
+/// The source an instruction was written as: from its mnemonic to the end
+/// of the line, leaving out any comment and trailing whitespace
+fn statement_span(source_lines: &[&str], mnemonic: &Token) -> Span {
+    let text = source_lines.get(mnemonic.line - 1).copied().unwrap_or("");
+    let statement = text.split('#').next().unwrap_or("").trim_end();
+    let end_column = (statement.chars().count() + 1).max(mnemonic.column + mnemonic.text.len());
+    Span {
+        line: mnemonic.line as u32,
+        column: mnemonic.column as u32,
+        end_line: mnemonic.line as u32,
+        end_column: end_column as u32,
     }
 }
 
-/// Assembles an R-type instruction
-fn assemble_r(r_struct: R, r_args: Vec<&str>) -> Result<u32, &'static str> {
-    let mut rs: u8;
-    let mut rt: u8;
-    let mut rd: u8;
-    let mut shamt: u8;
+/// Points at a pseudo-instruction for [PseudoPolicy::Forbid] or
+/// [PseudoPolicy::Warn], with the real instructions it expands into as help
+fn pseudo_instruction_error(
+    mnemonic: &Token,
+    args: &[Token],
+    expanded: &[(Token, Vec<Token>)],
+    message: String,
+) -> AssemblerError {
+    let written = |(mnemonic, args): &(Token, Vec<Token>)| {
+        let operands: Vec<&str> = args.iter().map(Token::as_str).collect();
+        format!("{} {}", mnemonic.as_str(), operands.join(", "))
+    };
+    let instructions: Vec<String> = expanded.iter().map(written).collect();
+    AssemblerError::PseudoInstruction {
+        location: Location::spanning(mnemonic, args.last().unwrap_or(mnemonic)).into(),
+        token: mnemonic.text.clone(),
+        message,
+        help: format!("it expands into `{}`", instructions.join("; ")),
+    }
+}
+
+/// Whether the instructions a pseudo-instruction expands into use $at
+fn uses_at(expanded: &[(Token, Vec<Token>)]) -> bool {
+    expanded
+        .iter()
+        .flat_map(|(_, args)| args)
+        .any(|arg| arg.as_str() == "$at")
+}
 
-    match r_struct.form {
-        RForm::RdRsRt => {
-            enforce_length(&r_args, 3)?;
-            rd = assemble_reg(r_args[0])?;
-            rs = assemble_reg(r_args[1])?;
-            rt = assemble_reg(r_args[2])?;
-            shamt = r_struct.shamt;
+/// Checks the labels named by `.globl` and `.extern`. Returns the labels to
+/// export, which must be defined here, and the ones left for the linker,
+/// which must not be
+fn symbol_visibility(
+    globals: &[&Token],
+    externs: &[&Token],
+    labels: &BTreeMap<String, u32>,
+    label_sites: &BTreeMap<&str, &Token>,
+) -> Result<(BTreeSet<String>, Vec<String>), AssemblerError> {
+    let mut unresolved: Vec<String> = vec![];
+    for label in externs {
+        if let Some(definition) = label_sites.get(label.as_str()) {
+            return Err(AssemblerError::SymbolVisibility {
+                location: Location::at(definition).into(),
+                token: label.text.clone(),
+                message: format!(
+                    "label `{}` is defined here, but was declared `.extern` on line {}",
+                    label.as_str(),
+                    label.line
+                ),
+                previous: Some(Location::at(label).into()),
+                help: "`.extern` is for labels defined in another file; remove it to use this definition".to_string(),
+            });
         }
-        RForm::RdRtShamt => {
-            enforce_length(&r_args, 3)?;
-            rd = assemble_reg(r_args[0])?;
-            rs = 0;
-            rt = assemble_reg(r_args[1])?;
-            shamt = match r_args[2].parse::<u8>() {
-                Ok(v) => v,
-                Err(_) => return Err("Failed to parse shamt"),
-            }
+        if !unresolved.contains(&label.text) {
+            unresolved.push(label.text.clone());
         }
-    };
+    }
 
-    let mut funct = r_struct.funct;
+    let mut exported: BTreeSet<String> = BTreeSet::new();
+    for label in globals {
+        if !labels.contains_key(label.as_str()) {
+            let help = match similar_label(labels, label.as_str()) {
+                Some(suggestion) => format!("a label with a similar name exists: `{}`", suggestion),
+                None => "a label can only be exported from the file that defines it".to_string(),
+            };
+            return Err(AssemblerError::SymbolVisibility {
+                location: Location::at(label).into(),
+                token: label.text.clone(),
+                message: format!(
+                    "`.globl` exports `{}`, which is not defined in this file",
+                    label.as_str()
+                ),
+                previous: None,
+                help,
+            });
+        }
+        exported.insert(label.text.clone());
+    }
+    Ok((exported, unresolved))
+}
+
+/// Adds the symbols of another build to `labels`, leaving out any the
+/// source defines itself, and returns the names added
+fn import_symbols(labels: &mut BTreeMap<String, u32>, symbols: &[Symbol]) -> BTreeSet<String> {
+    let mut imported = BTreeSet::new();
+    for symbol in symbols {
+        if !labels.contains_key(&symbol.name) {
+            labels.insert(symbol.name.clone(), symbol.address);
+            imported.insert(symbol.name.clone());
+        }
+    }
+    imported
+}
 
-    // Mask
-    rs = mask_u8(rs, 5)?;
-    rt &= mask_u8(rt, 5)?;
-    rd &= mask_u8(rd, 5)?;
-    shamt &= mask_u8(shamt, 5)?;
-    funct &= mask_u8(funct, 6)?;
+/// Gives the labels waiting for something to name the address `address`
+fn define_labels(labels: &mut BTreeMap<String, u32>, pending: &mut Vec<&Token>, address: u32) {
+    for label in pending.drain(..) {
+        trace!("Inserting label {} at {:x}", label.as_str(), address);
+        labels.insert(label.text.clone(), address);
+    }
+}
+
+/// The size of each label for the symbol table. A data label covers the
+/// bytes up to the next data label or the end of `.data`, unless `.size`
+/// says otherwise; code labels only have the sizes `.size` gives them
+fn symbol_sizes(
+    labels: &BTreeMap<String, u32>,
+    data: std::ops::RangeInclusive<u32>,
+    declared: &[(&Token, u32)],
+) -> Result<BTreeMap<String, u32>, AssemblerError> {
+    let data_end = *data.end();
+    let mut starts: Vec<u32> = labels
+        .values()
+        .copied()
+        .filter(|address| data.contains(address))
+        .collect();
+    starts.sort();
+    starts.dedup();
+
+    let mut sizes: BTreeMap<String, u32> = labels
+        .iter()
+        .filter(|(_, address)| data.contains(address))
+        .map(|(name, address)| {
+            let end = starts
+                .iter()
+                .find(|start| *start > address)
+                .copied()
+                .unwrap_or(data_end);
+            (name.clone(), end - address)
+        })
+        .collect();
+    for (label, size) in declared {
+        label_address(labels, label)?;
+        sizes.insert(label.text.clone(), *size);
+    }
+    Ok(sizes)
+}
+
+/// Checks that `token`, an instruction or data directive, is in the
+/// section it belongs in
+fn check_section(
+    section: SectionKind,
+    expected: SectionKind,
+    token: &Token,
+) -> Result<(), AssemblerError> {
+    if section == expected {
+        return Ok(());
+    }
+    let what = match expected {
+        SectionKind::Text => format!("instruction `{}`", token.as_str()),
+        SectionKind::Data => format!("data directive `{}`", token.as_str()),
+    };
+    Err(AssemblerError::WrongSection {
+        location: Location::at(token).into(),
+        token: token.text.clone(),
+        message: format!(
+            "{} in {}, switch sections with `{}` first",
+            what,
+            section.name(),
+            expected.name()
+        ),
+    })
+}
 
-    // opcode : 31 - 26
-    let mut result = 0x000000;
+/// Checks that `.text` and `.data`, wherever their bases and `.org` put
+/// them, do not share any addresses
+fn check_overlap(
+    text: std::ops::Range<u32>,
+    data: std::ops::Range<u32>,
+) -> Result<(), AssemblerError> {
+    if text.is_empty() || data.is_empty() || text.end <= data.start || data.end <= text.start {
+        return Ok(());
+    }
+    Err(AssemblerError::SectionOverlap {
+        token: DATA_SECTION.to_string(),
+        message: format!(
+            ".text at 0x{:08x} to 0x{:08x} overlaps .data at 0x{:08x} to 0x{:08x}; move one of them with `.org`, --text-base or --data-base",
+            text.start,
+            text.end - 1,
+            data.start,
+            data.end - 1
+        ),
+    })
+}
 
-    // rs :     25 - 21
-    println!("rs: {}", rs);
-    result = (result << 6) | u32::from(rs);
+/// Number of single-character insertions, deletions and substitutions
+/// needed to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
 
-    // rt :     20 - 16
-    println!("rt: {}", rt);
-    result = (result << 5) | u32::from(rt);
+/// The declared label closest in spelling to `name`, if any is close enough
+/// to plausibly be a typo
+fn similar_label(labels: &BTreeMap<String, u32>, name: &str) -> Option<String> {
+    let threshold = (name.chars().count() / 3).max(1);
+    labels
+        .keys()
+        .map(|label| (edit_distance(name, label), label))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min()
+        .map(|(_, label)| label.clone())
+}
 
-    // rd :     15 - 11
-    println!("rd: {}", rd);
-    result = (result << 5) | u32::from(rd);
+/// Where execution starts: the `entry` label or `0x` address if one was
+/// requested, otherwise `main` if the program declares it, otherwise the
+/// first instruction. `text` runs from the first instruction to the
+/// address just past the last
+pub fn entry_point(
+    entry: Option<&str>,
+    labels: &BTreeMap<String, u32>,
+    text: std::ops::Range<u32>,
+) -> Result<u32, AssemblerError> {
+    let Some(entry) = entry else {
+        return Ok(labels.get("main").copied().unwrap_or(text.start));
+    };
 
-    // shamt : 10 - 6
-    println!("shamt: {}", shamt);
-    result = (result << 5) | u32::from(shamt);
+    let invalid = |message: String, suggestion: Option<String>| AssemblerError::InvalidEntry {
+        token: entry.to_string(),
+        message,
+        suggestion,
+    };
 
-    // funct : 5 - 0
-    result = (result << 6) | u32::from(funct);
+    let Some(hex) = entry.strip_prefix("0x") else {
+        return labels.get(entry).copied().ok_or_else(|| {
+            invalid(
+                format!("entry point `{}` is not a declared label", entry),
+                similar_label(labels, entry),
+            )
+        });
+    };
 
-    println!(
-        "0x{:0shortwidth$x} {:0width$b}",
-        result,
-        result,
-        shortwidth = 8,
-        width = 32
-    );
-    Ok(result)
+    let address = u32::from_str_radix(hex, 16).map_err(|_| {
+        invalid(
+            format!("entry point `{}` is not a valid address", entry),
+            None,
+        )
+    })?;
+    if !text.contains(&address) || address % MIPS_INSTR_BYTE_WIDTH != 0 {
+        return Err(invalid(
+            format!(
+                "entry point `{}` is not the address of an instruction, expected 0x{:08x} to 0x{:08x}",
+                entry,
+                text.start,
+                text.end.saturating_sub(MIPS_INSTR_BYTE_WIDTH).max(text.start)
+            ),
+            None,
+        ));
+    }
+    Ok(address)
 }
 
-/// Assembles an I-type instruction
-fn assemble_i(
-    i_struct: I,
-    i_args: Vec<&str>,
-    labels: &HashMap<&str, u32>,
-    instr_address: u32,
-) -> Result<u32, &'static str> {
-    let mut rs: u8;
-    let mut rt: u8;
-    let imm: u16;
-
-    match i_struct.form {
-        IForm::RtImm => {
-            enforce_length(&i_args, 2)?;
-            rs = 0;
-            rt = assemble_reg(i_args[0])?;
-            imm = match i_args[1].parse::<u16>() {
-                Ok(v) => v,
-                Err(_) => return Err("Failed to parse imm"),
-            }
-        }
-        IForm::RtImmRs => {
-            enforce_length(&i_args, 3)?;
-            rt = assemble_reg(i_args[0])?;
-            imm = match i_args[1].parse::<u16>() {
-                Ok(v) => v,
-                Err(_) => return Err("Failed to parse imm"),
+/// Checks every branch and jump target, and every label laid out by
+/// `.word`, against the declared labels before anything is encoded, so all
+/// references to a missing label can be reported together. Labels in
+/// `externs` count as declared, but only jumps, `la` and `.word` can refer
+/// to them
+fn check_labels(
+    vernac_sequence: &[MipsCST],
+    labels: &BTreeMap<String, u32>,
+    externs: &[String],
+) -> Result<(), AssemblerError> {
+    let mut undeclared: Option<String> = None;
+    let mut references: Vec<Location> = vec![];
+
+    for sub_cst in vernac_sequence {
+        // Branches are relative to the pc, so they carry no relocation for
+        // the linker to fill in
+        let (mnemonic, targets, branch) = match sub_cst {
+            MipsCST::Instruction(mnemonic, args) => (
+                mnemonic,
+                label_operand(mnemonic, args).into_iter().collect(),
+                !is_jump(mnemonic.as_str())
+                    && mnemonic.as_str() != "la"
+                    && memory_label(mnemonic.as_str(), args).is_none(),
+            ),
+            MipsCST::Directive(name, args) => (name, data_labels(name, args), false),
+            _ => continue,
+        };
+        for target in targets {
+            // Anything other than a label plus or minus a number is
+            // reported when it is encoded
+            let Some((label, _)) = label_offset(target) else {
+                continue;
             };
-            rs = assemble_reg(i_args[2])?;
-        }
-        IForm::RsRtLabel => {
-            enforce_length(&i_args, 3)?;
-            rs = assemble_reg(i_args[0])?;
-            rt = assemble_reg(i_args[1])?;
-            match labels.get(i_args[2]) {
-                // Subtract byte width due to branch delay
-                Some(v) => imm = ((*v) - instr_address - MIPS_INSTR_BYTE_WIDTH) as u16,
-                None => return Err("Undeclared label"),
+            if labels.contains_key(&label) {
+                continue;
+            }
+            if externs.contains(&label) {
+                if branch {
+                    return Err(AssemblerError::OperandType {
+                    location: Location::at(target).into(),
+                    token: target.text.clone(),
+                    message: format!(
+                        "`{}` cannot branch to `{}`, which is defined in another object; use `j` or `jal`",
+                        mnemonic.as_str(),
+                        label
+                    ),
+                });
+                }
+                continue;
+            }
+            // Only the first missing label is reported, with all of its uses
+            if *undeclared.get_or_insert_with(|| label.clone()) == label {
+                references.push(Location::at(target));
             }
         }
-        IForm::RtRsImm => {
-            enforce_length(&i_args, 3)?;
-            rt = assemble_reg(i_args[0])?;
-            rs = assemble_reg(i_args[1])?;
-            imm = match i_args[2].parse::<u16>() {
-                Ok(v) => v,
-                Err(_) => return Err("Failed to parse imm"),
-            };
-        }
+    }
+
+    match undeclared {
+        Some(name) => Err(AssemblerError::UndeclaredLabel {
+            suggestion: similar_label(labels, &name),
+            token: name,
+            references,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Assembles a machine instruction at `instr_address`: each operand is
+/// parsed as its entry in the ISA table says and placed in its field of the
+/// entry's fixed bits
+fn assemble_operands(
+    info: &InstructionInfo,
+    mnemonic: &Token,
+    args: Vec<Token>,
+    labels: &BTreeMap<String, u32>,
+    instr_address: u32,
+) -> Result<u32, AssemblerError> {
+    // `jalr $rs` is short for `jalr $ra, $rs`
+    let args = match (info.mnemonic, args.as_slice()) {
+        ("jalr", [target]) => vec![
+            Token {
+                text: "$ra".to_string(),
+                ..target.clone()
+            },
+            target.clone(),
+        ],
+        _ => args,
     };
 
-    let mut opcode = i_struct.opcode;
-
-    // Mask
-    println!("Masking rs");
-    rs = mask_u8(rs, 5)?;
-    println!("Masking rt");
-    rt = mask_u8(rt, 5)?;
-    println!("Masking opcode");
-    opcode = mask_u8(opcode, 6)?;
-    // No need to mask imm, it's already a u16
-
-    // opcode : 31 - 26
-    let mut result: u32 = opcode.into();
-
-    // rs :     25 - 21
-    println!("rs: {}", rs);
-    result = (result << 5) | u32::from(rs);
-
-    // rt :     20 - 16
-    println!("rt: {}", rt);
-    result = (result << 5) | u32::from(rt);
-
-    // imm :    15 - 0
-    println!("imm: {}", imm);
-    result = (result << 16) | u32::from(imm);
-
-    println!(
-        "0x{:0shortwidth$x} {:0width$b}",
-        result,
-        result,
-        shortwidth = 8,
-        width = 32
-    );
-    Ok(result)
-}
-
-/// Assembles a J-type instruction
-fn assemble_j(
-    j_struct: J,
-    j_args: Vec<&str>,
-    labels: &HashMap<&str, u32>,
-) -> Result<u32, &'static str> {
-    enforce_length(&j_args, 1)?;
-
-    let jump_address: u32 = labels[j_args[0]];
-    println!("Masking jump address");
-    println!("Jump address original: {}", jump_address);
-    let mut masked_jump_address = mask_u32(jump_address, 28)?;
-    println!("Jump address masked: {}", masked_jump_address);
-    if jump_address != masked_jump_address {
-        return Err("Tried to assemble illegal jump address");
-    }
-
-    // Byte-align jump address
-    masked_jump_address >>= 2;
-
-    let mut opcode = j_struct.opcode;
-
-    // Mask
-    println!("Masking opcode");
-    opcode = mask_u8(opcode, 6)?;
-    // No need to mask imm, it's already a u16
-
-    // opcode : 31 - 26
-    let mut result: u32 = opcode.into();
-
-    // imm :    25 - 0
-    println!("imm: {}", masked_jump_address);
-    result = (result << 26) | masked_jump_address;
-
-    println!(
-        "0x{:0shortwidth$x} {:0width$b}",
-        result,
-        result,
-        shortwidth = 8,
-        width = 32
-    );
-    Ok(result)
+    let operands = info.operands.list();
+    let names: Vec<&str> = operands.iter().map(|operand| operand.name()).collect();
+    check_operands(mnemonic, &args, &names, &info.operands.signature())?;
+
+    let mut word = info.template();
+    for (operand, arg) in operands.iter().zip(&args) {
+        let value = match operand {
+            Operand::Rd | Operand::Rs | Operand::Rt | Operand::Cop0 => {
+                u32::from(assemble_reg(arg)?)
+            }
+            Operand::Fd | Operand::Fs | Operand::Ft => u32::from(assemble_freg(arg)?),
+            Operand::Shamt => u32::from(parse_shamt(arg)?),
+            Operand::Imm => u32::from(encode_imm(info, ImmKind::Signed, mnemonic, &args, arg)?),
+            Operand::Uimm => u32::from(encode_imm(info, ImmKind::Unsigned, mnemonic, &args, arg)?),
+            Operand::Label => u32::from(branch_offset(labels, arg, instr_address)?),
+            // Byte-align the low 28 bits of the target
+            Operand::Target => {
+                let address = label_address(labels, arg)?;
+                check_jump_region(arg, address, instr_address)?;
+                (address & 0x0fff_ffff) >> 2
+            }
+        };
+        trace!("{}: {}", operand.name(), value);
+        word |= operand.field().place(value);
+    }
+    Ok(word)
+}
+
+/// Checks that a jump at `instr_address` can reach `target`. A J-type
+/// instruction only holds the low 28 bits of its target, the rest come from
+/// the address of its delay slot, so it cannot leave the 256MB region it is
+/// in. Labels declared `.extern` are at 0 until linked, and are checked
+/// when they are
+fn check_jump_region(label: &Token, target: u32, instr_address: u32) -> Result<(), AssemblerError> {
+    let region = instr_address.wrapping_add(MIPS_INSTR_BYTE_WIDTH) & 0xf000_0000;
+    if target == 0 || target & 0xf000_0000 == region {
+        return Ok(());
+    }
+    Err(AssemblerError::FieldOverflow {
+        location: Location::at(label).into(),
+        token: label.text.clone(),
+        message: format!(
+            "`{}` at 0x{:08x} is outside the 256MB region a jump at 0x{:08x} can reach; load its address and use `jr` instead",
+            label.as_str(),
+            target,
+            instr_address
+        ),
+    })
 }
 
 use crate::parser::*;
 use pest::Parser;
 
-// General assembler entrypoint
-pub fn assemble(program_arguments: &Args) -> Result<(), String> {
-    // IO Setup
-    let input_fn = &program_arguments.input_as;
-    let output_fn = &program_arguments.output_as;
-
-    let output_file: File = match File::create(output_fn) {
-        Ok(v) => v,
-        Err(_) => return Err("Failed to open output file".to_string()),
+/// Converts a pest parse failure into an [AssemblerError::Syntax]
+fn syntax_error(err: pest::error::Error<Rule>, source: &str) -> AssemblerError {
+    let (line, column) = match err.line_col {
+        pest::error::LineColLocation::Pos(pos) => pos,
+        pest::error::LineColLocation::Span(start, _) => start,
     };
-
-    // Read input
-    let file_contents: String = match fs::read_to_string(input_fn) {
-        Ok(v) => v,
-        Err(_) => return Err("Failed to read input file contents".to_string()),
+    // Underline the whitespace-delimited word the parser choked on
+    let token = source
+        .lines()
+        .nth(line.saturating_sub(1))
+        .and_then(|l| {
+            l.chars()
+                .skip(column.saturating_sub(1))
+                .collect::<String>()
+                .split_whitespace()
+                .next()
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+    let message = match err.variant {
+        pest::error::ErrorVariant::ParsingError { .. } => "unexpected input".to_string(),
+        pest::error::ErrorVariant::CustomError { message } => message,
     };
+    AssemblerError::Syntax {
+        location: Box::new(Location {
+            line,
+            column,
+            length: token.chars().count(),
+            ..Default::default()
+        }),
+        token,
+        message,
+    }
+}
+
+/// Settings for [assemble_source]
+#[derive(Debug, Clone, Default)]
+pub struct AssemblerOptions {
+    /// Name of the source, only used to label diagnostics
+    pub file_name: String,
+    /// The byte order to encode in
+    pub endian: Endian,
+    /// Label or `0x` address to start executing at, see [entry_point]
+    pub entry: Option<String>,
+    /// What to put in the delay slot after each branch and jump
+    pub delay_slots: DelaySlots,
+    /// Whether pseudo-instructions are expanded quietly, with a warning,
+    /// or not at all
+    pub pseudo_instructions: PseudoPolicy,
+    /// Whether loads and stores written with the base register first are
+    /// accepted quietly or with a warning
+    pub memory_operands: MemoryOperands,
+    /// Where `.text` starts, [TEXT_ADDRESS_BASE] if not given. `.org` in
+    /// the source takes precedence
+    pub text_base: Option<u32>,
+    /// Where `.data` starts, [DATA_ADDRESS_BASE] if not given. `.org` in
+    /// the source takes precedence
+    pub data_base: Option<u32>,
+    /// Symbols from another build, such as the base image an overlay or
+    /// patch is loaded into, that the source may refer to by name. Labels
+    /// the source defines take precedence, and imported symbols are left
+    /// out of the symbol table
+    pub imported_symbols: Vec<Symbol>,
+    /// Which warnings are reported, and which are errors, see [crate::lint]
+    pub warnings: WarningLevels,
+    /// Whether to take out instructions with nothing to do, see
+    /// [crate::optimize]
+    pub optimize: bool,
+}
+
+impl AssemblerOptions {
+    /// The settings that change what gets assembled, as recorded in a
+    /// [BuildInfo]. The file name only labels diagnostics, so is left out
+    pub fn settings(&self) -> String {
+        let mut settings = format!(
+            "endian={} entry={} delay-slots={:?} pseudo-instructions={:?} memory-operands={:?} text-base=0x{:08x} data-base=0x{:08x}",
+            self.endian,
+            self.entry.as_deref().unwrap_or("main"),
+            self.delay_slots,
+            self.pseudo_instructions,
+            self.memory_operands,
+            self.text_base.unwrap_or(TEXT_ADDRESS_BASE),
+            self.data_base.unwrap_or(DATA_ADDRESS_BASE)
+        );
+        if !self.imported_symbols.is_empty() {
+            settings.push_str(&format!(
+                " imported-symbols={}",
+                self.imported_symbols.len()
+            ));
+        }
+        if self.optimize {
+            settings.push_str(" optimize");
+        }
+        settings
+    }
+}
+
+/// A block of assembled bytes and where it is loaded
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Section {
+    pub name: String,
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Which part of an instruction holds a symbol's address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RelocationKind {
+    /// Bits 27-2 of the address in a J-type target field, like R_MIPS_26
+    Jump26,
+    /// The upper 16 bits in an immediate field, as loaded by `lui`
+    Hi16,
+    /// The upper 16 bits, plus one when bit 15 is set, as loaded by `lui`
+    /// before a load or store whose offset is the lower 16 bits, like
+    /// R_MIPS_HI16
+    Hi16Adjusted,
+    /// The lower 16 bits in an immediate field, as combined by `ori` or
+    /// taken as the offset of a load or store
+    Lo16,
+    /// A whole word holding the address, as laid out by `.word`, like
+    /// R_MIPS_32
+    Word32,
+}
+
+/// A place in a section that holds the address of a symbol. The address is
+/// already filled in, but has to be patched if the symbol moves
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Relocation {
+    pub section: String,
+    /// Byte offset of the instruction or word from the start of the section
+    pub offset: u32,
+    pub symbol: String,
+    pub kind: RelocationKind,
+    /// Added to the address of the symbol, for operands like `table+8`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub addend: i32,
+}
 
-    // Parse into CST
-    let cst = parse_rule(
-        MipsParser::parse(Rule::vernacular, file_contents.as_str())
-            .expect("Failed to parse")
-            .next()
-            .unwrap(),
-    );
-    print_cst(&cst);
-
-    // Set up line info
-    let lineinfo_fn = format!("{}.li", &program_arguments.output_as);
-    let mut lineinfo: Vec<LineInfo> = vec![];
-    let mut line_number: u32 = 1;
-
-    let vernac_sequence: Vec<MipsCST> = if let MipsCST::Sequence(v) = cst {
-        v
+fn is_zero(value: &i32) -> bool {
+    *value == 0
+}
+
+/// A program assembled in memory
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AssembledObject {
+    /// The assembled sections: `.text`, then `.data` if the program lays
+    /// out any data, each at the address it is loaded at
+    pub sections: Vec<Section>,
+    /// Where execution starts
+    pub entry: u32,
+    /// The byte order the sections were encoded in
+    pub endian: Endian,
+    pub lineinfo: LineTable,
+    /// Every label defined in the program and its address, sorted by
+    /// address. Those exported with `.globl` are marked global
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+    /// The largest alignment anything in `.data` asks for, which linking
+    /// has to keep
+    #[serde(default)]
+    pub data_alignment: u32,
+    /// Labels declared `.extern` and not defined here. References to them
+    /// hold address 0 until the object is linked
+    #[serde(default)]
+    pub externs: Vec<String>,
+    /// Which tool built the object and from what, filled in by whoever
+    /// writes it out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildInfo>,
+    /// Problems that did not stop the program assembling, in source order
+    #[serde(skip)]
+    pub warnings: Vec<Warning>,
+    /// What `-O` took out, in source order
+    #[serde(skip)]
+    pub optimizations: Vec<Optimization>,
+}
+
+impl AssembledObject {
+    /// The bytes of the section called `name`, if there is one
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// The encoded instructions
+    pub fn text(&self) -> &[u8] {
+        self.section(TEXT_SECTION)
+            .map_or(&[], |section| section.data.as_slice())
+    }
+
+    /// The initialized data, empty if the program has none
+    pub fn data(&self) -> &[u8] {
+        self.section(DATA_SECTION)
+            .map_or(&[], |section| section.data.as_slice())
+    }
+
+    /// Where the instructions are loaded
+    pub fn text_address(&self) -> u32 {
+        self.section(TEXT_SECTION)
+            .map_or(TEXT_ADDRESS_BASE, |section| section.address)
+    }
+
+    /// Where the initialized data is loaded. A program without data has
+    /// no `.data` section to say, so this falls back to [DATA_ADDRESS_BASE]
+    pub fn data_address(&self) -> u32 {
+        self.section(DATA_SECTION)
+            .map_or(DATA_ADDRESS_BASE, |section| section.address)
+    }
+}
+
+/// The name of the section instructions are assembled into
+pub const TEXT_SECTION: &str = ".text";
+/// The name of the section data directives lay out bytes in
+pub const DATA_SECTION: &str = ".data";
+
+/// Assembles `source` entirely in memory. This is the whole assembler
+/// pipeline; writing the results to files is left to the caller
+pub fn assemble_source(
+    source: &str,
+    options: &AssemblerOptions,
+) -> Result<AssembledObject, AssemblerError> {
+    assemble_optimized(source, options)
+        .map(|mut assembled| {
+            assembled.warnings = assembled
+                .warnings
+                .into_iter()
+                .map(|Warning(w)| Warning(w.with_source(&options.file_name, source)))
+                .collect();
+            assembled
+        })
+        .map_err(|e| e.with_source(&options.file_name, source))
+}
+
+/// Assembles `source` into raw instructions, as loaded by name-emu
+pub fn assemble_to_bytes(
+    source: &str,
+    options: &AssemblerOptions,
+) -> Result<Vec<u8>, AssemblerError> {
+    Ok(assemble_source(source, options)?.text().to_vec())
+}
+
+/// Assembles `source` into an ELF executable with DWARF line information
+pub fn assemble_to_object(
+    source: &str,
+    options: &AssemblerOptions,
+) -> Result<Vec<u8>, AssemblerError> {
+    Ok(object_bytes(
+        &assemble_source(source, options)?,
+        &options.file_name,
+    ))
+}
+
+/// Writes an assembled program as an ELF executable. `source_file` is named
+/// in its debug information
+pub fn object_bytes(assembled: &AssembledObject, source_file: &str) -> Vec<u8> {
+    write_elf(&ElfProgram {
+        endian: assembled.endian,
+        entry: assembled.entry,
+        text_address: assembled.text_address(),
+        text: assembled.text(),
+        data_address: assembled.data_address(),
+        data: assembled.data(),
+        symbols: &assembled.symbols,
+        source_file,
+        lineinfo: &assembled.lineinfo,
+        build_info: assembled.build.as_ref(),
+    })
+}
+
+/// Encodes a single real instruction located at `current_addr`
+fn assemble_instruction(
+    mnemonic: &Token,
+    args: Vec<Token>,
+    labels: &BTreeMap<String, u32>,
+    current_addr: u32,
+) -> Result<u32, AssemblerError> {
+    if let Some(info) = operation(mnemonic.as_str()) {
+        trace!("-----------------------------------");
+        trace!(
+            "[{:?}] {} - fixed bits [{:08x}]",
+            info.format,
+            mnemonic.as_str(),
+            info.template()
+        );
+        assemble_operands(info, mnemonic, args, labels, current_addr)
     } else {
-        vec![cst]
-    };
+        Err(AssemblerError::UnknownInstruction {
+            location: Location::at(mnemonic).into(),
+            token: mnemonic.text.clone(),
+        })
+    }
+}
 
-    // Assign addresses to labels
-    let mut current_addr: u32 = TEXT_ADDRESS_BASE;
-    let mut labels: HashMap<&str, u32> = HashMap::new();
-    for sub_cst in &vernac_sequence {
-        match sub_cst {
-            MipsCST::Label(label_str) => {
-                println!("Inserting label {} at {:x}", label_str, current_addr);
-                labels.insert(label_str, current_addr);
-                continue;
-            }
-            MipsCST::Instruction(_, _) => (),
-            MipsCST::Sequence(_) => unreachable!(),
-        };
+/// Parses `source` into its labels, instructions and directives, in source
+/// order, without assembling anything. Every token keeps where it was
+/// found, so tools such as name-lsp can point back into the source
+pub fn parse_source(source: &str) -> Result<Vec<MipsCST>, AssemblerError> {
+    let cst = match MipsParser::parse(Rule::vernacular, source) {
+        Ok(mut pairs) => match pairs.next() {
+            Some(pair) => parse_rule(pair),
+            None => MipsCST::Sequence(vec![]),
+        },
+        Err(e) => return Err(syntax_error(e, source)),
+    };
+    Ok(match cst {
+        MipsCST::Sequence(v) => v,
+        cst => vec![cst],
+    })
+}
 
-        current_addr += MIPS_INSTR_BYTE_WIDTH
+/// Assembles `file_contents`, shortened as far as it will go if
+/// [AssemblerOptions::optimize] is set
+fn assemble_optimized(
+    file_contents: &str,
+    options: &AssemblerOptions,
+) -> Result<AssembledObject, AssemblerError> {
+    let mut plan = Plan::new();
+    for _ in 0..optimize::MAX_PASSES {
+        let (assembled, found) = assemble_program(file_contents, options, &plan)?;
+        if found == plan {
+            return Ok(assembled);
+        }
+        plan = found;
     }
+    // The layout never settled, so leave the program as it is
+    assemble_program(file_contents, options, &Plan::new()).map(|(assembled, _)| assembled)
+}
 
-    current_addr = TEXT_ADDRESS_BASE;
+/// Assembles `file_contents` into an [AssembledObject], with the
+/// statements in `plan` shortened as it says. Also returns the statements
+/// that could be shortened with the labels where they ended up, which is
+/// `plan` again once the layout has settled
+fn assemble_program(
+    file_contents: &str,
+    options: &AssemblerOptions,
+    plan: &Plan,
+) -> Result<(AssembledObject, Plan), AssemblerError> {
+    let endian = options.endian;
+
+    let vernac_sequence = parse_source(file_contents)?;
+    if log::enabled(Verbosity::Parser) {
+        for cst in &vernac_sequence {
+            print_cst(cst);
+        }
+    }
 
-    // Assemble instructions
-    for sub_cst in vernac_sequence {
+    // Set up line info. Everything comes from the one file for now
+    let mut lineinfo = LineTable {
+        files: vec![options.file_name.clone()],
+        ..Default::default()
+    };
+    let source_lines: Vec<&str> = file_contents.lines().collect();
+    let mut text: Vec<u8> = vec![];
+    let mut relocations: Vec<Relocation> = vec![];
+    let mut warnings: Vec<Warning> = vec![];
+
+    let vernac_sequence = substitute_constants(vernac_sequence)?;
+    let (vernac_sequence, operand_warnings) =
+        normalize_memory_operands(vernac_sequence, options.memory_operands);
+    warnings.extend(operand_warnings);
+    // Lints look at the program as written, before delay slots are filled
+    let written = vernac_sequence.clone();
+    let vernac_sequence = fill_delay_slots(vernac_sequence, options.delay_slots);
+    // Jumps to the next instruction can be taken out wherever labels are
+    let mut found = Plan::new();
+    if options.optimize {
+        found.extend(
+            optimize::jumps_to_next(&vernac_sequence, options.delay_slots)
+                .into_iter()
+                .map(|index| (index, 0)),
+        );
+    }
+    let mut optimizations: Vec<Optimization> = vec![];
+
+    // Assign addresses to labels. A label names whatever comes after it,
+    // so it is only placed once that is known to be aligned
+    let mut section = SectionKind::Text;
+    let mut text_base = options.text_base.unwrap_or(TEXT_ADDRESS_BASE);
+    let mut data_base = options.data_base.unwrap_or(DATA_ADDRESS_BASE);
+    let mut current_addr: u32 = text_base;
+    let mut data_addr: u32 = data_base;
+    let mut labels: BTreeMap<String, u32> = BTreeMap::new();
+    let mut pending: Vec<&Token> = vec![];
+    // Where each label was defined, for reporting duplicates
+    let mut label_sites: BTreeMap<&str, &Token> = BTreeMap::new();
+    // Data labels take the type of the first directive after them that
+    // lays out bytes, past any `.align`
+    let mut untyped: Vec<String> = vec![];
+    let mut data_types: BTreeMap<String, DataType> = BTreeMap::new();
+    let mut sizes: Vec<(&Token, u32)> = vec![];
+    let mut globals: Vec<&Token> = vec![];
+    let mut externs: Vec<&Token> = vec![];
+    let mut largest_alignment: u32 = 1;
+    // The zeros each `.org` pads its section with, in source order
+    let mut org_padding: Vec<(SectionKind, u32)> = vec![];
+    for (index, sub_cst) in vernac_sequence.iter().enumerate() {
         match sub_cst {
+            MipsCST::Label(label) => {
+                if let Some(previous) = label_sites.get(label.as_str()) {
+                    return Err(AssemblerError::DuplicateLabel {
+                        location: Location::at(label).into(),
+                        token: label.text.clone(),
+                        previous: Box::new(Location::at(previous)),
+                    });
+                }
+                label_sites.insert(label.as_str(), label);
+                pending.push(label);
+            }
             MipsCST::Instruction(mnemonic, args) => {
-                // Update line info
-                lineinfo.push(LineInfo {
-                    instr_addr: current_addr,
-                    line_number: line_number,
-                    line_contents: instr_to_str(mnemonic, &args),
-                    psuedo_op: "".to_string(),
-                });
-
-                if let Ok(instr_info) = r_operation(mnemonic) {
-                    println!("-----------------------------------");
-                    println!(
-                        "[R] {} - shamt [{:x}] - funct [{:x}]",
-                        mnemonic, instr_info.shamt, instr_info.funct
-                    );
-                    match assemble_r(instr_info, args) {
-                        Ok(assembled_r) => {
-                            if write_u32(&output_file, assembled_r).is_err() {
-                                return Err("Failed to write to output binary".to_string());
-                            }
+                check_section(section, SectionKind::Text, mnemonic)?;
+                define_labels(&mut labels, &mut pending, current_addr);
+                let count = match plan.get(&index) {
+                    Some(count) => *count as u32,
+                    None if is_pseudo(mnemonic.as_str(), args) => expanded_len(mnemonic, args)?,
+                    None => 1,
+                };
+                current_addr = advance(current_addr, count * MIPS_INSTR_BYTE_WIDTH, mnemonic)?;
+            }
+            MipsCST::Directive(name, args) => match directive(name)? {
+                Directive::Section(kind) => {
+                    let here = match section {
+                        SectionKind::Text => current_addr,
+                        SectionKind::Data => data_addr,
+                    };
+                    define_labels(&mut labels, &mut pending, here);
+                    untyped.clear();
+                    section = kind;
+                }
+                Directive::Global => globals.extend(args),
+                Directive::Extern => externs.extend(args),
+                Directive::Size => sizes.push(symbol_size(name, args)?),
+                Directive::Org => {
+                    let (here, base) = match section {
+                        SectionKind::Text => (&mut current_addr, &mut text_base),
+                        SectionKind::Data => (&mut data_addr, &mut data_base),
+                    };
+                    let empty = *here == *base;
+                    let address = org_address(name, args, section, *here, empty)?;
+                    if empty {
+                        *base = address;
+                        org_padding.push((section, 0));
+                    } else {
+                        org_padding.push((section, address - *here));
+                    }
+                    *here = address;
+                }
+                Directive::Data => {
+                    check_section(section, SectionKind::Data, name)?;
+                    let alignment = data_alignment(name, args)?;
+                    // Padding up to the next multiple of the alignment
+                    let padding = data_addr.wrapping_neg() % alignment;
+                    data_addr = advance(data_addr, padding, name)?;
+                    largest_alignment = largest_alignment.max(alignment);
+                    untyped.extend(pending.iter().map(|label| label.text.clone()));
+                    define_labels(&mut labels, &mut pending, data_addr);
+                    if name.as_str() != ".align" {
+                        if let Some(data_type) = data_type(name) {
+                            data_types
+                                .extend(untyped.iter().map(|label| (label.clone(), data_type)));
                         }
-                        Err(e) => return Err(e.to_string()),
+                        untyped.clear();
                     }
-                } else if let Ok(instr_info) = i_operation(mnemonic) {
-                    println!("-----------------------------------");
-                    println!("[I] {} - opcode [{:x}]", mnemonic, instr_info.opcode);
-
-                    match assemble_i(instr_info, args, &labels, current_addr) {
-                        Ok(assembled_i) => {
-                            if write_u32(&output_file, assembled_i).is_err() {
-                                return Err("Failed to write to output binary".to_string());
-                            }
+                    let bytes = data_bytes(name, args, endian, None)?.len();
+                    data_addr = advance(data_addr, bytes.try_into().unwrap_or(u32::MAX), name)?;
+                }
+                Directive::Set => {
+                    set_option(name, args)?;
+                }
+            },
+            MipsCST::Sequence(_) => unreachable!(),
+        };
+    }
+    let here = match section {
+        SectionKind::Text => current_addr,
+        SectionKind::Data => data_addr,
+    };
+    define_labels(&mut labels, &mut pending, here);
+
+    let (exported, mut unresolved) = symbol_visibility(&globals, &externs, &labels, &label_sites)?;
+
+    check_overlap(text_base..current_addr, data_base..data_addr)?;
+    let entry = entry_point(options.entry.as_deref(), &labels, text_base..current_addr)?;
+    let sizes = symbol_sizes(&labels, data_base..=data_addr, &sizes)?;
+    let imported = import_symbols(&mut labels, &options.imported_symbols);
+    unresolved.retain(|label| !imported.contains(label));
+    check_labels(&vernac_sequence, &labels, &unresolved)?;
+    labels.extend(unresolved.iter().map(|label| (label.clone(), 0)));
+
+    current_addr = text_base;
+    let mut data: Vec<u8> = vec![];
+    let mut org_padding = org_padding.into_iter();
+    // Whether pseudo-instructions may use $at, until `.set noat`
+    let mut at_available = true;
+
+    // Assemble instructions and lay out data
+    for (index, sub_cst) in vernac_sequence.into_iter().enumerate() {
+        let (mnemonic, args) = match sub_cst {
+            MipsCST::Instruction(mnemonic, args) => (mnemonic, args),
+            MipsCST::Directive(name, args) => {
+                match directive(&name)? {
+                    Directive::Data => {
+                        let start = data_base + data.len() as u32;
+                        data.resize(
+                            (start.next_multiple_of(data_alignment(&name, &args)?) - data_base)
+                                as usize,
+                            0,
+                        );
+                        if name.as_str() == ".word" {
+                            relocations.extend(word_relocations(&args, data.len() as u32));
                         }
-                        Err(e) => return Err(e.to_string()),
+                        data.extend(data_bytes(&name, &args, endian, Some(&labels))?);
                     }
-                } else if let Ok(instr_info) = j_operation(mnemonic) {
-                    println!("-----------------------------------");
-                    println!("[J] {} - opcode [{:x}]", mnemonic, instr_info.opcode);
-
-                    match assemble_j(instr_info, args, &labels) {
-                        Ok(assembled_j) => {
-                            if write_u32(&output_file, assembled_j).is_err() {
-                                return Err("Failed to write to output binary".to_string());
-                            }
+                    Directive::Org => match org_padding.next() {
+                        Some((SectionKind::Text, padding)) => {
+                            text.resize(text.len() + padding as usize, 0);
+                            current_addr += padding;
+                        }
+                        Some((SectionKind::Data, padding)) => {
+                            data.resize(data.len() + padding as usize, 0);
+                        }
+                        None => unreachable!("every `.org` was sized while placing labels"),
+                    },
+                    Directive::Set => {
+                        if let SetOption::At(on) = set_option(&name, &args)? {
+                            at_available = on;
                         }
-                        Err(e) => return Err(e.to_string()),
                     }
-                } else {
-                    return Err("Failed to match instruction".to_string());
+                    _ => (),
                 }
-            }
-            MipsCST::Label(_) => {
-                line_number += 1;
                 continue;
             }
             _ => continue,
         };
 
-        current_addr += MIPS_INSTR_BYTE_WIDTH;
-        line_number += 1;
-    }
+        if plan.get(&index) == Some(&0) {
+            optimizations.push(Optimization {
+                line: mnemonic.line,
+                statement: optimize::statement(&mnemonic, &args),
+                change: "left out, it jumps to the instruction that runs next anyway".to_string(),
+                removed: 1,
+            });
+            continue;
+        }
+        // Optimized output is not linked, so what moved is not recorded
+        if !plan.contains_key(&index) {
+            relocations.extend(relocations_for(&mnemonic, &args, current_addr - text_base));
+        }
 
-    if program_arguments.line_info {
-        if let Err(e) = lineinfo_export(lineinfo_fn, lineinfo) {
-            return Err(e.to_string());
+        // Every instruction a pseudo-instruction expands into points back
+        // at it in the line info
+        let span = statement_span(&source_lines, &mnemonic);
+        let (instructions, pseudo_op) = if is_pseudo(mnemonic.as_str(), &args) {
+            let expanded = expand(&mnemonic, &args, Some(&labels))?;
+            if !at_available && uses_at(&expanded) {
+                return Err(pseudo_instruction_error(
+                    &mnemonic,
+                    &args,
+                    &expanded,
+                    format!(
+                        "`{}` needs $at here, which `.set noat` leaves to the program",
+                        mnemonic.as_str()
+                    ),
+                ));
+            }
+            match options.pseudo_instructions {
+                PseudoPolicy::Forbid => {
+                    return Err(pseudo_instruction_error(
+                        &mnemonic,
+                        &args,
+                        &expanded,
+                        format!(
+                            "`{}` is a pseudo-instruction, and only real instructions are allowed",
+                            mnemonic.as_str()
+                        ),
+                    ))
+                }
+                PseudoPolicy::Warn => warnings.push(Warning(pseudo_instruction_error(
+                    &mnemonic,
+                    &args,
+                    &expanded,
+                    format!(
+                        "pseudo-instruction `{}` expanded into {} instruction(s)",
+                        mnemonic.as_str(),
+                        expanded.len()
+                    ),
+                ))),
+                PseudoPolicy::Allow | PseudoPolicy::Annotate => (),
+            }
+            lineinfo.pseudo_ops.push(PseudoOp {
+                text: instr_to_str(&mnemonic, &args),
+                span,
+            });
+            (expanded, Some(lineinfo.pseudo_ops.len() as u32 - 1))
+        } else {
+            (vec![(mnemonic.clone(), args.clone())], None)
+        };
+
+        // Only what the layout made room for is shortened; anything else
+        // that could be is for the next pass
+        let instructions = if options.optimize && pseudo_op.is_some() {
+            let (shortened, changes) = optimize::peephole(&instructions);
+            if shortened.len() < instructions.len() {
+                found.insert(index, shortened.len());
+            }
+            if plan.get(&index) == Some(&shortened.len()) {
+                optimizations.push(Optimization {
+                    line: mnemonic.line,
+                    statement: optimize::statement(&mnemonic, &args),
+                    change: changes.join("; "),
+                    removed: instructions.len() - shortened.len(),
+                });
+                shortened
+            } else {
+                instructions
+            }
+        } else {
+            instructions
+        };
+
+        for (mnemonic, args) in instructions {
+            // Update line info
+            let line_contents = instr_to_str(&mnemonic, &args);
+            lineinfo.lines.push(LineInfo {
+                instr_addr: current_addr,
+                file: 0,
+                span,
+                line_contents: line_contents.clone(),
+                pseudo_op,
+            });
+
+            let assembled = assemble_instruction(&mnemonic, args, &labels, current_addr)?;
+            info!(
+                "0x{:08x}: 0x{:08x} {:032b} {}",
+                current_addr, assembled, assembled, line_contents
+            );
+            write_u32(&mut text, assembled, endian).expect("writing to a Vec cannot fail");
+
+            current_addr += MIPS_INSTR_BYTE_WIDTH;
         }
     }
 
-    Ok(())
+    warnings.extend(lint::check(&lint::Program {
+        sequence: &written,
+        labels: &labels,
+        data: data_base..data_addr,
+        entry: options.entry.as_deref().unwrap_or("main"),
+    }));
+    let warnings = options.warnings.sort(warnings)?;
+
+    // Imported symbols are at fixed addresses, so nothing that refers to
+    // them moves when linked, and they belong to the build they came from
+    relocations.retain(|relocation| !imported.contains(&relocation.symbol));
+    let mut symbols: Vec<Symbol> = labels
+        .into_iter()
+        .filter(|(name, _)| !unresolved.contains(name) && !imported.contains(name))
+        .map(|(name, address)| Symbol {
+            address,
+            size: sizes.get(&name).copied().unwrap_or(0),
+            data_type: data_types.get(&name).copied(),
+            global: exported.contains(&name),
+            name,
+        })
+        .collect();
+    symbols.sort();
+
+    let mut sections = vec![Section {
+        name: TEXT_SECTION.to_string(),
+        address: text_base,
+        data: text,
+    }];
+    if !data.is_empty() {
+        sections.push(Section {
+            name: DATA_SECTION.to_string(),
+            address: data_base,
+            data,
+        });
+    }
+
+    let assembled = AssembledObject {
+        sections,
+        entry,
+        endian,
+        lineinfo,
+        symbols,
+        relocations,
+        data_alignment: largest_alignment,
+        externs: unresolved,
+        build: None,
+        warnings,
+        optimizations,
+    };
+    Ok((assembled, found))
 }
@@ -0,0 +1,101 @@
+/// Other ways of writing the operands of a load or store. Some course
+/// materials put the base register before the offset, as in
+/// `lw $t0, ($t1), 4` or `lw $t0, $t1, 4`, or leave the offset out with
+/// no parentheses, as in `lw $t0, $t1`. These are rewritten into the
+/// usual `lw $t0, 4($t1)` before anything else sees them
+use crate::error::{AssemblerError, Location, Warning};
+use crate::nma::is_memory_access;
+use crate::parser::{MipsCST, Token};
+use serde::Deserialize;
+
+/// How the assembler takes loads and stores written another way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryOperands {
+    /// Accept them, with a warning showing the usual way to write them
+    #[default]
+    Strict,
+    /// Accept them without comment
+    Lenient,
+}
+
+impl std::str::FromStr for MemoryOperands {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(MemoryOperands::Strict),
+            "lenient" => Ok(MemoryOperands::Lenient),
+            _ => Err(format!(
+                "unknown memory operand mode `{}`, expected strict or lenient",
+                s
+            )),
+        }
+    }
+}
+
+fn is_register(token: &Token) -> bool {
+    token.as_str().starts_with('$')
+}
+
+/// The operands of a load or store in the usual order, `$rt, offset, $rs`,
+/// if they were written another way
+fn reordered(mnemonic: &str, args: &[Token]) -> Option<Vec<Token>> {
+    if !is_memory_access(mnemonic) {
+        return None;
+    }
+    match args {
+        // `lw $t0, ($t1), 4` or `lw $t0, $t1, 4`
+        [rt, base, offset] if is_register(base) && !is_register(offset) => {
+            Some(vec![rt.clone(), offset.clone(), base.clone()])
+        }
+        // `lw $t0, $t1`
+        [rt, base] if is_register(base) => {
+            let offset = Token {
+                text: "0".to_string(),
+                ..base.clone()
+            };
+            Some(vec![rt.clone(), offset, base.clone()])
+        }
+        _ => None,
+    }
+}
+
+/// Rewrites every load and store written another way into the usual form,
+/// with a warning for each in [MemoryOperands::Strict]
+pub fn normalize_memory_operands(
+    sequence: Vec<MipsCST>,
+    mode: MemoryOperands,
+) -> (Vec<MipsCST>, Vec<Warning>) {
+    let mut warnings = vec![];
+    let normalized = sequence
+        .into_iter()
+        .map(|cst| match cst {
+            MipsCST::Instruction(mnemonic, args) => {
+                match reordered(mnemonic.as_str(), &args) {
+                    Some(usual) => {
+                        if mode == MemoryOperands::Strict {
+                            warnings.push(Warning(AssemblerError::OperandType {
+                                location: Location::spanning(&mnemonic, args.last().unwrap())
+                                    .into(),
+                                token: mnemonic.text.clone(),
+                                message: format!(
+                                    "`{}` takes its offset before the base register, as in `{} {}, {}({})`",
+                                    mnemonic.as_str(),
+                                    mnemonic.as_str(),
+                                    usual[0].as_str(),
+                                    usual[1].as_str(),
+                                    usual[2].as_str()
+                                ),
+                            }));
+                        }
+                        MipsCST::Instruction(mnemonic, usual)
+                    }
+                    None => MipsCST::Instruction(mnemonic, args),
+                }
+            }
+            cst => cst,
+        })
+        .collect();
+    (normalized, warnings)
+}
@@ -0,0 +1,143 @@
+/// The peephole optimizer behind `-O`. It only takes out instructions that
+/// pseudo-instruction expansion or the program leaves with nothing to do:
+///
+/// - `lui $at, 0` before the `ori` that finishes building an address in
+///   the low 64KB, or the load or store that adds the lower half as its
+///   offset, which then reads `$zero` instead
+/// - `ori $at, $at, 0`, where the low half of an address is zero
+/// - `j label` where `label` is the instruction right after it, or right
+///   after its delay slot when delay slots are filled
+///
+/// `$at` may be left holding something else afterwards, which only matters
+/// to a program that reads it, and the addresses the program is assembled
+/// at are taken to be final, so the output cannot be linked.
+use crate::delay::DelaySlots;
+use crate::nma::is_memory_access;
+use crate::parser::{MipsCST, Token};
+use crate::pseudo::Expanded;
+use name_core::register::Register;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How many instructions each statement that can be shortened takes once
+/// it is, by its position in the program. Statements not in it take as many
+/// as they would unoptimized
+pub(crate) type Plan = BTreeMap<usize, usize>;
+
+/// Shortening one statement moves the labels after it, which can change
+/// what else can be shortened. Assembling is repeated until the plan stops
+/// changing, up to this many times
+pub(crate) const MAX_PASSES: usize = 8;
+
+/// One change the optimizer made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optimization {
+    /// The line of source the instructions came from
+    pub line: usize,
+    /// The statement as written
+    pub statement: String,
+    /// What was done with it
+    pub change: String,
+    /// How many instructions that saved
+    pub removed: usize,
+}
+
+impl fmt::Display for Optimization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}: `{}`: {}",
+            self.line, self.statement, self.change
+        )
+    }
+}
+
+/// A statement as it would be written
+pub(crate) fn statement(mnemonic: &Token, args: &[Token]) -> String {
+    let args: Vec<&str> = args.iter().map(Token::as_str).collect();
+    format!("{} {}", mnemonic.as_str(), args.join(", "))
+        .trim_end()
+        .to_string()
+}
+
+fn is_register(token: &Token, register: Register) -> bool {
+    token.as_str().parse::<Register>() == Ok(register)
+}
+
+fn is_zero(token: &Token) -> bool {
+    token.as_str().parse::<i64>() == Ok(0)
+}
+
+/// Takes the instructions with nothing to do out of what a
+/// pseudo-instruction expanded into, saying what was taken out
+pub(crate) fn peephole(expanded: &[Expanded]) -> (Vec<Expanded>, Vec<String>) {
+    let mut kept: Vec<Expanded> = vec![];
+    let mut changes = vec![];
+    let mut rest = expanded.iter().peekable();
+    while let Some((mnemonic, args)) = rest.next() {
+        match (mnemonic.as_str(), args.as_slice()) {
+            ("lui", [at, hi]) if is_register(at, Register::At) && is_zero(hi) => {
+                // The register $at is read from: the source of `ori`, the
+                // base of a load or store
+                let base = |next: &Token| if next.as_str() == "ori" { 1 } else { 2 };
+                if let Some((next, next_args)) = rest.next_if(|(next, next_args)| {
+                    (next.as_str() == "ori" || is_memory_access(next.as_str()))
+                        && next_args.len() == 3
+                        && is_register(&next_args[base(next)], Register::At)
+                }) {
+                    let mut next_args = next_args.clone();
+                    next_args[base(next)].text = "$zero".to_string();
+                    changes.push(format!(
+                        "`lui $at, 0` left out, `{}` builds the address from $zero",
+                        statement(next, &next_args)
+                    ));
+                    kept.push((next.clone(), next_args));
+                    continue;
+                }
+            }
+            ("ori", [rt, rs, lo])
+                if is_register(rt, Register::At)
+                    && is_register(rs, Register::At)
+                    && is_zero(lo) =>
+            {
+                changes.push(
+                    "`ori $at, $at, 0` left out, the low half of the address is 0".to_string(),
+                );
+                continue;
+            }
+            _ => (),
+        }
+        kept.push((mnemonic.clone(), args.clone()));
+    }
+    (kept, changes)
+}
+
+/// The positions of the `j` instructions in `sequence` that jump to the
+/// instruction that would run next anyway. With delay slots filled, that is
+/// the one after the slot, and the slot stays
+pub(crate) fn jumps_to_next(sequence: &[MipsCST], delay_slots: DelaySlots) -> Vec<usize> {
+    let is_jump_to_next = |index: usize| {
+        let MipsCST::Instruction(mnemonic, args) = &sequence[index] else {
+            return false;
+        };
+        let ("j", [target]) = (mnemonic.as_str(), args.as_slice()) else {
+            return false;
+        };
+        let after = &sequence[index + 1..];
+        let after = match (delay_slots, after.split_first()) {
+            (DelaySlots::Off, _) => after,
+            (_, Some((MipsCST::Instruction(..), after))) => after,
+            _ => return false,
+        };
+        after
+            .iter()
+            .map_while(|cst| match cst {
+                MipsCST::Label(label) => Some(label),
+                _ => None,
+            })
+            .any(|label| label.as_str() == target.as_str())
+    };
+    (0..sequence.len())
+        .filter(|index| is_jump_to_next(*index))
+        .collect()
+}
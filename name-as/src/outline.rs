@@ -0,0 +1,80 @@
+/// The labels and constants a program defines and where, for editors.
+/// These come straight from the parsed source, so they are there to list
+/// and jump to even while the program does not assemble
+use crate::directive::SectionKind;
+use crate::expr::is_constant_definition;
+use crate::parser::{MipsCST, Token};
+
+/// What a name is defined as
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefinitionKind {
+    /// A label, in the section it names a place in
+    Label(SectionKind),
+    /// A constant from `.eqv`, `.equ` or `.set`, and its value as written
+    Constant(Token),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    /// The name, where it is defined
+    pub name: Token,
+    pub kind: DefinitionKind,
+}
+
+/// Every label and constant `sequence` defines, in source order. A constant
+/// given a new value with `.set` is listed at each place it is set
+pub fn definitions(sequence: &[MipsCST]) -> Vec<Definition> {
+    let mut section = SectionKind::Text;
+    let mut found = vec![];
+    for cst in sequence {
+        match cst {
+            MipsCST::Label(name) => found.push(Definition {
+                name: name.clone(),
+                kind: DefinitionKind::Label(section),
+            }),
+            MipsCST::Directive(name, args) if is_constant_definition(name, args) => {
+                if let [constant, value] = args.as_slice() {
+                    found.push(Definition {
+                        name: constant.clone(),
+                        kind: DefinitionKind::Constant(value.clone()),
+                    });
+                }
+            }
+            MipsCST::Directive(name, _) => match name.as_str() {
+                ".text" => section = SectionKind::Text,
+                ".data" => section = SectionKind::Data,
+                _ => (),
+            },
+            MipsCST::Sequence(v) => found.extend(definitions(v)),
+            MipsCST::Instruction(..) => (),
+        }
+    }
+    found
+}
+
+/// The label or constant name that `column` (counted in characters from 1)
+/// of `line` falls in, if any, and the column it starts at. Registers and
+/// numbers are not names
+pub fn name_at(line: &str, column: usize) -> Option<(usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_name_char = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    let at = column.checked_sub(1)?;
+    if !chars.get(at).is_some_and(is_name_char) {
+        return None;
+    }
+    let start = chars[..at]
+        .iter()
+        .rposition(|c| !is_name_char(c))
+        .map_or(0, |i| i + 1);
+    let end = chars[at..]
+        .iter()
+        .position(|c| !is_name_char(c))
+        .map_or(chars.len(), |i| at + i);
+    let name: String = chars[start..end].iter().collect();
+    let after_register = start > 0 && chars[start - 1] == '$';
+    let after_directive = start > 0 && chars[start - 1] == '.';
+    if after_register || after_directive || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((start + 1, name))
+}
@@ -0,0 +1,492 @@
+/// Output formats: everything that turns an [AssembledObject] into the
+/// bytes of an output file. Each format is an [OutputWriter], picked with
+/// `--format`, so adding one does not touch the rest of the assembler.
+/// Programs using name-as as a library can implement [OutputWriter] for
+/// formats of their own
+use crate::link::object_to_string;
+use crate::nma::{object_bytes, AssembledObject, Section, MIPS_INSTR_BYTE_WIDTH};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Writes an assembled program in one output format
+pub trait OutputWriter {
+    /// Writes `assembled` to `out`. `source_file` is the file it was
+    /// assembled from, for formats that record it
+    fn write(
+        &self,
+        assembled: &AssembledObject,
+        source_file: &str,
+        out: &mut dyn Write,
+    ) -> io::Result<()>;
+
+    /// Whether the format holds `.data` as well as `.text`
+    fn keeps_data(&self) -> bool {
+        true
+    }
+
+    /// Whether references to `.extern` labels can be left for `name link`
+    /// to fill in
+    fn relocatable(&self) -> bool {
+        false
+    }
+}
+
+/// What gets written to OUTPUT
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    /// The raw .text bytes, as loaded by name-emu
+    #[default]
+    Binary,
+    /// A MIPS ELF executable with DWARF line information
+    Elf,
+    /// A relocatable object for `name link`, see [crate::link]
+    Object,
+    /// Intel HEX, as read by EPROM programmers and most FPGA tools
+    Ihex,
+    /// Motorola S-records
+    Srec,
+    /// Words in hex for Verilog's `$readmemh`
+    Readmemh,
+    /// A Xilinx memory coefficient file, for initializing block RAM
+    Coe,
+    /// An Altera (Intel) memory initialization file
+    Mif,
+    /// Every word with its address and the instruction it encodes
+    Annotated,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" | "bin" => Ok(OutputFormat::Binary),
+            "elf" => Ok(OutputFormat::Elf),
+            "object" => Ok(OutputFormat::Object),
+            "ihex" => Ok(OutputFormat::Ihex),
+            "srec" => Ok(OutputFormat::Srec),
+            "readmemh" => Ok(OutputFormat::Readmemh),
+            "coe" => Ok(OutputFormat::Coe),
+            "mif" => Ok(OutputFormat::Mif),
+            "annotated" => Ok(OutputFormat::Annotated),
+            _ => Err(format!(
+                "unknown output format `{}`, expected binary, elf, object, ihex, srec, readmemh, coe, mif or annotated",
+                s
+            )),
+        }
+    }
+}
+
+/// How the formats for initializing memory in a hardware design lay out
+/// the program: `$readmemh`, COE and MIF
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryShape {
+    /// Bits in each word of the memory: 8, 16 or 32
+    pub width: u32,
+    /// How many words the memory has. COE and MIF files are padded with
+    /// zeros to fill it. Left out, they hold just the program
+    pub depth: Option<u32>,
+}
+
+impl Default for MemoryShape {
+    fn default() -> MemoryShape {
+        MemoryShape {
+            width: 32,
+            depth: None,
+        }
+    }
+}
+
+impl MemoryShape {
+    fn word_bytes(&self) -> u32 {
+        self.width / 8
+    }
+}
+
+/// The most words a COE or MIF file holds without `--depth`, which the
+/// default memory map, with `.text` and `.data` 256MB apart, is far past
+const MAX_IMAGE_WORDS: u64 = 1 << 20;
+
+impl OutputFormat {
+    /// The writer for this format, with memories 32 bits wide
+    pub fn writer(self) -> Box<dyn OutputWriter> {
+        self.shaped_writer(MemoryShape::default())
+    }
+
+    /// The writer for this format, with memories laid out as `shape` says
+    pub fn shaped_writer(self, shape: MemoryShape) -> Box<dyn OutputWriter> {
+        match self {
+            OutputFormat::Binary => Box::new(BinaryWriter),
+            OutputFormat::Elf => Box::new(ElfWriter),
+            OutputFormat::Object => Box::new(ObjectWriter),
+            OutputFormat::Ihex => Box::new(IntelHexWriter),
+            OutputFormat::Srec => Box::new(SrecWriter),
+            OutputFormat::Readmemh => Box::new(ReadmemhWriter(shape)),
+            OutputFormat::Coe => Box::new(CoeWriter(shape)),
+            OutputFormat::Mif => Box::new(MifWriter(shape)),
+            OutputFormat::Annotated => Box::new(AnnotatedWriter),
+        }
+    }
+}
+
+/// The `.text` bytes and nothing else, as loaded by name-emu
+pub struct BinaryWriter;
+
+impl OutputWriter for BinaryWriter {
+    fn write(&self, assembled: &AssembledObject, _: &str, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(assembled.text())
+    }
+
+    fn keeps_data(&self) -> bool {
+        false
+    }
+}
+
+/// An ELF executable with DWARF line information, see
+/// [crate::nma::object_bytes]
+pub struct ElfWriter;
+
+impl OutputWriter for ElfWriter {
+    fn write(
+        &self,
+        assembled: &AssembledObject,
+        source_file: &str,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        out.write_all(&object_bytes(assembled, source_file))
+    }
+}
+
+/// A relocatable `object` document for `name link`
+pub struct ObjectWriter;
+
+impl OutputWriter for ObjectWriter {
+    fn write(&self, assembled: &AssembledObject, _: &str, out: &mut dyn Write) -> io::Result<()> {
+        let json = object_to_string(assembled).map_err(|e| io::Error::other(e.to_string()))?;
+        out.write_all(json.as_bytes())
+    }
+
+    fn relocatable(&self) -> bool {
+        true
+    }
+}
+
+/// Bytes per data record in Intel HEX and S-record output
+const RECORD_BYTES: usize = 16;
+
+/// Splits `section` into records of at most [RECORD_BYTES], none of which
+/// crosses a 64KB boundary, paired with their address
+fn records(section: &Section) -> Vec<(u32, &[u8])> {
+    let mut records = vec![];
+    let mut offset = 0;
+    while offset < section.data.len() {
+        let address = section.address + offset as u32;
+        let to_boundary = 0x10000 - (address & 0xffff) as usize;
+        let length = RECORD_BYTES
+            .min(section.data.len() - offset)
+            .min(to_boundary);
+        records.push((address, &section.data[offset..offset + length]));
+        offset += length;
+    }
+    records
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Intel HEX with 32-bit addresses: extended linear address records for
+/// the upper half of each address, data records, a start linear address
+/// record for the entry point and an end of file record
+pub struct IntelHexWriter;
+
+impl IntelHexWriter {
+    fn record(out: &mut dyn Write, address: u16, kind: u8, data: &[u8]) -> io::Result<()> {
+        let mut bytes = vec![data.len() as u8];
+        bytes.extend(address.to_be_bytes());
+        bytes.push(kind);
+        bytes.extend(data);
+        let checksum = bytes
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+            .wrapping_neg();
+        bytes.push(checksum);
+        writeln!(out, ":{}", hex_bytes(&bytes))
+    }
+}
+
+impl OutputWriter for IntelHexWriter {
+    fn write(&self, assembled: &AssembledObject, _: &str, out: &mut dyn Write) -> io::Result<()> {
+        let mut upper: Option<u16> = None;
+        for section in &assembled.sections {
+            for (address, data) in records(section) {
+                let high = (address >> 16) as u16;
+                if upper != Some(high) {
+                    IntelHexWriter::record(out, 0, 0x04, &high.to_be_bytes())?;
+                    upper = Some(high);
+                }
+                IntelHexWriter::record(out, address as u16, 0x00, data)?;
+            }
+        }
+        IntelHexWriter::record(out, 0, 0x05, &assembled.entry.to_be_bytes())?;
+        IntelHexWriter::record(out, 0, 0x01, &[])
+    }
+}
+
+/// Motorola S-records with 32-bit addresses: an S0 header naming the
+/// source file, S3 data records, an S5 record count and an S7 record
+/// holding the entry point
+pub struct SrecWriter;
+
+impl SrecWriter {
+    fn record(out: &mut dyn Write, kind: u8, address: &[u8], data: &[u8]) -> io::Result<()> {
+        let mut bytes = vec![(address.len() + data.len() + 1) as u8];
+        bytes.extend(address);
+        bytes.extend(data);
+        let checksum = !bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        bytes.push(checksum);
+        writeln!(out, "S{}{}", kind, hex_bytes(&bytes))
+    }
+}
+
+impl OutputWriter for SrecWriter {
+    fn write(
+        &self,
+        assembled: &AssembledObject,
+        source_file: &str,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let header = &source_file.as_bytes()[..source_file.len().min(64)];
+        SrecWriter::record(out, 0, &[0, 0], header)?;
+        let mut count: u32 = 0;
+        for section in &assembled.sections {
+            for (address, data) in records(section) {
+                SrecWriter::record(out, 3, &address.to_be_bytes(), data)?;
+                count += 1;
+            }
+        }
+        // S5 only has room for a 16-bit count, and is optional
+        if let Ok(count) = u16::try_from(count) {
+            SrecWriter::record(out, 5, &count.to_be_bytes(), &[])?;
+        }
+        SrecWriter::record(out, 7, &assembled.entry.to_be_bytes(), &[])
+    }
+}
+
+/// Splits `section` into whole words of `width` bytes, with the address of
+/// the first. Sections that do not start or end on a word boundary are
+/// padded with zeros to one
+fn words(section: &Section, assembled: &AssembledObject, width: u32) -> (u32, Vec<u32>) {
+    let lead = (section.address % width) as usize;
+    let mut bytes = vec![0; lead];
+    bytes.extend(&section.data);
+    (
+        section.address - lead as u32,
+        split_words(&bytes, assembled, width),
+    )
+}
+
+/// `bytes` as words of `width` bytes in the program's byte order, the last
+/// padded with zeros
+fn split_words(bytes: &[u8], assembled: &AssembledObject, width: u32) -> Vec<u32> {
+    bytes
+        .chunks(width as usize)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            match width {
+                1 => u32::from(word[0]),
+                2 => u32::from(assembled.endian.u16_from_bytes([word[0], word[1]])),
+                _ => assembled.endian.u32_from_bytes(word),
+            }
+        })
+        .collect()
+}
+
+/// A word in as many hex digits as the memory is wide
+fn hex_word(word: u32, shape: &MemoryShape) -> String {
+    format!("{:0width$x}", word, width = (shape.width / 4) as usize)
+}
+
+/// One word per line for Verilog's `$readmemh`, each section starting with
+/// an `@` word address, so a memory declared as `reg [31:0] mem [...]` and
+/// indexed by address / 4 can load the program as is. Narrower memories
+/// are indexed by address / 2 or by address
+pub struct ReadmemhWriter(pub MemoryShape);
+
+impl OutputWriter for ReadmemhWriter {
+    fn write(&self, assembled: &AssembledObject, _: &str, out: &mut dyn Write) -> io::Result<()> {
+        let width = self.0.word_bytes();
+        for section in &assembled.sections {
+            let (address, words) = words(section, assembled, width);
+            writeln!(out, "// {} at 0x{:08x}", section.name, address)?;
+            writeln!(out, "@{:08x}", address / width)?;
+            for word in words {
+                writeln!(out, "{}", hex_word(word, &self.0))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The whole program as one memory, with word 0 at the start of the lowest
+/// section and zeros in any gap between sections, padded to the depth of
+/// the memory if it has one. Returns the address of word 0 and the words
+fn memory_image(assembled: &AssembledObject, shape: &MemoryShape) -> io::Result<(u32, Vec<u32>)> {
+    let width = shape.word_bytes();
+    let start = assembled
+        .sections
+        .iter()
+        .map(|section| section.address - section.address % width)
+        .min()
+        .unwrap_or(0);
+    let end = assembled
+        .sections
+        .iter()
+        .map(|section| section.address as u64 + section.data.len() as u64)
+        .max()
+        .unwrap_or(0);
+    let needed = (end - start as u64).div_ceil(width as u64);
+    let depth = shape.depth.map_or(needed, u64::from);
+    if needed > depth {
+        return Err(io::Error::other(format!(
+            "the program needs {} words of memory, more than the {} of --depth",
+            needed, depth
+        )));
+    }
+    if needed > MAX_IMAGE_WORDS && shape.depth.is_none() {
+        let layout: Vec<String> = assembled
+            .sections
+            .iter()
+            .map(|section| format!("{} at 0x{:08x}", section.name, section.address))
+            .collect();
+        return Err(io::Error::other(format!(
+            "{} are too far apart for one memory; place them together with --text-base and --data-base, or give --depth",
+            layout.join(" and ")
+        )));
+    }
+
+    let mut bytes = vec![0; (end - start as u64) as usize];
+    for section in &assembled.sections {
+        let offset = (section.address - start) as usize;
+        bytes[offset..offset + section.data.len()].copy_from_slice(&section.data);
+    }
+    let mut words = split_words(&bytes, assembled, width);
+    words.resize(depth as usize, 0);
+    Ok((start, words))
+}
+
+/// A Xilinx COE file: the words of [memory_image] in hex, separated by
+/// commas, for the block memory generator to initialize a RAM or ROM with
+pub struct CoeWriter(pub MemoryShape);
+
+impl OutputWriter for CoeWriter {
+    fn write(
+        &self,
+        assembled: &AssembledObject,
+        source_file: &str,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (start, words) = memory_image(assembled, &self.0)?;
+        writeln!(
+            out,
+            "; {}: {} {}-bit words from 0x{:08x}, entry point 0x{:08x}",
+            source_file,
+            words.len(),
+            self.0.width,
+            start,
+            assembled.entry
+        )?;
+        writeln!(out, "memory_initialization_radix=16;")?;
+        writeln!(out, "memory_initialization_vector=")?;
+        let lines: Vec<String> = words.iter().map(|word| hex_word(*word, &self.0)).collect();
+        writeln!(out, "{};", lines.join(",\n"))
+    }
+}
+
+/// An Altera MIF file: the words of [memory_image], each at its word
+/// address, with runs of zeros at the end written as one range
+pub struct MifWriter(pub MemoryShape);
+
+impl OutputWriter for MifWriter {
+    fn write(
+        &self,
+        assembled: &AssembledObject,
+        source_file: &str,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (start, words) = memory_image(assembled, &self.0)?;
+        writeln!(
+            out,
+            "-- {}: {}-bit words from 0x{:08x}, entry point 0x{:08x}",
+            source_file, self.0.width, start, assembled.entry
+        )?;
+        writeln!(out, "WIDTH={};", self.0.width)?;
+        writeln!(out, "DEPTH={};", words.len())?;
+        writeln!(out, "ADDRESS_RADIX=HEX;")?;
+        writeln!(out, "DATA_RADIX=HEX;")?;
+        writeln!(out, "CONTENT BEGIN")?;
+        let used = words
+            .iter()
+            .rposition(|word| *word != 0)
+            .map_or(0, |last| last + 1);
+        for (address, word) in words[..used].iter().enumerate() {
+            writeln!(out, "    {:x} : {};", address, hex_word(*word, &self.0))?;
+        }
+        match words.len() - used {
+            0 => (),
+            1 => writeln!(out, "    {:x} : {};", used, hex_word(0, &self.0))?,
+            _ => writeln!(
+                out,
+                "    [{:x}..{:x}] : {};",
+                used,
+                words.len() - 1,
+                hex_word(0, &self.0)
+            )?,
+        }
+        writeln!(out, "END;")
+    }
+}
+
+/// Every word of every section with its address, and for instructions the
+/// instruction and the line of source it came from:
+///
+/// ```text
+/// 00400000  3c011001  lui $at 4097  # prog.asm:9
+/// ```
+pub struct AnnotatedWriter;
+
+impl OutputWriter for AnnotatedWriter {
+    fn write(&self, assembled: &AssembledObject, _: &str, out: &mut dyn Write) -> io::Result<()> {
+        let lineinfo = &assembled.lineinfo;
+        let by_address: BTreeMap<u32, _> = lineinfo
+            .lines
+            .iter()
+            .map(|li| (li.instr_addr, li))
+            .collect();
+        writeln!(out, "# entry point 0x{:08x}", assembled.entry)?;
+        for section in &assembled.sections {
+            writeln!(out, "\n# {} at 0x{:08x}", section.name, section.address)?;
+            let (start, words) = words(section, assembled, MIPS_INSTR_BYTE_WIDTH);
+            for (index, word) in words.into_iter().enumerate() {
+                let address = start + index as u32 * MIPS_INSTR_BYTE_WIDTH;
+                let line = format!("{:08x}  {:08x}", address, word);
+                match by_address.get(&address) {
+                    Some(li) => writeln!(
+                        out,
+                        "{}  {:<24}  # {}:{}",
+                        line,
+                        li.line_contents,
+                        lineinfo
+                            .files
+                            .get(li.file as usize)
+                            .map_or("", String::as_str),
+                        li.span.line
+                    )?,
+                    None => writeln!(out, "{}", line)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
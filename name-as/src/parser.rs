@@ -2,49 +2,122 @@ use pest::iterators::Pair;
 use pest_derive::Parser;
 
 #[derive(Parser)]
-#[grammar_inline = r#"
+#[grammar_inline = r##"
 alpha = _{ 'a'..'z' | 'A'..'Z' }
 digit = _{ '0'..'9' }
-WHITESPACE = _{ " " | NEWLINE }
+WHITESPACE = _{ " " | "\t" }
+COMMENT = _{ "#" ~ (!NEWLINE ~ ANY)* }
 
-ident = @{ alpha ~ (alpha | digit)* }
+ident = @{ (alpha | "_") ~ (alpha | digit | "_")* }
 
 label = { ident ~ ":" }
 
-register = @{ "$" ~ ident }
-instruction_arg = @{ ident | register | digit+ }
+// Floating-point mnemonics carry their format after a dot, e.g. `add.s`
+mnemonic = @{ alpha ~ (alpha | digit | ".")* }
+
+register = @{ "$" ~ (ident | ASCII_DIGIT+) }
+// Numbers, labels and constant expressions of them such as `-4`,
+// `(SIZE * 4) + 8` or `table+4`, worked out by expr.rs
+space = _{ " " | "\t" }
+operator = _{ "<<" | ">>" | "+" | "-" | "*" | "/" | "%" | "&" | "^" | "|" }
+term = _{
+    ("-" | "~" | "+")* ~ space* ~
+    ("(" ~ space* ~ expression ~ space* ~ ")" | "0x" ~ ASCII_HEX_DIGIT+ | digit+ | ident)
+}
+expression = _{ term ~ (space* ~ operator ~ space* ~ term)* }
+instruction_arg = @{ register | expression }
 standard_args = _{ 
-   instruction_arg ~ ("," ~ WHITESPACE* ~ instruction_arg){, 2}
+   instruction_arg ~ ("," ~ instruction_arg)*
+}
+// `lw $t0, ($sp)` leaves out the offset, which is then 0
+no_offset = { &"(" }
+mem_access_args = _{
+    instruction_arg ~ "," ~ (instruction_arg | no_offset) ~ "(" ~ instruction_arg ~ ")"
+}
+// `lw $t0, ($t1), 4` puts the base first, see operands.rs
+base_first_args = _{
+    instruction_arg ~ "," ~ "(" ~ instruction_arg ~ ")" ~ "," ~ instruction_arg
 }
-mem_access_args = _{ instruction_arg ~ "," ~ instruction_arg ~ "(" ~ instruction_arg ~ ")" }
-instruction_args = _{ mem_access_args | standard_args }
-instruction = { ident ~ instruction_args }
+instruction_args = _{ base_first_args | mem_access_args | standard_args }
+instruction = { mnemonic ~ instruction_args? }
 
-vernacular = { (instruction | label)* }
-"#]
+// Assembler directives such as `.data`, `.word 1, 2` or `.asciiz "hi\n"`
+string = @{ "\"" ~ ("\\" ~ ANY | !("\"" | NEWLINE) ~ ANY)* ~ "\"" }
+// Only `.float` takes decimals, such as `-2.5` or `6.02e23`
+decimal = _{ "-"? ~ digit+ ~ "." ~ digit+ ~ (("e" | "E") ~ "-"? ~ digit+)? }
+directive_name = @{ "." ~ alpha+ }
+directive_arg = @{ string | decimal | expression }
+// The comma is optional, as in `.eqv SIZE 16`
+directive = { directive_name ~ (directive_arg ~ (","? ~ directive_arg)*)? }
+
+// Instructions and directives end at the end of the line, any number of
+// labels may precede one
+line = _{ label* ~ (directive | instruction)? }
+vernacular = { SOI ~ line ~ (NEWLINE ~ line)* ~ EOI }
+"##]
 pub struct MipsParser;
 
+/// A piece of source text along with where it was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Token {
+    fn from_pair(pair: &Pair<Rule>) -> Token {
+        let (line, column) = pair.as_span().start_pos().line_col();
+        Token {
+            text: pair.as_str().to_string(),
+            line,
+            column,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
 #[derive(Debug, Clone)]
-pub enum MipsCST<'a> {
-    Label(&'a str),
-    Instruction(&'a str, Vec<&'a str>),
-    Sequence(Vec<MipsCST<'a>>),
+pub enum MipsCST {
+    Label(Token),
+    Instruction(Token, Vec<Token>),
+    Directive(Token, Vec<Token>),
+    Sequence(Vec<MipsCST>),
 }
 
 pub fn parse_rule(pair: Pair<Rule>) -> MipsCST {
     match pair.as_rule() {
-        Rule::vernacular => MipsCST::Sequence(pair.into_inner().map(parse_rule).collect()),
-        Rule::label => MipsCST::Label(pair.into_inner().next().unwrap().as_str()),
+        Rule::vernacular => MipsCST::Sequence(
+            pair.into_inner()
+                .filter(|p| p.as_rule() != Rule::EOI)
+                .map(parse_rule)
+                .collect(),
+        ),
+        Rule::label => MipsCST::Label(Token::from_pair(&pair.into_inner().next().unwrap())),
         Rule::instruction => {
             let mut inner = pair.into_inner();
-            let opcode = inner.next().unwrap().as_str();
-            let args = inner.clone().map(|p| p.as_str()).collect::<Vec<&str>>();
+            let opcode = Token::from_pair(&inner.next().unwrap());
+            let args = inner
+                .map(|p| match p.as_rule() {
+                    Rule::no_offset => Token {
+                        text: "0".to_string(),
+                        ..Token::from_pair(&p)
+                    },
+                    _ => Token::from_pair(&p),
+                })
+                .collect::<Vec<Token>>();
             MipsCST::Instruction(opcode, args)
         }
-        _ => {
-            println!("Unreachable: {:?}", pair.as_rule());
-            unreachable!()
+        Rule::directive => {
+            let mut inner = pair.into_inner();
+            let name = Token::from_pair(&inner.next().unwrap());
+            let args = inner.map(|p| Token::from_pair(&p)).collect::<Vec<Token>>();
+            MipsCST::Directive(name, args)
         }
+        rule => unreachable!("unexpected rule {:?}", rule),
     }
 }
 
@@ -59,8 +132,15 @@ pub fn cst_map(cst: &MipsCST, f: fn(&MipsCST) -> ()) {
 
 pub fn print_cst(cst: &MipsCST) {
     match cst {
-        MipsCST::Label(s) => println!("{}:", s),
-        MipsCST::Instruction(mnemonic, args) => println!("\t{} {}", mnemonic, args.join(", ")),
+        MipsCST::Label(s) => eprintln!("{}:", s.as_str()),
+        MipsCST::Instruction(mnemonic, args) | MipsCST::Directive(mnemonic, args) => eprintln!(
+            "\t{} {}",
+            mnemonic.as_str(),
+            args.iter()
+                .map(Token::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
         MipsCST::Sequence(v) => {
             for sub_cst in v {
                 print_cst(sub_cst)
@@ -69,6 +149,10 @@ pub fn print_cst(cst: &MipsCST) {
     }
 }
 
-pub fn instr_to_str(mnemonic: &str, args: &[&str]) -> String {
-    format!("{} {}", mnemonic, args.join(" "))
+pub fn instr_to_str(mnemonic: &Token, args: &[Token]) -> String {
+    format!(
+        "{} {}",
+        mnemonic.as_str(),
+        args.iter().map(Token::as_str).collect::<Vec<_>>().join(" ")
+    )
 }
@@ -0,0 +1,270 @@
+/// Pseudo-instructions, which the assembler rewrites into one or more real
+/// instructions before encoding
+use crate::error::{AssemblerError, Location};
+use crate::expr::Expr;
+use crate::nma::{check_operands, is_memory_access, label_address, parse_int};
+use crate::parser::Token;
+use name_core::register::Register;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// What the assembler does when it meets a pseudo-instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PseudoPolicy {
+    /// Expand it without comment
+    #[default]
+    Allow,
+    /// Expand it, with a warning showing what it became
+    Warn,
+    /// Reject it, for assignments that must be written in real
+    /// instructions only
+    Forbid,
+    /// Expand it and mark each instruction it became in the listing
+    Annotate,
+}
+
+impl std::str::FromStr for PseudoPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(PseudoPolicy::Allow),
+            "warn" => Ok(PseudoPolicy::Warn),
+            "forbid" => Ok(PseudoPolicy::Forbid),
+            "annotate" => Ok(PseudoPolicy::Annotate),
+            _ => Err(format!(
+                "unknown pseudo-instruction policy `{}`, expected allow, warn, forbid or annotate",
+                s
+            )),
+        }
+    }
+}
+
+/// A real instruction produced by expanding a pseudo-instruction
+pub type Expanded = (Token, Vec<Token>);
+
+/// Whether `mnemonic args` is a pseudo-instruction: `li`, `la`, `move`,
+/// `neg`, `subi`, a branch on a comparison, or a load or store from a label
+pub fn is_pseudo(mnemonic: &str, args: &[Token]) -> bool {
+    matches!(mnemonic, "li" | "la" | "move" | "neg" | "subi")
+        || is_pseudo_branch(mnemonic)
+        || memory_label(mnemonic, args).is_some()
+}
+
+/// Whether `mnemonic` is a pseudo-instruction that branches, to the label
+/// that is its last operand
+pub fn is_pseudo_branch(mnemonic: &str) -> bool {
+    matches!(mnemonic, "beqz" | "bnez" | "blt" | "bgt" | "ble" | "bge")
+}
+
+/// The upper half of `address` for `lui`, rounded up when the lower half
+/// will be added as a negative offset
+pub fn hi_adjusted(address: u32) -> u32 {
+    address.wrapping_add(0x8000) >> 16
+}
+
+/// The label a load or store takes its address from, as in `lw $t0, table`
+/// or `lw $t0, table+8($t1)`, if it names one. Its address needs all 32
+/// bits, so unlike a numeric offset it cannot be encoded in the instruction
+pub fn memory_label<'a>(mnemonic: &str, args: &'a [Token]) -> Option<&'a Token> {
+    if !is_memory_access(mnemonic) || !(2..=3).contains(&args.len()) {
+        return None;
+    }
+    let offset = &args[1];
+    Expr::parse(offset.as_str())
+        .is_some_and(|expr| !expr.names().is_empty())
+        .then_some(offset)
+}
+
+/// How many real instructions a pseudo-instruction expands into. This has
+/// to be known before labels are, so that addresses can be assigned
+pub fn expanded_len(mnemonic: &Token, args: &[Token]) -> Result<u32, AssemblerError> {
+    Ok(expand(mnemonic, args, None)?.len() as u32)
+}
+
+/// Rewrites a pseudo-instruction into real instructions, the same ones MARS
+/// expands it into. Without `labels`, every label is taken to be at address
+/// 0, which is enough to count the instructions but not to encode them
+pub fn expand(
+    mnemonic: &Token,
+    args: &[Token],
+    labels: Option<&BTreeMap<String, u32>>,
+) -> Result<Vec<Expanded>, AssemblerError> {
+    match mnemonic.as_str() {
+        // Load a 32-bit constant, in as few instructions as it fits in
+        "li" => {
+            check_operands(mnemonic, args, &["$rt", "imm"], "$rt, imm")?;
+            let (rt, imm) = (&args[0], &args[1]);
+            let value = parse_int(imm)?;
+
+            if !(i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(&value) {
+                return Err(AssemblerError::InvalidImmediate {
+                    location: Location::at(imm).into(),
+                    token: imm.text.clone(),
+                    message: format!("immediate `{}` does not fit in 32 bits", imm.as_str()),
+                });
+            }
+
+            if (i64::from(i16::MIN)..=i64::from(i16::MAX)).contains(&value) {
+                Ok(vec![instr(
+                    mnemonic,
+                    "addiu",
+                    [rt, &reg(mnemonic, "$zero"), imm],
+                )])
+            } else if (0..=0xffff).contains(&value) {
+                Ok(vec![instr(
+                    mnemonic,
+                    "ori",
+                    [rt, &reg(mnemonic, "$zero"), imm],
+                )])
+            } else {
+                let value = value as u32;
+                let hi = synthesize(imm, (value >> 16).to_string());
+                let lo = synthesize(imm, (value & 0xffff).to_string());
+                let at = reg(mnemonic, "$at");
+                Ok(vec![
+                    instr(mnemonic, "lui", [&at, &hi]),
+                    instr(mnemonic, "ori", [rt, &at, &lo]),
+                ])
+            }
+        }
+        "move" => {
+            check_operands(mnemonic, args, &["$rd", "$rs"], "$rd, $rs")?;
+            let zero = reg(mnemonic, "$zero");
+            Ok(vec![instr(mnemonic, "addu", [&args[0], &zero, &args[1]])])
+        }
+        "neg" => {
+            check_operands(mnemonic, args, &["$rd", "$rs"], "$rd, $rs")?;
+            let zero = reg(mnemonic, "$zero");
+            Ok(vec![instr(mnemonic, "sub", [&args[0], &zero, &args[1]])])
+        }
+        // Subtract a constant, which is first built in $at
+        "subi" => {
+            check_operands(mnemonic, args, &["$rt", "$rs", "imm"], "$rt, $rs, imm")?;
+            let (rt, rs, imm) = (&args[0], &args[1], &args[2]);
+            let value = parse_int(imm)?;
+            let at = reg(mnemonic, "$at");
+            let mut expanded = if (i64::from(i16::MIN)..=i64::from(i16::MAX)).contains(&value) {
+                vec![instr(mnemonic, "addi", [&at, &reg(mnemonic, "$zero"), imm])]
+            } else if (i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(&value) {
+                let value = value as u32;
+                let hi = synthesize(imm, (value >> 16).to_string());
+                let lo = synthesize(imm, (value & 0xffff).to_string());
+                vec![
+                    instr(mnemonic, "lui", [&at, &hi]),
+                    instr(mnemonic, "ori", [&at, &at, &lo]),
+                ]
+            } else {
+                return Err(AssemblerError::InvalidImmediate {
+                    location: Location::at(imm).into(),
+                    token: imm.text.clone(),
+                    message: format!("immediate `{}` does not fit in 32 bits", imm.as_str()),
+                });
+            };
+            expanded.push(instr(mnemonic, "sub", [rt, rs, &at]));
+            Ok(expanded)
+        }
+        "beqz" | "bnez" => {
+            check_operands(mnemonic, args, &["$rs", "label"], "$rs, label")?;
+            let branch = if mnemonic.as_str() == "beqz" {
+                "beq"
+            } else {
+                "bne"
+            };
+            let zero = reg(mnemonic, "$zero");
+            Ok(vec![instr(mnemonic, branch, [&args[0], &zero, &args[1]])])
+        }
+        // Branch on a comparison of two registers: `slt` sets $at, with the
+        // operands swapped for `bgt` and `ble`, and the branch tests it
+        "blt" | "bgt" | "ble" | "bge" => {
+            check_operands(mnemonic, args, &["$rs", "$rt", "label"], "$rs, $rt, label")?;
+            let (rs, rt, label) = (&args[0], &args[1], &args[2]);
+            let (first, second) = match mnemonic.as_str() {
+                "blt" | "bge" => (rs, rt),
+                _ => (rt, rs),
+            };
+            let branch = match mnemonic.as_str() {
+                "blt" | "bgt" => "bne",
+                _ => "beq",
+            };
+            let at = reg(mnemonic, "$at");
+            Ok(vec![
+                instr(mnemonic, "slt", [&at, first, second]),
+                instr(mnemonic, branch, [&at, &reg(mnemonic, "$zero"), label]),
+            ])
+        }
+        // Load the address of a label. Always two instructions, since the
+        // address isn't known when the layout is decided
+        "la" => {
+            check_operands(mnemonic, args, &["$rt", "label"], "$rt, label")?;
+            let (rt, label) = (&args[0], &args[1]);
+            let address = match labels {
+                Some(labels) => label_address(labels, label)?,
+                None => 0,
+            };
+
+            let at = reg(mnemonic, "$at");
+            let hi = synthesize(label, (address >> 16).to_string());
+            let lo = synthesize(label, (address & 0xffff).to_string());
+            Ok(vec![
+                instr(mnemonic, "lui", [&at, &hi]),
+                instr(mnemonic, "ori", [rt, &at, &lo]),
+            ])
+        }
+        // Load or store at a label, plus a base register if one is given.
+        // The upper half of the address goes in $at, and the lower half is
+        // the offset of the instruction itself
+        _ if memory_label(mnemonic.as_str(), args).is_some() => {
+            let (rt, label, base) = (&args[0], &args[1], args.get(2));
+            if base.is_some_and(|base| base.as_str().parse::<Register>() == Ok(Register::At)) {
+                return Err(AssemblerError::OperandType {
+                    location: Location::at(&args[2]).into(),
+                    token: args[2].text.clone(),
+                    message: format!(
+                        "`{}` from a label builds the address in $at, so $at cannot be its base register",
+                        mnemonic.as_str()
+                    ),
+                });
+            }
+            let address = match labels {
+                Some(labels) => label_address(labels, label)?,
+                None => 0,
+            };
+
+            let at = reg(mnemonic, "$at");
+            let hi = synthesize(label, hi_adjusted(address).to_string());
+            let lo = synthesize(label, (address as u16 as i16).to_string());
+            let mut expanded = vec![instr(mnemonic, "lui", [&at, &hi])];
+            if let Some(base) =
+                base.filter(|base| base.as_str().parse::<Register>() != Ok(Register::Zero))
+            {
+                expanded.push(instr(mnemonic, "addu", [&at, &at, base]));
+            }
+            expanded.push(instr(mnemonic, mnemonic.as_str(), [rt, &lo, &at]));
+            Ok(expanded)
+        }
+        _ => unreachable!("{} is not a pseudo-instruction", mnemonic.as_str()),
+    }
+}
+
+/// A token that does not appear in the source, reported at the position of
+/// the token it was derived from
+fn synthesize(origin: &Token, text: String) -> Token {
+    Token {
+        text,
+        line: origin.line,
+        column: origin.column,
+    }
+}
+
+fn reg(origin: &Token, name: &str) -> Token {
+    synthesize(origin, name.to_string())
+}
+
+fn instr<const N: usize>(origin: &Token, mnemonic: &str, args: [&Token; N]) -> Expanded {
+    (
+        synthesize(origin, mnemonic.to_string()),
+        args.into_iter().cloned().collect(),
+    )
+}
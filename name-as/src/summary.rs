@@ -0,0 +1,150 @@
+/// A short report of what a program assembled into: how much of each
+/// region of memory its sections take up and how many instructions it has,
+/// for a quick check that it fits the memory map it will run in
+use crate::nma::{AssembledObject, DATA_SECTION, MIPS_INSTR_BYTE_WIDTH, TEXT_SECTION};
+use name_core::memmap;
+use name_core::schema;
+use serde::Serialize;
+use std::fmt;
+
+/// How much room name-emu gives `.text`
+pub const TEXT_REGION_SIZE: u32 = memmap::TEXT_SIZE;
+/// How much room name-emu gives `.data`, up to the start of the heap
+pub const DATA_REGION_SIZE: u32 = memmap::HEAP_START - memmap::DATA_START;
+
+const SUMMARY_KIND: &str = "summary";
+
+/// How the summary is written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// A few lines for people
+    Text,
+    /// A `summary` document, see [name_core::schema]
+    Json,
+}
+
+impl std::str::FromStr for SummaryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SummaryFormat::Text),
+            "json" => Ok(SummaryFormat::Json),
+            _ => Err(format!(
+                "unknown summary format `{}`, expected `text` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// The size of each region sections are loaded into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Regions {
+    pub text: u32,
+    pub data: u32,
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        Regions {
+            text: TEXT_REGION_SIZE,
+            data: DATA_REGION_SIZE,
+        }
+    }
+}
+
+/// How much of its region one section uses
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionUsage {
+    pub name: String,
+    pub address: u32,
+    pub bytes: u32,
+    pub region_size: u32,
+    pub percent_used: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// `.text` then `.data`, listed even when empty
+    pub sections: Vec<SectionUsage>,
+    /// Real instructions in `.text`, counting those pseudo-instructions
+    /// and delay slots added
+    pub instructions: u32,
+    /// Pseudo-instructions in the source
+    pub pseudo_instructions: u32,
+    /// Real instructions the pseudo-instructions expanded into
+    pub expanded_instructions: u32,
+}
+
+impl Summary {
+    pub fn new(assembled: &AssembledObject, regions: Regions) -> Summary {
+        let usage = |name: &str, address: u32, region_size: u32| {
+            let bytes = assembled
+                .section(name)
+                .map_or(0, |section| section.data.len() as u32);
+            SectionUsage {
+                name: name.to_string(),
+                address,
+                bytes,
+                region_size,
+                percent_used: match region_size {
+                    0 => 0.0,
+                    size => bytes as f64 * 100.0 / size as f64,
+                },
+            }
+        };
+        let lines = &assembled.lineinfo.lines;
+        Summary {
+            sections: vec![
+                usage(TEXT_SECTION, assembled.text_address(), regions.text),
+                usage(DATA_SECTION, assembled.data_address(), regions.data),
+            ],
+            instructions: assembled.text().len() as u32 / MIPS_INSTR_BYTE_WIDTH,
+            pseudo_instructions: assembled.lineinfo.pseudo_ops.len() as u32,
+            expanded_instructions: lines.iter().filter(|li| li.pseudo_op.is_some()).count() as u32,
+        }
+    }
+
+    /// The summary as written to stdout by `--summary`
+    pub fn render(&self, format: SummaryFormat) -> Result<String, Box<dyn std::error::Error>> {
+        match format {
+            SummaryFormat::Text => Ok(format!("{}\n", self)),
+            SummaryFormat::Json => schema::to_json(SUMMARY_KIND, self),
+        }
+    }
+}
+
+/// ```text
+/// .text     184 bytes at 0x00400000,   4.5% of 4096
+/// .data      24 bytes at 0x10010000,   0.0% of 196608
+/// 46 instructions, 6 of them from 3 pseudo-instructions
+/// ```
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for section in &self.sections {
+            write!(
+                f,
+                "{:<6} {:>6} bytes at 0x{:08x}, {:>5.1}% of {}",
+                section.name,
+                section.bytes,
+                section.address,
+                section.percent_used,
+                section.region_size
+            )?;
+            if section.bytes > section.region_size {
+                write!(
+                    f,
+                    ", {} bytes too large",
+                    section.bytes - section.region_size
+                )?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{} instructions, {} of them from {} pseudo-instructions",
+            self.instructions, self.expanded_instructions, self.pseudo_instructions
+        )
+    }
+}
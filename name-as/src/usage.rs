@@ -0,0 +1,88 @@
+/// Usage statistics for instructors: how often each kind of error and
+/// warning comes up in each assignment, so a course can see which mistakes
+/// its students make most. Nothing is recorded unless a log file is given
+/// with `--usage-log`, and nothing is sent anywhere; the log holds counts by
+/// diagnostic code and nothing of the source or who wrote it.
+///
+/// Each run adds to the log, a `usage` document (see [name_core::schema]):
+///
+/// ```json
+/// { "kind": "usage", "version": 1, "assignments": {
+///     "lab3": { "runs": 12, "clean": 4,
+///               "diagnostics": { "undeclared-label": 5, "syntax": 3 } } } }
+/// ```
+///
+/// Logs collected from a class can be added up with `name usage-report`
+use name_core::schema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const USAGE_KIND: &str = "usage";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct AssignmentUsage {
+    /// Times the assignment was assembled
+    pub runs: u64,
+    /// Runs with no errors or warnings
+    pub clean: u64,
+    /// How many times each diagnostic code was reported
+    pub diagnostics: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct UsageLog {
+    pub assignments: BTreeMap<String, AssignmentUsage>,
+}
+
+impl UsageLog {
+    /// Counts one run of `assignment` that reported `codes`
+    pub fn record(&mut self, assignment: &str, codes: &[&str]) {
+        let usage = self.assignments.entry(assignment.to_string()).or_default();
+        usage.runs += 1;
+        if codes.is_empty() {
+            usage.clean += 1;
+        }
+        for code in codes {
+            *usage.diagnostics.entry(code.to_string()).or_default() += 1;
+        }
+    }
+
+    /// Adds the counts of `other` to these
+    pub fn merge(&mut self, other: UsageLog) {
+        for (assignment, theirs) in other.assignments {
+            let usage = self.assignments.entry(assignment).or_default();
+            usage.runs += theirs.runs;
+            usage.clean += theirs.clean;
+            for (code, count) in theirs.diagnostics {
+                *usage.diagnostics.entry(code).or_default() += count;
+            }
+        }
+    }
+
+    /// Reads a log, or an empty one if `path` does not exist yet
+    pub fn load(path: &Path) -> Result<UsageLog, Box<dyn std::error::Error>> {
+        match fs::read_to_string(path) {
+            Ok(json) => schema::from_json(USAGE_KIND, &json),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(UsageLog::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, schema::to_json(USAGE_KIND, self)?)?;
+        Ok(())
+    }
+}
+
+/// Adds one run of `assignment` to the log at `path`
+pub fn record_usage(
+    path: &Path,
+    assignment: &str,
+    codes: &[&str],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut log = UsageLog::load(path)?;
+    log.record(assignment, codes);
+    log.save(path)
+}
@@ -0,0 +1,70 @@
+/// `--watch`: assembling again every time the source is saved, for keeping
+/// a terminal open next to the editor. Each file's directory is watched
+/// rather than the file itself, since many editors save by writing a new
+/// file and renaming it over the old one
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for an editor to finish saving before building, since
+/// one save can set off several events
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Calls `build` once, then again whenever one of `files` changes, until
+/// the process is stopped
+pub fn watch(files: &[PathBuf], mut build: impl FnMut()) -> Result<(), String> {
+    let files: Vec<PathBuf> = files.iter().map(|file| absolute(file)).collect();
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|e| format!("cannot watch files: {}", e))?;
+    for file in &files {
+        let directory = file.parent().unwrap_or(Path::new("."));
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("cannot watch {}: {}", directory.display(), e))?;
+    }
+
+    build();
+    eprintln!("watching for changes, stop with Ctrl-C");
+    loop {
+        let event = receiver
+            .recv()
+            .map_err(|_| "stopped receiving file changes".to_string())?;
+        let Some(changed) = changed_file(event, &files) else {
+            continue;
+        };
+        while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+        eprintln!("\n{} changed, assembling again", changed.display());
+        build();
+    }
+}
+
+/// Which of `files` an event is about, if any
+fn changed_file(event: notify::Result<Event>, files: &[PathBuf]) -> Option<PathBuf> {
+    let event = event.ok()?;
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        return None;
+    }
+    event
+        .paths
+        .iter()
+        .map(|path| absolute(path))
+        .find(|path| files.contains(path))
+}
+
+/// `path` from the root, so that paths from events compare equal to the
+/// ones given on the command line
+fn absolute(path: &Path) -> PathBuf {
+    let path = match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new(".").join(path),
+        _ => path.to_path_buf(),
+    };
+    match (path.parent().map(Path::canonicalize), path.file_name()) {
+        (Some(Ok(directory)), Some(name)) => directory.join(name),
+        _ => path,
+    }
+}
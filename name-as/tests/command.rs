@@ -0,0 +1,64 @@
+// The input and output files filled into a shell line are quoted, so a
+// file name with spaces or shell syntax in it stays one argument and never
+// runs as a command of its own.
+
+use name_as::command::{quote_for_cmd, quote_for_sh, AssemblerCommand};
+
+#[test]
+fn sh_quoting() {
+    assert_eq!(quote_for_sh("prog.asm"), "prog.asm");
+    assert_eq!(quote_for_sh("/tmp/a-b_c.s"), "/tmp/a-b_c.s");
+    assert_eq!(quote_for_sh(""), "''");
+    assert_eq!(quote_for_sh("my prog.asm"), "'my prog.asm'");
+    assert_eq!(quote_for_sh("a;rm -rf ~"), "'a;rm -rf ~'");
+    assert_eq!(quote_for_sh("$(whoami)"), "'$(whoami)'");
+    assert_eq!(quote_for_sh("it's.asm"), "'it'\\''s.asm'");
+}
+
+#[test]
+fn cmd_quoting() {
+    assert_eq!(quote_for_cmd("C:\\mips\\prog.asm"), "C:\\mips\\prog.asm");
+    assert_eq!(quote_for_cmd(""), "\"\"");
+    assert_eq!(quote_for_cmd("my prog.asm"), "\"my prog.asm\"");
+    assert_eq!(quote_for_cmd("a&del b"), "\"a&del b\"");
+    assert_eq!(quote_for_cmd("100%.asm"), "\"100\"^%\".asm\"");
+}
+
+#[test]
+fn argv_is_not_quoted() {
+    let command = AssemblerCommand::Argv(vec![
+        "as".to_string(),
+        "${INPUT}".to_string(),
+        "-o".to_string(),
+        "${OUTPUT}".to_string(),
+    ]);
+    let command = command.to_command("my prog.asm", "out;put.bin").unwrap();
+    let args: Vec<_> = command.get_args().collect();
+    assert_eq!(args, ["my prog.asm", "-o", "out;put.bin"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_line_keeps_file_names_whole() {
+    let dir = std::env::temp_dir().join(format!("name_as_command_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("my prog;touch injected.asm");
+    let output = dir.join("it's out.bin");
+    std::fs::write(&input, "nop\n").unwrap();
+
+    let command = AssemblerCommand::Shell("cp ${INPUT} ${OUTPUT}".to_string());
+    let status = command
+        .to_command(&input.to_string_lossy(), &output.to_string_lossy())
+        .unwrap()
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+
+    let copied = std::fs::read_to_string(&output);
+    let injected = dir.join("injected.asm").exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(status.success());
+    assert_eq!(copied.unwrap(), "nop\n");
+    assert!(!injected);
+}
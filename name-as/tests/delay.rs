@@ -0,0 +1,212 @@
+// Filling delay slots puts a nop after every branch and jump, or with
+// reorder the instruction before it when moving it there can't change
+// what either does. Each program is compared with the one it should
+// assemble to, written out by hand.
+
+use name_as::delay::DelaySlots;
+use name_as::nma::{assemble_source, AssemblerOptions};
+
+fn words(source: &str, delay_slots: DelaySlots) -> Vec<u32> {
+    let options = AssemblerOptions {
+        delay_slots,
+        ..Default::default()
+    };
+    let assembled = assemble_source(source, &options).unwrap();
+    assembled
+        .text()
+        .chunks(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect()
+}
+
+// Asserts that `source` fills its slots to the same code as `expected`,
+// assembled as written
+fn fills_to(source: &str, mode: DelaySlots, expected: &str) {
+    assert_eq!(
+        words(source, mode),
+        words(expected, DelaySlots::Off),
+        "{:?}:{}",
+        mode,
+        source
+    );
+}
+
+const BRANCH: &str = "
+main:   addiu $t1, $t1, 1
+        beq $t0, $zero, main
+";
+
+#[test]
+fn off_assembles_as_written() {
+    fills_to(BRANCH, DelaySlots::Off, BRANCH);
+}
+
+#[test]
+fn nop_follows_every_branch_and_jump() {
+    fills_to(
+        BRANCH,
+        DelaySlots::Nop,
+        "
+main:   addiu $t1, $t1, 1
+        beq $t0, $zero, main
+        nop
+",
+    );
+    fills_to(
+        "
+main:   jal main
+        jr $ra
+",
+        DelaySlots::Nop,
+        "
+main:   jal main
+        nop
+        jr $ra
+        nop
+",
+    );
+}
+
+#[test]
+fn reorder_moves_an_independent_instruction() {
+    fills_to(
+        BRANCH,
+        DelaySlots::Reorder,
+        "
+main:   beq $t0, $zero, main
+        addiu $t1, $t1, 1
+",
+    );
+}
+
+#[test]
+fn reorder_never_moves_what_the_branch_depends_on() {
+    let source = "
+main:   addiu $t0, $t0, -1
+        bne $t0, $zero, main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   addiu $t0, $t0, -1
+        bne $t0, $zero, main
+        nop
+",
+    );
+    // Nor an instruction that reads a register the branch writes
+    let source = "
+main:   addu $t0, $ra, $zero
+        jal main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   addu $t0, $ra, $zero
+        jal main
+        nop
+",
+    );
+    let source = "
+main:   addu $t9, $t0, $zero
+        jr $t9
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   addu $t9, $t0, $zero
+        jr $t9
+        nop
+",
+    );
+    // Nor one that uses $at, which a branch on a comparison sets first
+    let source = "
+main:   addu $t2, $at, $zero
+        blt $t0, $t1, main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   addu $t2, $at, $zero
+        slt $at, $t0, $t1
+        bne $at, $zero, main
+        nop
+",
+    );
+}
+
+#[test]
+fn reorder_never_moves_across_a_label() {
+    // Code that branches to `here` expects the add to have run
+    let source = "
+main:   addiu $t1, $t1, 1
+here:   beq $t0, $zero, main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   addiu $t1, $t1, 1
+here:   beq $t0, $zero, main
+        nop
+",
+    );
+}
+
+#[test]
+fn reorder_never_moves_syscalls_or_slots() {
+    let source = "
+main:   syscall
+        j main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   syscall
+        j main
+        nop
+",
+    );
+    // The add fills the first branch's slot, so the second gets a nop
+    let source = "
+main:   addiu $t1, $t1, 1
+        beq $t0, $zero, main
+        beq $t2, $zero, main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   beq $t0, $zero, main
+        addiu $t1, $t1, 1
+        beq $t2, $zero, main
+        nop
+",
+    );
+}
+
+#[test]
+fn noreorder_leaves_code_as_written() {
+    let source = "
+        .set noreorder
+main:   addiu $t1, $t1, 1
+        beq $t0, $zero, main
+        .set reorder
+        addiu $t1, $t1, 1
+        j main
+";
+    fills_to(
+        source,
+        DelaySlots::Reorder,
+        "
+main:   addiu $t1, $t1, 1
+        beq $t0, $zero, main
+        j main
+        addiu $t1, $t1, 1
+",
+    );
+}
@@ -0,0 +1,74 @@
+// Warnings come from the program as written, only the ones turned on are
+// reported, and those made errors stop it assembling.
+
+use name_as::lint::WarningLevels;
+use name_as::nma::{assemble_source, AssemblerOptions};
+
+const PROGRAM: &str = r#"        .data
+table:  .byte 200, 5
+        .text
+main:   la $t0, table
+        jal table
+spare:  j main
+        addi $t0, $t0, 1
+        li $v0, 1
+        syscall
+"#;
+
+fn options(flags: &[&str]) -> AssemblerOptions {
+    let mut warnings = WarningLevels::default();
+    for flag in flags {
+        warnings.apply(flag).unwrap();
+    }
+    AssemblerOptions {
+        file_name: "lint.asm".to_string(),
+        data_base: Some(0x00500000),
+        warnings,
+        ..Default::default()
+    }
+}
+
+fn codes(flags: &[&str]) -> Vec<(String, usize)> {
+    let assembled = assemble_source(PROGRAM, &options(flags)).unwrap();
+    assembled
+        .warnings
+        .iter()
+        .map(|warning| {
+            let diagnostic = warning.to_diagnostic();
+            (diagnostic.code, diagnostic.span.unwrap().line)
+        })
+        .collect()
+}
+
+#[test]
+fn default_warnings() {
+    assert_eq!(
+        codes(&[]),
+        [
+            ("branch-to-data".to_string(), 5),
+            ("unreachable-code".to_string(), 7)
+        ]
+    );
+}
+
+#[test]
+fn all_warnings_in_source_order() {
+    assert_eq!(
+        codes(&["all", "no-unreachable-code"]),
+        [
+            ("immediate-truncation".to_string(), 2),
+            ("branch-to-data".to_string(), 5),
+            ("unused-label".to_string(), 6),
+            ("missing-exit".to_string(), 9)
+        ]
+    );
+}
+
+#[test]
+fn warnings_made_errors() {
+    let error = assemble_source(PROGRAM, &options(&["error=unreachable-code"])).unwrap_err();
+    assert_eq!(error.code(), "unreachable-code");
+    let error = assemble_source(PROGRAM, &options(&["error", "no-branch-to-data"])).unwrap_err();
+    assert_eq!(error.code(), "unreachable-code");
+    assert!(WarningLevels::default().apply("no-such-warning").is_err());
+}
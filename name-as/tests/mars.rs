@@ -0,0 +1,72 @@
+// A program using everything MARS accepts and NAME does not has to come
+// out of the import as one NAME assembles, laid out as MARS would.
+
+use name_as::mars::{translate_settings, translate_source, NoteKind};
+use name_as::nma::{assemble_source, AssemblerOptions};
+
+const LIBRARY: &str = r#".macro exit
+        li $v0, 10
+        syscall
+.end_macro
+"#;
+
+const PROGRAM: &str = r#".include "library.asm"
+.eqv COUNTER $t0
+.macro countdown (%reg, %from)
+        li %reg, %from
+again:  addi %reg, %reg, -1
+        bne %reg, $zero, again
+.end_macro
+        .data 0x10010000
+zeros:  .word 0:3
+pi:     .double 1.5
+.extern shared 8
+        .text
+main:   li COUNTER, 'A'
+        countdown($t1, 5)
+        countdown($t2, 2)
+        exit
+"#;
+
+#[test]
+fn mars_program_assembles() {
+    let mut include = |name: &str| match name {
+        "library.asm" => Ok(LIBRARY.to_string()),
+        _ => Err("no such file".to_string()),
+    };
+    let imported = translate_source(PROGRAM, &mut include);
+    let assembled = assemble_source(&imported.source, &AssemblerOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, imported.source));
+
+    // 0, 0, 0, then 1.5 aligned to 8 bytes with its low word first
+    let data: Vec<u32> = assembled
+        .data()
+        .chunks(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .take(6)
+        .collect();
+    assert_eq!(data, [0, 0, 0, 0, 0, 0x3ff80000]);
+    // Each use of the macro has its own label
+    assert!(imported.source.contains("again_M1:") && imported.source.contains("again_M2:"));
+    assert!(imported.source.contains("li $t0, 65"));
+    assert!(imported
+        .notes
+        .iter()
+        .all(|note| note.kind != NoteKind::Unsupported));
+}
+
+#[test]
+fn mars_settings_become_a_config() {
+    let (config, notes) =
+        translate_settings("ExtendedAssembler=false\nMemoryConfiguration=CompactDataAtZero\n");
+    let config: name_as::config::Config = toml::from_str(&config).unwrap();
+    assert_eq!(
+        config.pseudo_instructions,
+        Some(name_as::pseudo::PseudoPolicy::Forbid)
+    );
+    assert_eq!(
+        (config.text_base, config.data_base),
+        (Some(0x3000), Some(0))
+    );
+    assert_eq!(notes.len(), 2);
+}
@@ -0,0 +1,78 @@
+// -O only takes out instructions with nothing to do, and the labels after
+// them move back to match.
+
+use name_as::nma::{assemble_source, AssembledObject, AssemblerOptions};
+
+const PROGRAM: &str = r#"        .data
+value:  .word 5
+        .text
+main:   la $t0, value
+        j next
+next:   lw $t1, 0($t0)
+        beq $t1, $zero, main
+        j done
+done:   li $v0, 10
+        syscall
+"#;
+
+fn assemble(optimize: bool) -> AssembledObject {
+    let options = AssemblerOptions {
+        file_name: "optimize.asm".to_string(),
+        data_base: Some(0x00001000),
+        optimize,
+        ..Default::default()
+    };
+    assemble_source(PROGRAM, &options).unwrap()
+}
+
+fn words(assembled: &AssembledObject) -> Vec<u32> {
+    assembled
+        .text()
+        .chunks_exact(4)
+        .map(|word| {
+            assembled
+                .endian
+                .u32_from_bytes([word[0], word[1], word[2], word[3]])
+        })
+        .collect()
+}
+
+#[test]
+fn unoptimized_is_untouched() {
+    let assembled = assemble(false);
+    assert!(assembled.optimizations.is_empty());
+    assert_eq!(words(&assembled).len(), 8);
+}
+
+#[test]
+fn leaves_out_redundant_instructions() {
+    let assembled = assemble(true);
+    let lines: Vec<usize> = assembled.optimizations.iter().map(|o| o.line).collect();
+    assert_eq!(lines, vec![4, 5, 8]);
+    let removed: usize = assembled.optimizations.iter().map(|o| o.removed).sum();
+    assert_eq!(removed, 3);
+
+    let words = words(&assembled);
+    assert_eq!(words.len(), 5);
+    // ori $t0, $zero, 0x1000
+    assert_eq!(words[0], 0x34081000);
+    // lw $t1, 0($t0)
+    assert_eq!(words[1], 0x8d090000);
+    // beq $t1, $zero, main, now two instructions back from its delay slot
+    assert_eq!(words[2], 0x1120fffd);
+}
+
+#[test]
+fn loads_from_low_labels_read_zero() {
+    let options = AssemblerOptions {
+        file_name: "optimize.asm".to_string(),
+        data_base: Some(0x00001000),
+        optimize: true,
+        ..Default::default()
+    };
+    let source = "        .data\nvalue:  .word 5\n        .text\nmain:   lw $t1, value\n";
+    let assembled = assemble_source(source, &options).unwrap();
+    assert_eq!(assembled.optimizations.len(), 1);
+    // lw $t1, 0x1000($zero)
+    assert_eq!(words(&assembled), vec![0x8c091000]);
+}
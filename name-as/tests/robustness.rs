@@ -0,0 +1,399 @@
+// No input may make the assembler panic: whatever the source, assembling it
+// ends in an object or in a diagnostic. Every input here, whether from the
+// corpus of hand-written troublemakers or mutated from a valid program, is
+// assembled with each delay slot and pseudo-instruction setting, and what
+// comes out is rendered the way name-as would, as an error or as every
+// output format and a listing.
+//
+// The mutations are generated from a fixed seed, so a failure always names
+// the same input. An input that once made the assembler panic belongs in
+// CORPUS, so that it keeps being tried whatever the mutations turn up.
+
+use name_as::delay::DelaySlots;
+use name_as::listing::listing;
+use name_as::nma::{assemble_source, AssemblerOptions};
+use name_as::output::OutputFormat;
+use name_as::pseudo::PseudoPolicy;
+use std::panic;
+
+/// Valid programs that mutations start from, between them using every kind
+/// of statement
+const SEEDS: &[&str] = &[
+    include_str!("../.artifacts/mips_test.asm"),
+    r#".eqv SIZE, 4
+.set COUNT, SIZE*2
+        .data
+        .align 2
+table:  .word 1, 2, table+4, -1
+name:   .asciiz "hi\n"
+bytes:  .byte 1, 0x7f, -128
+        .half 0xffff
+        .float 1.5
+buffer: .space COUNT
+        .globl main
+        .extern helper
+        .text
+main:   li $t0, 0x12345678
+        la $a0, table
+        lw $t1, 4($a0)
+        lw $t2, table+8($t1)
+        sb $t2, name
+        lwc1 $f0, ($sp)
+        beq $t0, $t1, done
+        addi $t0, $t0, -(SIZE << 2)
+        jal helper
+        jalr $t1
+done:   jr $ra
+        .org main+64
+        syscall
+"#,
+    r#"        .set noreorder
+loop:   bne $t0, $zero, loop
+        nop
+        .set reorder
+        .set noat
+        add.s $f0, $f1, $f2
+        c.eq.s $f0, $f2
+        bc1t loop
+        .set at
+        sll $t0, $t1, 31
+        sltiu $t0, $t1, 0xffff
+"#,
+];
+
+/// Inputs that make particular trouble for the assembler, as they have in
+/// the past or could
+const CORPUS: &[&str] = &[
+    "",
+    "\n\n\n",
+    ":",
+    "main:",
+    "main: main:",
+    "j",
+    "j main",
+    "jal 0x7fffffff",
+    "beq $t0, $t0, before\nbefore:",
+    "before: nop\nbeq $t0, $t0, before",
+    "beq $t0, $t0, 0x7fffffff",
+    "addi $t0, $t0, 99999999999999999999999",
+    "addi $t0, $t0, -9223372036854775808",
+    "addi $t0, $t0, -(-9223372036854775807-1)",
+    "addi $t0, $t0, 1/0",
+    "addi $t0, $t0, 1%0",
+    "addi $t0, $t0, 1<<64",
+    "addi $t0, $t0, 1<<-1",
+    "addi $t0, $t0, (((((((((1)))))))))",
+    "addi $t0, $t0, ((",
+    "lw $t0, 99999999999($t1)",
+    "lw $t0, ($t1",
+    "lw $t0, label+1($t1)",
+    "lw $t0, ($t1)",
+    "lw $t0, ()",
+    "lw $t0, ($t1)($t1)",
+    "lw $t0, nowhere",
+    "lw $t0, 4, $t1",
+    "lw $t0, ($t1), 4",
+    "lw $t0, ($t1), $t2",
+    "lw $t0, $t1",
+    "lw $t0, ($t1), label",
+    "x: lw $t0, x($at)",
+    "x: lw $t0, x*2($t1)",
+    "x: lw $t0, x+0x7fffffff($t1)",
+    ".set noat\nx: sw $t0, x($t1)",
+    "sll $t0, $t0, -1",
+    "sll $t0, $t0, 4294967296",
+    "add $99, $t0, $t0",
+    "add $, $, $",
+    "add.s $f99, $f0, $f0",
+    "li $t0",
+    "li $t0, 0x1ffffffff",
+    "li $t0, -2147483649",
+    "la $t0",
+    "la $t0, 4",
+    "la $t0, nowhere",
+    "jr",
+    "jalr $t0, $t1, $t2",
+    ".text\n.text\n.data\n.data",
+    ".data\nnop",
+    ".word 1",
+    ".data\n.word",
+    ".data\n.word 99999999999",
+    ".data\n.word label-label",
+    ".data\n.half 70000",
+    ".data\n.byte 300",
+    ".data\n.float",
+    ".data\n.float nan",
+    ".data\n.float 1e999",
+    ".data\n.ascii",
+    ".data\n.asciiz \"\\\"",
+    ".data\n.asciiz \"\\q\"",
+    ".data\n.asciiz \"é\"",
+    ".data\n.space -1",
+    ".data\n.space 0xffffffff",
+    ".data\n.space 0xffffffff\n.space 0xffffffff",
+    ".data\n.align",
+    ".data\n.align 99",
+    ".org",
+    ".org 0",
+    ".org 0xffffffff\nnop\nnop",
+    "nop\n.org 0",
+    ".data\n.org 0xfffffffc\n.word 1, 2",
+    ".size",
+    ".size x, 4",
+    "x: .size x, 0xffffffff",
+    ".globl",
+    ".globl nowhere",
+    ".extern main\nmain:",
+    ".eqv",
+    ".eqv X",
+    ".eqv 4, 4",
+    ".eqv X, X",
+    ".eqv X, 1\n.eqv X, 2",
+    ".eqv MMIO_BASE, 1\n.eqv MMIO_BASE, 2",
+    "MMIO_BASE: lw $t0, MMIO_BASE",
+    "lw $t0, MMIO_CONSOLE_TX",
+    ".set",
+    ".set X",
+    ".set 1, 2, 3",
+    ".set X, 1\n.set X, X*99999999999\n.set X, X*99999999999",
+    ".nonsense",
+    "nonsense $t0",
+    "\u{0}",
+    "\t\t\t",
+    "é: nop",
+    "main: j main+0x7fffffff",
+    "main: j main-0x7fffffff",
+];
+
+/// Bits of assembly that mutations splice into the seeds
+const FRAGMENTS: &[&str] = &[
+    "$",
+    "$t0",
+    "$31",
+    "$32",
+    "$f31",
+    "$zero",
+    ",",
+    ", ",
+    "(",
+    ")",
+    ":",
+    "\"",
+    "\n",
+    " ",
+    "-",
+    "0x",
+    "0xffffffff",
+    "0x7fffffff",
+    "-2147483648",
+    "99999999999999999999",
+    "<<63",
+    "/0",
+    "+",
+    "*",
+    "label",
+    "main",
+    "test",
+    "table",
+    ".text",
+    ".data",
+    ".word",
+    ".space 0xffffffff",
+    ".align 12",
+    ".org 0",
+    ".org 0xfffffff0",
+    ".set noat",
+    ".set noreorder",
+    ".eqv Q, 9",
+    "li",
+    "la",
+    "beq",
+    "j",
+    "jr",
+    "jal",
+    "sll",
+    "lw",
+    "nop",
+    "é",
+];
+
+/// How many mutants are made from each seed
+const MUTANTS_PER_SEED: usize = 500;
+
+/// A small deterministic random number generator, so every run tries the
+/// same inputs
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// The same program with a few random edits
+fn mutate(seed: &str, rng: &mut Rng) -> String {
+    let mut chars: Vec<char> = seed.chars().collect();
+    for _ in 0..1 + rng.below(4) {
+        let at = rng.below(chars.len() + 1);
+        match rng.below(5) {
+            0 if at < chars.len() => {
+                chars.remove(at);
+            }
+            1 => {
+                let fragment = FRAGMENTS[rng.below(FRAGMENTS.len())];
+                chars.splice(at..at, fragment.chars());
+            }
+            2 => chars.truncate(at),
+            3 if at < chars.len() => {
+                let end = (at + 1 + rng.below(12)).min(chars.len());
+                let copied: Vec<char> = chars[at..end].to_vec();
+                let to = rng.below(chars.len() + 1);
+                chars.splice(to..to, copied);
+            }
+            _ => {
+                let mut lines: Vec<String> = chars
+                    .iter()
+                    .collect::<String>()
+                    .lines()
+                    .map(String::from)
+                    .collect();
+                if lines.len() > 1 {
+                    let (a, b) = (rng.below(lines.len()), rng.below(lines.len()));
+                    lines.swap(a, b);
+                }
+                chars = lines.join("\n").chars().collect();
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+/// Assembles `source` every way name-as can, and renders whatever comes
+/// out of it
+fn exercise(source: &str) {
+    for delay_slots in [DelaySlots::Off, DelaySlots::Nop, DelaySlots::Reorder] {
+        for pseudo_instructions in [
+            PseudoPolicy::Allow,
+            PseudoPolicy::Warn,
+            PseudoPolicy::Forbid,
+        ] {
+            let options = AssemblerOptions {
+                file_name: "fuzz.asm".to_string(),
+                delay_slots,
+                pseudo_instructions,
+                ..Default::default()
+            };
+            match assemble_source(source, &options) {
+                Ok(assembled) => {
+                    for warning in &assembled.warnings {
+                        let _ = warning.to_string();
+                    }
+                    for format in [
+                        "binary",
+                        "elf",
+                        "object",
+                        "ihex",
+                        "srec",
+                        "readmemh",
+                        "coe",
+                        "mif",
+                        "annotated",
+                    ] {
+                        let writer = format.parse::<OutputFormat>().unwrap().writer();
+                        let mut out = vec![];
+                        let _ = writer.write(&assembled, "fuzz.asm", &mut out);
+                    }
+                    let _ = listing(source, &assembled, true);
+                }
+                Err(error) => {
+                    let _ = error.to_string();
+                    let _ = error.to_diagnostic();
+                }
+            }
+        }
+    }
+}
+
+/// Everything name-as writes for `source` with the default options: every
+/// output format and the listing, or the error
+fn rendered(source: &str) -> Vec<u8> {
+    let options = AssemblerOptions {
+        file_name: "stable.asm".to_string(),
+        ..Default::default()
+    };
+    let mut out = vec![];
+    match assemble_source(source, &options) {
+        Ok(assembled) => {
+            for warning in &assembled.warnings {
+                out.extend(warning.to_string().bytes());
+            }
+            for format in ["elf", "object", "annotated"] {
+                let writer = format.parse::<OutputFormat>().unwrap().writer();
+                let _ = writer.write(&assembled, "stable.asm", &mut out);
+            }
+            out.extend(listing(source, &assembled, true).bytes());
+        }
+        Err(error) => out.extend(error.to_string().bytes()),
+    }
+    out
+}
+
+/// Runs `exercise` on every input, returning the ones that panicked
+fn panicking<'a>(inputs: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    panic::set_hook(Box::new(|_| {}));
+    let failed = inputs
+        .into_iter()
+        .filter(|input| panic::catch_unwind(|| exercise(input)).is_err())
+        .cloned()
+        .collect();
+    let _ = panic::take_hook();
+    failed
+}
+
+#[test]
+fn corpus_does_not_panic() {
+    let inputs: Vec<String> = SEEDS
+        .iter()
+        .chain(CORPUS)
+        .map(|input| input.to_string())
+        .collect();
+    let failed = panicking(&inputs);
+    assert!(failed.is_empty(), "the assembler panicked on {:#?}", failed);
+}
+
+#[test]
+fn mutants_do_not_panic() {
+    let mut rng = Rng(0x4e414d45);
+    let inputs: Vec<String> = SEEDS
+        .iter()
+        .flat_map(|seed| {
+            (0..MUTANTS_PER_SEED)
+                .map(|_| mutate(seed, &mut rng))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let failed = panicking(&inputs);
+    assert!(failed.is_empty(), "the assembler panicked on {:#?}", failed);
+}
+
+#[test]
+fn output_is_the_same_every_time() {
+    for source in SEEDS.iter().chain(CORPUS) {
+        let first = rendered(source);
+        for _ in 0..4 {
+            assert!(
+                rendered(source) == first,
+                "assembling {:?} twice gave different output",
+                source
+            );
+        }
+    }
+}
@@ -1 +0,0 @@
-pub mod lineinfo;
\ No newline at end of file
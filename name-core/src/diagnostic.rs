@@ -0,0 +1,49 @@
+// A tool-independent form of an error or warning about a source file, for
+// editors and other programs that want structured output rather than the
+// rendered text.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A range of source text. Lines and columns are 1-indexed, and `length` is
+// measured in characters
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+// A secondary position that helps explain the diagnostic
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Note {
+    pub message: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // Identifies the kind of problem, e.g. `undeclared-label`
+    pub code: String,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<Note>,
+    pub help: Option<String>,
+}
+
+// The document of kind `diagnostics`: every error and warning from one run,
+// in the order they were found, as written by name-as --error-format json
+pub const DIAGNOSTICS_KIND: &str = "diagnostics";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DiagnosticReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
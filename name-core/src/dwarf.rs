@@ -0,0 +1,186 @@
+// Minimal DWARF 2 debug information for ELF output: a single compile unit
+// covering .text and a line table built from the assembler's line info, which
+// is enough for gdb and objdump to map addresses back to source lines.
+
+use crate::elf::Writer;
+use crate::endian::Endian;
+use crate::lineinfo::{LineInfo, LineTable};
+
+const DWARF_VERSION: u16 = 2;
+const ADDRESS_SIZE: u8 = 4;
+const INSTRUCTION_SIZE: u8 = 4;
+
+const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+const DW_CHILDREN_NO: u8 = 0;
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_STMT_LIST: u64 = 0x10;
+const DW_AT_LOW_PC: u64 = 0x11;
+const DW_AT_HIGH_PC: u64 = 0x12;
+const DW_AT_LANGUAGE: u64 = 0x13;
+const DW_AT_PRODUCER: u64 = 0x25;
+const DW_FORM_ADDR: u64 = 0x01;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_LANG_MIPS_ASSEMBLER: u16 = 0x8001;
+
+// Line program header parameters. Only standard opcodes are emitted, so
+// line_base and line_range just need to be valid.
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+const DW_LNS_SET_FILE: u8 = 0x04;
+const DW_LNS_SET_COLUMN: u8 = 0x05;
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+
+const COMPILE_UNIT_ABBREV: u64 = 1;
+
+pub fn debug_abbrev(endian: Endian) -> Vec<u8> {
+    let mut abbrev = Writer::new(endian);
+    abbrev.uleb128(COMPILE_UNIT_ABBREV);
+    abbrev.uleb128(DW_TAG_COMPILE_UNIT);
+    abbrev.u8(DW_CHILDREN_NO);
+    for (attribute, form) in [
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_PRODUCER, DW_FORM_STRING),
+        (DW_AT_LANGUAGE, DW_FORM_DATA2),
+        (DW_AT_STMT_LIST, DW_FORM_DATA4),
+        (DW_AT_LOW_PC, DW_FORM_ADDR),
+        (DW_AT_HIGH_PC, DW_FORM_ADDR),
+    ] {
+        abbrev.uleb128(attribute);
+        abbrev.uleb128(form);
+    }
+    abbrev.uleb128(0);
+    abbrev.uleb128(0);
+    // End of the abbreviation table
+    abbrev.uleb128(0);
+    abbrev.bytes
+}
+
+// One compile unit for the whole program, pointing at the line table at the
+// start of .debug_line
+pub fn debug_info(endian: Endian, source_file: &str, text_start: u32, text_end: u32) -> Vec<u8> {
+    let mut info = Writer::new(endian);
+    info.u32(0); // unit_length, patched below
+    info.u16(DWARF_VERSION);
+    info.u32(0); // offset into .debug_abbrev
+    info.u8(ADDRESS_SIZE);
+
+    info.uleb128(COMPILE_UNIT_ABBREV);
+    info.string(source_file);
+    info.string(concat!("NAME ", env!("CARGO_PKG_VERSION")));
+    info.u16(DW_LANG_MIPS_ASSEMBLER);
+    info.u32(0); // offset into .debug_line
+    info.u32(text_start);
+    info.u32(text_end);
+
+    let unit_length = info.len() - 4;
+    info.patch_u32(0, unit_length);
+    info.bytes
+}
+
+// A single sequence covering .text, with a row for every assembled
+// instruction. The file table is the line info's, or just `source_file`
+// if it names none.
+pub fn debug_line(
+    endian: Endian,
+    source_file: &str,
+    lineinfo: &LineTable,
+    text_end: u32,
+) -> Vec<u8> {
+    let mut line = Writer::new(endian);
+    line.u32(0); // unit_length, patched below
+    line.u16(DWARF_VERSION);
+    line.u32(0); // header_length, patched below
+    let header_start = line.len();
+
+    line.u8(INSTRUCTION_SIZE);
+    line.u8(1); // default_is_stmt
+    line.u8(LINE_BASE as u8);
+    line.u8(LINE_RANGE);
+    line.u8(OPCODE_BASE);
+    line.raw(&STANDARD_OPCODE_LENGTHS);
+    // No include directories, so files are relative to the compile
+    // directory
+    line.u8(0);
+    let files = match lineinfo.files.as_slice() {
+        [] => vec![source_file.to_string()],
+        files => files.to_vec(),
+    };
+    for file in &files {
+        line.string(file);
+        line.uleb128(0); // directory index
+        line.uleb128(0); // modification time
+        line.uleb128(0); // file length
+    }
+    line.u8(0);
+
+    let header_length = line.len() - header_start;
+    line.patch_u32(6, header_length);
+
+    let mut rows: Vec<&LineInfo> = lineinfo.lines.iter().collect();
+    rows.sort_by_key(|li| li.instr_addr);
+
+    if let Some(first) = rows.first() {
+        extended_op(
+            &mut line,
+            DW_LNE_SET_ADDRESS,
+            &endian.u32_to_bytes(first.instr_addr),
+        );
+
+        // The state machine starts in the first file on line 1, column 0,
+        // at the address just set. DWARF numbers files from 1
+        let mut address = first.instr_addr;
+        let mut file: u32 = 0;
+        let mut line_number: u32 = 1;
+        let mut column: u32 = 0;
+        for row in rows {
+            if row.file != file {
+                line.u8(DW_LNS_SET_FILE);
+                line.uleb128(row.file as u64 + 1);
+                file = row.file;
+            }
+            if row.span.line != line_number {
+                line.u8(DW_LNS_ADVANCE_LINE);
+                line.sleb128(row.span.line as i64 - line_number as i64);
+                line_number = row.span.line;
+            }
+            if row.span.column != column {
+                line.u8(DW_LNS_SET_COLUMN);
+                line.uleb128(row.span.column as u64);
+                column = row.span.column;
+            }
+            if row.instr_addr != address {
+                line.u8(DW_LNS_ADVANCE_PC);
+                line.uleb128(((row.instr_addr - address) / INSTRUCTION_SIZE as u32) as u64);
+                address = row.instr_addr;
+            }
+            line.u8(DW_LNS_COPY);
+        }
+
+        // The sequence ends just past the last instruction
+        if text_end > address {
+            line.u8(DW_LNS_ADVANCE_PC);
+            line.uleb128(((text_end - address) / INSTRUCTION_SIZE as u32) as u64);
+        }
+        extended_op(&mut line, DW_LNE_END_SEQUENCE, &[]);
+    }
+
+    let unit_length = line.len() - 4;
+    line.patch_u32(0, unit_length);
+    line.bytes
+}
+
+fn extended_op(line: &mut Writer, opcode: u8, operands: &[u8]) {
+    line.u8(0);
+    line.uleb128(operands.len() as u64 + 1);
+    line.u8(opcode);
+    line.raw(operands);
+}
@@ -0,0 +1,689 @@
+// Writes assembled programs as 32-bit MIPS ELF executables, so they can be
+// inspected with standard tools (readelf, objdump, gdb) and loaded by
+// emulators other than NAME, and reads executables produced by other
+// toolchains so NAME can run them.
+
+use crate::buildinfo::BuildInfo;
+use crate::dwarf;
+use crate::endian::Endian;
+use crate::lineinfo::LineTable;
+use crate::symbols::Symbol;
+
+const ELF_HEADER_SIZE: u16 = 52;
+const PROGRAM_HEADER_SIZE: u16 = 32;
+const SECTION_HEADER_SIZE: u16 = 40;
+const SYMBOL_SIZE: u32 = 16;
+// .text and .data are each placed on their own page so their file offsets
+// and addresses agree modulo the page size, as loaders expect
+const PAGE_SIZE: u32 = 0x1000;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS32: u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+const ET_EXEC: u16 = 2;
+const EM_MIPS: u16 = 8;
+// MIPS32, o32 ABI
+const EF_MIPS_ARCH_32: u32 = 0x5000_0000;
+const EF_MIPS_ABI_O32: u32 = 0x0000_1000;
+
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOTE: u32 = 7;
+const SHF_WRITE: u32 = 1;
+const SHF_ALLOC: u32 = 2;
+const SHF_EXECINSTR: u32 = 4;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const SHN_UNDEF: u16 = 0;
+
+// The owner and type of the note holding a BuildInfo record, as JSON
+const NOTE_OWNER: &str = "NAME";
+const NT_NAME_BUILDINFO: u32 = 1;
+// And of the note holding a CRC-32 of each loadable segment, as the
+// address, size in the file and checksum of each in turn, so a truncated
+// or corrupted executable is refused instead of run
+const NT_NAME_CHECKSUMS: u32 = 2;
+const CHECKSUM_SIZE: u32 = 12;
+
+// Everything needed to write an executable
+pub struct ElfProgram<'a> {
+    pub endian: Endian,
+    pub entry: u32,
+    pub text_address: u32,
+    pub text: &'a [u8],
+    // Left out of the file entirely when empty
+    pub data_address: u32,
+    pub data: &'a [u8],
+    pub symbols: &'a [Symbol],
+    // Used for the DWARF line table
+    pub source_file: &'a str,
+    pub lineinfo: &'a LineTable,
+    // Written as a note in .note.name if present, see buildinfo.rs
+    pub build_info: Option<&'a BuildInfo>,
+}
+
+// Builds up a file in a given byte order
+pub(crate) struct Writer {
+    pub bytes: Vec<u8>,
+    endian: Endian,
+}
+
+impl Writer {
+    pub fn new(endian: Endian) -> Writer {
+        Writer {
+            bytes: vec![],
+            endian,
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend(self.endian.u16_to_bytes(value));
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend(self.endian.u32_to_bytes(value));
+    }
+
+    pub fn raw(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    // A null-terminated string
+    pub fn string(&mut self, text: &str) {
+        self.raw(text.as_bytes());
+        self.u8(0);
+    }
+
+    pub fn uleb128(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.u8(byte);
+                return;
+            }
+            self.u8(byte | 0x80);
+        }
+    }
+
+    pub fn sleb128(&mut self, mut value: i64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+            if done {
+                self.u8(byte);
+                return;
+            }
+            self.u8(byte | 0x80);
+        }
+    }
+
+    // Overwrites a u32 written earlier, for lengths only known afterwards
+    pub fn patch_u32(&mut self, offset: u32, value: u32) {
+        let offset = offset as usize;
+        self.bytes[offset..offset + 4].copy_from_slice(&self.endian.u32_to_bytes(value));
+    }
+
+    pub fn pad_to(&mut self, offset: u32) {
+        self.bytes.resize(offset as usize, 0);
+    }
+
+    pub fn align(&mut self, alignment: u32) {
+        let aligned = self.len().div_ceil(alignment) * alignment;
+        self.pad_to(aligned);
+    }
+}
+
+// A section's contents and the header fields that differ between sections
+struct Section {
+    name: &'static str,
+    kind: u32,
+    flags: u32,
+    address: u32,
+    data: Vec<u8>,
+    link: u32,
+    info: u32,
+    alignment: u32,
+    entry_size: u32,
+}
+
+impl Section {
+    fn new(name: &'static str, kind: u32, data: Vec<u8>) -> Section {
+        Section {
+            name,
+            kind,
+            flags: 0,
+            address: 0,
+            data,
+            link: 0,
+            info: 0,
+            alignment: 1,
+            entry_size: 0,
+        }
+    }
+}
+
+// A string table and the offset of each string added to it
+struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl StringTable {
+    fn new() -> StringTable {
+        StringTable { bytes: vec![0] }
+    }
+
+    fn add(&mut self, text: &str) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(text.as_bytes());
+        self.bytes.push(0);
+        offset
+    }
+}
+
+pub fn write_elf(program: &ElfProgram) -> Vec<u8> {
+    let endian = program.endian;
+    let text_end = program.text_address + program.text.len() as u32;
+
+    let has_data = !program.data.is_empty();
+    let data_end = program.data_address + program.data.len() as u32;
+
+    // Section indices, in the order the sections are listed below
+    const TEXT_INDEX: u16 = 1;
+    const DATA_INDEX: u16 = 2;
+    let strtab_index = if has_data { 4 } else { 3 };
+
+    let mut strtab = StringTable::new();
+    let mut symtab = Writer::new(endian);
+    // The null symbol, then local symbols, which ELF requires to come
+    // before global ones
+    symtab.raw(&[0; SYMBOL_SIZE as usize]);
+    let (globals, locals): (Vec<&Symbol>, Vec<&Symbol>) =
+        program.symbols.iter().partition(|symbol| symbol.global);
+    let first_global = 1 + locals.len() as u32;
+    for symbol in locals.into_iter().chain(globals) {
+        let binding = if symbol.global { STB_GLOBAL } else { STB_LOCAL };
+        symtab.u32(strtab.add(&symbol.name));
+        symtab.u32(symbol.address);
+        symtab.u32(symbol.size);
+        let in_data = has_data && (program.data_address..data_end).contains(&symbol.address);
+        if in_data {
+            symtab.u8((binding << 4) | STT_OBJECT);
+            symtab.u8(0);
+            symtab.u16(DATA_INDEX);
+        } else {
+            symtab.u8((binding << 4) | STT_NOTYPE);
+            symtab.u8(0);
+            symtab.u16(TEXT_INDEX);
+        }
+    }
+
+    let mut text = Section::new(".text", SHT_PROGBITS, program.text.to_vec());
+    text.flags = SHF_ALLOC | SHF_EXECINSTR;
+    text.address = program.text_address;
+    text.alignment = 4;
+
+    let mut data = Section::new(".data", SHT_PROGBITS, program.data.to_vec());
+    data.flags = SHF_ALLOC | SHF_WRITE;
+    data.address = program.data_address;
+    data.alignment = 4;
+
+    let mut symbols = Section::new(".symtab", SHT_SYMTAB, symtab.bytes);
+    symbols.link = strtab_index;
+    // Index of the first global symbol
+    symbols.info = first_global;
+    symbols.alignment = 4;
+    symbols.entry_size = SYMBOL_SIZE;
+
+    let mut sections = vec![text];
+    if has_data {
+        sections.push(data);
+    }
+    sections.extend([
+        symbols,
+        Section::new(".strtab", SHT_STRTAB, strtab.bytes),
+        Section::new(".debug_abbrev", SHT_PROGBITS, dwarf::debug_abbrev(endian)),
+        Section::new(
+            ".debug_info",
+            SHT_PROGBITS,
+            dwarf::debug_info(endian, program.source_file, program.text_address, text_end),
+        ),
+        Section::new(
+            ".debug_line",
+            SHT_PROGBITS,
+            dwarf::debug_line(endian, program.source_file, program.lineinfo, text_end),
+        ),
+    ]);
+
+    let mut notes = Writer::new(endian);
+    let mut checksums = Writer::new(endian);
+    let mut segments = vec![(program.text_address, program.text)];
+    if has_data {
+        segments.push((program.data_address, program.data));
+    }
+    for (address, bytes) in segments {
+        checksums.u32(address);
+        checksums.u32(bytes.len() as u32);
+        checksums.u32(crc32(bytes));
+    }
+    write_note(&mut notes, NT_NAME_CHECKSUMS, &checksums.bytes);
+    if let Some(build_info) = program.build_info {
+        let description = build_info
+            .to_json()
+            .expect("a build info record always serializes");
+        write_note(&mut notes, NT_NAME_BUILDINFO, description.as_bytes());
+    }
+    let mut section = Section::new(".note.name", SHT_NOTE, notes.bytes);
+    section.alignment = 4;
+    sections.push(section);
+
+    let mut shstrtab = StringTable::new();
+    let names: Vec<u32> = sections.iter().map(|s| shstrtab.add(s.name)).collect();
+    let shstrtab_name = shstrtab.add(".shstrtab");
+    let shstrtab_index = sections.len() as u16 + 1;
+    sections.push(Section::new(".shstrtab", SHT_STRTAB, shstrtab.bytes));
+
+    // Section contents, starting with .text on its own page
+    let mut file = Writer::new(endian);
+    file.pad_to(PAGE_SIZE);
+    let mut offsets = vec![];
+    for section in &sections {
+        if section.flags & SHF_ALLOC != 0 {
+            file.align(PAGE_SIZE);
+        }
+        file.align(section.alignment);
+        offsets.push(file.len());
+        file.raw(&section.data);
+    }
+    file.align(4);
+    let section_headers = file.len();
+
+    // The null section header, then one per section
+    file.raw(&[0; SECTION_HEADER_SIZE as usize]);
+    for (i, section) in sections.iter().enumerate() {
+        let name = if i + 1 == shstrtab_index as usize {
+            shstrtab_name
+        } else {
+            names[i]
+        };
+        file.u32(name);
+        file.u32(section.kind);
+        file.u32(section.flags);
+        file.u32(section.address);
+        file.u32(offsets[i]);
+        file.u32(section.data.len() as u32);
+        file.u32(section.link);
+        file.u32(section.info);
+        file.u32(section.alignment);
+        file.u32(section.entry_size);
+    }
+
+    // Finally the headers at the start of the file
+    let mut header = Writer::new(endian);
+    header.raw(&ELF_MAGIC);
+    header.u8(ELFCLASS32);
+    header.u8(match endian {
+        Endian::Little => ELFDATA2LSB,
+        Endian::Big => ELFDATA2MSB,
+    });
+    header.u8(1); // ELF version
+    header.pad_to(16);
+    header.u16(ET_EXEC);
+    header.u16(EM_MIPS);
+    header.u32(1);
+    header.u32(program.entry);
+    header.u32(ELF_HEADER_SIZE as u32);
+    header.u32(section_headers);
+    header.u32(EF_MIPS_ARCH_32 | EF_MIPS_ABI_O32);
+    header.u16(ELF_HEADER_SIZE);
+    header.u16(PROGRAM_HEADER_SIZE);
+    header.u16(if has_data { 2 } else { 1 });
+    header.u16(SECTION_HEADER_SIZE);
+    header.u16(sections.len() as u16 + 1);
+    header.u16(shstrtab_index);
+
+    // One loadable segment holding .text
+    header.u32(PT_LOAD);
+    header.u32(offsets[0]);
+    header.u32(program.text_address);
+    header.u32(program.text_address);
+    header.u32(program.text.len() as u32);
+    header.u32(program.text.len() as u32);
+    header.u32(PF_R | PF_X);
+    header.u32(PAGE_SIZE);
+
+    // And another for .data, which is writable but not executable
+    if has_data {
+        header.u32(PT_LOAD);
+        header.u32(offsets[DATA_INDEX as usize - 1]);
+        header.u32(program.data_address);
+        header.u32(program.data_address);
+        header.u32(program.data.len() as u32);
+        header.u32(program.data.len() as u32);
+        header.u32(PF_R | PF_W);
+        header.u32(PAGE_SIZE);
+    }
+
+    file.bytes[..header.bytes.len()].copy_from_slice(&header.bytes);
+    file.bytes
+}
+
+fn write_note(notes: &mut Writer, kind: u32, description: &[u8]) {
+    notes.u32(NOTE_OWNER.len() as u32 + 1);
+    notes.u32(description.len() as u32);
+    notes.u32(kind);
+    notes.string(NOTE_OWNER);
+    notes.align(4);
+    notes.raw(description);
+    notes.align(4);
+}
+
+// The CRC-32 used by zip and PNG, computed a bit at a time since
+// executables are small
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// A loadable segment of an executable
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub address: u32,
+    // The bytes stored in the file. Anything past them up to `mem_size`,
+    // such as .bss, starts out as zeros.
+    pub data: Vec<u8>,
+    pub mem_size: u32,
+    pub executable: bool,
+}
+
+// The parts of an executable needed to run it
+#[derive(Debug, Clone)]
+pub struct ElfExecutable {
+    pub endian: Endian,
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    // Named functions, objects and labels from .symtab, if it was kept
+    pub symbols: Vec<Symbol>,
+    // The provenance record name-as or `name link` left in .note.name
+    pub build_info: Option<BuildInfo>,
+    // Whether the segments were checked against checksums in .note.name.
+    // Executables from other toolchains have none
+    pub verified: bool,
+}
+
+// Whether a file looks like an ELF file rather than raw instructions
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(&ELF_MAGIC)
+}
+
+// Reads fields out of a file in a given byte order, failing on anything
+// past its end
+struct Reader<'a> {
+    bytes: &'a [u8],
+    endian: Endian,
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, offset: u32, length: u32) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        let start = offset as usize;
+        let end = start + length as usize;
+        self.bytes
+            .get(start..end)
+            .ok_or_else(|| format!("truncated ELF file, expected data at 0x{:x}", offset).into())
+    }
+
+    fn u8(&self, offset: u32) -> Result<u8, Box<dyn std::error::Error>> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: u32) -> Result<u16, Box<dyn std::error::Error>> {
+        let bytes = self.slice(offset, 2)?;
+        Ok(self.endian.u16_from_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, offset: u32) -> Result<u32, Box<dyn std::error::Error>> {
+        let bytes = self.slice(offset, 4)?;
+        Ok(self
+            .endian
+            .u32_from_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // The null-terminated string starting at `offset`
+    fn string(&self, offset: u32) -> Result<String, Box<dyn std::error::Error>> {
+        let bytes = self
+            .slice(offset, 0)
+            .map(|_| &self.bytes[offset as usize..])?;
+        let end = bytes
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or("unterminated string in ELF file")?;
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+// Reads a statically linked 32-bit MIPS executable in either byte order,
+// such as one produced by `mips-linux-gnu-gcc -static` or by name-as
+pub fn read_elf(bytes: &[u8]) -> Result<ElfExecutable, Box<dyn std::error::Error>> {
+    if !is_elf(bytes) {
+        return Err("not an ELF file".into());
+    }
+    let endian = match bytes.get(5) {
+        Some(&ELFDATA2LSB) => Endian::Little,
+        Some(&ELFDATA2MSB) => Endian::Big,
+        _ => return Err("unknown ELF byte order".into()),
+    };
+    let file = Reader { bytes, endian };
+
+    if file.u8(4)? != ELFCLASS32 {
+        return Err("only 32-bit ELF files are supported".into());
+    }
+    if file.u16(18)? != EM_MIPS {
+        return Err("not a MIPS executable".into());
+    }
+    if file.u16(16)? != ET_EXEC {
+        return Err(
+            "only executables can be loaded, link position-dependent code with -static".into(),
+        );
+    }
+
+    let entry = file.u32(24)?;
+    let program_headers = file.u32(28)?;
+    let section_headers = file.u32(32)?;
+    let program_header_size = file.u16(42)? as u32;
+    let program_header_count = file.u16(44)? as u32;
+    let section_header_size = file.u16(46)? as u32;
+    let section_header_count = file.u16(48)? as u32;
+
+    let mut segments = vec![];
+    for i in 0..program_header_count {
+        let header = program_headers.saturating_add(i * program_header_size);
+        if file.u32(header)? != PT_LOAD {
+            continue;
+        }
+        let offset = file.u32(header + 4)?;
+        let address = file.u32(header + 8)?;
+        let file_size = file.u32(header + 16)?;
+        let mem_size = file.u32(header + 20)?;
+        let flags = file.u32(header + 24)?;
+        if file_size > mem_size {
+            return Err(format!(
+                "segment at 0x{:08x} is larger in the file than in memory",
+                address
+            )
+            .into());
+        }
+        segments.push(Segment {
+            address,
+            data: file.slice(offset, file_size)?.to_vec(),
+            mem_size,
+            executable: flags & PF_X != 0,
+        });
+    }
+    if segments.is_empty() {
+        return Err("ELF file has no loadable segments".into());
+    }
+
+    // Symbols are optional, stripped executables still run
+    let mut symbols = vec![];
+    for i in 0..section_header_count {
+        let header = section_headers.saturating_add(i * section_header_size);
+        if file.u32(header + 4)? != SHT_SYMTAB {
+            continue;
+        }
+        let offset = file.u32(header + 16)?;
+        let size = file.u32(header + 20)?;
+        let link = file.u32(header + 24)?;
+        let strtab_header =
+            section_headers.saturating_add(link.saturating_mul(section_header_size));
+        let strtab = file.u32(strtab_header.saturating_add(16))?;
+
+        for symbol in (offset..offset.saturating_add(size)).step_by(SYMBOL_SIZE as usize) {
+            let info = file.u8(symbol + 12)?;
+            let kind = info & 0xf;
+            let section = file.u16(symbol + 14)?;
+            if section == SHN_UNDEF || ![STT_NOTYPE, STT_OBJECT, STT_FUNC].contains(&kind) {
+                continue;
+            }
+            let name = file.string(strtab.saturating_add(file.u32(symbol)?))?;
+            if !name.is_empty() {
+                // ELF has no notion of element types, only sizes
+                symbols.push(Symbol {
+                    address: file.u32(symbol + 4)?,
+                    name,
+                    size: file.u32(symbol + 8)?,
+                    data_type: None,
+                    global: info >> 4 == STB_GLOBAL,
+                });
+            }
+        }
+    }
+    symbols.sort();
+
+    let mut build_info = None;
+    let mut verified = false;
+    for i in 0..section_header_count {
+        let header = section_headers.saturating_add(i * section_header_size);
+        if file.u32(header + 4)? != SHT_NOTE {
+            continue;
+        }
+        let offset = file.u32(header + 16)?;
+        let size = file.u32(header + 20)?;
+        for note in read_notes(&file, offset, size)? {
+            match note.kind {
+                NT_NAME_BUILDINFO if build_info.is_none() => {
+                    build_info = Some(BuildInfo::from_json(&String::from_utf8_lossy(
+                        note.description,
+                    ))?);
+                }
+                NT_NAME_CHECKSUMS => {
+                    verify_segments(&file, note.description, &segments)?;
+                    verified = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(ElfExecutable {
+        endian,
+        entry,
+        segments,
+        symbols,
+        build_info,
+        verified,
+    })
+}
+
+// A note from NAME: its type and the bytes of its description
+struct Note<'a> {
+    kind: u32,
+    description: &'a [u8],
+}
+
+// Each note from NAME in a note section. Notes from other tools, such as
+// the GNU build ID, are skipped
+fn read_notes<'a>(
+    file: &Reader<'a>,
+    offset: u32,
+    size: u32,
+) -> Result<Vec<Note<'a>>, Box<dyn std::error::Error>> {
+    let end = offset.saturating_add(size);
+    let mut notes = vec![];
+    let mut note = offset;
+    while note.saturating_add(12) <= end {
+        let name_size = file.u32(note)?;
+        let description_size = file.u32(note + 4)?;
+        let kind = file.u32(note + 8)?;
+        let name = note + 12;
+        let description = name.saturating_add(name_size.next_multiple_of(4));
+        if file.string(name)? == NOTE_OWNER {
+            notes.push(Note {
+                kind,
+                description: file.slice(description, description_size)?,
+            });
+        }
+        note = description.saturating_add(description_size.next_multiple_of(4));
+    }
+    Ok(notes)
+}
+
+// Checks each segment named in a checksum note against the one loaded
+fn verify_segments(
+    file: &Reader,
+    checksums: &[u8],
+    segments: &[Segment],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = Reader {
+        bytes: checksums,
+        endian: file.endian,
+    };
+    for entry in (0..checksums.len() as u32 / CHECKSUM_SIZE).map(|i| i * CHECKSUM_SIZE) {
+        let address = reader.u32(entry)?;
+        let size = reader.u32(entry + 4)?;
+        let checksum = reader.u32(entry + 8)?;
+        let intact = segments.iter().any(|segment| {
+            segment.address == address
+                && segment.data.len() as u32 == size
+                && crc32(&segment.data) == checksum
+        });
+        if !intact {
+            return Err(format!(
+                "the segment at 0x{:08x} does not match its checksum, so the file is corrupted; assemble it again",
+                address
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+// The fields of a MIPS instruction word and how they are packed into it.
+// The assembler fills in an Instruction and encodes it; the emulator decodes
+// each word it fetches back into one. Both go through here, so the two
+// cannot disagree about where a field lives, and `decode(encode(i)) == i`
+// and `encode(decode(w)) == w` can be checked for any instruction or word.
+
+// The layout of a word is picked by its opcode, in bits 31-26
+pub const SPECIAL_OPCODE: u8 = 0x00;
+pub const J_OPCODE: u8 = 0x02;
+pub const JAL_OPCODE: u8 = 0x03;
+pub const COP0_OPCODE: u8 = 0x10;
+pub const COP1_OPCODE: u8 = 0x11;
+pub const SPECIAL2_OPCODE: u8 = 0x1c;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // SPECIAL and SPECIAL2: three registers, a shift amount and a function
+    // code picking the operation
+    R {
+        opcode: u8,
+        rs: u8,
+        rt: u8,
+        rd: u8,
+        shamt: u8,
+        funct: u8,
+    },
+    // Two registers and a 16-bit immediate or branch offset
+    I {
+        opcode: u8,
+        rs: u8,
+        rt: u8,
+        imm: u16,
+    },
+    // `j` and `jal`: bits 27-2 of the target address
+    J {
+        opcode: u8,
+        target: u32,
+    },
+    // Coprocessors 0 and 1. Branches use ft for the condition and the low
+    // 16 bits, fs, fd and funct together, for the offset
+    C {
+        opcode: u8,
+        fmt: u8,
+        ft: u8,
+        fs: u8,
+        fd: u8,
+        funct: u8,
+    },
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> u8 {
+        match *self {
+            Instruction::R { opcode, .. }
+            | Instruction::I { opcode, .. }
+            | Instruction::J { opcode, .. }
+            | Instruction::C { opcode, .. } => opcode,
+        }
+    }
+}
+
+// Where each field sits in a word. Coprocessor instructions reuse the same
+// positions: fmt is rs, ft is rt, fs is rd and fd is shamt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Opcode,
+    Rs,
+    Rt,
+    Rd,
+    Shamt,
+    Funct,
+    Imm,
+    Target,
+}
+
+impl Field {
+    // The field's width and the bit it starts at
+    const fn layout(self) -> (u32, u32) {
+        match self {
+            Field::Opcode => (6, 26),
+            Field::Rs => (5, 21),
+            Field::Rt => (5, 16),
+            Field::Rd => (5, 11),
+            Field::Shamt => (5, 6),
+            Field::Funct => (6, 0),
+            Field::Imm => (16, 0),
+            Field::Target => (26, 0),
+        }
+    }
+
+    // The bits of a word the field covers
+    pub const fn mask(self) -> u32 {
+        let (bits, shift) = self.layout();
+        ((1 << bits) - 1) << shift
+    }
+
+    // Places `value` in the field. Anything wider than the field is cut off,
+    // which decode() then shows up as a different value
+    pub fn place(self, value: impl Into<u32>) -> u32 {
+        let (_, shift) = self.layout();
+        (value.into() << shift) & self.mask()
+    }
+
+    pub fn get(self, word: u32) -> u32 {
+        let (_, shift) = self.layout();
+        (word & self.mask()) >> shift
+    }
+}
+
+// Packs the fields of `instruction` into a word
+pub fn encode(instruction: Instruction) -> u32 {
+    match instruction {
+        Instruction::R {
+            opcode,
+            rs,
+            rt,
+            rd,
+            shamt,
+            funct,
+        } => {
+            Field::Opcode.place(opcode)
+                | Field::Rs.place(rs)
+                | Field::Rt.place(rt)
+                | Field::Rd.place(rd)
+                | Field::Shamt.place(shamt)
+                | Field::Funct.place(funct)
+        }
+        Instruction::I {
+            opcode,
+            rs,
+            rt,
+            imm,
+        } => {
+            Field::Opcode.place(opcode)
+                | Field::Rs.place(rs)
+                | Field::Rt.place(rt)
+                | Field::Imm.place(imm)
+        }
+        Instruction::J { opcode, target } => {
+            Field::Opcode.place(opcode) | Field::Target.place(target)
+        }
+        Instruction::C {
+            opcode,
+            fmt,
+            ft,
+            fs,
+            fd,
+            funct,
+        } => {
+            Field::Opcode.place(opcode)
+                | Field::Rs.place(fmt)
+                | Field::Rt.place(ft)
+                | Field::Rd.place(fs)
+                | Field::Shamt.place(fd)
+                | Field::Funct.place(funct)
+        }
+    }
+}
+
+// Splits a word into the fields of its layout
+pub fn decode(word: u32) -> Instruction {
+    let opcode = Field::Opcode.get(word) as u8;
+    match opcode {
+        SPECIAL_OPCODE | SPECIAL2_OPCODE => Instruction::R {
+            opcode,
+            rs: Field::Rs.get(word) as u8,
+            rt: Field::Rt.get(word) as u8,
+            rd: Field::Rd.get(word) as u8,
+            shamt: Field::Shamt.get(word) as u8,
+            funct: Field::Funct.get(word) as u8,
+        },
+        J_OPCODE | JAL_OPCODE => Instruction::J {
+            opcode,
+            target: Field::Target.get(word),
+        },
+        COP0_OPCODE | COP1_OPCODE => Instruction::C {
+            opcode,
+            fmt: Field::Rs.get(word) as u8,
+            ft: Field::Rt.get(word) as u8,
+            fs: Field::Rd.get(word) as u8,
+            fd: Field::Shamt.get(word) as u8,
+            funct: Field::Funct.get(word) as u8,
+        },
+        _ => Instruction::I {
+            opcode,
+            rs: Field::Rs.get(word) as u8,
+            rt: Field::Rt.get(word) as u8,
+            imm: Field::Imm.get(word) as u16,
+        },
+    }
+}
@@ -0,0 +1,69 @@
+// Byte order shared by the assembler and the emulator, so that a binary
+// written with one setting is read back with the same one.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    Big,
+    // MARS and SPIM on x86 both default to little endian
+    #[default]
+    Little,
+}
+
+impl Endian {
+    pub fn u16_to_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            Endian::Big => value.to_be_bytes(),
+            Endian::Little => value.to_le_bytes(),
+        }
+    }
+
+    pub fn u32_to_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            Endian::Big => value.to_be_bytes(),
+            Endian::Little => value.to_le_bytes(),
+        }
+    }
+
+    pub fn u16_from_bytes(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Endian::Big => u16::from_be_bytes(bytes),
+            Endian::Little => u16::from_le_bytes(bytes),
+        }
+    }
+
+    pub fn u32_from_bytes(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endian::Big => u32::from_be_bytes(bytes),
+            Endian::Little => u32::from_le_bytes(bytes),
+        }
+    }
+}
+
+impl FromStr for Endian {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "big" => Ok(Endian::Big),
+            "little" => Ok(Endian::Little),
+            _ => Err(format!(
+                "Unknown endianness `{}`, expected `big` or `little`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Endian {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Endian::Big => write!(f, "big"),
+            Endian::Little => write!(f, "little"),
+        }
+    }
+}
@@ -0,0 +1,573 @@
+// The MIPS32 user-mode instructions NAME knows about, and the coprocessor 0
+// ones exception handlers need. Each entry carries a sample encoding so
+// tools can check their own coverage against the same list.
+//
+// This is the one description of each instruction: the assembler encodes
+// from its operands and sample, the disassembler finds entries by their
+// fixed bits and prints the operands back, and the emulator finds the entry
+// for each word it runs the same way and carries out its Op. Every Op has
+// to be handled by the emulator before it compiles, so anything the
+// assembler accepts can also be run.
+
+use crate::encoding::Field;
+use serde::Serialize;
+use std::fmt;
+
+// How an instruction's fields are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Format {
+    R,
+    I,
+    J,
+    // Coprocessor 1 (floating-point)
+    F,
+    // Coprocessor 0 (exceptions and the timer)
+    C0,
+}
+
+// The revision of the architecture that introduced an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum IsaLevel {
+    Mips1,
+    Mips2,
+    Mips32,
+}
+
+impl fmt::Display for IsaLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IsaLevel::Mips1 => write!(f, "MIPS I"),
+            IsaLevel::Mips2 => write!(f, "MIPS II"),
+            IsaLevel::Mips32 => write!(f, "MIPS32"),
+        }
+    }
+}
+
+// One operand as written in source, and the field of the word it goes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Operand {
+    Rd,
+    Rs,
+    Rt,
+    Shamt,
+    // A 16-bit immediate, sign-extended or zero-extended when it is used
+    Imm,
+    Uimm,
+    // A branch target, encoded as an offset in words from the delay slot
+    Label,
+    // A jump target, encoded as bits 27-2 of its address
+    Target,
+    Fd,
+    Fs,
+    Ft,
+    // A coprocessor 0 register, written as a number such as `$12`
+    Cop0,
+}
+
+impl Operand {
+    // The operand's name in messages and signatures
+    pub fn name(self) -> &'static str {
+        match self {
+            Operand::Rd => "$rd",
+            Operand::Rs => "$rs",
+            Operand::Rt => "$rt",
+            Operand::Shamt => "shamt",
+            Operand::Imm | Operand::Uimm => "imm",
+            Operand::Label | Operand::Target => "label",
+            Operand::Fd => "$fd",
+            Operand::Fs => "$fs",
+            Operand::Ft => "$ft",
+            Operand::Cop0 => "$rd",
+        }
+    }
+
+    pub fn field(self) -> Field {
+        match self {
+            Operand::Rs => Field::Rs,
+            Operand::Rt | Operand::Ft => Field::Rt,
+            Operand::Rd | Operand::Fs | Operand::Cop0 => Field::Rd,
+            Operand::Shamt | Operand::Fd => Field::Shamt,
+            Operand::Imm | Operand::Uimm | Operand::Label => Field::Imm,
+            Operand::Target => Field::Target,
+        }
+    }
+}
+
+// The operands an instruction takes, in the order they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Operands {
+    RdRsRt,
+    RdRtShamt,
+    // Variable shifts, which take the shift amount from $rs
+    RdRtRs,
+    RsRt,
+    Rd,
+    Rs,
+    // `jalr` can leave out $rd to link through $ra
+    RdRs,
+    Empty,
+    RtRsImm,
+    RtRsUimm,
+    RtUimm,
+    // Loads and stores, e.g. `lw $t0, 4($sp)`
+    RtOffsetRs,
+    FtOffsetRs,
+    RsRtLabel,
+    RsLabel,
+    Target,
+    FdFsFt,
+    FdFs,
+    // Comparisons, which set condition flag 0
+    FsFt,
+    // Moves between an integer and a floating-point register
+    RtFs,
+    // Branches on condition flag 0
+    Label,
+    RtCop0,
+}
+
+impl Operands {
+    pub fn list(self) -> &'static [Operand] {
+        use Operand::*;
+        match self {
+            Operands::RdRsRt => &[Rd, Rs, Rt],
+            Operands::RdRtShamt => &[Rd, Rt, Shamt],
+            Operands::RdRtRs => &[Rd, Rt, Rs],
+            Operands::RsRt => &[Rs, Rt],
+            Operands::Rd => &[Rd],
+            Operands::Rs => &[Rs],
+            Operands::RdRs => &[Rd, Rs],
+            Operands::Empty => &[],
+            Operands::RtRsImm => &[Rt, Rs, Imm],
+            Operands::RtRsUimm => &[Rt, Rs, Uimm],
+            Operands::RtUimm => &[Rt, Uimm],
+            Operands::RtOffsetRs => &[Rt, Imm, Rs],
+            Operands::FtOffsetRs => &[Ft, Imm, Rs],
+            Operands::RsRtLabel => &[Rs, Rt, Label],
+            Operands::RsLabel => &[Rs, Label],
+            Operands::Target => &[Target],
+            Operands::FdFsFt => &[Fd, Fs, Ft],
+            Operands::FdFs => &[Fd, Fs],
+            Operands::FsFt => &[Fs, Ft],
+            Operands::RtFs => &[Rt, Fs],
+            Operands::Label => &[Label],
+            Operands::RtCop0 => &[Rt, Cop0],
+        }
+    }
+
+    // Whether the operands are a register and an `imm($rs)` address
+    pub fn is_memory(self) -> bool {
+        matches!(self, Operands::RtOffsetRs | Operands::FtOffsetRs)
+    }
+
+    // The operands as they are written in source
+    pub fn signature(self) -> String {
+        let names: Vec<&str> = self.list().iter().map(|operand| operand.name()).collect();
+        match names.as_slice() {
+            [register, offset, base] if self.is_memory() => {
+                format!("{}, {}({})", register, offset, base)
+            }
+            _ => names.join(", "),
+        }
+    }
+}
+
+// What an instruction does, as far as counting what a program executed goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Class {
+    Alu,
+    Load,
+    Store,
+    Branch,
+    Jump,
+    Syscall,
+    // Coprocessor 1 arithmetic, compares, conversions and moves
+    Float,
+    // Traps, coprocessor 0 and anything else
+    Other,
+}
+
+impl Class {
+    pub const ALL: [Class; 8] = [
+        Class::Alu,
+        Class::Load,
+        Class::Store,
+        Class::Branch,
+        Class::Jump,
+        Class::Syscall,
+        Class::Float,
+        Class::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Alu => "alu",
+            Class::Load => "load",
+            Class::Store => "store",
+            Class::Branch => "branch",
+            Class::Jump => "jump",
+            Class::Syscall => "syscall",
+            Class::Float => "float",
+            Class::Other => "other",
+        }
+    }
+}
+
+// What an instruction does, one for each entry in the table. The emulator
+// carries each of these out; a disassembler or assembler has no use for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Op {
+    // Shifts
+    Sll,
+    Srl,
+    Sra,
+    Sllv,
+    Srlv,
+    Srav,
+    // Register jumps and system calls
+    Jr,
+    Jalr,
+    Movz,
+    Movn,
+    Syscall,
+    Break,
+    Sync,
+    // HI and LO
+    Mfhi,
+    Mthi,
+    Mflo,
+    Mtlo,
+    Mult,
+    Multu,
+    Div,
+    Divu,
+    // Arithmetic and logic
+    Add,
+    Addu,
+    Sub,
+    Subu,
+    And,
+    Or,
+    Xor,
+    Nor,
+    Slt,
+    Sltu,
+    // Conditional traps
+    Tge,
+    Tgeu,
+    Tlt,
+    Tltu,
+    Teq,
+    Tne,
+    // SPECIAL2
+    Madd,
+    Maddu,
+    Mul,
+    Msub,
+    Msubu,
+    Clz,
+    Clo,
+    // Branches
+    Bltz,
+    Bgez,
+    Bltzal,
+    Bgezal,
+    Beq,
+    Bne,
+    Blez,
+    Bgtz,
+    // Jumps
+    J,
+    Jal,
+    // Immediate arithmetic and logic
+    Addi,
+    Addiu,
+    Slti,
+    Sltiu,
+    Andi,
+    Ori,
+    Xori,
+    Lui,
+    // Loads and stores
+    Lb,
+    Lh,
+    Lwl,
+    Lw,
+    Lbu,
+    Lhu,
+    Lwr,
+    Sb,
+    Sh,
+    Swl,
+    Sw,
+    Swr,
+    Ll,
+    Lwc1,
+    Ldc1,
+    Sc,
+    Swc1,
+    Sdc1,
+    // Coprocessor 1 moves and branches
+    Mfc1,
+    Mtc1,
+    Bc1f,
+    Bc1t,
+    // Single precision
+    AddS,
+    SubS,
+    MulS,
+    DivS,
+    SqrtS,
+    AbsS,
+    MovS,
+    NegS,
+    CvtDS,
+    CvtWS,
+    CEqS,
+    CLtS,
+    CLeS,
+    // Double precision
+    AddD,
+    SubD,
+    MulD,
+    DivD,
+    MovD,
+    CvtSD,
+    // Word conversions
+    CvtSW,
+    CvtDW,
+    // Coprocessor 0, with $8 (BadVAddr) as the sample register
+    Mfc0,
+    Mtc0,
+    Eret,
+    // Looks up an instruction by mnemonic
+    // The instruction a word encodes, if it is one in the table
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct InstructionInfo {
+    pub mnemonic: &'static str,
+    pub op: Op,
+    pub format: Format,
+    pub level: IsaLevel,
+    pub operands: Operands,
+    pub class: Class,
+    // A valid encoding of the instruction with arbitrary operands
+    pub sample: u32,
+}
+
+impl InstructionInfo {
+    // The bits that are the same in every encoding of the instruction: all
+    // of them but the fields its operands go in
+    pub fn fixed_mask(&self) -> u32 {
+        self.operands
+            .list()
+            .iter()
+            .fold(!0, |mask, operand| mask & !operand.field().mask())
+    }
+
+    // The instruction with every operand field zero, for operands to be
+    // placed into
+    pub fn template(&self) -> u32 {
+        self.sample & self.fixed_mask()
+    }
+
+    pub fn matches(&self, word: u32) -> bool {
+        word & self.fixed_mask() == self.template()
+    }
+}
+
+// Sample operands: $t0 as the destination, $t1 and $t2 as sources, or $f0,
+// $f2 and $f4 for floating-point instructions
+const RD: u32 = 8 << 11;
+const RS: u32 = 9 << 21;
+const RT: u32 = 10 << 16;
+const FD: u32 = 0;
+const FS: u32 = 2 << 11;
+const FT: u32 = 4 << 16;
+
+const fn special(funct: u32) -> u32 {
+    funct
+}
+
+const fn special2(funct: u32) -> u32 {
+    (0x1c << 26) | funct
+}
+
+const fn regimm(rt: u32) -> u32 {
+    (0x01 << 26) | (rt << 16)
+}
+
+const fn opcode(opcode: u32) -> u32 {
+    opcode << 26
+}
+
+const fn cop0(fmt: u32, funct: u32) -> u32 {
+    (0x10 << 26) | (fmt << 21) | funct
+}
+
+const fn cop1(fmt: u32, funct: u32) -> u32 {
+    (0x11 << 26) | (fmt << 21) | funct
+}
+
+const fn entry(
+    mnemonic: &'static str,
+    op: Op,
+    format: Format,
+    level: IsaLevel,
+    operands: Operands,
+    class: Class,
+    sample: u32,
+) -> InstructionInfo {
+    InstructionInfo {
+        mnemonic,
+        op,
+        format,
+        level,
+        operands,
+        class,
+        sample,
+    }
+}
+
+use Class::{Alu, Branch, Float, Jump, Load, Other, Store, Syscall};
+use Format::{C0, F, I, J, R};
+use IsaLevel::{Mips1, Mips2, Mips32};
+use Operands::*;
+
+#[rustfmt::skip]
+pub const INSTRUCTIONS: &[InstructionInfo] = &[
+    // Shifts
+    entry("sll", Op::Sll, R, Mips1, RdRtShamt, Alu, special(0x00) | RD | RT | (3 << 6)),
+    entry("srl", Op::Srl, R, Mips1, RdRtShamt, Alu, special(0x02) | RD | RT | (3 << 6)),
+    entry("sra", Op::Sra, R, Mips1, RdRtShamt, Alu, special(0x03) | RD | RT | (3 << 6)),
+    entry("sllv", Op::Sllv, R, Mips1, RdRtRs, Alu, special(0x04) | RD | RS | RT),
+    entry("srlv", Op::Srlv, R, Mips1, RdRtRs, Alu, special(0x06) | RD | RS | RT),
+    entry("srav", Op::Srav, R, Mips1, RdRtRs, Alu, special(0x07) | RD | RS | RT),
+    // Register jumps and system calls
+    entry("jr", Op::Jr, R, Mips1, Rs, Jump, special(0x08) | RS),
+    entry("jalr", Op::Jalr, R, Mips1, RdRs, Jump, special(0x09) | RD | RS),
+    entry("movz", Op::Movz, R, Mips32, RdRsRt, Alu, special(0x0a) | RD | RS | RT),
+    entry("movn", Op::Movn, R, Mips32, RdRsRt, Alu, special(0x0b) | RD | RS | RT),
+    entry("syscall", Op::Syscall, R, Mips1, Empty, Syscall, special(0x0c)),
+    entry("break", Op::Break, R, Mips1, Empty, Other, special(0x0d)),
+    entry("sync", Op::Sync, R, Mips2, Empty, Other, special(0x0f)),
+    // HI and LO
+    entry("mfhi", Op::Mfhi, R, Mips1, Rd, Alu, special(0x10) | RD),
+    entry("mthi", Op::Mthi, R, Mips1, Rs, Alu, special(0x11) | RS),
+    entry("mflo", Op::Mflo, R, Mips1, Rd, Alu, special(0x12) | RD),
+    entry("mtlo", Op::Mtlo, R, Mips1, Rs, Alu, special(0x13) | RS),
+    entry("mult", Op::Mult, R, Mips1, RsRt, Alu, special(0x18) | RS | RT),
+    entry("multu", Op::Multu, R, Mips1, RsRt, Alu, special(0x19) | RS | RT),
+    entry("div", Op::Div, R, Mips1, RsRt, Alu, special(0x1a) | RS | RT),
+    entry("divu", Op::Divu, R, Mips1, RsRt, Alu, special(0x1b) | RS | RT),
+    // Arithmetic and logic
+    entry("add", Op::Add, R, Mips1, RdRsRt, Alu, special(0x20) | RD | RS | RT),
+    entry("addu", Op::Addu, R, Mips1, RdRsRt, Alu, special(0x21) | RD | RS | RT),
+    entry("sub", Op::Sub, R, Mips1, RdRsRt, Alu, special(0x22) | RD | RS | RT),
+    entry("subu", Op::Subu, R, Mips1, RdRsRt, Alu, special(0x23) | RD | RS | RT),
+    entry("and", Op::And, R, Mips1, RdRsRt, Alu, special(0x24) | RD | RS | RT),
+    entry("or", Op::Or, R, Mips1, RdRsRt, Alu, special(0x25) | RD | RS | RT),
+    entry("xor", Op::Xor, R, Mips1, RdRsRt, Alu, special(0x26) | RD | RS | RT),
+    entry("nor", Op::Nor, R, Mips1, RdRsRt, Alu, special(0x27) | RD | RS | RT),
+    entry("slt", Op::Slt, R, Mips1, RdRsRt, Alu, special(0x2a) | RD | RS | RT),
+    entry("sltu", Op::Sltu, R, Mips1, RdRsRt, Alu, special(0x2b) | RD | RS | RT),
+    // Conditional traps
+    entry("tge", Op::Tge, R, Mips2, RsRt, Other, special(0x30) | RS | RT),
+    entry("tgeu", Op::Tgeu, R, Mips2, RsRt, Other, special(0x31) | RS | RT),
+    entry("tlt", Op::Tlt, R, Mips2, RsRt, Other, special(0x32) | RS | RT),
+    entry("tltu", Op::Tltu, R, Mips2, RsRt, Other, special(0x33) | RS | RT),
+    entry("teq", Op::Teq, R, Mips2, RsRt, Other, special(0x34) | RS | RT),
+    entry("tne", Op::Tne, R, Mips2, RsRt, Other, special(0x36) | RS | RT),
+    // SPECIAL2
+    entry("madd", Op::Madd, R, Mips32, RsRt, Alu, special2(0x00) | RS | RT),
+    entry("maddu", Op::Maddu, R, Mips32, RsRt, Alu, special2(0x01) | RS | RT),
+    entry("mul", Op::Mul, R, Mips32, RdRsRt, Alu, special2(0x02) | RD | RS | RT),
+    entry("msub", Op::Msub, R, Mips32, RsRt, Alu, special2(0x04) | RS | RT),
+    entry("msubu", Op::Msubu, R, Mips32, RsRt, Alu, special2(0x05) | RS | RT),
+    entry("clz", Op::Clz, R, Mips32, RdRs, Alu, special2(0x20) | RD | RS),
+    entry("clo", Op::Clo, R, Mips32, RdRs, Alu, special2(0x21) | RD | RS),
+    // Branches
+    entry("bltz", Op::Bltz, I, Mips1, RsLabel, Branch, regimm(0x00) | RS | 4),
+    entry("bgez", Op::Bgez, I, Mips1, RsLabel, Branch, regimm(0x01) | RS | 4),
+    entry("bltzal", Op::Bltzal, I, Mips1, RsLabel, Branch, regimm(0x10) | RS | 4),
+    entry("bgezal", Op::Bgezal, I, Mips1, RsLabel, Branch, regimm(0x11) | RS | 4),
+    entry("beq", Op::Beq, I, Mips1, RsRtLabel, Branch, opcode(0x04) | RS | RT | 4),
+    entry("bne", Op::Bne, I, Mips1, RsRtLabel, Branch, opcode(0x05) | RS | RT | 4),
+    entry("blez", Op::Blez, I, Mips1, RsLabel, Branch, opcode(0x06) | RS | 4),
+    entry("bgtz", Op::Bgtz, I, Mips1, RsLabel, Branch, opcode(0x07) | RS | 4),
+    // Jumps
+    entry("j", Op::J, J, Mips1, Target, Jump, opcode(0x02) | 0x100000),
+    entry("jal", Op::Jal, J, Mips1, Target, Jump, opcode(0x03) | 0x100000),
+    // Immediate arithmetic and logic
+    entry("addi", Op::Addi, I, Mips1, RtRsImm, Alu, opcode(0x08) | RS | RT | 4),
+    entry("addiu", Op::Addiu, I, Mips1, RtRsImm, Alu, opcode(0x09) | RS | RT | 4),
+    entry("slti", Op::Slti, I, Mips1, RtRsImm, Alu, opcode(0x0a) | RS | RT | 4),
+    entry("sltiu", Op::Sltiu, I, Mips1, RtRsImm, Alu, opcode(0x0b) | RS | RT | 4),
+    entry("andi", Op::Andi, I, Mips1, RtRsUimm, Alu, opcode(0x0c) | RS | RT | 4),
+    entry("ori", Op::Ori, I, Mips1, RtRsUimm, Alu, opcode(0x0d) | RS | RT | 4),
+    entry("xori", Op::Xori, I, Mips1, RtRsUimm, Alu, opcode(0x0e) | RS | RT | 4),
+    entry("lui", Op::Lui, I, Mips1, RtUimm, Alu, opcode(0x0f) | RT | 4),
+    // Loads and stores
+    entry("lb", Op::Lb, I, Mips1, RtOffsetRs, Load, opcode(0x20) | RS | RT | 4),
+    entry("lh", Op::Lh, I, Mips1, RtOffsetRs, Load, opcode(0x21) | RS | RT | 4),
+    entry("lwl", Op::Lwl, I, Mips1, RtOffsetRs, Load, opcode(0x22) | RS | RT | 4),
+    entry("lw", Op::Lw, I, Mips1, RtOffsetRs, Load, opcode(0x23) | RS | RT | 4),
+    entry("lbu", Op::Lbu, I, Mips1, RtOffsetRs, Load, opcode(0x24) | RS | RT | 4),
+    entry("lhu", Op::Lhu, I, Mips1, RtOffsetRs, Load, opcode(0x25) | RS | RT | 4),
+    entry("lwr", Op::Lwr, I, Mips1, RtOffsetRs, Load, opcode(0x26) | RS | RT | 4),
+    entry("sb", Op::Sb, I, Mips1, RtOffsetRs, Store, opcode(0x28) | RS | RT | 4),
+    entry("sh", Op::Sh, I, Mips1, RtOffsetRs, Store, opcode(0x29) | RS | RT | 4),
+    entry("swl", Op::Swl, I, Mips1, RtOffsetRs, Store, opcode(0x2a) | RS | RT | 4),
+    entry("sw", Op::Sw, I, Mips1, RtOffsetRs, Store, opcode(0x2b) | RS | RT | 4),
+    entry("swr", Op::Swr, I, Mips1, RtOffsetRs, Store, opcode(0x2e) | RS | RT | 4),
+    entry("ll", Op::Ll, I, Mips2, RtOffsetRs, Load, opcode(0x30) | RS | RT | 4),
+    entry("lwc1", Op::Lwc1, I, Mips1, FtOffsetRs, Load, opcode(0x31) | RS | FT | 4),
+    entry("ldc1", Op::Ldc1, I, Mips2, FtOffsetRs, Load, opcode(0x35) | RS | FT | 8),
+    entry("sc", Op::Sc, I, Mips2, RtOffsetRs, Store, opcode(0x38) | RS | RT | 4),
+    entry("swc1", Op::Swc1, I, Mips1, FtOffsetRs, Store, opcode(0x39) | RS | FT | 4),
+    entry("sdc1", Op::Sdc1, I, Mips2, FtOffsetRs, Store, opcode(0x3d) | RS | FT | 8),
+    // Coprocessor 1 moves and branches
+    entry("mfc1", Op::Mfc1, F, Mips1, RtFs, Float, cop1(0x00, 0) | RT | FS),
+    entry("mtc1", Op::Mtc1, F, Mips1, RtFs, Float, cop1(0x04, 0) | RT | FS),
+    entry("bc1f", Op::Bc1f, F, Mips1, Label, Branch, cop1(0x08, 0) | 4),
+    entry("bc1t", Op::Bc1t, F, Mips1, Label, Branch, cop1(0x08, 0) | (1 << 16) | 4),
+    // Single precision
+    entry("add.s", Op::AddS, F, Mips1, FdFsFt, Float, cop1(0x10, 0x00) | FD | FS | FT),
+    entry("sub.s", Op::SubS, F, Mips1, FdFsFt, Float, cop1(0x10, 0x01) | FD | FS | FT),
+    entry("mul.s", Op::MulS, F, Mips1, FdFsFt, Float, cop1(0x10, 0x02) | FD | FS | FT),
+    entry("div.s", Op::DivS, F, Mips1, FdFsFt, Float, cop1(0x10, 0x03) | FD | FS | FT),
+    entry("sqrt.s", Op::SqrtS, F, Mips2, FdFs, Float, cop1(0x10, 0x04) | FD | FS),
+    entry("abs.s", Op::AbsS, F, Mips1, FdFs, Float, cop1(0x10, 0x05) | FD | FS),
+    entry("mov.s", Op::MovS, F, Mips1, FdFs, Float, cop1(0x10, 0x06) | FD | FS),
+    entry("neg.s", Op::NegS, F, Mips1, FdFs, Float, cop1(0x10, 0x07) | FD | FS),
+    entry("cvt.d.s", Op::CvtDS, F, Mips1, FdFs, Float, cop1(0x10, 0x21) | FD | FS),
+    entry("cvt.w.s", Op::CvtWS, F, Mips1, FdFs, Float, cop1(0x10, 0x24) | FD | FS),
+    entry("c.eq.s", Op::CEqS, F, Mips1, FsFt, Float, cop1(0x10, 0x32) | FS | FT),
+    entry("c.lt.s", Op::CLtS, F, Mips1, FsFt, Float, cop1(0x10, 0x3c) | FS | FT),
+    entry("c.le.s", Op::CLeS, F, Mips1, FsFt, Float, cop1(0x10, 0x3e) | FS | FT),
+    // Double precision
+    entry("add.d", Op::AddD, F, Mips1, FdFsFt, Float, cop1(0x11, 0x00) | FD | FS | FT),
+    entry("sub.d", Op::SubD, F, Mips1, FdFsFt, Float, cop1(0x11, 0x01) | FD | FS | FT),
+    entry("mul.d", Op::MulD, F, Mips1, FdFsFt, Float, cop1(0x11, 0x02) | FD | FS | FT),
+    entry("div.d", Op::DivD, F, Mips1, FdFsFt, Float, cop1(0x11, 0x03) | FD | FS | FT),
+    entry("mov.d", Op::MovD, F, Mips1, FdFs, Float, cop1(0x11, 0x06) | FD | FS),
+    entry("cvt.s.d", Op::CvtSD, F, Mips1, FdFs, Float, cop1(0x11, 0x20) | FD | FS),
+    // Word conversions
+    entry("cvt.s.w", Op::CvtSW, F, Mips1, FdFs, Float, cop1(0x14, 0x20) | FD | FS),
+    entry("cvt.d.w", Op::CvtDW, F, Mips1, FdFs, Float, cop1(0x14, 0x21) | FD | FS),
+    // Coprocessor 0, with $8 (BadVAddr) as the sample register
+    entry("mfc0", Op::Mfc0, C0, Mips1, RtCop0, Other, cop0(0x00, 0) | RT | RD),
+    entry("mtc0", Op::Mtc0, C0, Mips1, RtCop0, Other, cop0(0x04, 0) | RT | RD),
+    entry("eret", Op::Eret, C0, Mips32, Empty, Other, cop0(0x10, 0x18)),
+];
+
+// Looks up an instruction by mnemonic
+pub fn instruction(mnemonic: &str) -> Option<&'static InstructionInfo> {
+    INSTRUCTIONS.iter().find(|info| info.mnemonic == mnemonic)
+}
+
+// The instruction a word encodes, if it is one in the table
+pub fn identify(word: u32) -> Option<&'static InstructionInfo> {
+    INSTRUCTIONS.iter().find(|info| info.matches(word))
+}
@@ -0,0 +1,13 @@
+pub mod buildinfo;
+pub mod diagnostic;
+pub mod dwarf;
+pub mod elf;
+pub mod encoding;
+pub mod endian;
+pub mod isa;
+pub mod lineinfo;
+pub mod machine;
+pub mod memmap;
+pub mod register;
+pub mod schema;
+pub mod symbols;
@@ -0,0 +1,286 @@
+// Line information maps every assembled instruction back to the source it
+// came from, so debuggers and listings can show source instead of
+// addresses. Each row records the file and the span of source it was
+// assembled from; the instructions a pseudo-instruction expands into all
+// point at one shared record of that pseudo-instruction.
+//
+// Line info is written next to the binary, either as versioned JSON (see
+// schema.rs) or in a compact binary form:
+//
+//   "NMLI" u32 version
+//   u32 count, then each file name
+//   u32 count, then each pseudo-instruction: span, text
+//   u32 count, then each row: address, file, span, pseudo-instruction, text
+//
+// where numbers are little-endian u32s, strings are null-terminated, a span
+// is four numbers and a row with no pseudo-instruction stores u32::MAX.
+// Both formats are version 2; version 1 was the TOML format of earlier
+// releases, which had no spans or files and is no longer read.
+
+use crate::elf::Writer;
+use crate::endian::Endian;
+use crate::schema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::str::FromStr;
+
+const LINEINFO_KIND: &str = "lineinfo";
+const BINARY_MAGIC: &[u8; 4] = b"NMLI";
+const NO_PSEUDO_OP: u32 = u32::MAX;
+
+// A range of source text. Lines and columns count from 1, and the end is
+// just past the last character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+
+// A pseudo-instruction as written in the source
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PseudoOp {
+    pub text: String,
+    pub span: Span,
+}
+
+// One assembled instruction
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LineInfo {
+    pub instr_addr: u32,
+    // Index into LineTable::files
+    pub file: u32,
+    pub span: Span,
+    // The machine instruction, as the assembler understood it
+    pub line_contents: String,
+    // Index into LineTable::pseudo_ops of the pseudo-instruction this was
+    // expanded from, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pseudo_op: Option<u32>,
+}
+
+// The line info of a whole program, with rows in address order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LineTable {
+    pub files: Vec<String>,
+    pub pseudo_ops: Vec<PseudoOp>,
+    pub lines: Vec<LineInfo>,
+}
+
+impl LineTable {
+    // The row for the instruction at `address`
+    pub fn at(&self, address: u32) -> Option<&LineInfo> {
+        self.lines
+            .binary_search_by_key(&address, |li| li.instr_addr)
+            .ok()
+            .map(|index| &self.lines[index])
+    }
+
+    pub fn file(&self, li: &LineInfo) -> Option<&str> {
+        self.files.get(li.file as usize).map(String::as_str)
+    }
+
+    pub fn pseudo_op(&self, li: &LineInfo) -> Option<&PseudoOp> {
+        li.pseudo_op
+            .and_then(|index| self.pseudo_ops.get(index as usize))
+    }
+
+    // The rows assembled from a pseudo-instruction, in address order
+    pub fn expansion(&self, pseudo_op: u32) -> impl Iterator<Item = &LineInfo> {
+        self.lines
+            .iter()
+            .filter(move |li| li.pseudo_op == Some(pseudo_op))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineInfoFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl FromStr for LineInfoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(LineInfoFormat::Json),
+            "binary" => Ok(LineInfoFormat::Binary),
+            _ => Err(format!(
+                "unknown line info format `{}`, expected `json` or `binary`",
+                s
+            )),
+        }
+    }
+}
+
+pub fn lineinfo_to_bytes(
+    table: &LineTable,
+    format: LineInfoFormat,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match format {
+        LineInfoFormat::Json => Ok(schema::to_json(LINEINFO_KIND, table)?.into_bytes()),
+        LineInfoFormat::Binary => Ok(binary(table)),
+    }
+}
+
+pub fn lineinfo_export(
+    filename: String,
+    table: &LineTable,
+    format: LineInfoFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(filename, lineinfo_to_bytes(table, format)?)?;
+
+    Ok(())
+}
+
+// Reads either format back, telling them apart by the binary magic number
+pub fn lineinfo_import(file_contents: &[u8]) -> Result<LineTable, Box<dyn std::error::Error>> {
+    let mut table = if file_contents.starts_with(BINARY_MAGIC) {
+        from_binary(file_contents)?
+    } else {
+        let text = std::str::from_utf8(file_contents)
+            .map_err(|_| "line info is neither JSON nor NAME's binary format")?;
+        if !text.trim_start().starts_with('{') {
+            return Err(
+                "line info is in the TOML format of an older NAME, reassemble to update it".into(),
+            );
+        }
+        schema::from_json(LINEINFO_KIND, text)?
+    };
+    table.lines.sort_by_key(|li| li.instr_addr);
+    Ok(table)
+}
+
+fn binary(table: &LineTable) -> Vec<u8> {
+    let mut out = Writer::new(Endian::Little);
+    out.raw(BINARY_MAGIC);
+    out.u32(schema::version(LINEINFO_KIND));
+
+    out.u32(table.files.len() as u32);
+    for file in &table.files {
+        out.string(file);
+    }
+    out.u32(table.pseudo_ops.len() as u32);
+    for pseudo_op in &table.pseudo_ops {
+        write_span(&mut out, pseudo_op.span);
+        out.string(&pseudo_op.text);
+    }
+    out.u32(table.lines.len() as u32);
+    for li in &table.lines {
+        out.u32(li.instr_addr);
+        out.u32(li.file);
+        write_span(&mut out, li.span);
+        out.u32(li.pseudo_op.unwrap_or(NO_PSEUDO_OP));
+        out.string(&li.line_contents);
+    }
+    out.bytes
+}
+
+fn write_span(out: &mut Writer, span: Span) {
+    for value in [span.line, span.column, span.end_line, span.end_column] {
+        out.u32(value);
+    }
+}
+
+// Reads the binary format front to back
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Cursor<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8], Box<dyn std::error::Error>> {
+        let taken = self
+            .bytes
+            .get(self.offset..self.offset + length)
+            .ok_or_else(|| format!("truncated line info, expected data at 0x{:x}", self.offset))?;
+        self.offset += length;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        let bytes = self.take(4)?;
+        Ok(Endian::Little.u32_from_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let rest = &self.bytes[self.offset.min(self.bytes.len())..];
+        let end = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or("unterminated string in line info")?;
+        let text = String::from_utf8_lossy(&rest[..end]).into_owned();
+        self.offset += end + 1;
+        Ok(text)
+    }
+
+    fn span(&mut self) -> Result<Span, Box<dyn std::error::Error>> {
+        Ok(Span {
+            line: self.u32()?,
+            column: self.u32()?,
+            end_line: self.u32()?,
+            end_column: self.u32()?,
+        })
+    }
+
+    // A count followed by that many items, without trusting the count to
+    // size the allocation
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        let count = self.u32()?;
+        let mut items = vec![];
+        for _ in 0..count {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+}
+
+fn from_binary(bytes: &[u8]) -> Result<LineTable, Box<dyn std::error::Error>> {
+    let mut cursor = Cursor {
+        bytes,
+        offset: BINARY_MAGIC.len(),
+    };
+    let version = cursor.u32()?;
+    if version != schema::version(LINEINFO_KIND) {
+        return Err(format!(
+            "line info is version {}, but this version of NAME reads version {}",
+            version,
+            schema::version(LINEINFO_KIND)
+        )
+        .into());
+    }
+
+    let files = cursor.list(|cursor| cursor.string())?;
+    let pseudo_ops = cursor.list(|cursor| {
+        let span = cursor.span()?;
+        Ok(PseudoOp {
+            text: cursor.string()?,
+            span,
+        })
+    })?;
+    let lines = cursor.list(|cursor| {
+        let instr_addr = cursor.u32()?;
+        let file = cursor.u32()?;
+        let span = cursor.span()?;
+        let pseudo_op = Some(cursor.u32()?).filter(|index| *index != NO_PSEUDO_OP);
+        Ok(LineInfo {
+            instr_addr,
+            file,
+            span,
+            line_contents: cursor.string()?,
+            pseudo_op,
+        })
+    })?;
+
+    Ok(LineTable {
+        files,
+        pseudo_ops,
+        lines,
+    })
+}
@@ -0,0 +1,116 @@
+// The architectural state of the emulated machine at one point in time:
+// the registers, coprocessor 0 and, when taken with it, memory. Debuggers
+// step back by restoring an earlier snapshot, graders checkpoint with them
+// and tests compare them. Devices, the console and open files are not part
+// of a snapshot.
+//
+// Snapshots written by `dump` in the debugger are read back by every later
+// version of NAME. A field added to MachineState needs a serde default, for
+// snapshots from before it; any other change to the format bumps its
+// version in schema.rs and adds a migration to MIGRATIONS that rewrites
+// older snapshots to match.
+
+use crate::schema::{self, Migration};
+use serde::{Deserialize, Serialize};
+
+pub const MACHINE_STATE_KIND: &str = "machine-state";
+
+// Upgrades from each earlier version of the snapshot format, oldest first
+const MIGRATIONS: &[Migration] = &[
+    // Version 2 added fp_condition, which version 1 snapshots read as false
+    |_| Ok(()),
+];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MachineState {
+    pub pc: u32,
+    // General purpose registers, indexed by number
+    pub regs: [u32; 32],
+    pub floats: [f32; 32],
+    // Coprocessor 1 condition flag 0
+    #[serde(default)]
+    pub fp_condition: bool,
+    pub hi: u32,
+    pub lo: u32,
+    // Coprocessor 0 exception state
+    pub epc: u32,
+    pub cause: u32,
+    #[serde(default)]
+    pub bad_vaddr: u32,
+    // The rest of coprocessor 0: Status and the Count/Compare timer
+    #[serde(default)]
+    pub status: u32,
+    #[serde(default)]
+    pub count: u32,
+    #[serde(default)]
+    pub compare: u32,
+    // Set once the program has exited
+    pub exit_code: Option<u32>,
+    // Left out of snapshots that only look at the registers, and of those
+    // from before memory was part of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryImage>,
+}
+
+// Memory: the regions the program may use and every page it has written
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct MemoryImage {
+    pub regions: Vec<RegionState>,
+    // In address order
+    pub pages: Vec<Page>,
+    // The lowest address $sp has held, which bounds the heap
+    pub stack_low: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RegionState {
+    pub base: u32,
+    pub length: u32,
+    pub max_length: u32,
+    pub executable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Page {
+    pub address: u32,
+    // Written as hex, two digits a byte
+    #[serde(with = "hex_bytes")]
+    pub bytes: Vec<u8>,
+}
+
+mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom(
+                "page contents have an odd number of hex digits",
+            ));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| D::Error::custom("page contents are not hex"))
+            })
+            .collect()
+    }
+}
+
+impl MachineState {
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        schema::to_json(MACHINE_STATE_KIND, self)
+    }
+
+    // Reads a snapshot written by this version of NAME or an earlier one
+    pub fn from_json(json: &str) -> Result<MachineState, Box<dyn std::error::Error>> {
+        schema::from_json_migrating(MACHINE_STATE_KIND, json, MIGRATIONS)
+    }
+}
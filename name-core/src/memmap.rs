@@ -0,0 +1,50 @@
+// The memory map of the machine name-emu emulates, which is MARS's default
+// one. The emulator lays memory out from these, and the assembler both
+// places sections by them and offers them to programs as constants (see
+// CONSTANTS), so a program that polls a device or checks a pointer against
+// the heap always agrees with the machine it runs on.
+
+pub const TEXT_START: u32 = 0x00400000;
+pub const TEXT_SIZE: u32 = 0x1000;
+// .data comes before the heap, which starts out empty and is grown by sbrk
+pub const DATA_START: u32 = 0x10010000;
+pub const HEAP_START: u32 = 0x10040000;
+pub const HEAP_SIZE: u32 = 0x00400000;
+// The stack grows down from the top of user memory towards the heap
+pub const STACK_END: u32 = 0x80000000;
+
+// MARS's Keyboard and Display MMIO Simulator, and its registers as offsets
+// from its base address
+pub const KEYBOARD_DISPLAY_ADDRESS: u32 = 0xffff0000;
+pub const KEYBOARD_DISPLAY_LENGTH: u32 = 16;
+pub const RECEIVER_CONTROL: u32 = 0x0;
+pub const RECEIVER_DATA: u32 = 0x4;
+pub const TRANSMITTER_CONTROL: u32 = 0x8;
+pub const TRANSMITTER_DATA: u32 = 0xc;
+
+// The constants every program can use as though it had defined them with
+// `.eqv`. A program's own labels and constants of the same name take
+// precedence. Ends are one past the last byte
+pub const CONSTANTS: &[(&str, u32)] = &[
+    ("TEXT_START", TEXT_START),
+    ("TEXT_END", TEXT_START + TEXT_SIZE),
+    ("DATA_START", DATA_START),
+    ("DATA_END", HEAP_START),
+    ("HEAP_START", HEAP_START),
+    ("HEAP_END", HEAP_START + HEAP_SIZE),
+    ("STACK_END", STACK_END),
+    ("MMIO_BASE", KEYBOARD_DISPLAY_ADDRESS),
+    (
+        "MMIO_CONSOLE_RX_CONTROL",
+        KEYBOARD_DISPLAY_ADDRESS + RECEIVER_CONTROL,
+    ),
+    ("MMIO_CONSOLE_RX", KEYBOARD_DISPLAY_ADDRESS + RECEIVER_DATA),
+    (
+        "MMIO_CONSOLE_TX_CONTROL",
+        KEYBOARD_DISPLAY_ADDRESS + TRANSMITTER_CONTROL,
+    ),
+    (
+        "MMIO_CONSOLE_TX",
+        KEYBOARD_DISPLAY_ADDRESS + TRANSMITTER_DATA,
+    ),
+];
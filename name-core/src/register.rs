@@ -0,0 +1,292 @@
+// The MIPS general purpose registers by their conventional names, shared by
+// the assembler and the emulator so neither has to hardcode register numbers.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    Zero,
+    At,
+    V0,
+    V1,
+    A0,
+    A1,
+    A2,
+    A3,
+    T0,
+    T1,
+    T2,
+    T3,
+    T4,
+    T5,
+    T6,
+    T7,
+    S0,
+    S1,
+    S2,
+    S3,
+    S4,
+    S5,
+    S6,
+    S7,
+    T8,
+    T9,
+    K0,
+    K1,
+    Gp,
+    Sp,
+    Fp,
+    Ra,
+}
+
+impl Register {
+    // Every register, indexed by its number
+    pub const ALL: [Register; 32] = [
+        Register::Zero,
+        Register::At,
+        Register::V0,
+        Register::V1,
+        Register::A0,
+        Register::A1,
+        Register::A2,
+        Register::A3,
+        Register::T0,
+        Register::T1,
+        Register::T2,
+        Register::T3,
+        Register::T4,
+        Register::T5,
+        Register::T6,
+        Register::T7,
+        Register::S0,
+        Register::S1,
+        Register::S2,
+        Register::S3,
+        Register::S4,
+        Register::S5,
+        Register::S6,
+        Register::S7,
+        Register::T8,
+        Register::T9,
+        Register::K0,
+        Register::K1,
+        Register::Gp,
+        Register::Sp,
+        Register::Fp,
+        Register::Ra,
+    ];
+
+    pub fn number(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_number(number: usize) -> Option<Register> {
+        Register::ALL.get(number).copied()
+    }
+
+    // The name as written in assembly, including the leading `$`
+    pub fn name(self) -> &'static str {
+        match self {
+            Register::Zero => "$zero",
+            Register::At => "$at",
+            Register::V0 => "$v0",
+            Register::V1 => "$v1",
+            Register::A0 => "$a0",
+            Register::A1 => "$a1",
+            Register::A2 => "$a2",
+            Register::A3 => "$a3",
+            Register::T0 => "$t0",
+            Register::T1 => "$t1",
+            Register::T2 => "$t2",
+            Register::T3 => "$t3",
+            Register::T4 => "$t4",
+            Register::T5 => "$t5",
+            Register::T6 => "$t6",
+            Register::T7 => "$t7",
+            Register::S0 => "$s0",
+            Register::S1 => "$s1",
+            Register::S2 => "$s2",
+            Register::S3 => "$s3",
+            Register::S4 => "$s4",
+            Register::S5 => "$s5",
+            Register::S6 => "$s6",
+            Register::S7 => "$s7",
+            Register::T8 => "$t8",
+            Register::T9 => "$t9",
+            Register::K0 => "$k0",
+            Register::K1 => "$k1",
+            Register::Gp => "$gp",
+            Register::Sp => "$sp",
+            Register::Fp => "$fp",
+            Register::Ra => "$ra",
+        }
+    }
+}
+
+impl From<Register> for usize {
+    fn from(register: Register) -> usize {
+        register.number()
+    }
+}
+
+// Numbered register groups: their letter and first and last members
+const GROUPS: [(char, Register, Register); 5] = [
+    ('v', Register::V0, Register::V1),
+    ('a', Register::A0, Register::A3),
+    ('t', Register::T0, Register::T9),
+    ('s', Register::S0, Register::S7),
+    ('k', Register::K0, Register::K1),
+];
+
+// Why a register name was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterParseError {
+    // Does not start with `$`
+    NotARegister,
+    // A group letter with an index past the end of the group, e.g. `$v2`
+    OutOfGroup { first: Register, last: Register },
+    // A `$n` register number greater than 31
+    NumberOutOfRange,
+    // A floating-point register where an integer one is expected, e.g. `$f2`
+    ExpectedInteger,
+    // An integer register where a floating-point one is expected, e.g. `$t0`
+    ExpectedFloat,
+    // A `$fn` register number greater than 31
+    FloatNumberOutOfRange,
+    // Anything else, e.g. `$x1`
+    Unknown,
+}
+
+impl RegisterParseError {
+    // A message naming the rejected text
+    pub fn describe(&self, text: &str) -> String {
+        match self {
+            RegisterParseError::NotARegister => {
+                format!("expected a register but found `{}`", text)
+            }
+            RegisterParseError::OutOfGroup { first, last } => format!(
+                "unknown register `{}`, expected {} to {}",
+                text, first, last
+            ),
+            RegisterParseError::NumberOutOfRange => format!(
+                "register number out of range in `{}`, expected $0 to $31",
+                text
+            ),
+            RegisterParseError::ExpectedInteger => format!(
+                "expected an integer register but found floating-point register `{}`",
+                text
+            ),
+            RegisterParseError::ExpectedFloat => format!(
+                "expected a floating-point register but found `{}`, expected $f0 to $f31",
+                text
+            ),
+            RegisterParseError::FloatNumberOutOfRange => format!(
+                "register number out of range in `{}`, expected $f0 to $f31",
+                text
+            ),
+            RegisterParseError::Unknown => format!("unknown register `{}`", text),
+        }
+    }
+}
+
+// Accepts every conventional name ($zero, $t0, $ra, ...), the $s8 alias for
+// $fp, and plain register numbers $0 to $31
+impl FromStr for Register {
+    type Err = RegisterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s
+            .strip_prefix('$')
+            .ok_or(RegisterParseError::NotARegister)?;
+
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+            return name
+                .parse::<usize>()
+                .ok()
+                .and_then(Register::from_number)
+                .ok_or(RegisterParseError::NumberOutOfRange);
+        }
+
+        if name == "s8" {
+            return Ok(Register::Fp);
+        }
+        if let Some(register) = Register::ALL.iter().find(|r| &r.name()[1..] == name) {
+            return Ok(*register);
+        }
+        let float_number = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok());
+        if float_number.is_some_and(|n| n < 32) {
+            return Err(RegisterParseError::ExpectedInteger);
+        }
+
+        let mut chars = name.chars();
+        let group = chars
+            .next()
+            .and_then(|letter| GROUPS.iter().find(|(l, _, _)| *l == letter));
+        match group {
+            Some((_, first, last)) if chars.all(|c| c.is_ascii_digit()) && name.len() > 1 => {
+                Err(RegisterParseError::OutOfGroup {
+                    first: *first,
+                    last: *last,
+                })
+            }
+            _ => Err(RegisterParseError::Unknown),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// A coprocessor 1 register, $f0 through $f31. These have no conventional
+// names, so only the number is kept, checked on construction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FloatRegister(u8);
+
+impl FloatRegister {
+    pub fn new(number: usize) -> Option<FloatRegister> {
+        (number < 32).then_some(FloatRegister(number as u8))
+    }
+
+    pub fn number(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl From<FloatRegister> for usize {
+    fn from(register: FloatRegister) -> usize {
+        register.number()
+    }
+}
+
+// Accepts $f0 to $f31
+impl FromStr for FloatRegister {
+    type Err = RegisterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s
+            .strip_prefix('$')
+            .ok_or(RegisterParseError::NotARegister)?;
+
+        match name.strip_prefix('f') {
+            Some(digits) if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {
+                digits
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(FloatRegister::new)
+                    .ok_or(RegisterParseError::FloatNumberOutOfRange)
+            }
+            _ if s.parse::<Register>().is_ok() => Err(RegisterParseError::ExpectedFloat),
+            _ => Err(RegisterParseError::Unknown),
+        }
+    }
+}
+
+impl fmt::Display for FloatRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$f{}", self.0)
+    }
+}
@@ -0,0 +1,115 @@
+// Every JSON document NAME writes is wrapped in a small header naming what
+// it contains and which version of that format it follows, so a reader can
+// reject a file it does not understand instead of misreading it.
+//
+//   { "kind": "symbols", "version": 1, ...fields of the document... }
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// Bumped whenever a serialized type changes incompatibly
+pub const SCHEMA_VERSION: u32 = 1;
+// Kinds that have changed on their own since, with their current version
+const KIND_VERSIONS: &[(&str, u32)] = &[
+    // Version 1 was TOML, see lineinfo.rs
+    ("lineinfo", 2),
+    // Version 1 had no fp_condition, see machine.rs
+    ("machine-state", 2),
+];
+
+// Upgrades the fields of a document from one version of its kind to the
+// next. A version that only adds fields needs nothing done, as long as the
+// new fields have a serde default to fill them in with.
+pub type Migration = fn(&mut serde_json::Map<String, serde_json::Value>) -> Result<(), String>;
+
+// The version of `kind` this build of NAME writes and reads
+pub fn version(kind: &str) -> u32 {
+    KIND_VERSIONS
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .map_or(SCHEMA_VERSION, |(_, version)| *version)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Versioned<T> {
+    pub kind: String,
+    pub version: u32,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+pub fn to_json<T: Serialize>(kind: &str, data: &T) -> Result<String, Box<dyn std::error::Error>> {
+    let document = Versioned {
+        kind: kind.to_string(),
+        version: version(kind),
+        data,
+    };
+    Ok(serde_json::to_string_pretty(&document)? + "\n")
+}
+
+pub fn from_json<T: DeserializeOwned>(
+    kind: &str,
+    json: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let document: Versioned<T> = serde_json::from_str(json)?;
+    if document.kind != kind {
+        return Err(format!("expected a {} file but found {}", kind, document.kind).into());
+    }
+    if document.version != version(kind) {
+        return Err(format!(
+            "{} file is version {}, but this version of NAME reads version {}",
+            kind,
+            document.version,
+            version(kind)
+        )
+        .into());
+    }
+    Ok(document.data)
+}
+
+// Reads a document written by this version of NAME or any earlier one that
+// `migrations` reaches back to: `migrations[i]` upgrades version i + 1 to
+// version i + 2, so the last one upgrades to the version read here.
+pub fn from_json_migrating<T: DeserializeOwned>(
+    kind: &str,
+    json: &str,
+    migrations: &[Migration],
+) -> Result<T, Box<dyn std::error::Error>> {
+    let current = version(kind);
+    let serde_json::Value::Object(mut fields) = serde_json::from_str(json)? else {
+        return Err(format!("expected a {} file", kind).into());
+    };
+    let found = fields
+        .get("kind")
+        .and_then(|kind| kind.as_str())
+        .unwrap_or_default();
+    if found != kind {
+        return Err(format!("expected a {} file but found {}", kind, found).into());
+    }
+    let Some(found_version) = fields.get("version").and_then(|version| version.as_u64()) else {
+        return Err(format!("{} file has no version", kind).into());
+    };
+    let found_version = u32::try_from(found_version).unwrap_or(u32::MAX);
+    let oldest = current.saturating_sub(migrations.len() as u32);
+    if found_version > current {
+        return Err(format!(
+            "{} file is version {}, from a newer version of NAME than this one, which reads versions {} to {}",
+            kind, found_version, oldest, current
+        )
+        .into());
+    }
+    if found_version < oldest {
+        return Err(format!(
+            "{} file is version {}, which is too old for this version of NAME to read",
+            kind, found_version
+        )
+        .into());
+    }
+
+    for migration in &migrations[(found_version - oldest) as usize..] {
+        migration(&mut fields).map_err(|e| format!("cannot read old {} file: {}", kind, e))?;
+    }
+    fields.insert("version".to_string(), current.into());
+    let document: Versioned<T> = serde_json::from_value(serde_json::Value::Object(fields))?;
+    Ok(document.data)
+}
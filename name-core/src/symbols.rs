@@ -0,0 +1,223 @@
+// The label to address map produced by the assembler. It is written next to
+// the binary so the debugger and other tools can turn addresses back into
+// names without having the source.
+
+use crate::schema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct Symbol {
+    // Field order matters: symbols sort by address, then by name
+    pub address: u32,
+    pub name: String,
+    // How many bytes the symbol covers, from `.size` or the data after a
+    // label, or 0 if unknown
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub size: u32,
+    // For data labels, the directive their contents were laid out with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<DataType>,
+    // Exported with .globl, so other objects can refer to it when linked.
+    // Every other label is local to the file it is defined in
+    #[serde(default, skip_serializing_if = "is_local")]
+    pub global: bool,
+}
+
+fn is_zero(size: &u32) -> bool {
+    *size == 0
+}
+
+fn is_local(global: &bool) -> bool {
+    !*global
+}
+
+// The kind of value a data label holds, which tells tools such as the
+// debugger how to show it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    Word,
+    Half,
+    Byte,
+    // Single precision numbers laid out by .float
+    Float,
+    // Characters laid out by .ascii or .asciiz
+    Ascii,
+}
+
+impl DataType {
+    // The size of one element in bytes
+    pub fn size(self) -> u32 {
+        match self {
+            DataType::Word | DataType::Float => 4,
+            DataType::Half => 2,
+            DataType::Byte | DataType::Ascii => 1,
+        }
+    }
+}
+
+impl FromStr for DataType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "word" => Ok(DataType::Word),
+            "half" => Ok(DataType::Half),
+            "byte" => Ok(DataType::Byte),
+            "float" => Ok(DataType::Float),
+            "ascii" | "string" => Ok(DataType::Ascii),
+            _ => Err(format!(
+                "unknown data type `{}`, expected word, half, byte, float or ascii",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataType::Word => write!(f, "word"),
+            DataType::Half => write!(f, "half"),
+            DataType::Byte => write!(f, "byte"),
+            DataType::Float => write!(f, "float"),
+            DataType::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFormat {
+    // One `0x00400000 main` pair per line, followed by the size and type
+    // of data labels where known: `0x10010000 buffer 40 word`, and
+    // `global` for exported labels: `0x00400000 main global`
+    Text,
+    // {"kind": "symbols", "version": 1, "symbols": [{"address": 4194304, "name": "main"}, ...]}
+    Json,
+}
+
+impl SymbolFormat {
+    // Appended to the output file name to name the symbol file
+    pub fn extension(self) -> &'static str {
+        match self {
+            SymbolFormat::Text => "sym",
+            SymbolFormat::Json => "sym.json",
+        }
+    }
+}
+
+impl FromStr for SymbolFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SymbolFormat::Text),
+            "json" => Ok(SymbolFormat::Json),
+            _ => Err(format!(
+                "unknown symbol format `{}`, expected `text` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SymbolFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolFormat::Text => write!(f, "text"),
+            SymbolFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+const SYMBOLS_KIND: &str = "symbols";
+
+#[derive(Deserialize, Serialize)]
+struct SymbolFile {
+    symbols: Vec<Symbol>,
+}
+
+pub fn symbols_to_string(
+    symbols: &[Symbol],
+    format: SymbolFormat,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match format {
+        SymbolFormat::Text => Ok(symbols
+            .iter()
+            .map(|symbol| {
+                let mut line = format!("0x{:08x} {}", symbol.address, symbol.name);
+                if symbol.size != 0 || symbol.data_type.is_some() {
+                    line.push_str(&format!(" {}", symbol.size));
+                }
+                if let Some(data_type) = symbol.data_type {
+                    line.push_str(&format!(" {}", data_type));
+                }
+                if symbol.global {
+                    line.push_str(" global");
+                }
+                line + "\n"
+            })
+            .collect()),
+        SymbolFormat::Json => schema::to_json(
+            SYMBOLS_KIND,
+            &SymbolFile {
+                symbols: symbols.to_vec(),
+            },
+        ),
+    }
+}
+
+pub fn symbols_export(
+    filename: String,
+    symbols: &[Symbol],
+    format: SymbolFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(filename, symbols_to_string(symbols, format)?)?;
+
+    Ok(())
+}
+
+// Reads either format back, telling them apart by the leading `{` of JSON
+pub fn symbols_import(file_contents: &str) -> Result<Vec<Symbol>, Box<dyn std::error::Error>> {
+    if file_contents.trim_start().starts_with('{') {
+        let file: SymbolFile = schema::from_json(SYMBOLS_KIND, file_contents)?;
+        return Ok(file.symbols);
+    }
+
+    let mut symbols = vec![];
+    for line in file_contents.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_symbol_line(line) {
+            Some(symbol) => symbols.push(symbol),
+            None => return Err(format!("malformed symbol line `{}`", line).into()),
+        }
+    }
+    Ok(symbols)
+}
+
+fn parse_symbol_line(line: &str) -> Option<Symbol> {
+    let mut fields: Vec<&str> = line.split_whitespace().collect();
+    let global = fields.len() > 2 && fields.last() == Some(&"global");
+    if global {
+        fields.pop();
+    }
+    let (address, name, rest) = match fields.as_slice() {
+        [address, name, rest @ ..] if rest.len() <= 2 => (address, name, rest),
+        _ => return None,
+    };
+    Some(Symbol {
+        address: u32::from_str_radix(address.strip_prefix("0x")?, 16).ok()?,
+        name: name.to_string(),
+        size: match rest.first() {
+            Some(size) => size.parse().ok()?,
+            None => 0,
+        },
+        data_type: match rest.get(1) {
+            Some(data_type) => Some(data_type.parse().ok()?),
+            None => None,
+        },
+        global,
+    })
+}
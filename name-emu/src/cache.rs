@@ -0,0 +1,250 @@
+use std::fmt;
+use std::str::FromStr;
+
+// An optional model of the instruction and data caches, for seeing how a
+// program's access pattern plays with a given cache. Caches only count hits
+// and misses: memory is always read and written directly, so a cache never
+// changes what a program does.
+//
+// A cache holds `size` bytes in blocks of `block_size` bytes, grouped into
+// sets of `ways` blocks. An address can only be cached in one set, picked by
+// the bits just above the block offset; one way is direct-mapped and a
+// single set is fully associative. When a set is full the replacement
+// policy picks which block to evict.
+//
+// Stores allocate a block on a miss like loads do (write-allocate), so the
+// write policy makes no difference to the hit rate and is not modelled.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    // Evict the block used longest ago
+    Lru,
+    // Evict the block brought in longest ago
+    Fifo,
+    // Evict any block. The choice is pseudo-random but the same every run,
+    // so results can be reproduced.
+    Random,
+}
+
+impl FromStr for Replacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lru" => Ok(Replacement::Lru),
+            "fifo" => Ok(Replacement::Fifo),
+            "random" => Ok(Replacement::Random),
+            _ => Err(format!(
+                "unknown replacement policy `{}`, expected lru, fifo or random",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Replacement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Replacement::Lru => write!(f, "lru"),
+            Replacement::Fifo => write!(f, "fifo"),
+            Replacement::Random => write!(f, "random"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub size: u32,
+    pub block_size: u32,
+    pub ways: u32,
+    pub replacement: Replacement,
+}
+
+impl CacheConfig {
+    pub fn sets(&self) -> u32 {
+        self.size / (self.block_size * self.ways)
+    }
+}
+
+// `SIZE:BLOCK:WAYS[:POLICY]` in bytes, such as `1024:16:2:lru`. Sizes must
+// be powers of two, and the policy defaults to LRU.
+impl FromStr for CacheConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let (size, block_size, ways, replacement) = match fields.as_slice() {
+            [size, block_size, ways] => (size, block_size, ways, Replacement::Lru),
+            [size, block_size, ways, replacement] => (size, block_size, ways, replacement.parse()?),
+            _ => {
+                return Err(format!(
+                    "expected SIZE:BLOCK:WAYS[:POLICY] but found `{}`",
+                    s
+                ))
+            }
+        };
+        let number = |text: &str, what: &str| -> Result<u32, String> {
+            match text.parse::<u32>() {
+                Ok(value) if value.is_power_of_two() => Ok(value),
+                _ => Err(format!(
+                    "the {} must be a power of two, not `{}`",
+                    what, text
+                )),
+            }
+        };
+        let config = CacheConfig {
+            size: number(size, "cache size")?,
+            block_size: number(block_size, "block size")?,
+            ways: number(ways, "associativity")?,
+            replacement,
+        };
+        if config
+            .block_size
+            .checked_mul(config.ways)
+            .is_none_or(|set| set > config.size)
+        {
+            return Err(format!(
+                "a {} byte cache is too small for {} way(s) of {} byte blocks",
+                config.size, config.ways, config.block_size
+            ));
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for CacheConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let organisation = match (self.ways, self.sets()) {
+            (1, _) => "direct-mapped".to_string(),
+            (_, 1) => "fully associative".to_string(),
+            (ways, _) => format!("{}-way set associative", ways),
+        };
+        write!(
+            f,
+            "{} bytes, {} byte blocks, {}",
+            self.size, self.block_size, organisation
+        )?;
+        if self.ways > 1 {
+            write!(f, ", {} replacement", self.replacement)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    tag: u32,
+    // When the block was last used, or brought in for FIFO, counted in accesses
+    stamp: u64,
+}
+
+// A small pseudo-random generator, xorshift32. It is seeded, so whatever
+// it picks is the same every run with the same seed. Random replacement
+// uses it, and so does timing jitter (see jitter.rs)
+#[derive(Debug, Clone)]
+pub struct Xorshift {
+    state: u32,
+}
+
+impl Xorshift {
+    pub fn new(seed: u32) -> Xorshift {
+        // xorshift gets stuck at 0
+        Xorshift { state: seed.max(1) }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    // A number from 0 up to but not including 1
+    pub fn next_fraction(&mut self) -> f64 {
+        self.next_u32() as f64 / (1u64 << 32) as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub config: CacheConfig,
+    // The blocks in each set, at most `ways` of them
+    sets: Vec<Vec<Line>>,
+    pub hits: u64,
+    pub misses: u64,
+    // Misses that had to evict a block
+    pub evictions: u64,
+    accesses: u64,
+    random: Xorshift,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Cache {
+        Cache {
+            config,
+            sets: vec![vec![]; config.sets() as usize],
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+            accesses: 0,
+            random: Xorshift::new(0x2545_f491),
+        }
+    }
+
+    // Looks up the block holding `address`, bringing it in on a miss.
+    // Returns whether it was a hit.
+    pub fn access(&mut self, address: u32) -> bool {
+        self.accesses += 1;
+        let block = address / self.config.block_size;
+        let set_count = self.config.sets();
+        let tag = block / set_count;
+        let set = &mut self.sets[(block % set_count) as usize];
+
+        if let Some(line) = set.iter_mut().find(|line| line.tag == tag) {
+            if self.config.replacement == Replacement::Lru {
+                line.stamp = self.accesses;
+            }
+            self.hits += 1;
+            return true;
+        }
+
+        self.misses += 1;
+        let line = Line {
+            tag,
+            stamp: self.accesses,
+        };
+        if set.len() < self.config.ways as usize {
+            set.push(line);
+            return false;
+        }
+        self.evictions += 1;
+        let victim = match self.config.replacement {
+            Replacement::Lru | Replacement::Fifo => {
+                (0..set.len()).min_by_key(|&i| set[i].stamp).unwrap_or(0)
+            }
+            Replacement::Random => self.random.next_u32() as usize % set.len(),
+        };
+        set[victim] = line;
+        false
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+
+    // One line of statistics, labelled with which cache this is
+    pub fn format(&self, name: &str) -> String {
+        format!(
+            "{} ({}): {} hits, {} misses, {} evictions, {:.1}% hit rate",
+            name,
+            self.config,
+            self.hits,
+            self.misses,
+            self.evictions,
+            self.hit_rate() * 100.0
+        )
+    }
+}
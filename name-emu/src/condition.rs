@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use name_core::register::Register;
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::Mips;
+
+// Conditions on debugger breakpoints, as in `break loop if $t0 == 5 &&
+// mem[$sp] != 0`. A condition is an expression over 32-bit values, which
+// is true when it is not zero:
+//
+//   5  -3  0x10  'a'      numbers and characters
+//   $t0  $hi  $lo  $pc    registers
+//   counter               a label, standing for its address
+//   mem[e]                the word at address e; half[e] and byte[e] read
+//                         less and sign-extend it
+//   - !                   negation and logical not
+//   * / %  + -            arithmetic, which wraps
+//   == != < <= > >=       signed comparisons, giving 1 or 0
+//   && ||                 logical and and or, which stop early
+//   ( )                   grouping
+//
+// Labels are looked up once, when the breakpoint is set. Registers and
+// memory are read each time the breakpoint is reached.
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i32),
+    Register(Register),
+    Hi,
+    Lo,
+    Pc,
+    Load(Width, Box<Expr>),
+    Unary(char, Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Width {
+    Byte,
+    Half,
+    Word,
+}
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+    text: String,
+    expr: Expr,
+}
+
+// Why a condition could not be worked out
+#[derive(Debug)]
+pub enum EvalError {
+    DivisionByZero,
+    Memory(ExecutionErrors),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Memory(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+// Operators from loosest to tightest binding
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<=", ">=", "<", ">"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+struct Parser<'a> {
+    tokens: Vec<String>,
+    position: usize,
+    labels: &'a BTreeMap<String, u32>,
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' {
+            // A character literal such as 'a' or '\n'
+            let end = (i + 1..chars.len())
+                .find(|&j| chars[j] == '\'' && chars[j - 1] != '\\')
+                .ok_or("unterminated character literal")?;
+            tokens.push(chars[i..=end].iter().collect());
+            i = end + 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.' {
+            let end = (i..chars.len())
+                .find(|&j| {
+                    !(chars[j].is_ascii_alphanumeric()
+                        || chars[j] == '_'
+                        || chars[j] == '$'
+                        || chars[j] == '.')
+                })
+                .unwrap_or(chars.len());
+            tokens.push(chars[i..end].iter().collect());
+            i = end;
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if ["==", "!=", "<=", ">=", "&&", "||"].contains(&two.as_str()) {
+                tokens.push(two);
+                i += 2;
+            } else if "+-*/%<>!()[]".contains(c) {
+                tokens.push(c.to_string());
+                i += 1;
+            } else {
+                return Err(format!("unexpected `{}`", c));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(token: &str) -> Option<i32> {
+    if let Some(literal) = token
+        .strip_prefix('\'')
+        .and_then(|token| token.strip_suffix('\''))
+    {
+        return match literal {
+            "\\n" => Some('\n' as i32),
+            "\\t" => Some('\t' as i32),
+            "\\0" => Some(0),
+            "\\\\" => Some('\\' as i32),
+            "\\'" => Some('\'' as i32),
+            _ if literal.chars().count() == 1 => literal.chars().next().map(|c| c as i32),
+            _ => None,
+        };
+    }
+    let parsed = match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse::<u32>().ok(),
+    };
+    parsed.map(|value| value as i32)
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or("the condition ends too soon")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected `{}` but found `{}`", expected, token)),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(operator) = operators
+            .iter()
+            .find(|operator| self.peek() == Some(**operator))
+        {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(Box::new(left), operator, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some("-") => {
+                self.position += 1;
+                Ok(Expr::Unary('-', Box::new(self.unary()?)))
+            }
+            Some("!") => {
+                self.position += 1;
+                Ok(Expr::Unary('!', Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self.next()?;
+        let width = match token.as_str() {
+            "mem" => Some(Width::Word),
+            "half" => Some(Width::Half),
+            "byte" => Some(Width::Byte),
+            _ => None,
+        };
+        if let (Some(width), Some("[")) = (width, self.peek()) {
+            self.position += 1;
+            let address = self.binary(0)?;
+            self.expect("]")?;
+            return Ok(Expr::Load(width, Box::new(address)));
+        }
+        match token.as_str() {
+            "(" => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            "$hi" => Ok(Expr::Hi),
+            "$lo" => Ok(Expr::Lo),
+            "$pc" => Ok(Expr::Pc),
+            register if register.starts_with('$') => register
+                .parse::<Register>()
+                .map(Expr::Register)
+                .map_err(|_| format!("no register named `{}`", register)),
+            _ => {
+                if let Some(number) = parse_number(&token) {
+                    return Ok(Expr::Number(number));
+                }
+                match self.labels.get(&token) {
+                    Some(address) => Ok(Expr::Number(*address as i32)),
+                    None => Err(format!("`{}` is not a number, register or label", token)),
+                }
+            }
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(text: &str, labels: &BTreeMap<String, u32>) -> Result<Condition, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            labels,
+        };
+        let expr = parser.binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected `{}` after the condition", token));
+        }
+        Ok(Condition {
+            text: text.trim().to_string(),
+            expr,
+        })
+    }
+
+    // Whether the condition holds on the machine as it is
+    pub fn holds(&self, mips: &Mips) -> Result<bool, EvalError> {
+        Ok(eval(&self.expr, mips)? != 0)
+    }
+}
+
+fn eval(expr: &Expr, mips: &Mips) -> Result<i32, EvalError> {
+    Ok(match expr {
+        Expr::Number(value) => *value,
+        Expr::Register(register) => mips.reg(*register) as i32,
+        Expr::Hi => mips.mult_hi as i32,
+        Expr::Lo => mips.mult_lo as i32,
+        Expr::Pc => mips.pc() as i32,
+        Expr::Load(width, address) => {
+            let address = eval(address, mips)? as u32;
+            match width {
+                Width::Word => mips.read_w(address).map(|word| word as i32),
+                Width::Half => mips.read_h(address).map(|half| half as i16 as i32),
+                Width::Byte => mips.read_b(address).map(|byte| byte as i8 as i32),
+            }
+            .map_err(EvalError::Memory)?
+        }
+        Expr::Unary('-', operand) => eval(operand, mips)?.wrapping_neg(),
+        Expr::Unary(_, operand) => (eval(operand, mips)? == 0) as i32,
+        Expr::Binary(left, "&&", right) => {
+            (eval(left, mips)? != 0 && eval(right, mips)? != 0) as i32
+        }
+        Expr::Binary(left, "||", right) => {
+            (eval(left, mips)? != 0 || eval(right, mips)? != 0) as i32
+        }
+        Expr::Binary(left, operator, right) => {
+            let (left, right) = (eval(left, mips)?, eval(right, mips)?);
+            match *operator {
+                "==" => (left == right) as i32,
+                "!=" => (left != right) as i32,
+                "<" => (left < right) as i32,
+                "<=" => (left <= right) as i32,
+                ">" => (left > right) as i32,
+                ">=" => (left >= right) as i32,
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "/" | "%" if right == 0 => return Err(EvalError::DivisionByZero),
+                "/" => left.wrapping_div(right),
+                _ => left.wrapping_rem(right),
+            }
+        }
+    })
+}
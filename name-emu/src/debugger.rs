@@ -0,0 +1,874 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+use name_core::elf::{is_elf, read_elf};
+use name_core::lineinfo::{lineinfo_import, LineTable};
+use name_core::machine::MachineState;
+use name_core::register::Register;
+use name_core::symbols::{symbols_import, Symbol, SymbolFormat};
+
+use crate::condition::Condition;
+use crate::limits::{Watchdog, LIMIT_EXIT_CODE};
+use crate::watch::{data_symbols, AccessKind, Watch, WatchValue};
+use crate::{entry_address, report_audit_warnings, reset_mips, with_handler, DynResult, Options};
+use name_emu::disasm::disassemble;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::history::History;
+use name_emu::mips::Mips;
+
+const USAGE: &str =
+    "USAGE: name debug [object file] [line info file (optional for ELF)] [source file (optional)] [--endian big|little] [--entry label|address] [--audit] [--linux] [--verify-load] [--delay-slots] [--stats] [--icache size:block:ways[:policy]] [--dcache size:block:ways[:policy]] [--fault target:bit@when,...] [--fault-seed n] [--jitter probability] [--jitter-seed n] [--files directory] [--newline lf|crlf] [--expand-tabs width] [--strip-read-newline] [--handler label|address] [--trap-syscalls] [--max-steps n] [--timeout seconds] [--history n]";
+
+// How many steps reverse-step can go back without --history
+const DEFAULT_HISTORY: usize = 10000;
+
+const HELP: &str = "\
+Commands:
+  break <target> [if <condition>]
+                     Set a breakpoint at a label, source line, 0x address, or $register (alias: b),
+                     optionally only stopping when a condition holds, such as `$t0 == 5 && mem[$sp] != 0`.
+                     Conditions use numbers, registers, labels, mem[addr], half[addr], byte[addr],
+                     + - * / %, comparisons, ! && || and parentheses
+  delete <target>    Remove a breakpoint (alias: d)
+  breakpoints        List breakpoints
+  step [count]       Execute one or more instructions (alias: s)
+  continue           Run until a breakpoint, exception, or exit (alias: c)
+  reverse-step [n]   Undo one or more instructions (alias: rs)
+  reverse-continue   Undo instructions until a breakpoint, a watch changes, or the history runs out (alias: rc)
+                     Registers and memory go back; output printed, input read and files written do not.
+                     The last 10000 instructions are kept, or as many as --history gives
+  regs               Show the register file (alias: r)
+  fregs              Show the floating-point registers and condition flag (alias: f)
+  mem <addr> <len>   Dump memory starting at a label or address (alias: m)
+  disasm [addr] [n]  Disassemble n instructions starting at addr, default pc (alias: x)
+  watch <expr>       Stop when a value changes: a label, label[index] or address, optionally `as half` etc.,
+                     or a register such as $t0 (alias: w)
+  rwatch <expr>      Stop when the program reads any byte of a label, label[index] or address
+  awatch <expr>      Stop when the program reads or writes any byte of one, even writing the same value
+  unwatch <expr>     Remove a watch
+  print [expr]       Show a watch expression in full, or every data label, as declared (alias: p)
+  watches            List watches and their current values
+  stats              Show how many instructions of each kind have run, an estimate of the cycles taken, and cache hits and misses
+  dump <file>        Write the registers, pc, hi, lo, floating-point state and memory to a JSON snapshot
+  load <file>        Put the registers and memory back as a snapshot from dump has them, from this or an older
+                     version of NAME (snapshots from before memory was dumped leave it as it is)
+  restart            Reload the program and start over
+  help               Show this message
+  quit               Leave the debugger (alias: q)
+An empty line repeats the previous command.";
+
+// A command-line frontend over Mips::step_one, for stepping through
+// assembled programs without an editor attached.
+struct Debugger {
+    mips: Mips,
+    program_data: Vec<u8>,
+    options: Options,
+    entry: u32,
+    lineinfo: LineTable,
+    // Label names from the symbol file or the source, resolved to the
+    // address of the first instruction after them
+    labels: BTreeMap<String, u32>,
+    // The symbol table, which gives data labels a type and size for watches
+    symbols: Vec<Symbol>,
+    // Each breakpoint with the condition it stops on, if it has one
+    breakpoints: BTreeMap<u32, Option<Condition>>,
+    // Each watch with the value it had when last checked
+    watches: Vec<(Watch, Result<WatchValue, ExecutionErrors>)>,
+    // Registers watched the same way
+    register_watches: Vec<(Register, u32)>,
+    // Watches that stop on reads, or on any access, rather than on changes
+    access_watches: Vec<(Watch, AccessKind)>,
+    // Time spent running since the program was loaded, against --max-steps
+    // and --timeout. A program that reaches a limit ends the session
+    watchdog: Watchdog,
+    // The steps run since the program was loaded, most recent last, for
+    // reverse-step and reverse-continue
+    history: History,
+}
+
+pub(crate) fn debug_main(args: &[String], options: Options) -> DynResult<()> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(USAGE.into());
+    }
+
+    let program_data = std::fs::read(&args[0])
+        .map_err(|why| format!("Failed to open provided object file. Reason: {}", why))?;
+    let lineinfo = match args.get(1) {
+        Some(lineinfo_fn) => {
+            let lineinfo_contents = std::fs::read(lineinfo_fn).map_err(|why| {
+                format!("Failed to open provided line info file. Reason: {}", why)
+            })?;
+            lineinfo_import(&lineinfo_contents)?
+        }
+        // Executables from other toolchains have no line info, only symbols
+        None if is_elf(&program_data) => LineTable::default(),
+        None => return Err(USAGE.into()),
+    };
+
+    let mut labels = match args.get(2) {
+        Some(source_fn) => {
+            let source = std::fs::read_to_string(source_fn)
+                .map_err(|why| format!("Failed to open provided source file. Reason: {}", why))?;
+            find_labels(&source, &lineinfo)
+        }
+        None => BTreeMap::new(),
+    };
+    let symbols = load_symbols(&args[0], &program_data, &mut labels)?;
+
+    let entry = entry_address(&program_data, &options, &labels)?;
+    let options = with_handler(&options, &labels)?;
+
+    let mut debugger = Debugger {
+        mips: reset_mips(&program_data, &options, entry)?,
+        watchdog: Watchdog::new(options.limits),
+        history: History::new(options.history.unwrap_or(DEFAULT_HISTORY)),
+        program_data,
+        options,
+        entry,
+        lineinfo,
+        labels,
+        symbols,
+        breakpoints: BTreeMap::new(),
+        watches: vec![],
+        register_watches: vec![],
+        access_watches: vec![],
+    };
+
+    debugger.repl()
+}
+
+// Adds the symbols of an ELF executable, then those from the file
+// `--symbols` wrote next to the object file, if there is one. They are
+// exact, so they win over labels recovered from the source. The symbols
+// read are returned, the symbol file's last so that they take precedence.
+pub(crate) fn load_symbols(
+    object_fn: &str,
+    program_data: &[u8],
+    labels: &mut BTreeMap<String, u32>,
+) -> DynResult<Vec<Symbol>> {
+    let mut symbols = vec![];
+    if is_elf(program_data) {
+        symbols.extend(read_elf(program_data)?.symbols);
+    }
+    for format in [SymbolFormat::Text, SymbolFormat::Json] {
+        let symbols_fn = format!("{}.{}", object_fn, format.extension());
+        if let Ok(contents) = std::fs::read_to_string(&symbols_fn) {
+            let imported = symbols_import(&contents).map_err(|why| {
+                format!("Failed to read symbol file {}. Reason: {}", symbols_fn, why)
+            })?;
+            symbols.extend(imported);
+            break;
+        }
+    }
+    labels.extend(
+        symbols
+            .iter()
+            .map(|symbol| (symbol.name.clone(), symbol.address)),
+    );
+    Ok(symbols)
+}
+
+// Finds `label:` definitions in the source and maps each to the address of
+// the first instruction at or after its line
+pub(crate) fn find_labels(source: &str, lineinfo: &LineTable) -> BTreeMap<String, u32> {
+    let mut by_line: Vec<(u32, u32)> = lineinfo
+        .lines
+        .iter()
+        .map(|li| (li.span.line, li.instr_addr))
+        .collect();
+    by_line.sort();
+
+    let mut labels = BTreeMap::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i as u32 + 1;
+        let Some((name, _)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim();
+        let is_ident = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_ident {
+            continue;
+        }
+        if let Some((_, address)) = by_line.iter().find(|(l, _)| *l >= line_number) {
+            labels.insert(name.to_string(), *address);
+        }
+    }
+    labels
+}
+
+impl Debugger {
+    fn repl(&mut self) -> DynResult<()> {
+        println!("NAME debugger. Type `help` for a list of commands.");
+        self.print_location();
+
+        let stdin = io::stdin();
+        let mut previous = String::new();
+        loop {
+            print!("(name) ");
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                break;
+            }
+            let line = if line.trim().is_empty() {
+                previous.clone()
+            } else {
+                line.trim().to_string()
+            };
+            previous = line.clone();
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((command, operands)) = words.split_first() else {
+                continue;
+            };
+
+            match *command {
+                "break" | "b" => self.set_breakpoint(operands, true),
+                "delete" | "d" => self.set_breakpoint(operands, false),
+                "breakpoints" => {
+                    for (address, condition) in &self.breakpoints {
+                        match condition {
+                            Some(condition) => println!(
+                                "  0x{:08x}  {}  if {}",
+                                address,
+                                self.describe(*address),
+                                condition
+                            ),
+                            None => println!("  0x{:08x}  {}", address, self.describe(*address)),
+                        }
+                    }
+                }
+                "step" | "s" => {
+                    let count = operands
+                        .first()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .unwrap_or(1);
+                    self.step(count);
+                }
+                "continue" | "c" => self.cont(),
+                "reverse-step" | "rs" => {
+                    let count = operands
+                        .first()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .unwrap_or(1);
+                    self.reverse_step(count);
+                }
+                "reverse-continue" | "rc" => self.reverse_continue(),
+                "regs" | "r" => self.print_registers(),
+                "fregs" | "f" => println!("{}", self.mips.format_floats()),
+                "mem" | "m" => self.print_memory(operands),
+                "disasm" | "x" => self.print_disassembly(operands),
+                "watch" | "w" => self.add_watch(&line[command.len()..]),
+                "rwatch" => self.add_access_watch(&line[command.len()..], AccessKind::Read),
+                "awatch" => self.add_access_watch(&line[command.len()..], AccessKind::ReadOrWrite),
+                "unwatch" => self.remove_watch(&line[command.len()..]),
+                "print" | "p" => self.print_data(&line[command.len()..]),
+                "watches" => {
+                    for (watch, value) in &self.watches {
+                        println!("  {} = {}", watch, describe_value(watch, value));
+                    }
+                    for (register, value) in &self.register_watches {
+                        println!("  {} = {}", register, describe_register(*value));
+                    }
+                    for (watch, kind) in &self.access_watches {
+                        match kind {
+                            AccessKind::Read => println!("  {} (on read)", watch),
+                            AccessKind::ReadOrWrite => println!("  {} (on read or write)", watch),
+                        }
+                    }
+                }
+                "stats" => self.print_stats(),
+                "dump" => self.dump_state(operands),
+                "load" => self.load_state(operands),
+                "restart" => {
+                    self.mips = reset_mips(&self.program_data, &self.options, self.entry)?;
+                    self.watchdog = Watchdog::new(self.options.limits);
+                    self.history.clear();
+                    self.check_watches();
+                    self.print_location();
+                }
+                "help" | "h" => println!("{}", HELP),
+                "quit" | "q" => break,
+                _ => println!(
+                    "Unknown command `{}`. Type `help` for a list of commands.",
+                    command
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Turns a label, source line number, 0x-prefixed address, or register
+    // holding an address into an address
+    fn resolve(&self, target: &str) -> Option<u32> {
+        if target.starts_with('$') {
+            return target
+                .parse::<Register>()
+                .ok()
+                .map(|register| self.mips.reg(register));
+        }
+        if let Some(hex) = target.strip_prefix("0x") {
+            return u32::from_str_radix(hex, 16).ok();
+        }
+        if let Ok(line) = target.parse::<u32>() {
+            return self
+                .lineinfo
+                .lines
+                .iter()
+                .filter(|li| li.span.line == line)
+                .map(|li| li.instr_addr)
+                .min();
+        }
+        self.labels.get(target).copied()
+    }
+
+    fn set_breakpoint(&mut self, operands: &[&str], enable: bool) {
+        let Some(target) = operands.first() else {
+            println!("Expected a label, line number, or address");
+            return;
+        };
+        let condition = match operands.get(1..).unwrap_or(&[]) {
+            [] => None,
+            ["if", condition @ ..] if enable => {
+                match Condition::parse(&condition.join(" "), &self.labels) {
+                    Ok(condition) => Some(condition),
+                    Err(why) => {
+                        println!("Could not use the condition: {}", why);
+                        return;
+                    }
+                }
+            }
+            _ => {
+                println!("Expected `if` and a condition after the target");
+                return;
+            }
+        };
+        match self.resolve(target) {
+            Some(address) if enable => {
+                match &condition {
+                    Some(condition) => println!(
+                        "Breakpoint set at 0x{:08x}  {}  if {}",
+                        address,
+                        self.describe(address),
+                        condition
+                    ),
+                    None => println!(
+                        "Breakpoint set at 0x{:08x}  {}",
+                        address,
+                        self.describe(address)
+                    ),
+                }
+                self.breakpoints.insert(address, condition);
+            }
+            Some(address) => {
+                if self.breakpoints.remove(&address).is_some() {
+                    println!("Breakpoint removed from 0x{:08x}", address);
+                } else {
+                    println!("No breakpoint at 0x{:08x}", address);
+                }
+            }
+            None => println!("Could not resolve `{}` to an instruction address", target),
+        }
+    }
+
+    fn add_watch(&mut self, expression: &str) {
+        if expression.trim().is_empty() {
+            println!("Expected a label, label[index], address or register to watch");
+            return;
+        }
+        if expression.trim().starts_with('$') {
+            match expression.trim().parse::<Register>() {
+                Ok(register) => {
+                    let value = self.mips.reg(register);
+                    println!("Watching {} = {}", register, describe_register(value));
+                    self.register_watches.push((register, value));
+                }
+                Err(_) => println!("Could not watch `{}`: no such register", expression.trim()),
+            }
+            return;
+        }
+        match Watch::parse(expression, &self.labels, &self.symbols) {
+            Ok(watch) => {
+                let value = watch.read(&self.mips);
+                println!("Watching {} = {}", watch, describe_value(&watch, &value));
+                self.watches.push((watch, value));
+            }
+            Err(why) => println!("Could not watch `{}`: {}", expression.trim(), why),
+        }
+    }
+
+    // Shows a data label the way it was declared, or all of them without
+    // an expression
+    fn print_data(&self, expression: &str) {
+        let expressions: Vec<String> = if expression.trim().is_empty() {
+            data_symbols(&self.symbols)
+                .into_iter()
+                .map(|symbol| symbol.name.clone())
+                .collect()
+        } else {
+            vec![expression.trim().to_string()]
+        };
+        if expressions.is_empty() {
+            println!("The program has no data labels");
+        }
+        for expression in expressions {
+            match Watch::parse(&expression, &self.labels, &self.symbols) {
+                Ok(watch) => match watch.pretty(&self.mips) {
+                    Ok(text) => println!("{}", text),
+                    Err(e) => println!("{}: {}", watch, e),
+                },
+                Err(why) => println!("Could not print `{}`: {}", expression, why),
+            }
+        }
+    }
+
+    fn add_access_watch(&mut self, expression: &str, kind: AccessKind) {
+        if expression.trim().is_empty() {
+            println!("Expected a label, label[index] or address to watch");
+            return;
+        }
+        match Watch::parse(expression, &self.labels, &self.symbols) {
+            Ok(watch) => {
+                println!(
+                    "Watching {} for {}",
+                    watch,
+                    match kind {
+                        AccessKind::Read => "reads",
+                        AccessKind::ReadOrWrite => "reads and writes",
+                    }
+                );
+                self.access_watches.push((watch, kind));
+            }
+            Err(why) => println!("Could not watch `{}`: {}", expression.trim(), why),
+        }
+    }
+
+    fn remove_watch(&mut self, expression: &str) {
+        let expression = expression.trim();
+        let before = self.watches.len() + self.register_watches.len() + self.access_watches.len();
+        self.watches
+            .retain(|(watch, _)| watch.expression != expression);
+        self.register_watches
+            .retain(|(register, _)| expression.parse::<Register>().ok() != Some(*register));
+        self.access_watches
+            .retain(|(watch, _)| watch.expression != expression);
+        if self.watches.len() + self.register_watches.len() + self.access_watches.len() == before {
+            println!("No watch on `{}`", expression);
+        }
+    }
+
+    // Re-reads every watch, reporting those whose value changed. Returns
+    // whether any did.
+    fn check_watches(&mut self) -> bool {
+        let mut changed = false;
+        for (watch, previous) in &mut self.watches {
+            let value = watch.read(&self.mips);
+            if value != *previous {
+                println!(
+                    "Watch {}: {} -> {}",
+                    watch.expression,
+                    describe_value(watch, previous),
+                    describe_value(watch, &value)
+                );
+                *previous = value;
+                changed = true;
+            }
+        }
+        for (register, previous) in &mut self.register_watches {
+            let value = self.mips.reg(*register);
+            if value != *previous {
+                println!(
+                    "Watch {}: {} -> {}",
+                    register,
+                    describe_register(*previous),
+                    describe_register(value)
+                );
+                *previous = value;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // Reports the access watches the last step touched, going by the
+    // reads and writes recorded while it ran. Returns whether any were
+    fn check_accesses(&mut self) -> bool {
+        let reads = std::mem::take(self.mips.memory_reads.get_mut());
+        let writes: Vec<u32> = self
+            .mips
+            .memory_writes
+            .drain(..)
+            .map(|write| write.address)
+            .collect();
+        let mut accessed = false;
+        for (watch, kind) in &self.access_watches {
+            let read = reads.iter().any(|address| watch.covers(*address));
+            let written = *kind == AccessKind::ReadOrWrite
+                && writes.iter().any(|address| watch.covers(*address));
+            if read || written {
+                let value = watch.read(&self.mips);
+                let access = if written { "written" } else { "read" };
+                println!(
+                    "Watch {}: {}, now {}",
+                    watch.expression,
+                    access,
+                    describe_value(watch, &value)
+                );
+                accessed = true;
+            }
+        }
+        accessed
+    }
+
+    fn step(&mut self, count: usize) {
+        self.watchdog.resume();
+        self.run_steps(count);
+        self.watchdog.pause();
+    }
+
+    fn run_steps(&mut self, count: usize) {
+        for _ in 0..count {
+            // The last instruction can change a watch too
+            let running = self.execute_one();
+            let changed = self.check_accesses() | self.check_watches();
+            if !running {
+                return;
+            }
+            if changed {
+                break;
+            }
+        }
+        self.print_location();
+    }
+
+    fn cont(&mut self) {
+        self.watchdog.resume();
+        self.run_until_stopped();
+        self.watchdog.pause();
+    }
+
+    fn run_until_stopped(&mut self) {
+        loop {
+            let running = self.execute_one();
+            let changed = self.check_accesses() | self.check_watches();
+            if !running {
+                return;
+            }
+            if changed {
+                self.print_location();
+                return;
+            }
+            if self.breakpoint_hit() {
+                self.print_location();
+                return;
+            }
+        }
+    }
+
+    fn reverse_step(&mut self, count: usize) {
+        for _ in 0..count {
+            if !self.undo_one() {
+                break;
+            }
+            if self.check_watches() {
+                break;
+            }
+        }
+        self.print_location();
+    }
+
+    fn reverse_continue(&mut self) {
+        while self.undo_one() {
+            if self.check_watches() {
+                break;
+            }
+            if self.breakpoint_hit() {
+                break;
+            }
+        }
+        self.print_location();
+    }
+
+    // Whether to stop at a breakpoint at pc, saying so if so: always for
+    // a plain breakpoint, and when its condition holds or cannot be worked
+    // out for a conditional one
+    fn breakpoint_hit(&self) -> bool {
+        let pc = self.mips.pc();
+        match self.breakpoints.get(&pc) {
+            None => false,
+            Some(None) => {
+                println!("Hit breakpoint");
+                true
+            }
+            Some(Some(condition)) => match condition.holds(&self.mips) {
+                Ok(false) => false,
+                Ok(true) => {
+                    println!("Hit breakpoint, {}", condition);
+                    true
+                }
+                Err(why) => {
+                    println!(
+                        "Stopped at breakpoint: could not work out `{}`: {}",
+                        condition, why
+                    );
+                    true
+                }
+            },
+        }
+    }
+
+    // Undoes the last step run. Returns whether there was one to undo
+    fn undo_one(&mut self) -> bool {
+        if self.history.undo(&mut self.mips) {
+            return true;
+        }
+        match self.history.capacity() {
+            0 => println!("No history is kept; start the debugger with --history n to step back"),
+            capacity => println!(
+                "No earlier steps to go back to (up to {} are kept)",
+                capacity
+            ),
+        }
+        false
+    }
+
+    fn print_stats(&self) {
+        println!("{}", self.mips.stats.format());
+        if self.mips.icache.is_some() || self.mips.dcache.is_some() {
+            println!("{}", self.mips.format_caches());
+        }
+    }
+
+    // Runs one instruction, reporting why execution stopped if it did.
+    // Returns whether the program can keep going.
+    fn execute_one(&mut self) -> bool {
+        if let Err(ExecutionErrors::Event {
+            event: ExecutionEvents::ProgramComplete,
+        }) = self.mips.prev_ins_result
+        {
+            println!("The program has finished. Use `restart` to run it again.");
+            return false;
+        }
+        if let Some(reason) = self.watchdog.exceeded(&self.mips) {
+            println!("\nStopped: {}", reason);
+            self.print_registers();
+            self.print_location();
+            if self.options.stats {
+                self.print_stats();
+            }
+            std::process::exit(LIMIT_EXIT_CODE);
+        }
+
+        self.history.begin(&mut self.mips);
+        // Access watches need every byte the step reads and writes
+        let watching = !self.access_watches.is_empty();
+        if watching {
+            self.mips.record_reads = true;
+            self.mips.record_writes = true;
+        }
+        let step = self.mips.step_one(&mut io::sink());
+        self.history.finish(&mut self.mips);
+        self.mips.record_reads = false;
+        self.mips.record_writes = false;
+        report_audit_warnings(&mut self.mips);
+        match step {
+            Ok(()) => true,
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => {
+                println!(
+                    "\nProgram exited with code {}",
+                    self.mips.exit_code.unwrap_or(0)
+                );
+                if self.options.stats {
+                    self.print_stats();
+                }
+                false
+            }
+            Err(error) => {
+                match error.exception_code() {
+                    Some(code) => println!(
+                        "\nException at 0x{:08x} ({}): {}",
+                        self.mips.epc, code, error
+                    ),
+                    None => println!("\nError at 0x{:08x}: {}", self.mips.pc(), error),
+                }
+                self.print_location();
+                false
+            }
+        }
+    }
+
+    // The source line an address came from, if known, along with the
+    // pseudo-instruction it is part of
+    fn describe(&self, address: u32) -> String {
+        let Some(li) = self.lineinfo.at(address) else {
+            return String::new();
+        };
+        match self.lineinfo.pseudo_op(li) {
+            Some(pseudo_op) => format!(
+                "line {}: {} (from {})",
+                li.span.line, li.line_contents, pseudo_op.text
+            ),
+            None => format!("line {}: {}", li.span.line, li.line_contents),
+        }
+    }
+
+    fn print_location(&mut self) {
+        let pc = self.mips.pc();
+        match self.mips.read_w(pc) {
+            Ok(word) => println!(
+                "=> 0x{:08x}  {:<28} {}",
+                pc,
+                disassemble(word, pc),
+                self.describe(pc)
+            ),
+            Err(_) => println!("=> 0x{:08x}", pc),
+        }
+    }
+
+    fn print_registers(&self) {
+        println!("{}", self.mips.format_registers());
+    }
+
+    fn dump_state(&self, operands: &[&str]) {
+        let Some(path) = operands.first() else {
+            println!("Usage: dump <file>");
+            return;
+        };
+        let written = self
+            .mips
+            .snapshot()
+            .to_json()
+            .and_then(|json| Ok(std::fs::write(path, json)?));
+        match written {
+            Ok(()) => println!("Machine state written to {}", path),
+            Err(why) => println!("Failed to write {}. Reason: {}", path, why),
+        }
+    }
+
+    fn load_state(&mut self, operands: &[&str]) {
+        let Some(path) = operands.first() else {
+            println!("Usage: load <file>");
+            return;
+        };
+        let state = std::fs::read_to_string(path)
+            .map_err(|why| why.into())
+            .and_then(|json| MachineState::from_json(&json));
+        match state {
+            Ok(state) => {
+                self.mips.restore(&state);
+                // The steps before the load led somewhere else
+                self.history.clear();
+                println!("Machine state loaded from {}", path);
+                self.check_watches();
+                self.print_location();
+            }
+            Err(why) => println!("Failed to load {}. Reason: {}", path, why),
+        }
+    }
+
+    fn print_memory(&mut self, operands: &[&str]) {
+        let (Some(target), Some(length)) = (operands.first(), operands.get(1)) else {
+            println!("Usage: mem <addr> <len>");
+            return;
+        };
+        let Some(start) = self.resolve(target) else {
+            println!("Could not resolve `{}` to an address", target);
+            return;
+        };
+        let Ok(length) = length.parse::<u32>() else {
+            println!("Expected a byte count but found `{}`", length);
+            return;
+        };
+
+        // The dump stops at the top of the address space rather than wrapping
+        for row in (0..length).step_by(16) {
+            let Some(row_address) = start.checked_add(row) else {
+                break;
+            };
+            let mut line = format!("0x{:08x}:", row_address);
+            for offset in row..row.saturating_add(16).min(length) {
+                let Some(address) = start.checked_add(offset) else {
+                    break;
+                };
+                match self.mips.read_b(address) {
+                    Ok(byte) => line.push_str(&format!(" {:02x}", byte)),
+                    Err(_) => line.push_str(" ??"),
+                }
+            }
+            println!("{}", line);
+        }
+    }
+
+    fn print_disassembly(&mut self, operands: &[&str]) {
+        let start = match operands.first() {
+            Some(target) => match self.resolve(target) {
+                Some(address) => address,
+                None => {
+                    println!("Could not resolve `{}` to an address", target);
+                    return;
+                }
+            },
+            None => self.mips.pc(),
+        };
+        let count = operands
+            .get(1)
+            .and_then(|n| n.parse::<u32>().ok())
+            .unwrap_or(10);
+
+        for i in 0..count {
+            let Some(address) = i
+                .checked_mul(4)
+                .and_then(|offset| start.checked_add(offset))
+            else {
+                break;
+            };
+            if address as usize >= self.mips.stop_address {
+                break;
+            }
+            let Ok(word) = self.mips.read_w(address) else {
+                break;
+            };
+            for (name, _) in self.labels.iter().filter(|(_, a)| **a == address) {
+                println!("{}:", name);
+            }
+            let marker = if address == self.mips.pc() {
+                "=>"
+            } else {
+                "  "
+            };
+            let breakpoint = if self.breakpoints.contains_key(&address) {
+                "*"
+            } else {
+                " "
+            };
+            println!(
+                "{}{} 0x{:08x}  {:08x}  {:<28} {}",
+                marker,
+                breakpoint,
+                address,
+                word,
+                disassemble(word, address),
+                self.describe(address)
+            );
+        }
+    }
+}
+
+fn describe_register(value: u32) -> String {
+    format!("{} (0x{:08x})", value as i32, value)
+}
+
+fn describe_value(watch: &Watch, value: &Result<WatchValue, ExecutionErrors>) -> String {
+    match value {
+        Ok(value) => watch.format(value),
+        Err(_) => "<unreadable>".to_string(),
+    }
+}
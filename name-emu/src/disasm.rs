@@ -0,0 +1,69 @@
+use name_core::isa::{identify, Operand};
+use name_core::register::Register;
+
+use crate::mips::{Instructions, Mips};
+
+// Turns a machine word back into assembly text. The address is needed to
+// resolve PC-relative branch and region-relative jump targets.
+pub fn disassemble(word: u32, address: u32) -> String {
+    if word == 0 {
+        return "nop".to_string();
+    }
+    if let Instructions::Hypercall(r) = Mips::decode(word) {
+        return format!(
+            "udi 0x{:x}, {}, {}, {}",
+            r.funct,
+            Register::ALL[r.rd].name(),
+            Register::ALL[r.rs].name(),
+            Register::ALL[r.rt].name()
+        );
+    }
+    let Some(info) = identify(word) else {
+        return unknown(word);
+    };
+
+    let operands: Vec<String> = info
+        .operands
+        .list()
+        .iter()
+        .map(|operand| format_operand(*operand, word, address))
+        .collect();
+    match operands.as_slice() {
+        [] => info.mnemonic.to_string(),
+        [register, offset, base] if info.operands.is_memory() => {
+            format!("{} {}, {}({})", info.mnemonic, register, offset, base)
+        }
+        _ => format!("{} {}", info.mnemonic, operands.join(", ")),
+    }
+}
+
+fn format_operand(operand: Operand, word: u32, address: u32) -> String {
+    let value = operand.field().get(word);
+    match operand {
+        Operand::Rd | Operand::Rs | Operand::Rt => Register::ALL[value as usize].name().to_string(),
+        Operand::Shamt => value.to_string(),
+        Operand::Imm => (value as u16 as i16).to_string(),
+        Operand::Uimm => format!("0x{:x}", value),
+        // Branch offsets are in words, relative to the delay slot
+        Operand::Label => {
+            let offset = (value as u16 as i16 as i32) << 2;
+            format!(
+                "0x{:08x}",
+                address.wrapping_add(4).wrapping_add(offset as u32)
+            )
+        }
+        // Jumps keep the top nybble of the delay slot's address
+        Operand::Target => {
+            format!(
+                "0x{:08x}",
+                (address.wrapping_add(4) & 0xF0000000) | (value << 2)
+            )
+        }
+        Operand::Fd | Operand::Fs | Operand::Ft => format!("$f{}", value),
+        Operand::Cop0 => format!("${}", value),
+    }
+}
+
+fn unknown(word: u32) -> String {
+    format!(".word 0x{:08x}", word)
+}
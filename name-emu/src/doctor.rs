@@ -0,0 +1,353 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use name_as::command::substitute;
+use name_as::config::{read_config, Config};
+use name_as::nma::{assemble_source, AssembledObject, AssemblerOptions};
+
+use crate::DynResult;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::mips::Mips;
+use name_emu::syscall::BufferConsole;
+
+const USAGE: &str = "USAGE: name doctor [config file (default: name.toml)]";
+
+// `name doctor`: checks that NAME works on this machine, for students to
+// run before asking for help. It assembles and runs a small program, reads
+// the config file name-as would pick up here, looks for the programs its
+// as_cmd runs, and checks that the terminal suits the interactive modes,
+// then prints what it found.
+
+// Sums 1 to 10 in a subroutine and prints the result, so the assembler,
+// pseudo-instructions, data, calls and syscalls all have to work
+const SMOKE_TEST: &str = r#"
+        .data
+message: .asciiz "1 + ... + 10 = "
+        .text
+main:   la $a0, message
+        li $v0, 4
+        syscall
+        li $a0, 10
+        jal sum
+        addu $a0, $v0, $zero
+        li $v0, 1
+        syscall
+        li $v0, 10
+        syscall
+sum:    li $v0, 0
+loop:   add $v0, $v0, $a0
+        addi $a0, $a0, -1
+        bne $a0, $zero, loop
+        jr $ra
+"#;
+const SMOKE_TEST_OUTPUT: &str = "1 + ... + 10 = 55";
+const SMOKE_TEST_STEPS: u64 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    // Works, but may not be what the student expects
+    Warning,
+    Failed,
+}
+
+struct Finding {
+    status: Status,
+    check: &'static str,
+    detail: String,
+}
+
+fn finding(status: Status, check: &'static str, detail: impl Into<String>) -> Finding {
+    Finding {
+        status,
+        check,
+        detail: detail.into(),
+    }
+}
+
+fn smoke_test(findings: &mut Vec<Finding>) {
+    let options = AssemblerOptions {
+        file_name: "smoke-test.asm".to_string(),
+        ..Default::default()
+    };
+    let assembled = match assemble_source(SMOKE_TEST, &options) {
+        Ok(assembled) => assembled,
+        Err(e) => {
+            findings.push(finding(
+                Status::Failed,
+                "assembler",
+                format!("the test program does not assemble: {}", e.message()),
+            ));
+            return;
+        }
+    };
+    findings.push(finding(
+        Status::Ok,
+        "assembler",
+        format!(
+            "the test program assembled into {} instructions",
+            assembled.text().len() / 4
+        ),
+    ));
+
+    match run(&assembled) {
+        Ok(output) if output == SMOKE_TEST_OUTPUT => findings.push(finding(
+            Status::Ok,
+            "emulator",
+            format!("the test program printed \"{}\"", output),
+        )),
+        Ok(output) => findings.push(finding(
+            Status::Failed,
+            "emulator",
+            format!(
+                "the test program printed \"{}\" instead of \"{}\"",
+                output, SMOKE_TEST_OUTPUT
+            ),
+        )),
+        Err(e) => findings.push(finding(Status::Failed, "emulator", e)),
+    }
+}
+
+// Runs the test program, giving what it printed
+fn run(assembled: &AssembledObject) -> Result<String, String> {
+    let console = BufferConsole::new("");
+    let output = console.output.clone();
+    let mut mips: Mips = Default::default();
+    mips.console = Box::new(console);
+    mips.load_text_at(assembled.text_address(), assembled.text(), assembled.entry)
+        .map_err(|e| e.to_string())?;
+    mips.load_data_at(assembled.data_address(), assembled.data())
+        .map_err(|e| e.to_string())?;
+
+    while mips.stats.instructions < SMOKE_TEST_STEPS {
+        match mips.step_one(&mut io::sink()) {
+            Ok(()) => continue,
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => return Ok(output.borrow().clone()),
+            Err(e) => {
+                return Err(format!(
+                    "the test program stopped at 0x{:08x}: {}",
+                    mips.pc(),
+                    e
+                ))
+            }
+        }
+    }
+    Err(format!(
+        "the test program was still running after {} instructions",
+        SMOKE_TEST_STEPS
+    ))
+}
+
+fn check_config(path: &str, findings: &mut Vec<Finding>) {
+    if !Path::new(path).exists() {
+        findings.push(finding(
+            Status::Warning,
+            "config",
+            format!(
+                "there is no {} here, so name-as will use its own settings",
+                path
+            ),
+        ));
+        return;
+    }
+    let config = match read_config(path) {
+        Ok(config) => config,
+        Err(e) => {
+            findings.push(finding(
+                Status::Failed,
+                "config",
+                format!("{} cannot be read: {}", path, e),
+            ));
+            return;
+        }
+    };
+    let assembler = if config.as_cmd.is_empty() {
+        "NMA".to_string()
+    } else {
+        "its as_cmd".to_string()
+    };
+    let profiles = match config.profiles.len() {
+        0 => String::new(),
+        n => format!(
+            ", with {} profile(s): {}",
+            n,
+            config
+                .profiles
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    findings.push(finding(
+        Status::Ok,
+        "config",
+        format!("{} assembles with {}{}", path, assembler, profiles),
+    ));
+    check_commands(&config, findings);
+}
+
+// Each program the config's as_cmd runs has to be on the PATH. Those only
+// a profile runs are warned about, since the profile may never be used
+fn check_commands(config: &Config, findings: &mut Vec<Finding>) {
+    let commands =
+        config
+            .as_cmd
+            .iter()
+            .map(|command| (None, command))
+            .chain(config.profiles.iter().flat_map(|(name, profile)| {
+                profile
+                    .as_cmd
+                    .iter()
+                    .flatten()
+                    .map(move |command| (Some(name), command))
+            }));
+    let mut checked: Vec<String> = vec![];
+    for (profile, command) in commands {
+        let missing = match profile {
+            None => Status::Failed,
+            Some(_) => Status::Warning,
+        };
+        let needed_by = profile.map_or(String::new(), |name| format!(" (profile {})", name));
+        let Some(program) = command.program() else {
+            findings.push(finding(
+                missing,
+                "as_cmd",
+                format!("`{}` has no program to run{}", command, needed_by),
+            ));
+            continue;
+        };
+        let program = match substitute(program, "", "") {
+            Ok(program) => program,
+            Err(e) => {
+                findings.push(finding(missing, "as_cmd", format!("{}{}", e, needed_by)));
+                continue;
+            }
+        };
+        if checked.contains(&program) {
+            continue;
+        }
+        match find_program(&program) {
+            Some(path) => findings.push(finding(
+                Status::Ok,
+                "as_cmd",
+                format!("`{}` is {}", program, path.display()),
+            )),
+            None => findings.push(finding(
+                missing,
+                "as_cmd",
+                format!(
+                    "`{}` is not installed, or not on the PATH{}",
+                    program, needed_by
+                ),
+            )),
+        }
+        checked.push(program);
+    }
+}
+
+// Where `program` would be run from
+fn find_program(program: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    let candidates: Vec<PathBuf> =
+        if program.contains(std::path::MAIN_SEPARATOR) || program.contains('/') {
+            vec![PathBuf::from(program)]
+        } else {
+            env::split_paths(&env::var_os("PATH").unwrap_or_default())
+                .map(|dir| dir.join(program))
+                .collect()
+        };
+    candidates
+        .into_iter()
+        .flat_map(|path| {
+            extensions
+                .iter()
+                .map(move |extension| PathBuf::from(format!("{}{}", path.display(), extension)))
+        })
+        .find(|path| path.is_file())
+}
+
+// `name debug` and `name learn` read commands line by line and print as
+// they go, so they need a terminal on both ends
+fn check_terminal(findings: &mut Vec<Finding>) {
+    let (stdin, stdout) = (io::stdin().is_terminal(), io::stdout().is_terminal());
+    if !stdin || !stdout {
+        let which = match (stdin, stdout) {
+            (false, false) => "neither input nor output is",
+            (false, true) => "input is not",
+            _ => "output is not",
+        };
+        findings.push(finding(
+            Status::Warning,
+            "terminal",
+            format!(
+                "{} a terminal, so `name debug` and `name learn` will not be interactive",
+                which
+            ),
+        ));
+        return;
+    }
+    match env::var("TERM").ok().as_deref() {
+        Some("dumb") => findings.push(finding(
+            Status::Warning,
+            "terminal",
+            "TERM is `dumb`, so line editing may not work",
+        )),
+        Some(term) => findings.push(finding(
+            Status::Ok,
+            "terminal",
+            format!("interactive, TERM is `{}`", term),
+        )),
+        None => findings.push(finding(Status::Ok, "terminal", "interactive")),
+    }
+}
+
+pub fn doctor_main(args: &[String]) -> DynResult<()> {
+    let config_fn = match args {
+        [] => name_as::args::DEFAULT_CONFIG,
+        [config_fn] => config_fn.as_str(),
+        _ => return Err(USAGE.into()),
+    };
+
+    let mut findings = vec![];
+    smoke_test(&mut findings);
+    check_config(config_fn, &mut findings);
+    check_terminal(&mut findings);
+
+    println!("NAME {}", env!("CARGO_PKG_VERSION"));
+    for Finding {
+        status,
+        check,
+        detail,
+    } in &findings
+    {
+        let label = match status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Failed => "FAILED",
+        };
+        println!("  {:<8} {:<10} {}", label, check, detail);
+    }
+    println!();
+
+    let failed = findings
+        .iter()
+        .filter(|finding| finding.status == Status::Failed)
+        .count();
+    if failed > 0 {
+        return Err(format!(
+            "{} check(s) failed; include this report when asking for help",
+            failed
+        )
+        .into());
+    }
+    println!("NAME is ready to use");
+    Ok(())
+}
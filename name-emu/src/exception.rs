@@ -1,104 +1,107 @@
 use std::fmt;
 
-use dap::types::{ExceptionDetails, ExceptionBreakMode};
-use dap::responses::ExceptionInfoResponse;
-
-use crate::mips::REGISTER_NAMES;
-
-#[derive(Debug)]
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum ExecutionErrors {
     // The program attempted to access an address that was within a
     // valid range, but was outside the current allocation for that range.
     // This should be treated as a warning, and read out as zero.
-    MemoryObviousOverrunAccess { load_address: u32 },
+    MemoryObviousOverrunAccess {
+        load_address: u32,
+    },
     // The program attempted to read from an area for which no valid range existed.
-    MemoryIllegalAccess { load_address: u32 },
+    MemoryIllegalAccess {
+        load_address: u32,
+    },
+    // A halfword or word access whose address is not a multiple of its size.
+    // `store` tells a store apart from a load, as the hardware does.
+    MemoryUnalignedAccess {
+        load_address: u32,
+        size: u32,
+        store: bool,
+    },
 
-    UndefinedInstruction { instruction: u32 },
-    // Can also refer to underflow
-    IntegerOverflow { rt: usize, rs: usize, value1: u32, value2: u32 },
+    UndefinedInstruction {
+        instruction: u32,
+    },
+    // A trapping add or subtract (add, addi, sub) overflowed as signed arithmetic.
+    // Can also refer to underflow. `op` is '+' or '-'.
+    ArithmeticOverflow {
+        address: u32,
+        lhs: u32,
+        rhs: u32,
+        op: char,
+    },
 
-    Event { event: ExecutionEvents }
+    // A break instruction ran
+    Breakpoint {
+        address: u32,
+    },
+    // A conditional trap (tge, teq, ...) found its condition true
+    Trap {
+        address: u32,
+    },
+
+    // The program requested a syscall service that NAME does not provide
+    UnknownSyscall {
+        service: u32,
+    },
+    // A syscall expected input (e.g. an integer) that could not be read
+    SyscallInputError {
+        service: u32,
+    },
+    // sbrk was asked for more memory than the heap region can hold
+    HeapExhausted {
+        requested: u32,
+    },
+    // The heap and the stack have grown into each other: either the heap
+    // break was moved past the lowest address $sp has held, or $sp was
+    // moved below the break
+    HeapStackCollision {
+        heap_break: u32,
+        stack_low: u32,
+    },
+    // jr or jalr jumped to an address that does not hold code
+    IllegalJump {
+        target: u32,
+    },
+    // An instruction was fetched from an address that is misaligned or
+    // outside memory, as after a jump or eret to a bad address
+    IllegalFetch {
+        address: u32,
+    },
+    // The program being loaded does not fit in the .text or .data region
+    ProgramTooLarge {
+        length: u32,
+    },
+
+    Event {
+        event: ExecutionEvents,
+    },
 }
 
-#[derive(Debug)]
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum ExecutionEvents {
     // The program is done executing.
-    ProgramComplete
-
-    // Eventually instruction/data/etc. breakpoints will go here too
+    ProgramComplete, // Eventually instruction/data/etc. breakpoints will go here too
 }
 
 impl fmt::Display for ExecutionErrors {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let ExecutionErrors::HeapStackCollision {
+            heap_break,
+            stack_low,
+        } = self
+        {
+            return write!(
+                f,
+                "HeapStackCollision {{ heap_break: 0x{:08x}, stack_low: 0x{:08x} }}",
+                heap_break, stack_low
+            );
+        }
         write!(f, "{:?}", self)
         // or, alternatively:
         // fmt::Debug::fmt(self, f)
     }
 }
 
-
-pub fn exception_pretty_print(reason: Result<(), ExecutionErrors>) -> ExceptionInfoResponse {
-    match reason {
-    Ok(()) => ExceptionInfoResponse {
-        exception_id: "No exception".into(),
-        description: None,
-        break_mode: ExceptionBreakMode::Never,
-        details: None
-    },
-    Err(reason) =>  match reason {
-        // These events aren't lifted out as exceptions,
-        // so a well-formed debug adapter should not attempt to view them
-        ExecutionErrors::Event { .. } => ExceptionInfoResponse {
-            exception_id: "Execution Event".into(),
-            description: None,
-            break_mode: ExceptionBreakMode::Never,
-            details: None
-        },
-        ExecutionErrors::MemoryObviousOverrunAccess { load_address } => ExceptionInfoResponse { 
-            exception_id: "Buffer Overflow".into(), 
-            description: Some("NAME detected a buffer overflow error. You may have attempted an acccess outside the bounds of a heap buffer.".into()), 
-            break_mode: ExceptionBreakMode::Always, 
-            details: Some(ExceptionDetails { 
-                message: Some( format!("Access location: {:x}", load_address)
-            ), 
-            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
-        },
-        ExecutionErrors::MemoryIllegalAccess { load_address } => 
-        ExceptionInfoResponse { 
-            exception_id: "Illegal Access".into(), 
-            description: Some("The program attempted to access memory that does not exist.".into()), 
-            break_mode: ExceptionBreakMode::Always, 
-            details: Some(ExceptionDetails { 
-                message: Some( format!("Access location: {:x}", load_address)
-            ), 
-            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
-        },
-        ExecutionErrors::UndefinedInstruction { instruction } =>
-        ExceptionInfoResponse { 
-            exception_id: "Undefined Instruction".into(), 
-            description: Some("The program attempted to execute a MIPS instruction that does not exist.".into()), 
-            break_mode: ExceptionBreakMode::Always, 
-            details: Some(ExceptionDetails { 
-                message: Some( format!("Instruction: {:x}", instruction)
-            ), 
-            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
-        },
-        ExecutionErrors::IntegerOverflow { rt, rs, value1, value2 } =>
-        ExceptionInfoResponse { 
-            exception_id: "Integer Overflow".into(), 
-            description: Some("The program attempted to perform an integer operation that caused an overflow.".into()), 
-            break_mode: ExceptionBreakMode::Always, 
-            details: Some(ExceptionDetails { 
-                message: Some( format!("rs: {}, value: {:x}\nrt: {}, value: {:x}", REGISTER_NAMES[rs], value1, REGISTER_NAMES[rt], value2)
-            ), 
-            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
-        },
-            
-    }
-    }
-
-   
-}
\ No newline at end of file
+impl std::error::Error for ExecutionErrors {}
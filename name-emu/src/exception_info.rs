@@ -0,0 +1,166 @@
+use dap::responses::ExceptionInfoResponse;
+use dap::types::{ExceptionBreakMode, ExceptionDetails};
+
+use name_emu::exception::ExecutionErrors;
+
+// Describes the result of the last instruction for the debug adapter's
+// exceptionInfo request
+pub fn exception_pretty_print(reason: Result<(), ExecutionErrors>) -> ExceptionInfoResponse {
+    match reason {
+    Ok(()) => ExceptionInfoResponse {
+        exception_id: "No exception".into(),
+        description: None,
+        break_mode: ExceptionBreakMode::Never,
+        details: None
+    },
+    Err(reason) =>  match reason {
+        // These events aren't lifted out as exceptions,
+        // so a well-formed debug adapter should not attempt to view them
+        ExecutionErrors::Event { .. } => ExceptionInfoResponse {
+            exception_id: "Execution Event".into(),
+            description: None,
+            break_mode: ExceptionBreakMode::Never,
+            details: None
+        },
+        ExecutionErrors::MemoryObviousOverrunAccess { load_address } => ExceptionInfoResponse {
+            exception_id: "Buffer Overflow".into(),
+            description: Some("NAME detected a buffer overflow error. You may have attempted an acccess outside the bounds of a heap buffer.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Access location: {:x}", load_address)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::MemoryIllegalAccess { load_address } =>
+        ExceptionInfoResponse {
+            exception_id: "Illegal Access".into(),
+            description: Some("The program attempted to access memory that does not exist.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Access location: {:x}", load_address)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::MemoryUnalignedAccess { load_address, size, store } =>
+        ExceptionInfoResponse {
+            exception_id: "Unaligned Access".into(),
+            description: Some("The program attempted a halfword or word access at an address that is not a multiple of its size.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("{} of {} bytes at {:x}", if store { "Store" } else { "Load" }, size, load_address)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::UndefinedInstruction { instruction } =>
+        ExceptionInfoResponse {
+            exception_id: "Undefined Instruction".into(),
+            description: Some("The program attempted to execute a MIPS instruction that does not exist.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Instruction: {:x}", instruction)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::ArithmeticOverflow { address, lhs, rhs, op } =>
+        ExceptionInfoResponse {
+            exception_id: "Arithmetic Overflow".into(),
+            description: Some("A signed add or subtract overflowed. Use addu, addiu or subu if wraparound is intended.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("At 0x{:08x}: {} {} {}", address, lhs as i32, op, rhs as i32)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::Breakpoint { address } =>
+        ExceptionInfoResponse {
+            exception_id: "Breakpoint".into(),
+            description: Some("The program ran a break instruction, and has no exception handler to take it.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("At 0x{:08x}", address)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::Trap { address } =>
+        ExceptionInfoResponse {
+            exception_id: "Trap".into(),
+            description: Some("A conditional trap instruction such as teq found its condition true, and the program has no exception handler to take it.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("At 0x{:08x}", address)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::UnknownSyscall { service } =>
+        ExceptionInfoResponse {
+            exception_id: "Unknown Syscall".into(),
+            description: Some("The program requested a syscall service that NAME does not implement. Check the value in $v0.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Service: {}", service)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::SyscallInputError { service } =>
+        ExceptionInfoResponse {
+            exception_id: "Syscall Input Error".into(),
+            description: Some("The program asked for input that could not be read or was not in the expected format.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Service: {}", service)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::HeapExhausted { requested } =>
+        ExceptionInfoResponse {
+            exception_id: "Heap Exhausted".into(),
+            description: Some("The program asked sbrk for more memory than the heap can provide.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Requested bytes: {}", requested)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::HeapStackCollision { heap_break, stack_low } =>
+        ExceptionInfoResponse {
+            exception_id: "Heap Stack Collision".into(),
+            description: Some("The heap and the stack grew into each other. You may have recursed too deeply or allocated too much with sbrk.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Heap break: {:x}, lowest stack address: {:x}", heap_break, stack_low)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::IllegalJump { target } =>
+        ExceptionInfoResponse {
+            exception_id: "Illegal Jump".into(),
+            description: Some("A jr or jalr jumped to an address that does not hold code. Check the register it jumped through; an index into a jump table may be out of range.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Jump target: {:x}", target)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::IllegalFetch { address } =>
+        ExceptionInfoResponse {
+            exception_id: "Illegal Fetch".into(),
+            description: Some("Execution reached an address that is not a multiple of 4 or is outside memory, so no instruction could be read from it. Check the last jump, branch or eret.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Fetch address: {:x}", address)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+        ExecutionErrors::ProgramTooLarge { length } =>
+        ExceptionInfoResponse {
+            exception_id: "Program Too Large".into(),
+            description: Some("The assembled program does not fit in the memory set aside for its .text or .data section.".into()),
+            break_mode: ExceptionBreakMode::Always,
+            details: Some(ExceptionDetails {
+                message: Some( format!("Program length: {} bytes", length)
+            ),
+            type_name: None, full_type_name: None, evaluate_name: None, stack_trace: None, inner_exception: None })
+        },
+    }
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+use std::str::FromStr;
+
+use name_core::register::Register;
+
+use crate::exception::ExecutionErrors;
+use crate::hook::ExecutionHook;
+use crate::mips::Mips;
+
+// Fault injection, for assignments on detecting and correcting errors: a
+// fault flips one bit of a register or of a word in memory while the
+// program runs, as a stray particle might on real hardware. Each fault is
+// written TARGET:BIT@WHEN, where
+//
+//   TARGET  is a register ($t0, $8, $f2) or the 0x address of a word
+//   BIT     is the bit to flip, 0 to 31, or * for a random one each time
+//   WHEN    is the instruction to flip it before, counting from 1, or pP to
+//           flip it before each instruction with probability P
+//
+// so `$t0:3@100` flips bit 3 of $t0 just before the 100th instruction runs
+// and `0x10010000:*@p0.001` flips a random bit of that word once every
+// thousand instructions or so. Several faults are separated by commas.
+//
+// Random choices come from a seeded generator, so a run with the same
+// faults and seed always goes the same way. Faults are injected by a
+// built-in execution hook (see hook.rs), which reports each one it injects
+// in audit_warnings.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultTarget {
+    Register(Register),
+    Float(usize),
+    // A word of memory, flipped in the machine's byte order so that bit 0
+    // is always the least significant bit of the word
+    Memory(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultTrigger {
+    // Before the instruction with this number, counting from 1
+    Step(u64),
+    // Before each instruction, with this probability
+    Probability(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fault {
+    pub target: FaultTarget,
+    // None for a random bit
+    pub bit: Option<u8>,
+    pub trigger: FaultTrigger,
+}
+
+impl FromStr for Fault {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("expected TARGET:BIT@WHEN such as `$t0:3@100`, got `{}`", s);
+        let (target, rest) = s.split_once(':').ok_or_else(expected)?;
+        let (bit, when) = rest.split_once('@').ok_or_else(expected)?;
+
+        let target = if let Some(hex) = target.strip_prefix("0x") {
+            let address = u32::from_str_radix(hex, 16)
+                .map_err(|_| format!("`{}` is not an address", target))?;
+            if address % 4 != 0 {
+                return Err(format!("0x{:08x} is not word aligned", address));
+            }
+            FaultTarget::Memory(address)
+        } else if let Some(number) = target
+            .strip_prefix("$f")
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            if number >= 32 {
+                return Err(format!("`{}` is not a register", target));
+            }
+            FaultTarget::Float(number)
+        } else {
+            match target.parse::<Register>() {
+                Ok(Register::Zero) => {
+                    return Err("$zero is wired to 0 and cannot be faulted".to_string())
+                }
+                Ok(register) => FaultTarget::Register(register),
+                Err(_) => {
+                    return Err(format!(
+                        "`{}` is neither a register nor a 0x address",
+                        target
+                    ))
+                }
+            }
+        };
+
+        let bit = match bit {
+            "*" => None,
+            _ => match bit.parse::<u8>() {
+                Ok(bit) if bit < 32 => Some(bit),
+                _ => return Err(format!("bit `{}` is not * or 0 to 31", bit)),
+            },
+        };
+
+        let trigger = if let Some(probability) = when.strip_prefix('p') {
+            match probability.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => FaultTrigger::Probability(p),
+                _ => {
+                    return Err(format!(
+                        "probability `{}` is not between 0 and 1",
+                        probability
+                    ))
+                }
+            }
+        } else {
+            match when.parse::<u64>() {
+                Ok(step) if step > 0 => FaultTrigger::Step(step),
+                _ => {
+                    return Err(format!(
+                        "`{}` is neither an instruction number from 1 nor pP",
+                        when
+                    ))
+                }
+            }
+        };
+
+        Ok(Fault {
+            target,
+            bit,
+            trigger,
+        })
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.target {
+            FaultTarget::Register(register) => write!(f, "{}", register)?,
+            FaultTarget::Float(number) => write!(f, "$f{}", number)?,
+            FaultTarget::Memory(address) => write!(f, "0x{:08x}", address)?,
+        }
+        match self.bit {
+            Some(bit) => write!(f, ":{}", bit)?,
+            None => write!(f, ":*")?,
+        }
+        match self.trigger {
+            FaultTrigger::Step(step) => write!(f, "@{}", step),
+            FaultTrigger::Probability(p) => write!(f, "@p{}", p),
+        }
+    }
+}
+
+// Reads a comma-separated list of faults, as given to --fault
+pub fn parse_faults(s: &str) -> Result<Vec<Fault>, String> {
+    s.split(',')
+        .map(|fault| fault.trim().parse::<Fault>())
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    faults: Vec<Fault>,
+    random_state: u64,
+}
+
+impl FaultInjector {
+    pub fn new(faults: Vec<Fault>, seed: u64) -> FaultInjector {
+        // xorshift gets stuck at 0
+        FaultInjector {
+            faults,
+            random_state: seed.max(1),
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        self.random_state
+    }
+
+    // A number from 0 up to but not including 1
+    fn next_fraction(&mut self) -> f64 {
+        (self.next_random() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Flips `bit` of the fault's target, reporting what it did
+    fn inject(mips: &mut Mips, target: FaultTarget, bit: u8) {
+        let mask = 1u32 << bit;
+        let (name, before, after) = match target {
+            FaultTarget::Register(register) => {
+                let before = mips.reg(register);
+                // Bypasses set_reg so audit mode does not report the write
+                mips.regs[usize::from(register)] = before ^ mask;
+                (register.to_string(), before, before ^ mask)
+            }
+            FaultTarget::Float(number) => {
+                let before = mips.floats[number].to_bits();
+                mips.floats[number] = f32::from_bits(before ^ mask);
+                (format!("$f{}", number), before, before ^ mask)
+            }
+            FaultTarget::Memory(address) => {
+                let Ok(before) = mips.read_w(address) else {
+                    mips.audit_warnings.push(format!(
+                        "0x{:08x}: fault not injected, 0x{:08x} is not mapped",
+                        mips.pc(),
+                        address
+                    ));
+                    return;
+                };
+                // Written straight to memory so traces do not show the flip
+                // as a store by the program
+                let bytes = mips.endian.u32_to_bytes(before ^ mask);
+                mips.memory.set_bytes(address, &bytes);
+                (format!("0x{:08x}", address), before, before ^ mask)
+            }
+        };
+        mips.audit_warnings.push(format!(
+            "0x{:08x}: fault injected, bit {} of {} flipped from 0x{:08x} to 0x{:08x}",
+            mips.pc(),
+            bit,
+            name,
+            before,
+            after
+        ));
+    }
+}
+
+impl ExecutionHook for FaultInjector {
+    fn before_step(&mut self, mips: &mut Mips) -> Result<(), ExecutionErrors> {
+        let step = mips.stats.instructions + 1;
+        for index in 0..self.faults.len() {
+            let fault = self.faults[index];
+            let fires = match fault.trigger {
+                FaultTrigger::Step(n) => n == step,
+                FaultTrigger::Probability(p) => self.next_fraction() < p,
+            };
+            if !fires {
+                continue;
+            }
+            let bit = match fault.bit {
+                Some(bit) => bit,
+                None => (self.next_random() % 32) as u8,
+            };
+            Self::inject(mips, fault.target, bit);
+        }
+        Ok(())
+    }
+}
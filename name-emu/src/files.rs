@@ -0,0 +1,201 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use name_core::register::Register::{A0, A1, A2, V0};
+
+use crate::exception::ExecutionErrors;
+use crate::mips::Mips;
+
+// Where the files a program opens with the MARS file syscalls (13 to 16)
+// live. Like Console, syscalls only ever talk to this trait: by default a
+// program gets an empty in-memory sandbox, and frontends can hand it
+// prepared files or pass it through to a directory on the host.
+//
+// Files are read and written whole. An open file is kept in memory and
+// written back after every write, so a program that never closes its files
+// still leaves them behind, as it would in MARS.
+pub trait FileSystem: Debug {
+    // The contents of the file at `path`, or None if it cannot be read
+    fn read_file(&mut self, path: &str) -> Option<Vec<u8>>;
+    // Replaces the contents of the file at `path`, creating it if need
+    // be. Returns false if it cannot be written
+    fn write_file(&mut self, path: &str, contents: &[u8]) -> bool;
+}
+
+// Files kept in memory, for running programs without touching the host.
+// The files are shared so they can still be read once the file system has
+// been handed to the machine.
+#[derive(Debug, Default)]
+pub struct VirtualFileSystem {
+    pub files: Rc<RefCell<BTreeMap<String, Vec<u8>>>>,
+}
+
+impl VirtualFileSystem {
+    pub fn new(files: BTreeMap<String, Vec<u8>>) -> Self {
+        VirtualFileSystem {
+            files: Rc::new(RefCell::new(files)),
+        }
+    }
+}
+
+impl FileSystem for VirtualFileSystem {
+    fn read_file(&mut self, path: &str) -> Option<Vec<u8>> {
+        self.files.borrow().get(path).cloned()
+    }
+
+    fn write_file(&mut self, path: &str, contents: &[u8]) -> bool {
+        self.files
+            .borrow_mut()
+            .insert(path.to_string(), contents.to_vec());
+        true
+    }
+}
+
+// The flags syscall 13 takes in $a1, as in MARS
+const OPEN_READ: u32 = 0;
+const OPEN_WRITE: u32 = 1;
+const OPEN_APPEND: u32 = 9;
+
+// Descriptors 0 to 2 are the console, so files are numbered from 3
+const FIRST_FILE_DESCRIPTOR: u32 = 3;
+
+// MARS reports every failure as -1
+const FAILED: u32 = -1i32 as u32;
+
+#[derive(Debug, Clone)]
+pub(crate) struct OpenFile {
+    path: String,
+    contents: Vec<u8>,
+    // Where the next read starts. Writes always go on the end
+    position: usize,
+    writable: bool,
+}
+
+impl Mips {
+    // Syscall 13: opens the file named by the string at $a0 with the flags
+    // in $a1, returning a file descriptor in $v0
+    pub(crate) fn syscall_open(&mut self) -> Result<(), ExecutionErrors> {
+        let path = self.read_c_string(self.reg(A0))?;
+        let file = match self.reg(A1) {
+            OPEN_READ => self.files.read_file(&path).map(|contents| OpenFile {
+                path,
+                contents,
+                position: 0,
+                writable: false,
+            }),
+            OPEN_WRITE => self.files.write_file(&path, &[]).then_some(OpenFile {
+                path,
+                contents: vec![],
+                position: 0,
+                writable: true,
+            }),
+            OPEN_APPEND => {
+                let contents = self.files.read_file(&path).unwrap_or_default();
+                self.files.write_file(&path, &contents).then_some(OpenFile {
+                    path,
+                    contents,
+                    position: 0,
+                    writable: true,
+                })
+            }
+            _ => None,
+        };
+
+        let descriptor = match file {
+            Some(file) => {
+                let slot = match self.open_files.iter().position(Option::is_none) {
+                    Some(slot) => slot,
+                    None => {
+                        self.open_files.push(None);
+                        self.open_files.len() - 1
+                    }
+                };
+                self.open_files[slot] = Some(file);
+                slot as u32 + FIRST_FILE_DESCRIPTOR
+            }
+            None => FAILED,
+        };
+        self.set_reg(V0, descriptor);
+        Ok(())
+    }
+
+    // Syscall 14: reads at most $a2 bytes from descriptor $a0 into the
+    // buffer at $a1, returning how many were read in $v0, 0 at the end
+    // of the file
+    pub(crate) fn syscall_read(&mut self) -> Result<(), ExecutionErrors> {
+        let (descriptor, buffer, count) = (self.reg(A0), self.reg(A1), self.reg(A2) as usize);
+        let bytes: Vec<u8> = if descriptor == 0 {
+            // A line at most from the console, so a prompt can be answered
+            let mut line = String::new();
+            while line.len() < count && !line.ends_with('\n') {
+                match self.console.read_char() {
+                    Some(c) => line.push(c),
+                    None => break,
+                }
+            }
+            line.into_bytes()
+        } else {
+            match self.open_file(descriptor) {
+                Some(file) if !file.writable => {
+                    let end = (file.position + count).min(file.contents.len());
+                    let bytes = file.contents[file.position..end].to_vec();
+                    file.position = end;
+                    bytes
+                }
+                _ => {
+                    self.set_reg(V0, FAILED);
+                    return Ok(());
+                }
+            }
+        };
+
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write_b(buffer.wrapping_add(i as u32), *byte)?;
+        }
+        self.set_reg(V0, bytes.len() as u32);
+        Ok(())
+    }
+
+    // Syscall 15: writes $a2 bytes from the buffer at $a1 to descriptor
+    // $a0, returning how many were written in $v0
+    pub(crate) fn syscall_write(&mut self) -> Result<(), ExecutionErrors> {
+        let (descriptor, buffer, count) = (self.reg(A0), self.reg(A1), self.reg(A2));
+        let mut bytes = vec![];
+        for i in 0..count {
+            bytes.push(self.read_b(buffer.wrapping_add(i))?);
+        }
+
+        let written = if descriptor == 1 || descriptor == 2 {
+            self.print(&String::from_utf8_lossy(&bytes));
+            true
+        } else {
+            let slot = descriptor.checked_sub(FIRST_FILE_DESCRIPTOR);
+            match slot.and_then(|slot| self.open_files.get_mut(slot as usize)) {
+                Some(Some(file)) if file.writable => {
+                    file.contents.extend(&bytes);
+                    self.files.write_file(&file.path, &file.contents)
+                }
+                _ => false,
+            }
+        };
+        self.set_reg(V0, if written { count } else { FAILED });
+        Ok(())
+    }
+
+    // Syscall 16: closes descriptor $a0. Closing one that is not open
+    // does nothing
+    pub(crate) fn syscall_close(&mut self) -> Result<(), ExecutionErrors> {
+        if self.open_file(self.reg(A0)).is_some() {
+            let slot = (self.reg(A0) - FIRST_FILE_DESCRIPTOR) as usize;
+            self.open_files[slot] = None;
+        }
+        Ok(())
+    }
+
+    fn open_file(&mut self, descriptor: u32) -> Option<&mut OpenFile> {
+        let slot = descriptor.checked_sub(FIRST_FILE_DESCRIPTOR)? as usize;
+        self.open_files.get_mut(slot)?.as_mut()
+    }
+}
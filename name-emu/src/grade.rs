@@ -0,0 +1,823 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::watch::{Watch, WatchValue};
+use crate::{load_program, DynResult, Options};
+use name_core::symbols::Symbol;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::files::VirtualFileSystem;
+use name_emu::mips::Mips;
+use name_emu::syscall::{BufferConsole, ConsoleSettings};
+
+const USAGE: &str =
+    "USAGE: name grade [rubric file] [program (optional if the rubric names one) | --batch submissions directory [--anonymize key file]] [--json | --csv] [--endian big|little] [--entry label|address] [--linux] [--verify-load] [--delay-slots] [--newline lf|crlf] [--expand-tabs width] [--strip-read-newline] [--handler label|address] [--trap-syscalls]";
+
+const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+
+// How to grade a program, read from a TOML rubric file:
+//
+//   program = "sum.asm"        # optional, relative to the rubric, or in
+//                              # batch mode to each submission's folder
+//   input = "3\n4\n"           # what the program reads from the console
+//   expected_output = "7\n"    # compared ignoring trailing whitespace
+//   output_points = 6
+//   max_steps = 100000         # runaway programs are stopped here
+//   newline = "crlf"           # console settings, as --newline,
+//   expand_tabs = 8            # --expand-tabs and --strip-read-newline
+//   strip_read_newline = true  # which they take the place of
+//
+//   [[checkpoint]]
+//   at = "loop"                # a label or 0x address
+//   points = 2
+//   description = "Enters the summing loop"
+//
+//   [[watch]]
+//   watch = "counter"          # a data label, see watch.rs for the syntax
+//   expected = 10              # a number, a list of them, or a string
+//   points = 2
+//   description = "Counts every input"
+//
+//   [files]                    # files the program can open, which only
+//   "numbers.txt" = "3\n4\n"   # exist in memory while it runs
+//
+//   [[file]]
+//   path = "sum.txt"           # a file the program should write
+//   expected = "7\n"           # compared ignoring trailing whitespace
+//   points = 2
+//   description = "Saves the sum"
+//
+// A checkpoint earns its points when execution reaches it, whatever the
+// program goes on to print, so a submission that gets partway through the
+// assignment still gets partial credit. Watches and files are checked once
+// the program stops, even if it stopped with an error. Programs being graded
+// never see the host's files, whatever --files says.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rubric {
+    program: Option<String>,
+    #[serde(default)]
+    input: String,
+    expected_output: Option<String>,
+    #[serde(default)]
+    output_points: f64,
+    max_steps: Option<u64>,
+    newline: Option<String>,
+    expand_tabs: Option<usize>,
+    strip_read_newline: Option<bool>,
+    #[serde(default, rename = "checkpoint")]
+    checkpoints: Vec<Checkpoint>,
+    #[serde(default, rename = "watch")]
+    watches: Vec<WatchCheck>,
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+    #[serde(default, rename = "file")]
+    file_checks: Vec<FileCheck>,
+}
+
+impl Rubric {
+    // The console settings given on the command line, with any the rubric
+    // sets in their place, so every submission is graded the same way
+    fn console(&self, mut console: ConsoleSettings) -> DynResult<ConsoleSettings> {
+        if let Some(newline) = &self.newline {
+            console.newline = newline.parse().map_err(|why| format!("newline: {}", why))?;
+        }
+        match self.expand_tabs {
+            Some(0) => return Err("expand_tabs must be at least 1".into()),
+            Some(width) => console.tab_width = Some(width),
+            None => (),
+        }
+        if let Some(strip) = self.strip_read_newline {
+            console.strip_read_newline = strip;
+        }
+        Ok(console)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Checkpoint {
+    at: String,
+    points: f64,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WatchCheck {
+    watch: String,
+    expected: Expected,
+    points: f64,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileCheck {
+    path: String,
+    expected: String,
+    points: f64,
+    description: Option<String>,
+}
+
+// What a watch should read once the program has run
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Expected {
+    Number(i64),
+    Numbers(Vec<i64>),
+    Text(String),
+}
+
+impl Expected {
+    fn matches(&self, watch: &Watch, value: &WatchValue) -> bool {
+        match (self, value) {
+            (Expected::Number(expected), WatchValue::Numbers(actual)) => {
+                matches!(actual.as_slice(), [actual] if watch.number_matches(*expected, *actual))
+            }
+            (Expected::Numbers(expected), WatchValue::Numbers(actual)) => {
+                expected.len() == actual.len()
+                    && expected
+                        .iter()
+                        .zip(actual)
+                        .all(|(expected, actual)| watch.number_matches(*expected, *actual))
+            }
+            (Expected::Text(expected), WatchValue::Text(actual)) => expected == actual,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CheckpointResult {
+    at: String,
+    description: Option<String>,
+    // None if `at` is a label the program doesn't define
+    address: Option<u32>,
+    points: f64,
+    reached: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct WatchResult {
+    watch: String,
+    description: Option<String>,
+    expected: Expected,
+    // The value read, as the debugger would show it, or None if the watch
+    // could not be read
+    value: Option<String>,
+    points: f64,
+    matched: bool,
+    // Why the watch could not be read, such as a label the program doesn't define
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileResult {
+    path: String,
+    description: Option<String>,
+    // What the program left in the file, or None if it never wrote it
+    contents: Option<String>,
+    points: f64,
+    matched: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct GradeReport {
+    program: String,
+    score: f64,
+    max_score: f64,
+    // None if the rubric doesn't check output
+    output_matched: Option<bool>,
+    output: String,
+    checkpoints: Vec<CheckpointResult>,
+    watches: Vec<WatchResult>,
+    files: Vec<FileResult>,
+    // How the console was set up, which the expected output assumes
+    console: String,
+    steps: u64,
+    // Why the program didn't run to completion, if it didn't
+    error: Option<String>,
+}
+
+// Runs `program` against `rubric`. Problems with the program itself, such as
+// failing to assemble, are reported in the result rather than as an error so
+// that they cost the submission points instead of stopping the grader.
+fn grade(rubric: &Rubric, program: &str, options: &Options) -> GradeReport {
+    let max_steps = rubric.max_steps.unwrap_or(DEFAULT_MAX_STEPS);
+    let mut run = Run {
+        console: options.console,
+        ..Default::default()
+    };
+
+    run.error = match load_program(program, options) {
+        Err(e) => Some(format!("failed to load: {}", e)),
+        Ok((mut mips, symbols)) => {
+            run.labels = symbols
+                .iter()
+                .map(|symbol| (symbol.name.clone(), symbol.address))
+                .collect();
+            let console = BufferConsole::new(&rubric.input);
+            let console_output = console.output.clone();
+            mips.console = Box::new(console);
+            let files = VirtualFileSystem::new(
+                rubric
+                    .files
+                    .iter()
+                    .map(|(path, contents)| (path.clone(), contents.clone().into_bytes()))
+                    .collect(),
+            );
+            let written = files.files.clone();
+            mips.files = Box::new(files);
+
+            let error = loop {
+                if run.steps == max_steps {
+                    break Some(format!("stopped after {} steps", max_steps));
+                }
+                run.coverage.insert(mips.pc());
+                run.steps += 1;
+                match mips.step_one(&mut io::sink()) {
+                    Ok(()) => continue,
+                    Err(ExecutionErrors::Event {
+                        event: ExecutionEvents::ProgramComplete,
+                    }) => break None,
+                    Err(e) => {
+                        break Some(match e.exception_code() {
+                            Some(code) => {
+                                format!("exception at 0x{:08x} ({}): {}", mips.epc, code, e)
+                            }
+                            None => format!("error at 0x{:08x}: {}", mips.pc(), e),
+                        })
+                    }
+                }
+            };
+            run.output = console_output.borrow().clone();
+            run.watches = read_watches(rubric, &mips, &run.labels, &symbols);
+            run.files = written.borrow().clone();
+            error
+        }
+    };
+
+    score(rubric, program, run)
+}
+
+// What happened when a program ran, as far as grading is concerned
+#[derive(Debug, Default)]
+struct Run {
+    labels: BTreeMap<String, u32>,
+    // Every address an instruction was executed from
+    coverage: BTreeSet<u32>,
+    output: String,
+    // The watches of the rubric, in order, read after the program stopped
+    watches: Vec<Result<(Watch, WatchValue), String>>,
+    // Every file in the sandbox once the program stopped
+    files: BTreeMap<String, Vec<u8>>,
+    console: ConsoleSettings,
+    steps: u64,
+    error: Option<String>,
+}
+
+fn read_watches(
+    rubric: &Rubric,
+    mips: &Mips,
+    labels: &BTreeMap<String, u32>,
+    symbols: &[Symbol],
+) -> Vec<Result<(Watch, WatchValue), String>> {
+    rubric
+        .watches
+        .iter()
+        .map(|check| {
+            let watch = Watch::parse(&check.watch, labels, symbols)?;
+            let value = watch
+                .read(mips)
+                .map_err(|e| format!("could not read 0x{:08x}: {}", watch.address, e))?;
+            Ok((watch, value))
+        })
+        .collect()
+}
+
+fn score(rubric: &Rubric, program: &str, run: Run) -> GradeReport {
+    let checkpoints: Vec<CheckpointResult> = rubric
+        .checkpoints
+        .iter()
+        .map(|checkpoint| {
+            let address = checkpoint
+                .at
+                .strip_prefix("0x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| run.labels.get(&checkpoint.at).copied());
+            CheckpointResult {
+                at: checkpoint.at.clone(),
+                description: checkpoint.description.clone(),
+                address,
+                points: checkpoint.points,
+                reached: address.is_some_and(|address| run.coverage.contains(&address)),
+            }
+        })
+        .collect();
+
+    let watches: Vec<WatchResult> = rubric
+        .watches
+        .iter()
+        .enumerate()
+        .map(|(i, check)| {
+            let read = run
+                .watches
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| Err("the program did not run".to_string()));
+            let (value, matched, error) = match read {
+                Ok((watch, value)) => (
+                    Some(watch.format(&value)),
+                    check.expected.matches(&watch, &value),
+                    None,
+                ),
+                Err(why) => (None, false, Some(why)),
+            };
+            WatchResult {
+                watch: check.watch.clone(),
+                description: check.description.clone(),
+                expected: check.expected.clone(),
+                value,
+                points: check.points,
+                matched,
+                error,
+            }
+        })
+        .collect();
+
+    let files: Vec<FileResult> = rubric
+        .file_checks
+        .iter()
+        .map(|check| {
+            let contents = run
+                .files
+                .get(&check.path)
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+            FileResult {
+                path: check.path.clone(),
+                description: check.description.clone(),
+                matched: contents
+                    .as_ref()
+                    .is_some_and(|contents| contents.trim_end() == check.expected.trim_end()),
+                contents,
+                points: check.points,
+            }
+        })
+        .collect();
+
+    let output_matched = rubric
+        .expected_output
+        .as_ref()
+        .map(|expected| run.output.trim_end() == expected.trim_end());
+
+    let mut score = checkpoints
+        .iter()
+        .filter(|c| c.reached)
+        .fold(0.0, |sum, c| sum + c.points)
+        + watches
+            .iter()
+            .filter(|w| w.matched)
+            .fold(0.0, |sum, w| sum + w.points)
+        + files
+            .iter()
+            .filter(|f| f.matched)
+            .fold(0.0, |sum, f| sum + f.points);
+    let mut max_score = checkpoints.iter().fold(0.0, |sum, c| sum + c.points)
+        + watches.iter().fold(0.0, |sum, w| sum + w.points)
+        + files.iter().fold(0.0, |sum, f| sum + f.points);
+    if let Some(matched) = output_matched {
+        max_score += rubric.output_points;
+        if matched {
+            score += rubric.output_points;
+        }
+    }
+
+    GradeReport {
+        program: program.to_string(),
+        score,
+        max_score,
+        output_matched,
+        output: run.output,
+        checkpoints,
+        watches,
+        files,
+        console: run.console.to_string(),
+        steps: run.steps,
+        error: run.error,
+    }
+}
+
+fn print_report(report: &GradeReport) {
+    println!("{}: {}/{}", report.program, report.score, report.max_score);
+    for checkpoint in &report.checkpoints {
+        let earned = if checkpoint.reached {
+            checkpoint.points
+        } else {
+            0.0
+        };
+        let mut line = format!(
+            "  [{}] {}/{} {}",
+            if checkpoint.reached { 'x' } else { ' ' },
+            earned,
+            checkpoint.points,
+            checkpoint.at
+        );
+        if let Some(description) = &checkpoint.description {
+            line.push_str(&format!(": {}", description));
+        }
+        if checkpoint.address.is_none() {
+            line.push_str(" (label not found)");
+        }
+        println!("{}", line);
+    }
+    for watch in &report.watches {
+        let earned = if watch.matched { watch.points } else { 0.0 };
+        let mut line = format!(
+            "  [{}] {}/{} {}",
+            if watch.matched { 'x' } else { ' ' },
+            earned,
+            watch.points,
+            watch.watch
+        );
+        match (&watch.value, &watch.error) {
+            (Some(value), _) if watch.matched => line.push_str(&format!(" = {}", value)),
+            (Some(value), _) => {
+                let expected = serde_json::to_string(&watch.expected).unwrap_or_default();
+                line.push_str(&format!(" = {}, expected {}", value, expected));
+            }
+            (None, Some(error)) => line.push_str(&format!(" ({})", error)),
+            (None, None) => (),
+        }
+        if let Some(description) = &watch.description {
+            line.push_str(&format!(": {}", description));
+        }
+        println!("{}", line);
+    }
+    for file in &report.files {
+        let earned = if file.matched { file.points } else { 0.0 };
+        let mut line = format!(
+            "  [{}] {}/{} file {}",
+            if file.matched { 'x' } else { ' ' },
+            earned,
+            file.points,
+            file.path
+        );
+        match &file.contents {
+            None => line.push_str(" (not written)"),
+            Some(_) if !file.matched => line.push_str(" differs"),
+            Some(_) => (),
+        }
+        if let Some(description) = &file.description {
+            line.push_str(&format!(": {}", description));
+        }
+        println!("{}", line);
+    }
+    if let Some(matched) = report.output_matched {
+        println!(
+            "  [{}] output {}",
+            if matched { 'x' } else { ' ' },
+            if matched { "matches" } else { "differs" }
+        );
+    }
+    println!("  console: {}", report.console);
+    if let Some(error) = &report.error {
+        println!("  {}", error);
+    }
+}
+
+// Grades one submission on a fresh machine. A panic in the emulator is
+// caught and counted against the submission so the rest of a batch still
+// gets graded.
+fn grade_isolated(rubric: &Rubric, program: &str, options: &Options) -> GradeReport {
+    match panic::catch_unwind(AssertUnwindSafe(|| grade(rubric, program, options))) {
+        Ok(report) => report,
+        Err(_) => score(
+            rubric,
+            program,
+            Run {
+                error: Some("the emulator crashed while running this submission".to_string()),
+                console: options.console,
+                ..Default::default()
+            },
+        ),
+    }
+}
+
+// The extensions of files in a submissions directory that are taken to be
+// programs: assembly source, or an executable from the assembler or linker
+const PROGRAM_EXTENSIONS: &[&str] = &["asm", "s", "o", "elf"];
+
+// The program to grade for each entry of a submissions directory, in name
+// order, or why none could be found. An entry is either the program itself
+// or a folder holding it: the file the rubric names, or else its only .asm
+// or .s file. Other files, such as notes or the rubric itself when it is
+// kept alongside the submissions, are not submissions.
+fn submissions(
+    dir: &str,
+    rubric: &Rubric,
+    rubric_fn: &str,
+) -> DynResult<Vec<(String, Result<PathBuf, String>)>> {
+    let rubric_path = fs::canonicalize(rubric_fn).ok();
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|why| {
+            format!(
+                "Failed to open submissions directory {}. Reason: {}",
+                dir, why
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| !file_name(path).starts_with('.'))
+        .filter(|path| path.is_dir() || (path.is_file() && is_program(path)))
+        .filter(|path| rubric_path.is_none() || fs::canonicalize(path).ok() != rubric_path)
+        .collect();
+    entries.sort();
+
+    Ok(entries
+        .into_iter()
+        .map(|path| {
+            let name = file_name(&path);
+            if !path.is_dir() {
+                return (name, Ok(path));
+            }
+            let program = match &rubric.program {
+                Some(program) => Ok(path.join(program)),
+                None => {
+                    let sources: Vec<PathBuf> = fs::read_dir(&path)
+                        .map(|entries| {
+                            entries
+                                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                                .filter(|path| {
+                                    path.extension()
+                                        .is_some_and(|ext| ext == "asm" || ext == "s")
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    match sources.as_slice() {
+                        [source] => Ok(source.clone()),
+                        _ => Err(format!(
+                            "expected one .asm or .s file, found {}",
+                            sources.len()
+                        )),
+                    }
+                }
+            };
+            (name, program)
+        })
+        .collect())
+}
+
+fn is_program(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        PROGRAM_EXTENSIONS
+            .iter()
+            .any(|program| ext.eq_ignore_ascii_case(program))
+    })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// Grades every submission in `dir`. With `key_fn`, submissions are reported
+// under numbered ids instead of their names, with the names only written to
+// the key file so the instructor can match them up afterwards.
+fn grade_batch(
+    rubric: &Rubric,
+    rubric_fn: &str,
+    dir: &str,
+    key_fn: Option<&str>,
+    options: &Options,
+) -> DynResult<Vec<GradeReport>> {
+    let submissions = submissions(dir, rubric, rubric_fn)?;
+    let mut key = String::from("id,submission\n");
+    let mut reports = vec![];
+
+    for (index, (name, program)) in submissions.into_iter().enumerate() {
+        let mut report = match &program {
+            Ok(path) => grade_isolated(rubric, &path.to_string_lossy(), options),
+            Err(why) => {
+                let run = Run {
+                    error: Some(format!("no program found: {}", why)),
+                    console: options.console,
+                    ..Default::default()
+                };
+                score(rubric, &name, run)
+            }
+        };
+
+        if key_fn.is_some() {
+            let id = format!("submission-{:03}", index + 1);
+            key.push_str(&format!("{},{}\n", id, csv_field(&name)));
+            // Paths in diagnostics would give the name away
+            if let Ok(path) = &program {
+                let path = path.to_string_lossy();
+                report.error = report.error.map(|error| error.replace(path.as_ref(), &id));
+            }
+            report.program = id;
+        } else {
+            report.program = name;
+        }
+        reports.push(report);
+    }
+
+    if let Some(key_fn) = key_fn {
+        fs::write(key_fn, key)
+            .map_err(|why| format!("Failed to write key file {}. Reason: {}", key_fn, why))?;
+    }
+    Ok(reports)
+}
+
+// One row per report, with a score column for each checkpoint
+fn csv_report(rubric: &Rubric, reports: &[GradeReport]) -> String {
+    let mut header = vec![
+        "submission".to_string(),
+        "score".to_string(),
+        "max_score".to_string(),
+    ];
+    header.extend(
+        rubric
+            .checkpoints
+            .iter()
+            .map(|checkpoint| csv_field(&checkpoint.at)),
+    );
+    header.extend(rubric.watches.iter().map(|check| csv_field(&check.watch)));
+    header.extend(
+        rubric
+            .file_checks
+            .iter()
+            .map(|check| csv_field(&check.path)),
+    );
+    header.extend([
+        "output_matched".to_string(),
+        "steps".to_string(),
+        "error".to_string(),
+    ]);
+    let mut csv = header.join(",") + "\n";
+
+    for report in reports {
+        let mut row = vec![
+            csv_field(&report.program),
+            report.score.to_string(),
+            report.max_score.to_string(),
+        ];
+        row.extend(
+            report
+                .checkpoints
+                .iter()
+                .map(|c| if c.reached { c.points } else { 0.0 }.to_string()),
+        );
+        row.extend(
+            report
+                .watches
+                .iter()
+                .map(|w| if w.matched { w.points } else { 0.0 }.to_string()),
+        );
+        row.extend(
+            report
+                .files
+                .iter()
+                .map(|f| if f.matched { f.points } else { 0.0 }.to_string()),
+        );
+        row.push(
+            report
+                .output_matched
+                .map(|matched| matched.to_string())
+                .unwrap_or_default(),
+        );
+        row.push(report.steps.to_string());
+        row.push(csv_field(report.error.as_deref().unwrap_or("")));
+        csv.push_str(&(row.join(",") + "\n"));
+    }
+    csv
+}
+
+// Quotes a CSV field if it needs it
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// Removes a flag from the arguments, returning whether it was there
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+// Removes a flag and the value after it from the arguments
+fn take_value(args: &mut Vec<String>, flag: &str) -> DynResult<Option<String>> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("Expected a value after {}", flag).into()),
+        None => Ok(None),
+    }
+}
+
+// `name grade rubric.toml [program]`: runs a program against a rubric and
+// reports the score, as text, or as JSON or CSV.
+// `name grade rubric.toml --batch dir` does the same for every submission in
+// a directory, continuing past any that fail, and reports them together.
+pub fn grade_main(args: &[String], options: &Options) -> DynResult<()> {
+    let mut args = args.to_vec();
+    let json = take_flag(&mut args, "--json");
+    let csv = take_flag(&mut args, "--csv");
+    let batch = take_value(&mut args, "--batch")?;
+    let key_fn = take_value(&mut args, "--anonymize")?;
+    if json && csv {
+        return Err("Only one of --json and --csv can be given".into());
+    }
+    if key_fn.is_some() && batch.is_none() {
+        return Err("--anonymize only applies to --batch".into());
+    }
+
+    let (rubric_fn, program) = match (args.as_slice(), &batch) {
+        ([rubric_fn], _) => (rubric_fn, None),
+        ([rubric_fn, program], None) => (rubric_fn, Some(program.clone())),
+        _ => return Err(USAGE.into()),
+    };
+
+    let contents = std::fs::read_to_string(rubric_fn)
+        .map_err(|why| format!("Failed to open rubric {}. Reason: {}", rubric_fn, why))?;
+    let rubric: Rubric =
+        toml::from_str(&contents).map_err(|e| format!("Invalid rubric {}: {}", rubric_fn, e))?;
+    let options = &Options {
+        console: rubric
+            .console(options.console)
+            .map_err(|e| format!("Invalid rubric {}: {}", rubric_fn, e))?,
+        ..options.clone()
+    };
+
+    let reports = match &batch {
+        Some(dir) => grade_batch(&rubric, rubric_fn, dir, key_fn.as_deref(), options)?,
+        None => {
+            // A program named in the rubric is relative to the rubric
+            let program = match (program, &rubric.program) {
+                (Some(program), _) => program,
+                (None, Some(program)) => Path::new(rubric_fn)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(program)
+                    .to_string_lossy()
+                    .into_owned(),
+                (None, None) => {
+                    return Err("The rubric doesn't name a program, so one must be given".into())
+                }
+            };
+            vec![grade(&rubric, &program, options)]
+        }
+    };
+
+    if json {
+        match (batch.is_some(), reports.as_slice()) {
+            (false, [report]) => println!("{}", serde_json::to_string_pretty(report)?),
+            _ => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+    } else if csv {
+        print!("{}", csv_report(&rubric, &reports));
+    } else {
+        for report in &reports {
+            print_report(report);
+        }
+        if batch.is_some() {
+            let failed = reports
+                .iter()
+                .filter(|report| report.error.is_some())
+                .count();
+            let total = reports.iter().fold(0.0, |sum, report| sum + report.score);
+            let mean = if reports.is_empty() {
+                0.0
+            } else {
+                total / reports.len() as f64
+            };
+            let max_score = reports.first().map_or(0.0, |report| report.max_score);
+            println!();
+            println!(
+                "{} submissions, mean score {:.2}/{}, {} did not run to completion",
+                reports.len(),
+                mean,
+                max_score,
+                failed
+            );
+        }
+    }
+    Ok(())
+}
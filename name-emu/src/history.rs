@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use name_core::machine::MachineState;
+
+use crate::memory::Region;
+use crate::mips::{BranchDelays, Mips};
+
+// Reverse execution: a journal of what each step changed, so a debugger can
+// step back after running past a bug. Rather than snapshot all of memory
+// every step, each entry keeps the registers from before the step and the
+// bytes it stored over, which is cheap enough to take on every instruction.
+// The journal holds a bounded number of steps and forgets the oldest first.
+//
+// Undoing a step puts back the machine: registers, coprocessor 0, memory,
+// the heap and any branch waiting on its delay slot. What the program did
+// outside it stays done, so output already printed, input already read,
+// files and devices are not taken back, and the statistics and caches
+// still count the steps.
+
+#[derive(Debug)]
+struct Step {
+    state: MachineState,
+    // sbrk grows the heap region, so the regions are kept with the step
+    regions: Vec<Region>,
+    stack_low: u32,
+    branch: (BranchDelays, u32),
+    // Undoing the exit syscall lets the program run on
+    exit_code: Option<u32>,
+    // The bytes the step stored over, as (address, old value), in the
+    // order it stored them
+    overwritten: Vec<(u32, u8)>,
+}
+
+#[derive(Debug)]
+pub struct History {
+    capacity: usize,
+    steps: VecDeque<Step>,
+    // The step begun but not yet finished
+    pending: Option<Step>,
+}
+
+impl History {
+    // A history of up to `capacity` steps. With 0, nothing is recorded
+    pub fn new(capacity: usize) -> History {
+        History {
+            capacity,
+            steps: VecDeque::new(),
+            pending: None,
+        }
+    }
+
+    // How many steps can be undone
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Forgets every step, as when the machine is replaced
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.pending = None;
+    }
+
+    // Call before each step_one
+    pub fn begin(&mut self, mips: &mut Mips) {
+        if self.capacity == 0 {
+            return;
+        }
+        mips.record_writes = true;
+        mips.memory_writes.clear();
+        self.pending = Some(Step {
+            state: mips.register_state(),
+            regions: mips.memory.regions.clone(),
+            stack_low: mips.stack_low,
+            branch: (mips.branch_delay_status, mips.branch_delay_target),
+            exit_code: mips.exit_code,
+            overwritten: vec![],
+        });
+    }
+
+    // Call after each step_one, whatever it returned
+    pub fn finish(&mut self, mips: &mut Mips) {
+        let Some(mut step) = self.pending.take() else {
+            return;
+        };
+        mips.record_writes = false;
+        // The writes stay recorded until the next step for a debugger to
+        // check watchpoints against
+        step.overwritten = mips
+            .memory_writes
+            .iter()
+            .map(|write| (write.address, write.old))
+            .collect();
+        if self.steps.len() == self.capacity {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+    }
+
+    // Puts the machine back as it was before the last step recorded.
+    // Returns false if there is no step left to undo
+    pub fn undo(&mut self, mips: &mut Mips) -> bool {
+        let Some(step) = self.steps.pop_back() else {
+            return false;
+        };
+        for &(address, old) in step.overwritten.iter().rev() {
+            mips.memory.set(address, old);
+        }
+        mips.memory.regions = step.regions;
+        mips.restore(&step.state);
+        mips.stack_low = step.stack_low;
+        (mips.branch_delay_status, mips.branch_delay_target) = step.branch;
+        mips.exit_code = step.exit_code;
+        mips.prev_ins_result = Ok(());
+        true
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt::Debug;
+
+use crate::exception::ExecutionErrors;
+use crate::mips::Mips;
+
+// Execution hooks run before every instruction with the whole machine in
+// hand, for features that watch or tamper with a program from outside it,
+// such as fault injection (see fault.rs). A hook sees the machine with the
+// program counter at the instruction about to run, after
+// `stats.instructions` instructions have completed. Anything it wants the
+// user to know goes in `audit_warnings`; returning an error raises it as an
+// exception at the instruction.
+pub trait ExecutionHook: Debug {
+    fn before_step(&mut self, mips: &mut Mips) -> Result<(), ExecutionErrors>;
+}
+
+// The hooks installed on a machine, run in the order they were added
+pub type ExecutionHooks = Vec<Box<dyn ExecutionHook>>;
+
+impl Mips {
+    pub fn add_hook(&mut self, hook: Box<dyn ExecutionHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub(crate) fn run_hooks(&mut self) -> Result<(), ExecutionErrors> {
+        if self.hooks.is_empty() {
+            return Ok(());
+        }
+        // The hooks are taken out while they run so they can borrow the
+        // machine mutably, and any a hook adds are kept after them
+        let mut hooks = std::mem::take(&mut self.hooks);
+        let result = hooks.iter_mut().try_for_each(|hook| hook.before_step(self));
+        hooks.append(&mut self.hooks);
+        self.hooks = hooks;
+        result
+    }
+}
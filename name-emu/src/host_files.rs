@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use name_emu::files::FileSystem;
+
+// Passes the file syscalls through to a directory on the host, for
+// `--files DIR`. Paths are taken relative to the directory and may not
+// leave it, so a program can only touch the files it was given.
+#[derive(Debug)]
+pub struct HostFileSystem {
+    root: PathBuf,
+}
+
+impl HostFileSystem {
+    pub fn new(root: &str) -> Self {
+        HostFileSystem {
+            root: PathBuf::from(root),
+        }
+    }
+
+    // Where `path` is on the host, or None if it would be outside the root
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = Path::new(path);
+        path.components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+            .then(|| self.root.join(path))
+    }
+}
+
+impl FileSystem for HostFileSystem {
+    fn read_file(&mut self, path: &str) -> Option<Vec<u8>> {
+        fs::read(self.resolve(path)?).ok()
+    }
+
+    fn write_file(&mut self, path: &str, contents: &[u8]) -> bool {
+        self.resolve(path)
+            .is_some_and(|path| fs::write(path, contents).is_ok())
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+
+use crate::exception::ExecutionErrors;
+use crate::mips::{Mips, Rtype};
+
+// MIPS32 sets aside SPECIAL2 function codes 0x10 to 0x1f for user-defined
+// instructions (UDI). NAME treats them as hypercalls: instructions that are
+// carried out by Rust code on the host, for experimenting with new
+// instructions or I/O devices without touching the interpreter.
+pub use name_core::encoding::SPECIAL2_OPCODE;
+pub const HYPERCALL_FUNCTS: RangeInclusive<u8> = 0x10..=0x1f;
+
+// The host side of a hypercall. It gets the whole machine and the decoded
+// register fields of the instruction, which it is free to interpret as it
+// likes. Returning an error raises it as an exception at the instruction.
+pub trait Hypercall: Debug {
+    fn call(&mut self, mips: &mut Mips, ins: &Rtype) -> Result<(), ExecutionErrors>;
+}
+
+// The handlers installed on a machine, keyed by function code
+pub type Hypercalls = BTreeMap<u8, Box<dyn Hypercall>>;
+
+// The plugin hook: register hypercall handlers here and every machine the
+// emulator creates will have them, e.g.
+//
+//   mips.register_hypercall(0x10, Box::new(PrintHex));
+//
+// where PrintHex implements Hypercall. Function codes without a handler are
+// reserved instructions, as on hardware without UDI support.
+pub(crate) fn install_hypercalls(_mips: &mut Mips) {}
+
+impl Mips {
+    // Makes the user-defined instruction with function code `funct` run
+    // `handler`, replacing any handler it had before
+    pub fn register_hypercall(&mut self, funct: u8, handler: Box<dyn Hypercall>) {
+        assert!(
+            HYPERCALL_FUNCTS.contains(&funct),
+            "hypercall function code 0x{:x} is outside 0x10 to 0x1f",
+            funct
+        );
+        self.hypercalls.insert(funct, handler);
+    }
+
+    pub(crate) fn dispatch_hypercall(
+        &mut self,
+        ins: Rtype,
+        opcode: u32,
+    ) -> Result<(), ExecutionErrors> {
+        // The handler is taken out while it runs so it can borrow the
+        // machine mutably
+        let Some(mut handler) = self.hypercalls.remove(&ins.funct) else {
+            return Err(ExecutionErrors::UndefinedInstruction {
+                instruction: opcode,
+            });
+        };
+        let result = handler.call(self, &ins);
+        self.hypercalls.entry(ins.funct).or_insert(handler);
+        result
+    }
+}
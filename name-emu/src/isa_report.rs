@@ -0,0 +1,95 @@
+use std::io;
+
+use name_core::isa::{InstructionInfo, INSTRUCTIONS};
+
+use crate::DynResult;
+use name_emu::disasm::disassemble;
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::{Mips, DOT_TEXT_START_ADDRESS};
+
+const USAGE: &str = "USAGE: name isa-report";
+
+// What each part of NAME does with one instruction. Rather than being kept
+// by hand, every flag is found by handing the instruction's sample encoding
+// to the tool in question.
+struct Support {
+    assembles: bool,
+    disassembles: bool,
+    executes: bool,
+}
+
+impl Support {
+    fn complete(&self) -> bool {
+        self.assembles && self.disassembles && self.executes
+    }
+}
+
+fn check(info: &InstructionInfo) -> Support {
+    Support {
+        assembles: name_as::nma::is_instruction(info.mnemonic),
+        disassembles: disassembles(info),
+        executes: executes(info),
+    }
+}
+
+fn disassembles(info: &InstructionInfo) -> bool {
+    let text = disassemble(info.sample, DOT_TEXT_START_ADDRESS);
+    text.split_whitespace().next() == Some(info.mnemonic)
+}
+
+// Runs the sample on a fresh machine. Anything short of it being rejected
+// as undefined counts, since the sample's operands are arbitrary and may
+// well fault, e.g. by loading from an unmapped address.
+fn executes(info: &InstructionInfo) -> bool {
+    let mut mips: Mips = Default::default();
+    let text = mips.endian.u32_to_bytes(info.sample);
+    if mips.load_text(&text, DOT_TEXT_START_ADDRESS).is_err() {
+        return false;
+    }
+    !matches!(
+        mips.step_one(&mut io::sink()),
+        Err(ExecutionErrors::UndefinedInstruction { .. })
+    )
+}
+
+fn flag(supported: bool) -> &'static str {
+    if supported {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+// Lists every instruction in the shared ISA table with whether the
+// assembler, disassembler and emulator support it
+pub fn isa_report_main(args: &[String]) -> DynResult<()> {
+    if !args.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    println!("Mnemonic   Format  Level    Assembles  Disassembles  Executes");
+    let mut complete = 0;
+    for info in INSTRUCTIONS {
+        let support = check(info);
+        if support.complete() {
+            complete += 1;
+        }
+        println!(
+            "{:<10} {:<7} {:<8} {:<10} {:<13} {}",
+            info.mnemonic,
+            format!("{:?}", info.format),
+            info.level.to_string(),
+            flag(support.assembles),
+            flag(support.disassembles),
+            flag(support.executes),
+        );
+    }
+    println!();
+    println!(
+        "{} of {} instructions are fully supported",
+        complete,
+        INSTRUCTIONS.len()
+    );
+
+    Ok(())
+}
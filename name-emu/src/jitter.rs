@@ -0,0 +1,71 @@
+use crate::cache::Xorshift;
+use crate::exception::ExecutionErrors;
+use crate::hook::ExecutionHook;
+use crate::mips::Mips;
+use crate::trap::CAUSE_TIMER;
+
+// Timing jitter, for stress-testing interrupt handlers and code that makes
+// assumptions about when things happen. Before each instruction, with a
+// given probability, the machine either stalls for a few cycles, which
+// Count and the cycle estimate see, or raises a timer interrupt that Count
+// and Compare did not ask for. A handler that takes the timer bit of Cause
+// on trust rather than checking Count against Compare, or a loop that
+// expects to see every value of Count, then goes wrong where it would
+// otherwise have worked by luck.
+//
+// Interrupts are only injected once the program has an exception handler
+// to take them, and each one is reported in audit_warnings. Choices come
+// from a seeded generator (see cache.rs), so a run with the same
+// probability and seed always goes the same way.
+
+// The most cycles one stall lasts
+pub const MAX_STALL: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct Jitter {
+    probability: f64,
+    random: Xorshift,
+}
+
+impl Jitter {
+    pub fn new(probability: f64, seed: u32) -> Jitter {
+        Jitter {
+            probability,
+            random: Xorshift::new(seed),
+        }
+    }
+}
+
+// Reads the probability given to --jitter
+pub fn parse_probability(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("probability `{}` is not between 0 and 1", s)),
+    }
+}
+
+impl ExecutionHook for Jitter {
+    fn before_step(&mut self, mips: &mut Mips) -> Result<(), ExecutionErrors> {
+        if self.random.next_fraction() >= self.probability {
+            return Ok(());
+        }
+        let interrupt = self.random.next_u32().is_multiple_of(2);
+        if interrupt && mips.exception_handler.is_some() {
+            mips.cause |= CAUSE_TIMER;
+            mips.audit_warnings.push(format!(
+                "0x{:08x}: jitter raised a timer interrupt after {} instructions",
+                mips.pc(),
+                mips.stats.instructions
+            ));
+        } else {
+            // Each cycle counts as the timer sees it, so a stall can carry
+            // Count past Compare and raise a real timer interrupt
+            let cycles = 1 + self.random.next_u32() % MAX_STALL;
+            mips.stats.jitter_stalls += u64::from(cycles);
+            for _ in 0..cycles {
+                mips.tick_timer();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,497 @@
+use std::io::{self, BufRead, Write};
+
+use crate::DynResult;
+use name_as::nma::{assemble_source, AssemblerOptions};
+use name_core::register::Register;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::mips::Mips;
+use name_emu::syscall::BufferConsole;
+
+const USAGE: &str = "USAGE: name learn [lesson number | --list]";
+
+// Lesson programs are short; anything still running after this many steps
+// is stuck in a loop
+const MAX_STEPS: u64 = 10_000;
+
+// A lesson is a short explanation followed by exercises. Every exercise is
+// checked by the assembler and emulator themselves rather than against an
+// answer key, so any correct answer counts, not just the one the lesson
+// had in mind.
+struct Lesson {
+    title: &'static str,
+    text: &'static str,
+    exercises: &'static [Exercise],
+}
+
+enum Exercise {
+    // Write one instruction. Right if it assembles to the same word as
+    // `answer`
+    Assemble {
+        prompt: &'static str,
+        answer: &'static str,
+        hint: &'static str,
+    },
+    // Say what `register` holds once `program` has run
+    Predict {
+        prompt: &'static str,
+        program: &'static str,
+        register: &'static str,
+        hint: &'static str,
+    },
+    // Replace line `line` of `program`, counting from 1, so that it leaves
+    // `expected` in `register`
+    Fix {
+        prompt: &'static str,
+        program: &'static str,
+        line: usize,
+        register: &'static str,
+        expected: u32,
+        hint: &'static str,
+    },
+}
+
+impl Exercise {
+    fn hint(&self) -> &'static str {
+        match self {
+            Exercise::Assemble { hint, .. }
+            | Exercise::Predict { hint, .. }
+            | Exercise::Fix { hint, .. } => hint,
+        }
+    }
+}
+
+const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Registers and arithmetic",
+        text: "MIPS computes with 32 registers of 32 bits each. Arithmetic instructions name the
+register to write first, then the registers to read: `add $t0, $t1, $t2` puts
+$t1 + $t2 in $t0. With an `i` on the end, as in `addi`, the last operand is a
+number written into the instruction itself, an immediate.",
+        exercises: &[
+            Exercise::Assemble {
+                prompt: "Write the instruction that puts $t1 + $t2 in $t0.",
+                answer: "add $t0, $t1, $t2",
+                hint: "The register written comes first: add $t0, ...",
+            },
+            Exercise::Assemble {
+                prompt: "Write one instruction that puts $t1 + 10 in $t0.",
+                answer: "addi $t0, $t1, 10",
+                hint: "addi takes two registers and an immediate.",
+            },
+            Exercise::Predict {
+                prompt: "What is in $t2 once this has run?",
+                program: "li $t0, 7
+li $t1, 5
+sub $t2, $t1, $t0
+li $v0, 10
+syscall",
+                register: "$t2",
+                hint: "sub subtracts its last operand from the one before it. Negative answers are fine.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Shifts and multiplication",
+        text: "Shifting left by n places multiplies by 2 to the n, and is much cheaper than
+multiplying. `sll $t0, $t1, 2` puts $t1 * 4 in $t0. `srl` shifts right,
+bringing in zeros from the top. For other factors `mult` multiplies two
+registers, leaving the low 32 bits of the product in the LO register, where
+`mflo` can copy it from.",
+        exercises: &[
+            Exercise::Assemble {
+                prompt: "Write one instruction that puts $t1 * 8 in $t0 without multiplying.",
+                answer: "sll $t0, $t1, 3",
+                hint: "8 is 2 to the 3.",
+            },
+            Exercise::Predict {
+                prompt: "What is in $t1 once this has run?",
+                program: "li $t0, -1
+srl $t1, $t0, 28
+li $v0, 10
+syscall",
+                register: "$t1",
+                hint: "-1 is 32 one bits. Shifting right by 28 leaves the top 4 of them.",
+            },
+            Exercise::Predict {
+                prompt: "What is in $t2 once this has run?",
+                program: "li $t0, 6
+li $t1, 7
+mult $t0, $t1
+mflo $t2
+li $v0, 10
+syscall",
+                register: "$t2",
+                hint: "mflo copies the low half of the product.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Memory",
+        text: "Data lives in memory, declared under .data, and is brought into registers with
+loads and sent back with stores. `lw $t0, 8($t1)` loads the word at the
+address in $t1 plus 8. Words are 4 bytes, so the word after the one at
+address a is at a + 4, and a word's address must be a multiple of 4.",
+        exercises: &[
+            Exercise::Predict {
+                prompt: "What is in $t1 once this has run?",
+                program: ".data
+values: .word 10, 20, 30, 40
+.text
+la $t0, values
+lw $t1, 8($t0)
+li $v0, 10
+syscall",
+                register: "$t1",
+                hint: "8 bytes past the start of values is the third word.",
+            },
+            Exercise::Fix {
+                prompt: "This should load the second word of values into $t1, but stops with an error.",
+                program: ".data
+values: .word 10, 20, 30, 40
+.text
+la $t0, values
+lw $t1, 1($t0)
+li $v0, 10
+syscall",
+                line: 5,
+                register: "$t1",
+                expected: 20,
+                hint: "The offset counts bytes, not words.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Branches and loops",
+        text: "A branch compares two registers and goes to a label if the comparison holds:
+`beq $t0, $t1, done` goes to done when $t0 equals $t1, and `bne` when they
+differ. Otherwise the program carries on with the next instruction. A loop is
+a branch back to a label above it, taken until the loop is finished.",
+        exercises: &[
+            Exercise::Predict {
+                prompt: "What is in $t2 once this has run?",
+                program: "li $t0, 3
+li $t1, 3
+li $t2, 1
+beq $t0, $t1, skip
+li $t2, 2
+skip:
+li $v0, 10
+syscall",
+                register: "$t2",
+                hint: "$t0 and $t1 are equal, so beq jumps over the li after it.",
+            },
+            Exercise::Predict {
+                prompt: "What is in $t0 once this has run?",
+                program: "li $t0, 0
+li $t1, 4
+loop:
+add $t0, $t0, $t1
+addi $t1, $t1, -1
+bne $t1, $zero, loop
+li $v0, 10
+syscall",
+                register: "$t0",
+                hint: "The loop runs with $t1 at 4, 3, 2 and 1, and stops once it reaches 0.",
+            },
+            Exercise::Fix {
+                prompt: "This should count $s0 up to 5, but stops at 1.",
+                program: "li $s0, 0
+li $t0, 5
+loop:
+addi $s0, $s0, 1
+bne $s0, $t0, done
+done:
+li $v0, 10
+syscall",
+                line: 5,
+                register: "$s0",
+                expected: 5,
+                hint: "bne goes to its label while the registers differ. Where should it go while $s0 is short of 5?",
+            },
+        ],
+    },
+    Lesson {
+        title: "Procedures",
+        text: "`jal name` jumps to the procedure at the label name and leaves the address of
+the instruction after it in $ra, the return address. The procedure returns
+with `jr $ra`, which jumps to whatever address $ra holds. By convention the
+arguments go in $a0 to $a3 and the result comes back in $v0.",
+        exercises: &[
+            Exercise::Predict {
+                prompt: "What is in $s0 once this has run?",
+                program: "li $a0, 5
+jal triple
+add $s0, $v0, $zero
+li $a0, 2
+jal triple
+add $s0, $s0, $v0
+li $v0, 10
+syscall
+triple:
+sll $v0, $a0, 1
+add $v0, $v0, $a0
+jr $ra",
+                register: "$s0",
+                hint: "triple is called twice, and $s0 adds up what it returns.",
+            },
+            Exercise::Fix {
+                prompt: "This should leave 15 in $s0, but stops with an error when triple returns.",
+                program: "li $a0, 5
+j triple
+add $s0, $v0, $zero
+li $v0, 10
+syscall
+triple:
+sll $v0, $a0, 1
+add $v0, $v0, $a0
+jr $ra",
+                line: 2,
+                register: "$s0",
+                expected: 15,
+                hint: "What is in $ra when triple gets to jr $ra?",
+            },
+        ],
+    },
+];
+
+// Assembles and runs `source` to completion, or gives what went wrong
+fn run(source: &str) -> Result<Mips, String> {
+    let options = AssemblerOptions {
+        file_name: "exercise.asm".to_string(),
+        ..Default::default()
+    };
+    let assembled = assemble_source(source, &options).map_err(|e| e.to_string())?;
+
+    let mut mips: Mips = Default::default();
+    mips.console = Box::new(BufferConsole::new(""));
+    mips.load_text_at(assembled.text_address(), assembled.text(), assembled.entry)
+        .map_err(|e| e.to_string())?;
+    mips.load_data_at(assembled.data_address(), assembled.data())
+        .map_err(|e| e.to_string())?;
+
+    for _ in 0..MAX_STEPS {
+        match mips.step_one(&mut io::sink()) {
+            Ok(()) => continue,
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => return Ok(mips),
+            Err(e) => return Err(format!("the program stopped at 0x{:08x}: {}", mips.pc(), e)),
+        }
+    }
+    Err(format!(
+        "the program was still running after {} steps",
+        MAX_STEPS
+    ))
+}
+
+// The word a single instruction assembles to
+fn encode(instruction: &str) -> Result<u32, String> {
+    let options = AssemblerOptions {
+        file_name: "answer".to_string(),
+        ..Default::default()
+    };
+    let assembled = assemble_source(instruction, &options).map_err(|e| e.to_string())?;
+    match assembled.text() {
+        [a, b, c, d] => Ok(assembled.endian.u32_from_bytes([*a, *b, *c, *d])),
+        [] => Err("that is not an instruction".to_string()),
+        _ => Err(
+            "that is more than one instruction; pseudo-instructions don't count here".to_string(),
+        ),
+    }
+}
+
+fn register(mips: &Mips, name: &str) -> u32 {
+    let register: Register = name.parse().expect("lessons only name real registers");
+    mips.reg(register.number())
+}
+
+// A number as a student might write it: decimal, negative or 0x hex
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text
+            .parse::<i32>()
+            .map(|n| n as u32)
+            .or_else(|_| text.parse::<u32>())
+            .ok(),
+    }
+}
+
+fn print_program(program: &str) {
+    for (number, line) in program.lines().enumerate() {
+        println!("  {:>2} | {}", number + 1, line);
+    }
+}
+
+// Checks one answer. Ok if it is right, otherwise what to tell the student
+fn check(exercise: &Exercise, answer: &str) -> Result<(), String> {
+    match exercise {
+        Exercise::Assemble {
+            answer: expected, ..
+        } => {
+            let word = encode(answer)?;
+            let expected = encode(expected).expect("lesson answers assemble");
+            if word == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "that assembles, to 0x{:08x}, but does something else.",
+                    word
+                ))
+            }
+        }
+        Exercise::Predict {
+            program,
+            register: name,
+            ..
+        } => {
+            let Some(guess) = parse_number(answer) else {
+                return Err("answer with a number, such as 12, -3 or 0x1f.".to_string());
+            };
+            let mips = run(program).expect("lesson programs run");
+            if guess == register(&mips, name) {
+                Ok(())
+            } else {
+                Err(format!("{} holds something else.", name))
+            }
+        }
+        Exercise::Fix {
+            program,
+            line,
+            register: name,
+            expected,
+            ..
+        } => {
+            let fixed: Vec<&str> = program
+                .lines()
+                .enumerate()
+                .map(|(number, text)| if number + 1 == *line { answer } else { text })
+                .collect();
+            let mips = run(&fixed.join("\n"))?;
+            let value = register(&mips, name);
+            if value == *expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "it runs now, but leaves {} in {}.",
+                    value as i32, name
+                ))
+            }
+        }
+    }
+}
+
+enum Outcome {
+    Solved,
+    Skipped,
+    Quit,
+}
+
+// Asks for answers to one exercise until one is right or the student moves on
+fn attempt(exercise: &Exercise, input: &mut impl BufRead) -> io::Result<Outcome> {
+    match exercise {
+        Exercise::Assemble { prompt, .. } => println!("{}", prompt),
+        Exercise::Predict {
+            prompt, program, ..
+        } => {
+            println!("{}\n", prompt);
+            print_program(program);
+        }
+        Exercise::Fix {
+            prompt,
+            program,
+            line,
+            ..
+        } => {
+            println!("{}\n", prompt);
+            print_program(program);
+            println!("\nType a replacement for line {}.", line);
+        }
+    }
+
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(Outcome::Quit);
+        }
+        match answer.trim() {
+            "" => continue,
+            "hint" => println!("{}", exercise.hint()),
+            "skip" => return Ok(Outcome::Skipped),
+            "quit" => return Ok(Outcome::Quit),
+            answer => match check(exercise, answer) {
+                Ok(()) => {
+                    println!("Right!");
+                    return Ok(Outcome::Solved);
+                }
+                Err(why) => println!("Not quite: {}\nTry again, or type `hint` or `skip`.", why),
+            },
+        }
+    }
+}
+
+fn list() {
+    for (number, lesson) in LESSONS.iter().enumerate() {
+        println!(
+            "{:>2}. {} ({} exercises)",
+            number + 1,
+            lesson.title,
+            lesson.exercises.len()
+        );
+    }
+}
+
+// Walks through the built-in lessons from the first, or from the one given,
+// reading answers from stdin. Type `hint` for a hint, `skip` to move on and
+// `quit` to stop.
+pub fn learn_main(args: &[String]) -> DynResult<()> {
+    let first = match args {
+        [] => 1,
+        [flag] if flag == "--list" => {
+            list();
+            return Ok(());
+        }
+        [number] => match number.parse::<usize>() {
+            Ok(number) if (1..=LESSONS.len()).contains(&number) => number,
+            _ => return Err(format!("there are lessons 1 to {}\n{}", LESSONS.len(), USAGE).into()),
+        },
+        _ => return Err(USAGE.into()),
+    };
+
+    println!("Answer each exercise, or type `hint`, `skip` or `quit`.");
+    let mut input = io::stdin().lock();
+    let mut solved = 0;
+    let mut total = 0;
+    for (number, lesson) in LESSONS.iter().enumerate().skip(first - 1) {
+        println!(
+            "\n== Lesson {}: {} ==\n\n{}",
+            number + 1,
+            lesson.title,
+            lesson.text
+        );
+        for exercise in lesson.exercises {
+            println!();
+            total += 1;
+            match attempt(exercise, &mut input)? {
+                Outcome::Solved => solved += 1,
+                Outcome::Skipped => (),
+                Outcome::Quit => {
+                    println!(
+                        "\nSolved {} of {}. Pick up again with `name learn {}`.",
+                        solved,
+                        total - 1,
+                        number + 1
+                    );
+                    return Ok(());
+                }
+            }
+        }
+    }
+    println!(
+        "\nThat's every lesson: solved {} of {} exercises.",
+        solved, total
+    );
+    Ok(())
+}
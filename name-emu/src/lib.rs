@@ -0,0 +1,35 @@
+// The MIPS emulator core: the machine, its memory and the services it
+// provides to programs. Nothing here touches the filesystem or a terminal
+// directly, so it also builds for wasm32-unknown-unknown; the command-line
+// frontends and debug adapter live in the `name` binary.
+pub mod mips;
+
+pub mod memory;
+
+pub mod mmio;
+
+pub mod exception;
+
+pub mod syscall;
+
+pub mod files;
+
+pub mod linux;
+
+pub mod hypercall;
+
+pub mod hook;
+
+pub mod fault;
+
+pub mod jitter;
+
+pub mod disasm;
+
+pub mod trap;
+
+pub mod stats;
+
+pub mod cache;
+
+pub mod history;
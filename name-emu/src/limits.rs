@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use name_emu::mips::Mips;
+
+// `--max-steps` and `--timeout`: limits on how long `name run` and `name
+// debug` let a program go on, so a submission that loops forever is
+// stopped rather than left running. A program stopped by a limit has its
+// state dumped and the process exits with LIMIT_EXIT_CODE, which a grading
+// script can tell apart from the program's own exit codes and from errors.
+//
+// Steps are instructions run, so the step limit stops a program at the same
+// place every time. Only time spent running counts towards the timeout, not
+// time the debugger spends waiting at its prompt.
+
+// What `timeout` exits with when its command runs out of time
+pub const LIMIT_EXIT_CODE: i32 = 124;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+// Keeps track of a program's time against its limits
+#[derive(Debug)]
+pub struct Watchdog {
+    limits: Limits,
+    // Time spent running before the current stretch, and when that began
+    running: Duration,
+    since: Option<Instant>,
+}
+
+impl Watchdog {
+    pub fn new(limits: Limits) -> Watchdog {
+        Watchdog {
+            limits,
+            running: Duration::ZERO,
+            since: None,
+        }
+    }
+
+    // Starts counting time, as the program starts or carries on running
+    pub fn resume(&mut self) {
+        self.since.get_or_insert_with(Instant::now);
+    }
+
+    // Stops counting time, as the program stops to wait for the user
+    pub fn pause(&mut self) {
+        if let Some(since) = self.since.take() {
+            self.running += since.elapsed();
+        }
+    }
+
+    // Why the program may not run another instruction, if it has reached
+    // a limit
+    pub fn exceeded(&self, mips: &Mips) -> Option<String> {
+        if let Some(max_steps) = self.limits.max_steps {
+            if mips.stats.instructions >= max_steps {
+                return Some(format!("step limit of {} instructions reached", max_steps));
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            let elapsed = self.running + self.since.map_or(Duration::ZERO, |since| since.elapsed());
+            if elapsed >= timeout {
+                return Some(format!("time limit of {:?} reached", timeout));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,140 @@
+use name_core::register::Register::{Sp, A0, A1, A2, A3, V0};
+
+use crate::exception::ExecutionErrors;
+use crate::mips::{Mips, HEAP_START_ADDRESS};
+
+// Linux o32 numbers its syscalls from 4000
+const SYS_EXIT: u32 = 4001;
+const SYS_READ: u32 = 4003;
+const SYS_WRITE: u32 = 4004;
+const SYS_BRK: u32 = 4045;
+const SYS_EXIT_GROUP: u32 = 4246;
+
+// errno values, which differ between architectures
+const EBADF: u32 = 9;
+const EFAULT: u32 = 14;
+const ENOSYS: u32 = 89;
+
+// The words above the initial $sp that a Linux process starts with: argc,
+// an empty argv, an empty envp and an empty auxiliary vector (one key/value
+// pair). They are all zero, which memory already is.
+const PROCESS_STARTUP_WORDS: u32 = 5;
+
+impl Mips {
+    // Runs programs built for Linux, such as statically linked gcc output,
+    // instead of MARS-style programs. Only enough of the kernel is provided
+    // for simple programs: console I/O, exit and brk.
+    pub fn enable_linux_abi(&mut self) {
+        self.linux_abi = true;
+        // Leave room for the startup words, keeping $sp 8-byte aligned as the ABI requires
+        let top = self.reg(Sp) + 4;
+        self.set_reg(Sp, (top - PROCESS_STARTUP_WORDS * 4) & !7);
+    }
+
+    // Services a syscall instruction using the Linux o32 conventions: the
+    // number is in $v0, arguments in $a0-$a3 and the result in $v0, with $a3
+    // set to 1 and $v0 to the errno if the call failed.
+    pub(crate) fn linux_syscall(&mut self) -> Result<(), ExecutionErrors> {
+        let result = match self.reg(V0) {
+            SYS_EXIT | SYS_EXIT_GROUP => {
+                self.exit_code = Some(self.reg(A0));
+                Ok(0)
+            }
+            SYS_READ => self.linux_read(self.reg(A0), self.reg(A1), self.reg(A2)),
+            SYS_WRITE => self.linux_write(self.reg(A0), self.reg(A1), self.reg(A2)),
+            SYS_BRK => Ok(self.linux_brk(self.reg(A0))?),
+            number => {
+                if self.audit {
+                    let address = self.pc() - 4;
+                    self.audit_warnings.push(format!(
+                        "0x{:08x}: Linux syscall {} is not implemented, returning ENOSYS",
+                        address, number
+                    ));
+                }
+                Err(ENOSYS)
+            }
+        };
+
+        match result {
+            Ok(value) => {
+                self.set_reg(V0, value);
+                self.set_reg(A3, 0);
+            }
+            Err(errno) => {
+                self.set_reg(V0, errno);
+                self.set_reg(A3, 1);
+            }
+        }
+        Ok(())
+    }
+
+    // Standard output and standard error both go to the console
+    fn linux_write(&mut self, fd: u32, buffer: u32, count: u32) -> Result<u32, u32> {
+        if fd != 1 && fd != 2 {
+            return Err(EBADF);
+        }
+        let mut bytes = vec![];
+        for i in 0..count {
+            bytes.push(self.read_b(buffer + i).map_err(|_| EFAULT)?);
+        }
+        self.print(&String::from_utf8_lossy(&bytes));
+        Ok(count)
+    }
+
+    // Reads at most one line from standard input, like a terminal does.
+    // Returns 0 at end of input.
+    fn linux_read(&mut self, fd: u32, buffer: u32, count: u32) -> Result<u32, u32> {
+        if fd != 0 {
+            return Err(EBADF);
+        }
+        let mut length = 0;
+        while length < count {
+            // The console deals in characters, so the bytes of one that
+            // doesn't fit in what is left of the buffer wait for the next read
+            let byte = match self.linux_pending.pop_front() {
+                Some(byte) => byte,
+                None => {
+                    let Some(c) = self.console.read_char() else {
+                        break;
+                    };
+                    let mut encoded = [0; 4];
+                    let bytes = c.encode_utf8(&mut encoded).as_bytes();
+                    self.linux_pending.extend(&bytes[1..]);
+                    bytes[0]
+                }
+            };
+            if self.write_b(buffer + length, byte).is_err() {
+                self.linux_pending.push_front(byte);
+                return Err(EFAULT);
+            }
+            length += 1;
+            if byte == b'\n' {
+                break;
+            }
+        }
+        Ok(length)
+    }
+
+    // Moves the end of the heap to `address`, returning the new end. Like
+    // the kernel, an address of 0 or one that can't be satisfied leaves the
+    // heap as it is and returns the current end. Moving it past the stack
+    // is an error rather than a failed call, since the kernel would have
+    // kept a guard gap there.
+    fn linux_brk(&mut self, address: u32) -> Result<u32, ExecutionErrors> {
+        let stack_low = self.stack_low;
+        let Some(heap) = self.memory.region_mut(HEAP_START_ADDRESS) else {
+            return Ok(0);
+        };
+        let offset = address.wrapping_sub(heap.base);
+        if address != 0 && offset <= heap.max_length {
+            if address > stack_low {
+                return Err(ExecutionErrors::HeapStackCollision {
+                    heap_break: address,
+                    stack_low,
+                });
+            }
+            heap.length = offset;
+        }
+        Ok(heap.base + heap.length)
+    }
+}
@@ -1,525 +1,1511 @@
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
-
-use dap::events::{StoppedEventBody, ExitedEventBody, TerminatedEventBody};
-use dap::responses::{ReadMemoryResponse, SetExceptionBreakpointsResponse, ThreadsResponse, StackTraceResponse, ScopesResponse, VariablesResponse, ContinueResponse};
-use dap::types::{StoppedEventReason, Thread, StackFrame, Scope, Source, Variable};
+use std::io::{self, BufReader, BufWriter, Write};
+
+use dap::events::{ExitedEventBody, StoppedEventBody, TerminatedEventBody};
+use dap::responses::{
+    ContinueResponse, ReadMemoryResponse, ScopesResponse, SetExceptionBreakpointsResponse,
+    StackTraceResponse, ThreadsResponse, VariablesResponse,
+};
+use dap::types::{Scope, Source, StackFrame, StoppedEventReason, Thread, Variable};
 use thiserror::Error;
 
 use dap::prelude::*;
 
-mod mips;
-use mips::Mips;
+use name_emu::cache::{Cache, CacheConfig};
+use name_emu::disasm::disassemble;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::fault::{parse_faults, Fault, FaultInjector};
+use name_emu::jitter::{parse_probability, Jitter};
+use name_emu::mips::{self, Mips};
+use name_emu::syscall::ConsoleSettings;
+
+mod condition;
+
+mod exception_info;
+use exception_info::exception_pretty_print;
+
+mod debugger;
+
+mod doctor;
+
+mod grade;
+
+mod host_files;
+use host_files::HostFileSystem;
+
+mod isa_report;
+
+mod learn;
+
+mod limits;
+use limits::{Limits, Watchdog, LIMIT_EXIT_CODE};
+
+mod map;
+
+mod mars_check;
+mod mars_import;
+
+mod reference;
+
+mod verify;
+
+mod watch;
+
+mod trace;
+use trace::Tracer;
 
-mod exception;
-use exception::{ExecutionErrors, exception_pretty_print, ExecutionEvents};
+mod tui;
 
-use name_const::lineinfo::{LineInfo, lineinfo_import};
+mod until;
+use until::Until;
 
-use base64::{Engine as _, engine::general_purpose};
+use name_core::buildinfo::BuildInfo;
+use name_core::elf::{is_elf, read_elf};
+use name_core::endian::Endian;
+use name_core::lineinfo::{lineinfo_import, LineTable};
+use name_core::register::Register;
+use name_core::symbols::Symbol;
+
+use base64::{engine::general_purpose, Engine as _};
+use std::collections::BTreeMap;
 use std::env;
 use std::net::TcpListener;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 enum MyAdapterError {
-  #[error("Unhandled command")]
-  UnhandledCommandError,
+    // Unhandled requests are currently ignored rather than failed, see the end of main
+    #[allow(dead_code)]
+    #[error("Unhandled command")]
+    UnhandledCommandError,
+
+    #[error("Missing command")]
+    MissingCommandError,
 
-  #[error("Missing command")]
-  MissingCommandError,
+    #[error("Command argument error")]
+    CommandArgumentError,
 
-  #[error("Command argument error")]
-  CommandArgumentError,
-  
-  #[error("Argument parsing error")]
-  ArgumentParsingError
+    #[error("Argument parsing error")]
+    ArgumentParsingError,
 }
 
 type DynResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-fn reset_mips(program_data: &[u8]) -> Mips {
-  // Reset execution and begin again.
-  let mut mips: Mips = Default::default();  
+// Settings given as flags anywhere on the command line, shared by every mode
+#[derive(Debug, Clone, Default)]
+struct Options {
+    endian: Endian,
+    // Report writes to $zero, $k0 and $k1
+    audit: bool,
+    // Label or 0x address to start executing at instead of `main`
+    entry: Option<String>,
+    // Use Linux o32 syscalls instead of MARS ones
+    linux: bool,
+    // Check memory against the program after loading it
+    verify_load: bool,
+    // Run the instruction after each taken branch or jump before its target
+    delay_slots: bool,
+    // File to write an execution trace to, or `-` for stdout, and which
+    // instructions to include (see trace.rs)
+    trace: Option<String>,
+    trace_range: Option<String>,
+    trace_steps: Option<String>,
+    // Print instruction statistics when the program exits
+    stats: bool,
+    // Instruction and data caches to model (see cache.rs)
+    icache: Option<CacheConfig>,
+    dcache: Option<CacheConfig>,
+    // Bits to flip while the program runs, and the seed for random ones
+    // (see fault.rs)
+    faults: Vec<Fault>,
+    fault_seed: u64,
+    // How often to stall or raise a spurious timer interrupt between
+    // instructions, and the seed for when (see jitter.rs)
+    jitter: Option<f64>,
+    jitter_seed: u32,
+    // Directory the file syscalls open files in, instead of an empty
+    // in-memory sandbox (see files.rs)
+    files: Option<String>,
+    // How console output and input are translated (see syscall.rs)
+    console: ConsoleSettings,
+    // Label or 0x address of the exception handler, and the address it
+    // stands for once the program's labels are known (see with_handler)
+    handler: Option<String>,
+    handler_address: Option<u32>,
+    // Syscalls go to the exception handler (see trap.rs)
+    trap_syscalls: bool,
+    // When to stop a program that runs too long (see limits.rs)
+    limits: Limits,
+    // How many steps the debugger keeps to step back through, if not the
+    // default (see history.rs)
+    history: Option<usize>,
+    // Label or 0x address `name run` stops at, and the label of a function
+    // it stops once returned from (see until.rs)
+    until: Option<String>,
+    until_return_from: Option<String>,
+    // File `name run` writes the machine state to as a JSON snapshot when
+    // the program stops
+    dump: Option<String>,
+}
+
+// Sets up a fresh machine the way the options ask, apart from its byte order
+fn apply_options(mips: &mut Mips, options: &Options) {
+    mips.audit = options.audit;
+    mips.delay_slots = options.delay_slots;
+    mips.console_settings = options.console;
+    mips.exception_handler = options.handler_address;
+    mips.trap_syscalls = options.trap_syscalls;
+    mips.icache = options.icache.map(Cache::new);
+    mips.dcache = options.dcache.map(Cache::new);
+    if !options.faults.is_empty() {
+        mips.add_hook(Box::new(FaultInjector::new(
+            options.faults.clone(),
+            options.fault_seed,
+        )));
+    }
+    if let Some(probability) = options.jitter {
+        mips.add_hook(Box::new(Jitter::new(probability, options.jitter_seed)));
+    }
+    if let Some(dir) = &options.files {
+        mips.files = Box::new(HostFileSystem::new(dir));
+    }
+    if options.linux {
+        mips.enable_linux_abi();
+    }
+}
+
+// `program_data` is either raw instructions as written by name-as, or an ELF
+// executable from name-as --format elf or another toolchain
+fn reset_mips(program_data: &[u8], options: &Options, entry: u32) -> DynResult<Mips> {
+    // Reset execution and begin again.
+    let mut mips: Mips = Default::default();
+    mips.endian = options.endian;
+    apply_options(&mut mips, options);
+    let sections = if is_elf(program_data) {
+        let elf = read_elf(program_data)?;
+        mips.load_elf(&elf)?;
+        mips.set_pc(entry);
+        verify::elf_sections(&elf)
+    } else {
+        mips.load_text(program_data, entry)?;
+        vec![verify::Expected {
+            name: ".text".to_string(),
+            address: mips::DOT_TEXT_START_ADDRESS,
+            bytes: program_data.to_vec(),
+        }]
+    };
+    if options.verify_load {
+        verify::verify_load(&mips, &sections)?;
+    }
+
+    Ok(mips)
+}
 
-  for (i, byte) in program_data.iter().enumerate() {
-    mips.write_b(mips::DOT_TEXT_START_ADDRESS + i as u32, *byte).unwrap();
-  }
-  mips.stop_address = mips::DOT_TEXT_START_ADDRESS as usize + program_data.len();
+// Picks the entry point of an already assembled program the same way the
+// assembler does, using labels recovered from its source. ELF executables
+// start at their own entry point unless --entry says otherwise.
+fn entry_address(
+    program_data: &[u8],
+    options: &Options,
+    labels: &BTreeMap<String, u32>,
+) -> DynResult<u32> {
+    if is_elf(program_data) {
+        let elf = read_elf(program_data)?;
+        return match options.entry.as_deref() {
+            None => Ok(elf.entry),
+            Some(target) => target
+                .strip_prefix("0x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| labels.get(target).copied())
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown entry point `{}`, expected a label or 0x address",
+                        target
+                    )
+                    .into()
+                }),
+        };
+    }
 
-  mips
+    let text_end = mips::DOT_TEXT_START_ADDRESS + program_data.len() as u32;
+    Ok(name_as::nma::entry_point(
+        options.entry.as_deref(),
+        labels,
+        mips::DOT_TEXT_START_ADDRESS..text_end,
+    )
+    .map_err(|e| e.message())?)
 }
 
-fn main() -> DynResult<()> {
+// Removes `--endian <big|little>`, `--entry <label|address>`, `--audit`,
+// `--linux`, `--verify-load`, `--delay-slots`, `--trace <file|->`,
+// `--trace-range <start-end>`, `--trace-steps <first-last>`, `--stats`,
+// `--icache <config>`, `--dcache <config>`, `--fault <faults>`,
+// `--fault-seed <n>`, `--jitter <p>`, `--jitter-seed <n>`, `--files
+// <dir>`, `--newline <lf|crlf>`,
+// `--expand-tabs <width>`, `--strip-read-newline`, `--handler
+// <label|address>`, `--trap-syscalls`, `--max-steps <n>`, `--timeout
+// <seconds>`, `--history <n>`, `--until <label|address>`,
+// `--until-return-from <label|address>` and `--dump <file>` from the
+// arguments, wherever they appear
+fn take_options(args: &mut Vec<String>) -> DynResult<Options> {
+    let mut options = Options::default();
+
+    if let Some(index) = args.iter().position(|arg| arg == "--endian") {
+        if index + 1 >= args.len() {
+            return Err("Expected `big` or `little` after --endian".into());
+        }
+        options.endian = args[index + 1].parse::<Endian>()?;
+        args.drain(index..index + 2);
+    }
 
-  let args_strings: Vec<String> = env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--entry") {
+        if index + 1 >= args.len() {
+            return Err("Expected a label or address after --entry".into());
+        }
+        options.entry = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
 
-  if args_strings.len() != 5 {
-      return Err("USAGE: name-emu [port number] [source file] [object file] [line info file]".into());
-  }
-  let log_path = std::path::Path::join(env::temp_dir().as_path(), "name_log.txt");
-  let mut file = File::create(log_path)?;
-  file.write_all(b"NAME Development Log\n")?;
+    if let Some(index) = args.iter().position(|arg| arg == "--audit") {
+        options.audit = true;
+        args.remove(index);
+    }
 
+    if let Some(index) = args.iter().position(|arg| arg == "--linux") {
+        options.linux = true;
+        args.remove(index);
+    }
 
-  let port_string = args_strings.get(1).unwrap();
-  
-  let (in_port, out_port) = if let Ok(port_number) = port_string.parse::<u32>() {
+    if let Some(index) = args.iter().position(|arg| arg == "--verify-load") {
+        options.verify_load = true;
+        args.remove(index);
+    }
 
-      if let Ok(listener) = TcpListener::bind(format!("127.0.0.1:{}", port_number)) {
+    if let Some(index) = args.iter().position(|arg| arg == "--delay-slots") {
+        options.delay_slots = true;
+        args.remove(index);
+    }
 
-        let (stream, _) = listener.accept().unwrap();
-        (stream.try_clone().unwrap(), stream)
-      }
-      else {
-        println!("Failed to bind port {}", port_number);
-        return Err(Box::new(MyAdapterError::ArgumentParsingError));
-      }
-  }
-  else {
-    println!("Failed to parse port number");
-    return Err(Box::new(MyAdapterError::ArgumentParsingError));
-  };
-
-  let program_name = args_strings.get(2).unwrap();
-
-  let program_data = match std::fs::read(args_strings.get(3).unwrap()) {
-    Ok(program_data) => program_data,
-    Err(why) => {
-      println!("Failed to open provided object file. Reason: {}", why);
-      return Err(Box::new(MyAdapterError::ArgumentParsingError));      
-    }
-  };
-
-  let program_lineinfo = match std::fs::read_to_string(args_strings.get(4).unwrap()) {
-    Ok(program_lineinfo) => program_lineinfo,
-    Err(why) => {
-      println!("Failed to open provided line info file. Reason: {}", why);
-      return Err(Box::new(MyAdapterError::CommandArgumentError));      
-    }
-  };
-  let lineinfo = lineinfo_import(program_lineinfo)?;
-  writeln!(file, "Lineinfo read: {:?}", lineinfo)?;
-
-
-  let mut server = Server::new(BufReader::new(in_port), BufWriter::new(out_port));
-
-  let capabilities = types::Capabilities {
-    supports_configuration_done_request: Some(true),
-    supports_function_breakpoints: Some(true),
-    supports_conditional_breakpoints: Some(false),
-    supports_hit_conditional_breakpoints: Some(false),
-    supports_evaluate_for_hovers: Some(false),
-    exception_breakpoint_filters: None,
-    supports_step_back: Some(false),
-    supports_set_variable: Some(false),
-    supports_restart_frame: Some(false),
-    supports_goto_targets_request: Some(false),
-    supports_step_in_targets_request: Some(false),
-    supports_completions_request: Some(false),
-    completion_trigger_characters: None,
-    supports_modules_request: Some(false),
-    additional_module_columns: None,
-    supported_checksum_algorithms: None,
-    supports_restart_request: Some(true),
-    supports_exception_options: Some(false),
-    supports_value_formatting_options: Some(false),
-    supports_exception_info_request: Some(true),
-    support_terminate_debuggee: Some(false),
-    support_suspend_debuggee: Some(false),
-    supports_delayed_stack_trace_loading: Some(false),
-    supports_loaded_sources_request: Some(false),
-    supports_log_points: Some(false),
-    supports_terminate_threads_request: Some(false),
-    supports_set_expression: Some(false),
-    supports_terminate_request: Some(false),
-    supports_data_breakpoints: Some(false),
-    supports_read_memory_request: Some(false),
-    supports_write_memory_request: Some(false),
-    supports_disassemble_request: Some(false),
-    supports_cancel_request: Some(false),
-    supports_breakpoint_locations_request: Some(false),
-    supports_clipboard_context: Some(false),
-    supports_stepping_granularity: Some(false),
-    supports_instruction_breakpoints: Some(false),
-    supports_exception_filter_options: Some(false),
-    supports_single_thread_execution_requests: Some(false),
-  };
-
-  let mut mips: Mips = Default::default();
-
-loop {
-  let req = match server.poll_request()? {
-    Some(req) => req,
-    None => return Err(Box::new(MyAdapterError::MissingCommandError)),
-  };
-  writeln!(file, "Request {:?} received", req.command)?;
-  writeln!(file)?;
-  match req.command {
-    Command::Initialize(_) => {
-      let rsp = req.success(
-        ResponseBody::Initialize(capabilities.clone()),
-      );
-  
-      server.respond(rsp)?;
-  
-      server.send_event(Event::Initialized)?;
-
-      mips = reset_mips(&program_data);
-
-    }
-
-    // Launch does nothing in NAME, since all state was already set up by the time the protocol reached this point.
-    Command::Launch(_) => {
-
-      let rsp = req.success(
-        ResponseBody::Launch,
-      );
-      server.respond(rsp)?;
-
-      let stopped_event_body = StoppedEventBody {
-        reason: StoppedEventReason::Step,
-        description: None,
-        thread_id: Some(0),
-        preserve_focus_hint: None,
-        text: None,
-        all_threads_stopped: None,
-        hit_breakpoint_ids: None
-      };
-      server.send_event(Event::Stopped(stopped_event_body))?;
-    }
-
-    Command::WriteMemory(write_mem_args) => {
-      let bytes = general_purpose::STANDARD.decode(write_mem_args.data)?;
-      // let mut i = 0;
-      // for values in bytes.windows(4) {
-      //   let word: u32 = (values[0] as u32) << 24 & (values[1] as u32) << 16 & (values[2] as u32) << 8 & values[3] as u32;
-        
-      //   match mips.write_w(mips::DOT_TEXT + i, word) {
-      //     Ok(_) => (),
-      //     Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError))
-      //   }
-
-      //   i += 1;
-      // }
-
-      let address = match write_mem_args.memory_reference.parse::<u32>() {
-        Ok(i) => i,
-        Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError))
-      } + match write_mem_args.offset {
-        Some(value) => value as u32,
-        None => 0
-      };
-
-      for (i, byte) in bytes.iter().enumerate() {
-        match mips.write_b(address + i as u32, *byte) {
-          Ok(_) => (),
-          Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError))
-        }
-      }
-    }
-
-    Command::ReadMemory(ref read_mem_args) => {
-      let address = match read_mem_args.memory_reference.parse::<u32>() {
-        Ok(i) => i,
-        Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError))
-      } + match read_mem_args.offset {
-        Some(value) => value as u32,
-        None => 0
-      };
-
-      let mut out_bytes = vec![];
-      let mut response = ReadMemoryResponse {
-        address: read_mem_args.memory_reference.clone(),
-        unreadable_bytes: None,
-        data: None
-      };
-      
-      for i in 0..read_mem_args.count {
-        if let Ok(read_byte) = mips.read_b(address + i as u32) {
-          out_bytes.push(read_byte);
-        }
-        else {
-          response.unreadable_bytes = Some(read_mem_args.count - i);
-          break;
-        }
-      }
-      response.data = Some(general_purpose::STANDARD.encode(out_bytes));
-
-      let rsp = req.success(
-        ResponseBody::ReadMemory(response)
-      );
-  
-      server.respond(rsp)?;
-    }
-    
-    Command::Next(_) | Command::StepIn(_) => {
-      
-      let result = mips.step_one(&mut file);
-      let stopped_event_body = match result {
-        Ok(()) | Err(ExecutionErrors::Event { event: ExecutionEvents::ProgramComplete }) => {
-          StoppedEventBody {
-            reason: StoppedEventReason::Step,
-            description: None,
-            thread_id: Some(0),
-            preserve_focus_hint: None,
-            text: None,
-            all_threads_stopped: None,
-            hit_breakpoint_ids: None
-          }
-        }
-        Err(_) => {
-          StoppedEventBody {
-            reason: StoppedEventReason::Exception,
-            description: None,
-            thread_id: Some(0),
-            preserve_focus_hint: None,
-            text: None,
-            all_threads_stopped: None,
-            hit_breakpoint_ids: None
-          }
-        }
-      };
-
-      let rsp = req.success(
-        ResponseBody::Next
-      );
-      server.respond(rsp)?;
-
-      if let Err(ExecutionErrors::Event{event}) = result {
-        if event == ExecutionEvents::ProgramComplete {
-          server.send_event(Event::Terminated(None))?;
-          server.send_event(Event::Exited(ExitedEventBody{ exit_code: 0 }))?;
-        }
-      }
-      else {
-        writeln!(file, "{:?}", stopped_event_body)?;
-        writeln!(file, "{:?}", mips)?;
-        server.send_event(Event::Stopped(stopped_event_body))?;
-      }
-    }
-
-    Command::SetExceptionBreakpoints(_) => {
-      let rsp = req.success(
-        ResponseBody::SetExceptionBreakpoints(SetExceptionBreakpointsResponse{breakpoints: None})
-      );
-      server.respond(rsp)?;
-    }
-
-    Command::Threads => {
-      let rsp = req.success(
-        ResponseBody::Threads(ThreadsResponse{threads: vec![Thread{id: 0, name: "MIPS".to_string()}]})
-      );
-      server.respond(rsp)?;
-    }
-
-    Command::Disconnect(ref disconnect_args) => {
-
-      // If this is a restart sequence, don't die, and instead
-      // do nothing
-
-      let rst = disconnect_args.restart;
-      let restart = rst.map(serde_json::Value::Bool);
-
-      let terminated_event = TerminatedEventBody {
-        restart
-      };
-
-      server.send_event(Event::Terminated(Some(terminated_event)))?;
-
-      let rsp = req.success(
-        ResponseBody::Disconnect
-      );
-      server.respond(rsp)?;
-      
-      if let None | Some(false) = rst {
-        break;
-      }
-    }
-
-    Command::StackTrace(_) => {
-      let rsp = req.success(
-        ResponseBody::StackTrace(StackTraceResponse{stack_frames: vec![
-          StackFrame{
-            id: 0,
-            name: "mips".to_string(),
-            source: Some(Source { name: Some(program_name.to_string()), path: None, source_reference: Some(0), presentation_hint: None, origin: None, sources: None, adapter_data: None, checksums: None }),
-            line: lineinfo[&(mips.pc as u32)].line_number as i64,
-            column: 0,
-            end_line: None,
-            end_column: None,
-            can_restart: None,
-            instruction_pointer_reference: None,
-            module_id: None,
-            presentation_hint: None
-          }
-        ], total_frames: Some(3)})
-      );
-      server.respond(rsp)?;
-    }
-    
-    Command::Scopes(_) => {
-      let rsp = req.success(
-        ResponseBody::Scopes(ScopesResponse{
-          scopes: vec![
-            Scope {
-              name: "Registers".to_string(),
-              presentation_hint: Some(types::ScopePresentationhint::Registers),
-              // Notably, the magic 1001 is the only variables reference in this program. It can get the registers
-              // I'll probably want a second/third reference for floats etc.
-              variables_reference: 1001,
-              named_variables: None,
-              indexed_variables: None,
-              expensive: false,
-              source: None,
-              line: None,
-              column: None,
-              end_line: None,
-              end_column: None
-            }
-          ]
-        })
-      );
-      server.respond(rsp)?;
-    }
-
-    Command::Variables(ref variables_arguments) => {
-
-      // I'm sure I could collect() this somehow but it's 12:15AM
-      let mut registers = Vec::with_capacity(mips.regs.len());
-
-
-      if variables_arguments.variables_reference == 1001 {
-        for (i, reg) in mips.regs.iter().enumerate() {
-          registers.push(
-            Variable {
-              name: mips::REGISTER_NAMES[i].to_string(),
-              value: format!("0x{:X}", reg),
-              type_field: None,
-              presentation_hint: None,
-              evaluate_name: None, // But I'm sure this should be something
-              variables_reference: 0, // Apparently I should make this 0 for non-nested structs
-              named_variables: Some(0),
-              indexed_variables: Some(0),
-              memory_reference: None // I think this would be neat to implement...
+    if let Some(index) = args.iter().position(|arg| arg == "--stats") {
+        options.stats = true;
+        args.remove(index);
+    }
+
+    for (flag, cache) in [
+        ("--icache", &mut options.icache),
+        ("--dcache", &mut options.dcache),
+    ] {
+        if let Some(index) = args.iter().position(|arg| arg == flag) {
+            if index + 1 >= args.len() {
+                return Err(
+                    format!("Expected SIZE:BLOCK:WAYS[:lru|fifo|random] after {}", flag).into(),
+                );
             }
-          );
-        }
-        registers.push(
-          Variable {
-            name: mips::PC_NAME.to_string(),
-            value:format!("0x{:X}", mips.pc),
-            type_field: None,
-            presentation_hint: None,
-            evaluate_name: None, // But I'm sure this should be something
-            variables_reference: 0, // Apparently I should make this 0 for non-nested structs
-            named_variables: Some(0),
-            indexed_variables: Some(0),
-            memory_reference: None // I think this would be neat to implement...
-          }
+            *cache = Some(
+                args[index + 1]
+                    .parse::<CacheConfig>()
+                    .map_err(|why| format!("{}: {}", flag, why))?,
+            );
+            args.drain(index..index + 2);
+        }
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--trace") {
+        if index + 1 >= args.len() {
+            return Err("Expected a file name or `-` after --trace".into());
+        }
+        options.trace = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--trace-range") {
+        if index + 1 >= args.len() {
+            return Err(
+                "Expected a range of addresses such as `main-0x00400100` after --trace-range"
+                    .into(),
+            );
+        }
+        options.trace_range = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--trace-steps") {
+        if index + 1 >= args.len() {
+            return Err("Expected a range of steps such as `100-200` after --trace-steps".into());
+        }
+        options.trace_steps = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--fault") {
+        if index + 1 >= args.len() {
+            return Err("Expected faults such as `$t0:3@100` after --fault".into());
+        }
+        options.faults =
+            parse_faults(&args[index + 1]).map_err(|why| format!("--fault: {}", why))?;
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--files") {
+        if index + 1 >= args.len() {
+            return Err("Expected a directory after --files".into());
+        }
+        options.files = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--fault-seed") {
+        if index + 1 >= args.len() {
+            return Err("Expected a number after --fault-seed".into());
+        }
+        options.fault_seed = args[index + 1]
+            .parse::<u64>()
+            .map_err(|_| format!("--fault-seed: `{}` is not a number", args[index + 1]))?;
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--jitter") {
+        if index + 1 >= args.len() {
+            return Err("Expected a probability such as `0.01` after --jitter".into());
+        }
+        options.jitter =
+            Some(parse_probability(&args[index + 1]).map_err(|why| format!("--jitter: {}", why))?);
+        args.drain(index..index + 2);
+    }
+
+    let jitter_seed = match args.iter().position(|arg| arg == "--jitter-seed") {
+        Some(index) if index + 1 >= args.len() => {
+            return Err("Expected a number after --jitter-seed".into());
+        }
+        Some(index) => {
+            let seed = args[index + 1]
+                .parse::<u32>()
+                .map_err(|_| format!("--jitter-seed: `{}` is not a number", args[index + 1]))?;
+            args.drain(index..index + 2);
+            Some(seed)
+        }
+        None => None,
+    };
+    // Without a seed every run jitters differently, so the one picked is
+    // printed for a run that goes wrong to be repeated
+    if options.jitter.is_some() {
+        options.jitter_seed = jitter_seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |time| time.subsec_nanos())
+        });
+        eprintln!(
+            "Jitter seed {}; pass --jitter-seed {} to repeat this run",
+            options.jitter_seed, options.jitter_seed
         );
-      }
-
-      let rsp = req.success(
-        ResponseBody::Variables(VariablesResponse{variables: registers})
-      );
-      server.respond(rsp)?;
-    }
-
-    Command::Restart(_) => {
-      mips = reset_mips(&program_data);
-
-      let rsp = req.success(
-        ResponseBody::Restart
-      );
-      server.respond(rsp)?;
-
-      let stopped_event_body = StoppedEventBody {
-        reason: StoppedEventReason::Step,
-        description: None,
-        thread_id: Some(0),
-        preserve_focus_hint: None,
-        text: None,
-        all_threads_stopped: None,
-        hit_breakpoint_ids: None
-      };
-      server.send_event(Event::Stopped(stopped_event_body))?;
-    }
-
-    Command::ExceptionInfo(_) => {
-      let exception_info = exception_pretty_print(mips.prev_ins_result);
-
-      let rsp = req.success(
-        ResponseBody::ExceptionInfo(exception_info)
-      );
-
-      server.respond(rsp)?;
-    }
-
-    Command::Continue(_) => {
-      let rsp = req.success(
-        ResponseBody::Continue(ContinueResponse{ all_threads_continued: Some(true)})
-      );
-      server.respond(rsp)?;
-
-      // Keep stepping until something happens...
-      loop {
-        if let Err(_) = mips.step_one(&mut file) {
-          break;
-        }
-      }
-      // OK, what happened?
-      let stopped_event_body = match mips.prev_ins_result {
-        Ok(()) => unreachable!(), // It's unreachable.
-        Err(what_happened) => match what_happened {
-          ExecutionErrors::Event{event} => match event {
-            ExecutionEvents::ProgramComplete => {
-              StoppedEventBody {
-                reason: StoppedEventReason::Step,
-                description: None,
-                thread_id: Some(0),
-                preserve_focus_hint: None,
-                text: None,
-                all_threads_stopped: None,
-                hit_breakpoint_ids: None
-              }
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--newline") {
+        if index + 1 >= args.len() {
+            return Err("Expected `lf` or `crlf` after --newline".into());
+        }
+        options.console.newline = args[index + 1]
+            .parse()
+            .map_err(|why| format!("--newline: {}", why))?;
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--expand-tabs") {
+        if index + 1 >= args.len() {
+            return Err("Expected a number of columns after --expand-tabs".into());
+        }
+        match args[index + 1].parse::<usize>() {
+            Ok(width) if width > 0 => options.console.tab_width = Some(width),
+            _ => {
+                return Err(format!(
+                    "--expand-tabs: `{}` is not a number of columns",
+                    args[index + 1]
+                )
+                .into())
+            }
+        }
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--strip-read-newline") {
+        options.console.strip_read_newline = true;
+        args.remove(index);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--handler") {
+        if index + 1 >= args.len() {
+            return Err("Expected a label or address after --handler".into());
+        }
+        options.handler = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--trap-syscalls") {
+        options.trap_syscalls = true;
+        args.remove(index);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--max-steps") {
+        if index + 1 >= args.len() {
+            return Err("Expected a number of instructions after --max-steps".into());
+        }
+        match args[index + 1].parse::<u64>() {
+            Ok(steps) if steps > 0 => options.limits.max_steps = Some(steps),
+            _ => {
+                return Err(format!(
+                    "--max-steps: `{}` is not a number of instructions",
+                    args[index + 1]
+                )
+                .into())
+            }
+        }
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--timeout") {
+        if index + 1 >= args.len() {
+            return Err("Expected a number of seconds after --timeout".into());
+        }
+        match args[index + 1].parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                options.limits.timeout = Some(Duration::from_secs_f64(seconds))
             }
-          },
-          _ => { // Some kind of exception occurred...
-            StoppedEventBody {
-              reason: StoppedEventReason::Exception,
-              description: None,
-              thread_id: Some(0),
-              preserve_focus_hint: None,
-              text: None,
-              all_threads_stopped: None,
-              hit_breakpoint_ids: None
+            _ => {
+                return Err(format!(
+                    "--timeout: `{}` is not a number of seconds",
+                    args[index + 1]
+                )
+                .into())
+            }
+        }
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--history") {
+        if index + 1 >= args.len() {
+            return Err("Expected a number of steps after --history".into());
+        }
+        match args[index + 1].parse::<usize>() {
+            Ok(steps) => options.history = Some(steps),
+            _ => {
+                return Err(
+                    format!("--history: `{}` is not a number of steps", args[index + 1]).into(),
+                )
+            }
+        }
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--until") {
+        if index + 1 >= args.len() {
+            return Err("Expected a label or address after --until".into());
+        }
+        options.until = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--until-return-from") {
+        if index + 1 >= args.len() {
+            return Err("Expected a label or address after --until-return-from".into());
+        }
+        options.until_return_from = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--dump") {
+        if index + 1 >= args.len() {
+            return Err("Expected a file name after --dump".into());
+        }
+        options.dump = Some(args[index + 1].clone());
+        args.drain(index..index + 2);
+    }
+
+    Ok(options)
+}
+
+// Prints any register write warnings raised since the last call
+fn report_audit_warnings(mips: &mut Mips) {
+    for warning in mips.audit_warnings.drain(..) {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+// Loads a program to run without an editor attached. Assembly source is
+// assembled in memory and an ELF executable is loaded as is. The program's
+// symbols are returned alongside the machine.
+fn load_program(path: &str, options: &Options) -> DynResult<(Mips, Vec<Symbol>)> {
+    load_program_lines(path, options).map(|(mips, symbols, _)| (mips, symbols))
+}
+
+// load_program, along with the line table of a program assembled from
+// source. An ELF executable's is empty
+fn load_program_lines(path: &str, options: &Options) -> DynResult<(Mips, Vec<Symbol>, LineTable)> {
+    let contents = std::fs::read(path)
+        .map_err(|why| format!("Failed to open provided source file. Reason: {}", why))?;
+    if is_elf(&contents) {
+        let symbols = read_elf(&contents)?.symbols;
+        let labels = symbols
+            .iter()
+            .map(|symbol| (symbol.name.clone(), symbol.address))
+            .collect();
+        let entry = entry_address(&contents, options, &labels)?;
+        let options = with_handler(options, &labels)?;
+        return Ok((
+            reset_mips(&contents, &options, entry)?,
+            symbols,
+            LineTable::default(),
+        ));
+    }
+
+    let source = String::from_utf8(contents)
+        .map_err(|_| format!("{} is neither assembly source nor an ELF executable", path))?;
+    let assembler_options = name_as::nma::AssemblerOptions {
+        file_name: path.to_string(),
+        endian: options.endian,
+        entry: options.entry.clone(),
+        ..Default::default()
+    };
+    let assembled = name_as::nma::assemble_source(&source, &assembler_options)?;
+    let labels = assembled
+        .symbols
+        .iter()
+        .map(|symbol| (symbol.name.clone(), symbol.address))
+        .collect();
+    let options = &with_handler(options, &labels)?;
+
+    let mut mips: Mips = Default::default();
+    mips.endian = assembled.endian;
+    apply_options(&mut mips, options);
+    mips.load_text_at(assembled.text_address(), assembled.text(), assembled.entry)?;
+    mips.load_data_at(assembled.data_address(), assembled.data())?;
+    if options.verify_load {
+        let sections: Vec<verify::Expected> = assembled
+            .sections
+            .iter()
+            .map(|section| verify::Expected {
+                name: section.name.clone(),
+                address: section.address,
+                bytes: section.data.clone(),
+            })
+            .collect();
+        verify::verify_load(&mips, &sections)?;
+    }
+    Ok((mips, assembled.symbols, assembled.lineinfo))
+}
+
+// The options with the --handler label or address looked up
+fn with_handler(options: &Options, labels: &BTreeMap<String, u32>) -> DynResult<Options> {
+    let handler_address = match &options.handler {
+        None => None,
+        Some(target) => Some(
+            target
+                .strip_prefix("0x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| labels.get(target).copied())
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown exception handler `{}`, expected a label or 0x address",
+                        target
+                    )
+                })?,
+        ),
+    };
+    Ok(Options {
+        handler_address,
+        ..options.clone()
+    })
+}
+
+// Why a run stopped before the program exited
+enum RunEnd {
+    Error(ExecutionErrors),
+    Limit(String),
+    // Reached a point given with --until or --until-return-from
+    Until(String),
+}
+
+// `name run program.asm`: assemble in memory, execute to completion, then
+// report the final register state and exit with the program's exit code.
+// An ELF executable is run as is. A program stopped by --max-steps or
+// --timeout exits with LIMIT_EXIT_CODE instead, and one stopped by --until
+// or --until-return-from exits with 0.
+fn run_main(args: &[String], options: &Options) -> DynResult<()> {
+    let [source_fn] = args else {
+        return Err("USAGE: name run [source file or ELF executable] [--endian big|little] [--entry label|address] [--audit] [--linux] [--verify-load] [--delay-slots] [--trace file|-] [--trace-range start-end] [--trace-steps first-last] [--stats] [--icache size:block:ways[:policy]] [--dcache size:block:ways[:policy]] [--fault target:bit@when,...] [--fault-seed n] [--jitter probability] [--jitter-seed n] [--files directory] [--newline lf|crlf] [--expand-tabs width] [--strip-read-newline] [--handler label|address] [--trap-syscalls] [--max-steps n] [--timeout seconds] [--until label|address] [--until-return-from label|address] [--dump file]".into());
+    };
+
+    let (mut mips, symbols) = match load_program(source_fn, options) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if (options.trace_range.is_some() || options.trace_steps.is_some()) && options.trace.is_none() {
+        return Err("--trace-range and --trace-steps need --trace".into());
+    }
+    let mut tracer = match &options.trace {
+        Some(path) => Some(Tracer::create(
+            path,
+            options.trace_range.as_deref(),
+            options.trace_steps.as_deref(),
+            &symbols,
+        )?),
+        None => None,
+    };
+
+    let labels: BTreeMap<String, u32> = symbols
+        .iter()
+        .map(|symbol| (symbol.name.clone(), symbol.address))
+        .collect();
+    let mut untils = vec![];
+    if let Some(target) = &options.until {
+        untils.push(Until::address(target, &labels)?);
+    }
+    if let Some(target) = &options.until_return_from {
+        untils.push(Until::return_from(target, &labels)?);
+    }
+
+    let mut watchdog = Watchdog::new(options.limits);
+    watchdog.resume();
+    let result = loop {
+        if let Some(reason) = untils.iter_mut().find_map(|until| until.reached(&mips)) {
+            break Err(RunEnd::Until(reason));
+        }
+        if let Some(reason) = watchdog.exceeded(&mips) {
+            break Err(RunEnd::Limit(reason));
+        }
+        let pending = tracer.as_mut().and_then(|tracer| tracer.begin(&mut mips));
+        let step = mips.step_one(&mut io::sink());
+        if let Some(tracer) = &mut tracer {
+            tracer.finish(&mut mips, pending, &step)?;
+        }
+        report_audit_warnings(&mut mips);
+        match step {
+            Ok(()) => continue,
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => break Ok(()),
+            Err(e) => break Err(RunEnd::Error(e)),
+        }
+    };
+
+    if let Some(tracer) = &mut tracer {
+        tracer.flush()?;
+    }
+    if let Some(path) = &options.dump {
+        std::fs::write(path, mips.snapshot().to_json()?)
+            .map_err(|why| format!("Failed to write {}. Reason: {}", path, why))?;
+    }
+
+    eprintln!();
+    eprintln!("{}", mips.format_registers());
+    eprintln!("Console: {}", mips.console_settings);
+    if options.stats {
+        eprintln!("{}", mips.stats.format());
+    }
+    if mips.icache.is_some() || mips.dcache.is_some() {
+        eprintln!("{}", mips.format_caches());
+    }
+    match result {
+        Ok(()) => {
+            let exit_code = mips.exit_code.unwrap_or(0);
+            for until in &untils {
+                eprintln!("The program never stopped for {}", until.describe());
             }
-          }
-        }
-      };
-      server.send_event(Event::Stopped(stopped_event_body))?;
-
-      // Whole second match body to figure out what to do about it
-      match mips.prev_ins_result {
-        Ok(()) => unreachable!(), // It's unreachable.
-        Err(what_happened) => match what_happened {
-          ExecutionErrors::Event{event} => match event {
-            ExecutionEvents::ProgramComplete => {
-              server.send_event(Event::Terminated(None))?;
-              server.send_event(Event::Exited(ExitedEventBody{ exit_code: 0 }))?;
+            eprintln!("Program exited with code {}", exit_code);
+            std::process::exit(exit_code as i32);
+        }
+        Err(RunEnd::Error(e)) => {
+            match e.exception_code() {
+                Some(code) => eprintln!("Exception at 0x{:08x} ({}): {}", mips.epc, code, e),
+                None => eprintln!("Error at 0x{:08x}: {}", mips.pc(), e),
             }
-          },
-          _ => { // Some kind of exception occurred...
-            () // Don't need to do anything else for now
-          }
+            std::process::exit(1);
+        }
+        Err(RunEnd::Limit(reason)) => {
+            print_stop(&mips, &reason);
+            std::process::exit(LIMIT_EXIT_CODE);
+        }
+        Err(RunEnd::Until(reason)) => {
+            print_stop(&mips, &reason);
+            Ok(())
+        }
+    }
+}
+
+// Says where a program was stopped before it exited, and why
+fn print_stop(mips: &Mips, reason: &str) {
+    let pc = mips.pc();
+    match mips.read_w(pc) {
+        Ok(word) => eprintln!(
+            "Stopped at 0x{:08x} ({}): {}",
+            pc,
+            disassemble(word, pc),
+            reason
+        ),
+        Err(_) => eprintln!("Stopped at 0x{:08x}: {}", pc, reason),
+    }
+}
+
+// `name data program.asm [expr...]`: show data labels the way they were
+// declared, as the program is loaded and before it runs. Each expression
+// is a watch expression (see watch.rs); without any, every data label is shown.
+fn data_main(args: &[String], options: &Options) -> DynResult<()> {
+    let Some((source_fn, expressions)) = args.split_first() else {
+        return Err("USAGE: name data [source file or ELF executable] [label, label[index] or address ...] [--endian big|little]".into());
+    };
+
+    let (mips, symbols) = load_program(source_fn, options)?;
+    let labels: BTreeMap<String, u32> = symbols
+        .iter()
+        .map(|symbol| (symbol.name.clone(), symbol.address))
+        .collect();
+    let expressions: Vec<String> = if expressions.is_empty() {
+        watch::data_symbols(&symbols)
+            .into_iter()
+            .map(|symbol| symbol.name.clone())
+            .collect()
+    } else {
+        expressions.to_vec()
+    };
+
+    for expression in expressions {
+        let watch = watch::Watch::parse(&expression, &labels, &symbols)
+            .map_err(|why| format!("Could not print `{}`: {}", expression, why))?;
+        println!("{}", watch.pretty(&mips)?);
+    }
+    Ok(())
+}
+
+// `name link a.o b.o -o prog`: links objects from name-as --format object
+// into one ELF executable, starting at --entry or the exported `main`
+fn link_main(args: &[String], options: &Options) -> DynResult<()> {
+    let usage = "USAGE: name link [object file ...] -o [executable] [--entry label|address] [--reproducible]";
+    let mut output: Option<&String> = None;
+    let mut object_paths: Vec<&String> = vec![];
+    let mut reproducible = false;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-o" => output = Some(args_iter.next().ok_or(usage)?),
+            "--reproducible" => reproducible = true,
+            _ => object_paths.push(arg),
+        }
+    }
+    let Some(output) = output else {
+        return Err(usage.into());
+    };
+    if object_paths.is_empty() {
+        return Err(usage.into());
+    }
+
+    let mut objects = vec![];
+    let mut contents = vec![];
+    for path in object_paths {
+        let json = std::fs::read_to_string(path)
+            .map_err(|why| format!("Failed to open object file {}. Reason: {}", path, why))?;
+        let object = name_as::link::object_from_str(&json).map_err(|why| {
+            format!(
+                "{} is not an object from name-as --format object: {}",
+                path, why
+            )
+        })?;
+        objects.push((path.clone(), object));
+        contents.push(json);
+    }
+
+    let mut linked =
+        name_as::link::link(&objects, options.entry.as_deref()).map_err(|e| e.to_string())?;
+    let inputs: Vec<&[u8]> = contents.iter().map(|json| json.as_bytes()).collect();
+    let settings = format!("entry={}", options.entry.as_deref().unwrap_or("main"));
+    linked.build = Some(BuildInfo::new(
+        "name link",
+        env!("CARGO_PKG_VERSION"),
+        &settings,
+        &inputs,
+        reproducible,
+    ));
+    let source_file = linked.lineinfo.files.first().cloned().unwrap_or_default();
+    std::fs::write(output, name_as::nma::object_bytes(&linked, &source_file))
+        .map_err(|why| format!("Failed to write {}. Reason: {}", output, why))?;
+    Ok(())
+}
+
+// `name inspect file [source ...]`: shows which toolchain built an ELF
+// executable or object and from what. Each source given is checked against
+// the recorded inputs, failing if any of them was not one
+fn inspect_main(args: &[String]) -> DynResult<()> {
+    let Some((path, sources)) = args.split_first() else {
+        return Err("USAGE: name inspect [ELF executable or object file] [source file ...]".into());
+    };
+    let contents =
+        std::fs::read(path).map_err(|why| format!("Failed to open {}. Reason: {}", path, why))?;
+    let mut verified = false;
+    let build_info = if is_elf(&contents) {
+        let executable = read_elf(&contents)?;
+        verified = executable.verified;
+        executable.build_info
+    } else {
+        let json = String::from_utf8(contents)
+            .ok()
+            .filter(|json| json.trim_start().starts_with('{'))
+            .ok_or_else(|| {
+                format!(
+                    "{} is a raw binary, which has no room for build information",
+                    path
+                )
+            })?;
+        name_as::link::object_from_str(&json)
+            .map_err(|why| {
+                format!(
+                    "{} is neither an ELF executable nor an object: {}",
+                    path, why
+                )
+            })?
+            .build
+    };
+    let Some(build_info) = build_info else {
+        return Err(format!("{} has no build information", path).into());
+    };
+    println!("{}", build_info);
+    if verified {
+        println!("segments match their checksums");
+    }
+
+    let mut mismatched = false;
+    for source in sources {
+        let source_contents = std::fs::read(source)
+            .map_err(|why| format!("Failed to open {}. Reason: {}", source, why))?;
+        if build_info.built_from(&source_contents) {
+            println!("{}: matches", source);
+        } else {
+            println!("{}: does not match any input", source);
+            mismatched = true;
+        }
+    }
+    if mismatched {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// `name usage-report log ...`: adds up the usage logs name-as writes with
+// --usage-log and lists, for each assignment, how often each error and
+// warning came up, most common first
+fn usage_report_main(args: &[String]) -> DynResult<()> {
+    if args.is_empty() {
+        return Err("USAGE: name usage-report [usage log] ...".into());
+    }
+
+    let mut total = name_as::usage::UsageLog::default();
+    for log_fn in args {
+        let json = std::fs::read_to_string(log_fn)
+            .map_err(|why| format!("Failed to open {}. Reason: {}", log_fn, why))?;
+        let log = name_core::schema::from_json("usage", &json)
+            .map_err(|e| format!("Invalid usage log {}: {}", log_fn, e))?;
+        total.merge(log);
+    }
+
+    for (assignment, usage) in &total.assignments {
+        println!(
+            "{}: {} runs, {} without errors or warnings",
+            assignment, usage.runs, usage.clean
+        );
+        let mut diagnostics: Vec<(&String, &u64)> = usage.diagnostics.iter().collect();
+        diagnostics.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (code, count) in diagnostics {
+            println!("  {:>6}  {}", count, code);
         }
-      }
     }
+    Ok(())
+}
 
-    _ => ()
-    // _ => () //Err(Box::new(MyAdapterError::UnhandledCommandError))
-  };
+// Each subcommand, with what it does, for the usage message
+const SUBCOMMANDS: &[(&str, &str)] = &[
+    ("run", "assemble a source file and run it"),
+    (
+        "debug",
+        "step through a program in the command-line debugger",
+    ),
+    ("data", "show a program's data labels as they were declared"),
+    ("tui", "run a program in a full-screen terminal frontend"),
+    ("map", "draw a program's address space as it is loaded"),
+    (
+        "link",
+        "combine separately assembled objects into one executable",
+    ),
+    ("inspect", "show which toolchain built a file and from what"),
+    ("grade", "score a program against an instructor's rubric"),
+    (
+        "isa-report",
+        "list which instructions each part of NAME supports",
+    ),
+    (
+        "mars-check",
+        "compare how NAME and MARS expand pseudo-instructions",
+    ),
+    (
+        "verify",
+        "compare a program's encoding with another assembler's",
+    ),
+    (
+        "import-mars",
+        "translate a program written for MARS, and its settings",
+    ),
+    ("doctor", "check that NAME works on this machine"),
+    ("learn", "work through lessons on MIPS assembly"),
+    ("usage-report", "add up the usage logs of a class"),
+];
+
+// The usage message for when no subcommand was given, listing them all.
+// Without one, NAME serves the debug adapter protocol to an editor
+fn usage() -> String {
+    let mut usage = String::from("USAGE: name <command> [arguments]\n\nCommands:\n");
+    for (command, description) in SUBCOMMANDS {
+        usage.push_str(&format!("  {:<14}{}\n", command, description));
+    }
+    usage.push_str("\nRun `name <command>` to see the arguments a command takes. An editor starts\nthe debug adapter with `name [port number] [source file] [object file] [line info file]`.");
+    usage
 }
 
-  Ok(())
+fn main() -> DynResult<()> {
+    let mut args_strings: Vec<String> = env::args().collect();
+    let options = take_options(&mut args_strings)?;
+
+    // `name debug ...` runs the interactive command-line debugger instead of the debug adapter
+    if args_strings.get(1).map(String::as_str) == Some("debug") {
+        return debugger::debug_main(&args_strings[2..], options);
+    }
+
+    // `name run ...` assembles a source file and runs it without an editor attached
+    if args_strings.get(1).map(String::as_str) == Some("run") {
+        return run_main(&args_strings[2..], &options);
+    }
+
+    // `name data ...` shows a program's data labels as they were declared
+    if args_strings.get(1).map(String::as_str) == Some("data") {
+        return data_main(&args_strings[2..], &options);
+    }
+
+    // `name tui ...` runs a program in a full-screen terminal frontend
+    if args_strings.get(1).map(String::as_str) == Some("tui") {
+        return tui::tui_main(&args_strings[2..], &options);
+    }
+
+    // `name map ...` draws a program's address space as it is loaded
+    if args_strings.get(1).map(String::as_str) == Some("map") {
+        return map::map_main(&args_strings[2..], &options);
+    }
+
+    // `name link ...` combines separately assembled objects into one executable
+    if args_strings.get(1).map(String::as_str) == Some("link") {
+        return link_main(&args_strings[2..], &options);
+    }
+
+    // `name inspect ...` shows which toolchain built a file and from what
+    if args_strings.get(1).map(String::as_str) == Some("inspect") {
+        return inspect_main(&args_strings[2..]);
+    }
+
+    // `name grade ...` scores a program against an instructor's rubric
+    if args_strings.get(1).map(String::as_str) == Some("grade") {
+        return grade::grade_main(&args_strings[2..], &options);
+    }
+
+    // `name isa-report` lists which instructions are supported by each part of NAME
+    if args_strings.get(1).map(String::as_str) == Some("isa-report") {
+        return isa_report::isa_report_main(&args_strings[2..]);
+    }
+
+    // `name mars-check` compares how NAME and MARS expand pseudo-instructions
+    if args_strings.get(1).map(String::as_str) == Some("mars-check") {
+        return mars_check::mars_check_main(&args_strings[2..]);
+    }
+
+    // `name verify ...` compares what a program assembles to with another assembler's output
+    if args_strings.get(1).map(String::as_str) == Some("verify") {
+        return reference::verify_main(&args_strings[2..], &options);
+    }
+
+    // `name import-mars ...` translates a program written for MARS, and its settings
+    if args_strings.get(1).map(String::as_str) == Some("import-mars") {
+        return mars_import::import_mars_main(&args_strings[2..]);
+    }
+
+    // `name doctor` checks that NAME works on this machine
+    if args_strings.get(1).map(String::as_str) == Some("doctor") {
+        return doctor::doctor_main(&args_strings[2..]);
+    }
+
+    // `name learn` walks through lessons on MIPS assembly, checking each answer by running it
+    if args_strings.get(1).map(String::as_str) == Some("learn") {
+        return learn::learn_main(&args_strings[2..]);
+    }
+
+    // `name usage-report ...` adds up the usage logs of a class, for an instructor
+    if args_strings.get(1).map(String::as_str) == Some("usage-report") {
+        return usage_report_main(&args_strings[2..]);
+    }
+
+    if args_strings.len() != 5 {
+        // Printed as is, since the error's debug form would run it onto one line
+        eprintln!("{}", usage());
+        std::process::exit(2);
+    }
+    let log_path = std::path::Path::join(env::temp_dir().as_path(), "name_log.txt");
+    let mut file = File::create(log_path)?;
+    file.write_all(b"NAME Development Log\n")?;
+
+    let port_string = args_strings.get(1).unwrap();
+
+    let (in_port, out_port) = if let Ok(port_number) = port_string.parse::<u32>() {
+        if let Ok(listener) = TcpListener::bind(format!("127.0.0.1:{}", port_number)) {
+            let (stream, _) = listener.accept().unwrap();
+            (stream.try_clone().unwrap(), stream)
+        } else {
+            println!("Failed to bind port {}", port_number);
+            return Err(Box::new(MyAdapterError::ArgumentParsingError));
+        }
+    } else {
+        println!("Failed to parse port number");
+        return Err(Box::new(MyAdapterError::ArgumentParsingError));
+    };
+
+    let program_name = args_strings.get(2).unwrap();
+
+    let program_data = match std::fs::read(args_strings.get(3).unwrap()) {
+        Ok(program_data) => program_data,
+        Err(why) => {
+            println!("Failed to open provided object file. Reason: {}", why);
+            return Err(Box::new(MyAdapterError::ArgumentParsingError));
+        }
+    };
+
+    let program_lineinfo = match std::fs::read(args_strings.get(4).unwrap()) {
+        Ok(program_lineinfo) => program_lineinfo,
+        Err(why) => {
+            println!("Failed to open provided line info file. Reason: {}", why);
+            return Err(Box::new(MyAdapterError::CommandArgumentError));
+        }
+    };
+    let lineinfo = lineinfo_import(&program_lineinfo)?;
+    writeln!(file, "Lineinfo read: {:?}", lineinfo)?;
+
+    let mut labels = match std::fs::read_to_string(program_name) {
+        Ok(source) => debugger::find_labels(&source, &lineinfo),
+        Err(_) => BTreeMap::new(),
+    };
+    debugger::load_symbols(args_strings.get(3).unwrap(), &program_data, &mut labels)?;
+    let entry = entry_address(&program_data, &options, &labels)?;
+    let options = with_handler(&options, &labels)?;
+
+    let mut server = Server::new(BufReader::new(in_port), BufWriter::new(out_port));
+
+    let capabilities = types::Capabilities {
+        supports_configuration_done_request: Some(true),
+        supports_function_breakpoints: Some(true),
+        supports_conditional_breakpoints: Some(false),
+        supports_hit_conditional_breakpoints: Some(false),
+        supports_evaluate_for_hovers: Some(false),
+        exception_breakpoint_filters: None,
+        supports_step_back: Some(false),
+        supports_set_variable: Some(false),
+        supports_restart_frame: Some(false),
+        supports_goto_targets_request: Some(false),
+        supports_step_in_targets_request: Some(false),
+        supports_completions_request: Some(false),
+        completion_trigger_characters: None,
+        supports_modules_request: Some(false),
+        additional_module_columns: None,
+        supported_checksum_algorithms: None,
+        supports_restart_request: Some(true),
+        supports_exception_options: Some(false),
+        supports_value_formatting_options: Some(false),
+        supports_exception_info_request: Some(true),
+        support_terminate_debuggee: Some(false),
+        support_suspend_debuggee: Some(false),
+        supports_delayed_stack_trace_loading: Some(false),
+        supports_loaded_sources_request: Some(false),
+        supports_log_points: Some(false),
+        supports_terminate_threads_request: Some(false),
+        supports_set_expression: Some(false),
+        supports_terminate_request: Some(false),
+        supports_data_breakpoints: Some(false),
+        supports_read_memory_request: Some(false),
+        supports_write_memory_request: Some(false),
+        supports_disassemble_request: Some(false),
+        supports_cancel_request: Some(false),
+        supports_breakpoint_locations_request: Some(false),
+        supports_clipboard_context: Some(false),
+        supports_stepping_granularity: Some(false),
+        supports_instruction_breakpoints: Some(false),
+        supports_exception_filter_options: Some(false),
+        supports_single_thread_execution_requests: Some(false),
+    };
+
+    let mut mips: Mips = Default::default();
+
+    loop {
+        let req = match server.poll_request()? {
+            Some(req) => req,
+            None => return Err(Box::new(MyAdapterError::MissingCommandError)),
+        };
+        writeln!(file, "Request {:?} received", req.command)?;
+        writeln!(file)?;
+        match req.command {
+            Command::Initialize(_) => {
+                let rsp = req.success(ResponseBody::Initialize(capabilities.clone()));
+
+                server.respond(rsp)?;
+
+                server.send_event(Event::Initialized)?;
+
+                mips = reset_mips(&program_data, &options, entry)?;
+            }
+
+            // Launch does nothing in NAME, since all state was already set up by the time the protocol reached this point.
+            Command::Launch(_) => {
+                let rsp = req.success(ResponseBody::Launch);
+                server.respond(rsp)?;
+
+                let stopped_event_body = StoppedEventBody {
+                    reason: StoppedEventReason::Step,
+                    description: None,
+                    thread_id: Some(0),
+                    preserve_focus_hint: None,
+                    text: None,
+                    all_threads_stopped: None,
+                    hit_breakpoint_ids: None,
+                };
+                server.send_event(Event::Stopped(stopped_event_body))?;
+            }
+
+            Command::WriteMemory(write_mem_args) => {
+                let bytes = general_purpose::STANDARD.decode(write_mem_args.data)?;
+                // let mut i = 0;
+                // for values in bytes.windows(4) {
+                //   let word: u32 = (values[0] as u32) << 24 & (values[1] as u32) << 16 & (values[2] as u32) << 8 & values[3] as u32;
+
+                //   match mips.write_w(mips::DOT_TEXT + i, word) {
+                //     Ok(_) => (),
+                //     Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError))
+                //   }
+
+                //   i += 1;
+                // }
+
+                let address = match write_mem_args.memory_reference.parse::<u32>() {
+                    Ok(i) => i,
+                    Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError)),
+                } + match write_mem_args.offset {
+                    Some(value) => value as u32,
+                    None => 0,
+                };
+
+                for (i, byte) in bytes.iter().enumerate() {
+                    match mips.write_b(address + i as u32, *byte) {
+                        Ok(_) => (),
+                        Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError)),
+                    }
+                }
+            }
+
+            Command::ReadMemory(ref read_mem_args) => {
+                let address = match read_mem_args.memory_reference.parse::<u32>() {
+                    Ok(i) => i,
+                    Err(_) => return Err(Box::new(MyAdapterError::CommandArgumentError)),
+                } + match read_mem_args.offset {
+                    Some(value) => value as u32,
+                    None => 0,
+                };
+
+                let mut out_bytes = vec![];
+                let mut response = ReadMemoryResponse {
+                    address: read_mem_args.memory_reference.clone(),
+                    unreadable_bytes: None,
+                    data: None,
+                };
+
+                for i in 0..read_mem_args.count {
+                    if let Ok(read_byte) = mips.read_b(address + i as u32) {
+                        out_bytes.push(read_byte);
+                    } else {
+                        response.unreadable_bytes = Some(read_mem_args.count - i);
+                        break;
+                    }
+                }
+                response.data = Some(general_purpose::STANDARD.encode(out_bytes));
+
+                let rsp = req.success(ResponseBody::ReadMemory(response));
+
+                server.respond(rsp)?;
+            }
+
+            Command::Next(_) | Command::StepIn(_) => {
+                let result = mips.step_one(&mut file);
+                let stopped_event_body = match result {
+                    Ok(())
+                    | Err(ExecutionErrors::Event {
+                        event: ExecutionEvents::ProgramComplete,
+                    }) => StoppedEventBody {
+                        reason: StoppedEventReason::Step,
+                        description: None,
+                        thread_id: Some(0),
+                        preserve_focus_hint: None,
+                        text: None,
+                        all_threads_stopped: None,
+                        hit_breakpoint_ids: None,
+                    },
+                    Err(_) => StoppedEventBody {
+                        reason: StoppedEventReason::Exception,
+                        description: None,
+                        thread_id: Some(0),
+                        preserve_focus_hint: None,
+                        text: None,
+                        all_threads_stopped: None,
+                        hit_breakpoint_ids: None,
+                    },
+                };
+
+                let rsp = req.success(ResponseBody::Next);
+                server.respond(rsp)?;
+
+                if let Err(ExecutionErrors::Event { event }) = result {
+                    if event == ExecutionEvents::ProgramComplete {
+                        server.send_event(Event::Terminated(None))?;
+                        server.send_event(Event::Exited(ExitedEventBody {
+                            exit_code: mips.exit_code.unwrap_or(0) as i64,
+                        }))?;
+                    }
+                } else {
+                    writeln!(file, "{:?}", stopped_event_body)?;
+                    writeln!(file, "{:?}", mips)?;
+                    server.send_event(Event::Stopped(stopped_event_body))?;
+                }
+            }
+
+            Command::SetExceptionBreakpoints(_) => {
+                let rsp = req.success(ResponseBody::SetExceptionBreakpoints(
+                    SetExceptionBreakpointsResponse { breakpoints: None },
+                ));
+                server.respond(rsp)?;
+            }
+
+            Command::Threads => {
+                let rsp = req.success(ResponseBody::Threads(ThreadsResponse {
+                    threads: vec![Thread {
+                        id: 0,
+                        name: "MIPS".to_string(),
+                    }],
+                }));
+                server.respond(rsp)?;
+            }
+
+            Command::Disconnect(ref disconnect_args) => {
+                // If this is a restart sequence, don't die, and instead
+                // do nothing
+
+                let rst = disconnect_args.restart;
+                let restart = rst.map(serde_json::Value::Bool);
+
+                let terminated_event = TerminatedEventBody { restart };
+
+                server.send_event(Event::Terminated(Some(terminated_event)))?;
+
+                let rsp = req.success(ResponseBody::Disconnect);
+                server.respond(rsp)?;
+
+                if let None | Some(false) = rst {
+                    break;
+                }
+            }
+
+            Command::StackTrace(_) => {
+                let location = lineinfo.at(mips.pc());
+                let span = location.map(|li| li.span).unwrap_or_default();
+                let source_name = location
+                    .and_then(|li| lineinfo.file(li))
+                    .unwrap_or(program_name);
+                let rsp = req.success(ResponseBody::StackTrace(StackTraceResponse {
+                    stack_frames: vec![StackFrame {
+                        id: 0,
+                        name: "mips".to_string(),
+                        source: Some(Source {
+                            name: Some(source_name.to_string()),
+                            path: None,
+                            source_reference: Some(0),
+                            presentation_hint: None,
+                            origin: None,
+                            sources: None,
+                            adapter_data: None,
+                            checksums: None,
+                        }),
+                        line: span.line as i64,
+                        column: span.column as i64,
+                        end_line: Some(span.end_line as i64),
+                        end_column: Some(span.end_column as i64),
+                        can_restart: None,
+                        instruction_pointer_reference: None,
+                        module_id: None,
+                        presentation_hint: None,
+                    }],
+                    total_frames: Some(3),
+                }));
+                server.respond(rsp)?;
+            }
+
+            Command::Scopes(_) => {
+                let rsp = req.success(ResponseBody::Scopes(ScopesResponse {
+                    scopes: vec![Scope {
+                        name: "Registers".to_string(),
+                        presentation_hint: Some(types::ScopePresentationhint::Registers),
+                        // Notably, the magic 1001 is the only variables reference in this program. It can get the registers
+                        // I'll probably want a second/third reference for floats etc.
+                        variables_reference: 1001,
+                        named_variables: None,
+                        indexed_variables: None,
+                        expensive: false,
+                        source: None,
+                        line: None,
+                        column: None,
+                        end_line: None,
+                        end_column: None,
+                    }],
+                }));
+                server.respond(rsp)?;
+            }
+
+            Command::Variables(ref variables_arguments) => {
+                // I'm sure I could collect() this somehow but it's 12:15AM
+                let mut registers = Vec::with_capacity(mips.regs.len());
+
+                if variables_arguments.variables_reference == 1001 {
+                    for (i, reg) in mips.regs.iter().enumerate() {
+                        registers.push(Variable {
+                            name: Register::ALL[i].to_string(),
+                            value: format!("0x{:X}", reg),
+                            type_field: None,
+                            presentation_hint: None,
+                            evaluate_name: None, // But I'm sure this should be something
+                            variables_reference: 0, // Apparently I should make this 0 for non-nested structs
+                            named_variables: Some(0),
+                            indexed_variables: Some(0),
+                            memory_reference: None, // I think this would be neat to implement...
+                        });
+                    }
+                    registers.push(Variable {
+                        name: mips::PC_NAME.to_string(),
+                        value: format!("0x{:X}", mips.pc),
+                        type_field: None,
+                        presentation_hint: None,
+                        evaluate_name: None, // But I'm sure this should be something
+                        variables_reference: 0, // Apparently I should make this 0 for non-nested structs
+                        named_variables: Some(0),
+                        indexed_variables: Some(0),
+                        memory_reference: None, // I think this would be neat to implement...
+                    });
+                }
+
+                let rsp = req.success(ResponseBody::Variables(VariablesResponse {
+                    variables: registers,
+                }));
+                server.respond(rsp)?;
+            }
+
+            Command::Restart(_) => {
+                mips = reset_mips(&program_data, &options, entry)?;
+
+                let rsp = req.success(ResponseBody::Restart);
+                server.respond(rsp)?;
+
+                let stopped_event_body = StoppedEventBody {
+                    reason: StoppedEventReason::Step,
+                    description: None,
+                    thread_id: Some(0),
+                    preserve_focus_hint: None,
+                    text: None,
+                    all_threads_stopped: None,
+                    hit_breakpoint_ids: None,
+                };
+                server.send_event(Event::Stopped(stopped_event_body))?;
+            }
+
+            Command::ExceptionInfo(_) => {
+                let exception_info = exception_pretty_print(mips.prev_ins_result);
+
+                let rsp = req.success(ResponseBody::ExceptionInfo(exception_info));
+
+                server.respond(rsp)?;
+            }
+
+            Command::Continue(_) => {
+                let rsp = req.success(ResponseBody::Continue(ContinueResponse {
+                    all_threads_continued: Some(true),
+                }));
+                server.respond(rsp)?;
+
+                // Keep stepping until something happens...
+                loop {
+                    if mips.step_one(&mut file).is_err() {
+                        break;
+                    }
+                }
+                // OK, what happened?
+                let stopped_event_body = match mips.prev_ins_result {
+                    Ok(()) => unreachable!(), // It's unreachable.
+                    Err(what_happened) => match what_happened {
+                        ExecutionErrors::Event { event } => match event {
+                            ExecutionEvents::ProgramComplete => StoppedEventBody {
+                                reason: StoppedEventReason::Step,
+                                description: None,
+                                thread_id: Some(0),
+                                preserve_focus_hint: None,
+                                text: None,
+                                all_threads_stopped: None,
+                                hit_breakpoint_ids: None,
+                            },
+                        },
+                        _ => {
+                            // Some kind of exception occurred...
+                            StoppedEventBody {
+                                reason: StoppedEventReason::Exception,
+                                description: None,
+                                thread_id: Some(0),
+                                preserve_focus_hint: None,
+                                text: None,
+                                all_threads_stopped: None,
+                                hit_breakpoint_ids: None,
+                            }
+                        }
+                    },
+                };
+                server.send_event(Event::Stopped(stopped_event_body))?;
+
+                // Whole second match body to figure out what to do about it
+                match mips.prev_ins_result {
+                    Ok(()) => unreachable!(), // It's unreachable.
+                    Err(what_happened) => match what_happened {
+                        ExecutionErrors::Event { event } => match event {
+                            ExecutionEvents::ProgramComplete => {
+                                server.send_event(Event::Terminated(None))?;
+                                server.send_event(Event::Exited(ExitedEventBody {
+                                    exit_code: mips.exit_code.unwrap_or(0) as i64,
+                                }))?;
+                            }
+                        },
+                        _ => { // Some kind of exception occurred...
+                             // Don't need to do anything else for now
+                        }
+                    },
+                }
+            }
+
+            _ => (), // _ => () //Err(Box::new(MyAdapterError::UnhandledCommandError))
+        };
+    }
+
+    Ok(())
 }
@@ -0,0 +1,290 @@
+use name_core::memmap;
+use name_core::register::Register;
+use name_core::symbols::Symbol;
+use name_emu::mips::Mips;
+
+use crate::{load_program, DynResult, Options};
+
+const USAGE: &str = "USAGE: name map [source file or ELF executable] [--svg] [--endian big|little] [--entry label|address]";
+
+// `name map`: draws the address space of a program as it is loaded, from
+// the top of memory down: each memory region the machine sets up, what the
+// program loaded into it and the labels there, the heap and stack and how
+// they grow, the device windows and the unmapped gaps in between. Areas are
+// drawn the same height whatever their size, with the size written beside
+// them, since the address space is too large to draw to scale. `--svg`
+// draws it as an SVG image instead of text.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Code,
+    Data,
+    Heap,
+    Stack,
+    Device,
+    Unmapped,
+}
+
+#[derive(Debug)]
+struct Area {
+    start: u32,
+    // One past the last byte, which for the area at the top of memory does
+    // not fit in a u32
+    end: u64,
+    kind: Kind,
+    name: String,
+    detail: String,
+    // The labels in the area, in address order
+    labels: Vec<(u32, String)>,
+}
+
+// A byte count the way people say it: 16 bytes, 4 KiB, 3.75 MiB
+fn format_size(bytes: u64) -> String {
+    let units = [("GiB", 1 << 30), ("MiB", 1 << 20), ("KiB", 1 << 10)];
+    for (unit, size) in units {
+        if bytes >= size {
+            let scaled = bytes as f64 / size as f64;
+            let text = format!("{:.2}", scaled);
+            return format!(
+                "{} {}",
+                text.trim_end_matches('0').trim_end_matches('.'),
+                unit
+            );
+        }
+    }
+    format!("{} bytes", bytes)
+}
+
+// The regions and devices of a loaded machine, with the gaps between them,
+// from the bottom of memory up
+fn areas(mips: &Mips, symbols: &[Symbol]) -> Vec<Area> {
+    let stack_start = memmap::HEAP_START + memmap::HEAP_SIZE;
+    let mut areas: Vec<Area> = vec![];
+    for region in &mips.memory.regions {
+        let (kind, name, detail) = match region.base {
+            memmap::HEAP_START => {
+                let detail = match region.length {
+                    0 => format!(
+                        "empty, sbrk grows it up to {}",
+                        format_size(region.max_length as u64)
+                    ),
+                    length => format!(
+                        "{} in use of {}",
+                        format_size(length as u64),
+                        format_size(region.max_length as u64)
+                    ),
+                };
+                (Kind::Heap, "heap".to_string(), detail)
+            }
+            base if base == stack_start => {
+                let detail = format!(
+                    "{}, grows down from $sp = 0x{:08x}",
+                    format_size(region.max_length as u64),
+                    mips.reg(Register::Sp)
+                );
+                (Kind::Stack, "stack".to_string(), detail)
+            }
+            base => {
+                let (kind, name) = match (region.executable, base) {
+                    (true, memmap::TEXT_START) => (Kind::Code, ".text"),
+                    (true, _) => (Kind::Code, "code"),
+                    (false, memmap::DATA_START) => (Kind::Data, ".data"),
+                    (false, _) => (Kind::Data, "data"),
+                };
+                let detail = format!(
+                    "{} loaded of {}",
+                    format_size(region.length as u64),
+                    format_size(region.max_length as u64)
+                );
+                (kind, name.to_string(), detail)
+            }
+        };
+        areas.push(Area {
+            start: region.base,
+            end: region.base as u64 + region.max_length as u64,
+            kind,
+            name,
+            detail,
+            labels: vec![],
+        });
+    }
+    for mapped in &mips.devices {
+        areas.push(Area {
+            start: mapped.base,
+            end: mapped.base as u64 + mapped.length as u64,
+            kind: Kind::Device,
+            name: format!("{} (MMIO)", mapped.device.name()),
+            detail: format_size(mapped.length as u64),
+            labels: vec![],
+        });
+    }
+    areas.sort_by_key(|area| area.start);
+
+    let mut symbols: Vec<&Symbol> = symbols.iter().collect();
+    symbols.sort_by_key(|symbol| symbol.address);
+    for symbol in symbols {
+        let address = symbol.address as u64;
+        if let Some(area) = areas
+            .iter_mut()
+            .find(|area| (area.start as u64..area.end).contains(&address))
+        {
+            area.labels.push((symbol.address, symbol.name.clone()));
+        }
+    }
+
+    // Whatever is left over is unmapped
+    let mut all = vec![];
+    let mut next = 0u64;
+    for area in areas {
+        if (area.start as u64) > next {
+            all.push(unmapped(next, area.start as u64));
+        }
+        next = next.max(area.end);
+        all.push(area);
+    }
+    if next < 1 << 32 {
+        all.push(unmapped(next, 1 << 32));
+    }
+    all
+}
+
+fn unmapped(start: u64, end: u64) -> Area {
+    Area {
+        start: start as u32,
+        end,
+        kind: Kind::Unmapped,
+        name: "unmapped".to_string(),
+        detail: format_size(end - start),
+        labels: vec![],
+    }
+}
+
+// The label lines drawn inside an area
+fn label_lines(area: &Area) -> Vec<String> {
+    area.labels
+        .iter()
+        .map(|(address, name)| format!("  0x{:08x} {}", address, name))
+        .collect()
+}
+
+//              +------------------------------+
+//   0x7fc40000 | stack                        | 3.75 MiB, grows down from $sp = 0x7ffffffc
+//              +------------------------------+
+//   0x10040000 | heap                         | empty, sbrk grows it up to 4 MiB
+fn render_text(areas: &[Area]) -> String {
+    let width = areas
+        .iter()
+        .map(|area| {
+            label_lines(area)
+                .iter()
+                .map(String::len)
+                .fold(area.name.len(), usize::max)
+        })
+        .max()
+        .unwrap_or(0);
+    let border = format!("           +{}+", "-".repeat(width + 2));
+
+    let mut lines = vec![border.clone()];
+    for area in areas.iter().rev() {
+        lines.push(format!(
+            "0x{:08x} | {:<width$} | {}",
+            area.start,
+            area.name,
+            area.detail,
+            width = width
+        ));
+        for label in label_lines(area) {
+            lines.push(format!("           | {:<width$} |", label, width = width));
+        }
+        lines.push(border.clone());
+    }
+    lines.join("\n")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn render_svg(areas: &[Area]) -> String {
+    const ROW: usize = 18;
+    const LEFT: usize = 100;
+    const BOX_WIDTH: usize = 260;
+
+    let mut body = vec![];
+    let mut y = 10;
+    for area in areas.iter().rev() {
+        let labels = label_lines(area);
+        let height = ROW * (2 + labels.len());
+        let fill = match area.kind {
+            Kind::Code => "#cfe2f3",
+            Kind::Data => "#d9ead3",
+            Kind::Heap => "#fff2cc",
+            Kind::Stack => "#fce5cd",
+            Kind::Device => "#ead1dc",
+            Kind::Unmapped => "#eeeeee",
+        };
+        body.push(format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="black"/>"#,
+            LEFT, y, BOX_WIDTH, height, fill
+        ));
+        body.push(format!(
+            r#"<text x="{}" y="{}" text-anchor="end">0x{:08x}</text>"#,
+            LEFT - 6,
+            y + height - 4,
+            area.start
+        ));
+        body.push(format!(
+            r#"<text x="{}" y="{}" font-weight="bold">{}</text>"#,
+            LEFT + 8,
+            y + ROW,
+            escape(&area.name)
+        ));
+        body.push(format!(
+            r#"<text x="{}" y="{}">{}</text>"#,
+            LEFT + BOX_WIDTH + 10,
+            y + ROW,
+            escape(&area.detail)
+        ));
+        for (i, label) in labels.iter().enumerate() {
+            body.push(format!(
+                r#"<text x="{}" y="{}">{}</text>"#,
+                LEFT + 8,
+                y + ROW * (i + 2),
+                escape(label.trim())
+            ));
+        }
+        y += height;
+    }
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n{}\n</svg>",
+        LEFT + BOX_WIDTH + 420,
+        y + 10,
+        body.join("\n")
+    )
+}
+
+pub fn map_main(args: &[String], options: &Options) -> DynResult<()> {
+    let mut svg = false;
+    let mut positional = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--svg" => svg = true,
+            _ => positional.push(arg),
+        }
+    }
+    let [path] = positional.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let (mips, symbols) = load_program(path, options)?;
+    let areas = areas(&mips, &symbols);
+    if svg {
+        println!("{}", render_svg(&areas));
+    } else {
+        println!("{}", render_text(&areas));
+    }
+    Ok(())
+}
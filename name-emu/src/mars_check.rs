@@ -0,0 +1,144 @@
+use name_as::nma::{assemble_source, AssemblerOptions};
+
+use crate::DynResult;
+use name_emu::disasm::disassemble;
+
+const USAGE: &str = "USAGE: name mars-check";
+
+// What MARS 4.5 assembles each pseudo-instruction into, taken from its
+// PseudoOps.txt, with the labels laid out by PROGRAM. Exercises written for
+// MARS often check the exact words a program assembles to, so any
+// pseudo-instruction NAME expands differently would mark a correct answer
+// wrong. Each row is checked by assembling both sides with NAME and
+// comparing the words.
+#[rustfmt::skip]
+const MARS_EXPANSIONS: &[(&str, &[&str])] = &[
+    ("li $t1, 100", &["addiu $t1, $zero, 100"]),
+    ("li $t1, -100", &["addiu $t1, $zero, -100"]),
+    ("li $t1, 40000", &["ori $t1, $zero, 40000"]),
+    ("li $t1, 100000", &["lui $at, 0x0001", "ori $t1, $at, 0x86a0"]),
+    ("li $t1, -40000", &["lui $at, 0xffff", "ori $t1, $at, 0x63c0"]),
+    ("li $t1, 0x12340000", &["lui $at, 0x1234", "ori $t1, $at, 0"]),
+    ("la $t1, word", &["lui $at, 0x1001", "ori $t1, $at, 0x0004"]),
+    ("la $t1, far", &["lui $at, 0x1001", "ori $t1, $at, 0x8008"]),
+    ("lw $t1, word", &["lui $at, 0x1001", "lw $t1, 4($at)"]),
+    ("lw $t1, far", &["lui $at, 0x1002", "lw $t1, -32760($at)"]),
+    ("lw $t1, word($t2)", &["lui $at, 0x1001", "addu $at, $at, $t2", "lw $t1, 4($at)"]),
+    ("sw $t1, word", &["lui $at, 0x1001", "sw $t1, 4($at)"]),
+    ("lb $t1, word", &["lui $at, 0x1001", "lb $t1, 4($at)"]),
+    ("sh $t1, word($t2)", &["lui $at, 0x1001", "addu $at, $at, $t2", "sh $t1, 4($at)"]),
+    ("lwc1 $f0, word", &["lui $at, 0x1001", "lwc1 $f0, 4($at)"]),
+    ("move $t1, $t2", &["addu $t1, $zero, $t2"]),
+    ("neg $t1, $t2", &["sub $t1, $zero, $t2"]),
+    ("subi $t1, $t2, 100", &["addi $at, $zero, 100", "sub $t1, $t2, $at"]),
+    ("subi $t1, $t2, 100000", &["lui $at, 0x0001", "ori $at, $at, 0x86a0", "sub $t1, $t2, $at"]),
+    ("beqz $t1, target", &["beq $t1, $zero, target"]),
+    ("bnez $t1, target", &["bne $t1, $zero, target"]),
+    ("blt $t1, $t2, target", &["slt $at, $t1, $t2", "bne $at, $zero, target"]),
+    ("bgt $t1, $t2, target", &["slt $at, $t2, $t1", "bne $at, $zero, target"]),
+    ("ble $t1, $t2, target", &["slt $at, $t2, $t1", "beq $at, $zero, target"]),
+    ("bge $t1, $t2, target", &["slt $at, $t1, $t2", "beq $at, $zero, target"]),
+];
+
+// Lays out the labels the table refers to, as MARS would: `word` at
+// 0x10010004 and `far` at 0x10018008, whose low half has its top bit set.
+// `target` is the first instruction, so branches to it encode the same
+// whatever comes after them.
+const PROGRAM: &str = "
+        .data
+pad:    .word 0
+word:   .word 0
+        .space 0x8000
+far:    .word 0
+        .text
+target: nop
+";
+
+enum Outcome {
+    Same,
+    Differs(Vec<u32>),
+    // NAME cannot assemble the pseudo-instruction at all
+    Unsupported(String),
+}
+
+// The words `statements` assemble to after PROGRAM, with their address
+fn assemble(statements: &[&str]) -> Result<(u32, Vec<u32>), String> {
+    let options = AssemblerOptions {
+        file_name: "mars-check.asm".to_string(),
+        ..Default::default()
+    };
+    let source = format!("{}{}\n", PROGRAM, statements.join("\n"));
+    let assembled = assemble_source(&source, &options).map_err(|e| e.to_string())?;
+    let words = assembled
+        .text()
+        .chunks_exact(4)
+        .skip(1)
+        .map(|word| {
+            assembled
+                .endian
+                .u32_from_bytes([word[0], word[1], word[2], word[3]])
+        })
+        .collect();
+    Ok((assembled.text_address() + 4, words))
+}
+
+fn listing(address: u32, words: &[u32]) -> String {
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| disassemble(*word, address + 4 * i as u32))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+pub fn mars_check_main(args: &[String]) -> DynResult<()> {
+    if !args.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let mut differing = 0;
+    for (statement, expansion) in MARS_EXPANSIONS {
+        let (address, mars) = assemble(expansion).map_err(|e| {
+            format!(
+                "the MARS expansion of `{}` does not assemble: {}",
+                statement, e
+            )
+        })?;
+        let outcome = match assemble(&[statement]) {
+            Ok((_, name)) if name == mars => Outcome::Same,
+            Ok((_, name)) => Outcome::Differs(name),
+            Err(e) => Outcome::Unsupported(e),
+        };
+
+        match outcome {
+            Outcome::Same => println!("  {:<24} same as MARS", statement),
+            Outcome::Differs(name) => {
+                differing += 1;
+                println!("  {:<24} differs", statement);
+                println!("      NAME: {}", listing(address, &name));
+                println!("      MARS: {}", listing(address, &mars));
+            }
+            Outcome::Unsupported(e) => {
+                differing += 1;
+                println!("  {:<24} not assembled by NAME", statement);
+                println!("      NAME: {}", e.lines().next().unwrap_or_default());
+                println!("      MARS: {}", listing(address, &mars));
+            }
+        }
+    }
+    println!();
+    println!(
+        "{} of {} pseudo-instructions expand as in MARS",
+        MARS_EXPANSIONS.len() - differing,
+        MARS_EXPANSIONS.len()
+    );
+
+    if differing > 0 {
+        return Err(format!(
+            "{} pseudo-instructions expand differently from MARS",
+            differing
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+
+use name_as::mars::{translate_settings, translate_source, ImportNote};
+use name_as::nma::{assemble_source, AssemblerOptions};
+
+use crate::DynResult;
+
+const USAGE: &str = "USAGE: name import-mars [MARS source file] [output file] [--settings MARS settings file] [--config config file to write (default: name.toml)]";
+
+// `name import-mars`: brings a program written for MARS over to NAME, for
+// moving a course's existing programs across. Whatever MARS accepts and
+// NAME does not is rewritten (see name_as::mars), each change is reported,
+// and the result is assembled to show whether anything is left to fix by
+// hand. MARS's settings, exported as a properties file, become a config
+// file for name-as.
+
+fn print_notes(file: &str, notes: &[ImportNote]) {
+    for note in notes {
+        let file = if note.file.is_empty() {
+            file
+        } else {
+            note.file.as_str()
+        };
+        match note.line {
+            0 => eprintln!("{}: {}: {}", file, note.kind.label(), note.message),
+            line => eprintln!("{}:{}: {}: {}", file, line, note.kind.label(), note.message),
+        }
+    }
+}
+
+fn write(path: &str, contents: &str) -> DynResult<()> {
+    fs::write(path, contents)
+        .map_err(|why| format!("Failed to write {}. Reason: {}", path, why).into())
+}
+
+pub fn import_mars_main(args: &[String]) -> DynResult<()> {
+    let mut positional = vec![];
+    let mut settings_fn = None;
+    let mut config_fn = name_as::args::DEFAULT_CONFIG.to_string();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--settings" => settings_fn = Some(args_iter.next().ok_or(USAGE)?.clone()),
+            "--config" => config_fn = args_iter.next().ok_or(USAGE)?.clone(),
+            _ => positional.push(arg),
+        }
+    }
+    let [source_fn, output_fn] = positional.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    // An import never overwrites work
+    let outputs = std::iter::once(output_fn.as_str())
+        .chain(settings_fn.is_some().then_some(config_fn.as_str()));
+    for path in outputs {
+        if Path::new(path).exists() {
+            return Err(format!(
+                "{} already exists; move it away or write somewhere else",
+                path
+            )
+            .into());
+        }
+    }
+
+    let source = fs::read_to_string(source_fn)
+        .map_err(|why| format!("Failed to open {}. Reason: {}", source_fn, why))?;
+    // `.include` names files relative to the one including them, as in MARS
+    let directory = Path::new(source_fn.as_str())
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+    let mut include =
+        |name: &str| fs::read_to_string(directory.join(name)).map_err(|why| why.to_string());
+    let imported = translate_source(&source, &mut include);
+    print_notes(source_fn, &imported.notes);
+    write(output_fn, &imported.source)?;
+    println!(
+        "Wrote {} with {} change(s) from MARS",
+        output_fn,
+        imported.notes.len()
+    );
+
+    if let Some(settings_fn) = settings_fn {
+        let properties = fs::read_to_string(&settings_fn)
+            .map_err(|why| format!("Failed to open {}. Reason: {}", settings_fn, why))?;
+        let (config, notes) = translate_settings(&properties);
+        print_notes(&settings_fn, &notes);
+        write(&config_fn, &config)?;
+        println!("Wrote the settings of {} to {}", settings_fn, config_fn);
+    }
+
+    let options = AssemblerOptions {
+        file_name: output_fn.to_string(),
+        ..Default::default()
+    };
+    match assemble_source(&imported.source, &options) {
+        Ok(_) => {
+            println!("{} assembles with NAME", output_fn);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            Err(format!(
+                "{} does not assemble yet; the error above needs fixing by hand",
+                output_fn
+            )
+            .into())
+        }
+    }
+}
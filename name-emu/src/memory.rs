@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use name_core::machine::{Page, RegionState};
+
+// Memory is stored in 4 KiB pages that are only allocated the first time
+// they are written. A page that was never written reads back as zeros, so a
+// region can span megabytes of address space without costing anything until
+// the program actually uses it.
+const PAGE_SIZE: u32 = 0x1000;
+
+// A range of addresses the program may use. Only the first `length` bytes
+// are in use; the rest, up to `max_length`, is reserved so that running past
+// the end of a region can be told apart from a wild access.
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub base: u32,
+    pub length: u32,
+    pub max_length: u32,
+    // Whether the region holds code that jr and jalr may jump into
+    pub executable: bool,
+}
+
+// How an address relates to the regions in memory
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Access {
+    // Inside the in-use part of a region
+    Mapped,
+    // Inside a region, but past the part of it that is in use
+    Overrun,
+    // Not inside any region
+    Unmapped,
+}
+
+#[derive(Debug, Default)]
+pub struct Memory {
+    pub regions: Vec<Region>,
+    pages: HashMap<u32, Box<[u8; PAGE_SIZE as usize]>>,
+}
+
+impl Memory {
+    pub fn add_region(&mut self, base: u32, length: u32, max_length: u32, executable: bool) {
+        self.regions.push(Region {
+            base,
+            length,
+            max_length,
+            executable,
+        });
+    }
+
+    pub fn region_mut(&mut self, base: u32) -> Option<&mut Region> {
+        self.regions.iter_mut().find(|region| region.base == base)
+    }
+
+    pub fn access(&self, address: u32) -> Access {
+        for region in &self.regions {
+            let offset = address.wrapping_sub(region.base);
+            if offset < region.length {
+                return Access::Mapped;
+            }
+            if offset < region.max_length {
+                return Access::Overrun;
+            }
+        }
+        Access::Unmapped
+    }
+
+    // The region whose in-use part holds `address`, as an index into regions
+    pub fn region_of(&self, address: u32) -> Option<usize> {
+        self.regions
+            .iter()
+            .position(|region| address.wrapping_sub(region.base) < region.length)
+    }
+
+    // Whether `address` is in use and holds code
+    pub fn executable(&self, address: u32) -> bool {
+        self.region_of(address)
+            .is_some_and(|index| self.regions[index].executable)
+    }
+
+    // Reads a byte without checking it against the regions
+    pub fn get(&self, address: u32) -> u8 {
+        match self.pages.get(&(address / PAGE_SIZE)) {
+            Some(page) => page[(address % PAGE_SIZE) as usize],
+            None => 0,
+        }
+    }
+
+    // Writes a byte without checking it against the regions, allocating its
+    // page if needed
+    pub fn set(&mut self, address: u32, value: u8) {
+        let page = self
+            .pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE as usize]));
+        page[(address % PAGE_SIZE) as usize] = value;
+    }
+
+    // Copies a block of bytes into memory starting at `address`
+    pub fn set_bytes(&mut self, address: u32, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.set(address + i as u32, *byte);
+        }
+    }
+
+    // The regions and every allocated page, for a snapshot
+    pub fn image(&self) -> (Vec<RegionState>, Vec<Page>) {
+        let regions = self
+            .regions
+            .iter()
+            .map(|region| RegionState {
+                base: region.base,
+                length: region.length,
+                max_length: region.max_length,
+                executable: region.executable,
+            })
+            .collect();
+        // A page of zeroes reads the same as one never touched, so the
+        // image is the same however the program came to its contents
+        let mut pages: Vec<Page> = self
+            .pages
+            .iter()
+            .filter(|(_, page)| page.iter().any(|&byte| byte != 0))
+            .map(|(number, page)| Page {
+                address: number * PAGE_SIZE,
+                bytes: page.to_vec(),
+            })
+            .collect();
+        pages.sort_by_key(|page| page.address);
+        (regions, pages)
+    }
+
+    // Replaces the regions and contents of memory with those of a snapshot
+    pub fn restore_image(&mut self, regions: &[RegionState], pages: &[Page]) {
+        self.regions = regions
+            .iter()
+            .map(|region| Region {
+                base: region.base,
+                length: region.length,
+                max_length: region.max_length,
+                executable: region.executable,
+            })
+            .collect();
+        self.pages.clear();
+        for page in pages {
+            self.set_bytes(page.address, &page.bytes);
+        }
+    }
+}
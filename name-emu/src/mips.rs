@@ -1,455 +1,1337 @@
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::io::Cursor;
+use name_core::elf::ElfExecutable;
+use name_core::encoding::{self, Field, Instruction, COP0_OPCODE, SPECIAL2_OPCODE};
+use name_core::endian::Endian;
+use name_core::isa::{identify, Op};
+use name_core::machine::{MachineState, MemoryImage};
+use name_core::memmap;
+use name_core::register::Register;
 
-use std::fs::File;
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::io::Write;
 
+use crate::cache::Cache;
 use crate::exception::{ExecutionErrors, ExecutionEvents};
+use crate::files::{FileSystem, OpenFile, VirtualFileSystem};
+use crate::hook::ExecutionHooks;
+use crate::hypercall::{install_hypercalls, Hypercalls, HYPERCALL_FUNCTS};
+use crate::memory::{Access, Memory, Region};
+use crate::mmio::{Devices, KeyboardDisplay, KEYBOARD_DISPLAY_ADDRESS, KEYBOARD_DISPLAY_LENGTH};
+use crate::stats::{self, Category, Statistics};
+use crate::syscall::{Console, ConsoleSettings, StdConsole};
+use crate::trap::INITIAL_STATUS;
 
-pub const DOT_TEXT_START_ADDRESS: u32 = 0x00400000;
-const DOT_TEXT_MAX_LENGTH: u32 = 0x1000;
-const LEN_TEXT_INITIAL: usize = 200;
+// The memory map is shared with the assembler (see name_core::memmap)
+pub const DOT_TEXT_START_ADDRESS: u32 = memmap::TEXT_START;
+const DOT_TEXT_MAX_LENGTH: u32 = memmap::TEXT_SIZE;
 const MIPS_INSTRUCTION_LENGTH: usize = 4;
+// The heap starts out empty and is grown by sbrk, as in MARS
+pub const HEAP_START_ADDRESS: u32 = memmap::HEAP_START;
+// .data comes before the heap, as laid out by name-as
+pub const DOT_DATA_START_ADDRESS: u32 = memmap::DATA_START;
+const DOT_DATA_MAX_LENGTH: u32 = HEAP_START_ADDRESS - DOT_DATA_START_ADDRESS;
+const HEAP_MAX_LENGTH: u32 = memmap::HEAP_SIZE;
+// The stack occupies the top of user memory and grows down towards the heap,
+// with everything above the heap's reserved space open to it. $sp starts at
+// the highest word in it.
+pub(crate) const STACK_END_ADDRESS: u32 = memmap::STACK_END;
+pub(crate) const STACK_START_ADDRESS: u32 = HEAP_START_ADDRESS + HEAP_MAX_LENGTH;
+const STACK_MAX_LENGTH: u32 = STACK_END_ADDRESS - STACK_START_ADDRESS;
+const INITIAL_STACK_POINTER: u32 = STACK_END_ADDRESS - 4;
 
-pub const REGISTER_NAMES: [&str; 32] = [
-    "$zero",
-    "$at",
-    "$v0",
-    "$v1",
-    "$a0",
-    "$a1",
-    "$a2",
-    "$a3",
-    "$t0",
-    "$t1",
-    "$t2",
-    "$t3",
-    "$t4",
-    "$t5",
-    "$t6",
-    "$t7",
-    "$s0",
-    "$s1",
-    "$s2",
-    "$s3",
-    "$s4",
-    "$s5",
-    "$s6",
-    "$s7",
-    "$t8",
-    "$t9",
-    "$k0",
-    "$k1",
-    "$gp",
-    "$sp",
-    "$fp",
-    "$ra"
-];
 pub const PC_NAME: &str = "$pc";
 
-#[derive(Debug)]
-enum BranchDelays {
+// Values of the fmt field of coprocessor 1 instructions
+pub const FMT_MF: u8 = 0x00;
+pub const FMT_MT: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum BranchDelays {
     NotActive,
     Set,
-    Ready
+    Ready,
+}
+
+// A byte the program stored, and the one it replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteWrite {
+    pub address: u32,
+    pub old: u8,
+    pub new: u8,
 }
 
 #[derive(Debug)]
-pub(crate) struct Mips {
+pub struct Mips {
     pub regs: [u32; 32],
     pub floats: [f32; 32],
+    // Coprocessor 1 condition flag 0, set by c.cond.s and tested by bc1t/bc1f
+    pub fp_condition: bool,
     pub mult_hi: u32,
     pub mult_lo: u32,
     pub pc: usize,
 
     // Branch delay slots are implemented by filling this buffer with the
     // branch target, which will be triggered after the following instruction
-    branch_delay_target: u32,
-    branch_delay_status: BranchDelays,
-    
-
-    // A list of vectors of memory pools, their base addresses, and their
-    // lengths.
-    // Memories allocated at runtime will actually have Vec lengths shorter
-    // than this by 0x10. This is intended to alert the user that they 
-    // probably wrote out of bounds, allowing us to return a clearer exception
-    // and explanation as to what happened.
-    pub memories: Vec<(Vec<u8>, u32, u32)>,
+    pub(crate) branch_delay_target: u32,
+    pub(crate) branch_delay_status: BranchDelays,
+    // Set by branch_to, so step can tell a taken branch from one that
+    // fell through
+    transferred: bool,
+    // When false (the default, matching MARS), taken branches and jumps
+    // transfer control immediately instead of after the delay slot
+    pub delay_slots: bool,
+    // Coprocessor 0 state describing the most recent exception: the address
+    // of the faulting instruction, the Cause register and, for an address
+    // error, the address that was bad (see trap.rs)
+    pub epc: u32,
+    pub cause: u32,
+    pub bad_vaddr: u32,
+    // The rest of coprocessor 0: the Status register, which enables
+    // interrupts, and the timer, which raises one when Count, counting up
+    // once per instruction, reaches Compare
+    pub status: u32,
+    pub count: u32,
+    pub compare: u32,
+    // Where exceptions and interrupts go. Without a handler, an exception
+    // stops the program and interrupts are never taken
+    pub exception_handler: Option<u32>,
+    // When set, syscall raises a syscall exception for the handler to
+    // service instead of being serviced by NAME, except in the handler
+    pub trap_syscalls: bool,
+    // Byte order of multi-byte memory accesses, including instruction fetch.
+    // Must match the order the program was assembled with.
+    pub endian: Endian,
+    // The lowest address $sp has held. The heap may not grow past it, nor
+    // the stack below the heap break (see check_stack)
+    pub stack_low: u32,
+
+    // The regions of the address space the program may use, backed by
+    // pages that are allocated as they are written (see memory.rs)
+    pub memory: Memory,
+    // Devices that loads and stores in their ranges go to instead of
+    // memory, such as MARS's keyboard and display (see mmio.rs)
+    pub devices: Devices,
     // The end of the MIPS program. In NAME, the program terminates when no more instructions exist
     // (as in, falling off the bottom is valid).
     pub stop_address: usize,
-    
+
     // Memory for the result of a previous instruction (useful for tracking exceptions)
-    pub prev_ins_result: Result<(), ExecutionErrors>
-}
+    pub prev_ins_result: Result<(), ExecutionErrors>,
+
+    // Set once the program requests to exit through a syscall
+    pub exit_code: Option<u32>,
+    // Where syscalls read input from and write output to
+    pub console: Box<dyn Console>,
+    // How output and input are translated on the way (see syscall.rs), and
+    // the column output has reached, for expanding tabs
+    pub console_settings: ConsoleSettings,
+    pub(crate) console_column: usize,
+    // Where the files programs open live, and the ones they have open,
+    // indexed by descriptor less 3 (see files.rs)
+    pub files: Box<dyn FileSystem>,
+    pub(crate) open_files: Vec<Option<OpenFile>>,
+
+    // Syscalls follow the Linux o32 ABI instead of MARS (see linux.rs)
+    pub linux_abi: bool,
+    // Bytes of a character that a Linux read had no room left for, which
+    // the next read returns first
+    pub(crate) linux_pending: VecDeque<u8>,
+    // Host handlers for user-defined instructions (see hypercall.rs)
+    pub hypercalls: Hypercalls,
+    // Run before every instruction, such as the fault injector (see hook.rs)
+    pub hooks: ExecutionHooks,
 
+    // When set, suspicious register writes are recorded in audit_warnings
+    // for the frontend to report. Execution hooks report there too.
+    pub audit: bool,
+    pub audit_warnings: Vec<String>,
+
+    // When set, every byte stored to memory is recorded in memory_writes,
+    // in order, for execution traces to report and for history.rs to undo
+    pub record_writes: bool,
+    pub memory_writes: Vec<ByteWrite>,
+    // Likewise every byte the program reads, other than instruction
+    // fetches, for the debugger's read watchpoints. Reads only borrow the
+    // machine, so the record sits in a RefCell
+    pub record_reads: bool,
+    pub memory_reads: RefCell<Vec<u32>>,
+
+    // What the program has executed so far (see stats.rs)
+    pub stats: Statistics,
+    // Optional models of the instruction and data caches, which count hits
+    // and misses for fetches and for loads and stores (see cache.rs)
+    pub icache: Option<Cache>,
+    pub dcache: Option<Cache>,
+}
 
 impl Default for Mips {
     fn default() -> Self {
-        Self {
-            regs: [0; 32],
+        let mut regs = [0; 32];
+        regs[Register::Sp.number()] = INITIAL_STACK_POINTER;
+
+        let mut mips = Self {
+            regs,
             floats: [0f32; 32],
+            fp_condition: false,
             mult_hi: 0,
             mult_lo: 0,
             pc: DOT_TEXT_START_ADDRESS as usize,
             branch_delay_target: 0,
             branch_delay_status: BranchDelays::NotActive,
-            memories: vec![
-                (vec![0; LEN_TEXT_INITIAL], DOT_TEXT_START_ADDRESS, DOT_TEXT_MAX_LENGTH)   
-            ],
+            transferred: false,
+            delay_slots: false,
+            epc: 0,
+            cause: 0,
+            bad_vaddr: 0,
+            status: INITIAL_STATUS,
+            count: 0,
+            compare: 0,
+            exception_handler: None,
+            trap_syscalls: false,
+            endian: Endian::Little,
+            stack_low: INITIAL_STACK_POINTER,
+            // .text is filled in by load_text and the heap is grown by sbrk.
+            // The whole stack is in use from the start since it is addressed from the top.
+            memory: {
+                let mut memory = Memory::default();
+                memory.add_region(DOT_TEXT_START_ADDRESS, 0, DOT_TEXT_MAX_LENGTH, true);
+                memory.add_region(HEAP_START_ADDRESS, 0, HEAP_MAX_LENGTH, false);
+                memory.add_region(
+                    STACK_START_ADDRESS,
+                    STACK_MAX_LENGTH,
+                    STACK_MAX_LENGTH,
+                    false,
+                );
+                memory
+            },
+            devices: vec![],
             stop_address: DOT_TEXT_START_ADDRESS as usize,
-            prev_ins_result: Ok(())
-        }
+            prev_ins_result: Ok(()),
+            exit_code: None,
+            console: Box::new(StdConsole::default()),
+            console_settings: ConsoleSettings::default(),
+            console_column: 0,
+            files: Box::new(VirtualFileSystem::default()),
+            open_files: vec![],
+            linux_abi: false,
+            linux_pending: VecDeque::new(),
+            hypercalls: Hypercalls::new(),
+            hooks: vec![],
+            audit: false,
+            audit_warnings: vec![],
+            record_writes: false,
+            memory_writes: vec![],
+            record_reads: false,
+            memory_reads: RefCell::new(vec![]),
+            stats: Statistics::default(),
+            icache: None,
+            dcache: None,
+        };
+        install_hypercalls(&mut mips);
+        mips.map_device(
+            KEYBOARD_DISPLAY_ADDRESS,
+            KEYBOARD_DISPLAY_LENGTH,
+            Box::new(KeyboardDisplay::default()),
+        );
+        mips
     }
 }
 
 #[derive(Debug)]
-struct Rtype {
-    rs: usize,
-    rt: usize,
-    rd: usize,
-    shamt: u8,
-    funct: u8
+pub struct Rtype {
+    pub rs: usize,
+    pub rt: usize,
+    pub rd: usize,
+    pub shamt: u8,
+    pub funct: u8,
 }
 
 #[derive(Debug)]
-struct Itype {
-    opcode: u32,
-    rs: usize,
-    rt: usize,
-    imm: u16
+pub struct Itype {
+    pub opcode: u32,
+    pub rs: usize,
+    pub rt: usize,
+    pub imm: u16,
 }
 
 #[derive(Debug)]
-struct Jtype {
-    opcode: u32,
-    dest: u32
+pub struct Jtype {
+    pub opcode: u32,
+    pub dest: u32,
 }
 
-// struct Jtype
-// struct Ftype
+// Coprocessor 1 (floating-point) instructions
+#[derive(Debug)]
+pub struct Ftype {
+    // Selects the operand format (single, word) or, for moves and
+    // branches, the operation itself
+    pub fmt: u8,
+    pub ft: usize,
+    pub fs: usize,
+    pub fd: usize,
+    pub funct: u8,
+    // The low 16 bits, which branches use as their offset
+    pub imm: u16,
+}
 
 #[derive(Debug)]
-enum Instructions {
+pub enum Instructions {
     R(Rtype),
     I(Itype),
-    J(Jtype)
-    //F type
+    J(Jtype),
+    F(Ftype),
+    // Coprocessor 0 moves and eret, which share the coprocessor 1 layout
+    Cop0(Ftype),
+    // A user-defined instruction, run by a host handler
+    Hypercall(Rtype),
 }
 
 impl Mips {
+    // Reads a general purpose register. Accepts a Register or, for operands
+    // decoded straight out of an instruction, a raw register number.
+    pub fn reg(&self, reg: impl Into<usize>) -> u32 {
+        self.regs[reg.into()]
+    }
 
-    fn dispatch_r(&mut self, ins: Rtype, opcode: u32) -> Result<(), ExecutionErrors> {
-
-        match ins.funct {
-            // Shift-left logical
-            0x0 => {
-                self.regs[ins.rd] = self.regs[ins.rt] << ins.shamt;
-            }
-            // Shift-right logical
-            0x2 => {
-                self.regs[ins.rd] = self.regs[ins.rt] >> ins.shamt;
-            }
-            // Add
-            0x20 => {
-                let result = self.regs[ins.rt].checked_add(self.regs[ins.rs]);
-                match result {
-                    Some(value) => {self.regs[ins.rd] = value;}
-                    None => {
-                        return Err(ExecutionErrors::IntegerOverflow { 
-                        rt: ins.rt, 
-                        rs: ins.rs, 
-                        value1: self.regs[ins.rt],
-                        value2: self.regs[ins.rs]
-                        });
-                    }
+    // Every register write goes through here. Writes to $zero are discarded,
+    // and in audit mode writes to $zero, $k0 and $k1 are reported since user
+    // programs have no business touching them.
+    pub fn set_reg(&mut self, reg: impl Into<usize>, value: u32) {
+        let reg = reg.into();
+        let register = Register::from_number(reg);
+        if self.audit && matches!(register, Some(Register::Zero | Register::K0 | Register::K1)) {
+            let address = (self.pc - MIPS_INSTRUCTION_LENGTH) as u32;
+            self.audit_warnings.push(format!(
+                "0x{:08x}: write of 0x{:08x} to {}{}",
+                address,
+                value,
+                Register::ALL[reg],
+                if reg == 0 {
+                    " discarded"
+                } else {
+                    ", which is reserved for the kernel"
+                }
+            ));
+        }
+        if reg != 0 {
+            self.regs[reg] = value;
+        }
+    }
+
+    // Division by zero does not trap on MIPS, it just leaves HI and LO
+    // unpredictable. Like MARS, NAME leaves them unchanged, and audit mode
+    // points it out since it is almost always a bug.
+    fn divide_by_zero(&mut self) {
+        if self.audit {
+            let address = (self.pc - MIPS_INSTRUCTION_LENGTH) as u32;
+            self.audit_warnings.push(format!(
+                "0x{:08x}: division by zero, hi and lo are left unchanged",
+                address
+            ));
+        }
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.pc as u32
+    }
+
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = pc as usize;
+    }
+
+    // Reads a floating-point register. Like reg, accepts a FloatRegister or
+    // a raw register number.
+    pub fn float(&self, reg: impl Into<usize>) -> f32 {
+        self.floats[reg.into()]
+    }
+
+    pub fn set_float(&mut self, reg: impl Into<usize>, value: f32) {
+        self.floats[reg.into()] = value;
+    }
+
+    // Reads a double, which takes an even-numbered register and the one
+    // after it, with the low word in the even one
+    pub fn double(&self, reg: impl Into<usize>) -> f64 {
+        let reg = reg.into();
+        let (low, high) = (self.floats[reg].to_bits(), self.floats[reg + 1].to_bits());
+        f64::from_bits((high as u64) << 32 | low as u64)
+    }
+
+    pub fn set_double(&mut self, reg: impl Into<usize>, value: f64) {
+        let reg = reg.into();
+        let bits = value.to_bits();
+        self.floats[reg] = f32::from_bits(bits as u32);
+        self.floats[reg + 1] = f32::from_bits((bits >> 32) as u32);
+    }
+
+    // Transfers control to target, either immediately or after the
+    // delay slot depending on how the machine is configured.
+    fn branch_to(&mut self, target: u32) {
+        self.transferred = true;
+        if self.delay_slots {
+            self.branch_delay_target = target;
+            self.branch_delay_status = BranchDelays::Set;
+        } else {
+            self.pc = target as usize;
+        }
+    }
+
+    // jr and jalr jump to whatever their register holds, which for a jump
+    // table is only as good as the index used to pick the entry. The target
+    // is checked at the jump, so a bad one is reported where it was used
+    // rather than as a fetch from nowhere. It has to be an aligned address in
+    // code, or the end of the program, where returning ends it. Audit mode
+    // also points out jumps that leave the segment they are in, which is
+    // legal but rarely meant.
+    fn check_indirect_jump(&mut self, target: u32) -> Result<(), ExecutionErrors> {
+        if !target.is_multiple_of(MIPS_INSTRUCTION_LENGTH as u32) {
+            return Err(ExecutionErrors::MemoryUnalignedAccess {
+                load_address: target,
+                size: 4,
+                store: false,
+            });
+        }
+        if target as usize == self.stop_address {
+            return Ok(());
+        }
+        if !self.memory.executable(target) {
+            return Err(ExecutionErrors::IllegalJump { target });
+        }
+        let address = (self.pc - MIPS_INSTRUCTION_LENGTH) as u32;
+        if self.audit && self.memory.region_of(address) != self.memory.region_of(target) {
+            self.audit_warnings.push(format!(
+                "0x{:08x}: indirect jump to 0x{:08x} leaves the segment it is in",
+                address, target
+            ));
+        }
+        Ok(())
+    }
+
+    // The address a branch with the given immediate lands on.
+    // By the time an instruction is dispatched pc already points past it,
+    // so this is relative to the delay slot as the ISA specifies.
+    fn branch_target(&self, imm: u16) -> u32 {
+        (self.pc as u32).wrapping_add(((imm as i16 as i32) << 2) as u32)
+    }
+
+    // The address execution should come back to after a jump-and-link.
+    // With delay slots the instruction after the jump has already run by
+    // the time the callee returns, so skip over it.
+    fn return_address(&self) -> u32 {
+        if self.delay_slots {
+            self.pc as u32 + MIPS_INSTRUCTION_LENGTH as u32
+        } else {
+            self.pc as u32
+        }
+    }
+
+    // Carries out the instruction the shared ISA table says a word is. The
+    // fields are named as the instruction's operands use them
+    fn execute(&mut self, op: Op, word: u32) -> Result<(), ExecutionErrors> {
+        let rs = Field::Rs.get(word) as usize;
+        let rt = Field::Rt.get(word) as usize;
+        let rd = Field::Rd.get(word) as usize;
+        let shamt = Field::Shamt.get(word);
+        let imm = Field::Imm.get(word) as u16;
+        // Floating-point instructions put their registers in the same places
+        let (ft, fs, fd) = (rt, rd, shamt as usize);
+        // Every immediate is sign-extended but those of the logical
+        // operations and lui, which are zero-extended
+        let simm = imm as i16 as i32 as u32;
+        // Loads and stores address memory as base register plus offset
+        let address = self.regs[rs].wrapping_add(simm);
+        let undefined = ExecutionErrors::UndefinedInstruction { instruction: word };
+
+        match op {
+            // No operation, encoded as sll $zero, $zero, 0. It is not a
+            // write to $zero as far as audit mode is concerned
+            Op::Sll if word == 0 => (),
+            Op::Sll => self.set_reg(rd, self.regs[rt] << shamt),
+            Op::Srl => self.set_reg(rd, self.regs[rt] >> shamt),
+            Op::Sra => self.set_reg(rd, ((self.regs[rt] as i32) >> shamt) as u32),
+            // Variable shifts use the low five bits of $rs
+            Op::Sllv => self.set_reg(rd, self.regs[rt] << (self.regs[rs] & 31)),
+            Op::Srlv => self.set_reg(rd, self.regs[rt] >> (self.regs[rs] & 31)),
+            Op::Srav => self.set_reg(rd, ((self.regs[rt] as i32) >> (self.regs[rs] & 31)) as u32),
+            Op::Jr => {
+                let target = self.regs[rs];
+                self.check_indirect_jump(target)?;
+                self.branch_to(target);
+            }
+            Op::Jalr => {
+                let target = self.regs[rs];
+                self.check_indirect_jump(target)?;
+                self.set_reg(rd, self.return_address());
+                self.branch_to(target);
+            }
+            // Conditional moves, on whether $rt is zero
+            Op::Movz => {
+                if self.regs[rt] == 0 {
+                    self.set_reg(rd, self.regs[rs]);
+                }
+            }
+            Op::Movn => {
+                if self.regs[rt] != 0 {
+                    self.set_reg(rd, self.regs[rs]);
+                }
+            }
+            Op::Syscall => {
+                if self.syscall_trapped() {
+                    return Ok(());
+                }
+                if self.linux_abi {
+                    self.linux_syscall()?;
+                } else {
+                    self.syscall()?;
+                }
+            }
+            Op::Break => return Err(self.breakpoint()),
+            // With one processor, memory accesses are already in order
+            Op::Sync => (),
+            Op::Mfhi => self.set_reg(rd, self.mult_hi),
+            Op::Mthi => self.mult_hi = self.regs[rs],
+            Op::Mflo => self.set_reg(rd, self.mult_lo),
+            Op::Mtlo => self.mult_lo = self.regs[rs],
+            // The 64-bit product is split across HI and LO
+            Op::Mult => self.set_hi_lo(self.signed_product(rs, rt)),
+            Op::Multu => self.set_hi_lo(self.unsigned_product(rs, rt)),
+            // LO gets the quotient and HI the remainder. Dividing the most
+            // negative number by -1 wraps rather than trapping
+            Op::Div => {
+                let (dividend, divisor) = (self.regs[rs] as i32, self.regs[rt] as i32);
+                if divisor == 0 {
+                    self.divide_by_zero();
+                } else {
+                    self.mult_lo = dividend.wrapping_div(divisor) as u32;
+                    self.mult_hi = dividend.wrapping_rem(divisor) as u32;
                 }
             }
-            // Subtract
-            0x22 => {
-                let result = self.regs[ins.rt].checked_sub(self.regs[ins.rs]);
-                match result {
-                    Some(value) => {self.regs[ins.rd] = value;}
-                    None => {
-                        return Err(ExecutionErrors::IntegerOverflow { 
-                        rt: ins.rt, 
-                        rs: ins.rs, 
-                        value1: self.regs[ins.rt],
-                        value2: self.regs[ins.rs]
-                        });
+            Op::Divu => {
+                let (dividend, divisor) = (self.regs[rs], self.regs[rt]);
+                match dividend.checked_div(divisor) {
+                    Some(quotient) => {
+                        self.mult_lo = quotient;
+                        self.mult_hi = dividend % divisor;
                     }
+                    None => self.divide_by_zero(),
+                }
+            }
+            // add, addi and sub trap on signed overflow; the unsigned forms
+            // wrap instead
+            Op::Add => {
+                let (lhs, rhs) = (self.regs[rs], self.regs[rt]);
+                match (lhs as i32).checked_add(rhs as i32) {
+                    Some(value) => self.set_reg(rd, value as u32),
+                    None => return Err(self.overflow(lhs, rhs, '+')),
+                }
+            }
+            Op::Addu => self.set_reg(rd, self.regs[rs].wrapping_add(self.regs[rt])),
+            Op::Sub => {
+                let (lhs, rhs) = (self.regs[rs], self.regs[rt]);
+                match (lhs as i32).checked_sub(rhs as i32) {
+                    Some(value) => self.set_reg(rd, value as u32),
+                    None => return Err(self.overflow(lhs, rhs, '-')),
+                }
+            }
+            Op::Subu => self.set_reg(rd, self.regs[rs].wrapping_sub(self.regs[rt])),
+            Op::And => self.set_reg(rd, self.regs[rs] & self.regs[rt]),
+            Op::Or => self.set_reg(rd, self.regs[rs] | self.regs[rt]),
+            Op::Xor => self.set_reg(rd, self.regs[rs] ^ self.regs[rt]),
+            Op::Nor => self.set_reg(rd, !(self.regs[rs] | self.regs[rt])),
+            Op::Slt => self.set_reg(rd, ((self.regs[rs] as i32) < (self.regs[rt] as i32)) as u32),
+            Op::Sltu => self.set_reg(rd, (self.regs[rs] < self.regs[rt]) as u32),
+            Op::Tge => self.trap_if((self.regs[rs] as i32) >= (self.regs[rt] as i32))?,
+            Op::Tgeu => self.trap_if(self.regs[rs] >= self.regs[rt])?,
+            Op::Tlt => self.trap_if((self.regs[rs] as i32) < (self.regs[rt] as i32))?,
+            Op::Tltu => self.trap_if(self.regs[rs] < self.regs[rt])?,
+            Op::Teq => self.trap_if(self.regs[rs] == self.regs[rt])?,
+            Op::Tne => self.trap_if(self.regs[rs] != self.regs[rt])?,
+            // Multiply and add to, or subtract from, HI and LO taken as one
+            // 64-bit value
+            Op::Madd => self.set_hi_lo(self.hi_lo().wrapping_add(self.signed_product(rs, rt))),
+            Op::Maddu => self.set_hi_lo(self.hi_lo().wrapping_add(self.unsigned_product(rs, rt))),
+            Op::Msub => self.set_hi_lo(self.hi_lo().wrapping_sub(self.signed_product(rs, rt))),
+            Op::Msubu => self.set_hi_lo(self.hi_lo().wrapping_sub(self.unsigned_product(rs, rt))),
+            // The low word of the product. MIPS32 leaves HI and LO
+            // unpredictable afterwards; NAME leaves them as they were
+            Op::Mul => self.set_reg(rd, self.signed_product(rs, rt) as u32),
+            Op::Clz => self.set_reg(rd, self.regs[rs].leading_zeros()),
+            Op::Clo => self.set_reg(rd, self.regs[rs].leading_ones()),
+            Op::Bltz => self.branch_if((self.regs[rs] as i32) < 0, imm),
+            Op::Bgez => self.branch_if((self.regs[rs] as i32) >= 0, imm),
+            // The linking forms set $ra whether or not the branch is taken
+            Op::Bltzal => {
+                let taken = (self.regs[rs] as i32) < 0;
+                self.set_reg(Register::Ra, self.return_address());
+                self.branch_if(taken, imm);
+            }
+            Op::Bgezal => {
+                let taken = (self.regs[rs] as i32) >= 0;
+                self.set_reg(Register::Ra, self.return_address());
+                self.branch_if(taken, imm);
+            }
+            Op::Beq => self.branch_if(self.regs[rs] == self.regs[rt], imm),
+            Op::Bne => self.branch_if(self.regs[rs] != self.regs[rt], imm),
+            Op::Blez => self.branch_if((self.regs[rs] as i32) <= 0, imm),
+            Op::Bgtz => self.branch_if((self.regs[rs] as i32) > 0, imm),
+            // Jumps keep the top nybble of pc and replace the rest, so they
+            // can reach anywhere in the same 256MB
+            Op::J => self.branch_to(self.jump_target(word)),
+            Op::Jal => {
+                self.set_reg(Register::Ra, self.return_address());
+                self.branch_to(self.jump_target(word));
+            }
+            Op::Addi => {
+                let lhs = self.regs[rs];
+                match (lhs as i32).checked_add(simm as i32) {
+                    Some(value) => self.set_reg(rt, value as u32),
+                    None => return Err(self.overflow(lhs, simm, '+')),
                 }
             }
-            // Or
-            0x25 => {
-                self.regs[ins.rd] = self.regs[ins.rt] | self.regs[ins.rs];
+            // Despite the name the immediate is sign-extended, "unsigned"
+            // only means no overflow trap
+            Op::Addiu => self.set_reg(rt, self.regs[rs].wrapping_add(simm)),
+            Op::Slti => self.set_reg(rt, ((self.regs[rs] as i32) < (simm as i32)) as u32),
+            // The immediate is sign-extended, then compared unsigned
+            Op::Sltiu => self.set_reg(rt, (self.regs[rs] < simm) as u32),
+            Op::Andi => self.set_reg(rt, self.regs[rs] & imm as u32),
+            Op::Ori => self.set_reg(rt, self.regs[rs] | imm as u32),
+            Op::Xori => self.set_reg(rt, self.regs[rs] ^ imm as u32),
+            Op::Lui => self.set_reg(rt, (imm as u32) << 16),
+            // Loads zero-extend, and the signed forms then sign-extend
+            Op::Lb => {
+                let value = self.load(address, 1)?;
+                self.set_reg(rt, value as u8 as i8 as i32 as u32);
+            }
+            Op::Lh => {
+                Self::check_alignment(address, 2, false)?;
+                let value = self.load(address, 2)?;
+                self.set_reg(rt, value as u16 as i16 as i32 as u32);
+            }
+            // Load Linked is for atomic accesses across processors. NAME
+            // has one, so it is the same as lw
+            Op::Lw | Op::Ll => {
+                Self::check_alignment(address, 4, false)?;
+                let value = self.load(address, 4)?;
+                self.set_reg(rt, value);
+            }
+            Op::Lbu => {
+                let value = self.load(address, 1)?;
+                self.set_reg(rt, value);
+            }
+            Op::Lhu => {
+                Self::check_alignment(address, 2, false)?;
+                let value = self.load(address, 2)?;
+                self.set_reg(rt, value);
+            }
+            Op::Lwl => {
+                let shift = 24 - self.lane_shift(address, 1);
+                let word = self.load(address & !3, 4)?;
+                self.set_reg(rt, (word << shift) | (self.regs[rt] & ((1 << shift) - 1)));
+            }
+            Op::Lwr => {
+                let shift = self.lane_shift(address, 1);
+                let word = self.load(address & !3, 4)?;
+                self.set_reg(rt, (word >> shift) | (self.regs[rt] & !(u32::MAX >> shift)));
+            }
+            Op::Sb => self.store(address, self.regs[rt], 1)?,
+            Op::Sh => {
+                Self::check_alignment(address, 2, true)?;
+                self.store(address, self.regs[rt], 2)?;
+            }
+            Op::Sw => {
+                Self::check_alignment(address, 4, true)?;
+                self.store(address, self.regs[rt], 4)?;
+            }
+            Op::Swl => {
+                let shift = 24 - self.lane_shift(address, 1);
+                let word = self.load(address & !3, 4)?;
+                let merged = (word & !(u32::MAX >> shift)) | (self.regs[rt] >> shift);
+                self.store(address & !3, merged, 4)?;
+            }
+            Op::Swr => {
+                let shift = self.lane_shift(address, 1);
+                let word = self.load(address & !3, 4)?;
+                let merged = (word & ((1 << shift) - 1)) | (self.regs[rt] << shift);
+                self.store(address & !3, merged, 4)?;
+            }
+            // Store Conditional is the second half of Load Linked. With no
+            // other processor to interfere it always succeeds, so it stores
+            // the word and reports success by setting rt to 1
+            Op::Sc => {
+                Self::check_alignment(address, 4, true)?;
+                self.store(address, self.regs[rt], 4)?;
+                self.set_reg(rt, 1);
             }
-            // Xor
-            0x26 => {
-                self.regs[ins.rd] = self.regs[ins.rt] ^ self.regs[ins.rs];
+            // Floating-point loads and stores copy bits, with doubles split
+            // across a register pair as in double()
+            Op::Lwc1 => {
+                Self::check_alignment(address, 4, false)?;
+                let value = self.load(address, 4)?;
+                self.set_float(ft, f32::from_bits(value));
             }
-            // Nor
-            0x27 => {
-                self.regs[ins.rd] = !(self.regs[ins.rt] | self.regs[ins.rs]);
+            Op::Swc1 => {
+                Self::check_alignment(address, 4, true)?;
+                self.store(address, self.float(ft).to_bits(), 4)?;
             }
-            // Set Less Than
-            0x2A => { 
-                self.regs[ins.rd] = if (self.regs[ins.rs] as i32) < (self.regs[ins.rt] as i32) { 1 } else { 0 };
+            Op::Ldc1 => {
+                Self::check_even(&[ft], undefined)?;
+                Self::check_alignment(address, 8, false)?;
+                let (low, high) = self.double_words(address);
+                let bits = (self.load(high, 4)? as u64) << 32 | self.load(low, 4)? as u64;
+                self.set_double(ft, f64::from_bits(bits));
             }
-            // Set on Less Than Unsigned
-            0x2B => { 
-                self.regs[ins.rd] = if self.regs[ins.rs] < self.regs[ins.rt] { 1 } else { 0 };
+            Op::Sdc1 => {
+                Self::check_even(&[ft], undefined)?;
+                Self::check_alignment(address, 8, true)?;
+                let (low, high) = self.double_words(address);
+                let bits = self.double(ft).to_bits();
+                self.store(low, bits as u32, 4)?;
+                self.store(high, (bits >> 32) as u32, 4)?;
             }
-            _ => return Err(ExecutionErrors::UndefinedInstruction {instruction: opcode})
+            Op::Mfc1 => self.set_reg(rt, self.float(fs).to_bits()),
+            Op::Mtc1 => self.set_float(fs, f32::from_bits(self.regs[rt])),
+            // Branch on condition flag 0 true or false
+            Op::Bc1f => self.branch_if(!self.fp_condition, imm),
+            Op::Bc1t => self.branch_if(self.fp_condition, imm),
+            Op::AddS => self.set_float(fd, self.float(fs) + self.float(ft)),
+            Op::SubS => self.set_float(fd, self.float(fs) - self.float(ft)),
+            Op::MulS => self.set_float(fd, self.float(fs) * self.float(ft)),
+            Op::DivS => self.set_float(fd, self.float(fs) / self.float(ft)),
+            Op::SqrtS => self.set_float(fd, self.float(fs).sqrt()),
+            Op::AbsS => self.set_float(fd, self.float(fs).abs()),
+            Op::MovS => self.set_float(fd, self.float(fs)),
+            Op::NegS => self.set_float(fd, -self.float(fs)),
+            Op::CvtDS => {
+                Self::check_even(&[fd], undefined)?;
+                self.set_double(fd, self.float(fs) as f64);
+            }
+            // Rounds to nearest as the default rounding mode does. Values
+            // that don't fit, and NaN, produce the invalid operation result
+            // 2^31 - 1
+            Op::CvtWS => {
+                let value = self.float(fs).round_ties_even();
+                let word = if value >= i32::MIN as f32 && value < i32::MAX as f32 {
+                    value as i32
+                } else {
+                    i32::MAX
+                };
+                self.set_float(fd, f32::from_bits(word as u32));
+            }
+            // Comparisons with NaN are false
+            Op::CEqS => self.fp_condition = self.float(fs) == self.float(ft),
+            Op::CLtS => self.fp_condition = self.float(fs) < self.float(ft),
+            Op::CLeS => self.fp_condition = self.float(fs) <= self.float(ft),
+            Op::AddD | Op::SubD | Op::MulD | Op::DivD => {
+                Self::check_even(&[fd, fs, ft], undefined)?;
+                let (lhs, rhs) = (self.double(fs), self.double(ft));
+                let value = match op {
+                    Op::AddD => lhs + rhs,
+                    Op::SubD => lhs - rhs,
+                    Op::MulD => lhs * rhs,
+                    _ => lhs / rhs,
+                };
+                self.set_double(fd, value);
+            }
+            Op::MovD => {
+                Self::check_even(&[fd, fs], undefined)?;
+                self.set_double(fd, self.double(fs));
+            }
+            Op::CvtSD => {
+                Self::check_even(&[fs], undefined)?;
+                self.set_float(fd, self.double(fs) as f32);
+            }
+            // fs holds a two's complement integer
+            Op::CvtSW => {
+                let value = self.float(fs).to_bits() as i32;
+                self.set_float(fd, value as f32);
+            }
+            Op::CvtDW => {
+                Self::check_even(&[fd], undefined)?;
+                let value = self.float(fs).to_bits() as i32;
+                self.set_double(fd, value as f64);
+            }
+            Op::Mfc0 => self.set_reg(rt, self.read_cop0(rd)),
+            Op::Mtc0 => self.write_cop0(rd, self.regs[rt]),
+            Op::Eret => self.eret(),
         }
         Ok(())
     }
-    fn dispatch_i(&mut self, ins: Itype, opcode: u32) -> Result<(), ExecutionErrors> {
-
-        let memory_address = (ins.rt as i64 + (ins.imm as i64)) as u32;
-
-        match ins.opcode {
-            // Set on Less Than Immediate (signed)
-            // If rs is less than sign-extended 16 bit immediate using signed comparison, then set rt to 1
-            // Casting on imm is to sign extend. See load byte casts
-            0xA => { 
-                self.regs[ins.rt] = if (self.regs[ins.rs] as i32) < (ins.imm as i16 as i32) { 1 } else { 0 };
-            }
-            // Set on Less Than Immediate (unsigned)
-            // If rs is less than sign-extended 16-bit immediate using unsigned comparison, then set rt to 1
-            // casting is to sign extend again
-            0xB => { 
-                self.regs[ins.rt] = if self.regs[ins.rs] < (ins.imm as i16 as i32 as u32) { 1 } else { 0 };
-            }
-            // Or Immediate
-            0xD => {
-                // Rust zero-extends unsigned values when up-casting
-                self.regs[ins.rt] = self.regs[ins.rs] | ins.imm as u32;
-            }
-            // Load Upper Immediate
-            0xF => {
-                self.regs[ins.rt] = (ins.imm as u32) << 16;
-            }
-            // Load word (0x23) and Load Linked (0x30).
-            // A word on Load Linked-- This is an instruction for atomic accesses
-            // across SMP processors. NAME does not implement SMP, so this is equal to
-            // Load word.
-            0x23 | 0x30 =>{
-                self.regs[ins.rt] = self.read_w(memory_address)?;
-            }
-            // Load byte unsigned
-            // Note that "as u32" WILL zero extend
-            0x24 =>{
-                self.regs[ins.rt] = self.read_b(memory_address)? as u32;
-            }
-            // Load halfword unsigned
-            // Note that "as u32" WILL zero extend
-            0x25 => {
-                self.regs[ins.rt] = self.read_h(memory_address)? as u32;
-            }
-            // Load byte (signed)
-            // Note that I force a sign extension through a convuluted series of casts
-            // u8 -> i8 (same bits) -> i32 (more bits, sign extension) -> u32 (same bits)
-            0x20 => {
-                self.regs[ins.rt] = self.read_b(memory_address)? as i8 as i32 as u32;
-            }
-            // Load halfword (signed), same deal
-            0x21 => {
-                self.regs[ins.rt] = self.read_h(memory_address)? as i16 as i32 as u32;
-            }
-            // Store byte
-            0x28 => {
-                self.write_b(memory_address, self.regs[ins.rt] as u8)?;
-            }
-            // Store halfword
-            0x29 => {
-                self.write_h(memory_address, self.regs[ins.rt] as u16)?;
-            }
-            // Store word (0x2b) and Store Conditional (0x38).
-            // Store Conditional is the second half of Load Linked, and it's an equivalent
-            // op for the same reason.
-            0x2b | 0x38 => {
-                self.write_w(memory_address, self.regs[ins.rt])?;
-            }
-            // Branch if Equal
-            0x4 => {
-                if self.regs[ins.rt] == self.regs[ins.rs] {
-                    self.branch_delay_target = (ins.imm as u32) << 2;
-                    self.branch_delay_status = BranchDelays::Set;
+
+    fn branch_if(&mut self, taken: bool, imm: u16) {
+        if taken {
+            self.branch_to(self.branch_target(imm));
+        }
+    }
+
+    // The address a j or jal goes to: the top nybble of the delay slot's
+    // address, then the 26-bit target shifted left twice
+    fn jump_target(&self, word: u32) -> u32 {
+        (self.pc as u32 & 0xF0000000) | (Field::Target.get(word) << 2)
+    }
+
+    fn signed_product(&self, rs: usize, rt: usize) -> u64 {
+        ((self.regs[rs] as i32 as i64) * (self.regs[rt] as i32 as i64)) as u64
+    }
+
+    fn unsigned_product(&self, rs: usize, rt: usize) -> u64 {
+        (self.regs[rs] as u64) * (self.regs[rt] as u64)
+    }
+
+    // HI and LO as one 64-bit value, HI the upper half
+    fn hi_lo(&self) -> u64 {
+        (self.mult_hi as u64) << 32 | self.mult_lo as u64
+    }
+
+    fn set_hi_lo(&mut self, value: u64) {
+        self.mult_hi = (value >> 32) as u32;
+        self.mult_lo = value as u32;
+    }
+
+    // Double precision instructions name a pair of registers by the even
+    // one; naming an odd one is undefined
+    fn check_even(registers: &[usize], undefined: ExecutionErrors) -> Result<(), ExecutionErrors> {
+        match registers.iter().all(|register| register % 2 == 0) {
+            true => Ok(()),
+            false => Err(undefined),
+        }
+    }
+
+    // Where the low and high words of the double at `address` are, which
+    // depends on the byte order since the double is stored as one value
+    fn double_words(&self, address: u32) -> (u32, u32) {
+        match self.endian {
+            Endian::Little => (address, address + 4),
+            Endian::Big => (address + 4, address),
+        }
+    }
+
+    // Splits a word into fields with the same decoder the assembler's
+    // encoder is checked against, for what goes by the layout of a word
+    // rather than what it does: hypercalls, the execution log and the
+    // statistics. What it does comes from the ISA table, see execute
+    pub fn decode(instruction: u32) -> Instructions {
+        match encoding::decode(instruction) {
+            // User-defined instructions, which share the SPECIAL2 layout
+            // with madd, mul and the like
+            Instruction::R {
+                opcode: SPECIAL2_OPCODE,
+                rs,
+                rt,
+                rd,
+                shamt,
+                funct,
+            } if HYPERCALL_FUNCTS.contains(&funct) => Instructions::Hypercall(Rtype {
+                rs: rs as usize,
+                rt: rt as usize,
+                rd: rd as usize,
+                shamt,
+                funct,
+            }),
+            Instruction::R {
+                rs,
+                rt,
+                rd,
+                shamt,
+                funct,
+                ..
+            } => Instructions::R(Rtype {
+                rs: rs as usize,
+                rt: rt as usize,
+                rd: rd as usize,
+                shamt,
+                funct,
+            }),
+            Instruction::J { opcode, target } => Instructions::J(Jtype {
+                opcode: opcode.into(),
+                dest: target,
+            }),
+            // Coprocessors 0 and 1
+            Instruction::C {
+                opcode,
+                fmt,
+                ft,
+                fs,
+                fd,
+                funct,
+            } => {
+                let coprocessor = Ftype {
+                    fmt,
+                    ft: ft as usize,
+                    fs: fs as usize,
+                    fd: fd as usize,
+                    funct,
+                    imm: instruction as u16,
+                };
+                if opcode == COP0_OPCODE {
+                    Instructions::Cop0(coprocessor)
+                } else {
+                    Instructions::F(coprocessor)
                 }
             }
-            // Branch if Not Equal
-            0x5 => {
-                if self.regs[ins.rt] != self.regs[ins.rs] {
-                    self.branch_delay_target = (ins.imm as u32) << 2;
-                    self.branch_delay_status = BranchDelays::Set;
-                }
+            Instruction::I {
+                opcode,
+                rs,
+                rt,
+                imm,
+            } => Instructions::I(Itype {
+                opcode: opcode.into(),
+                rs: rs as usize,
+                rt: rt as usize,
+                imm,
+            }),
+        }
+    }
+
+    // Halfword and word accesses must be naturally aligned
+    fn check_alignment(address: u32, size: u32, store: bool) -> Result<(), ExecutionErrors> {
+        if address.is_multiple_of(size) {
+            Ok(())
+        } else {
+            Err(ExecutionErrors::MemoryUnalignedAccess {
+                load_address: address,
+                size,
+                store,
+            })
+        }
+    }
+
+    // Places an assembled .text section in memory and starts execution at `entry`.
+    // The program ends when it runs off the end of the section.
+    pub fn load_text(&mut self, text: &[u8], entry: u32) -> Result<(), ExecutionErrors> {
+        self.load_text_at(DOT_TEXT_START_ADDRESS, text, entry)
+    }
+
+    // As load_text, for a program assembled with .text somewhere other than
+    // the usual address. The section's region replaces the usual one and is
+    // put ahead of the heap and stack, as with load_elf.
+    pub fn load_text_at(
+        &mut self,
+        address: u32,
+        text: &[u8],
+        entry: u32,
+    ) -> Result<(), ExecutionErrors> {
+        if text.len() > DOT_TEXT_MAX_LENGTH as usize {
+            return Err(ExecutionErrors::ProgramTooLarge {
+                length: text.len() as u32,
+            });
+        }
+
+        match self.memory.region_mut(address) {
+            Some(region) => region.length = text.len() as u32,
+            None => {
+                self.memory
+                    .regions
+                    .retain(|region| region.base != DOT_TEXT_START_ADDRESS);
+                self.memory.regions.insert(
+                    0,
+                    Region {
+                        base: address,
+                        length: text.len() as u32,
+                        max_length: DOT_TEXT_MAX_LENGTH,
+                        executable: true,
+                    },
+                );
             }
-            
+        }
+        self.memory.set_bytes(address, text);
+        self.stop_address = address as usize + text.len();
+        self.set_pc(entry);
+
+        Ok(())
+    }
 
-            _ => return Err(ExecutionErrors::UndefinedInstruction {instruction: opcode})
+    // Places an assembled .data section in memory byte for byte. It is
+    // already in the machine's byte order.
+    pub fn load_data(&mut self, data: &[u8]) -> Result<(), ExecutionErrors> {
+        self.load_data_at(DOT_DATA_START_ADDRESS, data)
+    }
+
+    // As load_data, for a program assembled with .data somewhere other than
+    // the usual address
+    pub fn load_data_at(&mut self, address: u32, data: &[u8]) -> Result<(), ExecutionErrors> {
+        if data.len() > DOT_DATA_MAX_LENGTH as usize {
+            return Err(ExecutionErrors::ProgramTooLarge {
+                length: data.len() as u32,
+            });
+        }
+
+        match self.memory.region_mut(address) {
+            Some(region) => region.length = data.len() as u32,
+            None => self.memory.regions.insert(
+                0,
+                Region {
+                    base: address,
+                    length: data.len() as u32,
+                    max_length: DOT_DATA_MAX_LENGTH,
+                    executable: false,
+                },
+            ),
         }
+        self.memory.set_bytes(address, data);
+
         Ok(())
     }
-    fn dispatch_j(&mut self, ins: Jtype, opcode: u32) -> Result<(), ExecutionErrors> {
-        // This instruction type takes the top nybble of PC and combines it with
-        // a 28-bit range (26 bits as encoded shifted left twice.)
-        // Thus, you can jump to anywhere in a 256MB address space.
-        match ins.opcode {
-            // Jump absolute
-            2 => {
-                self.branch_delay_status = BranchDelays::Set;
-                self.branch_delay_target = self.pc as u32 & 0xF0000000 | (ins.dest << 2);
-            }
-            // Jump And Link
-            3 => {
-                self.branch_delay_status = BranchDelays::Set;
-                self.branch_delay_target = self.pc as u32 & 0xF0000000 | (ins.dest << 2);
-                // $ra = register 31
-                self.regs[31] = self.pc as u32 + 8;
-            }
-            _ => return Err(ExecutionErrors::UndefinedInstruction {instruction: opcode})
+
+    // Maps the loadable segments of an ELF executable and starts execution at
+    // its entry point. The executable's own byte order replaces `endian`.
+    // As with load_text, the program ends when it runs off the end of the
+    // segment it starts in.
+    pub fn load_elf(&mut self, elf: &ElfExecutable) -> Result<(), ExecutionErrors> {
+        self.endian = elf.endian;
+
+        // The executable decides where its code goes. Its segments are put
+        // ahead of the heap and stack so they take precedence where they overlap.
+        self.memory
+            .regions
+            .retain(|region| region.base != DOT_TEXT_START_ADDRESS);
+        for (i, segment) in elf.segments.iter().enumerate() {
+            self.memory.regions.insert(
+                i,
+                Region {
+                    base: segment.address,
+                    length: segment.mem_size,
+                    max_length: segment.mem_size,
+                    executable: segment.executable,
+                },
+            );
+            self.memory.set_bytes(segment.address, &segment.data);
         }
 
+        let entry_segment = elf
+            .segments
+            .iter()
+            .find(|segment| {
+                segment.executable && elf.entry.wrapping_sub(segment.address) < segment.mem_size
+            })
+            .ok_or(ExecutionErrors::MemoryIllegalAccess {
+                load_address: elf.entry,
+            })?;
+        self.stop_address = (entry_segment.address + entry_segment.mem_size) as usize;
+        self.set_pc(elf.entry);
+
         Ok(())
     }
 
-    fn decode(&self, instruction: u32) -> Instructions {
-        let opcode = instruction >> 26 & 0b111111;
-        match opcode {
-            // R-type
-            0 => {
-                Instructions::R(Rtype {
-                    // These are all five-bit fields
-                    rs: (instruction >> 21 & 0b11111) as usize,
-                    rt: (instruction >> 16 & 0b11111) as usize,
-                    rd: (instruction >> 11 & 0b11111) as usize,
-                    shamt: (instruction >> 6 & 0b11111) as u8,
-                    // This is a six-bit field
-                    funct: (instruction & 0b111111) as u8
-                })
-            }
-            // J-type
-            0x2 | 0x3 => {
-                Instructions::J(Jtype {
-                    opcode,
-                    // Lower 26 bits of the instruction
-                    dest: instruction & 0b11111111111111111111111111
-                })
-            }
-            // I-type
-            _ => {
-                Instructions::I(Itype {
-                    opcode,
-                    rs: (instruction >> 21 & 0b11111) as usize,
-                    rt: (instruction >> 16 & 0b11111) as usize,
-                    imm: instruction as u16
-                })
-            }
-        }
-    }
-
-    // Given an address, return a pool of actual memory and the offset with
-    // which to access the requested data within it. Note that the offset 
-    // address is not necessarily allocated within the returned Vec, 
-    // this function just checks ranges.
-    fn map_memory(&mut self, address: u32) -> Option<(&mut Vec<u8>, u32)> {
-        // Access by the various pools of memory that exist.
-        // Note that if an address is supposedly within a region,
-        // but that region hasn't been initialized, it won't be within
-        // the Vecs size and therefore won't be addressed.
-        for (pool, base_address, max_length) in &mut self.memories {
-            if (*base_address .. *base_address + *max_length).contains(&address) {
-                return Some((pool, address - *base_address))
-            }
-        }
-        None
+    // The whole state of the machine, memory included, in the serializable
+    // form shared with other tools
+    pub fn snapshot(&self) -> MachineState {
+        let (regions, pages) = self.memory.image();
+        MachineState {
+            memory: Some(MemoryImage {
+                regions,
+                pages,
+                stack_low: self.stack_low,
+            }),
+            ..self.register_state()
+        }
     }
 
-    // This function attempts to access a byte of memory and returns an error if that memory doesn't exist
-    pub fn read_b(&mut self, address: u32) -> Result<u8, ExecutionErrors> {
-        if let Some((memory, offset)) = self.map_memory(address) {
-            if let Some(value) = memory.get(offset as usize) {
-                Ok(*value)
+    // The registers and coprocessor 0 without memory, which is cheap
+    // enough to take every step
+    pub fn register_state(&self) -> MachineState {
+        MachineState {
+            pc: self.pc(),
+            regs: self.regs,
+            floats: self.floats,
+            fp_condition: self.fp_condition,
+            hi: self.mult_hi,
+            lo: self.mult_lo,
+            epc: self.epc,
+            cause: self.cause,
+            bad_vaddr: self.bad_vaddr,
+            status: self.status,
+            count: self.count,
+            compare: self.compare,
+            exit_code: self.exit_code,
+            memory: None,
+        }
+    }
+
+    // Puts back what snapshot took. A snapshot without memory leaves memory
+    // as it is. Execution carries on from the snapshot's pc, outside any
+    // delay slot
+    pub fn restore(&mut self, state: &MachineState) {
+        self.set_pc(state.pc);
+        self.branch_delay_status = BranchDelays::NotActive;
+        self.regs = state.regs;
+        self.floats = state.floats;
+        self.fp_condition = state.fp_condition;
+        self.mult_hi = state.hi;
+        self.mult_lo = state.lo;
+        self.epc = state.epc;
+        self.cause = state.cause;
+        self.bad_vaddr = state.bad_vaddr;
+        self.status = state.status;
+        self.count = state.count;
+        self.compare = state.compare;
+        self.exit_code = state.exit_code;
+        if let Some(memory) = &state.memory {
+            self.memory.restore_image(&memory.regions, &memory.pages);
+            self.stack_low = memory.stack_low;
+        }
+    }
+
+    // The register file laid out four to a line, followed by pc, hi and lo
+    pub fn format_registers(&self) -> String {
+        let mut out = String::new();
+        for (i, value) in self.regs.iter().enumerate() {
+            out.push_str(&format!("{:>5} = 0x{:08x}", Register::ALL[i].name(), value));
+            out.push_str(if i % 4 == 3 { "\n" } else { "   " });
+        }
+        out.push_str(&format!(
+            "{:>5} = 0x{:08x}      hi = 0x{:08x}      lo = 0x{:08x}",
+            PC_NAME, self.pc, self.mult_hi, self.mult_lo
+        ));
+        out
+    }
+
+    // The floating-point registers four to a line, followed by the condition flag
+    pub fn format_floats(&self) -> String {
+        let mut out = String::new();
+        for (i, value) in self.floats.iter().enumerate() {
+            // Keep tiny and huge values to the column width
+            let mut text = value.to_string();
+            if text.len() > 14 {
+                text = format!("{:e}", value);
             }
-            // Although this memory access was technically within this range,
-            // the Vec did not actually fit within it. This means that the user
-            // wrote out of bounds of the buffer
-            else {
-                Err(ExecutionErrors::MemoryObviousOverrunAccess { load_address: address } )
+            out.push_str(&format!("{:>5} = {:<14}", format!("$f{}", i), text));
+            out.push_str(if i % 4 == 3 { "\n" } else { "   " });
+        }
+        out.push_str(&format!("   cc = {}", self.fp_condition as u8));
+        out
+    }
+
+    // A line of hit and miss counts for each cache being modelled, or
+    // nothing if there are none
+    pub fn format_caches(&self) -> String {
+        let lines: Vec<String> = [("icache", &self.icache), ("dcache", &self.dcache)]
+            .into_iter()
+            .filter_map(|(name, cache)| cache.as_ref().map(|cache| cache.format(name)))
+            .collect();
+        lines.join("\n")
+    }
+
+    // The end of the heap, one past the last byte sbrk or brk has handed out
+    pub fn heap_break(&self) -> u32 {
+        self.memory
+            .regions
+            .iter()
+            .find(|region| region.base == HEAP_START_ADDRESS)
+            .map_or(HEAP_START_ADDRESS, |heap| heap.base + heap.length)
+    }
+
+    // Grows the heap by the given number of bytes, returning the address of
+    // the newly allocated block. Allocations are kept word-aligned.
+    pub fn sbrk(&mut self, bytes: u32) -> Result<u32, ExecutionErrors> {
+        let stack_low = self.stack_low;
+        let Some(heap) = self.memory.region_mut(HEAP_START_ADDRESS) else {
+            return Err(ExecutionErrors::HeapExhausted { requested: bytes });
+        };
+
+        // Memory past the break is never written, so the new block reads as zeros
+        let old_break = heap.base + heap.length;
+        let aligned = bytes.checked_add(3).map(|b| b & !3);
+        match aligned {
+            Some(aligned) if heap.length as u64 + aligned as u64 <= heap.max_length as u64 => {
+                let heap_break = old_break + aligned;
+                if heap_break > stack_low {
+                    return Err(ExecutionErrors::HeapStackCollision {
+                        heap_break,
+                        stack_low,
+                    });
+                }
+                heap.length += aligned;
+                Ok(old_break)
             }
+            _ => Err(ExecutionErrors::HeapExhausted { requested: bytes }),
+        }
+    }
+
+    // Follows $sp down as the stack grows. Once it drops below the heap
+    // break, pushes would overwrite heap allocations, so execution stops.
+    fn check_stack(&mut self) -> Result<(), ExecutionErrors> {
+        let stack_low = self.stack_low.min(self.reg(Register::Sp));
+        let heap_break = self.heap_break();
+        if stack_low < heap_break {
+            return Err(ExecutionErrors::HeapStackCollision {
+                heap_break,
+                stack_low,
+            });
+        }
+        self.stack_low = stack_low;
+        Ok(())
+    }
+
+    // This function attempts to access a byte of memory and returns an error if that memory doesn't exist
+    pub fn read_b(&self, address: u32) -> Result<u8, ExecutionErrors> {
+        let byte = self.peek_b(address)?;
+        if self.record_reads {
+            self.memory_reads.borrow_mut().push(address);
+        }
+        Ok(byte)
+    }
+    // Reads a byte without recording it, as instruction fetches do
+    fn peek_b(&self, address: u32) -> Result<u8, ExecutionErrors> {
+        match self.memory.access(address) {
+            Access::Mapped => Ok(self.memory.get(address)),
+            // Although this memory access was technically within a region,
+            // it was past the part in use. This means that the user read
+            // out of bounds of the buffer
+            Access::Overrun => Err(ExecutionErrors::MemoryObviousOverrunAccess {
+                load_address: address,
+            }),
+            Access::Unmapped => Err(ExecutionErrors::MemoryIllegalAccess {
+                load_address: address,
+            }),
         }
-        else { Err(ExecutionErrors::MemoryIllegalAccess { load_address: address } ) }
     }
     // Reads two bytes and returns a halfword
-    pub fn read_h(&mut self, address: u32) -> Result<u16, ExecutionErrors> {
+    pub fn read_h(&self, address: u32) -> Result<u16, ExecutionErrors> {
         let bytes = [self.read_b(address)?, self.read_b(address + 1)?];
-        Ok(Cursor::new(bytes).read_u16::<LittleEndian>().unwrap())
+        Ok(self.endian.u16_from_bytes(bytes))
     }
     // Reads four bytes and returns a word
-    pub fn read_w(&mut self, address: u32) -> Result<u32, ExecutionErrors> {
-        let bytes = [self.read_b(address)?, self.read_b(address + 1)?,
-                        self.read_b(address + 2)?, self.read_b(address + 3)?];
-        Ok(Cursor::new(bytes).read_u32::<LittleEndian>().unwrap())
+    pub fn read_w(&self, address: u32) -> Result<u32, ExecutionErrors> {
+        let bytes = [
+            self.read_b(address)?,
+            self.read_b(address + 1)?,
+            self.read_b(address + 2)?,
+            self.read_b(address + 3)?,
+        ];
+        Ok(self.endian.u32_from_bytes(bytes))
+    }
+    // Reads the instruction at an address, which has to be aligned and in
+    // memory
+    fn fetch(&self, address: u32) -> Result<u32, ExecutionErrors> {
+        let illegal = ExecutionErrors::IllegalFetch { address };
+        if !address.is_multiple_of(MIPS_INSTRUCTION_LENGTH as u32) {
+            return Err(illegal);
+        }
+        let mut bytes = [0; 4];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.peek_b(address + i as u32).map_err(|_| illegal)?;
+        }
+        Ok(self.endian.u32_from_bytes(bytes))
     }
 
-    
     // Writes one byte
     pub fn write_b(&mut self, address: u32, value: u8) -> Result<(), ExecutionErrors> {
-        if let Some((memory, offset)) = self.map_memory(address) {
-            if let Some(element) = memory.get_mut(offset as usize) {
-                *element = value;
+        match self.memory.access(address) {
+            Access::Mapped => {
+                if self.record_writes {
+                    let old = self.memory.get(address);
+                    self.memory_writes.push(ByteWrite {
+                        address,
+                        old,
+                        new: value,
+                    });
+                }
+                self.memory.set(address, value);
                 Ok(())
             }
-            else {
-                Err(ExecutionErrors::MemoryObviousOverrunAccess { load_address: address }
-                )
-            }
+            Access::Overrun => Err(ExecutionErrors::MemoryObviousOverrunAccess {
+                load_address: address,
+            }),
+            Access::Unmapped => Err(ExecutionErrors::MemoryIllegalAccess {
+                load_address: address,
+            }),
         }
-        else { Err(ExecutionErrors::MemoryIllegalAccess { load_address: address } ) }
     }
-    // Writes a halfword in little endian form
+    // Writes a halfword in the machine's byte order
     pub fn write_h(&mut self, address: u32, value: u16) -> Result<(), ExecutionErrors> {
-        let mut bytes = vec![];
-        bytes.write_u16::<LittleEndian>(value).unwrap();
-        self.write_b(address, bytes[0])?;
-        self.write_b(address, bytes[1])?;
+        for (i, byte) in self.endian.u16_to_bytes(value).into_iter().enumerate() {
+            self.write_b(address + i as u32, byte)?;
+        }
         Ok(())
     }
-    // Writes a word in little endian form
+    // Writes a word in the machine's byte order
     pub fn write_w(&mut self, address: u32, value: u32) -> Result<(), ExecutionErrors> {
-        let mut bytes = vec![];
-        bytes.write_u32::<LittleEndian>(value).unwrap();
-        self.write_b(address, bytes[0])?;
-        self.write_b(address, bytes[1])?;
-        self.write_b(address, bytes[2])?;
-        self.write_b(address, bytes[3])?;
+        for (i, byte) in self.endian.u32_to_bytes(value).into_iter().enumerate() {
+            self.write_b(address + i as u32, byte)?;
+        }
         Ok(())
     }
 
-    pub fn step_one(&mut self, mut f :&mut File) -> Result<(), ExecutionErrors> {
-        let opcode = self.read_w(self.pc as u32)?;
-        self.pc += MIPS_INSTRUCTION_LENGTH;
+    // Executes a single instruction, remembering the outcome in prev_ins_result.
+    // Each decoded instruction is logged to `f`; pass io::sink() to skip that
+    pub fn step_one(&mut self, f: &mut dyn Write) -> Result<(), ExecutionErrors> {
+        let result = self.step(f);
+        self.prev_ins_result = result;
+        result
+    }
+
+    fn step(&mut self, f: &mut dyn Write) -> Result<(), ExecutionErrors> {
+        // Falling off the end of .text (or jumping exactly there) ends the program
+        if self.pc == self.stop_address || self.exit_code.is_some() {
+            return Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            });
+        }
+
+        if let Err(error) = self.run_hooks() {
+            if self.trap(&error) {
+                return Ok(());
+            }
+            return Err(error);
+        }
+        self.take_interrupt();
 
-        if self.pc == self.stop_address {
-            return Err(ExecutionErrors::Event { event: ExecutionEvents::ProgramComplete });
+        let opcode = match self.fetch(self.pc as u32) {
+            Ok(opcode) => opcode,
+            Err(error) => {
+                if self.trap(&error) {
+                    return Ok(());
+                }
+                return Err(error);
+            }
+        };
+        if let Some(icache) = &mut self.icache {
+            icache.access(self.pc as u32);
         }
+        self.pc += MIPS_INSTRUCTION_LENGTH;
 
-        let instruction = self.decode(opcode);
-        writeln!(f,"{:?}", instruction);
+        let instruction = Self::decode(opcode);
+        let _ = writeln!(f, "{:?}", instruction);
 
-        let ins_result = match instruction {
-            Instructions::R(rtype) => self.dispatch_r(rtype, opcode),
-            Instructions::I(itype) => self.dispatch_i(itype, opcode),
-            Instructions::J(jtype) => self.dispatch_j(jtype, opcode)
+        // Loads and stores may overwrite their base register, so work out
+        // the address they access for the statistics up front
+        let accessed = match &instruction {
+            Instructions::I(itype) => {
+                Some(self.regs[itype.rs].wrapping_add(itype.imm as i16 as u32))
+            }
+            _ => None,
         };
+        self.transferred = false;
+        let regs = self.regs;
 
-        // The zero register is ALWAYS 0.
-        // If an instruction wrote to the zero register, discard that result here.
-        self.regs[0] = 0;
+        let ins_result = match (instruction, identify(opcode)) {
+            (Instructions::Hypercall(rtype), _) => self.dispatch_hypercall(rtype, opcode),
+            (_, Some(info)) => self.execute(info.op, opcode),
+            (_, None) => Err(ExecutionErrors::UndefinedInstruction {
+                instruction: opcode,
+            }),
+        }
+        .and_then(|()| {
+            // The instruction that takes the stack into the heap is undone,
+            // so running it again after the error does not apply it twice
+            self.check_stack().inspect_err(|_| self.regs = regs)
+        });
 
-        if ins_result.is_err() {
-            self.pc -= MIPS_INSTRUCTION_LENGTH; // 
+        if let Err(error) = ins_result {
+            self.pc -= MIPS_INSTRUCTION_LENGTH;
+            if self.trap(&error) {
+                return Ok(());
+            }
+            return ins_result;
+        }
+        self.stats
+            .record(opcode, accessed, self.transferred, self.delay_slots);
+        self.tick_timer();
+        if let (Some(dcache), Some(address)) = (&mut self.dcache, accessed) {
+            if matches!(stats::category(opcode), Category::Load | Category::Store) {
+                dcache.access(address);
+            }
         }
 
         // Branch delay slots are handled here. On the instruction the branch is set,
@@ -464,8 +1346,14 @@ impl Mips {
             }
         }
 
-        self.prev_ins_result = ins_result;
+        // Report completion as soon as the last instruction has run so
+        // frontends never stop on an address past the end of the program
+        if self.pc == self.stop_address || self.exit_code.is_some() {
+            return Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            });
+        }
 
         ins_result
     }
-}
\ No newline at end of file
+}
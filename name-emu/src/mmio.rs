@@ -0,0 +1,180 @@
+use std::fmt::Debug;
+
+use name_core::endian::Endian;
+
+use crate::exception::ExecutionErrors;
+use crate::mips::Mips;
+
+// Memory-mapped devices: loads and stores to a device's range of addresses
+// go to the device instead of memory, so programs can do I/O by polling
+// registers the way bare-metal code does rather than through syscalls.
+// Devices work in words. A load of a byte or halfword reads the word
+// holding it, and a store of one writes the word holding it with the other
+// bytes zero, in the machine's byte order.
+//
+// Like execution hooks, a device has the whole machine in hand while it
+// handles an access, for the console or anything else it stands in front
+// of. Only the program's own loads and stores reach a device: the debugger,
+// watches and traces read its range as unmapped, so looking at a device
+// never disturbs it.
+pub trait MmioDevice: Debug {
+    // The word at `offset` bytes into the device's range, a multiple of 4
+    fn read(&mut self, offset: u32, mips: &mut Mips) -> u32;
+    fn write(&mut self, offset: u32, value: u32, mips: &mut Mips);
+    // What the device is, for `name map`
+    fn name(&self) -> &str {
+        "device"
+    }
+}
+
+#[derive(Debug)]
+pub struct MappedDevice {
+    pub base: u32,
+    pub length: u32,
+    pub device: Box<dyn MmioDevice>,
+}
+
+// The devices mapped into a machine's address space
+pub type Devices = Vec<MappedDevice>;
+
+impl Mips {
+    // Maps `device` at the `length` bytes from `base`, in front of any
+    // memory there
+    pub fn map_device(&mut self, base: u32, length: u32, device: Box<dyn MmioDevice>) {
+        self.devices.push(MappedDevice {
+            base,
+            length,
+            device,
+        });
+    }
+
+    fn device_at(&self, address: u32) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|mapped| address.wrapping_sub(mapped.base) < mapped.length)
+    }
+
+    // Where the `size` bytes at `address` sit in the word holding them, as
+    // a shift from the least significant bit
+    pub(crate) fn lane_shift(&self, address: u32, size: u32) -> u32 {
+        let byte = address % 4;
+        match self.endian {
+            Endian::Little => 8 * byte,
+            Endian::Big => 8 * (4 - size - byte),
+        }
+    }
+
+    // Runs `access` on the device at `index` for an access to `address`,
+    // with the machine in hand. The devices are taken out while it runs so
+    // it can borrow the machine mutably, and any it maps are kept after them
+    fn with_device<T>(
+        &mut self,
+        index: usize,
+        address: u32,
+        access: impl FnOnce(&mut dyn MmioDevice, u32, &mut Mips) -> T,
+    ) -> T {
+        let mut devices = std::mem::take(&mut self.devices);
+        let mapped = &mut devices[index];
+        let offset = (address - mapped.base) & !3;
+        let result = access(mapped.device.as_mut(), offset, self);
+        devices.append(&mut self.devices);
+        self.devices = devices;
+        result
+    }
+
+    // A load of `size` bytes by the program, zero-extended
+    pub(crate) fn load(&mut self, address: u32, size: u32) -> Result<u32, ExecutionErrors> {
+        if let Some(index) = self.device_at(address) {
+            let word = self.with_device(index, address, |device, offset, mips| {
+                device.read(offset, mips)
+            });
+            let mask = if size == 4 {
+                u32::MAX
+            } else {
+                (1 << (8 * size)) - 1
+            };
+            return Ok((word >> self.lane_shift(address, size)) & mask);
+        }
+        match size {
+            1 => self.read_b(address).map(u32::from),
+            2 => self.read_h(address).map(u32::from),
+            _ => self.read_w(address),
+        }
+    }
+
+    // A store of the low `size` bytes of `value` by the program
+    pub(crate) fn store(
+        &mut self,
+        address: u32,
+        value: u32,
+        size: u32,
+    ) -> Result<(), ExecutionErrors> {
+        if let Some(index) = self.device_at(address) {
+            let word = value << self.lane_shift(address, size);
+            self.with_device(index, address, |device, offset, mips| {
+                device.write(offset, word, mips)
+            });
+            return Ok(());
+        }
+        match size {
+            1 => self.write_b(address, value as u8),
+            2 => self.write_h(address, value as u16),
+            _ => self.write_w(address, value),
+        }
+    }
+}
+
+// Where MARS puts its keyboard and display, which every machine has mapped,
+// and its registers as offsets (see name_core::memmap)
+pub use name_core::memmap::{KEYBOARD_DISPLAY_ADDRESS, KEYBOARD_DISPLAY_LENGTH};
+use name_core::memmap::{RECEIVER_CONTROL, RECEIVER_DATA, TRANSMITTER_CONTROL, TRANSMITTER_DATA};
+
+// MARS's Keyboard and Display MMIO Simulator, on the console. Bit 0 of the
+// receiver control word is set while a key is waiting in the receiver data
+// word, and reading the data clears it. The display is always ready, so bit
+// 0 of the transmitter control word is always set, and a byte stored to the
+// transmitter data word is printed straight away.
+//
+// Keys come from the console: polling the receiver control word while no
+// key is waiting reads a character, which on a terminal waits for a line to
+// be typed. Interrupts are not raised, so the interrupt enable bits read
+// back as 0.
+#[derive(Debug, Default)]
+pub struct KeyboardDisplay {
+    // The last key read from the console, and whether the program has yet
+    // to read it
+    key: Option<char>,
+    ready: bool,
+}
+
+impl MmioDevice for KeyboardDisplay {
+    fn read(&mut self, offset: u32, mips: &mut Mips) -> u32 {
+        match offset {
+            RECEIVER_CONTROL => {
+                if !self.ready {
+                    if let Some(key) = mips.console.read_char() {
+                        self.key = Some(key);
+                        self.ready = true;
+                    }
+                }
+                self.ready as u32
+            }
+            RECEIVER_DATA => {
+                self.ready = false;
+                self.key.map_or(0, u32::from)
+            }
+            TRANSMITTER_CONTROL => 1,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u32, mips: &mut Mips) {
+        if offset == TRANSMITTER_DATA {
+            mips.print(&char::from(value as u8).to_string());
+        }
+    }
+
+    fn name(&self) -> &str {
+        "keyboard and display"
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use name_as::nma::{
+    assemble_source, AssembledObject, AssemblerOptions, DATA_SECTION, TEXT_SECTION,
+};
+use name_core::endian::Endian;
+use name_core::lineinfo::LineInfo;
+use name_emu::disasm::disassemble;
+
+use crate::{DynResult, Options};
+
+const USAGE: &str =
+    "USAGE: name verify [source file] --against [reference file] [--data] [--endian big|little]";
+
+// `name verify prog.asm --against ref.bin`: assembles a program and
+// compares what it assembles to, word by word, with the output of another
+// assembler such as MARS, listing each address where they differ with both
+// words disassembled and the line of source it came from. For checking the
+// encoder against a known good one, and for grading exercises where the
+// answer is the words a program assembles to.
+//
+// The reference is either raw binary, in the byte order given by --endian,
+// or text with one hex word per line, as MARS dumps memory with "Hexadecimal
+// Text". It holds `.text`, or `.data` with --data. MARS dumps whole pages of
+// `.data`, so zero words after the end of the program are not counted.
+
+// The words of a reference file
+fn read_reference(bytes: &[u8], endian: Endian) -> Result<Vec<u32>, String> {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let words: Option<Vec<u32>> = lines
+            .iter()
+            .map(|line| u32::from_str_radix(line.trim_start_matches("0x"), 16).ok())
+            .collect();
+        if let Some(words) = words.filter(|_| !lines.is_empty()) {
+            return Ok(words);
+        }
+    }
+    if !bytes.len().is_multiple_of(4) {
+        return Err(format!(
+            "{} bytes is not a whole number of words, nor one hex word per line",
+            bytes.len()
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|word| endian.u32_from_bytes([word[0], word[1], word[2], word[3]]))
+        .collect())
+}
+
+// The words of one section of the assembled program, and where it starts
+fn section_words(assembled: &AssembledObject, name: &str) -> (u32, Vec<u32>) {
+    let Some(section) = assembled.section(name) else {
+        return (assembled.data_address(), vec![]);
+    };
+    let mut bytes = section.data.clone();
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+    let words = bytes
+        .chunks_exact(4)
+        .map(|word| {
+            assembled
+                .endian
+                .u32_from_bytes([word[0], word[1], word[2], word[3]])
+        })
+        .collect();
+    (section.address, words)
+}
+
+fn describe(word: Option<u32>, address: u32, text: bool) -> String {
+    match word {
+        None => "(nothing)".to_string(),
+        Some(word) if text => format!("0x{:08x}  {}", word, disassemble(word, address)),
+        Some(word) => format!("0x{:08x}", word),
+    }
+}
+
+pub fn verify_main(args: &[String], options: &Options) -> DynResult<()> {
+    let mut source_fn = None;
+    let mut reference_fn = None;
+    let mut data = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--against" => reference_fn = Some(args.next().ok_or(USAGE)?),
+            "--data" => data = true,
+            _ if source_fn.is_none() => source_fn = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let (Some(source_fn), Some(reference_fn)) = (source_fn, reference_fn) else {
+        return Err(USAGE.into());
+    };
+
+    let source = std::fs::read_to_string(source_fn)
+        .map_err(|why| format!("Failed to open {}. Reason: {}", source_fn, why))?;
+    let assembler_options = AssemblerOptions {
+        file_name: source_fn.to_string(),
+        endian: options.endian,
+        entry: options.entry.clone(),
+        ..Default::default()
+    };
+    let assembled = assemble_source(&source, &assembler_options)?;
+    let reference = std::fs::read(reference_fn)
+        .map_err(|why| format!("Failed to open {}. Reason: {}", reference_fn, why))?;
+    let mut expected = read_reference(&reference, options.endian)
+        .map_err(|why| format!("{}: {}", reference_fn, why))?;
+
+    let section = if data { DATA_SECTION } else { TEXT_SECTION };
+    let (start, words) = section_words(&assembled, section);
+    while expected.len() > words.len() && expected.last() == Some(&0) {
+        expected.pop();
+    }
+
+    let lines: BTreeMap<u32, &LineInfo> = assembled
+        .lineinfo
+        .lines
+        .iter()
+        .map(|line| (line.instr_addr, line))
+        .collect();
+    let mut differing = 0;
+    for index in 0..words.len().max(expected.len()) {
+        let address = start + 4 * index as u32;
+        let (name, reference) = (words.get(index).copied(), expected.get(index).copied());
+        if name == reference {
+            continue;
+        }
+        differing += 1;
+        let place = lines
+            .get(&address)
+            .map(|line| format!("  {}:{}", source_fn, line.span.line))
+            .unwrap_or_default();
+        println!("0x{:08x}{}", address, place);
+        println!("    NAME       {}", describe(name, address, !data));
+        println!("    reference  {}", describe(reference, address, !data));
+    }
+
+    if differing == 0 {
+        println!(
+            "{} matches {}: {} words of {}",
+            source_fn,
+            reference_fn,
+            words.len(),
+            section
+        );
+        return Ok(());
+    }
+
+    // A reference in the other byte order differs in every word
+    let swapped: Vec<u32> = expected.iter().map(|word| word.swap_bytes()).collect();
+    if !words.is_empty() && swapped == words {
+        println!();
+        println!("{} matches once its bytes are swapped; it looks like it was written in the other byte order, see --endian", reference_fn);
+    }
+    Err(format!(
+        "{} of {} words of {} differ from {}",
+        differing,
+        words.len().max(expected.len()),
+        section,
+        reference_fn
+    )
+    .into())
+}
@@ -0,0 +1,186 @@
+use name_core::isa::identify;
+
+use crate::mips::{
+    Instructions, Mips, DOT_DATA_START_ADDRESS, DOT_TEXT_START_ADDRESS, FMT_MT, HEAP_START_ADDRESS,
+    STACK_END_ADDRESS, STACK_START_ADDRESS,
+};
+
+// Counts of what a program has executed, kept up to date by Mips::step so
+// students can reason about what their program costs. Only instructions
+// that complete are counted; one that raises an exception is not.
+//
+// The cycle estimate assumes the classic five stage pipeline with
+// forwarding: every instruction takes a cycle, plus a stall when an
+// instruction uses the result of the load right before it, plus a flushed
+// fetch after each taken branch or jump unless delay slots are emulated.
+// Stalls injected by --jitter are added on top (see jitter.rs).
+
+// What each instruction is counted as comes from the shared ISA table
+pub use name_core::isa::Class as Category;
+
+// Hypercalls and anything that is not in the table count as other
+pub fn category(word: u32) -> Category {
+    match Mips::decode(word) {
+        Instructions::Hypercall(_) => Category::Other,
+        _ => identify(word).map_or(Category::Other, |info| info.class),
+    }
+}
+
+// The part of the address space a load or store touched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Area {
+    Text,
+    Data,
+    Heap,
+    Stack,
+    Other,
+}
+
+impl Area {
+    pub const ALL: [Area; 5] = [Area::Text, Area::Data, Area::Heap, Area::Stack, Area::Other];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Area::Text => "text",
+            Area::Data => "data",
+            Area::Heap => "heap",
+            Area::Stack => "stack",
+            Area::Other => "other",
+        }
+    }
+
+    pub fn of(address: u32) -> Area {
+        match address {
+            a if (DOT_TEXT_START_ADDRESS..DOT_DATA_START_ADDRESS).contains(&a) => Area::Text,
+            a if (DOT_DATA_START_ADDRESS..HEAP_START_ADDRESS).contains(&a) => Area::Data,
+            a if (HEAP_START_ADDRESS..STACK_START_ADDRESS).contains(&a) => Area::Heap,
+            a if (STACK_START_ADDRESS..STACK_END_ADDRESS).contains(&a) => Area::Stack,
+            _ => Area::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    pub instructions: u64,
+    // Indexed like Category::ALL
+    pub by_category: [u64; 8],
+    pub branches_taken: u64,
+    pub branches_not_taken: u64,
+    // Indexed like Area::ALL
+    pub loads: [u64; 5],
+    pub stores: [u64; 5],
+    pub load_use_stalls: u64,
+    pub flushes: u64,
+    // Cycles stalled by timing jitter (see jitter.rs)
+    pub jitter_stalls: u64,
+    // The register the previous instruction loaded into, if it was a load
+    pending_load: Option<usize>,
+}
+
+impl Statistics {
+    // Counts an instruction that completed. `address` is the memory it
+    // loaded from or stored to, and `transferred` whether it changed the
+    // flow of control.
+    pub(crate) fn record(
+        &mut self,
+        word: u32,
+        address: Option<u32>,
+        transferred: bool,
+        delay_slots: bool,
+    ) {
+        let category = category(word);
+        self.instructions += 1;
+        self.by_category[category as usize] += 1;
+
+        if category == Category::Branch {
+            if transferred {
+                self.branches_taken += 1;
+            } else {
+                self.branches_not_taken += 1;
+            }
+        }
+        if transferred && !delay_slots {
+            self.flushes += 1;
+        }
+
+        if let Some(address) = address {
+            match category {
+                Category::Load => self.loads[Area::of(address) as usize] += 1,
+                Category::Store => self.stores[Area::of(address) as usize] += 1,
+                _ => (),
+            }
+        }
+
+        let (sources, loaded) = registers(word, category);
+        if self
+            .pending_load
+            .is_some_and(|register| register != 0 && sources.contains(&register))
+        {
+            self.load_use_stalls += 1;
+        }
+        self.pending_load = loaded;
+    }
+
+    pub fn count(&self, category: Category) -> u64 {
+        self.by_category[category as usize]
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.instructions + self.load_use_stalls + self.flushes + self.jitter_stalls
+    }
+
+    // A summary a few lines long, for printing when a program exits
+    pub fn format(&self) -> String {
+        let categories: Vec<String> = Category::ALL
+            .iter()
+            .map(|category| format!("{} {}", category.name(), self.count(*category)))
+            .collect();
+        let areas = |counts: &[u64; 5]| -> String {
+            Area::ALL
+                .iter()
+                .map(|area| format!("{} {}", area.name(), counts[*area as usize]))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        format!(
+            "Instructions executed: {} (about {} cycles)\n  {}\n\
+             Branches: {} taken, {} not taken\n\
+             Loads: {}\n\
+             Stores: {}\n\
+             Pipeline: {} load-use stalls, {} fetches flushed after taken branches and jumps",
+            self.instructions,
+            self.cycles(),
+            categories.join(", "),
+            self.branches_taken,
+            self.branches_not_taken,
+            areas(&self.loads),
+            areas(&self.stores),
+            self.load_use_stalls,
+            self.flushes,
+        )
+    }
+}
+
+// The general purpose registers an instruction reads, and the one it loads
+// into if it is a load. Floating point registers are left out, so a
+// stall on a loaded float is not counted.
+fn registers(word: u32, category: Category) -> (Vec<usize>, Option<usize>) {
+    match Mips::decode(word) {
+        Instructions::R(r) => (vec![r.rs, r.rt], None),
+        Instructions::I(i) => match (category, i.opcode) {
+            // lwc1 and ldc1 load a float register
+            (Category::Load, 0x31 | 0x35) => (vec![i.rs], None),
+            (Category::Load, _) => (vec![i.rs], Some(i.rt)),
+            // swc1 and sdc1 store a float register
+            (Category::Store, 0x39 | 0x3d) => (vec![i.rs], None),
+            (Category::Store, _) | (Category::Branch, 0x4 | 0x5) => (vec![i.rs, i.rt], None),
+            _ => (vec![i.rs], None),
+        },
+        // mtc1 reads rt
+        Instructions::F(f) if f.fmt == FMT_MT => (vec![f.ft], None),
+        // and so does mtc0
+        Instructions::Cop0(c) if c.fmt == FMT_MT => (vec![c.ft], None),
+        _ => (vec![], None),
+    }
+}
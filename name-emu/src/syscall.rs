@@ -0,0 +1,291 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use name_core::register::Register::{A0, A1, V0};
+
+use crate::exception::ExecutionErrors;
+use crate::mips::Mips;
+
+// Where a program's console I/O goes. Syscalls only ever talk to this trait,
+// so frontends can swap stdin/stdout for a scripted buffer, a debug adapter
+// output channel, etc.
+pub trait Console: Debug {
+    fn write_str(&mut self, text: &str);
+    // Returns a line of input including its trailing newline, or None at end of input
+    fn read_line(&mut self) -> Option<String>;
+    // Returns a single character of input, or None at end of input
+    fn read_char(&mut self) -> Option<char>;
+}
+
+// The process's own stdin and stdout
+#[derive(Debug, Default)]
+pub struct StdConsole {
+    // Characters left over from a line that was only partially consumed by read_char
+    pending: VecDeque<char>,
+}
+
+impl Console for StdConsole {
+    fn write_str(&mut self, text: &str) {
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        if !self.pending.is_empty() {
+            return Some(self.pending.drain(..).collect());
+        }
+        let mut line = String::new();
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        if self.pending.is_empty() {
+            let line = self.read_line()?;
+            self.pending.extend(line.chars());
+        }
+        self.pending.pop_front()
+    }
+}
+
+// Input taken from a string and output collected in memory, for running
+// programs with nobody at the keyboard. The output is shared so it can
+// still be read once the console has been handed to the machine.
+#[derive(Debug, Default)]
+pub struct BufferConsole {
+    input: VecDeque<char>,
+    pub output: Rc<RefCell<String>>,
+}
+
+impl BufferConsole {
+    pub fn new(input: &str) -> Self {
+        BufferConsole {
+            input: input.chars().collect(),
+            output: Rc::default(),
+        }
+    }
+}
+
+impl Console for BufferConsole {
+    fn write_str(&mut self, text: &str) {
+        self.output.borrow_mut().push_str(text);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        if self.input.is_empty() {
+            return None;
+        }
+        let length = self
+            .input
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(self.input.len(), |i| i + 1);
+        Some(self.input.drain(..length).collect())
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        self.input.pop_front()
+    }
+}
+
+// What a printed newline becomes on the console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Newline {
+    #[default]
+    Lf,
+    // Every \n is printed as \r\n, as programs on Windows do
+    Crlf,
+}
+
+impl FromStr for Newline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Newline::Lf),
+            "crlf" => Ok(Newline::Crlf),
+            _ => Err(format!("expected `lf` or `crlf`, got `{}`", s)),
+        }
+    }
+}
+
+// How text passes between a program and its console. Output that looks
+// right in MARS but differs by a carriage return, a tab or a newline left in
+// a string is a common reason for a program to fail a grader, so these are
+// all settings rather than fixed, and frontends show which are in use. The
+// defaults are MARS's: newlines and tabs printed as they are, and read
+// string keeping the newline as fgets does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConsoleSettings {
+    pub newline: Newline,
+    // Tabs are printed as spaces up to the next multiple of this many
+    // columns, or as they are if None
+    pub tab_width: Option<usize>,
+    // Read string (syscall 8) drops the newline ending the line it reads
+    pub strip_read_newline: bool,
+}
+
+impl fmt::Display for ConsoleSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.newline {
+            Newline::Lf => write!(f, "newlines as LF")?,
+            Newline::Crlf => write!(f, "newlines as CRLF")?,
+        }
+        match self.tab_width {
+            Some(width) => write!(f, ", tabs expanded to {} columns", width)?,
+            None => write!(f, ", tabs kept")?,
+        }
+        if self.strip_read_newline {
+            write!(f, ", read string strips the newline")
+        } else {
+            write!(f, ", read string keeps the newline")
+        }
+    }
+}
+
+impl Mips {
+    // Prints program output to the console, translated as console_settings
+    // asks. Every syscall that writes to the console goes through here
+    pub(crate) fn print(&mut self, text: &str) {
+        let settings = self.console_settings;
+        let mut translated = String::with_capacity(text.len());
+        for c in text.chars() {
+            match (c, settings.tab_width) {
+                ('\n', _) => {
+                    if settings.newline == Newline::Crlf {
+                        translated.push('\r');
+                    }
+                    translated.push('\n');
+                    self.console_column = 0;
+                }
+                ('\r', _) => {
+                    translated.push('\r');
+                    self.console_column = 0;
+                }
+                ('\t', Some(width)) => {
+                    let spaces = width - self.console_column % width;
+                    translated.extend(std::iter::repeat_n(' ', spaces));
+                    self.console_column += spaces;
+                }
+                (c, _) => {
+                    translated.push(c);
+                    self.console_column += 1;
+                }
+            }
+        }
+        self.console.write_str(&translated);
+    }
+
+    // Services a syscall instruction using the MARS/SPIM conventions:
+    // the service number is in $v0, arguments in $a0-$a3 and results in $v0.
+    pub(crate) fn syscall(&mut self) -> Result<(), ExecutionErrors> {
+        let service = self.reg(V0);
+
+        match service {
+            // Print integer
+            1 => {
+                let text = (self.reg(A0) as i32).to_string();
+                self.print(&text);
+            }
+            // Print string
+            4 => {
+                let text = self.read_c_string(self.reg(A0))?;
+                self.print(&text);
+            }
+            // Read integer
+            5 => {
+                let line = self.console.read_line().unwrap_or_default();
+                match line.trim().parse::<i32>() {
+                    Ok(value) => self.set_reg(V0, value as u32),
+                    Err(_) => return Err(ExecutionErrors::SyscallInputError { service }),
+                }
+            }
+            // Read string into the buffer at $a0 holding at most $a1 bytes.
+            // Like fgets, keeps the newline if it fits, unless the console
+            // settings say to strip it, and always null-terminates.
+            8 => {
+                let buffer = self.reg(A0);
+                let capacity = self.reg(A1) as i32;
+                if capacity >= 1 {
+                    let mut line = self.console.read_line().unwrap_or_default();
+                    if self.console_settings.strip_read_newline {
+                        let length = line.trim_end_matches(['\r', '\n']).len();
+                        line.truncate(length);
+                    }
+                    let bytes: Vec<u8> = line.bytes().take(capacity as usize - 1).collect();
+                    for (i, byte) in bytes.iter().enumerate() {
+                        self.write_b(buffer + i as u32, *byte)?;
+                    }
+                    self.write_b(buffer + bytes.len() as u32, 0)?;
+                }
+            }
+            // Allocate heap memory, returning the address of the new block
+            9 => {
+                let address = self.sbrk(self.reg(A0))?;
+                self.set_reg(V0, address);
+            }
+            // Exit
+            10 => {
+                self.exit_code = Some(0);
+            }
+            // Print character
+            11 => {
+                let c = self.reg(A0) as u8 as char;
+                self.print(&c.to_string());
+            }
+            // Read character
+            12 => match self.console.read_char() {
+                Some(c) => self.set_reg(V0, c as u32),
+                None => return Err(ExecutionErrors::SyscallInputError { service }),
+            },
+            // Open, read, write and close files (see files.rs)
+            13 => self.syscall_open()?,
+            14 => self.syscall_read()?,
+            15 => self.syscall_write()?,
+            16 => self.syscall_close()?,
+            // Exit with the value in $a0
+            17 => {
+                self.exit_code = Some(self.reg(A0));
+            }
+            // Print integer as hexadecimal
+            34 => {
+                let text = format!("0x{:08x}", self.reg(A0));
+                self.print(&text);
+            }
+            // Print integer as binary
+            35 => {
+                let text = format!("{:032b}", self.reg(A0));
+                self.print(&text);
+            }
+            // Print integer as unsigned
+            36 => {
+                let text = self.reg(A0).to_string();
+                self.print(&text);
+            }
+            _ => return Err(ExecutionErrors::UnknownSyscall { service }),
+        }
+
+        Ok(())
+    }
+
+    // Reads a null-terminated string out of memory
+    pub(crate) fn read_c_string(&mut self, mut address: u32) -> Result<String, ExecutionErrors> {
+        let mut bytes = vec![];
+        loop {
+            let byte = self.read_b(address)?;
+            if byte == 0 {
+                break;
+            }
+            bytes.push(byte);
+            address += 1;
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
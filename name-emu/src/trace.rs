@@ -0,0 +1,253 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use name_core::machine::MachineState;
+use name_core::register::Register;
+use name_core::symbols::Symbol;
+use name_emu::disasm::disassemble;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::mips::{ByteWrite, Mips};
+use serde::Serialize;
+
+use crate::DynResult;
+
+// `--trace <file|->`: records every instruction `name run` executes as one
+// JSON object per line, for autograders to compare a run against a
+// reference one. Only what an instruction changed is listed:
+//
+//   {"step":3,"address":4194312,"word":554172424,"instruction":"addi $t0, $t0, 8",
+//    "registers":[{"name":"$t0","value":8}]}
+//   {"step":4,"address":4194316,"word":2903572480,"instruction":"sw $t1, 0($t0)",
+//    "memory":[{"address":268500992,"bytes":[7,0,0,0]}]}
+//
+// Registers are listed in register number order, then hi, lo, the
+// coprocessor 0 registers and the floating point registers. Memory writes
+// are listed in the order they were made, with adjacent bytes merged.
+// A step that raised an exception has an `error` and shows the state the
+// exception left behind.
+//
+// `--trace-range START-END` only records instructions at addresses in that
+// range, given as labels or 0x addresses, and `--trace-steps FIRST-LAST`
+// only the FIRST to LAST instruction executed, counting from 1. Both
+// ranges include their ends and either end may be left out.
+
+#[derive(Debug, Serialize)]
+struct Record {
+    step: u64,
+    address: u32,
+    word: u32,
+    instruction: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    registers: Vec<RegisterWrite>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    floats: Vec<FloatWrite>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    memory: Vec<MemoryWrite>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RegisterWrite {
+    name: String,
+    value: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct FloatWrite {
+    name: String,
+    value: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct MemoryWrite {
+    address: u32,
+    bytes: Vec<u8>,
+}
+
+// The machine just before an instruction, for finish to compare against
+pub struct Pending {
+    address: u32,
+    word: u32,
+    state: MachineState,
+}
+
+pub struct Tracer {
+    out: Box<dyn Write>,
+    addresses: (u32, u32),
+    steps: (u64, u64),
+    step: u64,
+}
+
+impl Tracer {
+    // `path` is a file to create, or `-` for standard output
+    pub fn create(
+        path: &str,
+        range: Option<&str>,
+        steps: Option<&str>,
+        symbols: &[Symbol],
+    ) -> DynResult<Tracer> {
+        let (first_address, last_address) = match range {
+            Some(range) => parse_range(range, "--trace-range", |bound| address(bound, symbols))?,
+            None => (None, None),
+        };
+        let (first_step, last_step) = match steps {
+            Some(steps) => parse_range(steps, "--trace-steps", |bound| bound.parse::<u64>().ok())?,
+            None => (None, None),
+        };
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(BufWriter::new(File::create(path).map_err(|why| {
+                format!("Failed to create trace file {}. Reason: {}", path, why)
+            })?))
+        };
+        Ok(Tracer {
+            out,
+            addresses: (first_address.unwrap_or(0), last_address.unwrap_or(u32::MAX)),
+            steps: (first_step.unwrap_or(1), last_step.unwrap_or(u64::MAX)),
+            step: 0,
+        })
+    }
+
+    // Call before each step_one. Returns None when the program has already
+    // finished, so the step will not execute anything.
+    pub fn begin(&mut self, mips: &mut Mips) -> Option<Pending> {
+        let address = mips.pc();
+        if address as usize == mips.stop_address || mips.exit_code.is_some() {
+            return None;
+        }
+        mips.record_writes = true;
+        mips.memory_writes.clear();
+        Some(Pending {
+            address,
+            // A fetch that fails is reported as the step's error
+            word: mips.read_w(address).unwrap_or(0),
+            state: mips.register_state(),
+        })
+    }
+
+    // Call after each step_one with what begin returned and the step's result
+    pub fn finish(
+        &mut self,
+        mips: &mut Mips,
+        pending: Option<Pending>,
+        result: &Result<(), ExecutionErrors>,
+    ) -> io::Result<()> {
+        let Some(pending) = pending else {
+            return Ok(());
+        };
+        mips.record_writes = false;
+        self.step += 1;
+        if !(self.addresses.0..=self.addresses.1).contains(&pending.address)
+            || !(self.steps.0..=self.steps.1).contains(&self.step)
+        {
+            return Ok(());
+        }
+
+        let after = mips.register_state();
+        let before = &pending.state;
+        let mut registers: Vec<RegisterWrite> = (0..32)
+            .filter(|&i| before.regs[i] != after.regs[i])
+            .map(|i| RegisterWrite {
+                name: Register::ALL[i].name().to_string(),
+                value: after.regs[i],
+            })
+            .collect();
+        for (name, old, new) in [
+            ("hi", before.hi, after.hi),
+            ("lo", before.lo, after.lo),
+            ("epc", before.epc, after.epc),
+            ("cause", before.cause, after.cause),
+            ("badvaddr", before.bad_vaddr, after.bad_vaddr),
+            ("cc", before.fp_condition as u32, after.fp_condition as u32),
+        ] {
+            if old != new {
+                registers.push(RegisterWrite {
+                    name: name.to_string(),
+                    value: new,
+                });
+            }
+        }
+        // Compare bits so that a NaN written over itself is not a change
+        let floats = (0..32)
+            .filter(|&i| before.floats[i].to_bits() != after.floats[i].to_bits())
+            .map(|i| FloatWrite {
+                name: format!("$f{}", i),
+                value: after.floats[i],
+            })
+            .collect();
+
+        let mut memory: Vec<MemoryWrite> = vec![];
+        for ByteWrite {
+            address, new: byte, ..
+        } in mips.memory_writes.drain(..)
+        {
+            match memory.last_mut() {
+                Some(last) if last.address.wrapping_add(last.bytes.len() as u32) == address => {
+                    last.bytes.push(byte)
+                }
+                _ => memory.push(MemoryWrite {
+                    address,
+                    bytes: vec![byte],
+                }),
+            }
+        }
+
+        let error = match result {
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            })
+            | Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        };
+        let record = Record {
+            step: self.step,
+            address: pending.address,
+            word: pending.word,
+            instruction: disassemble(pending.word, pending.address),
+            registers,
+            floats,
+            memory,
+            error,
+        };
+        serde_json::to_writer(&mut self.out, &record)?;
+        writeln!(self.out)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// A label or 0x address
+fn address(text: &str, symbols: &[Symbol]) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => symbols
+            .iter()
+            .rev()
+            .find(|symbol| symbol.name == text)
+            .map(|symbol| symbol.address),
+    }
+}
+
+// `FIRST-LAST`, where either end may be left out to leave that side open
+fn parse_range<T>(
+    text: &str,
+    flag: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> DynResult<(Option<T>, Option<T>)> {
+    let (first, last) = text
+        .split_once('-')
+        .ok_or_else(|| format!("Expected FIRST-LAST after {} but found `{}`", flag, text))?;
+    let bound = |bound: &str| -> DynResult<Option<T>> {
+        if bound.is_empty() {
+            return Ok(None);
+        }
+        parse(bound).map(Some).ok_or_else(|| {
+            format!("`{}` in {} is not a label, address or number", bound, flag).into()
+        })
+    };
+    Ok((bound(first.trim())?, bound(last.trim())?))
+}
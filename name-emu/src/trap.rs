@@ -0,0 +1,250 @@
+use std::fmt;
+
+use crate::exception::ExecutionErrors;
+use crate::mips::{BranchDelays, Mips};
+
+// Coprocessor 0, as far as exception handlers need it. An exception either
+// stops the program, recording where and why in EPC and Cause for the
+// frontend to report, or, once a program has an exception handler, goes to
+// the handler:
+//
+//   - EPC is set to the faulting instruction, or for an interrupt the one
+//     about to run, and Cause to the exception code. An address error also
+//     sets BadVAddr to the address that was bad
+//   - Status.EXL is set, which holds off interrupts
+//   - execution continues at the handler, which returns with eret, going
+//     back to EPC and clearing EXL. To skip the faulting instruction, as
+//     after a syscall, the handler adds 4 to EPC first
+//
+// An exception inside the handler, with EXL still set, stops the program
+// as if there were no handler. Syscalls made there are always serviced by
+// NAME, so a handler can print. The delay slot bit of Cause is not modelled:
+// an exception in a delay slot returns to the slot, not the branch.
+//
+// The timer raises hardware interrupt 5 (Cause.IP7) when Count reaches
+// Compare. Writing Compare clears it.
+
+// Coprocessor 0 registers, as numbered by mfc0 and mtc0
+pub const BAD_VADDR: usize = 8;
+pub const COUNT: usize = 9;
+pub const COMPARE: usize = 11;
+pub const STATUS: usize = 12;
+pub const CAUSE: usize = 13;
+pub const EPC: usize = 14;
+
+// Status: interrupt enable, exception level and the interrupt mask
+pub const STATUS_IE: u32 = 1 << 0;
+pub const STATUS_EXL: u32 = 1 << 1;
+pub const STATUS_IM: u32 = 0xff00;
+// Every interrupt unmasked and enabled, as in MARS
+pub const INITIAL_STATUS: u32 = STATUS_IM | STATUS_IE;
+
+// Cause: the exception code, the pending interrupts, and among those the
+// two set by software and the timer
+pub const CAUSE_EXC_CODE: u32 = 0x7c;
+pub const CAUSE_IP: u32 = 0xff00;
+pub const CAUSE_SOFTWARE: u32 = 0x0300;
+pub const CAUSE_TIMER: u32 = 1 << 15;
+
+// Exception codes as stored in the ExcCode field (bits 6..2) of the CP0
+// Cause register. Only the ones NAME can currently raise are listed.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ExceptionCode {
+    // An enabled interrupt is pending
+    Interrupt = 0,
+    // A load from a misaligned address, or an instruction fetch from a
+    // misaligned address or one outside memory
+    AddressErrorLoad = 4,
+    // A store to a misaligned address
+    AddressErrorStore = 5,
+    // An instruction fetch touched memory that does not hold code
+    InstructionBusError = 6,
+    // A load, store or instruction fetch touched memory that does not exist
+    DataBusError = 7,
+    // A syscall, when syscalls are trapped
+    Syscall = 8,
+    // break
+    Breakpoint = 9,
+    // The instruction word does not decode to anything NAME implements
+    ReservedInstruction = 10,
+    // add, addi or sub overflowed
+    Overflow = 12,
+    // A conditional trap instruction's condition held
+    Trap = 13,
+}
+
+impl fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ExceptionCode::Interrupt => "interrupt",
+            ExceptionCode::AddressErrorLoad => "address error on load",
+            ExceptionCode::AddressErrorStore => "address error on store",
+            ExceptionCode::InstructionBusError => "bus error on instruction fetch",
+            ExceptionCode::DataBusError => "bus error",
+            ExceptionCode::Syscall => "syscall",
+            ExceptionCode::Breakpoint => "breakpoint",
+            ExceptionCode::ReservedInstruction => "reserved instruction",
+            ExceptionCode::Overflow => "arithmetic overflow",
+            ExceptionCode::Trap => "trap",
+        };
+        write!(f, "ExcCode {}: {}", *self as u32, name)
+    }
+}
+
+impl ExecutionErrors {
+    // The architectural exception this error corresponds to. Errors that are
+    // about the emulator itself rather than the program (running out of
+    // heap, asking for an unknown syscall, ...) have none.
+    pub fn exception_code(&self) -> Option<ExceptionCode> {
+        match self {
+            ExecutionErrors::MemoryObviousOverrunAccess { .. }
+            | ExecutionErrors::MemoryIllegalAccess { .. } => Some(ExceptionCode::DataBusError),
+            ExecutionErrors::MemoryUnalignedAccess { store: false, .. } => {
+                Some(ExceptionCode::AddressErrorLoad)
+            }
+            ExecutionErrors::MemoryUnalignedAccess { store: true, .. } => {
+                Some(ExceptionCode::AddressErrorStore)
+            }
+            ExecutionErrors::IllegalJump { .. } => Some(ExceptionCode::InstructionBusError),
+            ExecutionErrors::IllegalFetch { .. } => Some(ExceptionCode::AddressErrorLoad),
+            ExecutionErrors::UndefinedInstruction { .. } => {
+                Some(ExceptionCode::ReservedInstruction)
+            }
+            ExecutionErrors::ArithmeticOverflow { .. } => Some(ExceptionCode::Overflow),
+            ExecutionErrors::Breakpoint { .. } => Some(ExceptionCode::Breakpoint),
+            ExecutionErrors::Trap { .. } => Some(ExceptionCode::Trap),
+            _ => None,
+        }
+    }
+
+    // The address an address error goes back to, for BadVAddr
+    pub fn bad_address(&self) -> Option<u32> {
+        match self {
+            ExecutionErrors::MemoryUnalignedAccess { load_address, .. } => Some(*load_address),
+            ExecutionErrors::IllegalFetch { address } => Some(*address),
+            _ => None,
+        }
+    }
+}
+
+impl Mips {
+    // Records an exception in EPC and Cause the way the hardware would,
+    // going to the exception handler if there is one to take it. Returns
+    // whether it did; if not, execution stops and the frontend reports the
+    // error. Must be called with pc pointing at the faulting instruction.
+    pub(crate) fn trap(&mut self, error: &ExecutionErrors) -> bool {
+        let Some(code) = error.exception_code() else {
+            return false;
+        };
+        if let Some(address) = error.bad_address() {
+            self.bad_vaddr = address;
+        }
+        self.raise(code, self.pc())
+    }
+
+    fn raise(&mut self, code: ExceptionCode, epc: u32) -> bool {
+        self.epc = epc;
+        self.cause = (self.cause & !CAUSE_EXC_CODE) | (code as u32) << 2;
+        match self.exception_handler {
+            Some(handler) if self.status & STATUS_EXL == 0 => {
+                self.status |= STATUS_EXL;
+                self.branch_delay_status = BranchDelays::NotActive;
+                self.set_pc(handler);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Hands the syscall about to be serviced to the exception handler
+    // instead, if syscalls are trapped. pc has already moved past it
+    pub(crate) fn syscall_trapped(&mut self) -> bool {
+        self.trap_syscalls
+            && self.exception_handler.is_some()
+            && self.status & STATUS_EXL == 0
+            && self.raise(ExceptionCode::Syscall, self.pc() - 4)
+    }
+
+    // Goes to the exception handler before the next instruction if an
+    // interrupt is pending, unmasked and enabled
+    pub(crate) fn take_interrupt(&mut self) {
+        let pending = self.cause & self.status & CAUSE_IP != 0;
+        if pending
+            && self.status & (STATUS_IE | STATUS_EXL) == STATUS_IE
+            && self.exception_handler.is_some()
+        {
+            self.raise(ExceptionCode::Interrupt, self.pc());
+        }
+    }
+
+    // Counts a completed instruction, raising the timer interrupt when
+    // Count reaches Compare
+    pub(crate) fn tick_timer(&mut self) {
+        self.count = self.count.wrapping_add(1);
+        if self.count == self.compare {
+            self.cause |= CAUSE_TIMER;
+        }
+    }
+
+    // mfc0 and mtc0. Registers NAME does not model read as 0 and ignore
+    // writes, BadVAddr can only be read, and only the software interrupt
+    // bits of Cause can be written
+    pub(crate) fn read_cop0(&self, register: usize) -> u32 {
+        match register {
+            BAD_VADDR => self.bad_vaddr,
+            COUNT => self.count,
+            COMPARE => self.compare,
+            STATUS => self.status,
+            CAUSE => self.cause,
+            EPC => self.epc,
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn write_cop0(&mut self, register: usize, value: u32) {
+        match register {
+            COUNT => self.count = value,
+            COMPARE => {
+                self.compare = value;
+                self.cause &= !CAUSE_TIMER;
+            }
+            STATUS => self.status = value,
+            CAUSE => self.cause = (self.cause & !CAUSE_SOFTWARE) | (value & CAUSE_SOFTWARE),
+            EPC => self.epc = value,
+            _ => (),
+        }
+    }
+
+    pub(crate) fn eret(&mut self) {
+        self.status &= !STATUS_EXL;
+        self.set_pc(self.epc);
+    }
+
+    // The conditional traps, tge through tne, raise a trap exception when
+    // their condition holds
+    pub(crate) fn trap_if(&self, condition: bool) -> Result<(), ExecutionErrors> {
+        match condition {
+            true => Err(ExecutionErrors::Trap {
+                address: self.pc() - 4,
+            }),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn breakpoint(&self) -> ExecutionErrors {
+        ExecutionErrors::Breakpoint {
+            address: self.pc() - 4,
+        }
+    }
+
+    // Builds the overflow error for a trapping add or subtract. pc has
+    // already moved past the instruction by the time it executes.
+    pub(crate) fn overflow(&self, lhs: u32, rhs: u32, op: char) -> ExecutionErrors {
+        ExecutionErrors::ArithmeticOverflow {
+            address: self.pc() - 4,
+            lhs,
+            rhs,
+            op,
+        }
+    }
+}
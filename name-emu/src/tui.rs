@@ -0,0 +1,533 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, VecDeque};
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use name_core::lineinfo::LineTable;
+use name_core::memmap;
+use name_core::register::Register;
+use name_emu::disasm::disassemble;
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::mips::Mips;
+use name_emu::syscall::Console;
+
+use crate::limits::Watchdog;
+use crate::{load_program_lines, DynResult, Options};
+
+const USAGE: &str = "USAGE: name tui [source file or ELF executable] [--endian big|little] [--entry label|address] [--audit] [--linux] [--delay-slots] [--files directory] [--handler label|address] [--trap-syscalls] [--max-steps n] [--timeout seconds]";
+
+// `name tui`: a full-screen frontend in the terminal, laid out like MARS's
+// Execute tab. The program's instructions fill the left of the screen with
+// the source line each came from, the registers the right, and memory and
+// the console share the bottom. Registers that the last step or run
+// changed are highlighted.
+//
+// Console output collects in its pane. When the program reads input, the
+// screen gives way to the console until a line has been typed, since the
+// machine is in the middle of a syscall and cannot be drawn.
+
+const KEYS: &str = "s step  c continue  esc pause  b breakpoint  ↑↓ move  . pc  [ ] memory  m region  r restart  q quit";
+
+// How many instructions run between checks for a key while continuing
+const STEPS_PER_FRAME: usize = 20000;
+
+type SharedTerminal = Rc<RefCell<DefaultTerminal>>;
+
+// Console I/O for the TUI: output goes to the console pane, and input is
+// typed at a prompt that takes over the screen
+#[derive(Debug)]
+struct TuiConsole {
+    output: Rc<RefCell<String>>,
+    // The rest of a line read_char has started on
+    pending: VecDeque<char>,
+    terminal: TerminalHandle,
+}
+
+// The terminal, shared with the console so it can prompt for input
+#[derive(Clone)]
+struct TerminalHandle(SharedTerminal);
+
+impl std::fmt::Debug for TerminalHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "TerminalHandle")
+    }
+}
+
+impl TuiConsole {
+    // Shows the console with a prompt until a line is entered. Escape ends
+    // the input instead
+    fn prompt(&mut self) -> Option<String> {
+        let mut typed = String::new();
+        loop {
+            let output = self.output.borrow();
+            let drawn = self.terminal.0.borrow_mut().draw(|frame| {
+                let area = frame.area();
+                let lines = tail(&output, area.height.saturating_sub(3) as usize);
+                let mut text: Vec<Line> = lines.into_iter().map(Line::from).collect();
+                text.push(Line::from(Span::styled(format!("> {}_", typed), Style::default().add_modifier(Modifier::BOLD))));
+                let block = Block::bordered().title(" Console: the program is waiting for input (enter to send, esc for end of input) ");
+                frame.render_widget(Paragraph::new(text).block(block), area);
+            }).is_ok();
+            drop(output);
+            if !drawn {
+                return None;
+            }
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => {
+                    typed.push('\n');
+                    self.output.borrow_mut().push_str(&typed);
+                    return Some(typed);
+                }
+                KeyCode::Esc => return None,
+                KeyCode::Backspace => {
+                    typed.pop();
+                }
+                KeyCode::Char(c) => typed.push(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Console for TuiConsole {
+    fn write_str(&mut self, text: &str) {
+        self.output.borrow_mut().push_str(text);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        if !self.pending.is_empty() {
+            return Some(self.pending.drain(..).collect());
+        }
+        self.prompt()
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        if self.pending.is_empty() {
+            let line = self.prompt()?;
+            self.pending.extend(line.chars());
+        }
+        self.pending.pop_front()
+    }
+}
+
+// The last `count` lines of some text
+fn tail(text: &str, count: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+struct Tui {
+    path: String,
+    options: Options,
+    terminal: TerminalHandle,
+    mips: Mips,
+    lineinfo: LineTable,
+    output: Rc<RefCell<String>>,
+    breakpoints: BTreeSet<u32>,
+    // The instruction the cursor is on in the code pane
+    cursor: u32,
+    // The first address shown in the memory pane
+    memory_address: u32,
+    // The registers when the machine last stopped, to highlight changes
+    previous: [u32; 32],
+    running: bool,
+    status: String,
+    watchdog: Watchdog,
+}
+
+pub fn tui_main(args: &[String], options: &Options) -> DynResult<()> {
+    let [path] = args else {
+        return Err(USAGE.into());
+    };
+    // Load once before taking over the terminal, so errors print normally
+    load_program_lines(path, options)?;
+
+    let terminal = TerminalHandle(Rc::new(RefCell::new(ratatui::init())));
+    let result = Tui::new(path, options, terminal).and_then(|mut tui| tui.run());
+    ratatui::restore();
+    result
+}
+
+impl Tui {
+    fn new(path: &str, options: &Options, terminal: TerminalHandle) -> DynResult<Tui> {
+        let mut tui = Tui {
+            path: path.to_string(),
+            options: options.clone(),
+            terminal,
+            mips: Mips::default(),
+            lineinfo: LineTable::default(),
+            output: Rc::default(),
+            breakpoints: BTreeSet::new(),
+            cursor: 0,
+            memory_address: memmap::DATA_START,
+            previous: [0; 32],
+            running: false,
+            status: String::new(),
+            watchdog: Watchdog::new(options.limits),
+        };
+        tui.load()?;
+        Ok(tui)
+    }
+
+    // Loads the program afresh, keeping the breakpoints
+    fn load(&mut self) -> DynResult<()> {
+        let (mut mips, _, lineinfo) = load_program_lines(&self.path, &self.options)?;
+        self.output = Rc::default();
+        mips.console = Box::new(TuiConsole {
+            output: self.output.clone(),
+            pending: VecDeque::new(),
+            terminal: self.terminal.clone(),
+        });
+        self.cursor = mips.pc();
+        self.previous = mips.regs;
+        self.mips = mips;
+        self.lineinfo = lineinfo;
+        self.running = false;
+        self.watchdog = Watchdog::new(self.options.limits);
+        self.status = format!("Loaded {}", self.path);
+        Ok(())
+    }
+
+    fn run(&mut self) -> DynResult<()> {
+        loop {
+            let terminal = self.terminal.0.clone();
+            terminal.borrow_mut().draw(|frame| self.render(frame))?;
+
+            if self.running {
+                self.run_for_a_while();
+                if event::poll(Duration::ZERO)? {
+                    if let Event::Key(key) = event::read()? {
+                        if key.kind == KeyEventKind::Press
+                            && matches!(key.code, KeyCode::Esc | KeyCode::Char('c'))
+                        {
+                            self.pause();
+                            self.status = "Paused".to_string();
+                        }
+                    }
+                }
+                continue;
+            }
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Returns false to quit
+    fn handle_key(&mut self, key: KeyEvent) -> DynResult<bool> {
+        match key.code {
+            KeyCode::Char('q') => return Ok(false),
+            KeyCode::Char('s') => {
+                self.previous = self.mips.regs;
+                self.watchdog.resume();
+                if self.step_one() {
+                    self.status = "Stepped".to_string();
+                }
+                self.watchdog.pause();
+                self.cursor = self.mips.pc();
+            }
+            KeyCode::Char('c') => {
+                self.previous = self.mips.regs;
+                self.watchdog.resume();
+                self.running = true;
+                self.status = "Running, esc to pause".to_string();
+            }
+            KeyCode::Char('b') => {
+                // Toggles the breakpoint under the cursor
+                let removed = self.breakpoints.remove(&self.cursor);
+                if !removed {
+                    self.breakpoints.insert(self.cursor);
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-16),
+            KeyCode::PageDown => self.move_cursor(16),
+            KeyCode::Char('.') => self.cursor = self.mips.pc(),
+            KeyCode::Char('[') => self.memory_address = self.memory_address.wrapping_sub(16),
+            KeyCode::Char(']') => self.memory_address = self.memory_address.wrapping_add(16),
+            KeyCode::Char('{') => self.memory_address = self.memory_address.wrapping_sub(256),
+            KeyCode::Char('}') => self.memory_address = self.memory_address.wrapping_add(256),
+            KeyCode::Char('m') => {
+                // .data, then the heap, then the top of the stack
+                let sp = self.mips.reg(Register::Sp) & !15;
+                self.memory_address = if self.memory_address == memmap::DATA_START {
+                    memmap::HEAP_START
+                } else if self.memory_address == memmap::HEAP_START {
+                    sp
+                } else {
+                    memmap::DATA_START
+                };
+            }
+            KeyCode::Char('r') => {
+                if let Err(why) = self.load() {
+                    self.status = format!("Could not reload {}: {}", self.path, why);
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn pause(&mut self) {
+        self.running = false;
+        self.watchdog.pause();
+        self.cursor = self.mips.pc();
+    }
+
+    // Runs until a breakpoint, the end of the program, an error, or for
+    // long enough to check for a key
+    fn run_for_a_while(&mut self) {
+        for _ in 0..STEPS_PER_FRAME {
+            if !self.step_one() {
+                self.pause();
+                return;
+            }
+            if self.breakpoints.contains(&self.mips.pc()) {
+                self.pause();
+                self.status = "Hit breakpoint".to_string();
+                return;
+            }
+        }
+    }
+
+    // Runs one instruction, setting the status if the program cannot go
+    // on. Returns whether it can
+    fn step_one(&mut self) -> bool {
+        if let Err(ExecutionErrors::Event {
+            event: ExecutionEvents::ProgramComplete,
+        }) = self.mips.prev_ins_result
+        {
+            self.status = "The program has finished, r to run it again".to_string();
+            return false;
+        }
+        if let Some(reason) = self.watchdog.exceeded(&self.mips) {
+            self.status = format!("Stopped: {}", reason);
+            return false;
+        }
+        let step = self.mips.step_one(&mut io::sink());
+        if let Some(warning) = self.mips.audit_warnings.drain(..).next_back() {
+            self.status = format!("warning: {}", warning);
+        }
+        match step {
+            Ok(()) => true,
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => {
+                self.status = format!(
+                    "Program exited with code {}",
+                    self.mips.exit_code.unwrap_or(0)
+                );
+                false
+            }
+            Err(error) => {
+                self.status = match error.exception_code() {
+                    Some(code) => {
+                        format!("Exception at 0x{:08x} ({}): {}", self.mips.epc, code, error)
+                    }
+                    None => format!("Error at 0x{:08x}: {}", self.mips.pc(), error),
+                };
+                false
+            }
+        }
+    }
+
+    // The address of every instruction in executable memory
+    fn instructions(&self) -> Vec<u32> {
+        self.mips
+            .memory
+            .regions
+            .iter()
+            .filter(|region| region.executable)
+            .flat_map(|region| (region.base..region.base + region.length).step_by(4))
+            .collect()
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        let instructions = self.instructions();
+        let index = instructions
+            .iter()
+            .position(|address| *address == self.cursor)
+            .unwrap_or(0);
+        let moved = index
+            .saturating_add_signed(by)
+            .min(instructions.len().saturating_sub(1));
+        if let Some(address) = instructions.get(moved) {
+            self.cursor = *address;
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let rows = Layout::vertical([
+            Constraint::Min(10),
+            Constraint::Length(12),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+        let top = Layout::horizontal([Constraint::Min(40), Constraint::Length(31)]).split(rows[0]);
+        let bottom = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .split(rows[1]);
+
+        self.render_code(frame, top[0]);
+        self.render_registers(frame, top[1]);
+        self.render_memory(frame, bottom[0]);
+        let console = tail(
+            &self.output.borrow(),
+            bottom[1].height.saturating_sub(2) as usize,
+        );
+        frame.render_widget(
+            Paragraph::new(console.into_iter().map(Line::from).collect::<Vec<Line>>())
+                .block(Block::bordered().title(" Console ")),
+            bottom[1],
+        );
+        let status = Line::from(vec![
+            Span::styled(
+                format!(" {} ", self.status),
+                Style::default().add_modifier(Modifier::REVERSED),
+            ),
+            Span::raw(format!("  {}", KEYS)),
+        ]);
+        frame.render_widget(Paragraph::new(status), rows[2]);
+    }
+
+    // The instructions around the cursor, each with the source line it came from
+    fn render_code(&self, frame: &mut Frame, area: Rect) {
+        let instructions = self.instructions();
+        let height = area.height.saturating_sub(2) as usize;
+        let index = instructions
+            .iter()
+            .position(|address| *address == self.cursor)
+            .unwrap_or(0);
+        let first = index
+            .saturating_sub(height / 2)
+            .min(instructions.len().saturating_sub(height));
+        let pc = self.mips.pc();
+
+        let lines: Vec<Line> = instructions
+            .iter()
+            .skip(first)
+            .take(height)
+            .map(|&address| {
+                let marker = match (self.breakpoints.contains(&address), address == pc) {
+                    (true, true) => "*>",
+                    (true, false) => "* ",
+                    (false, true) => " >",
+                    (false, false) => "  ",
+                };
+                let instruction = self
+                    .mips
+                    .read_w(address)
+                    .map_or("??".to_string(), |word| disassemble(word, address));
+                let source = match self.lineinfo.at(address) {
+                    Some(li) => format!("{:>4}  {}", li.span.line, li.line_contents.trim()),
+                    None => String::new(),
+                };
+                let mut style = Style::default();
+                if address == pc {
+                    style = style.fg(Color::Black).bg(Color::Yellow);
+                } else if self.breakpoints.contains(&address) {
+                    style = style.fg(Color::Red);
+                }
+                if address == self.cursor {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+                Line::styled(
+                    format!(
+                        "{} 0x{:08x}  {:<26} {}",
+                        marker, address, instruction, source
+                    ),
+                    style,
+                )
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(format!(" {} ", self.path))),
+            area,
+        );
+    }
+
+    fn render_registers(&self, frame: &mut Frame, area: Rect) {
+        let mut lines: Vec<Line> = (0..32)
+            .map(|number| {
+                let value = self.mips.regs[number];
+                let style = if value != self.previous[number] {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Line::styled(
+                    format!(
+                        "{:<6} 0x{:08x} {:>11}",
+                        Register::ALL[number].to_string(),
+                        value,
+                        value as i32
+                    ),
+                    style,
+                )
+            })
+            .collect();
+        lines.push(Line::from(format!("{:<6} 0x{:08x}", "pc", self.mips.pc())));
+        lines.push(Line::from(format!(
+            "{:<6} 0x{:08x}",
+            "hi", self.mips.mult_hi
+        )));
+        lines.push(Line::from(format!(
+            "{:<6} 0x{:08x}",
+            "lo", self.mips.mult_lo
+        )));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Registers ")),
+            area,
+        );
+    }
+
+    fn render_memory(&self, frame: &mut Frame, area: Rect) {
+        let rows = area.height.saturating_sub(2) as u32;
+        let lines: Vec<Line> = (0..rows)
+            .map(|row| {
+                let start = self.memory_address.wrapping_add(row * 16);
+                let bytes: Vec<Option<u8>> = (0..16)
+                    .map(|i| self.mips.read_b(start.wrapping_add(i)).ok())
+                    .collect();
+                let hex: Vec<String> = bytes
+                    .iter()
+                    .map(|byte| byte.map_or("??".to_string(), |byte| format!("{:02x}", byte)))
+                    .collect();
+                let text: String = bytes
+                    .iter()
+                    .map(|byte| match byte {
+                        Some(byte) if byte.is_ascii_graphic() || *byte == b' ' => *byte as char,
+                        _ => '.',
+                    })
+                    .collect();
+                Line::from(format!("0x{:08x}  {}  {}", start, hex.join(" "), text))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Memory ")),
+            area,
+        );
+    }
+}
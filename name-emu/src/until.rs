@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use name_core::register::Register;
+use name_emu::mips::Mips;
+
+// `--until` and `--until-return-from`: points at which `name run` stops a
+// program partway and dumps its state, so a script can look at what a
+// program has computed by some point without driving the debugger.
+//
+// `--until label` stops before the instruction at the label runs for the
+// first time. `--until-return-from label` waits for the function at the
+// label to be called, then stops once it has returned to its caller, which
+// it can tell by pc reaching the return address the call left in $ra with
+// the stack popped back to where it was. Recursive calls in between are
+// part of the outermost one. main is called by nothing, so returning from
+// main means jumping to whatever $ra held when the program started.
+
+#[derive(Debug)]
+enum Point {
+    Address(u32),
+    // The function, and once it has been called, the return address and
+    // stack pointer of that call
+    ReturnFrom {
+        function: u32,
+        caller: Option<(u32, u32)>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Until {
+    target: String,
+    point: Point,
+}
+
+// A label or 0x address
+fn resolve(flag: &str, target: &str, labels: &BTreeMap<String, u32>) -> Result<u32, String> {
+    target
+        .strip_prefix("0x")
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .or_else(|| labels.get(target).copied())
+        .ok_or_else(|| {
+            format!(
+                "{}: unknown label `{}`, expected a label or 0x address",
+                flag, target
+            )
+        })
+}
+
+impl Until {
+    pub fn address(target: &str, labels: &BTreeMap<String, u32>) -> Result<Until, String> {
+        let address = resolve("--until", target, labels)?;
+        Ok(Until {
+            target: target.to_string(),
+            point: Point::Address(address),
+        })
+    }
+
+    pub fn return_from(target: &str, labels: &BTreeMap<String, u32>) -> Result<Until, String> {
+        let function = resolve("--until-return-from", target, labels)?;
+        Ok(Until {
+            target: target.to_string(),
+            point: Point::ReturnFrom {
+                function,
+                caller: None,
+            },
+        })
+    }
+
+    // Call before each instruction runs. Returns why the program should
+    // stop here, if it should
+    pub fn reached(&mut self, mips: &Mips) -> Option<String> {
+        let pc = mips.pc();
+        match &mut self.point {
+            Point::Address(address) => (pc == *address).then(|| format!("reached {}", self.target)),
+            Point::ReturnFrom { function, caller } => match *caller {
+                None => {
+                    if pc == *function {
+                        *caller = Some((mips.reg(Register::Ra), mips.reg(Register::Sp)));
+                    }
+                    None
+                }
+                Some((return_address, sp)) => (pc == return_address
+                    && mips.reg(Register::Sp) >= sp)
+                    .then(|| format!("returned from {}", self.target)),
+            },
+        }
+    }
+
+    // How the point was given on the command line, for saying it was
+    // never reached
+    pub fn describe(&self) -> String {
+        match self.point {
+            Point::Address(_) => format!("--until {}", self.target),
+            Point::ReturnFrom { .. } => format!("--until-return-from {}", self.target),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use name_core::elf::ElfExecutable;
+use name_emu::mips::Mips;
+
+use crate::DynResult;
+
+// `--verify-load`: after loading, reads every section of the program back
+// out of memory and compares it byte for byte with what the assembler or
+// linker laid out, to catch loader bugs such as a swapped byte order or a
+// truncated string.
+
+const DUMP_ROW_LENGTH: usize = 16;
+
+// A block of the program as it should appear in memory
+pub struct Expected {
+    pub name: String,
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+// The blocks an ELF executable loads. Whatever a segment has in memory past
+// its file contents, such as .bss, should read as zeros.
+pub fn elf_sections(elf: &ElfExecutable) -> Vec<Expected> {
+    elf.segments
+        .iter()
+        .map(|segment| {
+            let mut bytes = segment.data.clone();
+            bytes.resize(segment.mem_size as usize, 0);
+            Expected {
+                name: format!("segment {}", if segment.executable { "r-x" } else { "rw-" }),
+                address: segment.address,
+                bytes,
+            }
+        })
+        .collect()
+}
+
+// Checks each block against memory, reporting on stderr. Rows of the hex
+// dump that differ are shown with the object's bytes above the loaded ones
+// and the differences marked.
+pub fn verify_load(mips: &Mips, sections: &[Expected]) -> DynResult<()> {
+    let mut matched = true;
+    for section in sections {
+        matched &= verify_section(mips, section);
+    }
+    if matched {
+        Ok(())
+    } else {
+        Err("verify-load: memory does not match the program as assembled".into())
+    }
+}
+
+fn verify_section(mips: &Mips, section: &Expected) -> bool {
+    let loaded: Vec<Option<u8>> = (0..section.bytes.len() as u32)
+        .map(|offset| mips.read_b(section.address.wrapping_add(offset)).ok())
+        .collect();
+    let differing = section
+        .bytes
+        .iter()
+        .zip(&loaded)
+        .filter(|(expected, loaded)| Some(**expected) != **loaded)
+        .count();
+
+    if differing == 0 {
+        eprintln!(
+            "verify-load: {} at 0x{:08x}, {} bytes match",
+            section.name,
+            section.address,
+            section.bytes.len()
+        );
+        return true;
+    }
+    eprintln!(
+        "verify-load: {} at 0x{:08x}, {} of {} bytes differ",
+        section.name,
+        section.address,
+        differing,
+        section.bytes.len()
+    );
+
+    let rows = section
+        .bytes
+        .chunks(DUMP_ROW_LENGTH)
+        .zip(loaded.chunks(DUMP_ROW_LENGTH));
+    for (row, (expected, loaded)) in rows.enumerate() {
+        let marks: Vec<&str> = expected
+            .iter()
+            .zip(loaded)
+            .map(|(expected, loaded)| {
+                if Some(*expected) == *loaded {
+                    "  "
+                } else {
+                    "^^"
+                }
+            })
+            .collect();
+        if marks.iter().all(|mark| mark.trim().is_empty()) {
+            continue;
+        }
+        let address = section.address.wrapping_add((row * DUMP_ROW_LENGTH) as u32);
+        let object: Vec<String> = expected
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let memory: Vec<String> = loaded
+            .iter()
+            .map(|byte| byte.map_or("--".to_string(), |byte| format!("{:02x}", byte)))
+            .collect();
+        eprintln!("  {:08x}  object {}", address, object.join(" "));
+        eprintln!("            memory {}", memory.join(" "));
+        eprintln!("                   {}", marks.join(" ").trim_end());
+    }
+    false
+}
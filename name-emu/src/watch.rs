@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use name_core::symbols::{DataType, Symbol};
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::Mips;
+
+// Watches name a value in memory, for the debugger to report as it changes
+// or is read and written, and for the grader to check once a program has
+// run:
+//
+//   counter               the label's value, read the way its directive laid it out
+//   buffer[4]             one element, counted in the label's element size
+//   counter as half       read as a word, half, byte, float or ascii string instead
+//   0x10010008 as byte    an address works too, read as a word by default
+//
+// Types and sizes come from the symbol table, so a label without them (a
+// code label, or one recovered from the source) is read as a single word.
+// Later symbols win, as in load_symbols.
+
+// The longest string read for an ascii watch with no known size
+const MAX_STRING_LENGTH: u32 = 256;
+
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub expression: String,
+    pub address: u32,
+    pub data_type: DataType,
+    // Elements to read, or for an ascii watch the most characters to read
+    // before a NUL
+    pub count: u32,
+    // Whether count covers the whole symbol, as its size was known
+    pub sized: bool,
+}
+
+// Which accesses to a watch's bytes stop the debugger, whatever the value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Read,
+    ReadOrWrite,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchValue {
+    Numbers(Vec<u32>),
+    Text(String),
+}
+
+impl Watch {
+    pub fn parse(
+        expression: &str,
+        labels: &BTreeMap<String, u32>,
+        symbols: &[Symbol],
+    ) -> Result<Watch, String> {
+        let (target, data_type) = match expression.split_once(" as ") {
+            Some((target, data_type)) => {
+                (target.trim(), Some(data_type.trim().parse::<DataType>()?))
+            }
+            None => (expression.trim(), None),
+        };
+        let (name, index) = match target
+            .strip_suffix(']')
+            .and_then(|target| target.split_once('['))
+        {
+            Some((name, index)) => (
+                name.trim(),
+                Some(
+                    parse_number(index.trim())
+                        .ok_or_else(|| format!("expected an index but found `{}`", index))?,
+                ),
+            ),
+            None => (target, None),
+        };
+
+        let (base, symbol) = match name.strip_prefix("0x") {
+            Some(hex) => (
+                u32::from_str_radix(hex, 16)
+                    .map_err(|_| format!("`{}` is not an address", name))?,
+                None,
+            ),
+            None => (
+                *labels
+                    .get(name)
+                    .ok_or_else(|| format!("no label named `{}`", name))?,
+                symbols.iter().rev().find(|symbol| symbol.name == name),
+            ),
+        };
+        let size = symbol.map_or(0, |symbol| symbol.size);
+        let data_type = data_type
+            .or(symbol.and_then(|symbol| symbol.data_type))
+            .unwrap_or(DataType::Word);
+
+        let offset = index.unwrap_or(0).saturating_mul(data_type.size());
+        if size != 0 && offset >= size {
+            return Err(format!(
+                "index {} is past the end of `{}`, which is {} bytes",
+                index.unwrap_or(0),
+                name,
+                size
+            ));
+        }
+        let count = match (data_type, index) {
+            (DataType::Ascii, _) if size != 0 => size - offset,
+            (DataType::Ascii, _) => MAX_STRING_LENGTH,
+            (_, None) if size != 0 => (size / data_type.size()).max(1),
+            _ => 1,
+        };
+
+        Ok(Watch {
+            expression: expression.trim().to_string(),
+            address: base.wrapping_add(offset),
+            data_type,
+            count,
+            sized: size != 0,
+        })
+    }
+
+    // Whether an address is one of the bytes the watch covers
+    pub fn covers(&self, address: u32) -> bool {
+        let length = match self.data_type {
+            DataType::Ascii => self.count,
+            data_type => self.count * data_type.size(),
+        };
+        address.wrapping_sub(self.address) < length
+    }
+
+    pub fn read(&self, mips: &Mips) -> Result<WatchValue, ExecutionErrors> {
+        if self.data_type == DataType::Ascii {
+            let mut text = String::new();
+            for i in 0..self.count {
+                match mips.read_b(self.address.wrapping_add(i))? {
+                    0 => break,
+                    byte => text.push(byte as char),
+                }
+            }
+            return Ok(WatchValue::Text(text));
+        }
+
+        let size = self.data_type.size();
+        (0..self.count)
+            .map(|i| {
+                let address = self.address.wrapping_add(i * size);
+                match self.data_type {
+                    DataType::Word | DataType::Float => mips.read_w(address),
+                    DataType::Half => mips.read_h(address).map(u32::from),
+                    _ => mips.read_b(address).map(u32::from),
+                }
+            })
+            .collect::<Result<Vec<u32>, ExecutionErrors>>()
+            .map(WatchValue::Numbers)
+    }
+
+    // Shows a value the way this watch reads it, signed and in hex
+    pub fn format(&self, value: &WatchValue) -> String {
+        match value {
+            WatchValue::Text(text) => format!("{:?}", text),
+            WatchValue::Numbers(numbers) => {
+                let shown: Vec<String> = numbers
+                    .iter()
+                    .map(|number| self.format_number(*number))
+                    .collect();
+                match shown.as_slice() {
+                    [single] => single.clone(),
+                    _ => format!("[{}]", shown.join(", ")),
+                }
+            }
+        }
+    }
+
+    fn format_number(&self, number: u32) -> String {
+        match self.data_type {
+            DataType::Word => format!("{} (0x{:08x})", number as i32, number),
+            DataType::Float => format!(
+                "{} (0x{:08x})",
+                format_float(f32::from_bits(number)),
+                number
+            ),
+            DataType::Half => format!("{} (0x{:04x})", number as u16 as i16, number),
+            _ => format!("{} (0x{:02x})", number as u8 as i8, number),
+        }
+    }
+
+    // Everything the watch reads, on as many lines as it takes: an array
+    // one element per line and a sized ascii symbol, which may hold several
+    // strings, one string per line
+    pub fn pretty(&self, mips: &Mips) -> Result<String, ExecutionErrors> {
+        let mut out = self.to_string();
+        if self.data_type == DataType::Ascii && self.sized {
+            let strings = self.strings(mips)?;
+            if let [(_, text)] = strings.as_slice() {
+                out.push_str(&format!(" = {:?}", text));
+            } else {
+                for (address, text) in strings {
+                    out.push_str(&format!("\n  0x{:08x}  {:?}", address, text));
+                }
+            }
+            return Ok(out);
+        }
+
+        match self.read(mips)? {
+            WatchValue::Numbers(numbers) if numbers.len() > 1 => {
+                for (i, number) in numbers.into_iter().enumerate() {
+                    let address = self.address.wrapping_add(i as u32 * self.data_type.size());
+                    out.push_str(&format!(
+                        "\n  [{}] 0x{:08x}  {}",
+                        i,
+                        address,
+                        self.format_number(number)
+                    ));
+                }
+            }
+            value => out.push_str(&format!(" = {}", self.format(&value))),
+        }
+        Ok(out)
+    }
+
+    // The NUL-terminated strings laid out across the watch, with their
+    // addresses. Characters after the last NUL count as one more string.
+    fn strings(&self, mips: &Mips) -> Result<Vec<(u32, String)>, ExecutionErrors> {
+        let mut strings = vec![];
+        let mut start = self.address;
+        let mut text = String::new();
+        for i in 0..self.count {
+            let address = self.address.wrapping_add(i);
+            match mips.read_b(address)? {
+                0 => {
+                    strings.push((start, std::mem::take(&mut text)));
+                    start = address.wrapping_add(1);
+                }
+                byte => text.push(byte as char),
+            }
+        }
+        if !text.is_empty() || strings.is_empty() {
+            strings.push((start, text));
+        }
+        Ok(strings)
+    }
+
+    // Whether a number given in a rubric matches an element, ignoring the
+    // bits above the element size so that -1 matches 0xff for a byte. A
+    // float matches a number equal to its value.
+    pub fn number_matches(&self, expected: i64, actual: u32) -> bool {
+        if self.data_type == DataType::Float {
+            return expected as f32 == f32::from_bits(actual);
+        }
+        let mask = match self.data_type {
+            DataType::Word => u32::MAX,
+            DataType::Half => 0xffff,
+            _ => 0xff,
+        };
+        expected as u32 & mask == actual & mask
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at 0x{:08x}, {}",
+            self.expression, self.address, self.data_type
+        )?;
+        if self.data_type != DataType::Ascii && self.count > 1 {
+            write!(f, " x {}", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+// The data labels with a known type, as the assembler declared them, in
+// address order. Later symbols win, as in load_symbols.
+pub fn data_symbols(symbols: &[Symbol]) -> Vec<&Symbol> {
+    let mut data: Vec<&Symbol> = vec![];
+    for symbol in symbols
+        .iter()
+        .rev()
+        .filter(|symbol| symbol.data_type.is_some())
+    {
+        if !data.iter().any(|seen| seen.name == symbol.name) {
+            data.push(symbol);
+        }
+    }
+    data.sort();
+    data
+}
+
+// Switches to scientific notation for tiny and huge values, as
+// Mips::format_floats does
+fn format_float(value: f32) -> String {
+    let text = value.to_string();
+    if text.len() > 14 {
+        format!("{:e}", value)
+    } else {
+        text
+    }
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
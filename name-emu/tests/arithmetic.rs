@@ -0,0 +1,181 @@
+// Signed adds and subtracts trap on overflow, leaving their destination
+// alone, while the unsigned forms wrap around. Multiplies and divides put
+// their results in HI and LO.
+
+mod common;
+
+use name_core::register::Register::{S0, S1, S2, S3, T2};
+use name_emu::exception::ExecutionErrors;
+
+// Runs `instruction` with $t0 = 0x7fffffff and $t1 = 0x80000000, returning
+// the error it stopped with, if any, and what it left in $t2
+fn run(instruction: &str) -> (Option<ExecutionErrors>, u32) {
+    let program = format!(
+        r#"
+        .text
+main:   li $t0, 0x7fffffff
+        li $t1, 0x80000000
+        li $t2, 99
+op:     {instruction}
+        li $v0, 10
+        syscall
+"#
+    );
+    let mut mips = common::machine(&program);
+    let error = common::run_to_end(&mut mips).err();
+    if let Some(ExecutionErrors::ArithmeticOverflow { address, .. }) = &error {
+        assert_eq!(*address, common::label(&program, "op"), "{}", instruction);
+    }
+    (error, mips.reg(T2))
+}
+
+fn overflows(instruction: &str) -> bool {
+    match run(instruction) {
+        (Some(ExecutionErrors::ArithmeticOverflow { .. }), result) => {
+            assert_eq!(result, 99, "{} wrote its destination", instruction);
+            true
+        }
+        (None, _) => false,
+        (Some(error), _) => panic!("{} stopped with {}", instruction, error),
+    }
+}
+
+#[test]
+fn signed_arithmetic_traps_on_overflow() {
+    assert!(overflows("add $t2, $t0, $t0"));
+    assert!(overflows("add $t2, $t1, $t1"));
+    assert!(overflows("addi $t2, $t0, 1"));
+    assert!(overflows("addi $t2, $t1, -1"));
+    assert!(overflows("sub $t2, $t1, $t0"));
+    assert!(overflows("sub $t2, $t0, $t1"));
+}
+
+#[test]
+fn signed_arithmetic_in_range_does_not_trap() {
+    assert!(!overflows("add $t2, $t0, $t1"));
+    assert!(!overflows("addi $t2, $t0, -1"));
+    assert!(!overflows("sub $t2, $t0, $t0"));
+    assert_eq!(run("add $t2, $t0, $t1").1, 0xffffffff);
+    assert_eq!(run("sub $t2, $t1, $t1").1, 0);
+}
+
+#[test]
+fn unsigned_arithmetic_wraps() {
+    assert_eq!(run("addu $t2, $t0, $t0"), (None, 0xfffffffe));
+    assert_eq!(run("addiu $t2, $t0, 1"), (None, 0x80000000));
+    assert_eq!(run("addu $t2, $t1, $t1"), (None, 0));
+    assert_eq!(run("subu $t2, $t1, $t0"), (None, 1));
+}
+
+#[test]
+fn overflow_error_names_operands() {
+    let (error, _) = run("sub $t2, $t1, $t0");
+    match error {
+        Some(ExecutionErrors::ArithmeticOverflow { lhs, rhs, op, .. }) => {
+            assert_eq!((lhs, rhs, op), (0x80000000, 0x7fffffff, '-'));
+        }
+        other => panic!("expected an overflow, got {:?}", other),
+    }
+}
+
+#[test]
+fn unsigned_arithmetic_in_a_loop() {
+    // A checksum that is meant to wrap around runs to completion
+    let program = r#"
+        .text
+main:   li $t0, 0x40000000
+        li $t1, 8
+loop:   addu $s0, $s0, $t0
+        subu $s1, $s1, $t0
+        addiu $s2, $s2, -1
+        addiu $t1, $t1, -1
+        bgtz $t1, loop
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!(mips.reg(S0), 0);
+    assert_eq!(mips.reg(S1), 0);
+    assert_eq!(mips.reg(S2), (-8i32) as u32);
+}
+
+// Runs `instructions` on $t0 and $t1, then copies HI into $s0 and LO into $s1
+fn hi_lo(t0: i32, t1: i32, instructions: &str) -> (u32, u32) {
+    let program = format!(
+        r#"
+        .text
+main:   li $t0, {t0}
+        li $t1, {t1}
+        {instructions}
+        mfhi $s0
+        mflo $s1
+        li $v0, 10
+        syscall
+"#
+    );
+    let mut mips = common::machine(&program);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!((mips.mult_hi, mips.mult_lo), (mips.reg(S0), mips.reg(S1)));
+    (mips.reg(S0), mips.reg(S1))
+}
+
+#[test]
+fn multiply_splits_the_product_across_hi_and_lo() {
+    assert_eq!(hi_lo(6, 7, "mult $t0, $t1"), (0, 42));
+    assert_eq!(hi_lo(-6, 7, "mult $t0, $t1"), (0xffffffff, (-42i32) as u32));
+    assert_eq!(
+        hi_lo(0x40000000, 8, "mult $t0, $t1"),
+        (0x00000002, 0x00000000)
+    );
+    // As unsigned, -1 is 0xffffffff, and its square is 0xfffffffe00000001
+    assert_eq!(hi_lo(-1, -1, "mult $t0, $t1"), (0, 1));
+    assert_eq!(hi_lo(-1, -1, "multu $t0, $t1"), (0xfffffffe, 1));
+}
+
+#[test]
+fn divide_puts_remainder_in_hi_and_quotient_in_lo() {
+    assert_eq!(hi_lo(17, 5, "div $t0, $t1"), (2, 3));
+    // The quotient rounds towards zero and the remainder takes the sign
+    // of the dividend
+    assert_eq!(
+        hi_lo(-17, 5, "div $t0, $t1"),
+        ((-2i32) as u32, (-3i32) as u32)
+    );
+    assert_eq!(
+        hi_lo(-17, 5, "divu $t0, $t1"),
+        (0xffffffef % 5, 0xffffffef / 5)
+    );
+    // The one signed quotient that doesn't fit wraps around
+    assert_eq!(hi_lo(i32::MIN, -1, "div $t0, $t1"), (0, 0x80000000));
+}
+
+#[test]
+fn divide_by_zero_leaves_hi_and_lo() {
+    let set = "mthi $t0\n        mtlo $t1\n        ";
+    assert_eq!(hi_lo(11, 0, &format!("{set}div $t0, $t1")), (11, 0));
+    assert_eq!(hi_lo(11, 0, &format!("{set}divu $t0, $t1")), (11, 0));
+}
+
+#[test]
+fn move_to_hi_and_lo() {
+    assert_eq!(hi_lo(3, 4, "mthi $t0\n        mtlo $t1"), (3, 4));
+}
+
+#[test]
+fn factorial_through_lo() {
+    let program = r#"
+        .text
+main:   li $s3, 1
+        li $t0, 10
+loop:   mult $s3, $t0
+        mflo $s3
+        addiu $t0, $t0, -1
+        bgtz $t0, loop
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!(mips.reg(S3), 3628800);
+}
@@ -0,0 +1,142 @@
+// Branches and jumps go where the program says, taken or not, whichever
+// way their operands compare, and the linking ones leave the return
+// address in the register they link through.
+
+mod common;
+
+use name_core::register::Register::{Ra, S0, S1, T2};
+
+// Runs `branch`, with $t0 = -1 and $t1 = 1, and reports whether it went to
+// its target
+fn taken(branch: &str) -> bool {
+    let program = format!(
+        r#"
+        .text
+main:   li $t0, -1
+        li $t1, 1
+        {branch} target
+        li $s0, 1
+        li $v0, 10
+        syscall
+target: li $s0, 2
+        li $v0, 10
+        syscall
+"#
+    );
+    let mut mips = common::machine(&program);
+    common::run_to_end(&mut mips).unwrap();
+    match mips.reg(S0) {
+        1 => false,
+        2 => true,
+        other => panic!("{} ended with $s0 = {}", branch, other),
+    }
+}
+
+#[test]
+fn branches_compare_their_operands() {
+    let cases = [
+        ("beq $t1, $t1,", true),
+        ("beq $t0, $t1,", false),
+        ("bne $t0, $t1,", true),
+        ("bne $t0, $t0,", false),
+        ("bgtz $t1,", true),
+        ("bgtz $zero,", false),
+        ("bgtz $t0,", false),
+        ("blez $zero,", true),
+        ("blez $t0,", true),
+        ("blez $t1,", false),
+        ("bltz $t0,", true),
+        ("bltz $zero,", false),
+        ("bgez $zero,", true),
+        ("bgez $t1,", true),
+        ("bgez $t0,", false),
+        // Pseudo-instructions, which compare in $at and branch on it
+        ("beqz $zero,", true),
+        ("beqz $t1,", false),
+        ("bnez $t0,", true),
+        ("bnez $zero,", false),
+        ("blt $t0, $t1,", true),
+        ("blt $t1, $t1,", false),
+        ("bgt $t1, $t0,", true),
+        ("bgt $t1, $t1,", false),
+        ("ble $t1, $t1,", true),
+        ("ble $t1, $t0,", false),
+        ("bge $t1, $t1,", true),
+        ("bge $t0, $t1,", false),
+    ];
+    for (branch, expected) in cases {
+        assert_eq!(taken(branch), expected, "{}", branch);
+    }
+}
+
+#[test]
+fn backward_branch_loops() {
+    let program = r#"
+        .text
+main:   li $t0, 10
+loop:   addu $s0, $s0, $t0
+        addiu $t0, $t0, -1
+        bgtz $t0, loop
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!(mips.reg(S0), 55);
+}
+
+#[test]
+fn jumps_and_links() {
+    // double is called once through jal and once through jalr, and the
+    // second call links through $t2 instead of $ra
+    let program = r#"
+        .text
+main:   li $a0, 3
+call:   jal double
+        addu $s0, $v0, $zero
+        la $t9, double2
+        li $a0, 5
+again:  jalr $t2, $t9
+        addu $s1, $v0, $zero
+        j done
+        li $s0, 0
+double: addu $v0, $a0, $a0
+        jr $ra
+double2:
+        addu $v0, $a0, $a0
+        jr $t2
+done:   li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S0), 6);
+    assert_eq!(mips.reg(S1), 10);
+    assert_eq!(mips.reg(Ra), common::label(program, "call") + 4);
+    assert_eq!(mips.reg(T2), common::label(program, "again") + 4);
+}
+
+#[test]
+fn branches_that_link() {
+    // bgezal and bltzal link whether or not they are taken
+    let program = r#"
+        .text
+main:   li $t0, -1
+first:  bgezal $t0, skip
+        li $s0, 1
+        addu $s1, $ra, $zero
+second: bltzal $t0, skip
+        li $s0, 2
+        li $v0, 10
+        syscall
+skip:   li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S0), 1);
+    assert_eq!(mips.reg(S1), common::label(program, "first") + 4);
+    assert_eq!(mips.reg(Ra), common::label(program, "second") + 4);
+}
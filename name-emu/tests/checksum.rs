@@ -0,0 +1,43 @@
+// Executables from name-as carry a checksum of each segment, and loading
+// one whose bytes have changed since has to fail instead of running
+// whatever the damage left behind.
+
+use name_as::nma::{assemble_source, object_bytes, AssemblerOptions};
+use name_core::elf::read_elf;
+
+const PROGRAM: &str = r#"
+        .data
+value:  .word 42
+        .text
+main:   la $t0, value
+        lw $a0, 0($t0)
+        li $v0, 10
+        syscall
+"#;
+
+fn executable() -> Vec<u8> {
+    let assembled = assemble_source(PROGRAM, &AssemblerOptions::default()).unwrap();
+    object_bytes(&assembled, "program.asm")
+}
+
+#[test]
+fn intact_executable_is_verified() {
+    let executable = read_elf(&executable()).unwrap();
+    assert!(executable.verified);
+}
+
+#[test]
+fn corrupted_segments_are_refused() {
+    let bytes = executable();
+    let loaded = read_elf(&bytes).unwrap();
+    for segment in &loaded.segments {
+        let offset = bytes
+            .windows(segment.data.len())
+            .position(|window| window == segment.data.as_slice())
+            .unwrap();
+        let mut corrupted = bytes.clone();
+        corrupted[offset] ^= 0x01;
+        let error = read_elf(&corrupted).unwrap_err().to_string();
+        assert!(error.contains("checksum"), "{}", error);
+    }
+}
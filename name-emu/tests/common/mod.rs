@@ -0,0 +1,87 @@
+// Fixtures shared by the integration tests. Each test file that needs them
+// declares `mod common;`, so not every helper is used by every file.
+#![allow(dead_code)]
+
+use name_as::nma::{assemble_source, AssemblerOptions};
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::mips::Mips;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Assembles a program and loads its text and data into a fresh machine
+pub fn machine(program: &str) -> Mips {
+    machine_with(program, &AssemblerOptions::default())
+}
+
+// machine, assembling with other options than the defaults
+pub fn machine_with(program: &str, options: &AssemblerOptions) -> Mips {
+    let assembled = assemble_source(program, options).unwrap();
+    let mut mips: Mips = Default::default();
+    mips.load_text_at(assembled.text_address(), assembled.text(), assembled.entry)
+        .unwrap();
+    mips.load_data_at(assembled.data_address(), assembled.data())
+        .unwrap();
+    mips
+}
+
+// The address `label` is assembled at in `program`, such as a handler's
+pub fn label(program: &str, label: &str) -> u32 {
+    let assembled = assemble_source(program, &AssemblerOptions::default()).unwrap();
+    assembled
+        .symbols
+        .iter()
+        .find(|symbol| symbol.name == label)
+        .unwrap_or_else(|| panic!("no label {}", label))
+        .address
+}
+
+// Runs the program until it exits or stops with an error, which is
+// returned. One that is still going after a million steps fails the test
+pub fn run_to_end(mips: &mut Mips) -> Result<(), ExecutionErrors> {
+    for _ in 0..1_000_000 {
+        match mips.step_one(&mut io::sink()) {
+            Ok(()) => {}
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+    panic!("the program was still running at 0x{:08x}", mips.pc());
+}
+
+// Runs the `name` binary with `input` on its standard input
+pub fn name<S: AsRef<OsStr>>(args: &[S], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_name"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// An empty directory of its own for a test to write files in. Tests run in
+// parallel, so every call gets a different one
+pub fn temp_dir(test: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "name_{}_{}_{}",
+        test,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,49 @@
+// The debugger's commands take addresses straight from the user, so dumping
+// memory or disassembling near the top of the address space has to stop
+// there instead of overflowing.
+
+mod common;
+
+use name_as::nma::{assemble_to_object, AssemblerOptions};
+use std::ffi::OsStr;
+
+const PROGRAM: &str = r#"
+        .text
+main:   addi $t0, $t0, 1
+        j main
+"#;
+
+// Runs `name debug` on the program with `commands` on its standard input
+fn debug(commands: &str) -> String {
+    let dir = common::temp_dir("debugger");
+    let path = dir.join("program.elf");
+    let object = assemble_to_object(PROGRAM, &AssemblerOptions::default()).unwrap();
+    std::fs::write(&path, object).unwrap();
+
+    let output = common::name(&[OsStr::new("debug"), path.as_os_str()], commands);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn memory_dump_stops_at_top_of_address_space() {
+    let output = debug("mem 0xfffffffe 4\nquit\n");
+    assert!(output.contains("0xfffffffe: ?? ??\n"), "{}", output);
+}
+
+#[test]
+fn memory_dump_rows_stop_at_top_of_address_space() {
+    let output = debug("mem 0xffffffe0 64\nquit\n");
+    assert!(output.contains("0xfffffff0:"), "{}", output);
+    assert!(!output.contains("0x00000000:"), "{}", output);
+}
+
+#[test]
+fn disassembly_stops_at_top_of_address_space() {
+    debug("disasm 0xfffffffc 4\nquit\n");
+}
@@ -0,0 +1,123 @@
+// With delay slots emulated, the instruction after a taken branch or jump
+// runs before the target does, and a program the assembler filled the
+// slots of behaves as it would without them.
+
+mod common;
+
+use name_as::delay::DelaySlots;
+use name_as::nma::AssemblerOptions;
+use name_core::register::Register::{Ra, S0, S1, S2};
+use name_emu::mips::Mips;
+
+fn machine(program: &str, delay_slots: bool) -> Mips {
+    let mut mips = common::machine(program);
+    mips.delay_slots = delay_slots;
+    mips
+}
+
+#[test]
+fn slot_runs_before_the_target() {
+    // The slot sets $s0 and the target reads it, so $s1 shows the order
+    let program = r#"
+        .text
+main:   beq $zero, $zero, target
+        li $s0, 1
+        li $s2, 1
+target: addu $s1, $s0, $zero
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = machine(program, true);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!((mips.reg(S0), mips.reg(S1), mips.reg(S2)), (1, 1, 0));
+
+    // Without delay slots, the branch goes straight to its target
+    let mut mips = machine(program, false);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!((mips.reg(S0), mips.reg(S1), mips.reg(S2)), (0, 0, 0));
+}
+
+#[test]
+fn slot_runs_after_a_branch_not_taken() {
+    let program = r#"
+        .text
+main:   bne $zero, $zero, target
+        li $s0, 1
+        li $s2, 1
+target: li $v0, 10
+        syscall
+"#;
+    let mut mips = machine(program, true);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!((mips.reg(S0), mips.reg(S2)), (1, 1));
+}
+
+#[test]
+fn jump_and_link_returns_past_the_slot() {
+    // The slot of the jal runs once, before the call, and the call returns
+    // to the instruction after it
+    let program = r#"
+        .text
+main:   jal callee
+call:   addiu $s0, $s0, 1
+        li $v0, 10
+        syscall
+callee: addu $s1, $s0, $zero
+        jr $ra
+        addiu $s2, $s2, 1
+"#;
+    let mut mips = machine(program, true);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(Ra), common::label(program, "call") + 4);
+    assert_eq!(mips.reg(S0), 1);
+    assert_eq!(mips.reg(S1), 1);
+    assert_eq!(mips.reg(S2), 1);
+}
+
+// Sums 1 to 10 into $s0, counting the loop in $s1. The add before each
+// branch can move into its slot; the decrement the bne tests can't.
+const LOOP: &str = r#"
+        .text
+main:   li $t0, 10
+loop:   addu $s0, $s0, $t0
+        addiu $t0, $t0, -1
+        addiu $s1, $s1, 1
+        bne $t0, $zero, loop
+        addiu $s2, $s2, 1
+        j done
+done:   li $v0, 10
+        syscall
+"#;
+
+#[test]
+fn filled_slots_run_the_same_with_delay_slots() {
+    for mode in [DelaySlots::Nop, DelaySlots::Reorder] {
+        let options = AssemblerOptions {
+            delay_slots: mode,
+            ..Default::default()
+        };
+        let mut mips = common::machine_with(LOOP, &options);
+        mips.delay_slots = true;
+        common::run_to_end(&mut mips).unwrap();
+        assert_eq!(
+            (mips.reg(S0), mips.reg(S1), mips.reg(S2)),
+            (55, 10, 1),
+            "{:?}",
+            mode
+        );
+    }
+
+    let mut mips = machine(LOOP, false);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!((mips.reg(S0), mips.reg(S1), mips.reg(S2)), (55, 10, 1));
+}
+
+#[test]
+fn unfilled_slots_change_what_runs() {
+    // Assembled as written, the increment after the bne runs every time
+    // round the loop once slots are emulated
+    let mut mips = machine(LOOP, true);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!((mips.reg(S0), mips.reg(S1), mips.reg(S2)), (55, 10, 10));
+}
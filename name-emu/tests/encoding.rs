@@ -0,0 +1,348 @@
+// Golden encodings for every instruction in the shared ISA table. Each row
+// gives a canonical example and the word it must encode to, written out by
+// hand so that a typo in an opcode or funct field, whether in the ISA table,
+// the assembler or the disassembler, fails here instead of going unnoticed.
+//
+// Every ISA table entry must have a row: adding or editing an entry means
+// writing down its expected encoding too. The examples use the ISA table's
+// sample operands, so the golden word is also the entry's sample.
+//
+// Examples are assembled at the start of .text. `target` names the address
+// five instructions in, where a sample branch lands, and `main` the start
+// of .text, where a sample jump lands.
+
+use name_as::nma::{assemble_source, is_instruction, AssemblerOptions};
+use name_core::encoding::{decode, encode, Instruction};
+use name_core::isa::INSTRUCTIONS;
+use name_emu::disasm::disassemble;
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::{Mips, DOT_TEXT_START_ADDRESS};
+
+const GOLDEN: &[(&str, &str, u32)] = &[
+    // Shifts
+    ("sll", "sll $t0, $t2, 3", 0x000a40c0),
+    ("srl", "srl $t0, $t2, 3", 0x000a40c2),
+    ("sra", "sra $t0, $t2, 3", 0x000a40c3),
+    ("sllv", "sllv $t0, $t2, $t1", 0x012a4004),
+    ("srlv", "srlv $t0, $t2, $t1", 0x012a4006),
+    ("srav", "srav $t0, $t2, $t1", 0x012a4007),
+    // Register jumps and system calls
+    ("jr", "jr $t1", 0x01200008),
+    ("jalr", "jalr $t0, $t1", 0x01204009),
+    ("movz", "movz $t0, $t1, $t2", 0x012a400a),
+    ("movn", "movn $t0, $t1, $t2", 0x012a400b),
+    ("syscall", "syscall", 0x0000000c),
+    ("break", "break", 0x0000000d),
+    ("sync", "sync", 0x0000000f),
+    // HI and LO
+    ("mfhi", "mfhi $t0", 0x00004010),
+    ("mthi", "mthi $t1", 0x01200011),
+    ("mflo", "mflo $t0", 0x00004012),
+    ("mtlo", "mtlo $t1", 0x01200013),
+    ("mult", "mult $t1, $t2", 0x012a0018),
+    ("multu", "multu $t1, $t2", 0x012a0019),
+    ("div", "div $t1, $t2", 0x012a001a),
+    ("divu", "divu $t1, $t2", 0x012a001b),
+    // Arithmetic and logic
+    ("add", "add $t0, $t1, $t2", 0x012a4020),
+    ("addu", "addu $t0, $t1, $t2", 0x012a4021),
+    ("sub", "sub $t0, $t1, $t2", 0x012a4022),
+    ("subu", "subu $t0, $t1, $t2", 0x012a4023),
+    ("and", "and $t0, $t1, $t2", 0x012a4024),
+    ("or", "or $t0, $t1, $t2", 0x012a4025),
+    ("xor", "xor $t0, $t1, $t2", 0x012a4026),
+    ("nor", "nor $t0, $t1, $t2", 0x012a4027),
+    ("slt", "slt $t0, $t1, $t2", 0x012a402a),
+    ("sltu", "sltu $t0, $t1, $t2", 0x012a402b),
+    // Conditional traps
+    ("tge", "tge $t1, $t2", 0x012a0030),
+    ("tgeu", "tgeu $t1, $t2", 0x012a0031),
+    ("tlt", "tlt $t1, $t2", 0x012a0032),
+    ("tltu", "tltu $t1, $t2", 0x012a0033),
+    ("teq", "teq $t1, $t2", 0x012a0034),
+    ("tne", "tne $t1, $t2", 0x012a0036),
+    // SPECIAL2
+    ("madd", "madd $t1, $t2", 0x712a0000),
+    ("maddu", "maddu $t1, $t2", 0x712a0001),
+    ("mul", "mul $t0, $t1, $t2", 0x712a4002),
+    ("msub", "msub $t1, $t2", 0x712a0004),
+    ("msubu", "msubu $t1, $t2", 0x712a0005),
+    ("clz", "clz $t0, $t1", 0x71204020),
+    ("clo", "clo $t0, $t1", 0x71204021),
+    // Branches
+    ("bltz", "bltz $t1, target", 0x05200004),
+    ("bgez", "bgez $t1, target", 0x05210004),
+    ("bltzal", "bltzal $t1, target", 0x05300004),
+    ("bgezal", "bgezal $t1, target", 0x05310004),
+    ("beq", "beq $t1, $t2, target", 0x112a0004),
+    ("bne", "bne $t1, $t2, target", 0x152a0004),
+    ("blez", "blez $t1, target", 0x19200004),
+    ("bgtz", "bgtz $t1, target", 0x1d200004),
+    // Jumps
+    ("j", "j main", 0x08100000),
+    ("jal", "jal main", 0x0c100000),
+    // Immediate arithmetic and logic
+    ("addi", "addi $t2, $t1, 4", 0x212a0004),
+    ("addiu", "addiu $t2, $t1, 4", 0x252a0004),
+    ("slti", "slti $t2, $t1, 4", 0x292a0004),
+    ("sltiu", "sltiu $t2, $t1, 4", 0x2d2a0004),
+    ("andi", "andi $t2, $t1, 0x4", 0x312a0004),
+    ("ori", "ori $t2, $t1, 0x4", 0x352a0004),
+    ("xori", "xori $t2, $t1, 0x4", 0x392a0004),
+    ("lui", "lui $t2, 0x4", 0x3c0a0004),
+    // Loads and stores
+    ("lb", "lb $t2, 4($t1)", 0x812a0004),
+    ("lh", "lh $t2, 4($t1)", 0x852a0004),
+    ("lwl", "lwl $t2, 4($t1)", 0x892a0004),
+    ("lw", "lw $t2, 4($t1)", 0x8d2a0004),
+    ("lbu", "lbu $t2, 4($t1)", 0x912a0004),
+    ("lhu", "lhu $t2, 4($t1)", 0x952a0004),
+    ("lwr", "lwr $t2, 4($t1)", 0x992a0004),
+    ("sb", "sb $t2, 4($t1)", 0xa12a0004),
+    ("sh", "sh $t2, 4($t1)", 0xa52a0004),
+    ("swl", "swl $t2, 4($t1)", 0xa92a0004),
+    ("sw", "sw $t2, 4($t1)", 0xad2a0004),
+    ("swr", "swr $t2, 4($t1)", 0xb92a0004),
+    ("ll", "ll $t2, 4($t1)", 0xc12a0004),
+    ("lwc1", "lwc1 $f4, 4($t1)", 0xc5240004),
+    ("ldc1", "ldc1 $f4, 8($t1)", 0xd5240008),
+    ("sc", "sc $t2, 4($t1)", 0xe12a0004),
+    ("swc1", "swc1 $f4, 4($t1)", 0xe5240004),
+    ("sdc1", "sdc1 $f4, 8($t1)", 0xf5240008),
+    // Coprocessor 1 moves and branches
+    ("mfc1", "mfc1 $t2, $f2", 0x440a1000),
+    ("mtc1", "mtc1 $t2, $f2", 0x448a1000),
+    ("bc1f", "bc1f target", 0x45000004),
+    ("bc1t", "bc1t target", 0x45010004),
+    // Single precision
+    ("add.s", "add.s $f0, $f2, $f4", 0x46041000),
+    ("sub.s", "sub.s $f0, $f2, $f4", 0x46041001),
+    ("mul.s", "mul.s $f0, $f2, $f4", 0x46041002),
+    ("div.s", "div.s $f0, $f2, $f4", 0x46041003),
+    ("sqrt.s", "sqrt.s $f0, $f2", 0x46001004),
+    ("abs.s", "abs.s $f0, $f2", 0x46001005),
+    ("mov.s", "mov.s $f0, $f2", 0x46001006),
+    ("neg.s", "neg.s $f0, $f2", 0x46001007),
+    ("cvt.d.s", "cvt.d.s $f0, $f2", 0x46001021),
+    ("cvt.w.s", "cvt.w.s $f0, $f2", 0x46001024),
+    ("c.eq.s", "c.eq.s $f2, $f4", 0x46041032),
+    ("c.lt.s", "c.lt.s $f2, $f4", 0x4604103c),
+    ("c.le.s", "c.le.s $f2, $f4", 0x4604103e),
+    // Double precision
+    ("add.d", "add.d $f0, $f2, $f4", 0x46241000),
+    ("sub.d", "sub.d $f0, $f2, $f4", 0x46241001),
+    ("mul.d", "mul.d $f0, $f2, $f4", 0x46241002),
+    ("div.d", "div.d $f0, $f2, $f4", 0x46241003),
+    ("mov.d", "mov.d $f0, $f2", 0x46201006),
+    ("cvt.s.d", "cvt.s.d $f0, $f2", 0x46201020),
+    // Word conversions
+    ("cvt.s.w", "cvt.s.w $f0, $f2", 0x46801020),
+    ("cvt.d.w", "cvt.d.w $f0, $f2", 0x46801021),
+    // Coprocessor 0
+    ("mfc0", "mfc0 $t2, $8", 0x400a4000),
+    ("mtc0", "mtc0 $t2, $8", 0x408a4000),
+    ("eret", "eret", 0x42000018),
+];
+
+// Where `target` lands: the sample branches' offset of 4 instructions,
+// counted from the delay slot
+const TARGET: u32 = DOT_TEXT_START_ADDRESS + 5 * 4;
+
+fn golden(mnemonic: &str) -> Option<&'static (&'static str, &'static str, u32)> {
+    GOLDEN.iter().find(|(name, _, _)| *name == mnemonic)
+}
+
+// The first word the assembler produces for `example`
+fn assemble(example: &str) -> Result<u32, String> {
+    let source = format!(
+        ".text\nmain:\n{}\nnop\nnop\nnop\nnop\ntarget:\nnop\n",
+        example
+    );
+    let assembled =
+        assemble_source(&source, &AssemblerOptions::default()).map_err(|e| format!("{:?}", e))?;
+    let text = assembled.text();
+    Ok(assembled
+        .endian
+        .u32_from_bytes([text[0], text[1], text[2], text[3]]))
+}
+
+// The example as the disassembler writes it, with labels as addresses
+fn disassembled_form(example: &str) -> String {
+    example
+        .replace("target", &format!("0x{:08x}", TARGET))
+        .replace("main", &format!("0x{:08x}", DOT_TEXT_START_ADDRESS))
+}
+
+#[test]
+fn every_instruction_has_a_golden_encoding() {
+    let missing: Vec<&str> = INSTRUCTIONS
+        .iter()
+        .map(|info| info.mnemonic)
+        .filter(|mnemonic| golden(mnemonic).is_none())
+        .collect();
+    assert!(missing.is_empty(), "no golden encoding for {:?}", missing);
+
+    let stale: Vec<&str> = GOLDEN
+        .iter()
+        .map(|(mnemonic, _, _)| *mnemonic)
+        .filter(|mnemonic| !INSTRUCTIONS.iter().any(|info| info.mnemonic == *mnemonic))
+        .collect();
+    assert!(
+        stale.is_empty(),
+        "golden encodings for instructions not in the ISA table: {:?}",
+        stale
+    );
+}
+
+#[test]
+fn samples_match_golden_encodings() {
+    let mut failures = vec![];
+    for info in INSTRUCTIONS {
+        if let Some((_, _, word)) = golden(info.mnemonic) {
+            if info.sample != *word {
+                failures.push(format!(
+                    "{}: ISA sample 0x{:08x}, expected 0x{:08x}",
+                    info.mnemonic, info.sample, word
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// Only instructions the assembler supports are checked; `name isa-report`
+// lists the rest
+#[test]
+fn assembler_matches_golden_encodings() {
+    let mut failures = vec![];
+    for (mnemonic, example, word) in GOLDEN {
+        if !is_instruction(mnemonic) {
+            continue;
+        }
+        match assemble(example) {
+            Ok(assembled) if assembled == *word => (),
+            Ok(assembled) => failures.push(format!(
+                "{}: assembled to 0x{:08x}, expected 0x{:08x}",
+                example, assembled, word
+            )),
+            Err(why) => failures.push(format!("{}: failed to assemble: {}", example, why)),
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// Only instructions the disassembler supports are checked, the rest come
+// out as `.word`
+#[test]
+fn disassembler_matches_golden_encodings() {
+    let mut failures = vec![];
+    for (mnemonic, example, word) in GOLDEN {
+        let text = disassemble(*word, DOT_TEXT_START_ADDRESS);
+        if text.starts_with(".word") {
+            continue;
+        }
+        let expected = disassembled_form(example);
+        if text != expected {
+            failures.push(format!(
+                "{}: 0x{:08x} disassembled to `{}`, expected `{}`",
+                mnemonic, word, text, expected
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// Whatever the assembler accepts the emulator must be able to run. The
+// golden operands are arbitrary, so faulting, e.g. on a load from an
+// unmapped address, is fine; being rejected as undefined is not
+#[test]
+fn emulator_runs_every_golden_encoding() {
+    let mut failures = vec![];
+    for (mnemonic, _, word) in GOLDEN {
+        let mut mips = Mips::default();
+        let text = mips.endian.u32_to_bytes(*word);
+        mips.load_text(&text, DOT_TEXT_START_ADDRESS).unwrap();
+        if let Err(ExecutionErrors::UndefinedInstruction { .. }) =
+            mips.step_one(&mut std::io::sink())
+        {
+            failures.push(format!(
+                "{}: 0x{:08x} is undefined to the emulator",
+                mnemonic, word
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// A fixed sequence of words standing in for arbitrary ones, so a failure
+// can be reproduced
+fn words() -> impl Iterator<Item = u32> {
+    let mut state: u32 = 0x2545_f491;
+    std::iter::from_fn(move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        Some(state)
+    })
+}
+
+// Every word splits into fields that pack back into it, including the
+// golden encodings and every opcode
+#[test]
+fn decode_then_encode_gives_the_word_back() {
+    let golden = GOLDEN.iter().map(|(_, _, word)| *word);
+    let opcodes = (0..64u32).map(|opcode| opcode << 26 | 0x03ff_ffff);
+    for word in golden.chain(opcodes).chain(words().take(100_000)) {
+        assert_eq!(
+            encode(decode(word)),
+            word,
+            "0x{:08x} decoded to {:?}",
+            word,
+            decode(word)
+        );
+    }
+}
+
+// Fields that fit are packed into a word that splits back into them. The
+// layout follows from the opcode, so each instruction is built in the
+// layout its opcode decodes as
+#[test]
+fn encode_then_decode_gives_the_fields_back() {
+    let mut fields = words();
+    let mut field = |bits: u32| (fields.next().unwrap() & ((1 << bits) - 1)) as u8;
+    for _ in 0..100_000 {
+        let opcode = field(6);
+        let instruction = match decode(u32::from(opcode) << 26) {
+            Instruction::R { .. } => Instruction::R {
+                opcode,
+                rs: field(5),
+                rt: field(5),
+                rd: field(5),
+                shamt: field(5),
+                funct: field(6),
+            },
+            Instruction::I { .. } => Instruction::I {
+                opcode,
+                rs: field(5),
+                rt: field(5),
+                imm: u16::from(field(8)) << 8 | u16::from(field(8)),
+            },
+            Instruction::J { .. } => Instruction::J {
+                opcode,
+                target: u32::from(field(8)) << 18
+                    | u32::from(field(8)) << 10
+                    | u32::from(field(8)) << 2
+                    | u32::from(field(2)),
+            },
+            Instruction::C { .. } => Instruction::C {
+                opcode,
+                fmt: field(5),
+                ft: field(5),
+                fs: field(5),
+                fd: field(5),
+                funct: field(6),
+            },
+        };
+        assert_eq!(decode(encode(instruction)), instruction);
+    }
+}
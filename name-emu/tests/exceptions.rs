@@ -0,0 +1,210 @@
+// Once a program has an exception handler, exceptions and interrupts go to
+// it through coprocessor 0: EPC holds the instruction to go back to, Cause
+// says why, BadVAddr the address behind an address error, Status.EXL is set
+// until eret returns, and Count reaching Compare raises the timer interrupt.
+
+mod common;
+
+use name_core::register::Register::{S0, S1, S2, S3, S4, T1};
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::Mips;
+use name_emu::trap::{ExceptionCode, CAUSE_EXC_CODE, CAUSE_TIMER, STATUS_EXL};
+
+// The handler records EPC and Cause, then returns past the faulting
+// instruction. Main notes in $s2 that execution carried on after it.
+const OVERFLOW: &str = r#"
+        .text
+main:   lui $t0, 0x7fff
+        ori $t0, $t0, 0xffff
+fault:  addi $t1, $t0, 1
+        li $s2, 1
+        li $v0, 10
+        syscall
+handler:
+        mfc0 $s0, $14
+        mfc0 $s1, $13
+        addiu $k0, $s0, 4
+        mtc0 $k0, $14
+        eret
+"#;
+
+// Main sets Compare and spins until the handler has run. The handler
+// records Count, as its first instruction sees it, and Cause, turns the
+// timer off by moving Compare out of reach, and returns to the
+// instruction the interrupt came before.
+const TIMER: &str = r#"
+        .text
+main:   li $t0, 20
+        mtc0 $t0, $11
+spin:   addiu $s3, $s3, 1
+        beq $s4, $zero, spin
+        li $v0, 10
+        syscall
+handler:
+        mfc0 $s0, $9
+        mfc0 $s1, $13
+        li $s4, 1
+        mtc0 $zero, $11
+        eret
+"#;
+
+fn with_handler(program: &str) -> Mips {
+    let mut mips = common::machine(program);
+    mips.exception_handler = Some(common::label(program, "handler"));
+    mips
+}
+
+fn exception_code(cause: u32) -> u32 {
+    (cause & CAUSE_EXC_CODE) >> 2
+}
+
+#[test]
+fn handler_sees_epc_and_cause() {
+    let mut mips = with_handler(OVERFLOW);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S0), common::label(OVERFLOW, "fault"));
+    assert_eq!(exception_code(mips.reg(S1)), ExceptionCode::Overflow as u32);
+    assert_eq!(mips.reg(S2), 1);
+    // The destination of the trapping add is left alone
+    assert_eq!(mips.reg(T1), 0);
+}
+
+#[test]
+fn eret_leaves_exception_level() {
+    let mut mips = with_handler(OVERFLOW);
+    let handler = common::label(OVERFLOW, "handler");
+    while mips.pc() != handler {
+        mips.step_one(&mut std::io::sink()).unwrap();
+    }
+    assert_ne!(mips.status & STATUS_EXL, 0);
+
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!(mips.status & STATUS_EXL, 0);
+}
+
+#[test]
+fn exception_without_handler_stops_program() {
+    let mut mips = common::machine(OVERFLOW);
+    let error = common::run_to_end(&mut mips).unwrap_err();
+
+    assert!(matches!(error, ExecutionErrors::ArithmeticOverflow { .. }));
+    assert_eq!(mips.reg(S2), 0);
+}
+
+#[test]
+fn exception_in_handler_stops_program() {
+    let program = OVERFLOW.replace("eret", "break");
+    let mut mips = with_handler(&program);
+    let error = common::run_to_end(&mut mips).unwrap_err();
+
+    assert!(matches!(error, ExecutionErrors::Breakpoint { .. }));
+    assert_eq!(mips.reg(S2), 0);
+}
+
+#[test]
+fn timer_interrupts_when_count_reaches_compare() {
+    let mut mips = with_handler(TIMER);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S4), 1);
+    assert_eq!(
+        exception_code(mips.reg(S1)),
+        ExceptionCode::Interrupt as u32
+    );
+    assert_ne!(mips.reg(S1) & CAUSE_TIMER, 0);
+    assert_eq!(mips.reg(S0), 20);
+    // Writing Compare acknowledged the interrupt
+    assert_eq!(mips.cause & CAUSE_TIMER, 0);
+}
+
+#[test]
+fn timer_interrupt_waits_while_disabled() {
+    let program = TIMER.replace(
+        "main:   li $t0, 20",
+        "main:   mtc0 $zero, $12\n        li $t0, 20",
+    );
+    let mut mips = with_handler(&program);
+    for _ in 0..1000 {
+        mips.step_one(&mut std::io::sink()).unwrap();
+    }
+
+    assert_eq!(mips.reg(S4), 0);
+    assert!(mips.reg(S3) > 100);
+    assert_ne!(mips.cause & CAUSE_TIMER, 0);
+}
+
+#[test]
+fn trapped_syscall_goes_to_handler() {
+    // The handler skips the print, recording the exception, and services
+    // the exit itself, since NAME services syscalls made in the handler
+    let program = r#"
+        .text
+main:   li $v0, 1
+        li $a0, 42
+call:   syscall
+        li $s2, 1
+        li $v0, 10
+        syscall
+handler:
+        li $k1, 10
+        beq $v0, $k1, quit
+        mfc0 $s0, $14
+        mfc0 $s1, $13
+        addiu $k0, $s0, 4
+        mtc0 $k0, $14
+        eret
+quit:   syscall
+"#;
+    let mut mips = with_handler(program);
+    mips.trap_syscalls = true;
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S0), common::label(program, "call"));
+    assert_eq!(exception_code(mips.reg(S1)), ExceptionCode::Syscall as u32);
+    assert_eq!(mips.reg(S2), 1);
+    assert_eq!(mips.exit_code, Some(0));
+}
+
+// Main points EPC two bytes into itself and then makes the jump given in
+// place of JUMP. The handler records EPC, BadVAddr and Cause and exits.
+const BAD_JUMP: &str = r#"
+        .text
+main:   la $t0, main
+        addiu $t0, $t0, 2
+        mtc0 $t0, $14
+jump:   JUMP
+handler:
+        mfc0 $s0, $14
+        mfc0 $s1, $8
+        mfc0 $s2, $13
+        li $v0, 10
+        syscall
+"#;
+
+// Runs BAD_JUMP with `jump`, returning EPC, BadVAddr and the exception code
+// the handler saw
+fn bad_jump(jump: &str) -> (u32, u32, u32) {
+    let program = BAD_JUMP.replace("JUMP", jump);
+    let mut mips = with_handler(&program);
+    common::run_to_end(&mut mips).unwrap();
+    (mips.reg(S0), mips.reg(S1), exception_code(mips.reg(S2)))
+}
+
+#[test]
+fn bad_fetch_goes_to_handler() {
+    // Each jump is one instruction, so the labels are the same for all
+    let program = BAD_JUMP.replace("JUMP", "eret");
+    let main = common::label(&program, "main");
+    let jump = common::label(&program, "jump");
+    let address_error = ExceptionCode::AddressErrorLoad as u32;
+
+    // The fetch from where the jump lands faults, so EPC is that address
+    assert_eq!(bad_jump("eret"), (main + 2, main + 2, address_error));
+    assert_eq!(
+        bad_jump("j main+0x100000"),
+        (main + 0x100000, main + 0x100000, address_error)
+    );
+    // jr checks its target, so the jump itself faults
+    assert_eq!(bad_jump("jr $t0"), (jump, main + 2, address_error));
+}
@@ -0,0 +1,69 @@
+// Batch grading takes each program or folder in a submissions directory as
+// one submission, and nothing else: not the rubric kept alongside them, and
+// not stray notes or other files.
+
+mod common;
+
+use std::fs;
+
+const RUBRIC: &str = r#"
+expected_output = "hi\n"
+output_points = 1
+"#;
+
+const PROGRAM: &str = r#"
+        .data
+greeting: .asciiz "hi\n"
+        .text
+main:   la $a0, greeting
+        li $v0, 4
+        syscall
+        li $v0, 10
+        syscall
+"#;
+
+#[test]
+fn batch_grades_only_programs() {
+    let dir = common::temp_dir("grade");
+    let rubric = dir.join("rubric.toml");
+    fs::write(&rubric, RUBRIC).unwrap();
+    fs::write(dir.join("alice.asm"), PROGRAM).unwrap();
+    fs::write(dir.join("bob.s"), PROGRAM).unwrap();
+    fs::write(dir.join("notes.txt"), "grade these by Friday").unwrap();
+    fs::write(dir.join("README"), "").unwrap();
+    fs::create_dir(dir.join("carol")).unwrap();
+    fs::write(dir.join("carol").join("main.asm"), PROGRAM).unwrap();
+
+    let output = common::name(
+        &[
+            "grade".as_ref(),
+            rubric.as_os_str(),
+            "--batch".as_ref(),
+            dir.as_os_str(),
+            "--csv".as_ref(),
+        ],
+        "",
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let submissions: Vec<&str> = csv
+        .lines()
+        .skip(1)
+        .map(|row| row.split(',').next().unwrap())
+        .collect();
+    assert_eq!(submissions, ["alice.asm", "bob.s", "carol"], "{}", csv);
+    // Each one ran and printed the expected output
+    assert!(
+        csv.lines()
+            .skip(1)
+            .all(|row| row.split(',').nth(1) == Some("1")),
+        "{}",
+        csv
+    );
+}
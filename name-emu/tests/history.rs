@@ -0,0 +1,71 @@
+// Stepping back through the history has to retrace the program exactly:
+// every undo leaves the machine as it was before that step, heap growth
+// included, and a full history forgets its oldest steps first.
+
+mod common;
+
+use name_emu::history::History;
+use name_emu::mips::Mips;
+use std::io;
+
+const PROGRAM: &str = r#"
+        .data
+counter: .word 0
+        .text
+main:   li $v0, 9
+        li $a0, 64
+        syscall
+        addu $s0, $v0, $zero
+        la $t0, counter
+        li $t1, 0
+loop:   addi $t1, $t1, 1
+        sw $t1, 0($t0)
+        sb $t1, 3($s0)
+        addi $sp, $sp, -4
+        sw $t1, 0($sp)
+        j loop
+"#;
+
+fn step(mips: &mut Mips, history: &mut History) {
+    history.begin(mips);
+    mips.step_one(&mut io::sink()).unwrap();
+    history.finish(mips);
+}
+
+#[test]
+fn undo_retraces_every_step() {
+    let mut mips = common::machine(PROGRAM);
+    let mut history = History::new(100);
+    let mut before = vec![];
+    for _ in 0..40 {
+        before.push(mips.snapshot());
+        step(&mut mips, &mut history);
+    }
+    assert_eq!(history.len(), 40);
+
+    while let Some(expected) = before.pop() {
+        assert!(history.undo(&mut mips));
+        assert_eq!(mips.snapshot(), expected);
+    }
+    assert!(!history.undo(&mut mips));
+}
+
+#[test]
+fn full_history_forgets_oldest_steps() {
+    let mut mips = common::machine(PROGRAM);
+    let mut history = History::new(5);
+    for _ in 0..20 {
+        step(&mut mips, &mut history);
+    }
+    let after_fifteen = {
+        let mut replay = common::machine(PROGRAM);
+        for _ in 0..15 {
+            replay.step_one(&mut io::sink()).unwrap();
+        }
+        replay.snapshot()
+    };
+
+    assert_eq!(history.len(), 5);
+    while history.undo(&mut mips) {}
+    assert_eq!(mips.snapshot(), after_fifteen);
+}
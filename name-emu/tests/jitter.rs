@@ -0,0 +1,51 @@
+// Jitter is random but reproducible: a run with the same probability and
+// seed stalls and interrupts at the same places, and a different seed
+// goes differently.
+
+mod common;
+
+use name_core::register::Register::{S0, S1};
+use name_emu::jitter::Jitter;
+use name_emu::mips::Mips;
+
+// Counts loop iterations in $s0 and interrupts in $s1, recording Count in
+// $s2 each time round, which stalls move along. The handler acknowledges
+// the timer by writing Compare back as it was
+const PROGRAM: &str = r#"
+        .text
+main:   li $t0, 2000
+loop:   addiu $s0, $s0, 1
+        mfc0 $s2, $9
+        addiu $t0, $t0, -1
+        bgtz $t0, loop
+        li $v0, 10
+        syscall
+handler:
+        addiu $s1, $s1, 1
+        mfc0 $k0, $11
+        mtc0 $k0, $11
+        eret
+"#;
+
+fn run(seed: u32) -> Mips {
+    let mut mips = common::machine(PROGRAM);
+    mips.exception_handler = Some(common::label(PROGRAM, "handler"));
+    mips.add_hook(Box::new(Jitter::new(0.05, seed)));
+    common::run_to_end(&mut mips).unwrap();
+    mips
+}
+
+#[test]
+fn same_seed_runs_the_same() {
+    let first = run(7);
+    let second = run(7);
+
+    assert_eq!(first.reg(S0), 2000);
+    assert!(first.reg(S1) > 0);
+    assert_eq!(first.snapshot(), second.snapshot());
+}
+
+#[test]
+fn different_seeds_run_differently() {
+    assert_ne!(run(7).snapshot(), run(8).snapshot());
+}
@@ -0,0 +1,204 @@
+// Programs built for Linux read and write through the o32 syscalls, with
+// errors reported through $a3 and an errno in $v0, and a read returns every
+// byte of the input however small the buffer it is given, even in the
+// middle of a character.
+
+mod common;
+
+use name_core::register::Register::{A3, S0, S1, S2, S3, S4};
+use name_emu::mips::{Mips, DOT_DATA_START_ADDRESS, HEAP_START_ADDRESS};
+use name_emu::syscall::BufferConsole;
+
+// Runs `program` with the Linux ABI and `input` on the console, returning
+// the machine and what the program wrote
+fn run(program: &str, input: &str) -> (Mips, String) {
+    let mut mips = common::machine(program);
+    mips.enable_linux_abi();
+    let console = BufferConsole::new(input);
+    let output = console.output.clone();
+    mips.console = Box::new(console);
+    common::run_to_end(&mut mips).unwrap();
+    let output = output.borrow().clone();
+    (mips, output)
+}
+
+// Reads three times, a byte at a time, into `buffer`, keeping the total
+// number of bytes read in $s2 and the last result in $s1
+const READ_BYTES: &str = r#"
+        .data
+buffer: .space 4
+        .text
+main:   la $s0, buffer
+        li $s3, 3
+loop:   li $v0, 4003
+        li $a0, 0
+        addu $a1, $s0, $zero
+        li $a2, 1
+        syscall
+        addu $s1, $v0, $zero
+        addu $s2, $s2, $v0
+        addiu $s0, $s0, 1
+        addiu $s3, $s3, -1
+        bne $s3, $zero, loop
+        li $v0, 4001
+        li $a0, 0
+        syscall
+"#;
+
+#[test]
+fn read_keeps_bytes_that_do_not_fit() {
+    let (mips, _) = run(READ_BYTES, "é\n");
+
+    assert_eq!(mips.reg(S2), 3);
+    assert_eq!(mips.reg(S1), 1);
+    let bytes: Vec<u8> = (0..3)
+        .map(|i| mips.read_b(DOT_DATA_START_ADDRESS + i).unwrap())
+        .collect();
+    assert_eq!(bytes, "é\n".as_bytes());
+}
+
+#[test]
+fn read_returns_zero_at_end_of_input() {
+    let (mips, _) = run(READ_BYTES, "a");
+
+    assert_eq!(mips.reg(S2), 1);
+    assert_eq!(mips.reg(S1), 0);
+    assert_eq!(mips.read_b(DOT_DATA_START_ADDRESS).unwrap(), b'a');
+}
+
+#[test]
+fn write_to_standard_output_and_error() {
+    let program = r#"
+        .data
+text:   .ascii "out err"
+        .text
+main:   li $v0, 4004
+        li $a0, 1
+        la $a1, text
+        li $a2, 4
+        syscall
+        addu $s0, $v0, $zero
+        li $v0, 4004
+        li $a0, 2
+        la $a1, text
+        addiu $a1, $a1, 4
+        li $a2, 3
+        syscall
+        li $v0, 4246
+        li $a0, 0
+        syscall
+"#;
+    let (mips, output) = run(program, "");
+
+    assert_eq!(output, "out err");
+    assert_eq!(mips.reg(S0), 4);
+    assert_eq!(mips.reg(A3), 0);
+}
+
+#[test]
+fn failed_calls_set_a3_and_errno() {
+    // Writing to a descriptor that isn't open, then a call that isn't
+    // implemented
+    let program = r#"
+        .text
+main:   li $v0, 4004
+        li $a0, 7
+        li $a1, 0
+        li $a2, 1
+        syscall
+        addu $s0, $v0, $zero
+        addu $s1, $a3, $zero
+        li $v0, 4999
+        syscall
+        addu $s2, $v0, $zero
+        addu $s3, $a3, $zero
+        li $v0, 4001
+        li $a0, 0
+        syscall
+"#;
+    let (mips, _) = run(program, "");
+
+    assert_eq!((mips.reg(S0), mips.reg(S1)), (9, 1));
+    assert_eq!((mips.reg(S2), mips.reg(S3)), (89, 1));
+}
+
+#[test]
+fn exit_status() {
+    let program = r#"
+        .text
+main:   li $v0, 4001
+        li $a0, 7
+        syscall
+        li $s0, 1
+"#;
+    let (mips, _) = run(program, "");
+
+    assert_eq!(mips.exit_code, Some(7));
+    assert_eq!(mips.reg(S0), 0);
+}
+
+#[test]
+fn brk_moves_the_end_of_the_heap() {
+    // brk(0) asks where the heap ends, then the program moves it up 64
+    // bytes and uses the memory
+    let program = r#"
+        .text
+main:   li $v0, 4045
+        li $a0, 0
+        syscall
+        addu $s0, $v0, $zero
+        li $v0, 4045
+        addiu $a0, $s0, 64
+        syscall
+        addu $s1, $v0, $zero
+        li $t0, 5
+        sw $t0, 60($s0)
+        lw $s2, 60($s0)
+        li $v0, 4045
+        li $a0, 1
+        syscall
+        addu $s4, $v0, $zero
+        li $v0, 4001
+        li $a0, 0
+        syscall
+"#;
+    let (mips, _) = run(program, "");
+
+    assert_eq!(mips.reg(S0), HEAP_START_ADDRESS);
+    assert_eq!(mips.reg(S1), HEAP_START_ADDRESS + 64);
+    assert_eq!(mips.reg(S2), 5);
+    // An address it can't move to leaves the heap where it was
+    assert_eq!(mips.reg(S4), HEAP_START_ADDRESS + 64);
+}
+
+#[test]
+fn read_stops_at_end_of_line() {
+    let program = r#"
+        .data
+buffer: .space 32
+        .text
+main:   li $v0, 4003
+        li $a0, 0
+        la $a1, buffer
+        li $a2, 32
+        syscall
+        addu $s0, $v0, $zero
+        li $v0, 4003
+        li $a0, 0
+        la $a1, buffer
+        li $a2, 32
+        syscall
+        addu $s1, $v0, $zero
+        li $v0, 4001
+        li $a0, 0
+        syscall
+"#;
+    let (mips, _) = run(program, "first\nsecond\n");
+
+    assert_eq!(mips.reg(S0), 6);
+    assert_eq!(mips.reg(S1), 7);
+    let bytes: Vec<u8> = (0..7)
+        .map(|i| mips.read_b(DOT_DATA_START_ADDRESS + i).unwrap())
+        .collect();
+    assert_eq!(bytes, b"second\n");
+}
@@ -0,0 +1,13 @@
+// Every pseudo-instruction in the table `name mars-check` keeps expands
+// into the same words as in MARS, so exercises that check the exact words a
+// program assembles to mark the same answers right under NAME.
+
+mod common;
+
+#[test]
+fn pseudo_instructions_expand_as_in_mars() {
+    let output = common::name(&["mars-check"], "");
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", report);
+    assert!(!report.contains("differs"), "{}", report);
+}
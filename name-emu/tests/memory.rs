@@ -0,0 +1,235 @@
+// Loads sign- or zero-extend what they read, stores write only the bytes
+// they cover, loads and stores from a label reach it wherever it is, ll
+// and sc pair up, and a halfword or word access at an
+// address that is not a multiple of its size is an error. So is taking
+// the stack down into the heap, and the instruction that does it has no
+// effect.
+
+mod common;
+
+use name_core::register::Register::{Sp, S0, S1, S2, S3, S4, S5, S6, T1};
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::{DOT_DATA_START_ADDRESS, HEAP_START_ADDRESS};
+use std::io;
+
+const LOADS: &str = r#"
+        .data
+bytes:  .byte 0x80, 0x7f, 0xff, 0x01
+halves: .half 0x8001, 0x7ffe
+word:   .word 0xdeadbeef
+        .text
+main:   la $t0, bytes
+        lb $s0, 0($t0)
+        lbu $s1, 0($t0)
+        lb $s2, 1($t0)
+        la $t0, halves
+        lh $s3, 0($t0)
+        lhu $s4, 0($t0)
+        lh $s5, 2($t0)
+        lw $s6, 4($t0)
+        li $v0, 10
+        syscall
+"#;
+
+#[test]
+fn loads_extend_what_they_read() {
+    let mut mips = common::machine(LOADS);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S0), 0xffffff80);
+    assert_eq!(mips.reg(S1), 0x80);
+    assert_eq!(mips.reg(S2), 0x7f);
+    assert_eq!(mips.reg(S3), 0xffff8001);
+    assert_eq!(mips.reg(S4), 0x8001);
+    assert_eq!(mips.reg(S5), 0x7ffe);
+    assert_eq!(mips.reg(S6), 0xdeadbeef);
+}
+
+#[test]
+fn stores_write_only_their_bytes() {
+    let program = r#"
+        .data
+buffer: .word 0, 0, 0
+        .text
+main:   la $t0, buffer
+        li $t1, 0x11223344
+        sw $t1, 0($t0)
+        sh $t1, 4($t0)
+        sb $t1, 10($t0)
+        addiu $sp, $sp, -4
+        sw $t1, 0($sp)
+        lw $s0, 0($sp)
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+
+    let bytes: Vec<u8> = (0..12)
+        .map(|i| mips.read_b(DOT_DATA_START_ADDRESS + i).unwrap())
+        .collect();
+    // Little-endian, as assembled by default
+    assert_eq!(
+        bytes,
+        [0x44, 0x33, 0x22, 0x11, 0x44, 0x33, 0, 0, 0, 0, 0x44, 0]
+    );
+    assert_eq!(mips.read_w(DOT_DATA_START_ADDRESS).unwrap(), 0x11223344);
+    assert_eq!(mips.reg(S0), 0x11223344);
+}
+
+#[test]
+fn labels_past_32k_are_reached() {
+    // The lower half of far's address has its top bit set, so it is a
+    // negative offset and the upper half in $at has to be one more
+    let program = r#"
+        .data
+near:   .word 7
+        .space 0x8000
+far:    .word 0, 0
+        .text
+main:   lw $s0, near
+        sw $s0, far
+        li $t1, 4
+        sw $s0, far($t1)
+        lw $s1, far
+        lw $s2, far($t1)
+        li $v0, 10
+        syscall
+"#;
+    let far = common::label(program, "far");
+    assert!(far & 0x8000 != 0);
+
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+    assert_eq!(mips.reg(S0), 7);
+    assert_eq!(mips.reg(S1), 7);
+    assert_eq!(mips.reg(S2), 7);
+}
+
+#[test]
+fn store_conditional_succeeds_after_load_linked() {
+    let program = r#"
+        .data
+lock:   .word 5
+        .text
+main:   la $t0, lock
+        ll $s0, 0($t0)
+        addiu $s1, $s0, 1
+        sc $s1, 0($t0)
+        lw $s2, 0($t0)
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    common::run_to_end(&mut mips).unwrap();
+
+    assert_eq!(mips.reg(S0), 5);
+    assert_eq!(mips.reg(S1), 1);
+    assert_eq!(mips.reg(S2), 6);
+}
+
+// Runs `access` with $t0 one byte into a word of data, returning the error
+fn misaligned(access: &str) -> ExecutionErrors {
+    let program = format!(
+        r#"
+        .data
+buffer: .word 0, 0
+        .text
+main:   la $t0, buffer
+        addiu $t0, $t0, 1
+        li $t1, 7
+        {access}
+        li $v0, 10
+        syscall
+"#
+    );
+    let mut mips = common::machine(&program);
+    let error = common::run_to_end(&mut mips).unwrap_err();
+    // Nothing was loaded
+    assert_eq!(mips.reg(T1), 7, "{}", access);
+    error
+}
+
+#[test]
+fn misaligned_access_is_an_error() {
+    let address = DOT_DATA_START_ADDRESS + 1;
+    assert_eq!(
+        misaligned("lw $t1, 0($t0)"),
+        ExecutionErrors::MemoryUnalignedAccess {
+            load_address: address,
+            size: 4,
+            store: false
+        }
+    );
+    assert_eq!(
+        misaligned("lhu $t1, 0($t0)"),
+        ExecutionErrors::MemoryUnalignedAccess {
+            load_address: address,
+            size: 2,
+            store: false
+        }
+    );
+    assert_eq!(
+        misaligned("sw $t1, 0($t0)"),
+        ExecutionErrors::MemoryUnalignedAccess {
+            load_address: address,
+            size: 4,
+            store: true
+        }
+    );
+    assert_eq!(
+        misaligned("sh $t1, 2($t0)"),
+        ExecutionErrors::MemoryUnalignedAccess {
+            load_address: address + 2,
+            size: 2,
+            store: true
+        }
+    );
+}
+
+#[test]
+fn access_outside_memory_is_an_error() {
+    let program = r#"
+        .text
+main:   li $t0, 0x10
+        lw $t1, 0($t0)
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    let error = common::run_to_end(&mut mips).unwrap_err();
+    assert!(
+        matches!(
+            error,
+            ExecutionErrors::MemoryIllegalAccess { load_address: 0x10 }
+        ),
+        "{:?}",
+        error
+    );
+}
+
+#[test]
+fn stack_into_heap_is_refused_each_time() {
+    let program = r#"
+        .text
+main:   li $sp, 0x10040010
+push:   addiu $sp, $sp, -32
+        li $v0, 10
+        syscall
+"#;
+    let mut mips = common::machine(program);
+    let push = common::label(program, "push");
+    let collision = ExecutionErrors::HeapStackCollision {
+        heap_break: HEAP_START_ADDRESS,
+        stack_low: HEAP_START_ADDRESS - 0x10,
+    };
+    assert_eq!(common::run_to_end(&mut mips), Err(collision));
+    assert_eq!(mips.pc(), push);
+    assert_eq!(mips.reg(Sp), HEAP_START_ADDRESS + 0x10);
+
+    // Retrying, as a debugger continuing from the error does, fails the
+    // same way rather than pushing another 32 bytes
+    assert_eq!(mips.step_one(&mut io::sink()), Err(collision));
+    assert_eq!(mips.pc(), push);
+    assert_eq!(mips.reg(Sp), HEAP_START_ADDRESS + 0x10);
+}
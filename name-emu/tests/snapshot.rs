@@ -0,0 +1,62 @@
+// A snapshot taken partway through a program, restored after the program
+// has gone on to change registers and memory, has to put the machine back
+// exactly as it was, and has to survive being written out as JSON.
+
+mod common;
+
+use name_core::machine::MachineState;
+use name_emu::mips::Mips;
+use std::io;
+
+const PROGRAM: &str = r#"
+        .data
+counter: .word 0
+        .text
+main:   la $t0, counter
+        li $t1, 0
+loop:   addi $t1, $t1, 1
+        sw $t1, 0($t0)
+        mult $t1, $t1
+        addi $sp, $sp, -4
+        sw $t1, 0($sp)
+        j loop
+"#;
+
+fn run(mips: &mut Mips, steps: usize) {
+    for _ in 0..steps {
+        mips.step_one(&mut io::sink()).unwrap();
+    }
+}
+
+#[test]
+fn restore_undoes_execution() {
+    let mut mips = common::machine(PROGRAM);
+    run(&mut mips, 20);
+    let checkpoint = mips.snapshot();
+
+    run(&mut mips, 35);
+    assert_ne!(mips.snapshot(), checkpoint);
+
+    mips.restore(&checkpoint);
+    assert_eq!(mips.snapshot(), checkpoint);
+
+    // Running on from a restored machine goes the same way as the first time
+    run(&mut mips, 35);
+    let once = mips.snapshot();
+    mips.restore(&checkpoint);
+    run(&mut mips, 35);
+    assert_eq!(mips.snapshot(), once);
+}
+
+#[test]
+fn snapshot_survives_json() {
+    let mut mips = common::machine(PROGRAM);
+    run(&mut mips, 30);
+    let snapshot = mips.snapshot();
+    let json = snapshot.to_json().unwrap();
+    assert_eq!(MachineState::from_json(&json).unwrap(), snapshot);
+
+    let mut fresh = common::machine(PROGRAM);
+    fresh.restore(&MachineState::from_json(&json).unwrap());
+    assert_eq!(fresh.snapshot(), snapshot);
+}
@@ -0,0 +1,185 @@
+// Programs talk to the console and the heap through the MARS syscalls:
+// the service number goes in $v0, arguments in $a0 and $a1, and results
+// come back in $v0.
+
+mod common;
+
+use name_core::register::Register::{S0, S1, S2, V0};
+use name_emu::exception::ExecutionErrors;
+use name_emu::mips::{Mips, HEAP_START_ADDRESS};
+use name_emu::syscall::BufferConsole;
+
+// Runs `program` with `input` on the console, returning the machine, what
+// the program printed, and the error it stopped with, if any
+fn run(program: &str, input: &str) -> (Mips, String, Option<ExecutionErrors>) {
+    let mut mips = common::machine(program);
+    let console = BufferConsole::new(input);
+    let output = console.output.clone();
+    mips.console = Box::new(console);
+    let error = common::run_to_end(&mut mips).err();
+    let output = output.borrow().clone();
+    (mips, output, error)
+}
+
+#[test]
+fn print_services() {
+    let program = r#"
+        .data
+hello:  .asciiz "hello "
+        .text
+main:   la $a0, hello
+        li $v0, 4
+        syscall
+        li $a0, -42
+        li $v0, 1
+        syscall
+        li $a0, 32
+        li $v0, 11
+        syscall
+        li $a0, 255
+        li $v0, 34
+        syscall
+        li $a0, 32
+        li $v0, 11
+        syscall
+        li $a0, -1
+        li $v0, 36
+        syscall
+        li $v0, 10
+        syscall
+"#;
+    let (mips, output, error) = run(program, "");
+
+    assert_eq!(error, None);
+    assert_eq!(output, "hello -42 0x000000ff 4294967295");
+    assert_eq!(mips.exit_code, Some(0));
+}
+
+#[test]
+fn read_services() {
+    let program = r#"
+        .data
+buffer: .space 16
+        .text
+main:   li $v0, 5
+        syscall
+        addu $s0, $v0, $zero
+        li $v0, 12
+        syscall
+        addu $s1, $v0, $zero
+        li $v0, 12
+        syscall
+        la $a0, buffer
+        li $a1, 16
+        li $v0, 8
+        syscall
+        la $a0, buffer
+        li $v0, 4
+        syscall
+        li $v0, 10
+        syscall
+"#;
+    let (mips, output, error) = run(program, " -17\nx\nline of text\n");
+
+    assert_eq!(error, None);
+    assert_eq!(mips.reg(S0), (-17i32) as u32);
+    assert_eq!(mips.reg(S1), 'x' as u32);
+    // read string keeps the newline and null-terminates
+    assert_eq!(output, "line of text\n");
+}
+
+#[test]
+fn read_string_stops_at_buffer_size() {
+    let program = r#"
+        .data
+buffer: .space 8
+        .text
+main:   la $a0, buffer
+        li $a1, 5
+        li $v0, 8
+        syscall
+        la $a0, buffer
+        li $v0, 4
+        syscall
+        li $v0, 10
+        syscall
+"#;
+    let (_, output, error) = run(program, "truncated\n");
+
+    assert_eq!(error, None);
+    assert_eq!(output, "trun");
+}
+
+#[test]
+fn read_int_rejects_other_input() {
+    let program = r#"
+        .text
+main:   li $v0, 5
+        syscall
+        li $v0, 10
+        syscall
+"#;
+    let (_, _, error) = run(program, "twelve\n");
+    assert_eq!(
+        error,
+        Some(ExecutionErrors::SyscallInputError { service: 5 })
+    );
+}
+
+#[test]
+fn sbrk_hands_out_consecutive_blocks() {
+    let program = r#"
+        .text
+main:   li $a0, 16
+        li $v0, 9
+        syscall
+        addu $s0, $v0, $zero
+        li $a0, 8
+        li $v0, 9
+        syscall
+        addu $s1, $v0, $zero
+        li $t0, 77
+        sw $t0, 4($s1)
+        lw $s2, 4($s1)
+        li $v0, 10
+        syscall
+"#;
+    let (mips, _, error) = run(program, "");
+
+    assert_eq!(error, None);
+    assert_eq!(mips.reg(S0), HEAP_START_ADDRESS);
+    assert_eq!(mips.reg(S1), HEAP_START_ADDRESS + 16);
+    assert_eq!(mips.reg(S2), 77);
+}
+
+#[test]
+fn exit_stops_the_program() {
+    let program = r#"
+        .text
+main:   li $a0, 3
+        li $v0, 17
+        syscall
+        li $s0, 1
+"#;
+    let (mips, _, error) = run(program, "");
+
+    assert_eq!(error, None);
+    assert_eq!(mips.exit_code, Some(3));
+    assert_eq!(mips.reg(S0), 0);
+}
+
+#[test]
+fn unknown_service_is_an_error() {
+    let program = r#"
+        .text
+main:   li $v0, 1000
+        syscall
+"#;
+    let (mips, _, error) = run(program, "");
+
+    assert_eq!(
+        error,
+        Some(ExecutionErrors::UnknownSyscall { service: 1000 })
+    );
+    assert_eq!(mips.reg(V0), 1000);
+}
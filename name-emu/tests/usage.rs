@@ -0,0 +1,40 @@
+// Running `name` without a command lists every command it has, so that the
+// list is where a new user finds out what NAME can do.
+
+mod common;
+
+const COMMANDS: &[&str] = &[
+    "run",
+    "debug",
+    "data",
+    "tui",
+    "map",
+    "link",
+    "inspect",
+    "grade",
+    "isa-report",
+    "mars-check",
+    "verify",
+    "import-mars",
+    "doctor",
+    "learn",
+    "usage-report",
+];
+
+#[test]
+fn usage_lists_every_command() {
+    let output = common::name::<&str>(&[], "");
+    assert!(!output.status.success());
+
+    let usage = String::from_utf8(output.stderr).unwrap();
+    for command in COMMANDS {
+        assert!(
+            usage
+                .lines()
+                .any(|line| line.trim_start().starts_with(&format!("{} ", command))),
+            "{} is missing from\n{}",
+            command,
+            usage
+        );
+    }
+}
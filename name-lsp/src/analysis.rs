@@ -0,0 +1,249 @@
+// What the server knows about one document. Each request works from the
+// document's current text, parsing or assembling it again, which for
+// programs the size students write is quicker than keeping state in step
+// with edits
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, DocumentSymbol, Location, NumberOrString,
+    Position, Range, SymbolKind, Url,
+};
+use name_as::directive::SectionKind;
+use name_as::error::AssemblerError;
+use name_as::nma::{assemble_source, parse_source, AssembledObject, AssemblerOptions};
+use name_as::outline::{definitions, name_at, Definition, DefinitionKind};
+use name_core::diagnostic::{Diagnostic, Severity, Span};
+use name_core::memmap;
+
+/// Assembles `text` the way name-as would with no config file
+fn assemble(uri: &Url, text: &str) -> Result<AssembledObject, AssemblerError> {
+    let options = AssemblerOptions {
+        file_name: file_name(uri),
+        ..Default::default()
+    };
+    assemble_source(text, &options)
+}
+
+fn file_name(uri: &Url) -> String {
+    uri.to_file_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| uri.to_string())
+}
+
+/// `length` characters from `column` of `line`, both counted from 1 as
+/// the assembler does, to the editor's positions, counted from 0
+fn range(line: usize, column: usize, length: usize) -> Range {
+    let start = Position::new(
+        line.saturating_sub(1) as u32,
+        column.saturating_sub(1) as u32,
+    );
+    Range::new(
+        start,
+        Position::new(start.line, start.character + length as u32),
+    )
+}
+
+fn span_range(span: &Span) -> Range {
+    range(span.line, span.column, span.length)
+}
+
+fn definition_range(definition: &Definition) -> Range {
+    let name = &definition.name;
+    range(name.line, name.column, name.text.chars().count())
+}
+
+fn to_lsp_diagnostic(uri: &Url, diagnostic: Diagnostic) -> lsp_types::Diagnostic {
+    let message = match &diagnostic.help {
+        Some(help) => format!("{}\nhelp: {}", diagnostic.message, help),
+        None => diagnostic.message,
+    };
+    let related: Vec<DiagnosticRelatedInformation> = diagnostic
+        .notes
+        .iter()
+        .map(|note| DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), span_range(&note.span)),
+            message: note.message.clone(),
+        })
+        .collect();
+    lsp_types::Diagnostic {
+        range: diagnostic.span.as_ref().map(span_range).unwrap_or_default(),
+        severity: Some(match diagnostic.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+        }),
+        code: Some(NumberOrString::String(diagnostic.code)),
+        source: Some("name-as".to_string()),
+        message,
+        related_information: (!related.is_empty()).then_some(related),
+        ..Default::default()
+    }
+}
+
+/// The error that stops `text` assembling, or every warning if it does
+pub fn diagnostics(uri: &Url, text: &str) -> Vec<lsp_types::Diagnostic> {
+    let found = match assemble(uri, text) {
+        Ok(assembled) => assembled
+            .warnings
+            .iter()
+            .map(|warning| warning.to_diagnostic())
+            .collect(),
+        Err(error) => vec![error.to_diagnostic()],
+    };
+    found
+        .into_iter()
+        .map(|diagnostic| to_lsp_diagnostic(uri, diagnostic))
+        .collect()
+}
+
+/// The labels and constants `text` defines, or none if it does not parse
+fn definitions_in(text: &str) -> Vec<Definition> {
+    parse_source(text)
+        .map(|sequence| definitions(&sequence))
+        .unwrap_or_default()
+}
+
+/// The name at `position`, if there is one
+fn name_at_position(text: &str, position: Position) -> Option<String> {
+    let line = text.lines().nth(position.line as usize)?;
+    name_at(line, position.character as usize + 1).map(|(_, name)| name)
+}
+
+/// Where the label or constant at `position` is defined. A constant set
+/// more than once with `.set` has each of its definitions
+pub fn definition(uri: &Url, text: &str, position: Position) -> Vec<Location> {
+    let Some(name) = name_at_position(text, position) else {
+        return vec![];
+    };
+    definitions_in(text)
+        .iter()
+        .filter(|definition| definition.name.text == name)
+        .map(|definition| Location::new(uri.clone(), definition_range(definition)))
+        .collect()
+}
+
+/// What to show for `position`: what the label or constant there stands
+/// for, or else how the instructions on its line were encoded
+pub fn hover(uri: &Url, text: &str, position: Position) -> Option<String> {
+    let assembled = assemble(uri, text).ok();
+    if let Some(name) = name_at_position(text, position) {
+        if let Some(about) = describe_name(&name, text, assembled.as_ref()) {
+            return Some(about);
+        }
+    }
+    encodings(assembled.as_ref()?, position.line + 1)
+}
+
+fn describe_name(name: &str, text: &str, assembled: Option<&AssembledObject>) -> Option<String> {
+    let definitions = definitions_in(text);
+    match definitions
+        .iter()
+        .find(|definition| definition.name.text == name)
+    {
+        Some(Definition {
+            kind: DefinitionKind::Label(section),
+            ..
+        }) => {
+            let address = assembled
+                .and_then(|assembled| assembled.symbols.iter().find(|symbol| symbol.name == name))
+                .map_or(String::new(), |symbol| {
+                    format!(" at `0x{:08x}`", symbol.address)
+                });
+            Some(format!(
+                "label `{}` in `{}`{}",
+                name,
+                section.name(),
+                address
+            ))
+        }
+        Some(Definition {
+            kind: DefinitionKind::Constant(value),
+            ..
+        }) => Some(format!("constant `{}` = `{}`", name, value.as_str())),
+        None => memmap::CONSTANTS
+            .iter()
+            .find(|(constant, _)| *constant == name)
+            .map(|(constant, value)| {
+                format!(
+                    "constant `{}` = `0x{:08x}`, from the memory map",
+                    constant, value
+                )
+            }),
+    }
+}
+
+/// The address, encoding and fields of each instruction assembled from
+/// `line` (counted from 1), as a pseudo-instruction may become several
+fn encodings(assembled: &AssembledObject, line: u32) -> Option<String> {
+    let text = assembled.text();
+    let rows: Vec<String> = assembled
+        .lineinfo
+        .lines
+        .iter()
+        .filter(|row| row.span.line == line)
+        .filter_map(|row| {
+            let offset = row.instr_addr.checked_sub(assembled.text_address())? as usize;
+            let bytes = text.get(offset..offset + 4)?;
+            let word = assembled.endian.u32_from_bytes(bytes.try_into().ok()?);
+            Some(format!(
+                "0x{:08x}  0x{:08x}  {}\n                        {}",
+                row.instr_addr,
+                word,
+                row.line_contents,
+                fields(word)
+            ))
+        })
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    Some(format!("```text\n{}\n```", rows.join("\n")))
+}
+
+/// `word` in binary, split into the fields of its instruction format
+fn fields(word: u32) -> String {
+    let widths: &[u32] = match word >> 26 {
+        // Register-type, including the coprocessor and SPECIAL2 opcodes
+        0x00 | 0x10..=0x13 | 0x1c => &[6, 5, 5, 5, 5, 6],
+        // Jumps
+        0x02 | 0x03 => &[6, 26],
+        // Immediate-type
+        _ => &[6, 5, 5, 16],
+    };
+    let mut shift = 32;
+    widths
+        .iter()
+        .map(|width| {
+            shift -= width;
+            format!(
+                "{:0width$b}",
+                (word >> shift) & ((1 << width) - 1),
+                width = *width as usize
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The labels and constants of `text`, for the editor's outline
+#[allow(deprecated)]
+pub fn document_symbols(text: &str) -> Vec<DocumentSymbol> {
+    definitions_in(text)
+        .into_iter()
+        .map(|definition| {
+            let (kind, detail) = match &definition.kind {
+                DefinitionKind::Label(SectionKind::Text) => (SymbolKind::FUNCTION, ".text"),
+                DefinitionKind::Label(SectionKind::Data) => (SymbolKind::VARIABLE, ".data"),
+                DefinitionKind::Constant(value) => (SymbolKind::CONSTANT, value.as_str()),
+            };
+            let range = definition_range(&definition);
+            DocumentSymbol {
+                name: definition.name.text.clone(),
+                detail: Some(detail.to_string()),
+                kind,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: range,
+                children: None,
+            }
+        })
+        .collect()
+}
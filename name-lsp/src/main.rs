@@ -0,0 +1,200 @@
+// name-lsp: a language server for NAME's MIPS assembly, for editors that
+// speak the Language Server Protocol over stdin and stdout. As a document
+// is typed it is assembled and its errors and warnings are published.
+// Labels and constants can be jumped to from where they are used, hovering
+// shows what a name stands for or how the instructions on a line were
+// encoded, and the outline lists every label and constant.
+mod analysis;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, PublishDiagnostics,
+};
+use lsp_types::request::{DocumentSymbolRequest, GotoDefinition, HoverRequest};
+use lsp_types::{
+    DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, HoverProviderCapability, MarkupContent, MarkupKind, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Url,
+};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+type DynResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
+
+// The text of every open document
+type Documents = BTreeMap<Url, String>;
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        // Documents are sent whole on every change, as they are small
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+fn publish_diagnostics(connection: &Connection, uri: &Url, text: &str) -> DynResult<()> {
+    let params = PublishDiagnosticsParams::new(uri.clone(), analysis::diagnostics(uri, text), None);
+    notify::<PublishDiagnostics>(connection, params)
+}
+
+fn notify<N: lsp_types::notification::Notification>(
+    connection: &Connection,
+    params: N::Params,
+) -> DynResult<()> {
+    let notification = Notification::new(N::METHOD.to_string(), params);
+    connection
+        .sender
+        .send(Message::Notification(notification))?;
+    Ok(())
+}
+
+// What a request asks for, from the documents as they are now
+fn answer(documents: &Documents, request: &Request) -> Result<serde_json::Value, Response> {
+    use lsp_types::request::Request as _;
+    let invalid = |e: serde_json::Error| {
+        Response::new_err(
+            request.id.clone(),
+            ErrorCode::InvalidParams as i32,
+            e.to_string(),
+        )
+    };
+    let params = request.params.clone();
+    let result = match request.method.as_str() {
+        GotoDefinition::METHOD => {
+            let at = serde_json::from_value::<GotoDefinitionParams>(params)
+                .map_err(invalid)?
+                .text_document_position_params;
+            let locations = documents
+                .get(&at.text_document.uri)
+                .map(|text| analysis::definition(&at.text_document.uri, text, at.position))
+                .unwrap_or_default();
+            serde_json::to_value(GotoDefinitionResponse::Array(locations))
+        }
+        HoverRequest::METHOD => {
+            let at = serde_json::from_value::<HoverParams>(params)
+                .map_err(invalid)?
+                .text_document_position_params;
+            let hover = documents
+                .get(&at.text_document.uri)
+                .and_then(|text| analysis::hover(&at.text_document.uri, text, at.position))
+                .map(|value| Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: None,
+                });
+            serde_json::to_value(hover)
+        }
+        DocumentSymbolRequest::METHOD => {
+            let document = serde_json::from_value::<DocumentSymbolParams>(params)
+                .map_err(invalid)?
+                .text_document;
+            let symbols = documents
+                .get(&document.uri)
+                .map(|text| analysis::document_symbols(text))
+                .unwrap_or_default();
+            serde_json::to_value(DocumentSymbolResponse::Nested(symbols))
+        }
+        method => {
+            return Err(Response::new_err(
+                request.id.clone(),
+                ErrorCode::MethodNotFound as i32,
+                format!("name-lsp does not handle {}", method),
+            ))
+        }
+    };
+    result.map_err(|e| {
+        Response::new_err(
+            request.id.clone(),
+            ErrorCode::InternalError as i32,
+            e.to_string(),
+        )
+    })
+}
+
+fn handle_request(
+    connection: &Connection,
+    documents: &Documents,
+    request: Request,
+) -> DynResult<()> {
+    let response = match answer(documents, &request) {
+        Ok(result) => Response {
+            id: request.id,
+            result: Some(result),
+            error: None,
+        },
+        Err(response) => response,
+    };
+    connection.sender.send(Message::Response(response))?;
+    Ok(())
+}
+
+fn handle_notification(
+    connection: &Connection,
+    documents: &mut Documents,
+    notification: Notification,
+) -> DynResult<()> {
+    use lsp_types::notification::Notification as _;
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: lsp_types::DidOpenTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            let document = params.text_document;
+            publish_diagnostics(connection, &document.uri, &document.text)?;
+            documents.insert(document.uri, document.text);
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: lsp_types::DidChangeTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            // Full sync: the last change holds the whole document
+            if let Some(change) = params.content_changes.into_iter().last() {
+                let uri = params.text_document.uri;
+                publish_diagnostics(connection, &uri, &change.text)?;
+                documents.insert(uri, change.text);
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: lsp_types::DidCloseTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.remove(&uri);
+            notify::<PublishDiagnostics>(
+                connection,
+                PublishDiagnosticsParams::new(uri, vec![], None),
+            )?;
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn main() -> DynResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(capabilities())?;
+    connection.initialize(capabilities)?;
+
+    let mut documents = Documents::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                handle_request(&connection, &documents, request)?;
+            }
+            Message::Notification(notification) => {
+                handle_notification(&connection, &mut documents, notification)?
+            }
+            Message::Response(_) => (),
+        }
+    }
+    // The writer thread finishes once nothing can send to it any more
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! Browser bindings for NAME: assemble a program, then step it and inspect
+//! the machine from JavaScript. Everything runs in memory
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use name_as::nma::{assemble_source, assemble_to_bytes, AssemblerOptions};
+use name_emu::exception::{ExecutionErrors, ExecutionEvents};
+use name_emu::mips::Mips;
+use name_emu::syscall::BufferConsole;
+use wasm_bindgen::prelude::*;
+
+/// Assembles `source` and returns the contents of its .text section
+#[wasm_bindgen]
+pub fn assemble(source: &str) -> Result<Vec<u8>, JsError> {
+    assemble_to_bytes(source, &AssemblerOptions::default())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// A program loaded into a fresh machine, whose console reads from a fixed
+/// input and collects everything written to it
+#[wasm_bindgen]
+pub struct Emulator {
+    mips: Mips,
+    output: Rc<RefCell<String>>,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// Assembles `source` and loads it at its entry point. `input` is what
+    /// the program will read from the console
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: &str, input: &str) -> Result<Emulator, JsError> {
+        let assembled = assemble_source(source, &AssemblerOptions::default())
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        let console = BufferConsole::new(input);
+        let output = console.output.clone();
+        let mut mips: Mips = Default::default();
+        mips.endian = assembled.endian;
+        mips.console = Box::new(console);
+        mips.load_text_at(assembled.text_address(), assembled.text(), assembled.entry)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        mips.load_data_at(assembled.data_address(), assembled.data())
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Emulator { mips, output })
+    }
+
+    /// Executes one instruction. Returns false once the program has
+    /// finished; an exception is thrown as an error
+    pub fn step(&mut self) -> Result<bool, JsError> {
+        match self.mips.step_one(&mut io::sink()) {
+            Ok(()) => Ok(true),
+            Err(ExecutionErrors::Event {
+                event: ExecutionEvents::ProgramComplete,
+            }) => Ok(false),
+            Err(e) => Err(JsError::new(&format!("at 0x{:08x}: {}", self.mips.pc(), e))),
+        }
+    }
+
+    /// The 32 general purpose registers followed by pc, hi and lo
+    #[wasm_bindgen(js_name = readRegisters)]
+    pub fn read_registers(&self) -> Vec<u32> {
+        let mut registers = self.mips.regs.to_vec();
+        registers.extend([self.mips.pc(), self.mips.mult_hi, self.mips.mult_lo]);
+        registers
+    }
+
+    /// `length` bytes of memory starting at `address`. Unmapped addresses
+    /// read as zero
+    #[wasm_bindgen(js_name = readMemory)]
+    pub fn read_memory(&self, address: u32, length: u32) -> Vec<u8> {
+        (0..length)
+            .map(|i| self.mips.read_b(address.wrapping_add(i)).unwrap_or(0))
+            .collect()
+    }
+
+    /// Everything the program has written to the console since the last call
+    #[wasm_bindgen(js_name = takeOutput)]
+    pub fn take_output(&mut self) -> String {
+        self.output.take()
+    }
+
+    /// The code the program exited with, once it has called exit
+    #[wasm_bindgen(getter, js_name = exitCode)]
+    pub fn exit_code(&self) -> Option<u32> {
+        self.mips.exit_code
+    }
+}